@@ -215,6 +215,8 @@ pub enum Request {
     },
     MarkWindowsNeedingInfo(Vec<WindowId>),
     CloseWindow(WindowId),
+    /// Brings a minimized window back, unhiding its app if that is what hid it.
+    Unminimize(WindowId),
 
     SetWindowFrame(WindowId, CGRect, TransactionId, bool),
     SetBatchWindowFrame(Vec<(WindowId, CGRect)>, TransactionId),
@@ -484,6 +486,15 @@ impl State {
                     warn!(?wid, ?err, "Failed to close window");
                 }
             }
+            Request::Unminimize(wid) => {
+                if let Some(window) = self.windows.get(wid) {
+                    if window.hidden_by_app {
+                        self.running_app.unhide();
+                    } else if let Err(err) = window.elem.set_minimized(false) {
+                        warn!(?wid, ?err, "Failed to unminimize window");
+                    }
+                }
+            }
             Request::GetVisibleWindows { force_refresh } => {
                 let window_elems = match self.app.windows() {
                     Ok(elems) => elems,
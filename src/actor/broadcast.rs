@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-use crate::actor::app::WindowId;
+use crate::actor::app::{WindowId, pid_t};
 use crate::layout_engine::VirtualWorkspaceId;
+use crate::model::DisplayHints;
+use crate::model::server::SnapshotData;
 use crate::sys::screen::SpaceId;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -20,6 +22,9 @@ pub enum BroadcastEvent {
         windows: Vec<String>,
         space_id: SpaceId,
         display_uuid: Option<String>,
+        /// Every window on the space, on any of its workspaces.
+        #[serde(default)]
+        window_details: Vec<WindowDetails>,
     },
     WindowTitleChanged {
         window_id: WindowId,
@@ -31,6 +36,87 @@ pub enum BroadcastEvent {
         space_id: SpaceId,
         display_uuid: Option<String>,
     },
+    /// A different window became the focused window.
+    WindowFocused {
+        window_id: WindowId,
+        window_title: String,
+        app_name: Option<String>,
+        workspace_id: VirtualWorkspaceId,
+        workspace_name: String,
+        space_id: SpaceId,
+        display_uuid: Option<String>,
+    },
+    /// A newly created window was added to the layout.
+    WindowAdded {
+        window_id: WindowId,
+        window_title: String,
+        app_name: Option<String>,
+        workspace_id: VirtualWorkspaceId,
+        workspace_name: String,
+        space_id: SpaceId,
+        display_uuid: Option<String>,
+    },
+    /// A window seen for the first time was put on a workspace, by a
+    /// matching app rule or by default.
+    WindowAssigned {
+        window_id: WindowId,
+        workspace_id: VirtualWorkspaceId,
+        by_rule: bool,
+        space_id: SpaceId,
+    },
+    /// The opacity or always-on-top hints an app rule gives a window changed.
+    WindowDisplayHintsChanged {
+        window_id: WindowId,
+        space_id: SpaceId,
+        hints: DisplayHints,
+    },
+    /// The active layout of a space changed shape, as `rift query tree`
+    /// would draw it.
+    LayoutTreeChanged {
+        space_id: SpaceId,
+        tree: String,
+        fingerprint: u64,
+    },
+    /// Rift is exiting. The last event a subscriber receives.
+    ShuttingDown,
+    /// Initial state for a newly connected subscriber. Only delivered to
+    /// `client_port`, never fanned out to other subscribers.
+    Snapshot {
+        #[serde(skip)]
+        client_port: u32,
+        snapshot: Box<SnapshotData>,
+    },
+}
+
+impl BroadcastEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BroadcastEvent::WorkspaceChanged { .. } => "workspace_changed",
+            BroadcastEvent::WindowsChanged { .. } => "windows_changed",
+            BroadcastEvent::WindowTitleChanged { .. } => "window_title_changed",
+            BroadcastEvent::WindowFocused { .. } => "window_focused",
+            BroadcastEvent::WindowAdded { .. } => "window_added",
+            BroadcastEvent::WindowAssigned { .. } => "window_assigned",
+            BroadcastEvent::WindowDisplayHintsChanged { .. } => "window_display_hints_changed",
+            BroadcastEvent::LayoutTreeChanged { .. } => "layout_tree_changed",
+            BroadcastEvent::ShuttingDown => "shutting_down",
+            BroadcastEvent::Snapshot { .. } => "snapshot",
+        }
+    }
+}
+
+/// A window listed in `BroadcastEvent::WindowsChanged`, with what a bar
+/// needs to place it without asking again.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WindowDetails {
+    pub id: String,
+    pub pid: pid_t,
+    pub workspace_id: VirtualWorkspaceId,
+    pub workspace_name: String,
+    pub is_floating: bool,
+    pub is_focused: bool,
+    /// Whether the window's workspace is the one shown on its space.
+    pub is_active_workspace: bool,
 }
 
 pub type BroadcastSender = crate::actor::Sender<BroadcastEvent>;
@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::rc::Rc;
 
 use r#continue::continuation;
@@ -11,7 +12,9 @@ use crate::common::config::Config;
 use crate::model::server::{WindowData, WorkspaceData};
 use crate::model::virtual_workspace::VirtualWorkspaceId;
 use crate::sys::dispatch::block_on;
-use crate::ui::mission_control::{MissionControlAction, MissionControlMode, MissionControlOverlay};
+use crate::ui::mission_control::{
+    MissionControlAction, MissionControlMode, MissionControlOverlay, SelectionMemory,
+};
 
 #[derive(Debug)]
 pub enum Event {
@@ -38,6 +41,7 @@ pub struct MissionControlActor {
     mtm: MainThreadMarker,
     mission_control_active: bool,
     current_view_mode: Option<MissionControlViewMode>,
+    selection_memory: Rc<RefCell<SelectionMemory>>,
 }
 
 impl MissionControlActor {
@@ -55,6 +59,7 @@ impl MissionControlActor {
             mtm,
             mission_control_active: false,
             current_view_mode: None,
+            selection_memory: Rc::new(RefCell::new(SelectionMemory::default())),
         }
     }
 
@@ -87,6 +92,7 @@ impl MissionControlActor {
                 let this: &mut MissionControlActor = &mut *self_ptr;
                 this.handle_overlay_action(action);
             }));
+            overlay.set_selection_memory(self.selection_memory.clone());
             self.overlay = Some(overlay);
         }
         self.overlay.as_ref().unwrap()
@@ -112,6 +118,14 @@ impl MissionControlActor {
                     )));
                 self.dispose_overlay();
             }
+            MissionControlAction::ReorderWorkspace { from, to } => {
+                let _ =
+                    self.reactor_tx.try_send(reactor::Event::Command(reactor::Command::Layout(
+                        crate::layout_engine::LayoutCommand::ReorderWorkspace { from, to },
+                    )));
+                // The reactor handles events in order, so this sees the new order.
+                self.load_all_workspaces();
+            }
             MissionControlAction::FocusWindow { window_id, window_server_id } => {
                 let _ =
                     self.reactor_tx.try_send(reactor::Event::Command(reactor::Command::Reactor(
@@ -163,7 +177,10 @@ impl MissionControlActor {
             let overlay = self.ensure_overlay();
             overlay.update(MissionControlMode::AllWorkspaces(Vec::new()));
         }
+        self.load_all_workspaces();
+    }
 
+    fn load_all_workspaces(&mut self) {
         let (tx, fut) = continuation::<Vec<WorkspaceData>>();
         let event = reactor::Event::QueryWorkspaces { space_id: None, response: tx };
         if let Err(e) = self.reactor_tx.try_send(event) {
@@ -217,9 +217,10 @@ impl RaiseManager {
             if let Some(focus_idx) =
                 wids.iter().position(|wid| matches!(focus_window, Some((w, _)) if w == *wid))
             {
-                // Make sure focus window is always last.
-                let last = wids.len() - 1;
-                wids.swap(focus_idx, last);
+                // Make sure focus window is always last, keeping the others
+                // in the back-to-front order the layout gave them.
+                let focus = wids.remove(focus_idx);
+                wids.push(focus);
                 if focus_batch.is_none() {
                     focus_batch = Some((pid, wids, focus_window.unwrap().1, focus_quiet));
                     continue;
@@ -49,7 +49,10 @@ use crate::actor::{self, menu_bar, stack_line};
 use crate::common::collections::{BTreeMap, HashMap, HashSet};
 use crate::common::config::Config;
 use crate::common::log::MetricsCommand;
-use crate::layout_engine::{self as layout, Direction, LayoutCommand, LayoutEngine, LayoutEvent};
+use crate::layout_engine::{
+    self as layout, Direction, JournalEntry, LayoutCommand, LayoutEngine, LayoutEvent,
+    TreeRenderOptions, TreeSnapshot,
+};
 use crate::model::VirtualWorkspaceId;
 use crate::model::tx_store::WindowTxStore;
 use crate::model::virtual_workspace::AppRuleResult;
@@ -188,6 +191,14 @@ pub enum Event {
     #[serde(skip)]
     MissionControlNativeExited,
 
+    /// A member of a stack was chosen in the stack quick-jump picker.
+    #[serde(skip)]
+    StackMemberSelected {
+        space_id: SpaceId,
+        node_id: crate::model::tree::NodeId,
+        index: usize,
+    },
+
     /// A raise request completed. Used by the raise manager to track when
     /// all raise requests in a sequence have finished.
     RaiseCompleted {
@@ -223,6 +234,16 @@ pub enum Event {
         #[serde(skip)]
         response: r#continue::Sender<Vec<WindowData>>,
     },
+    /// Windows of one workspace with their current frames, or the frames they
+    /// would get on `screen` (default: the space's display) if it isn't active.
+    #[serde(skip)]
+    QueryWorkspaceWindows {
+        space_id: Option<SpaceId>,
+        workspace_index: usize,
+        screen: Option<CGRect>,
+        #[serde(skip)]
+        response: r#continue::Sender<Option<Vec<WindowData>>>,
+    },
     #[serde(skip)]
     QueryActiveWorkspace {
         space_id: Option<SpaceId>,
@@ -247,6 +268,30 @@ pub enum Event {
     },
     #[serde(skip)]
     QueryMetrics(r#continue::Sender<serde_json::Value>),
+    #[serde(skip)]
+    QueryLayoutTree {
+        space_id: Option<SpaceId>,
+        options: TreeRenderOptions,
+        #[serde(skip)]
+        response: r#continue::Sender<Option<String>>,
+    },
+    #[serde(skip)]
+    QueryTreeSnapshot {
+        space_id: Option<SpaceId>,
+        #[serde(skip)]
+        response: r#continue::Sender<Option<TreeSnapshot>>,
+    },
+    #[serde(skip)]
+    QueryJournal {
+        space_id: Option<SpaceId>,
+        #[serde(skip)]
+        response: r#continue::Sender<Vec<JournalEntry>>,
+    },
+    /// Publish a state snapshot to a newly connected broadcast subscriber.
+    #[serde(skip)]
+    SendSnapshot {
+        client_port: u32,
+    },
 
     #[serde(skip)]
     ConfigUpdated(Config),
@@ -292,6 +337,8 @@ pub enum ReactorCommand {
     ShowMissionControlAll,
     ShowMissionControlCurrent,
     DismissMissionControl,
+    /// Open the quick-jump picker for the focused stack.
+    ShowStackPicker,
     MoveMouseToDisplay(DisplaySelector),
     FocusDisplay(DisplaySelector),
     CloseWindow {
@@ -301,6 +348,10 @@ pub enum ReactorCommand {
         selector: DisplaySelector,
         window_id: Option<u32>,
     },
+    /// Defer layout passes and broadcasts until `EndBatch`, so scripted
+    /// bursts of commands settle in one pass per space.
+    BeginBatch,
+    EndBatch,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -377,6 +428,7 @@ pub struct Reactor {
     main_window_tracker_manager: managers::MainWindowTrackerManager,
     drag_manager: managers::DragManager,
     workspace_switch_manager: managers::WorkspaceSwitchManager,
+    batch_manager: managers::BatchManager,
     recording_manager: managers::RecordingManager,
     communication_manager: managers::CommunicationManager,
     notification_manager: managers::NotificationManager,
@@ -428,6 +480,11 @@ struct WindowState {
     is_ax_standard: bool,
     is_ax_root: bool,
     is_minimized: bool,
+    /// Whether the window was minimized because its app was hidden.
+    hidden_by_app: bool,
+    /// The workspace the window was on before it was minimized, so it can
+    /// still be listed there while it is gone from the layout.
+    minimized_from: Option<(SpaceId, VirtualWorkspaceId)>,
     is_manageable: bool,
     ignore_app_rule: bool,
     window_server_id: Option<WindowServerId>,
@@ -453,6 +510,8 @@ impl From<WindowInfo> for WindowState {
             is_ax_standard: info.is_standard,
             is_ax_root: info.is_root,
             is_minimized: info.is_minimized,
+            hidden_by_app: false,
+            minimized_from: None,
             is_manageable: false,
             ignore_app_rule: false,
             window_server_id: info.sys_id,
@@ -563,6 +622,7 @@ impl Reactor {
                 pending_workspace_switch_origin: None,
                 pending_workspace_mouse_warp: None,
             },
+            batch_manager: managers::BatchManager::default(),
             recording_manager: managers::RecordingManager { record },
             communication_manager: managers::CommunicationManager {
                 event_tap_tx: None,
@@ -571,6 +631,7 @@ impl Reactor {
                 event_broadcaster: broadcast_tx,
                 wm_sender: None,
                 events_tx: None,
+                last_broadcast_focus: None,
             },
             notification_manager: managers::NotificationManager {
                 last_sls_notification_ids: Vec::new(),
@@ -758,6 +819,12 @@ impl Reactor {
                             let _guard = span.enter();
                             self.handle_event(event);
                         }
+                        self.switch_from_emptied_workspaces();
+                    }
+                    self.autosave_layout();
+                    if self.batch_manager.expired(std::time::Instant::now()) {
+                        warn!("Command batch was not ended in time; ending it");
+                        self.end_batch();
                     }
                 }
                 _ = animation_timer.next() => {
@@ -784,11 +851,16 @@ impl Reactor {
             Event::QueryApplications(..)
                 | Event::QueryLayoutState { .. }
                 | Event::QueryMetrics(..)
+                | Event::QueryLayoutTree { .. }
+                | Event::QueryTreeSnapshot { .. }
+                | Event::QueryJournal { .. }
                 | Event::QueryWindowInfo { .. }
                 | Event::QueryWindows { .. }
                 | Event::QueryWorkspaces { .. }
+                | Event::QueryWorkspaceWindows { .. }
                 | Event::QueryActiveWorkspace { .. }
                 | Event::QueryDisplays(..)
+                | Event::SendSnapshot { .. }
         ) {
             return self.handle_query(event);
         }
@@ -811,6 +883,7 @@ impl Reactor {
             self.main_window_tracker_manager.main_window_tracker.handle_event(&event);
         let mut is_resize = false;
         let mut window_was_destroyed = false;
+        let mut skip_frame_pass = false;
 
         match event {
             Event::ApplicationLaunched {
@@ -923,7 +996,7 @@ impl Reactor {
                 SystemEventHandler::handle_raise_timeout(self, sequence_id);
             }
             Event::Command(Command::Layout(cmd)) => {
-                CommandEventHandler::handle_command_layout(self, cmd);
+                skip_frame_pass = !CommandEventHandler::handle_command_layout(self, cmd);
             }
             Event::Command(Command::Metrics(cmd)) => {
                 CommandEventHandler::handle_command_metrics(self, cmd);
@@ -962,6 +1035,12 @@ impl Reactor {
             Event::Command(Command::Reactor(ReactorCommand::DismissMissionControl)) => {
                 CommandEventHandler::handle_command_reactor_dismiss_mission_control(self);
             }
+            Event::Command(Command::Reactor(ReactorCommand::ShowStackPicker)) => {
+                CommandEventHandler::handle_command_reactor_show_stack_picker(self);
+            }
+            Event::StackMemberSelected { space_id, node_id, index } => {
+                CommandEventHandler::handle_stack_member_selected(self, space_id, node_id, index);
+            }
             Event::Command(Command::Reactor(ReactorCommand::MoveMouseToDisplay(selector))) => {
                 CommandEventHandler::handle_command_reactor_move_mouse_to_display(self, &selector);
             }
@@ -979,6 +1058,15 @@ impl Reactor {
             Event::Command(Command::Reactor(ReactorCommand::CloseWindow { window_server_id })) => {
                 CommandEventHandler::handle_command_reactor_close_window(self, window_server_id)
             }
+            Event::Command(Command::Reactor(ReactorCommand::BeginBatch)) => {
+                CommandEventHandler::handle_command_reactor_begin_batch(self);
+                skip_frame_pass = true;
+            }
+            Event::Command(Command::Reactor(ReactorCommand::EndBatch)) => {
+                // Ending the batch runs its own consolidated pass.
+                CommandEventHandler::handle_command_reactor_end_batch(self);
+                skip_frame_pass = true;
+            }
             _ => (),
         }
         if let Some(raised_window) = raised_window
@@ -991,7 +1079,9 @@ impl Reactor {
         }
 
         let mut layout_changed = false;
-        if !self.is_in_drag() || window_was_destroyed {
+        if skip_frame_pass {
+            trace!("No frame pass needed for this event; skipping it");
+        } else if !self.is_in_drag() || window_was_destroyed {
             layout_changed = self
                 .update_layout(
                     is_resize,
@@ -1038,6 +1128,7 @@ impl Reactor {
         }
 
         self.update_focus_border();
+        self.broadcast_focus_change();
     }
 
     fn create_window_data(&self, window_id: WindowId) -> Option<WindowData> {
@@ -1045,6 +1136,30 @@ impl Reactor {
         if window_state.is_minimized {
             return None;
         }
+        self.window_data(window_id)
+    }
+
+    /// Data for the windows minimized or hidden out of `workspace_id`. They are
+    /// no longer part of its layout, so they keep their last known frame.
+    fn minimized_window_data(
+        &self,
+        space: SpaceId,
+        workspace_id: VirtualWorkspaceId,
+    ) -> Vec<WindowData> {
+        let mut windows: Vec<WindowData> = self
+            .window_manager
+            .windows
+            .iter()
+            .filter(|(_, state)| state.minimized_from == Some((space, workspace_id)))
+            .filter_map(|(&wid, _)| self.window_data(wid))
+            .collect();
+        windows.sort_by_key(|window| window.id);
+        windows
+    }
+
+    /// Like [`Self::create_window_data`], but also describes minimized windows.
+    fn window_data(&self, window_id: WindowId) -> Option<WindowData> {
+        let window_state = self.window_manager.windows.get(&window_id)?;
         let app = self.app_manager.apps.get(&window_id.pid)?;
 
         let preferred_name = app.info.localized_name.clone().or_else(|| app.info.bundle_id.clone());
@@ -1057,6 +1172,8 @@ impl Reactor {
             is_focused: self.main_window() == Some(window_id),
             bundle_id: preferred_name,
             window_server_id: window_state.window_server_id.map(|wsid| wsid.as_u32()),
+            is_minimized: window_state.is_minimized && !window_state.hidden_by_app,
+            is_hidden: window_state.is_minimized && window_state.hidden_by_app,
         })
     }
 
@@ -1354,6 +1471,93 @@ impl Reactor {
         }
     }
 
+    fn broadcast_focus_change(&mut self) {
+        let focused = self.main_window();
+        if focused == self.communication_manager.last_broadcast_focus {
+            return;
+        }
+        self.communication_manager.last_broadcast_focus = focused;
+        let Some(window_id) = focused else { return };
+        let Some(space) = self.best_space_for_window_id(window_id) else {
+            return;
+        };
+        if let Some((window_title, app_name, workspace_id, workspace_name, display_uuid)) =
+            self.window_broadcast_context(window_id, space)
+        {
+            self.communication_manager
+                .event_broadcaster
+                .send(BroadcastEvent::WindowFocused {
+                    window_id,
+                    window_title,
+                    app_name,
+                    workspace_id,
+                    workspace_name,
+                    space_id: space,
+                    display_uuid,
+                });
+        }
+    }
+
+    fn broadcast_window_added(&mut self, window_id: WindowId, space: SpaceId) {
+        if let Some((window_title, app_name, workspace_id, workspace_name, display_uuid)) =
+            self.window_broadcast_context(window_id, space)
+        {
+            self.communication_manager.event_broadcaster.send(BroadcastEvent::WindowAdded {
+                window_id,
+                window_title,
+                app_name,
+                workspace_id,
+                workspace_name,
+                space_id: space,
+                display_uuid,
+            });
+        }
+    }
+
+    /// Title, app name, workspace and display for a window event on `space`.
+    /// The workspace is the one holding the window, which app rules may have
+    /// chosen over the active one.
+    fn window_broadcast_context(
+        &self,
+        window_id: WindowId,
+        space: SpaceId,
+    ) -> Option<(
+        String,
+        Option<String>,
+        VirtualWorkspaceId,
+        String,
+        Option<String>,
+    )> {
+        let window_title = self.window_manager.windows.get(&window_id)?.title.clone();
+        let app_name = self
+            .app_manager
+            .apps
+            .get(&window_id.pid)
+            .and_then(|app| app.info.localized_name.clone());
+        let engine = &self.layout_manager.layout_engine;
+        let workspace_id = engine
+            .virtual_workspace_manager()
+            .workspace_for_window(space, window_id)
+            .or_else(|| engine.active_workspace(space))?;
+        let workspace_name = engine
+            .workspace_name(space, workspace_id)
+            .unwrap_or_else(|| format!("Workspace {:?}", workspace_id));
+        let display_uuid = self.space_manager.screen_by_space(space).and_then(|screen| {
+            if screen.display_uuid.is_empty() {
+                None
+            } else {
+                Some(screen.display_uuid.clone())
+            }
+        });
+        Some((
+            window_title,
+            app_name,
+            workspace_id,
+            workspace_name,
+            display_uuid,
+        ))
+    }
+
     fn maybe_reapply_app_rules_for_window(&mut self, window_id: WindowId) {
         if !self.config_manager.config.virtual_workspaces.reapply_app_rules_on_title_change {
             return;
@@ -1551,21 +1755,13 @@ impl Reactor {
             .or_else(|| self.best_space_for_window_id(wid));
 
         let needs_layout = if session.origin_space != final_space {
-            if session.origin_space.is_some() {
-                self.send_layout_event(LayoutEvent::WindowRemoved(wid));
-            }
-            if let Some(space) = final_space {
-                if let Some(active_ws) = self.layout_manager.layout_engine.active_workspace(space) {
-                    let assigned = self
-                        .layout_manager
-                        .layout_engine
-                        .virtual_workspace_manager_mut()
-                        .assign_window_to_workspace(space, wid, active_ws);
-                    if !assigned {
-                        warn!("Failed to assign window {:?} to workspace {:?}", wid, active_ws);
-                    }
-                }
-                self.send_layout_event(LayoutEvent::WindowAdded(space, wid));
+            match final_space {
+                Some(to) => self.send_layout_event(LayoutEvent::WindowMovedToSpace {
+                    wid,
+                    from: session.origin_space,
+                    to,
+                }),
+                None => self.send_layout_event(LayoutEvent::WindowRemoved(wid)),
             }
             self.drag_manager.skip_layout_for_window = Some(wid);
             true
@@ -2018,11 +2214,19 @@ impl Reactor {
                 RefocusState::None => None,
             };
         let layout::EventResponse {
-            raise_windows,
+            mut raise_windows,
             mut focus_window,
+            fullscreen_windows,
             ..
         } = response;
         let original_focus = focus_window;
+        // Raise fullscreen windows of the workspace just switched to last, so
+        // they cover its other windows without waiting for a focus change.
+        for (wid, _) in fullscreen_windows {
+            if !raise_windows.contains(&wid) {
+                raise_windows.push(wid);
+            }
+        }
 
         let focus_quiet = if workspace_switch_space.is_some() {
             Quiet::Yes
@@ -2850,6 +3054,12 @@ impl Reactor {
         is_resize: bool,
         is_workspace_switch: bool,
     ) -> Result<bool, error::ReactorError> {
+        if self.batch_manager.is_active() {
+            trace!("Deferring layout update until the command batch ends");
+            self.batch_manager.layout_pending = true;
+            self.batch_manager.saw_workspace_switch |= is_workspace_switch;
+            return Ok(false);
+        }
         if self.layout_update_in_flight {
             trace!("Skipping redundant layout update");
             return Ok(false);
@@ -2860,6 +3070,49 @@ impl Reactor {
         result
     }
 
+    /// Writes the layout to the restore file if it changed and the autosave
+    /// interval has passed.
+    fn autosave_layout(&mut self) {
+        let now = std::time::Instant::now();
+        if let Err(e) = self.layout_manager.layout_engine.autosave_if_due(now) {
+            warn!("Could not autosave layout: {e}");
+        }
+    }
+
+    /// Leaves active workspaces that the last batch of events emptied, as
+    /// configured by `virtual_workspaces.on_empty`.
+    fn switch_from_emptied_workspaces(&mut self) {
+        let switches = self.layout_manager.layout_engine.switch_from_emptied_workspaces();
+        if switches.is_empty() {
+            return;
+        }
+        for (space, response) in switches {
+            self.workspace_switch_manager
+                .start_workspace_switch(WorkspaceSwitchOrigin::Auto);
+            self.handle_layout_response(response, Some(space));
+        }
+        let _ = self.update_layout(false, true).unwrap_or_else(|e| {
+            warn!("Layout update failed: {}", e);
+            false
+        });
+        self.workspace_switch_manager.mark_workspace_switch_inactive();
+    }
+
+    /// Ends the open command batch, if any, with one layout pass over every
+    /// active space and one broadcast per space that changed.
+    pub fn end_batch(&mut self) {
+        let Some((layout_pending, saw_workspace_switch)) = self.batch_manager.end() else {
+            return;
+        };
+        if layout_pending {
+            let _ = self.update_layout(false, saw_workspace_switch).unwrap_or_else(|e| {
+                warn!("Layout update failed: {}", e);
+                false
+            });
+        }
+        self.layout_manager.layout_engine.flush_deferred_broadcasts();
+    }
+
     pub fn restore_windows_on_exit(&mut self) {
         debug!("Restoring all windows to visible positions on exit");
         let spaces: Vec<SpaceId> = self.space_manager.iter_known_spaces().collect();
@@ -1,7 +1,9 @@
-use tracing::{error, info, warn};
+use std::time::Instant;
+
+use tracing::{debug, error, info, warn};
 
 use super::super::Screen;
-use crate::actor::app::{AppThreadHandle, Quiet, WindowId};
+use crate::actor::app::{AppThreadHandle, Quiet, Request, WindowId};
 use crate::actor::reactor::transaction_manager::TransactionId;
 use crate::actor::reactor::{DisplaySelector, Reactor, WorkspaceSwitchOrigin};
 use crate::actor::stack_line::Event as StackLineEvent;
@@ -17,7 +19,9 @@ use crate::sys::window_server::{self as window_server, WindowServerId};
 pub struct CommandEventHandler;
 
 impl CommandEventHandler {
-    pub fn handle_command_layout(reactor: &mut Reactor, cmd: LayoutCommand) {
+    /// Returns false if the command left every layout unchanged, in which case
+    /// the frame pass can be skipped.
+    pub fn handle_command_layout(reactor: &mut Reactor, cmd: LayoutCommand) -> bool {
         info!(?cmd);
         let visible_spaces_input: Vec<(SpaceId, _)> = reactor
             .space_manager
@@ -61,7 +65,63 @@ impl CommandEventHandler {
             reactor.workspace_switch_manager.mark_workspace_switch_inactive();
         }
 
+        // A window that stops floating rejoins the tree next to the tiled
+        // window nearest its current frame.
+        if matches!(cmd, LayoutCommand::ToggleWindowFloating)
+            && let Some(space) = reactor.workspace_command_space()
+        {
+            reactor.store_current_floating_positions(space);
+        }
+
+        let is_workspace_command = matches!(
+            cmd,
+            LayoutCommand::NextWorkspace(_)
+                | LayoutCommand::PrevWorkspace(_)
+                | LayoutCommand::SwitchToWorkspace(_)
+                | LayoutCommand::CreateWorkspace
+                | LayoutCommand::SwitchToLastWorkspace
+                | LayoutCommand::MoveWindowToWorkspace { .. }
+                | LayoutCommand::MergeWorkspaces { .. }
+                | LayoutCommand::ReorderWorkspace { .. }
+        );
+        let global_scope = reactor.layout_manager.layout_engine.virtual_workspace_manager().scope()
+            == config::WorkspaceScope::Global;
         let response = match &cmd {
+            LayoutCommand::SwitchToWorkspace(_)
+            | LayoutCommand::NextWorkspace(_)
+            | LayoutCommand::PrevWorkspace(_)
+            | LayoutCommand::SwitchToLastWorkspace
+                if global_scope =>
+            {
+                // Steps and back-and-forth resolve against the workspace shown
+                // on the display the command was issued from.
+                let engine = &reactor.layout_manager.layout_engine;
+                let target = workspace_space
+                    .and_then(|space| Some((space, engine.workspace_switch_index(space, &cmd)?)));
+                match target {
+                    Some((space, index)) => {
+                        for &other in visible_spaces.iter().filter(|&&other| other != space) {
+                            reactor.store_current_floating_positions(other);
+                        }
+                        let responses = reactor
+                            .layout_manager
+                            .layout_engine
+                            .switch_to_workspace_globally(space, &visible_spaces, index);
+                        // Raise what every display shows, but only move focus on
+                        // the display the switch was made from.
+                        let mut merged = EventResponse::default();
+                        for (target, response) in responses {
+                            merged.raise_windows.extend(response.raise_windows);
+                            merged.fullscreen_windows.extend(response.fullscreen_windows);
+                            if target == space {
+                                merged.focus_window = response.focus_window;
+                            }
+                        }
+                        merged
+                    }
+                    None => EventResponse::default(),
+                }
+            }
             LayoutCommand::NextWorkspace(_)
             | LayoutCommand::PrevWorkspace(_)
             | LayoutCommand::SwitchToWorkspace(_)
@@ -76,84 +136,58 @@ impl CommandEventHandler {
                     EventResponse::default()
                 }
             }
+            LayoutCommand::MergeWorkspaces { .. } | LayoutCommand::ReorderWorkspace { .. } => {
+                if let Some(space) = reactor.workspace_command_space() {
+                    reactor.store_current_floating_positions(space);
+                    reactor
+                        .layout_manager
+                        .layout_engine
+                        .handle_virtual_workspace_command(space, &cmd)
+                } else {
+                    EventResponse::default()
+                }
+            }
             LayoutCommand::MoveWindowToWorkspace { .. } => {
                 if let Some(space) = reactor.workspace_command_space() {
+                    reactor.store_current_floating_positions(space);
                     let response = reactor
                         .layout_manager
                         .layout_engine
                         .handle_virtual_workspace_command(space, &cmd);
 
-                    if let Some(target_workspace_id) = response.workspace_changed_to {
-                        let moved_window = response.focus_window;
-                        let workspaces = reactor
-                            .layout_manager
-                            .layout_engine
-                            .virtual_workspace_manager_mut()
-                            .list_workspaces(space);
-
-                        if let Some((workspace_index, _)) = workspaces
-                            .iter()
-                            .enumerate()
-                            .find(|(_, (ws_id, _))| *ws_id == target_workspace_id)
-                        {
-                            reactor.store_current_floating_positions(space);
-                            reactor
-                                .workspace_switch_manager
-                                .start_workspace_switch(WorkspaceSwitchOrigin::Auto);
-
-                            let _switch_response = reactor
-                                .layout_manager
-                                .layout_engine
-                                .handle_virtual_workspace_command(
-                                    space,
-                                    &LayoutCommand::SwitchToWorkspace(workspace_index),
-                                );
-
-                            if let Some(ws_id) = workspaces.get(workspace_index).map(|(id, _)| *id)
-                            {
-                                reactor
-                                    .layout_manager
-                                    .layout_engine
-                                    .virtual_workspace_manager_mut()
-                                    .set_active_workspace(space, ws_id);
-                                reactor
-                                    .layout_manager
-                                    .layout_engine
-                                    .update_active_floating_windows(space);
-                                reactor
-                                    .layout_manager
-                                    .layout_engine
-                                    .broadcast_workspace_changed(space);
-                                reactor
-                                    .layout_manager
-                                    .layout_engine
-                                    .broadcast_windows_changed(space);
-
-                                if let Some(mw) = moved_window {
-                                    reactor.layout_manager.layout_engine.set_focused_window(mw);
-                                    reactor
-                                        .layout_manager
-                                        .layout_engine
-                                        .virtual_workspace_manager_mut()
-                                        .set_last_focused_window(space, ws_id, Some(mw));
-                                }
-                            }
-
-                            let final_response = EventResponse {
-                                raise_windows: moved_window.map(|w| vec![w]).unwrap_or_default(),
-                                focus_window: moved_window,
-                                workspace_changed_to: None,
-                            };
-
-                            reactor.handle_layout_response(final_response, Some(space));
-                            return;
-                        }
+                    // With `move_follows_window` the engine has already
+                    // switched to the window's new workspace.
+                    if response.workspace_changed_to.is_some() {
+                        reactor
+                            .workspace_switch_manager
+                            .start_workspace_switch(WorkspaceSwitchOrigin::Auto);
+                        reactor.handle_layout_response(response, Some(space));
+                        return true;
                     }
 
                     reactor.handle_layout_response(response, None);
                 }
                 EventResponse::default()
             }
+            LayoutCommand::ManageWindow | LayoutCommand::UnmanageWindow => {
+                let space = reactor.workspace_command_space();
+                let target = reactor.layout_manager.layout_engine.focused_window();
+                let response = reactor.layout_manager.layout_engine.handle_command(
+                    space,
+                    &visible_spaces,
+                    &visible_space_centers,
+                    cmd,
+                );
+                // Keep the reactor's view in line so the window is (or is no
+                // longer) treated as part of the layout.
+                if let (Some(space), Some(wid)) = (space, target)
+                    && let Some(window) = reactor.window_manager.windows.get_mut(&wid)
+                {
+                    window.ignore_app_rule =
+                        !reactor.layout_manager.layout_engine.is_managed(space, wid);
+                }
+                response
+            }
             _ => reactor.layout_manager.layout_engine.handle_command(
                 reactor.workspace_command_space(),
                 &visible_spaces,
@@ -162,7 +196,9 @@ impl CommandEventHandler {
             ),
         };
 
+        let layout_changed = is_workspace_command || response.layout_changed;
         reactor.handle_layout_response(response, workspace_space);
+        layout_changed
     }
 
     pub fn handle_command_metrics(_reactor: &mut Reactor, cmd: MetricsCommand) {
@@ -178,10 +214,17 @@ impl CommandEventHandler {
             .layout_engine
             .set_layout_settings(&reactor.config_manager.config.settings.layout);
 
-        reactor
+        let responses = reactor
             .layout_manager
             .layout_engine
             .update_virtual_workspace_settings(&reactor.config_manager.config.virtual_workspaces);
+        for (_, response) in responses {
+            reactor.handle_layout_response(response, None);
+        }
+        reactor
+            .layout_manager
+            .layout_engine
+            .update_autosave_settings(&reactor.config_manager.config.settings.persistence);
 
         reactor
             .drag_manager
@@ -253,7 +296,16 @@ impl CommandEventHandler {
     pub fn handle_command_reactor_save_and_exit(reactor: &mut Reactor) {
         reactor.restore_windows_on_exit();
         let restore_path = config::restore_file().expect("Failed to determine restore file path");
-        match reactor.layout_manager.layout_engine.save(restore_path) {
+        let format = reactor.config_manager.config.settings.persistence.format;
+        let engine = &mut reactor.layout_manager.layout_engine;
+        let saved = engine.save(restore_path, format);
+        if saved.is_ok() {
+            // The restore file is the autosave file; don't write it twice.
+            engine.mark_saved();
+        }
+        let state = engine.shutdown();
+        info!(?state, "Layout engine shut down");
+        match saved {
             Ok(()) => std::process::exit(0),
             Err(e) => {
                 error!("Could not save layout: {e}");
@@ -274,7 +326,13 @@ impl CommandEventHandler {
         window_id: WindowId,
         window_server_id: Option<WindowServerId>,
     ) {
-        if reactor.window_manager.windows.contains_key(&window_id) {
+        if let Some(window) = reactor.window_manager.windows.get(&window_id) {
+            if window.is_minimized
+                && let Some(app) = reactor.app_manager.apps.get(&window_id.pid)
+                && let Err(e) = app.handle.send(Request::Unminimize(window_id))
+            {
+                warn!("Failed to send unminimize request: {}", e);
+            }
             if let Some(space) =
                 reactor.window_manager.windows.get(&window_id).and_then(|w| {
                     reactor.best_space_for_window(&w.frame_monotonic, w.window_server_id)
@@ -335,6 +393,80 @@ impl CommandEventHandler {
         }
     }
 
+    pub fn handle_command_reactor_show_stack_picker(reactor: &mut Reactor) {
+        let Some(tx) = reactor.communication_manager.stack_line_tx.clone() else {
+            return;
+        };
+        let Some(space) = reactor.workspace_command_space() else {
+            return;
+        };
+        let Some(screen) = reactor.space_manager.screen_by_space(space) else {
+            return;
+        };
+        let display_uuid =
+            (!screen.display_uuid.is_empty()).then_some(screen.display_uuid.as_str());
+        let screen_frame = screen.frame;
+        let settings = &reactor.config_manager.config.settings;
+        let gaps = settings.layout.gaps.effective_for_display(display_uuid);
+        let stack_line = &settings.ui.stack_line;
+        let (thickness, horiz, vert) = (
+            stack_line.thickness(),
+            stack_line.horiz_placement,
+            stack_line.vert_placement,
+        );
+
+        // The innermost stack on the selection path is the one the focused
+        // window sits in.
+        let Some(group) = reactor
+            .layout_manager
+            .layout_engine
+            .collect_group_containers_in_selection_path(
+                space,
+                screen_frame,
+                &gaps,
+                thickness,
+                horiz,
+                vert,
+            )
+            .pop()
+        else {
+            info!("No stack under the selection; not showing stack picker");
+            return;
+        };
+
+        let titles = group
+            .window_ids
+            .iter()
+            .map(|wid| {
+                reactor
+                    .window_manager
+                    .windows
+                    .get(wid)
+                    .map(|w| w.title.clone())
+                    .unwrap_or_default()
+            })
+            .collect();
+        if let Err(e) = tx.try_send(StackLineEvent::ShowPicker {
+            space_id: space,
+            node_id: group.node_id,
+            frame: group.frame,
+            titles,
+        }) {
+            warn!("Failed to send stack picker request: {}", e);
+        }
+    }
+
+    pub fn handle_stack_member_selected(
+        reactor: &mut Reactor,
+        space: SpaceId,
+        node_id: crate::model::tree::NodeId,
+        index: usize,
+    ) {
+        let response =
+            reactor.layout_manager.layout_engine.select_stack_member(space, node_id, index);
+        reactor.handle_layout_response(response, None);
+    }
+
     fn focus_first_window_on_screen(reactor: &mut Reactor, screen: &Screen) -> bool {
         if let Some(space) = reactor.space_manager.space_for_screen(screen) {
             let focus_target = reactor.last_focused_window_in_space(space).or_else(|| {
@@ -535,4 +667,20 @@ impl CommandEventHandler {
             warn!("Close window command ignored because no window is tracked");
         }
     }
+
+    pub fn handle_command_reactor_begin_batch(reactor: &mut Reactor) {
+        if !reactor.batch_manager.begin(Instant::now()) {
+            debug!("Command batch already open; ignoring begin_batch");
+            return;
+        }
+        reactor.layout_manager.layout_engine.defer_broadcasts();
+    }
+
+    pub fn handle_command_reactor_end_batch(reactor: &mut Reactor) {
+        if !reactor.batch_manager.is_active() {
+            debug!("No command batch open; ignoring end_batch");
+            return;
+        }
+        reactor.end_batch();
+    }
 }
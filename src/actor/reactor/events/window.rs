@@ -1,3 +1,4 @@
+use objc2_app_kit::NSRunningApplication;
 use objc2_core_foundation::CGRect;
 use tracing::{debug, trace, warn};
 
@@ -72,6 +73,7 @@ impl WindowEventHandler {
                 .unwrap_or(false);
             if should_dispatch {
                 reactor.send_layout_event(LayoutEvent::WindowAdded(space, wid));
+                reactor.broadcast_window_added(wid, space);
             }
         }
         // TODO: drag state is maybe managed by ensure_active_drag
@@ -160,11 +162,22 @@ impl WindowEventHandler {
     }
 
     pub fn handle_window_minimized(reactor: &mut Reactor, wid: WindowId) {
+        let minimized_from = reactor.window_manager.windows.get(&wid).and_then(|window| {
+            let space =
+                reactor.best_space_for_window(&window.frame_monotonic, window.window_server_id)?;
+            let engine = &reactor.layout_manager.layout_engine;
+            let workspace = engine.virtual_workspace_manager().workspace_for_window(space, wid)?;
+            Some((space, workspace))
+        });
         if let Some(window) = reactor.window_manager.windows.get_mut(&wid) {
             if window.is_minimized {
                 return;
             }
             window.is_minimized = true;
+            window.hidden_by_app =
+                NSRunningApplication::runningApplicationWithProcessIdentifier(wid.pid)
+                    .is_some_and(|app| app.isHidden());
+            window.minimized_from = minimized_from;
             window.is_manageable = false;
             if let Some(ws_id) = window.window_server_id {
                 reactor.window_manager.visible_windows.remove(&ws_id);
@@ -183,6 +196,8 @@ impl WindowEventHandler {
                         return;
                     }
                     window.is_minimized = false;
+                    window.hidden_by_app = false;
+                    window.minimized_from = None;
                     (
                         window.frame_monotonic,
                         window.window_server_id,
@@ -425,22 +440,11 @@ impl WindowEventHandler {
                         }
                     } else if let Some(space) = new_space {
                         if reactor.is_space_active(space) {
-                            if let Some(active_ws) =
-                                reactor.layout_manager.layout_engine.active_workspace(space)
-                            {
-                                let assigned = reactor
-                                    .layout_manager
-                                    .layout_engine
-                                    .virtual_workspace_manager_mut()
-                                    .assign_window_to_workspace(space, wid, active_ws);
-                                if !assigned {
-                                    warn!(
-                                        "Failed to assign window {:?} to workspace {:?}",
-                                        wid, active_ws
-                                    );
-                                }
-                            }
-                            reactor.send_layout_event(LayoutEvent::WindowAdded(space, wid));
+                            reactor.send_layout_event(LayoutEvent::WindowMovedToSpace {
+                                wid,
+                                from: old_space,
+                                to: space,
+                            });
                             let _ = reactor.update_layout(false, false).unwrap_or_else(|e| {
                                 warn!("Layout update failed: {}", e);
                                 false
@@ -487,6 +491,15 @@ impl WindowEventHandler {
                 return;
             }
             window.title = new_title.clone();
+            let (ax_role, ax_subrole) = (window.ax_role.clone(), window.ax_subrole.clone());
+            let app_info = reactor.app_manager.apps.get(&wid.pid).map(|app| app.info.clone());
+            reactor.layout_manager.layout_engine.refresh_display_hints(
+                wid,
+                app_info.as_ref(),
+                Some(&new_title),
+                ax_role.as_deref(),
+                ax_subrole.as_deref(),
+            );
             reactor.broadcast_window_title_changed(wid, previous_title, new_title);
             reactor.maybe_reapply_app_rules_for_window(wid);
         }
@@ -252,6 +252,8 @@ impl WindowDiscoveryHandler {
                         is_ax_standard: info.is_standard,
                         is_ax_root: info.is_root,
                         is_minimized: info.is_minimized,
+                        hidden_by_app: false,
+                        minimized_from: None,
                         is_manageable: false,
                         ignore_app_rule: false,
                         window_server_id: info.sys_id,
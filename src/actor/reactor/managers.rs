@@ -200,6 +200,52 @@ impl WorkspaceSwitchManager {
     }
 }
 
+/// Manages command batches, during which layout passes and broadcasts are
+/// deferred until the batch ends.
+#[derive(Default)]
+pub struct BatchManager {
+    pub started_at: Option<Instant>,
+    pub layout_pending: bool,
+    pub saw_workspace_switch: bool,
+}
+
+impl BatchManager {
+    /// Batches left open longer than this are ended automatically, so a
+    /// client that dies mid-batch can't freeze the layout.
+    pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub fn is_active(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    /// Returns false if a batch was already open.
+    pub fn begin(&mut self, now: Instant) -> bool {
+        if self.started_at.is_some() {
+            return false;
+        }
+        *self = BatchManager {
+            started_at: Some(now),
+            ..Default::default()
+        };
+        true
+    }
+
+    /// Closes the batch, returning whether it deferred a layout pass and
+    /// whether that pass includes a workspace switch.
+    pub fn end(&mut self) -> Option<(bool, bool)> {
+        self.started_at.take()?;
+        let ended = (self.layout_pending, self.saw_workspace_switch);
+        self.layout_pending = false;
+        self.saw_workspace_switch = false;
+        Some(ended)
+    }
+
+    pub fn expired(&self, now: Instant) -> bool {
+        self.started_at
+            .is_some_and(|started| now.duration_since(started) >= Self::TIMEOUT)
+    }
+}
+
 /// Manages refocus and cleanup state
 pub struct RefocusManager {
     pub stale_cleanup_state: super::StaleCleanupState,
@@ -223,6 +269,8 @@ pub struct CommunicationManager {
     pub event_broadcaster: BroadcastSender,
     pub wm_sender: Option<wm_controller::Sender>,
     pub events_tx: Option<actor::Sender<Event>>,
+    /// The focused window last announced to broadcast subscribers.
+    pub last_broadcast_focus: Option<WindowId>,
 }
 
 /// Manages recording state
@@ -302,6 +350,7 @@ impl LayoutManager {
                     stack_line_vert,
                     get_window_frame,
                 );
+            reactor.layout_manager.layout_engine.broadcast_tree_if_changed(space);
             layout_result.push((space, layout));
         }
 
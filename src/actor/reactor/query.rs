@@ -3,11 +3,14 @@
 use objc2_core_foundation::CGRect;
 
 use crate::actor::app::WindowId;
+use crate::actor::broadcast::BroadcastEvent;
 use crate::actor::menu_bar;
 use crate::actor::reactor::{Event, Reactor};
 use crate::common::collections::HashSet;
+use crate::layout_engine::{JournalEntry, TreeRenderOptions, TreeSnapshot};
 use crate::model::server::{
-    ApplicationData, DisplayData, LayoutStateData, WindowData, WorkspaceData,
+    ApplicationData, CompatibilityData, DisplayData, LayoutStateData, SnapshotData,
+    SpaceSnapshotData, WindowData, WorkspaceData,
 };
 use crate::model::virtual_workspace::VirtualWorkspaceId;
 use crate::sys::screen::{SpaceId, get_active_space_number};
@@ -23,6 +26,16 @@ impl Reactor {
                 let windows = self.handle_windows_query(space_id);
                 response.send(windows);
             }
+            Event::QueryWorkspaceWindows {
+                space_id,
+                workspace_index,
+                screen,
+                response,
+            } => {
+                let windows =
+                    self.handle_workspace_windows_query(space_id, workspace_index, screen);
+                response.send(windows);
+            }
             Event::QueryActiveWorkspace { space_id, response } => {
                 let active = self.handle_active_workspace_query(space_id);
                 response.send(active);
@@ -43,10 +56,29 @@ impl Reactor {
                 let metrics = self.handle_metrics_query();
                 response.send(metrics);
             }
+            Event::QueryLayoutTree { space_id, options, response } => {
+                let tree = self.handle_layout_tree_query(space_id, &options);
+                response.send(tree);
+            }
+            Event::QueryTreeSnapshot { space_id, response } => {
+                let snapshot = self.handle_tree_snapshot_query(space_id);
+                response.send(snapshot);
+            }
+            Event::QueryJournal { space_id, response } => {
+                let journal = self.handle_journal_query(space_id);
+                response.send(journal);
+            }
             Event::QueryDisplays(response) => {
                 let displays = self.handle_displays_query();
                 response.send(displays);
             }
+            Event::SendSnapshot { client_port } => {
+                let snapshot = self.handle_snapshot_query();
+                self.communication_manager.event_broadcaster.send(BroadcastEvent::Snapshot {
+                    client_port,
+                    snapshot: Box::new(snapshot),
+                });
+            }
             _ => {}
         }
     }
@@ -97,88 +129,20 @@ impl Reactor {
             };
 
         for (index, (workspace_id, workspace_name)) in workspace_list.iter().enumerate() {
-            let is_active = if let Some(space) = space_id {
-                self.layout_manager.layout_engine.active_workspace(space) == Some(*workspace_id)
-            } else {
-                false
+            let (is_active, windows) = match space_id {
+                Some(space) => (
+                    self.layout_manager.layout_engine.active_workspace(space)
+                        == Some(*workspace_id),
+                    self.workspace_windows_data(space, *workspace_id, None),
+                ),
+                None => (false, Vec::new()),
             };
 
-            let workspace_windows_ids: Vec<crate::actor::app::WindowId> =
-                if let Some(space) = space_id {
-                    if is_active {
-                        self.layout_manager.layout_engine.windows_in_active_workspace(space)
-                    } else {
-                        self.layout_manager
-                            .layout_engine
-                            .virtual_workspace_manager()
-                            .workspace_info(space, *workspace_id)
-                            .map(|ws| ws.windows().collect())
-                            .unwrap_or_default()
-                    }
-                } else {
-                    Vec::new()
-                };
-
-            let predicted_positions = if !is_active {
-                if let Some(space) = space_id {
-                    let screen_info = self
-                        .space_manager
-                        .screens
-                        .iter()
-                        .find(|s| s.space == Some(space))
-                        .cloned()
-                        .or_else(|| self.space_manager.screens.first().cloned());
-
-                    if let Some(screen) = screen_info {
-                        let display_uuid = if screen.display_uuid.is_empty() {
-                            None
-                        } else {
-                            Some(screen.display_uuid.as_str())
-                        };
-                        let gaps = self
-                            .config_manager
-                            .config
-                            .settings
-                            .layout
-                            .gaps
-                            .effective_for_display(display_uuid);
-                        self.layout_manager.layout_engine.calculate_layout_for_workspace(
-                            space,
-                            *workspace_id,
-                            screen.frame,
-                            &gaps,
-                            self.config_manager.config.settings.ui.stack_line.thickness(),
-                            self.config_manager.config.settings.ui.stack_line.horiz_placement,
-                            self.config_manager.config.settings.ui.stack_line.vert_placement,
-                        )
-                    } else {
-                        vec![]
-                    }
-                } else {
-                    vec![]
-                }
-            } else {
-                vec![]
-            };
-
-            let predicted_map: std::collections::HashMap<WindowId, CGRect> =
-                predicted_positions.into_iter().collect();
-
-            let mut windows: Vec<WindowData> = Vec::new();
-            for wid in workspace_windows_ids.into_iter() {
-                if let Some(mut wd) = self.create_window_data(wid) {
-                    if !is_active && let Some(pred) = predicted_map.get(&wid).copied() {
-                        wd.frame = pred;
-                    }
-                    windows.push(wd);
-                }
-            }
-
             workspaces.push(WorkspaceData {
                 id: format!("{:?}", workspace_id),
                 name: workspace_name.to_string(),
                 is_active,
-                window_count: windows.len(),
+                window_count: windows.iter().filter(|w| !w.is_offscreen()).count(),
                 windows,
                 index,
             });
@@ -187,6 +151,80 @@ impl Reactor {
         workspaces
     }
 
+    fn handle_workspace_windows_query(
+        &mut self,
+        space_id_param: Option<SpaceId>,
+        workspace_index: usize,
+        screen: Option<CGRect>,
+    ) -> Option<Vec<WindowData>> {
+        let space = space_id_param
+            .or_else(get_active_space_number)
+            .or_else(|| self.space_manager.screens.first().and_then(|s| s.space))?;
+        let workspace_id = self
+            .layout_manager
+            .layout_engine
+            .virtual_workspace_manager()
+            .workspace_at_index(space, workspace_index)?;
+        Some(self.workspace_windows_data(space, workspace_id, screen))
+    }
+
+    /// Windows of a workspace as `WindowData`. The active workspace reports
+    /// where its windows are; any other workspace reports where they would be
+    /// placed on `screen` (the space's display when `None`), computed from its
+    /// stored layout plus remembered floating positions. Both the workspace
+    /// list (and therefore mission control) and the per-workspace query go
+    /// through here so their geometry always agrees. Windows minimized out of
+    /// the workspace come last, flagged as such.
+    fn workspace_windows_data(
+        &self,
+        space: SpaceId,
+        workspace_id: VirtualWorkspaceId,
+        screen: Option<CGRect>,
+    ) -> Vec<WindowData> {
+        let mut windows = self.laid_out_windows_data(space, workspace_id, screen);
+        windows.extend(self.minimized_window_data(space, workspace_id));
+        windows
+    }
+
+    fn laid_out_windows_data(
+        &self,
+        space: SpaceId,
+        workspace_id: VirtualWorkspaceId,
+        screen: Option<CGRect>,
+    ) -> Vec<WindowData> {
+        let screen_info = self
+            .space_manager
+            .screens
+            .iter()
+            .find(|s| s.space == Some(space))
+            .or_else(|| self.space_manager.screens.first());
+        let display_uuid =
+            screen_info.map(|s| s.display_uuid.as_str()).filter(|uuid| !uuid.is_empty());
+        let settings = &self.config_manager.config.settings;
+        let gaps = settings.layout.gaps.effective_for_display(display_uuid);
+
+        self.layout_manager
+            .layout_engine
+            .windows_in_workspace(
+                space,
+                workspace_id,
+                screen.or_else(|| screen_info.map(|s| s.frame)),
+                &gaps,
+                settings.ui.stack_line.thickness(),
+                settings.ui.stack_line.horiz_placement,
+                settings.ui.stack_line.vert_placement,
+            )
+            .into_iter()
+            .filter_map(|(wid, frame)| {
+                let mut wd = self.create_window_data(wid)?;
+                if let Some(frame) = frame {
+                    wd.frame = frame;
+                }
+                Some(wd)
+            })
+            .collect()
+    }
+
     fn handle_active_workspace_query(
         &self,
         space_id_param: Option<SpaceId>,
@@ -219,17 +257,46 @@ impl Reactor {
             .collect()
     }
 
+    /// Builds the initial state for a new subscriber out of the same pieces the
+    /// individual queries return, so the two can never disagree.
+    fn handle_snapshot_query(&mut self) -> SnapshotData {
+        let displays = self.handle_displays_query();
+        let spaces: Vec<SpaceId> = self.space_manager.iter_known_spaces().collect();
+        let spaces = spaces
+            .into_iter()
+            .map(|space| SpaceSnapshotData {
+                space_id: space.get(),
+                workspaces: self.handle_workspace_query(Some(space)),
+            })
+            .collect();
+        let focused_window = self.main_window().and_then(|wid| self.create_window_data(wid));
+
+        SnapshotData {
+            displays,
+            spaces,
+            focused_window,
+            mission_control_active: self.is_mission_control_active(),
+            compatibility: CompatibilityData {
+                protocol_version: 1,
+                features: vec!["snapshot".to_string(), "sequenced_events".to_string()],
+            },
+        }
+    }
+
     fn handle_windows_query(&self, space_id: Option<SpaceId>) -> Vec<WindowData> {
         let target_space = space_id.or_else(|| self.space_manager.first_known_space());
 
         if let Some(space) = target_space {
-            let active_windows =
-                self.layout_manager.layout_engine.windows_in_active_workspace(space);
-
-            active_windows
+            let engine = &self.layout_manager.layout_engine;
+            let mut windows: Vec<WindowData> = engine
+                .windows_in_active_workspace(space)
                 .into_iter()
                 .filter_map(|wid| self.create_window_data(wid))
-                .collect()
+                .collect();
+            if let Some(workspace_id) = engine.active_workspace(space) {
+                windows.extend(self.minimized_window_data(space, workspace_id));
+            }
+            windows
         } else {
             self.window_manager
                 .windows
@@ -305,6 +372,34 @@ impl Reactor {
         })
     }
 
+    fn handle_layout_tree_query(
+        &self,
+        space_id: Option<SpaceId>,
+        options: &TreeRenderOptions,
+    ) -> Option<String> {
+        let space = space_id
+            .or_else(get_active_space_number)
+            .or_else(|| self.space_manager.screens.first().and_then(|s| s.space))?;
+        self.layout_manager.layout_engine.draw_tree_with_options(space, options)
+    }
+
+    fn handle_tree_snapshot_query(&self, space_id: Option<SpaceId>) -> Option<TreeSnapshot> {
+        let space = space_id
+            .or_else(get_active_space_number)
+            .or_else(|| self.space_manager.screens.first().and_then(|s| s.space))?;
+        self.layout_manager.layout_engine.tree_snapshot(space)
+    }
+
+    fn handle_journal_query(&self, space_id: Option<SpaceId>) -> Vec<JournalEntry> {
+        let Some(space) = space_id
+            .or_else(get_active_space_number)
+            .or_else(|| self.space_manager.screens.first().and_then(|s| s.space))
+        else {
+            return Vec::new();
+        };
+        self.layout_manager.layout_engine.dump_journal(space)
+    }
+
     fn handle_metrics_query(&self) -> serde_json::Value {
         let stats = self.layout_manager.layout_engine.virtual_workspace_manager().get_stats();
 
@@ -320,6 +415,8 @@ impl Reactor {
             "applications": self.app_manager.apps.len(),
             "screens": self.space_manager.screens.len(),
             "workspace_stats": workspace_stats,
+            "engine": self.layout_manager.layout_engine.metrics_snapshot(),
+            "mission_control": crate::ui::mission_control::render_health(),
         })
     }
 
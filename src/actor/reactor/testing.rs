@@ -292,6 +292,9 @@ impl Apps {
                 }
                 Request::Raise(..) => todo!(),
                 Request::CloseWindow(..) => todo!(),
+                Request::Unminimize(wid) => {
+                    events.push(Event::WindowDeminiaturized(wid));
+                }
             }
         }
         debug!(?events);
@@ -268,6 +268,8 @@ fn handle_layout_response_groups_windows_by_app_and_screen() {
             ],
             focus_window: None,
             workspace_changed_to: None,
+            layout_changed: false,
+            fullscreen_windows: Vec::new(),
         },
         None,
     );
@@ -312,6 +314,8 @@ fn handle_layout_response_includes_handles_for_raise_and_focus_windows() {
             raise_windows: vec![WindowId::new(1, 1)],
             focus_window: Some(WindowId::new(2, 1)),
             workspace_changed_to: None,
+            layout_changed: false,
+            fullscreen_windows: Vec::new(),
         },
         None,
     );
@@ -553,6 +557,8 @@ fn it_respects_wsid_suppression_for_apply_app_rules() {
             is_ax_standard: info.is_standard,
             is_ax_root: info.is_root,
             is_minimized: info.is_minimized,
+            hidden_by_app: false,
+            minimized_from: None,
             is_manageable: true,
             ignore_app_rule: false,
             window_server_id: info.sys_id,
@@ -650,3 +656,67 @@ fn it_handles_rapid_space_changes() {
         assert_eq!(state.frame, final_windows[&wid].frame);
     }
 }
+
+fn frame_writes(requests: Vec<Request>) -> BTreeMap<WindowId, usize> {
+    let mut writes = BTreeMap::new();
+    for request in requests {
+        match request {
+            Request::SetWindowFrame(wid, ..) => *writes.entry(wid).or_default() += 1,
+            Request::SetBatchWindowFrame(frames, _) => {
+                for (wid, _) in frames {
+                    *writes.entry(wid).or_default() += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    writes
+}
+
+#[test]
+fn batched_commands_settle_in_one_layout_pass() {
+    let mut apps = Apps::new();
+    let mut reactor = Reactor::new_for_test(LayoutEngine::new(
+        &crate::common::config::VirtualWorkspaceSettings::default(),
+        &crate::common::config::LayoutSettings::default(),
+        None,
+    ));
+    let screen = CGRect::new(CGPoint::new(0., 0.), CGSize::new(1000., 1000.));
+    reactor.handle_event(screen_params_event(
+        vec![screen],
+        vec![Some(SpaceId::new(1))],
+        vec![],
+    ));
+    reactor.handle_events(apps.make_app(1, make_windows(3)));
+    apps.simulate_until_quiet(&mut reactor);
+
+    reactor.handle_event(Event::Command(Command::Reactor(ReactorCommand::BeginBatch)));
+    for _ in 0..3 {
+        reactor.handle_event(Event::Command(Command::Layout(LayoutCommand::ToggleOrientation)));
+    }
+    assert!(frame_writes(apps.requests()).is_empty());
+
+    reactor.handle_event(Event::Command(Command::Reactor(ReactorCommand::EndBatch)));
+    let requests = apps.requests();
+    let writes = frame_writes(requests);
+    assert_eq!(
+        writes,
+        (1..=3).map(|idx| (WindowId::new(1, idx), 1)).collect::<BTreeMap<_, _>>()
+    );
+
+    // Without a batch each command gets its own pass again.
+    reactor.handle_event(Event::Command(Command::Layout(LayoutCommand::ToggleOrientation)));
+    assert_eq!(frame_writes(apps.requests()).len(), 3);
+}
+
+#[test]
+fn abandoned_batches_expire() {
+    let mut batch = managers::BatchManager::default();
+    let start = std::time::Instant::now();
+    assert!(batch.begin(start));
+    assert!(!batch.begin(start));
+    assert!(!batch.expired(start));
+    assert!(batch.expired(start + managers::BatchManager::TIMEOUT));
+    assert_eq!(batch.end(), Some((false, false)));
+    assert!(!batch.expired(start + managers::BatchManager::TIMEOUT));
+}
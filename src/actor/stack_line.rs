@@ -1,8 +1,11 @@
+use core::ffi::c_void;
 use std::collections::hash_map::Entry;
 use std::rc::Rc;
 
 use objc2::MainThreadMarker;
+use objc2_app_kit::{NSEvent, NSScreen};
 use objc2_core_foundation::{CGPoint, CGRect, CGSize};
+use objc2_core_graphics::{CGEvent, CGEventField, CGEventTapOptions, CGEventTapProxy, CGEventType};
 use tracing::instrument;
 
 use crate::actor::app::WindowId;
@@ -14,6 +17,7 @@ use crate::layout_engine::LayoutKind;
 use crate::model::tree::NodeId;
 use crate::sys::screen::{CoordinateConverter, SpaceId};
 use crate::ui::stack_line::{GroupDisplayData, GroupIndicatorWindow, GroupKind, IndicatorConfig};
+use crate::ui::stack_picker::{StackPickerAction, StackPickerOverlay};
 
 #[derive(Debug, Clone)]
 pub struct GroupInfo {
@@ -34,18 +38,54 @@ pub enum Event {
     },
     ScreenParametersChanged(CoordinateConverter),
     ConfigUpdated(Box<Config>),
+    /// Open the quick-jump picker over a stack, listing its members' titles
+    /// in stack order.
+    ShowPicker {
+        space_id: SpaceId,
+        node_id: NodeId,
+        frame: CGRect,
+        titles: Vec<String>,
+    },
 }
 
 pub struct StackLine {
     config: Config,
     rx: Receiver,
-    #[allow(dead_code)]
     mtm: MainThreadMarker,
     indicators: HashMap<NodeId, GroupIndicatorWindow>,
+    // Boxed so the picker's event tap can keep a stable pointer to it.
+    picker: Option<(SpaceId, NodeId, Box<StackPickerOverlay>)>,
     #[allow(dead_code)]
     reactor_tx: reactor::Sender,
     coordinate_converter: CoordinateConverter,
     group_sigs_by_space: HashMap<SpaceId, Vec<GroupSig>>,
+    /// Reads the scroll wheel while any indicator is shown.
+    scroll_tap: Option<crate::sys::event_tap::EventTap>,
+    /// The indicator being scrolled over and its partial steps.
+    scroll: Option<(NodeId, ScrollAccumulator)>,
+}
+
+/// Turns scroll wheel deltas into whole stack steps, carrying fractions
+/// over so a trackpad takes as much travel per step as one wheel notch.
+#[derive(Debug, Default)]
+struct ScrollAccumulator {
+    pending: f64,
+}
+
+impl ScrollAccumulator {
+    /// Adds `delta` lines, positive for scrolling down, and returns how many
+    /// members to step by, negative for stepping back.
+    fn add(&mut self, delta: f64) -> i64 {
+        self.pending += delta;
+        let steps = self.pending.trunc();
+        self.pending -= steps;
+        steps as i64
+    }
+}
+
+/// The member `steps` away from `selected`, wrapping around the stack.
+fn stepped_index(selected: usize, total: usize, steps: i64) -> usize {
+    (selected as i64 + steps).rem_euclid(total as i64) as usize
 }
 
 pub type Sender = actor::Sender<Event>;
@@ -64,9 +104,12 @@ impl StackLine {
             rx,
             mtm,
             indicators: HashMap::default(),
+            picker: None,
             reactor_tx,
             coordinate_converter,
             group_sigs_by_space: HashMap::default(),
+            scroll_tap: None,
+            scroll: None,
         }
     }
 
@@ -90,7 +133,9 @@ impl StackLine {
         if !self.is_enabled()
             && !matches!(
                 event,
-                Event::ConfigUpdated(_) | Event::ScreenParametersChanged(_)
+                Event::ConfigUpdated(_)
+                    | Event::ScreenParametersChanged(_)
+                    | Event::ShowPicker { .. }
             )
         {
             return;
@@ -105,6 +150,55 @@ impl StackLine {
             Event::ConfigUpdated(config) => {
                 self.handle_config_updated(config);
             }
+            Event::ShowPicker {
+                space_id,
+                node_id,
+                frame,
+                titles,
+            } => {
+                self.handle_show_picker(space_id, node_id, frame, titles);
+            }
+        }
+    }
+
+    fn handle_show_picker(
+        &mut self,
+        space_id: SpaceId,
+        node_id: NodeId,
+        frame: CGRect,
+        titles: Vec<String>,
+    ) {
+        self.picker = None;
+        if titles.is_empty() {
+            return;
+        }
+        let scale = NSScreen::mainScreen(self.mtm).map_or(1.0, |s| s.backingScaleFactor());
+        let self_ptr: *mut StackLine = self as *mut _;
+        let on_action = Rc::new(move |action| {
+            unsafe {
+                // safety: `self_ptr` remains valid while the actor lives.
+                let this: &mut StackLine = &mut *self_ptr;
+                this.handle_picker_action(action);
+            }
+        });
+        match StackPickerOverlay::new(frame, titles, scale, on_action) {
+            Ok(picker) => {
+                let picker = Box::new(picker);
+                picker.show();
+                self.picker = Some((space_id, node_id, picker));
+            }
+            Err(err) => tracing::warn!(?err, "failed to create stack picker window"),
+        }
+    }
+
+    fn handle_picker_action(&mut self, action: StackPickerAction) {
+        let Some((space_id, node_id, _picker)) = self.picker.take() else {
+            return;
+        };
+        if let StackPickerAction::Select(index) = action {
+            tracing::debug!(?node_id, index, "Stack picker selected member");
+            self.reactor_tx
+                .send(reactor::Event::StackMemberSelected { space_id, node_id, index });
         }
     }
 
@@ -142,6 +236,132 @@ impl StackLine {
         for group in groups {
             self.update_or_create_indicator(group);
         }
+        self.sync_scroll_tap();
+    }
+
+    /// Reads the scroll wheel only while there is an indicator to scroll.
+    fn sync_scroll_tap(&mut self) {
+        if !self.is_enabled() || self.indicators.is_empty() {
+            self.scroll_tap = None;
+            self.scroll = None;
+        } else if self.scroll_tap.is_none() {
+            self.scroll_tap = self.create_scroll_tap();
+        }
+    }
+
+    /// Steps the stack under `point` by whole wheel notches. `delta` is in
+    /// lines, positive for scrolling down. Returns whether the point was
+    /// over an indicator.
+    fn handle_scroll(&mut self, point: CGPoint, delta: f64) -> bool {
+        let Some((node_id, indicator)) = self
+            .indicators
+            .iter()
+            .find(|(_, indicator)| indicator.segment_at_screen_point(point).is_some())
+        else {
+            self.scroll = None;
+            return false;
+        };
+        let node_id = *node_id;
+        let (Some(space_id), Some(group_data)) = (indicator.space_id(), indicator.group_data())
+        else {
+            return true;
+        };
+        if group_data.total_count == 0 {
+            return true;
+        }
+        if self.scroll.as_ref().is_none_or(|(node, _)| *node != node_id) {
+            self.scroll = Some((node_id, ScrollAccumulator::default()));
+        }
+        let Some((_, accumulator)) = &mut self.scroll else {
+            return true;
+        };
+        let steps = accumulator.add(delta);
+        if steps != 0 {
+            let index = stepped_index(group_data.selected_index, group_data.total_count, steps);
+            tracing::debug!(?node_id, index, "Stack line scrolled");
+            self.reactor_tx
+                .send(reactor::Event::StackMemberSelected { space_id, node_id, index });
+        }
+        true
+    }
+
+    fn create_scroll_tap(&mut self) -> Option<crate::sys::event_tap::EventTap> {
+        #[repr(C)]
+        struct ScrollCtx {
+            stack_line: *mut StackLine,
+            consumes: bool,
+        }
+
+        unsafe fn drop_ctx(ptr: *mut c_void) {
+            unsafe {
+                drop(Box::from_raw(ptr as *mut ScrollCtx));
+            }
+        }
+
+        unsafe extern "C-unwind" fn scroll_callback(
+            _proxy: CGEventTapProxy,
+            etype: CGEventType,
+            event: core::ptr::NonNull<CGEvent>,
+            user_info: *mut c_void,
+        ) -> *mut CGEvent {
+            let ctx = unsafe { &*(user_info as *const ScrollCtx) };
+            let mut handled = false;
+            // safety: the tap is owned by the stack line and dropped with it.
+            if let Some(this) = unsafe { ctx.stack_line.as_mut() }
+                && etype == CGEventType::ScrollWheel
+            {
+                let cg_event = unsafe { event.as_ref() };
+                let point = CGEvent::location(Some(cg_event));
+                let delta = CGEvent::double_value_field(
+                    Some(cg_event),
+                    CGEventField::ScrollWheelEventFixedPtDeltaAxis1,
+                );
+                // Deltas are reported up-positive and already flipped for
+                // natural scrolling; undo both so down always steps forward.
+                let inverted = NSEvent::eventWithCGEvent(cg_event)
+                    .is_some_and(|nsevent| nsevent.isDirectionInvertedFromDevice());
+                let down = if inverted { delta } else { -delta };
+                handled = this.handle_scroll(point, down);
+            }
+            if handled && ctx.consumes {
+                core::ptr::null_mut()
+            } else {
+                event.as_ptr()
+            }
+        }
+
+        let mask = 1u64 << CGEventType::ScrollWheel.0 as u64;
+        let self_ptr: *mut StackLine = self as *mut _;
+        unsafe {
+            let ctx_ptr = Box::into_raw(Box::new(ScrollCtx {
+                stack_line: self_ptr,
+                consumes: true,
+            })) as *mut c_void;
+            if let Some(tap) = crate::sys::event_tap::EventTap::new_with_options(
+                CGEventTapOptions::Default,
+                mask,
+                Some(scroll_callback),
+                ctx_ptr,
+                Some(drop_ctx),
+            ) {
+                return Some(tap);
+            }
+            drop_ctx(ctx_ptr);
+            let ctx_ptr = Box::into_raw(Box::new(ScrollCtx {
+                stack_line: self_ptr,
+                consumes: false,
+            })) as *mut c_void;
+            let tap = crate::sys::event_tap::EventTap::new_listen_only(
+                mask,
+                Some(scroll_callback),
+                ctx_ptr,
+                Some(drop_ctx),
+            );
+            if tap.is_none() {
+                drop_ctx(ctx_ptr);
+            }
+            tap
+        }
     }
 
     fn handle_screen_parameters_changed(&mut self, converter: CoordinateConverter) {
@@ -180,6 +400,7 @@ impl StackLine {
             }
         }
 
+        self.sync_scroll_tap();
         tracing::debug!("Updated stack line configuration");
     }
 
@@ -217,8 +438,8 @@ impl StackLine {
 
     fn update_or_create_indicator(&mut self, group: GroupInfo) {
         let group_kind = match group.container_kind {
-            LayoutKind::HorizontalStack => GroupKind::Horizontal,
-            LayoutKind::VerticalStack => GroupKind::Vertical,
+            LayoutKind::HorizontalStack | LayoutKind::HorizontalTabbed => GroupKind::Horizontal,
+            LayoutKind::VerticalStack | LayoutKind::VerticalTabbed => GroupKind::Vertical,
             _ => {
                 tracing::warn!(?group.container_kind, "Unexpected container kind for group");
                 return;
@@ -366,6 +587,25 @@ mod tests {
         assert!(!LayoutKind::Horizontal.is_group());
     }
 
+    #[test]
+    fn scroll_steps_once_per_line_of_travel() {
+        let mut scroll = ScrollAccumulator::default();
+        assert_eq!(scroll.add(0.4), 0);
+        assert_eq!(scroll.add(0.4), 0);
+        assert_eq!(scroll.add(0.4), 1);
+        assert_eq!(scroll.add(3.0), 3);
+        assert_eq!(scroll.add(-0.5), 0);
+        assert_eq!(scroll.add(-1.0), -1);
+    }
+
+    #[test]
+    fn scroll_steps_wrap_around_the_stack() {
+        assert_eq!(stepped_index(0, 3, 1), 1);
+        assert_eq!(stepped_index(2, 3, 1), 0);
+        assert_eq!(stepped_index(0, 3, -1), 2);
+        assert_eq!(stepped_index(1, 3, 5), 0);
+    }
+
     #[test]
     fn test_calculate_indicator_frame() {
         let group_frame = CGRect::new(CGPoint::new(100.0, 200.0), CGSize::new(400.0, 300.0));
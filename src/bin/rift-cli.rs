@@ -61,6 +61,17 @@ enum QueryCommands {
         #[arg(long)]
         space_id: Option<u64>,
     },
+    /// List a workspace's windows with the frames they have, or would have if
+    /// the workspace were shown
+    WorkspaceWindows {
+        /// Zero-based workspace index
+        workspace: usize,
+        #[arg(long)]
+        space_id: Option<u64>,
+        /// Screen rect to lay the workspace out in, as x,y,width,height
+        #[arg(long, value_delimiter = ',', num_args = 4)]
+        screen: Option<Vec<f64>>,
+    },
     /// List windows (optionally filtered by space)
     Windows {
         #[arg(long)]
@@ -74,8 +85,34 @@ enum QueryCommands {
     Applications,
     /// Get layout state for a space
     Layout { space_id: u64 },
+    /// Render the layout tree of a space's active workspace
+    Tree {
+        #[arg(long)]
+        space_id: Option<u64>,
+        /// Summarize containers nested deeper than this
+        #[arg(long)]
+        max_depth: Option<usize>,
+        /// Only expand containers on the path to the selection
+        #[arg(long)]
+        focus_path: bool,
+        /// Stop after rendering this many nodes
+        #[arg(long)]
+        max_nodes: Option<usize>,
+    },
+    /// Print the layout tree of a space's active workspace with its
+    /// fingerprint; subscribe to `layout_tree_changed` to follow it
+    TreeSnapshot {
+        #[arg(long)]
+        space_id: Option<u64>,
+    },
     /// Get performance metrics
     Metrics,
+    /// Print the recent layout events and commands of a space, for bug
+    /// reports (needs `settings.layout.journal.enabled`)
+    Journal {
+        #[arg(long)]
+        space_id: Option<u64>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -114,6 +151,10 @@ enum ExecuteCommands {
     SaveAndExit,
     /// Show timing metrics
     ShowTiming,
+    /// Defer relayouts and broadcasts until end-batch (auto-ends after 5s)
+    BeginBatch,
+    /// Apply everything deferred since begin-batch in one pass
+    EndBatch,
 }
 
 #[derive(Subcommand)]
@@ -126,12 +167,25 @@ enum WindowCommands {
     Focus {
         direction: String, // up, down, left, right
     },
+    /// Focus the nth (zero-based) visible window of the layout, in tree order
+    FocusNth { index: usize },
     /// Toggle window floating state
     ToggleFloat,
     /// Toggle fullscreen mode (fills the whole screen, ignores outer gaps)
     ToggleFullscreen,
     /// Toggle fullscreen within configured outer gaps (respects outer gaps / fills tiling area)
     ToggleFullscreenWithinGaps,
+    /// Lift the window out of its containers and make it fullscreen within gaps
+    IsolateAndMaximize,
+    /// Tile the window even though app rules leave it unmanaged
+    Manage,
+    /// Take the window out of the layout and keep it out, whatever app rules say
+    Unmanage,
+    /// Keep the window's current size while sibling windows are added or removed
+    TogglePreserveSize,
+    /// Keep the window's container as arranged: it is not collapsed, nested
+    /// into or merged automatically until unjoined
+    ToggleContainerLock,
     /// Grow the current window size (increments by ~5%).
     ResizeGrow,
     /// Shrink the current window size (decrements by ~5%).
@@ -143,7 +197,15 @@ enum WindowCommands {
     ///   Examples:
     ///   rift-cli execute window resize-by --amount 0.05    # grow by 5%
     ///   rift-cli execute window resize-by --amount -0.10   # shrink by 10%
+    ///
+    ///   Amounts beyond ±0.5 are rejected; use `resize-px` for pixel values.
     ResizeBy { amount: f64 },
+    /// Resize the selected window by a number of pixels, measured along the
+    /// screen dimension of `direction` (width for left/right, height for up/down).
+    ResizePx {
+        direction: String, // up, down, left, right
+        pixels: f64,
+    },
     /// Close a window by window server identifier
     Close {
         /// Window Id (window server id or idx from window id)
@@ -169,6 +231,8 @@ enum WorkspaceCommands {
     Create,
     /// Switch to the last workspace
     Last,
+    /// Move every window of a workspace into another and remove the first
+    Merge { from: usize, into: usize },
 }
 
 #[derive(Subcommand)]
@@ -183,12 +247,53 @@ enum LayoutCommands {
     JoinWindow { direction: String },
     /// Toggle stacked state for the selected container
     ToggleStack,
+    /// Switch the selected stack between peeking and tabbed
+    ToggleTabbed,
+    /// Open the quick-jump picker for the focused stack
+    PickStackMember,
     /// Global orientation toggle that works consistently across layout modes (and between splits/stacks)
     ToggleOrientation,
+    /// Flip horizontal/vertical on every container under the selection's parent
+    RotateSubtree,
+    /// Turn the whole layout a quarter turn clockwise
+    RotateLayout {
+        /// Turn counter-clockwise instead
+        #[arg(long)]
+        counter_clockwise: bool,
+    },
+    /// Reverse the whole layout along an axis
+    MirrorLayout {
+        axis: String, // horizontal, vertical
+    },
+    /// Move the selected window to the front of the layout, making it the main window
+    PromoteToMain,
+    /// Reverse the order of the selected window's container, swapping its two children
+    FlipContainer,
+    /// Split the selected window's container 62/38 between its first child and the rest
+    GoldenRatio {
+        /// Give the larger share to the rest instead of the first child
+        #[arg(long)]
+        main_last: bool,
+    },
+    /// Put the first window in one column and stack the rest in another beside it
+    TwoColumn {
+        /// Put the first window's column on the right
+        #[arg(long)]
+        master_right: bool,
+    },
+    /// Split the selection into COUNT equal tiles of a container kind
+    SplitInto {
+        kind: String, // horizontal, vertical, horizontal_stack, vertical_stack
+        count: usize,
+    },
     /// Unjoin previously joined windows
     Unjoin,
     /// Toggle floating on the focused selection (tree focus)
     ToggleFocusFloat,
+    /// Revert the last structural layout change
+    Undo,
+    /// Re-apply the last reverted layout change
+    Redo,
 }
 
 #[derive(Subcommand)]
@@ -318,12 +423,12 @@ enum DisplayCommands {
 enum SubscribeCommands {
     /// Subscribe to Mach IPC events
     Mach {
-        /// Event to subscribe to (workspace_changed, windows_changed, window_title_changed, *)
+        /// Event to subscribe to (workspace_changed, windows_changed, window_title_changed, window_focused, window_added, window_assigned, window_display_hints_changed, layout_tree_changed, *)
         event: String,
     },
     /// Subscribe to events via CLI command execution
     Cli {
-        /// Event to subscribe to (workspace_changed, windows_changed, window_title_changed, *)
+        /// Event to subscribe to (workspace_changed, windows_changed, window_title_changed, window_focused, window_added, window_assigned, window_display_hints_changed, layout_tree_changed, *)
         #[arg(long)]
         event: String,
         /// Command to execute when event occurs
@@ -421,12 +526,37 @@ fn build_request(command: Commands) -> Result<RiftRequest, String> {
 fn build_query_request(query: QueryCommands) -> Result<RiftRequest, String> {
     match query {
         QueryCommands::Workspaces { space_id } => Ok(RiftRequest::GetWorkspaces { space_id }),
+        QueryCommands::WorkspaceWindows { workspace, space_id, screen } => {
+            let screen = match screen.as_deref() {
+                None => None,
+                Some(&[x, y, width, height]) => Some([x, y, width, height]),
+                Some(_) => return Err("--screen expects x,y,width,height".to_string()),
+            };
+            Ok(RiftRequest::GetWorkspaceWindows {
+                space_id,
+                workspace_index: workspace,
+                screen,
+            })
+        }
         QueryCommands::Windows { space_id } => Ok(RiftRequest::GetWindows { space_id }),
         QueryCommands::Displays => Ok(RiftRequest::GetDisplays),
         QueryCommands::Window { window_id } => Ok(RiftRequest::GetWindowInfo { window_id }),
         QueryCommands::Applications => Ok(RiftRequest::GetApplications),
         QueryCommands::Layout { space_id } => Ok(RiftRequest::GetLayoutState { space_id }),
+        QueryCommands::Tree {
+            space_id,
+            max_depth,
+            focus_path,
+            max_nodes,
+        } => Ok(RiftRequest::GetLayoutTree {
+            space_id,
+            max_depth,
+            focus_path_only: focus_path,
+            max_nodes,
+        }),
+        QueryCommands::TreeSnapshot { space_id } => Ok(RiftRequest::GetTreeSnapshot { space_id }),
         QueryCommands::Metrics => Ok(RiftRequest::GetMetrics),
+        QueryCommands::Journal { space_id } => Ok(RiftRequest::GetJournal { space_id }),
     }
 }
 
@@ -458,6 +588,12 @@ fn build_execute_request(execute: ExecuteCommands) -> Result<RiftRequest, String
         ExecuteCommands::ShowTiming => RiftCommand::Reactor(reactor::Command::Metrics(
             rift_wm::common::log::MetricsCommand::ShowTiming,
         )),
+        ExecuteCommands::BeginBatch => {
+            RiftCommand::Reactor(reactor::Command::Reactor(reactor::ReactorCommand::BeginBatch))
+        }
+        ExecuteCommands::EndBatch => {
+            RiftCommand::Reactor(reactor::Command::Reactor(reactor::ReactorCommand::EndBatch))
+        }
     };
 
     if let RiftCommand::Config(rift_wm::common::config::ConfigCommand::GetConfig) = &rift_command {
@@ -493,6 +629,11 @@ fn map_window_command(cmd: WindowCommands) -> Result<RiftCommand, String> {
         WindowCommands::Focus { direction } => Ok(RiftCommand::Reactor(reactor::Command::Layout(
             LC::MoveFocus(direction.into()),
         ))),
+        WindowCommands::FocusNth { index } => {
+            Ok(RiftCommand::Reactor(reactor::Command::Layout(LC::FocusNth {
+                index,
+            })))
+        }
         WindowCommands::ToggleFloat => Ok(RiftCommand::Reactor(reactor::Command::Layout(
             LC::ToggleWindowFloating,
         ))),
@@ -502,6 +643,21 @@ fn map_window_command(cmd: WindowCommands) -> Result<RiftCommand, String> {
         WindowCommands::ToggleFullscreenWithinGaps => Ok(RiftCommand::Reactor(
             reactor::Command::Layout(LC::ToggleFullscreenWithinGaps),
         )),
+        WindowCommands::IsolateAndMaximize => Ok(RiftCommand::Reactor(reactor::Command::Layout(
+            LC::IsolateAndMaximize,
+        ))),
+        WindowCommands::Manage => {
+            Ok(RiftCommand::Reactor(reactor::Command::Layout(LC::ManageWindow)))
+        }
+        WindowCommands::Unmanage => Ok(RiftCommand::Reactor(reactor::Command::Layout(
+            LC::UnmanageWindow,
+        ))),
+        WindowCommands::TogglePreserveSize => Ok(RiftCommand::Reactor(reactor::Command::Layout(
+            LC::TogglePreserveSize,
+        ))),
+        WindowCommands::ToggleContainerLock => Ok(RiftCommand::Reactor(reactor::Command::Layout(
+            LC::ToggleContainerLock,
+        ))),
         WindowCommands::ResizeGrow => Ok(RiftCommand::Reactor(reactor::Command::Layout(
             LC::ResizeWindowGrow,
         ))),
@@ -511,6 +667,12 @@ fn map_window_command(cmd: WindowCommands) -> Result<RiftCommand, String> {
         WindowCommands::ResizeBy { amount } => Ok(RiftCommand::Reactor(reactor::Command::Layout(
             LC::ResizeWindowBy { amount },
         ))),
+        WindowCommands::ResizePx { direction, pixels } => Ok(RiftCommand::Reactor(
+            reactor::Command::Layout(LC::ResizeWindowPx {
+                direction: direction.into(),
+                pixels,
+            }),
+        )),
         WindowCommands::Close { window_id } => {
             let wsid = parse_window_server_id(&window_id)?;
             Ok(RiftCommand::Reactor(reactor::Command::Reactor(
@@ -559,6 +721,9 @@ fn map_workspace_command(cmd: WorkspaceCommands) -> Result<RiftCommand, String>
         WorkspaceCommands::Last => Ok(RiftCommand::Reactor(reactor::Command::Layout(
             LC::SwitchToLastWorkspace,
         ))),
+        WorkspaceCommands::Merge { from, into } => Ok(RiftCommand::Reactor(
+            reactor::Command::Layout(LC::MergeWorkspaces { from, into }),
+        )),
     }
 }
 
@@ -576,15 +741,56 @@ fn map_layout_command(cmd: LayoutCommands) -> Result<RiftCommand, String> {
         LayoutCommands::ToggleStack => {
             Ok(RiftCommand::Reactor(reactor::Command::Layout(LC::ToggleStack)))
         }
+        LayoutCommands::ToggleTabbed => {
+            Ok(RiftCommand::Reactor(reactor::Command::Layout(LC::ToggleTabbed)))
+        }
+        LayoutCommands::PickStackMember => Ok(RiftCommand::Reactor(reactor::Command::Reactor(
+            reactor::ReactorCommand::ShowStackPicker,
+        ))),
         LayoutCommands::ToggleOrientation => Ok(RiftCommand::Reactor(reactor::Command::Layout(
             LC::ToggleOrientation,
         ))),
+        LayoutCommands::RotateSubtree => {
+            Ok(RiftCommand::Reactor(reactor::Command::Layout(LC::RotateSubtree)))
+        }
+        LayoutCommands::RotateLayout { counter_clockwise } => Ok(RiftCommand::Reactor(
+            reactor::Command::Layout(LC::RotateLayout { clockwise: !counter_clockwise }),
+        )),
+        LayoutCommands::MirrorLayout { axis } => Ok(RiftCommand::Reactor(
+            reactor::Command::Layout(LC::MirrorLayout {
+                axis: parse_orientation(&axis)?,
+            }),
+        )),
+        LayoutCommands::PromoteToMain => {
+            Ok(RiftCommand::Reactor(reactor::Command::Layout(LC::PromoteToMain)))
+        }
+        LayoutCommands::FlipContainer => {
+            Ok(RiftCommand::Reactor(reactor::Command::Layout(LC::FlipContainer)))
+        }
+        LayoutCommands::GoldenRatio { main_last } => {
+            Ok(RiftCommand::Reactor(reactor::Command::Layout(LC::GoldenRatio {
+                main_first: !main_last,
+            })))
+        }
+        LayoutCommands::TwoColumn { master_right } => {
+            Ok(RiftCommand::Reactor(reactor::Command::Layout(LC::TwoColumn {
+                master_on_left: !master_right,
+            })))
+        }
+        LayoutCommands::SplitInto { kind, count } => {
+            Ok(RiftCommand::Reactor(reactor::Command::Layout(LC::SplitInto {
+                kind: parse_layout_kind(&kind)?,
+                count,
+            })))
+        }
         LayoutCommands::Unjoin => {
             Ok(RiftCommand::Reactor(reactor::Command::Layout(LC::UnjoinWindows)))
         }
         LayoutCommands::ToggleFocusFloat => Ok(RiftCommand::Reactor(reactor::Command::Layout(
             LC::ToggleFocusFloating,
         ))),
+        LayoutCommands::Undo => Ok(RiftCommand::Reactor(reactor::Command::Layout(LC::Undo))),
+        LayoutCommands::Redo => Ok(RiftCommand::Reactor(reactor::Command::Layout(LC::Redo))),
     }
 }
 
@@ -756,6 +962,32 @@ fn parse_focus_direction(value: &str) -> Result<layout::Direction, String> {
     }
 }
 
+fn parse_layout_kind(value: &str) -> Result<layout::LayoutKind, String> {
+    match value.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+        "horizontal" => Ok(layout::LayoutKind::Horizontal),
+        "vertical" => Ok(layout::LayoutKind::Vertical),
+        "horizontal_stack" => Ok(layout::LayoutKind::HorizontalStack),
+        "vertical_stack" => Ok(layout::LayoutKind::VerticalStack),
+        "horizontal_tabbed" => Ok(layout::LayoutKind::HorizontalTabbed),
+        "vertical_tabbed" => Ok(layout::LayoutKind::VerticalTabbed),
+        other => Err(format!(
+            "Invalid layout kind '{}'; must be horizontal, vertical, horizontal_stack, vertical_stack, horizontal_tabbed, or vertical_tabbed",
+            other
+        )),
+    }
+}
+
+fn parse_orientation(value: &str) -> Result<layout::Orientation, String> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "horizontal" => Ok(layout::Orientation::Horizontal),
+        "vertical" => Ok(layout::Orientation::Vertical),
+        other => Err(format!(
+            "Invalid axis '{}'; must be horizontal or vertical",
+            other
+        )),
+    }
+}
+
 fn write_json(value: &Value, pretty: bool) -> Result<(), String> {
     let stdout = io::stdout();
     let mut handle = stdout.lock();
@@ -180,7 +180,7 @@ Enable it in System Settings > Desktop & Dock (Mission Control) and restart Rift
 
     let restore_path = restore_file().expect("Failed to determine restore file path");
     let restore_path_for_wm = restore_path.clone();
-    let layout = if opt.restore {
+    let mut layout = if opt.restore {
         match LayoutEngine::load(restore_path) {
             Ok(layout) => layout,
             Err(e) => {
@@ -201,6 +201,7 @@ Enable it in System Settings > Desktop & Dock (Mission Control) and restart Rift
             Some(broadcast_tx.clone()),
         )
     };
+    layout.enable_autosave(restore_path_for_wm.clone(), &config.settings.persistence);
     let (event_tap_tx, event_tap_rx) = rift_wm::actor::channel();
     let (menu_tx, menu_rx) = rift_wm::actor::channel();
     let (stack_line_tx, stack_line_rx) = rift_wm::actor::channel();
@@ -247,11 +248,16 @@ Enable it in System Settings > Desktop & Dock (Mission Control) and restart Rift
 
     let mach_bridge_rx = broadcast_rx;
 
+    let hooks_tx = ipc::hooks::spawn_hook_runner(config.settings.hooks.clone());
+
     let server_state_for_bridge = server_state.clone();
     std::thread::spawn(move || {
         let mut rx = mach_bridge_rx;
         let server_state = server_state_for_bridge;
         while let Some((_span, event)) = rx.blocking_recv() {
+            if let Some(hooks_tx) = &hooks_tx {
+                let _ = hooks_tx.send(event.clone());
+            }
             let state = server_state.read();
             state.publish(event);
         }
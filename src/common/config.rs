@@ -85,6 +85,15 @@ pub struct VirtualWorkspaceSettings {
     pub preserve_focus_per_workspace: bool,
     #[serde(default = "no")]
     pub workspace_auto_back_and_forth: bool,
+    /// What to do when the last window leaves the active workspace
+    #[serde(default)]
+    pub on_empty: OnEmptyWorkspace,
+    /// Switch to the workspace a window was just moved to
+    #[serde(default = "yes")]
+    pub move_follows_window: bool,
+    /// Whether switching workspaces affects only the focused display or all of them
+    #[serde(default)]
+    pub scope: WorkspaceScope,
     #[serde(default = "default_workspace_names")]
     pub workspace_names: Vec<String>,
     #[serde(default)]
@@ -93,6 +102,48 @@ pub struct VirtualWorkspaceSettings {
     pub reapply_app_rules_on_title_change: bool,
     #[serde(default)]
     pub app_rules: Vec<AppWorkspaceRule>,
+    /// Display-specific workspace overrides keyed by display UUID
+    #[serde(default)]
+    pub per_display: HashMap<String, WorkspaceOverride>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OnEmptyWorkspace {
+    /// Keep showing the empty workspace
+    #[default]
+    Stay,
+    /// Go back to the previously active workspace
+    #[serde(alias = "switch_previous", alias = "switch_last_active")]
+    SwitchToPrevious,
+    /// Go to the closest workspace (by index) that still has windows
+    SwitchToNearestNonempty,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspaceScope {
+    /// Each display switches its workspaces independently
+    #[default]
+    PerDisplay,
+    /// Switching to workspace N shows workspace N on every display
+    Global,
+}
+
+/// Overrides for workspace definitions on a per-display basis. Unset fields
+/// fall back to the global virtual workspace settings.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspaceOverride {
+    #[serde(default)]
+    pub default_workspace_count: Option<usize>,
+    #[serde(default)]
+    pub workspace_names: Option<Vec<String>>,
+    #[serde(default)]
+    pub default_workspace: Option<usize>,
+    /// Replaces the global app rules for windows on this display
+    #[serde(default)]
+    pub app_rules: Option<Vec<AppWorkspaceRule>>,
 }
 
 // Allow specifying a workspace by numeric index or by name in the config.
@@ -141,6 +192,13 @@ pub struct AppWorkspaceRule {
     /// non-empty string and will be compared against the accessibility subrole
     /// reported by the AX APIs for a window (exact string match).
     pub ax_subrole: Option<String>,
+
+    /// Optional: opacity from 0.0 (invisible) to 1.0 (opaque) to draw matching
+    /// windows with.
+    pub opacity: Option<f32>,
+    /// Keep matching windows above all others.
+    #[serde(default)]
+    pub always_on_top: bool,
 }
 
 impl Default for VirtualWorkspaceSettings {
@@ -151,16 +209,59 @@ impl Default for VirtualWorkspaceSettings {
             auto_assign_windows: true,
             preserve_focus_per_workspace: true,
             workspace_auto_back_and_forth: false,
+            on_empty: OnEmptyWorkspace::default(),
+            move_follows_window: true,
+            scope: WorkspaceScope::default(),
             workspace_names: default_workspace_names(),
             default_workspace: 0,
             reapply_app_rules_on_title_change: false,
             app_rules: Vec::new(),
+            per_display: HashMap::default(),
         }
     }
 }
 
 impl VirtualWorkspaceSettings {
+    /// Resolves the workspace settings for a display, applying any per-display overrides.
+    pub fn effective_for_display(&self, display_uuid: Option<&str>) -> VirtualWorkspaceSettings {
+        let mut resolved = VirtualWorkspaceSettings {
+            per_display: HashMap::default(),
+            ..self.clone()
+        };
+        if let Some(uuid) = display_uuid
+            && let Some(overrides) = self.per_display.get(uuid)
+        {
+            if let Some(count) = overrides.default_workspace_count {
+                resolved.default_workspace_count = count;
+            }
+            if let Some(names) = &overrides.workspace_names {
+                resolved.workspace_names = names.clone();
+            }
+            if let Some(default_workspace) = overrides.default_workspace {
+                resolved.default_workspace = default_workspace;
+            }
+            if let Some(app_rules) = &overrides.app_rules {
+                resolved.app_rules = app_rules.clone();
+            }
+        }
+        resolved
+    }
+
     pub fn validate(&self) -> Vec<String> {
+        let mut issues = self.validate_definitions();
+
+        for uuid in self.per_display.keys() {
+            for issue in self.effective_for_display(Some(uuid)).validate_definitions() {
+                if !issues.contains(&issue) {
+                    issues.push(format!("per_display.\"{uuid}\": {issue}"));
+                }
+            }
+        }
+
+        issues
+    }
+
+    fn validate_definitions(&self) -> Vec<String> {
         let mut issues = Vec::new();
 
         if self.default_workspace_count == 0 {
@@ -275,6 +376,15 @@ impl VirtualWorkspaceSettings {
                     issues.push(format!("Duplicate ax_subrole '{}' in rule {}", ax_sub, index));
                 }
             }
+
+            if let Some(opacity) = rule.opacity
+                && !(0.0..=1.0).contains(&opacity)
+            {
+                issues.push(format!(
+                    "App rule {} has opacity {} outside 0.0 to 1.0",
+                    index, opacity
+                ));
+            }
         }
 
         issues
@@ -341,6 +451,14 @@ pub struct Settings {
     #[serde(default)]
     pub window_snapping: WindowSnappingSettings,
 
+    /// Shell commands run when window manager events happen
+    #[serde(default)]
+    pub hooks: HookSettings,
+
+    /// How saved layout state is written to disk
+    #[serde(default)]
+    pub persistence: PersistenceSettings,
+
     /// Commands to run on startup (e.g., for subscribing to events)
     #[serde(default)]
     pub run_on_start: Vec<String>,
@@ -444,6 +562,64 @@ pub struct WindowSnappingSettings {
     pub drag_swap_fraction: f64,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct HookSettings {
+    /// Run after the active workspace on a display changes
+    #[serde(default)]
+    pub on_workspace_switch: Option<HookCommand>,
+    /// Run after a different window becomes focused
+    #[serde(default)]
+    pub on_focus_change: Option<HookCommand>,
+    /// Run after a new window is added to the layout
+    #[serde(default)]
+    pub on_window_added: Option<HookCommand>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HookCommand {
+    /// Passed to `sh -c`
+    pub command: String,
+    /// Events arriving within this many milliseconds of the first are
+    /// coalesced and the hook runs once with the latest. 0 runs it for every event.
+    #[serde(default = "default_hook_debounce_ms")]
+    pub debounce_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PersistenceSettings {
+    /// Encoding used when saving; files in any encoding can be loaded
+    #[serde(default)]
+    pub format: PersistenceFormat,
+    /// Save the layout at most this often (in seconds) while it changes, so
+    /// `--restore` can recover it after a crash. 0 disables autosaving.
+    #[serde(default = "default_autosave_secs")]
+    pub autosave_secs: f64,
+}
+
+impl Default for PersistenceSettings {
+    fn default() -> Self {
+        Self {
+            format: PersistenceFormat::default(),
+            autosave_secs: default_autosave_secs(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PersistenceFormat {
+    /// Human-readable RON
+    #[default]
+    Ron,
+    /// JSON, faster to write and read than RON for large states
+    Json,
+    /// Compact binary encoding, the smallest and fastest for large states
+    Binary,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum MenuBarDisplayMode {
@@ -509,16 +685,41 @@ pub struct MissionControlSettings {
     pub fade_enabled: bool,
     #[serde(default = "default_mission_control_fade_duration_ms")]
     pub fade_duration_ms: f64,
+    /// Restore the last selection of each mode when mission control reopens,
+    /// as long as the workspace or window is still present.
+    #[serde(default = "no")]
+    pub remember_selection: bool,
+    /// How far (in points) outside a tile a click or hover still picks it,
+    /// so the gaps between tiles do not dismiss the overlay.
+    #[serde(default = "default_mission_control_click_slop")]
+    pub click_slop: f64,
+    /// Whether keyboard navigation past the first or last tile wraps around
+    /// to the other end instead of stopping there.
+    #[serde(default = "yes")]
+    pub wrap_navigation: bool,
+    /// Most window previews kept in memory. The least recently shown are
+    /// dropped beyond it and captured again when needed; previews of tiles
+    /// on screen are always kept. 0 keeps every preview.
+    #[serde(default)]
+    pub preview_cache_limit: usize,
 }
 
 fn default_mission_control_fade_duration_ms() -> f64 {
     180.0
 }
 
+fn default_mission_control_click_slop() -> f64 {
+    24.0
+}
+
 fn default_drag_swap_fraction() -> f64 {
     0.3
 }
 
+fn default_hook_debounce_ms() -> u64 {
+    50
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
 #[serde(deny_unknown_fields)]
 pub struct WindowBorderSettings {
@@ -673,7 +874,7 @@ impl StackLineSettings {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct LayoutSettings {
     /// Layout mode: "traditional" (i3/sway style containers)
@@ -688,6 +889,88 @@ pub struct LayoutSettings {
     /// Dwindle layout settings (only applies when mode = "dwindle")
     #[serde(default)]
     pub dwindle: DwindleSettings,
+    /// BSP layout settings (only applies when mode = "bsp")
+    #[serde(default)]
+    pub bsp: BspSettings,
+    /// Container kind for the root of newly created workspace layouts (only
+    /// applies when mode = "traditional")
+    #[serde(default)]
+    pub default_root_kind: crate::layout_engine::LayoutKind,
+    /// Rebalance every layout after a window is removed. When false, the
+    /// removed window's share goes to its siblings in proportion to their
+    /// sizes, so manually tuned sizes keep their ratio.
+    #[serde(default = "yes")]
+    pub rebalance_on_remove: bool,
+    /// Insert windows an app reveals next to that app's windows already in
+    /// the layout instead of after the selection (only applies when mode =
+    /// "traditional")
+    #[serde(default)]
+    pub insert_near_app_windows: bool,
+    /// Stack the root of a layout once it holds more tiled windows than this,
+    /// unless its root kind was changed from `default_root_kind`. 0 turns it
+    /// off (only applies when mode = "traditional")
+    #[serde(default)]
+    pub auto_stack_threshold: usize,
+    /// Collapse single-child containers after a command leaves windows
+    /// nested deeper than this. Unset leaves nesting alone (only applies when
+    /// mode = "traditional")
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// Wrap every layout event and command in a debug-level tracing span and
+    /// log how long it took
+    #[serde(default)]
+    pub trace_timings: bool,
+    /// Recording of recent layout events and commands for bug reports
+    #[serde(default)]
+    pub journal: JournalSettings,
+}
+
+impl Default for LayoutSettings {
+    fn default() -> Self {
+        Self {
+            mode: LayoutMode::default(),
+            stack: StackSettings::default(),
+            gaps: GapSettings::default(),
+            dwindle: DwindleSettings::default(),
+            bsp: BspSettings::default(),
+            default_root_kind: crate::layout_engine::LayoutKind::default(),
+            rebalance_on_remove: true,
+            insert_near_app_windows: false,
+            auto_stack_threshold: 0,
+            max_depth: None,
+            trace_timings: false,
+            journal: JournalSettings::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct JournalSettings {
+    /// Keep a journal of each space's recent layout events and commands,
+    /// printed by `rift-cli query journal`
+    #[serde(default = "no")]
+    pub enabled: bool,
+    /// How many entries the journal keeps before dropping the oldest
+    #[serde(default = "default_journal_capacity")]
+    pub capacity: usize,
+    /// Leave window titles out of journaled events
+    #[serde(default = "no")]
+    pub scrub_titles: bool,
+}
+
+impl Default for JournalSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: default_journal_capacity(),
+            scrub_titles: false,
+        }
+    }
+}
+
+fn default_journal_capacity() -> usize {
+    256
 }
 
 /// Layout mode enum
@@ -745,6 +1028,16 @@ fn default_dwindle_split_ratio() -> f32 {
     0.5
 }
 
+/// BSP layout settings
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct BspSettings {
+    /// Rebalance the splits around every inserted or removed window so the
+    /// windows there get equal areas
+    #[serde(default)]
+    pub auto_balance: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 #[serde(rename_all = "snake_case")]
 pub enum StackDefaultOrientation {
@@ -754,6 +1047,18 @@ pub enum StackDefaultOrientation {
     Vertical,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StackToggleMode {
+    /// Stack the selection's container unless it is already stacked.
+    StackFirst,
+    /// Unstack the nearest stacked ancestor before trying to stack.
+    #[default]
+    UnstackFirst,
+    /// Cycle the container through horizontal, horizontal stack and vertical stack.
+    Cycle,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct StackSettings {
@@ -771,10 +1076,41 @@ pub struct StackSettings {
     /// - "horizontal"/"vertical": explicitly use a specific orientation
     #[serde(default = "default_stack_orientation")]
     pub default_orientation: StackDefaultOrientation,
+
+    /// How many pixels the focused window in a stack grows by so it "pops"
+    /// out of the stack. Set to 0 to disable the effect entirely.
+    #[serde(default = "default_stack_focus_pop_px")]
+    pub focus_pop_px: f64,
+
+    /// Order in which `toggle_stack` tries stacking and unstacking.
+    /// Options:
+    /// - "unstack_first" (default): unstack if inside a stack, otherwise stack
+    /// - "stack_first": stack the container unless it is already stacked
+    /// - "cycle": horizontal -> horizontal stack -> vertical stack -> horizontal
+    #[serde(default)]
+    pub toggle_mode: StackToggleMode,
+
+    /// When focus moves into a stack, raise every member of it rather than
+    /// only the revealed one, so members peeking out from under the focused
+    /// window never show stale content.
+    #[serde(default)]
+    pub raise_all_on_focus: bool,
+}
+
+/// How inner gaps give way when tiles get small.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GapPolicy {
+    /// Inner gaps are always the configured size, however small tiles get.
+    #[default]
+    Fixed,
+    /// Inner gaps shrink, down to nothing, so evenly shared tiles stay at
+    /// least `min_tile_px` long.
+    Adaptive,
 }
 
 /// Gap configuration for window spacing
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct GapSettings {
     /// Outer gaps (space between windows and screen edges)
@@ -786,11 +1122,43 @@ pub struct GapSettings {
     /// Display-specific gap overrides keyed by display UUID
     #[serde(default)]
     pub per_display: HashMap<String, GapOverride>,
+    /// Drop all gaps and the stack line reservation while a workspace holds
+    /// a single tiled window, giving it the whole screen
+    #[serde(default)]
+    pub smart: bool,
+    /// Whether smart gaps drop the outer gaps too. When false, a lone window
+    /// keeps them and only fills the space inside them
+    #[serde(default = "yes")]
+    pub smart_outer: bool,
+    /// Whether inner gaps stay fixed or shrink to keep tiles at `min_tile_px`
+    #[serde(default)]
+    pub policy: GapPolicy,
+    /// Smallest tile length, in pixels, the adaptive policy keeps
+    #[serde(default = "default_min_tile_px")]
+    pub min_tile_px: f64,
 }
 
-/// Outer gap configuration (space between windows and screen edges)
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
-#[serde(deny_unknown_fields)]
+fn default_min_tile_px() -> f64 {
+    100.0
+}
+
+impl Default for GapSettings {
+    fn default() -> Self {
+        Self {
+            outer: OuterGaps::default(),
+            inner: InnerGaps::default(),
+            per_display: HashMap::default(),
+            smart: false,
+            smart_outer: true,
+            policy: GapPolicy::default(),
+            min_tile_px: default_min_tile_px(),
+        }
+    }
+}
+
+/// Outer gap configuration (space between windows and screen edges).
+/// Accepts either a table of edges or a single number applied to all four.
+#[derive(Serialize, Debug, PartialEq, Clone, Default)]
 pub struct OuterGaps {
     /// Gap at the top of the screen
     #[serde(default)]
@@ -806,6 +1174,70 @@ pub struct OuterGaps {
     pub right: f64,
 }
 
+impl<'de> serde::Deserialize<'de> for OuterGaps {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct OuterGapsVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for OuterGapsVisitor {
+            type Value = OuterGaps;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str(
+                    "a number applied to every edge, or an object with top, left, bottom, right fields",
+                )
+            }
+
+            fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(OuterGaps::uniform(value))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(OuterGaps::uniform(value as f64))
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(OuterGaps::uniform(value as f64))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut gaps = OuterGaps::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "top" => gaps.top = map.next_value()?,
+                        "left" => gaps.left = map.next_value()?,
+                        "bottom" => gaps.bottom = map.next_value()?,
+                        "right" => gaps.right = map.next_value()?,
+                        _ => {
+                            return Err(serde::de::Error::unknown_field(
+                                &key,
+                                &["top", "left", "bottom", "right"],
+                            ));
+                        }
+                    }
+                }
+                Ok(gaps)
+            }
+        }
+
+        deserializer.deserialize_any(OuterGapsVisitor)
+    }
+}
+
 /// Inner gap configuration (space between windows)
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
 #[serde(deny_unknown_fields)]
@@ -835,6 +1267,9 @@ impl Default for StackSettings {
         Self {
             stack_offset: default_stack_offset(),
             default_orientation: default_stack_orientation(),
+            focus_pop_px: default_stack_focus_pop_px(),
+            toggle_mode: StackToggleMode::default(),
+            raise_all_on_focus: false,
         }
     }
 }
@@ -866,6 +1301,13 @@ impl Settings {
             ));
         }
 
+        if self.persistence.autosave_secs < 0.0 {
+            issues.push(format!(
+                "persistence.autosave_secs must be non-negative, got {}",
+                self.persistence.autosave_secs
+            ));
+        }
+
         issues
     }
 }
@@ -893,6 +1335,13 @@ impl StackSettings {
             ));
         }
 
+        if self.focus_pop_px < 0.0 {
+            issues.push(format!(
+                "focus_pop_px must be non-negative, got {}",
+                self.focus_pop_px
+            ));
+        }
+
         issues
     }
 }
@@ -907,6 +1356,13 @@ impl GapSettings {
         // Validate inner gaps
         issues.extend(self.inner.validate());
 
+        if self.min_tile_px < 0.0 {
+            issues.push(format!(
+                "min_tile_px must be non-negative, got {}",
+                self.min_tile_px
+            ));
+        }
+
         for (uuid, overrides) in &self.per_display {
             if let Some(outer) = &overrides.outer {
                 for issue in outer.validate() {
@@ -928,6 +1384,10 @@ impl GapSettings {
             outer: self.outer.clone(),
             inner: self.inner.clone(),
             per_display: HashMap::default(),
+            smart: self.smart,
+            smart_outer: self.smart_outer,
+            policy: self.policy,
+            min_tile_px: self.min_tile_px,
         };
         if let Some(uuid) = display_uuid
             && let Some(overrides) = self.per_display.get(uuid)
@@ -941,9 +1401,35 @@ impl GapSettings {
         }
         resolved
     }
+
+    /// The inner gap between `count` tiles sharing `len` pixels along one
+    /// axis. Under the adaptive policy it shrinks so that even shares stay at
+    /// least `min_tile_px` long.
+    pub fn inner_gap(&self, horizontal: bool, len: f64, count: usize) -> f64 {
+        let gap = if horizontal {
+            self.inner.horizontal
+        } else {
+            self.inner.vertical
+        };
+        if self.policy == GapPolicy::Fixed || count < 2 {
+            return gap;
+        }
+        let room = (len - self.min_tile_px * count as f64) / (count - 1) as f64;
+        gap.min(room.max(0.0))
+    }
 }
 
 impl OuterGaps {
+    /// The same gap on every edge.
+    pub fn uniform(gap: f64) -> Self {
+        Self {
+            top: gap,
+            left: gap,
+            bottom: gap,
+            right: gap,
+        }
+    }
+
     /// Validates outer gap configuration values and returns a list of issues found.
     pub fn validate(&self) -> Vec<String> {
         let mut issues = Vec::new();
@@ -1005,6 +1491,10 @@ fn default_stack_offset() -> f64 {
     40.0
 }
 
+fn default_stack_focus_pop_px() -> f64 {
+    10.0
+}
+
 fn default_stack_orientation() -> StackDefaultOrientation {
     StackDefaultOrientation::Perpendicular
 }
@@ -1022,6 +1512,10 @@ fn no() -> bool {
     false
 }
 
+fn default_autosave_secs() -> f64 {
+    5.0
+}
+
 fn default_workspace_count() -> usize {
     4
 }
@@ -1472,6 +1966,51 @@ mod tests {
         assert!(issues.iter().any(|i| i.contains("default_workspace")));
     }
 
+    #[test]
+    fn test_workspace_settings_per_display_override() {
+        let mut settings = VirtualWorkspaceSettings::default();
+        settings.per_display.insert(
+            "display-a".to_string(),
+            WorkspaceOverride {
+                default_workspace_count: Some(9),
+                workspace_names: Some(vec!["main".to_string()]),
+                ..Default::default()
+            },
+        );
+
+        let resolved = settings.effective_for_display(Some("display-a"));
+        assert_eq!(resolved.default_workspace_count, 9);
+        assert_eq!(resolved.workspace_names, vec!["main".to_string()]);
+        assert!(resolved.per_display.is_empty());
+
+        let fallback = settings.effective_for_display(Some("display-b"));
+        assert_eq!(
+            fallback.default_workspace_count,
+            settings.default_workspace_count
+        );
+        assert_eq!(fallback.workspace_names, settings.workspace_names);
+        assert!(settings.validate().is_empty());
+    }
+
+    #[test]
+    fn test_workspace_settings_validation_per_display_default_out_of_bounds() {
+        let mut settings = VirtualWorkspaceSettings::default();
+        settings.per_display.insert(
+            "display-a".to_string(),
+            WorkspaceOverride {
+                default_workspace_count: Some(2),
+                ..Default::default()
+            },
+        );
+        settings.workspace_names.clear();
+        settings.default_workspace = 3;
+        settings.default_workspace_count = 4;
+
+        let issues = settings.validate();
+        assert_eq!(issues.len(), 1, "{issues:?}");
+        assert!(issues[0].starts_with("per_display.\"display-a\": default_workspace (3)"));
+    }
+
     #[test]
     fn test_app_rule_validation_no_identifiers() {
         let mut settings = VirtualWorkspaceSettings::default();
@@ -1485,6 +2024,8 @@ mod tests {
             title_substring: None,
             ax_role: None,
             ax_subrole: None,
+            opacity: None,
+            always_on_top: false,
         });
         let issues = settings.validate();
         assert!(issues.iter().any(|i| i.contains("no app_id, app_name")));
@@ -1503,6 +2044,8 @@ mod tests {
             title_substring: None,
             ax_role: None,
             ax_subrole: None,
+            opacity: None,
+            always_on_top: false,
         });
         let issues = settings.validate();
         assert!(issues.iter().any(|i| i.contains("suspicious app_id")));
@@ -1521,6 +2064,8 @@ mod tests {
             title_substring: None,
             ax_role: None,
             ax_subrole: None,
+            opacity: None,
+            always_on_top: false,
         });
         let issues = settings.validate();
         assert!(issues.iter().any(|i| i.contains("references workspace 10")));
@@ -1539,11 +2084,33 @@ mod tests {
             title_substring: None,
             ax_role: None,
             ax_subrole: None,
+            opacity: None,
+            always_on_top: false,
         });
         let issues = settings.validate();
         assert!(issues.iter().any(|i| i.contains("empty title_regex")));
     }
 
+    #[test]
+    fn test_app_rule_validation_opacity_out_of_range() {
+        let mut settings = VirtualWorkspaceSettings::default();
+        settings.app_rules.push(AppWorkspaceRule {
+            app_id: Some("com.example.app".to_string()),
+            workspace: None,
+            floating: true,
+            manage: true,
+            app_name: None,
+            title_regex: None,
+            title_substring: None,
+            ax_role: None,
+            ax_subrole: None,
+            opacity: Some(1.5),
+            always_on_top: true,
+        });
+        let issues = settings.validate();
+        assert!(issues.iter().any(|i| i.contains("opacity 1.5 outside")));
+    }
+
     #[test]
     fn test_app_rule_validation_empty_title_substring() {
         let mut settings = VirtualWorkspaceSettings::default();
@@ -1557,6 +2124,8 @@ mod tests {
             title_substring: Some("".to_string()),
             ax_role: None,
             ax_subrole: None,
+            opacity: None,
+            always_on_top: false,
         });
         let issues = settings.validate();
         assert!(issues.iter().any(|i| i.contains("empty title_substring")));
@@ -1594,6 +2163,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_settings_validation_negative_autosave_interval() {
+        let mut settings = Settings::default();
+        settings.persistence.autosave_secs = -1.0;
+        let issues = settings.validate();
+        assert!(issues.iter().any(|i| i.contains("autosave_secs must be non-negative")));
+    }
+
     #[test]
     fn test_stack_settings_validation_negative_offset() {
         let stack = StackSettings {
@@ -1604,6 +2181,90 @@ mod tests {
         assert!(issues.iter().any(|i| i.contains("stack_offset must be non-negative")));
     }
 
+    #[test]
+    fn test_stack_settings_validation_negative_focus_pop() {
+        let stack = StackSettings {
+            focus_pop_px: -1.0,
+            ..Default::default()
+        };
+        let issues = stack.validate();
+        assert!(issues.iter().any(|i| i.contains("focus_pop_px must be non-negative")));
+    }
+
+    #[test]
+    fn test_stack_toggle_mode_parsing() {
+        let stack: StackSettings = toml::from_str("toggle_mode = \"cycle\"").unwrap();
+        assert_eq!(stack.toggle_mode, StackToggleMode::Cycle);
+
+        let stack: StackSettings = toml::from_str("toggle_mode = \"stack_first\"").unwrap();
+        assert_eq!(stack.toggle_mode, StackToggleMode::StackFirst);
+
+        let stack: StackSettings = toml::from_str("").unwrap();
+        assert_eq!(stack.toggle_mode, StackToggleMode::UnstackFirst);
+    }
+
+    #[test]
+    fn test_on_empty_parsing() {
+        for (value, expected) in [
+            ("stay", OnEmptyWorkspace::Stay),
+            ("switch_to_previous", OnEmptyWorkspace::SwitchToPrevious),
+            ("switch_previous", OnEmptyWorkspace::SwitchToPrevious),
+            ("switch_last_active", OnEmptyWorkspace::SwitchToPrevious),
+            (
+                "switch_to_nearest_nonempty",
+                OnEmptyWorkspace::SwitchToNearestNonempty,
+            ),
+        ] {
+            let settings: VirtualWorkspaceSettings =
+                toml::from_str(&format!("on_empty = \"{value}\"")).unwrap();
+            assert_eq!(settings.on_empty, expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn test_outer_gaps_single_value_expands_to_all_edges() {
+        let gaps: GapSettings = toml::from_str("outer = 12").unwrap();
+        assert_eq!(gaps.outer, OuterGaps::uniform(12.0));
+
+        let gaps: GapSettings = toml::from_str("outer = 7.5").unwrap();
+        assert_eq!(gaps.outer, OuterGaps::uniform(7.5));
+    }
+
+    #[test]
+    fn test_outer_gaps_per_edge_parsing() {
+        let gaps: GapSettings =
+            toml::from_str("[outer]\ntop = 10\nright = 20\nbottom = 30\nleft = 40").unwrap();
+        assert_eq!(
+            gaps.outer,
+            OuterGaps {
+                top: 10.0,
+                left: 40.0,
+                bottom: 30.0,
+                right: 20.0,
+            }
+        );
+
+        let gaps: GapSettings = toml::from_str("[outer]\ntop = 5").unwrap();
+        assert_eq!(gaps.outer.top, 5.0);
+        assert_eq!(gaps.outer.left, 0.0);
+
+        assert!(toml::from_str::<GapSettings>("[outer]\nmiddle = 5").is_err());
+    }
+
+    #[test]
+    fn test_outer_gaps_single_value_per_display_override() {
+        let gaps: GapSettings = toml::from_str(
+            "[outer]\ntop = 10\nright = 20\nbottom = 30\nleft = 40\n\n[per_display.\"uuid-a\"]\nouter = 4",
+        )
+        .unwrap();
+        assert_eq!(
+            gaps.effective_for_display(Some("uuid-a")).outer,
+            OuterGaps::uniform(4.0)
+        );
+        assert_eq!(gaps.effective_for_display(Some("uuid-b")).outer.right, 20.0);
+        assert_eq!(gaps.effective_for_display(None).outer.left, 40.0);
+    }
+
     #[test]
     fn test_outer_gaps_validation_negative_values() {
         let gaps = OuterGaps {
@@ -1645,6 +2306,8 @@ mod tests {
             },
         );
         gap_settings.per_display = overrides;
+        gap_settings.smart = true;
+        gap_settings.smart_outer = false;
 
         let effective = gap_settings.effective_for_display(Some("display-uuid"));
         assert_eq!(10.0, effective.outer.top);
@@ -1653,6 +2316,8 @@ mod tests {
         assert_eq!(40.0, effective.outer.right);
         assert_eq!(5.0, effective.inner.horizontal);
         assert_eq!(8.0, effective.inner.vertical);
+        assert!(effective.smart);
+        assert!(!effective.smart_outer);
     }
 
     #[test]
@@ -1667,6 +2332,24 @@ mod tests {
         assert_eq!(0.0, effective.inner.vertical);
     }
 
+    #[test]
+    fn test_adaptive_gap_policy_shrinks_inner_gaps_to_the_floor() {
+        let gaps: GapSettings =
+            toml::from_str("policy = \"adaptive\"\nmin_tile_px = 80\n[inner]\nhorizontal = 50")
+                .unwrap();
+        assert_eq!(gaps.policy, GapPolicy::Adaptive);
+        assert_eq!(gaps.inner_gap(true, 500.0, 5), 25.0);
+        assert_eq!(gaps.inner_gap(true, 300.0, 5), 0.0);
+        assert_eq!(gaps.inner_gap(true, 2000.0, 5), 50.0);
+        assert_eq!(gaps.inner_gap(true, 50.0, 1), 50.0);
+
+        let fixed = GapSettings {
+            policy: GapPolicy::Fixed,
+            ..gaps
+        };
+        assert_eq!(fixed.inner_gap(true, 500.0, 5), 50.0);
+    }
+
     #[test]
     fn test_config_validate_empty_is_valid() {
         let config = Config::default_config()
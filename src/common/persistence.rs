@@ -0,0 +1,468 @@
+//! Encodings for saved window manager state.
+//!
+//! State is written as RON, as JSON or in a compact binary form. Binary
+//! files start with [`MAGIC`] and the payload length; a JSON save is an
+//! object, so it starts with `{`, while a RON save starts with the `(` of a
+//! struct. That is how [`decode`] tells them apart: a file loads whichever
+//! format it was saved in, so `persistence.format` can be changed without
+//! losing saved state.
+//!
+//! The binary payload is MessagePack. Its values are tagged with their type,
+//! so internally tagged enums and [`MapEntries`], which need
+//! `deserialize_any`, round-trip as they do in RON and JSON.
+//!
+//! JSON object keys must be strings, so maps keyed by ids, tuples or sizes
+//! are saved through [`MapEntries`] as lists of `(key, value)` entries.
+//!
+//! Files are replaced with [`write_atomically`], which keeps the previous
+//! save at [`backup_path`] for [`read_with_backup`] to fall back on.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::hash::{BuildHasher, Hash};
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, bail};
+use serde::de::{DeserializeOwned, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeSeq;
+use serde::{Deserializer, Serialize, Serializer};
+use serde_with::de::DeserializeAsWrap;
+use serde_with::ser::SerializeAsWrap;
+use serde_with::{DeserializeAs, Same, SerializeAs};
+use tracing::warn;
+
+pub use crate::common::config::PersistenceFormat;
+
+/// Leads every binary file. The last byte is the encoding version; version 1
+/// was an earlier encoding that is no longer read.
+pub const MAGIC: &[u8; 8] = b"RIFTBIN\x02";
+const HEADER_LEN: usize = MAGIC.len() + size_of::<u64>();
+
+pub trait StateCodec {
+    fn encode<T: Serialize>(&self, value: &T) -> anyhow::Result<Vec<u8>>;
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T>;
+}
+
+pub struct RonCodec;
+
+impl StateCodec for RonCodec {
+    fn encode<T: Serialize>(&self, value: &T) -> anyhow::Result<Vec<u8>> {
+        Ok(ron::ser::to_string(value)?.into_bytes())
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T> {
+        let text = std::str::from_utf8(bytes).context("saved state is not valid UTF-8")?;
+        Ok(ron::from_str(text)?)
+    }
+}
+
+pub struct JsonCodec;
+
+impl StateCodec for JsonCodec {
+    fn encode<T: Serialize>(&self, value: &T) -> anyhow::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(value)?)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+pub struct BinaryCodec;
+
+impl StateCodec for BinaryCodec {
+    fn encode<T: Serialize>(&self, value: &T) -> anyhow::Result<Vec<u8>> {
+        let payload = rmp_serde::to_vec_named(value)?;
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        Ok(bytes)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T> {
+        if bytes.len() < HEADER_LEN || !bytes.starts_with(&MAGIC[..MAGIC.len() - 1]) {
+            bail!("saved state has no binary header");
+        }
+        let version = bytes[MAGIC.len() - 1];
+        if version != MAGIC[MAGIC.len() - 1] {
+            bail!("saved state uses unsupported binary version {version}");
+        }
+        let len = u64::from_le_bytes(bytes[MAGIC.len()..HEADER_LEN].try_into()?);
+        let payload = &bytes[HEADER_LEN..];
+        if payload.len() as u64 != len {
+            bail!(
+                "saved state is truncated or padded: header says {len} bytes, found {}",
+                payload.len()
+            );
+        }
+        Ok(rmp_serde::from_slice(payload)?)
+    }
+}
+
+impl PersistenceFormat {
+    /// The format `bytes` were saved in.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&MAGIC[..MAGIC.len() - 1]) {
+            return PersistenceFormat::Binary;
+        }
+        match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{') => PersistenceFormat::Json,
+            _ => PersistenceFormat::Ron,
+        }
+    }
+}
+
+pub fn encode<T: Serialize>(format: PersistenceFormat, value: &T) -> anyhow::Result<Vec<u8>> {
+    match format {
+        PersistenceFormat::Ron => RonCodec.encode(value),
+        PersistenceFormat::Json => JsonCodec.encode(value),
+        PersistenceFormat::Binary => BinaryCodec.encode(value),
+    }
+}
+
+/// Decodes state saved in any format.
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> anyhow::Result<T> {
+    match PersistenceFormat::detect(bytes) {
+        PersistenceFormat::Ron => RonCodec.decode(bytes),
+        PersistenceFormat::Json => JsonCodec.decode(bytes),
+        PersistenceFormat::Binary => BinaryCodec.decode(bytes),
+    }
+}
+
+/// Saves a map as a list of `(key, value)` entries, so that maps whose keys
+/// are not strings can be written as JSON. `K` and `V` say how keys and
+/// values are themselves saved, as for any `serde_as` adapter.
+///
+/// Maps saved as maps, as RON state from before this was introduced is, load
+/// too.
+pub struct MapEntries<K = Same, V = Same>(PhantomData<(K, V)>);
+
+fn serialize_entries<'a, K, V, KAs, VAs, S>(
+    entries: impl ExactSizeIterator<Item = (&'a K, &'a V)>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    K: 'a,
+    V: 'a,
+    KAs: SerializeAs<K>,
+    VAs: SerializeAs<V>,
+    S: Serializer,
+{
+    let mut seq = serializer.serialize_seq(Some(entries.len()))?;
+    for (key, value) in entries {
+        seq.serialize_element(&(
+            SerializeAsWrap::<K, KAs>::new(key),
+            SerializeAsWrap::<V, VAs>::new(value),
+        ))?;
+    }
+    seq.end()
+}
+
+struct EntriesVisitor<M, K, V, KAs, VAs>(PhantomData<(M, K, V, KAs, VAs)>);
+
+impl<'de, M, K, V, KAs, VAs> Visitor<'de> for EntriesVisitor<M, K, V, KAs, VAs>
+where
+    M: FromIterator<(K, V)>,
+    KAs: DeserializeAs<'de, K>,
+    VAs: DeserializeAs<'de, V>,
+{
+    type Value = M;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map or a list of (key, value) entries")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<M, A::Error> {
+        std::iter::from_fn(|| {
+            seq.next_element::<(DeserializeAsWrap<K, KAs>, DeserializeAsWrap<V, VAs>)>()
+                .transpose()
+        })
+        .map(|entry| entry.map(|(key, value)| (key.into_inner(), value.into_inner())))
+        .collect()
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<M, A::Error> {
+        std::iter::from_fn(|| {
+            map.next_entry::<DeserializeAsWrap<K, KAs>, DeserializeAsWrap<V, VAs>>()
+                .transpose()
+        })
+        .map(|entry| entry.map(|(key, value)| (key.into_inner(), value.into_inner())))
+        .collect()
+    }
+}
+
+impl<K, V, KAs, VAs, H> SerializeAs<std::collections::HashMap<K, V, H>> for MapEntries<KAs, VAs>
+where
+    KAs: SerializeAs<K>,
+    VAs: SerializeAs<V>,
+{
+    fn serialize_as<S: Serializer>(
+        source: &std::collections::HashMap<K, V, H>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serialize_entries::<K, V, KAs, VAs, S>(source.iter(), serializer)
+    }
+}
+
+impl<'de, K, V, KAs, VAs, H> DeserializeAs<'de, std::collections::HashMap<K, V, H>>
+    for MapEntries<KAs, VAs>
+where
+    K: Eq + Hash,
+    H: BuildHasher + Default,
+    KAs: DeserializeAs<'de, K>,
+    VAs: DeserializeAs<'de, V>,
+{
+    fn deserialize_as<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<std::collections::HashMap<K, V, H>, D::Error> {
+        deserializer.deserialize_any(EntriesVisitor::<_, K, V, KAs, VAs>(PhantomData))
+    }
+}
+
+impl<K, V, KAs, VAs> SerializeAs<BTreeMap<K, V>> for MapEntries<KAs, VAs>
+where
+    KAs: SerializeAs<K>,
+    VAs: SerializeAs<V>,
+{
+    fn serialize_as<S: Serializer>(
+        source: &BTreeMap<K, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serialize_entries::<K, V, KAs, VAs, S>(source.iter(), serializer)
+    }
+}
+
+impl<'de, K, V, KAs, VAs> DeserializeAs<'de, BTreeMap<K, V>> for MapEntries<KAs, VAs>
+where
+    K: Ord,
+    KAs: DeserializeAs<'de, K>,
+    VAs: DeserializeAs<'de, V>,
+{
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<K, V>, D::Error> {
+        deserializer.deserialize_any(EntriesVisitor::<_, K, V, KAs, VAs>(PhantomData))
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Where the save `path` replaced last is kept.
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+/// Replaces `path` with `bytes` so that readers see the old or the new
+/// contents but never a partial file: the bytes go to a temporary file in the
+/// same directory, which is then renamed over `path`. The replaced file is
+/// moved to [`backup_path`].
+pub fn write_atomically(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp = with_suffix(path, ".tmp");
+    let mut file = fs::File::create(&temp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+
+    match fs::rename(path, backup_path(path)) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err).context("could not keep a backup of the previous save"),
+    }
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Decodes the state saved at `path`, or the backup of the previous save if
+/// `path` is missing or does not decode.
+pub fn read_with_backup<T: DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let read = |path: &Path| -> anyhow::Result<T> { decode(&fs::read(path)?) };
+    match read(path) {
+        Ok(value) => Ok(value),
+        Err(err) => {
+            let backup = backup_path(path);
+            let value = read(&backup).map_err(|_| err)?;
+            warn!(
+                ?path,
+                ?backup,
+                "Saved state was unreadable; loaded the previous save"
+            );
+            Ok(value)
+        }
+    }
+}
+
+/// Limits how often changing state is saved: once marked dirty, a save is
+/// due right away and then at most once per interval.
+#[derive(Debug, Clone)]
+pub struct SaveDebounce {
+    interval: Duration,
+    dirty: bool,
+    last_save: Option<Instant>,
+}
+
+impl SaveDebounce {
+    pub fn new(interval: Duration) -> Self {
+        SaveDebounce {
+            interval,
+            dirty: false,
+            last_save: None,
+        }
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Whether unsaved changes should be written at `now`.
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.dirty
+            && self
+                .last_save
+                .is_none_or(|last| now.saturating_duration_since(last) >= self.interval)
+    }
+
+    pub fn saved(&mut self, now: Instant) {
+        self.dirty = false;
+        self.last_save = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_with::serde_as;
+
+    use super::*;
+    use crate::common::collections::HashMap;
+
+    #[serde_as]
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct State {
+        names: Vec<String>,
+        sizes: HashMap<u32, (f64, f64)>,
+        #[serde_as(as = "MapEntries")]
+        pairs: BTreeMap<(u32, u32), String>,
+    }
+
+    fn state() -> State {
+        State {
+            names: vec!["one".to_string(), "two".to_string()],
+            sizes: [(1, (1.0, 2.0)), (2, (0.5, 0.25))].into_iter().collect(),
+            pairs: [((1, 2), "a".to_string()), ((3, 4), "b".to_string())].into_iter().collect(),
+        }
+    }
+
+    const FORMATS: [PersistenceFormat; 3] = [
+        PersistenceFormat::Ron,
+        PersistenceFormat::Json,
+        PersistenceFormat::Binary,
+    ];
+
+    #[test]
+    fn decode_detects_the_format() {
+        for format in FORMATS {
+            let bytes = encode(format, &state()).unwrap();
+            assert_eq!(PersistenceFormat::detect(&bytes), format);
+            assert_eq!(decode::<State>(&bytes).unwrap(), state());
+        }
+    }
+
+    #[test]
+    fn map_entries_load_maps_saved_as_maps() {
+        let bytes = b"(names: [], sizes: {}, pairs: {(1, 2): \"a\", (3, 4): \"b\"})";
+        let decoded = decode::<State>(bytes).unwrap();
+        assert_eq!(decoded.pairs, state().pairs);
+    }
+
+    #[test]
+    fn binary_header_is_checked() {
+        let bytes = encode(PersistenceFormat::Binary, &state()).unwrap();
+        assert!(bytes.starts_with(MAGIC));
+
+        let err = decode::<State>(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert!(err.to_string().contains("truncated"), "{err}");
+
+        let mut padded = bytes.clone();
+        padded.push(0);
+        assert!(decode::<State>(&padded).is_err());
+
+        let mut old_version = bytes;
+        old_version[MAGIC.len() - 1] = 1;
+        let err = decode::<State>(&old_version).unwrap_err();
+        assert!(err.to_string().contains("version 1"), "{err}");
+    }
+
+    #[test]
+    fn atomic_writes_keep_the_previous_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state");
+        let mut first = state();
+        first.names.push("first".to_string());
+
+        write_atomically(&path, &encode(PersistenceFormat::Ron, &first).unwrap()).unwrap();
+        assert!(!backup_path(&path).exists());
+        write_atomically(&path, &encode(PersistenceFormat::Ron, &state()).unwrap()).unwrap();
+
+        assert_eq!(read_with_backup::<State>(&path).unwrap(), state());
+        assert_eq!(
+            decode::<State>(&fs::read(backup_path(&path)).unwrap()).unwrap(),
+            first
+        );
+        assert!(!with_suffix(&path, ".tmp").exists());
+    }
+
+    #[test]
+    fn unreadable_state_falls_back_to_the_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state");
+        write_atomically(&path, &encode(PersistenceFormat::Json, &state()).unwrap()).unwrap();
+        write_atomically(&path, &encode(PersistenceFormat::Json, &state()).unwrap()).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        assert_eq!(read_with_backup::<State>(&path).unwrap(), state());
+
+        fs::write(backup_path(&path), b"garbage").unwrap();
+        assert!(read_with_backup::<State>(&path).is_err());
+
+        let bytes = encode(PersistenceFormat::Ron, &state()).unwrap();
+        fs::write(backup_path(&path), &bytes).unwrap();
+        fs::write(&path, b"RIFTBIN\x02").unwrap();
+        assert_eq!(read_with_backup::<State>(&path).unwrap(), state());
+    }
+
+    #[test]
+    fn debounce_saves_at_most_once_per_interval() {
+        let start = Instant::now();
+        let interval = Duration::from_secs(5);
+        let mut debounce = SaveDebounce::new(interval);
+        assert!(!debounce.is_due(start));
+
+        debounce.mark_dirty();
+        assert!(debounce.is_due(start));
+        debounce.saved(start);
+
+        debounce.mark_dirty();
+        assert!(!debounce.is_due(start + Duration::from_secs(1)));
+        assert!(debounce.is_due(start + interval));
+    }
+}
@@ -2,9 +2,11 @@ use std::ffi::{CStr, c_char};
 use std::time::Duration;
 
 use r#continue::continuation;
+use objc2_core_foundation::{CGPoint, CGRect, CGSize};
 use tracing::{debug, error, info};
 
 pub mod cli_exec;
+pub mod hooks;
 pub mod protocol;
 pub mod subscriptions;
 
@@ -125,6 +127,8 @@ impl MachHandler {
             Event::QueryApplications(response) => std::mem::forget(response),
             Event::QueryLayoutState { response, .. } => std::mem::forget(response),
             Event::QueryMetrics(response) => std::mem::forget(response),
+            Event::QueryTreeSnapshot { response, .. } => std::mem::forget(response),
+            Event::QueryJournal { response, .. } => std::mem::forget(response),
             _ => {}
         }
     }
@@ -189,7 +193,12 @@ impl MachHandler {
         match request {
             RiftRequest::Subscribe { event } => {
                 let state = self.server_state.read();
-                state.subscribe_client(client_port, event.clone());
+                if state.subscribe_client(client_port, event.clone())
+                    && let Err(e) = self.reactor_tx.try_send(Event::SendSnapshot { client_port })
+                {
+                    error!("Failed to request snapshot for client {}: {}", client_port, e);
+                    state.cancel_snapshot(client_port);
+                }
                 RiftResponse::Success {
                     data: serde_json::json!({ "subscribed": event }),
                 }
@@ -242,6 +251,35 @@ impl MachHandler {
                 }
             }
 
+            RiftRequest::GetWorkspaceWindows {
+                space_id,
+                workspace_index,
+                screen,
+            } => {
+                let screen = screen.map(|[x, y, width, height]| {
+                    CGRect::new(CGPoint::new(x, y), CGSize::new(width, height))
+                });
+                match self.perform_query(|tx| Event::QueryWorkspaceWindows {
+                    space_id: space_id.map(crate::sys::screen::SpaceId::new),
+                    workspace_index,
+                    screen,
+                    response: tx,
+                }) {
+                    Ok(Some(windows)) => RiftResponse::Success {
+                        data: serde_json::to_value(windows).unwrap(),
+                    },
+                    Ok(None) => RiftResponse::Error {
+                        error: serde_json::json!({ "message": "Workspace not found", "workspace_index": workspace_index }),
+                    },
+                    Err(e) => {
+                        error!("{}", e);
+                        RiftResponse::Error {
+                            error: serde_json::json!({ "message": "Failed to get workspace windows response", "details": format!("{}", e) }),
+                        }
+                    }
+                }
+            }
+
             RiftRequest::GetDisplays => match self.perform_query(Event::QueryDisplays) {
                 Ok(displays) => RiftResponse::Success {
                     data: serde_json::to_value(displays).unwrap(),
@@ -314,6 +352,37 @@ impl MachHandler {
                 }
             }
 
+            RiftRequest::GetLayoutTree {
+                space_id,
+                max_depth,
+                focus_path_only,
+                max_nodes,
+            } => {
+                let options = crate::layout_engine::TreeRenderOptions {
+                    max_depth,
+                    focus_path_only,
+                    max_nodes,
+                };
+                match self.perform_query(|tx| Event::QueryLayoutTree {
+                    space_id: space_id.map(crate::sys::screen::SpaceId::new),
+                    options,
+                    response: tx,
+                }) {
+                    Ok(Some(tree)) => RiftResponse::Success {
+                        data: serde_json::json!({ "tree": tree }),
+                    },
+                    Ok(None) => RiftResponse::Error {
+                        error: serde_json::json!({ "message": "Space not found or inactive" }),
+                    },
+                    Err(e) => {
+                        error!("{}", e);
+                        RiftResponse::Error {
+                            error: serde_json::json!({ "message": "Failed to get layout tree response", "details": format!("{}", e) }),
+                        }
+                    }
+                }
+            }
+
             RiftRequest::GetApplications => match self.perform_query(Event::QueryApplications) {
                 Ok(applications) => RiftResponse::Success {
                     data: serde_json::to_value(applications).unwrap(),
@@ -336,6 +405,43 @@ impl MachHandler {
                 }
             },
 
+            RiftRequest::GetTreeSnapshot { space_id } => {
+                match self.perform_query(|tx| Event::QueryTreeSnapshot {
+                    space_id: space_id.map(crate::sys::screen::SpaceId::new),
+                    response: tx,
+                }) {
+                    Ok(Some(snapshot)) => RiftResponse::Success {
+                        data: serde_json::to_value(snapshot).unwrap(),
+                    },
+                    Ok(None) => RiftResponse::Error {
+                        error: serde_json::json!({ "message": "Space not found or inactive" }),
+                    },
+                    Err(e) => {
+                        error!("{}", e);
+                        RiftResponse::Error {
+                            error: serde_json::json!({ "message": "Failed to get tree snapshot response", "details": format!("{}", e) }),
+                        }
+                    }
+                }
+            }
+
+            RiftRequest::GetJournal { space_id } => {
+                match self.perform_query(|tx| Event::QueryJournal {
+                    space_id: space_id.map(crate::sys::screen::SpaceId::new),
+                    response: tx,
+                }) {
+                    Ok(journal) => RiftResponse::Success {
+                        data: serde_json::to_value(journal).unwrap(),
+                    },
+                    Err(e) => {
+                        error!("{}", e);
+                        RiftResponse::Error {
+                            error: serde_json::json!({ "message": "Failed to get journal response", "details": format!("{}", e) }),
+                        }
+                    }
+                }
+            }
+
             RiftRequest::GetConfig => {
                 match self.perform_config_query(config_actor::Event::QueryConfig) {
                     Ok(config) => match serde_json::to_value(&config) {
@@ -65,6 +65,7 @@ impl CliExecutor for DefaultCliExecutor {
                 windows,
                 space_id,
                 display_uuid,
+                ..
             } => {
                 env_vars.insert("RIFT_EVENT_TYPE".into(), "windows_changed".into());
                 env_vars.insert("RIFT_WORKSPACE_ID".into(), workspace_id.to_string());
@@ -100,6 +101,72 @@ impl CliExecutor for DefaultCliExecutor {
                     env_vars.insert("RIFT_DISPLAY_UUID".into(), display_uuid.clone());
                 }
             }
+            BroadcastEvent::WindowFocused {
+                window_id,
+                window_title,
+                app_name,
+                workspace_id,
+                workspace_name,
+                space_id,
+                display_uuid,
+            }
+            | BroadcastEvent::WindowAdded {
+                window_id,
+                window_title,
+                app_name,
+                workspace_id,
+                workspace_name,
+                space_id,
+                display_uuid,
+            } => {
+                env_vars.insert("RIFT_EVENT_TYPE".into(), event.name().into());
+                env_vars.insert("RIFT_WINDOW_ID".into(), window_id.to_debug_string());
+                env_vars.insert("RIFT_WINDOW_TITLE".into(), window_title.clone());
+                if let Some(app_name) = app_name.as_ref() {
+                    env_vars.insert("RIFT_APP_NAME".into(), app_name.clone());
+                }
+                env_vars.insert("RIFT_WORKSPACE_ID".into(), workspace_id.to_string());
+                env_vars.insert("RIFT_WORKSPACE_NAME".into(), workspace_name.clone());
+                env_vars.insert("RIFT_SPACE_ID".into(), space_id.to_string());
+                if let Some(display_uuid) = display_uuid.as_ref() {
+                    env_vars.insert("RIFT_DISPLAY_UUID".into(), display_uuid.clone());
+                }
+            }
+            BroadcastEvent::WindowAssigned {
+                window_id,
+                workspace_id,
+                by_rule,
+                space_id,
+            } => {
+                env_vars.insert("RIFT_EVENT_TYPE".into(), event.name().into());
+                env_vars.insert("RIFT_WINDOW_ID".into(), window_id.to_debug_string());
+                env_vars.insert("RIFT_WORKSPACE_ID".into(), workspace_id.to_string());
+                env_vars.insert("RIFT_ASSIGNED_BY_RULE".into(), by_rule.to_string());
+                env_vars.insert("RIFT_SPACE_ID".into(), space_id.to_string());
+            }
+            BroadcastEvent::WindowDisplayHintsChanged { window_id, space_id, hints } => {
+                env_vars.insert("RIFT_EVENT_TYPE".into(), event.name().into());
+                env_vars.insert("RIFT_WINDOW_ID".into(), window_id.to_debug_string());
+                env_vars.insert("RIFT_SPACE_ID".into(), space_id.to_string());
+                if let Some(opacity) = hints.opacity {
+                    env_vars.insert("RIFT_WINDOW_OPACITY".into(), opacity.to_string());
+                }
+                env_vars.insert(
+                    "RIFT_WINDOW_ALWAYS_ON_TOP".into(),
+                    hints.always_on_top.to_string(),
+                );
+            }
+            BroadcastEvent::LayoutTreeChanged { space_id, fingerprint, .. } => {
+                env_vars.insert("RIFT_EVENT_TYPE".into(), event.name().into());
+                env_vars.insert("RIFT_SPACE_ID".into(), space_id.to_string());
+                env_vars.insert("RIFT_LAYOUT_FINGERPRINT".into(), fingerprint.to_string());
+            }
+            BroadcastEvent::ShuttingDown => {
+                env_vars.insert("RIFT_EVENT_TYPE".into(), event.name().into());
+            }
+            BroadcastEvent::Snapshot { .. } => {
+                env_vars.insert("RIFT_EVENT_TYPE".into(), "snapshot".into());
+            }
         }
 
         let event_json = match serde_json::to_string(event) {
@@ -0,0 +1,408 @@
+//! Runs the shell commands configured under `settings.hooks`.
+//!
+//! The runner lives on its own thread and is fed from the broadcast stream,
+//! so nothing here can hold up the reactor or the layout engine. Each hook is
+//! rate limited by its `debounce_ms`: the first event opens a window, later
+//! events in the window only replace the payload, and the hook runs once with
+//! the latest payload when the window closes.
+
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use tracing::{trace, warn};
+
+use crate::actor::broadcast::BroadcastEvent;
+use crate::common::collections::HashMap;
+use crate::common::config::{HookCommand, HookSettings};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookKind {
+    WorkspaceSwitch,
+    FocusChange,
+    WindowAdded,
+}
+
+impl HookKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            HookKind::WorkspaceSwitch => "on_workspace_switch",
+            HookKind::FocusChange => "on_focus_change",
+            HookKind::WindowAdded => "on_window_added",
+        }
+    }
+}
+
+pub type HookEnv = Vec<(&'static str, String)>;
+
+pub trait HookExecutor: Send + 'static {
+    /// Starts `command` and returns without waiting for it to finish.
+    fn execute(&self, kind: HookKind, command: &str, env: &HookEnv);
+}
+
+/// Runs hooks with `sh -c`, logging commands that fail to start or exit
+/// unsuccessfully.
+pub struct ShellHookExecutor;
+
+impl HookExecutor for ShellHookExecutor {
+    fn execute(&self, kind: HookKind, command: &str, env: &HookEnv) {
+        let child = Command::new("/bin/sh")
+            .arg("-c")
+            .arg(command)
+            .envs(env.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn();
+        let child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                warn!(
+                    hook = kind.name(),
+                    "Failed to start hook command '{}': {}", command, e
+                );
+                return;
+            }
+        };
+        let command = command.to_string();
+        std::thread::spawn(move || match child.wait_with_output() {
+            Ok(output) if !output.status.success() => {
+                warn!(
+                    hook = kind.name(),
+                    "Hook command '{}' failed with status {}: {}",
+                    command,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            Ok(_) => trace!(hook = kind.name(), "Hook command '{}' completed", command),
+            Err(e) => warn!(
+                hook = kind.name(),
+                "Failed to wait for hook '{}': {}", command, e
+            ),
+        });
+    }
+}
+
+/// The hook an event triggers and the environment it runs with.
+pub fn hook_env(event: &BroadcastEvent) -> Option<(HookKind, HookEnv)> {
+    let (kind, workspace_name, display_uuid, window) = match event {
+        BroadcastEvent::WorkspaceChanged {
+            workspace_name, display_uuid, ..
+        } => (HookKind::WorkspaceSwitch, workspace_name, display_uuid, None),
+        BroadcastEvent::WindowFocused {
+            window_title,
+            app_name,
+            workspace_name,
+            display_uuid,
+            ..
+        } => (
+            HookKind::FocusChange,
+            workspace_name,
+            display_uuid,
+            Some((window_title, app_name)),
+        ),
+        BroadcastEvent::WindowAdded {
+            window_title,
+            app_name,
+            workspace_name,
+            display_uuid,
+            ..
+        } => (
+            HookKind::WindowAdded,
+            workspace_name,
+            display_uuid,
+            Some((window_title, app_name)),
+        ),
+        BroadcastEvent::WindowsChanged { .. }
+        | BroadcastEvent::WindowTitleChanged { .. }
+        | BroadcastEvent::WindowAssigned { .. }
+        | BroadcastEvent::WindowDisplayHintsChanged { .. }
+        | BroadcastEvent::LayoutTreeChanged { .. }
+        | BroadcastEvent::ShuttingDown
+        | BroadcastEvent::Snapshot { .. } => return None,
+    };
+
+    let mut env = vec![("RIFT_WORKSPACE", workspace_name.clone())];
+    if let Some(display_uuid) = display_uuid {
+        env.push(("RIFT_DISPLAY", display_uuid.clone()));
+    }
+    if let Some((window_title, app_name)) = window {
+        env.push(("RIFT_WINDOW_TITLE", window_title.clone()));
+        if let Some(app_name) = app_name {
+            env.push(("RIFT_APP", app_name.clone()));
+        }
+    }
+    Some((kind, env))
+}
+
+struct PendingHook {
+    due: Instant,
+    env: HookEnv,
+}
+
+pub struct HookRunner<E: HookExecutor> {
+    settings: HookSettings,
+    executor: E,
+    pending: HashMap<HookKind, PendingHook>,
+}
+
+impl<E: HookExecutor> HookRunner<E> {
+    pub fn new(settings: HookSettings, executor: E) -> Self {
+        HookRunner {
+            settings,
+            executor,
+            pending: HashMap::default(),
+        }
+    }
+
+    fn hook(&self, kind: HookKind) -> Option<&HookCommand> {
+        match kind {
+            HookKind::WorkspaceSwitch => self.settings.on_workspace_switch.as_ref(),
+            HookKind::FocusChange => self.settings.on_focus_change.as_ref(),
+            HookKind::WindowAdded => self.settings.on_window_added.as_ref(),
+        }
+    }
+
+    pub fn has_hooks(&self) -> bool {
+        [
+            HookKind::WorkspaceSwitch,
+            HookKind::FocusChange,
+            HookKind::WindowAdded,
+        ]
+        .into_iter()
+        .any(|kind| self.hook(kind).is_some())
+    }
+
+    pub fn handle_event(&mut self, event: &BroadcastEvent, now: Instant) {
+        let Some((kind, env)) = hook_env(event) else { return };
+        let Some(hook) = self.hook(kind) else { return };
+        let debounce = Duration::from_millis(hook.debounce_ms);
+        match self.pending.get_mut(&kind) {
+            Some(pending) => pending.env = env,
+            None => {
+                self.pending.insert(kind, PendingHook { due: now + debounce, env });
+            }
+        }
+        self.run_due(now);
+    }
+
+    /// Runs every hook whose debounce window has closed by `now`.
+    pub fn run_due(&mut self, now: Instant) {
+        let due: Vec<HookKind> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.due <= now)
+            .map(|(&kind, _)| kind)
+            .collect();
+        for kind in due {
+            let Some(pending) = self.pending.remove(&kind) else {
+                continue;
+            };
+            let Some(hook) = self.hook(kind) else { continue };
+            trace!(hook = kind.name(), "Running hook");
+            self.executor.execute(kind, &hook.command, &pending.env);
+        }
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.pending.values().map(|pending| pending.due).min()
+    }
+
+    fn run(mut self, rx: Receiver<BroadcastEvent>) {
+        loop {
+            let event = match self.next_deadline() {
+                Some(deadline) => {
+                    match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok(event) => Some(event),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                None => match rx.recv() {
+                    Ok(event) => Some(event),
+                    Err(_) => return,
+                },
+            };
+            let now = Instant::now();
+            match event {
+                Some(event) => self.handle_event(&event, now),
+                None => self.run_due(now),
+            }
+        }
+    }
+}
+
+pub type HookSender = mpsc::Sender<BroadcastEvent>;
+
+/// Starts the hook thread, or returns `None` when no hooks are configured.
+pub fn spawn_hook_runner(settings: HookSettings) -> Option<HookSender> {
+    let runner = HookRunner::new(settings, ShellHookExecutor);
+    if !runner.has_hooks() {
+        return None;
+    }
+    let (tx, rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("hooks".to_string())
+        .spawn(move || runner.run(rx))
+        .map_err(|e| warn!("Failed to start hook runner: {}", e))
+        .ok()?;
+    Some(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::actor::app::WindowId;
+    use crate::layout_engine::VirtualWorkspaceId;
+    use crate::sys::screen::SpaceId;
+
+    type Runs = Arc<Mutex<Vec<(HookKind, String, HookEnv)>>>;
+
+    #[derive(Clone, Default)]
+    struct Recorder(Runs);
+
+    impl HookExecutor for Recorder {
+        fn execute(&self, kind: HookKind, command: &str, env: &HookEnv) {
+            self.0.lock().push((kind, command.to_string(), env.clone()));
+        }
+    }
+
+    fn hook(command: &str, debounce_ms: u64) -> Option<HookCommand> {
+        Some(HookCommand {
+            command: command.to_string(),
+            debounce_ms,
+        })
+    }
+
+    fn runner(settings: HookSettings) -> (HookRunner<Recorder>, Runs) {
+        let recorder = Recorder::default();
+        let runs = recorder.0.clone();
+        (HookRunner::new(settings, recorder), runs)
+    }
+
+    fn workspace_changed(name: &str) -> BroadcastEvent {
+        BroadcastEvent::WorkspaceChanged {
+            space_id: SpaceId::new(1),
+            workspace_id: VirtualWorkspaceId::default(),
+            workspace_name: name.to_string(),
+            display_uuid: Some("display-1".to_string()),
+        }
+    }
+
+    fn window_focused(title: &str) -> BroadcastEvent {
+        BroadcastEvent::WindowFocused {
+            window_id: WindowId::new(1, 1),
+            window_title: title.to_string(),
+            app_name: Some("Terminal".to_string()),
+            workspace_id: VirtualWorkspaceId::default(),
+            workspace_name: "main".to_string(),
+            space_id: SpaceId::new(1),
+            display_uuid: None,
+        }
+    }
+
+    #[test]
+    fn env_is_built_from_the_event_payload() {
+        let (kind, env) = hook_env(&workspace_changed("web")).unwrap();
+        assert_eq!(kind, HookKind::WorkspaceSwitch);
+        assert_eq!(
+            env,
+            vec![
+                ("RIFT_WORKSPACE", "web".to_string()),
+                ("RIFT_DISPLAY", "display-1".to_string()),
+            ]
+        );
+
+        let (kind, env) = hook_env(&window_focused("vim")).unwrap();
+        assert_eq!(kind, HookKind::FocusChange);
+        assert_eq!(
+            env,
+            vec![
+                ("RIFT_WORKSPACE", "main".to_string()),
+                ("RIFT_WINDOW_TITLE", "vim".to_string()),
+                ("RIFT_APP", "Terminal".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn events_without_hooks_are_ignored() {
+        let (mut runner, runs) = runner(HookSettings {
+            on_focus_change: hook("focus", 0),
+            ..Default::default()
+        });
+        let now = Instant::now();
+
+        runner.handle_event(&workspace_changed("web"), now);
+        runner.run_due(now + Duration::from_secs(1));
+
+        assert!(runs.lock().is_empty());
+        assert_eq!(runner.next_deadline(), None);
+    }
+
+    #[test]
+    fn zero_debounce_runs_every_event() {
+        let (mut runner, runs) = runner(HookSettings {
+            on_focus_change: hook("focus", 0),
+            ..Default::default()
+        });
+        let now = Instant::now();
+
+        runner.handle_event(&window_focused("a"), now);
+        runner.handle_event(&window_focused("b"), now);
+
+        let runs = runs.lock();
+        assert_eq!(runs.len(), 2);
+        assert!(
+            runs.iter()
+                .all(|(kind, command, _)| { *kind == HookKind::FocusChange && command == "focus" })
+        );
+    }
+
+    #[test]
+    fn bursts_run_once_with_the_latest_payload() {
+        let (mut runner, runs) = runner(HookSettings {
+            on_workspace_switch: hook("switch", 100),
+            ..Default::default()
+        });
+        let start = Instant::now();
+
+        runner.handle_event(&workspace_changed("1"), start);
+        runner.handle_event(&workspace_changed("2"), start + Duration::from_millis(40));
+        runner.handle_event(&workspace_changed("3"), start + Duration::from_millis(80));
+        assert!(runs.lock().is_empty());
+        assert_eq!(runner.next_deadline(), Some(start + Duration::from_millis(100)));
+
+        runner.run_due(start + Duration::from_millis(100));
+        assert_eq!(runs.lock().len(), 1);
+        assert_eq!(runs.lock()[0].2[0], ("RIFT_WORKSPACE", "3".to_string()));
+        assert_eq!(runner.next_deadline(), None);
+
+        runner.handle_event(&workspace_changed("4"), start + Duration::from_millis(150));
+        runner.run_due(start + Duration::from_millis(250));
+        assert_eq!(runs.lock().len(), 2);
+    }
+
+    #[test]
+    fn hooks_are_debounced_independently() {
+        let (mut runner, runs) = runner(HookSettings {
+            on_workspace_switch: hook("switch", 100),
+            on_focus_change: hook("focus", 10),
+            ..Default::default()
+        });
+        let start = Instant::now();
+
+        runner.handle_event(&workspace_changed("1"), start);
+        runner.handle_event(&window_focused("a"), start);
+        runner.run_due(start + Duration::from_millis(10));
+
+        let kinds: Vec<_> = runs.lock().iter().map(|(kind, ..)| *kind).collect();
+        assert_eq!(kinds, vec![HookKind::FocusChange]);
+        assert_eq!(runner.next_deadline(), Some(start + Duration::from_millis(100)));
+    }
+}
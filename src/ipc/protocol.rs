@@ -8,6 +8,14 @@ pub enum RiftRequest {
     GetWorkspaces {
         space_id: Option<u64>,
     },
+    GetWorkspaceWindows {
+        space_id: Option<u64>,
+        workspace_index: usize,
+        /// Screen rect `[x, y, width, height]` to lay an inactive workspace out
+        /// in; defaults to the space's display.
+        #[serde(default)]
+        screen: Option<[f64; 4]>,
+    },
     GetDisplays,
     GetWindows {
         space_id: Option<u64>,
@@ -20,6 +28,21 @@ pub enum RiftRequest {
     },
     GetApplications,
     GetMetrics,
+    GetLayoutTree {
+        space_id: Option<u64>,
+        #[serde(default)]
+        max_depth: Option<usize>,
+        #[serde(default)]
+        focus_path_only: bool,
+        #[serde(default)]
+        max_nodes: Option<usize>,
+    },
+    GetTreeSnapshot {
+        space_id: Option<u64>,
+    },
+    GetJournal {
+        space_id: Option<u64>,
+    },
     GetConfig,
     ExecuteCommand {
         command: String,
@@ -1,11 +1,13 @@
 use std::ffi::CString;
 use std::os::raw::c_char;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use dashmap::DashMap;
 use dispatchr::queue;
 use dispatchr::time::Time;
 use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
 use serde_json::Value;
 use tracing::{debug, error, info, warn};
 
@@ -22,10 +24,37 @@ pub struct CliSubscription {
     pub args: Vec<String>,
 }
 
+/// Delivers serialized events to subscribed clients.
+pub trait EventTransport: Send + Sync + 'static {
+    fn send(&self, client_port: ClientPort, event_json: String);
+}
+
+pub struct MachEventTransport;
+
+impl EventTransport for MachEventTransport {
+    fn send(&self, client_port: ClientPort, event_json: String) {
+        schedule_event_send(client_port, event_json);
+    }
+}
+
+/// Wire format of a broadcast: the event plus a monotonically increasing
+/// sequence number so clients can order deliveries against their snapshot.
+#[derive(Serialize)]
+struct SequencedEvent<'a> {
+    seq: u64,
+    #[serde(flatten)]
+    event: &'a BroadcastEvent,
+}
+
 pub struct ServerState {
     subscriptions_by_client: DashMap<ClientPort, Vec<String>>,
     subscriptions_by_event: DashMap<String, Vec<ClientPort>>,
     cli_subscriptions: Mutex<HashMap<String, Vec<CliSubscription>>>,
+    /// Clients that subscribed but have not received their snapshot yet.
+    /// Live events are withheld from them since the snapshot will cover them.
+    awaiting_snapshot: Mutex<HashSet<ClientPort>>,
+    next_seq: AtomicU64,
+    transport: Box<dyn EventTransport>,
 }
 
 pub type SharedServerState = Arc<RwLock<ServerState>>;
@@ -38,15 +67,29 @@ impl Default for ServerState {
 
 impl ServerState {
     pub fn new() -> Self {
+        Self::with_transport(MachEventTransport)
+    }
+
+    pub fn with_transport(transport: impl EventTransport) -> Self {
         Self {
             subscriptions_by_client: DashMap::new(),
             subscriptions_by_event: DashMap::new(),
             cli_subscriptions: Mutex::new(HashMap::default()),
+            awaiting_snapshot: Mutex::new(HashSet::default()),
+            next_seq: AtomicU64::new(1),
+            transport: Box::new(transport),
         }
     }
 
-    pub fn subscribe_client(&self, client_port: ClientPort, event: String) {
+    /// Subscribes `client_port` to `event`. Returns true if this is the first
+    /// subscription of the client, in which case it is held back from live
+    /// events until its snapshot is published.
+    pub fn subscribe_client(&self, client_port: ClientPort, event: String) -> bool {
         info!("Client {} subscribing to event: {}", client_port, event);
+        let is_new_client = !self.subscriptions_by_client.contains_key(&client_port);
+        if is_new_client {
+            self.awaiting_snapshot.lock().insert(client_port);
+        }
         let mut added = false;
         self.subscriptions_by_client
             .entry(client_port)
@@ -72,6 +115,14 @@ impl ServerState {
                 .or_insert_with(|| vec![client_port]);
             info!("Client {} now subscribed to '{}'", client_port, event);
         }
+
+        is_new_client
+    }
+
+    /// Stops waiting for a snapshot for `client_port`, e.g. because it could
+    /// not be requested. The client receives live events from now on.
+    pub fn cancel_snapshot(&self, client_port: ClientPort) {
+        self.awaiting_snapshot.lock().remove(&client_port);
     }
 
     pub fn unsubscribe_client(&self, client_port: ClientPort, event: String) {
@@ -143,16 +194,28 @@ impl ServerState {
     }
 
     pub fn publish(&self, event: BroadcastEvent) {
+        let seq = self.next_seq.fetch_add(1, Ordering::SeqCst);
+        if let BroadcastEvent::Snapshot { client_port, .. } = &event {
+            self.deliver_snapshot(*client_port, seq, &event);
+            return;
+        }
         self.forward_event_to_cli_subscribers(event.clone());
-        self.forward_event_to_subscribers(event);
+        self.forward_event_to_subscribers(seq, event);
     }
 
-    fn forward_event_to_subscribers(&self, event: BroadcastEvent) {
-        let event_name = match &event {
-            BroadcastEvent::WorkspaceChanged { .. } => "workspace_changed",
-            BroadcastEvent::WindowsChanged { .. } => "windows_changed",
-            BroadcastEvent::WindowTitleChanged { .. } => "window_title_changed",
-        };
+    fn deliver_snapshot(&self, client_port: ClientPort, seq: u64, event: &BroadcastEvent) {
+        if !self.awaiting_snapshot.lock().remove(&client_port) {
+            debug!("Client {} is not awaiting a snapshot; dropping it", client_port);
+            return;
+        }
+        match serialize_event(seq, event) {
+            Some(event_json) => self.transport.send(client_port, event_json),
+            None => warn!("Client {} will not receive its snapshot", client_port),
+        }
+    }
+
+    fn forward_event_to_subscribers(&self, seq: u64, event: BroadcastEvent) {
+        let event_name = event.name();
 
         let mut targets: HashSet<ClientPort> = HashSet::default();
         if let Some(clients) = self.subscriptions_by_event.get(event_name) {
@@ -161,30 +224,26 @@ impl ServerState {
         if let Some(clients) = self.subscriptions_by_event.get("*") {
             targets.extend(clients.iter().copied());
         }
+        {
+            let awaiting = self.awaiting_snapshot.lock();
+            targets.retain(|client| !awaiting.contains(client));
+        }
 
         if targets.is_empty() {
             return;
         }
 
-        let event_json = match serde_json::to_string(&event) {
-            Ok(s) => s,
-            Err(e) => {
-                error!("Failed to serialize broadcast event: {}", e);
-                return;
-            }
+        let Some(event_json) = serialize_event(seq, &event) else {
+            return;
         };
 
         for client_port in targets {
-            schedule_event_send(client_port, event_json.clone());
+            self.transport.send(client_port, event_json.clone());
         }
     }
 
     fn forward_event_to_cli_subscribers(&self, event: BroadcastEvent) {
-        let event_name = match &event {
-            BroadcastEvent::WorkspaceChanged { .. } => "workspace_changed",
-            BroadcastEvent::WindowsChanged { .. } => "windows_changed",
-            BroadcastEvent::WindowTitleChanged { .. } => "window_title_changed",
-        };
+        let event_name = event.name();
 
         // Collect relevant subscriptions without full HashMap clone
         let mut relevant: Vec<CliSubscription> = Vec::new();
@@ -223,6 +282,7 @@ impl ServerState {
     }
 
     pub fn remove_client(&self, client_port: ClientPort) {
+        self.awaiting_snapshot.lock().remove(&client_port);
         if let Some((_k, events)) = self.subscriptions_by_client.remove(&client_port) {
             for event in events {
                 if let Some(mut entry) = self.subscriptions_by_event.get_mut(&event) {
@@ -237,6 +297,16 @@ impl ServerState {
     }
 }
 
+fn serialize_event(seq: u64, event: &BroadcastEvent) -> Option<String> {
+    match serde_json::to_string(&SequencedEvent { seq, event }) {
+        Ok(s) => Some(s),
+        Err(e) => {
+            error!("Failed to serialize broadcast event: {}", e);
+            None
+        }
+    }
+}
+
 fn schedule_event_send(client_port: ClientPort, event_json: String) {
     match queue::global(dispatchr::QoS::Utility) {
         Some(q) => unsafe {
@@ -251,3 +321,130 @@ fn schedule_event_send(client_port: ClientPort, event_json: String) {
         None => ServerState::send_event_to_client(client_port, &event_json),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout_engine::VirtualWorkspaceId;
+    use crate::model::server::{CompatibilityData, SnapshotData};
+    use crate::sys::screen::SpaceId;
+
+    #[derive(Clone, Default)]
+    struct RecordingTransport(Arc<Mutex<Vec<(ClientPort, Value)>>>);
+
+    impl EventTransport for RecordingTransport {
+        fn send(&self, client_port: ClientPort, event_json: String) {
+            let value = serde_json::from_str(&event_json).unwrap();
+            self.0.lock().push((client_port, value));
+        }
+    }
+
+    impl RecordingTransport {
+        fn delivered_to(&self, client_port: ClientPort) -> Vec<(u64, String)> {
+            self.0
+                .lock()
+                .iter()
+                .filter(|(port, _)| *port == client_port)
+                .map(|(_, v)| {
+                    (
+                        v["seq"].as_u64().unwrap(),
+                        v["type"].as_str().unwrap().to_string(),
+                    )
+                })
+                .collect()
+        }
+    }
+
+    fn workspace_changed() -> BroadcastEvent {
+        BroadcastEvent::WorkspaceChanged {
+            space_id: SpaceId::new(1),
+            workspace_id: VirtualWorkspaceId::default(),
+            workspace_name: "1".to_string(),
+            display_uuid: None,
+        }
+    }
+
+    fn snapshot_for(client_port: ClientPort) -> BroadcastEvent {
+        BroadcastEvent::Snapshot {
+            client_port,
+            snapshot: Box::new(SnapshotData {
+                displays: vec![],
+                spaces: vec![],
+                focused_window: None,
+                mission_control_active: false,
+                compatibility: CompatibilityData {
+                    protocol_version: 1,
+                    features: vec![],
+                },
+            }),
+        }
+    }
+
+    #[test]
+    fn new_subscriber_receives_snapshot_before_live_events() {
+        let transport = RecordingTransport::default();
+        let state = ServerState::with_transport(transport.clone());
+
+        assert!(state.subscribe_client(7, "*".to_string()));
+        // Emitted before the snapshot was assembled, so the snapshot covers it.
+        state.publish(workspace_changed());
+        state.publish(snapshot_for(7));
+        state.publish(workspace_changed());
+
+        let delivered = transport.delivered_to(7);
+        assert_eq!(
+            delivered.iter().map(|(_, ty)| ty.as_str()).collect::<Vec<_>>(),
+            vec!["snapshot", "workspace_changed"]
+        );
+        assert!(delivered[0].0 < delivered[1].0);
+    }
+
+    #[test]
+    fn snapshot_is_only_sent_to_its_client() {
+        let transport = RecordingTransport::default();
+        let state = ServerState::with_transport(transport.clone());
+
+        assert!(state.subscribe_client(1, "*".to_string()));
+        state.publish(snapshot_for(1));
+        assert!(state.subscribe_client(2, "*".to_string()));
+        state.publish(snapshot_for(2));
+
+        assert_eq!(transport.delivered_to(1).len(), 1);
+        assert_eq!(transport.delivered_to(2).len(), 1);
+    }
+
+    #[test]
+    fn existing_subscriber_is_not_held_back_by_additional_subscriptions() {
+        let transport = RecordingTransport::default();
+        let state = ServerState::with_transport(transport.clone());
+
+        assert!(state.subscribe_client(3, "windows_changed".to_string()));
+        state.publish(snapshot_for(3));
+        assert!(!state.subscribe_client(3, "workspace_changed".to_string()));
+        state.publish(workspace_changed());
+        // A stray snapshot for an already-initialised client is dropped.
+        state.publish(snapshot_for(3));
+
+        let delivered = transport.delivered_to(3);
+        assert_eq!(
+            delivered.iter().map(|(_, ty)| ty.as_str()).collect::<Vec<_>>(),
+            vec!["snapshot", "workspace_changed"]
+        );
+    }
+
+    #[test]
+    fn sequence_numbers_increase_across_events() {
+        let transport = RecordingTransport::default();
+        let state = ServerState::with_transport(transport.clone());
+
+        state.subscribe_client(4, "*".to_string());
+        state.cancel_snapshot(4);
+        for _ in 0..5 {
+            state.publish(workspace_changed());
+        }
+
+        let seqs: Vec<u64> = transport.delivered_to(4).into_iter().map(|(seq, _)| seq).collect();
+        assert_eq!(seqs.len(), 5);
+        assert!(seqs.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
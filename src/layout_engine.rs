@@ -1,16 +1,25 @@
 pub mod engine;
 mod floating;
 pub(crate) mod graph;
+mod journal;
+mod metrics;
+mod pending;
 pub mod systems;
+#[cfg(test)]
+mod testing;
 pub mod utils;
 mod workspaces;
 
-pub use engine::{EventResponse, LayoutCommand, LayoutEngine, LayoutEvent};
+pub use engine::{EventResponse, LayoutCommand, LayoutEngine, LayoutEvent, TreeSnapshot};
 pub(crate) use floating::FloatingManager;
 pub use graph::{Direction, LayoutKind, Orientation};
+pub use journal::{JournalEntry, JournalEntryKind};
+pub use metrics::{EngineMetrics, LayoutTreeSize, TimerSnapshot};
+pub(crate) use pending::PendingSpaceEvents;
 pub(crate) use systems::LayoutId;
 pub use systems::{
     BspLayoutSystem, DwindleLayoutSystem, LayoutSystem, LayoutSystemKind, TraditionalLayoutSystem,
+    TreeRenderOptions,
 };
 pub(crate) use workspaces::WorkspaceLayouts;
 
@@ -1,60 +1,158 @@
 #![allow(clippy::too_many_arguments, clippy::type_complexity)]
 
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::fs::{self, File};
-use std::io::{Read, Write};
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 use objc2_core_foundation::{CGPoint, CGRect, CGSize};
 use serde::{Deserialize, Serialize};
-use tracing::{debug, info, warn};
+use tracing::{debug, info, trace, warn};
 
-use super::{Direction, FloatingManager, LayoutId, LayoutSystemKind, WorkspaceLayouts};
+use super::journal::{Journal, JournalEntry, JournalEntryKind, timestamp_ms};
+use super::metrics::Metrics;
+use super::{
+    Direction, EngineMetrics, FloatingManager, LayoutId, LayoutKind, LayoutSystemKind,
+    LayoutTreeSize, Orientation, PendingSpaceEvents, TreeRenderOptions, WorkspaceLayouts,
+};
 use crate::actor::app::{AppInfo, WindowId, pid_t};
-use crate::actor::broadcast::{BroadcastEvent, BroadcastSender};
-use crate::common::collections::{HashMap, HashSet};
-use crate::common::config::LayoutSettings;
+use crate::actor::broadcast::{BroadcastEvent, BroadcastSender, WindowDetails};
+use crate::common::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use crate::common::config::{
+    LayoutSettings, OnEmptyWorkspace, PersistenceSettings, StackToggleMode,
+};
+use crate::common::persistence::{self, PersistenceFormat, SaveDebounce};
 use crate::layout_engine::LayoutSystem;
 use crate::model::virtual_workspace::{
-    AppRuleAssignment, AppRuleResult, VirtualWorkspaceId, VirtualWorkspaceManager,
+    AppRuleAssignment, AppRuleResult, DisplayHints, VirtualWorkspaceId, VirtualWorkspaceManager,
 };
 use crate::sys::screen::SpaceId;
 
+/// How long a focus report for a window the engine has not seen yet still
+/// counts when the window is added.
+const FOCUS_BEFORE_ADD: Duration = Duration::from_secs(1);
+
+/// Largest resize `ResizeWindowBy` accepts, as a fraction of the screen.
+/// Larger values are almost always pixel counts passed by mistake.
+const MAX_RESIZE_AMOUNT: f64 = 0.5;
+
+/// A stack as its stack bar draws it.
 #[derive(Debug, Clone)]
 pub struct GroupContainerInfo {
     pub node_id: crate::model::tree::NodeId,
     pub container_kind: super::LayoutKind,
     pub frame: CGRect,
     pub total_count: usize,
+    /// Index of the selected member in `window_ids`. Members are in tree
+    /// order for both orientations, which is left to right for horizontal
+    /// stacks and top to bottom for vertical ones.
     pub selected_index: usize,
+    /// The window each member shows, one per member.
     pub window_ids: Vec<crate::actor::app::WindowId>,
 }
 
 #[non_exhaustive]
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    PartialEq,
+    strum_macros::IntoStaticStr,
+    strum_macros::VariantNames,
+)]
 #[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
 pub enum LayoutCommand {
     NextWindow,
     PrevWindow,
     MoveFocus(#[serde(rename = "direction")] Direction),
+    /// Focus the `index`th (zero-based) visible window of the layout, counted
+    /// in tree order. Out-of-range indices are ignored.
+    FocusNth {
+        index: usize,
+    },
     Ascend,
     Descend,
     MoveNode(Direction),
+    /// Move the selection to the front of the root container.
+    PromoteToMain,
+    /// Reverse the children of the selection's parent, swapping them if
+    /// there are two.
+    FlipContainer,
 
     JoinWindow(Direction),
     ToggleStack,
+    /// Switch the selection's stack between peeking and tabbed, stacking its
+    /// container as tabbed if it is not stacked yet.
+    ToggleTabbed,
     ToggleOrientation,
+    /// Flip the axis of every container under the selection's parent.
+    RotateSubtree,
+    /// Turn the whole layout a quarter turn, keeping each window's share.
+    RotateLayout {
+        clockwise: bool,
+    },
+    /// Reverse every container of the layout along `axis`, so a 70/30 split
+    /// becomes 30/70.
+    MirrorLayout {
+        axis: Orientation,
+    },
     UnjoinWindows,
     ToggleFocusFloating,
     ToggleWindowFloating,
     ToggleFullscreen,
     ToggleFullscreenWithinGaps,
+    /// Lift the selection out of its containers to the root and make it
+    /// fullscreen within gaps. Layouts other than traditional toggle
+    /// fullscreen within gaps.
+    IsolateAndMaximize,
+    /// Tile the focused window even though app rules leave it unmanaged, in
+    /// the active workspace after the selection.
+    ManageWindow,
+    /// Take the focused window out of the layout and keep it out, whatever
+    /// app rules say.
+    UnmanageWindow,
+    /// Keep the focused window's current size as siblings are added or removed.
+    TogglePreserveSize,
+    /// Lock the selection's parent container against automatic restructuring,
+    /// or unlock it.
+    ToggleContainerLock,
+    /// Revert the last structural change (join, unjoin, move, split, stack).
+    Undo,
+    /// Re-apply the last change reverted by `Undo`.
+    Redo,
 
     ResizeWindowGrow,
     ResizeWindowShrink,
+    /// Grow (positive) or shrink (negative) the selection by a fraction of
+    /// the screen. Amounts beyond ±0.5 are rejected.
     ResizeWindowBy {
         amount: f64,
     },
+    /// Like `ResizeWindowBy`, but by `pixels` and only along the axis
+    /// `direction` points in.
+    ResizeWindowPx {
+        direction: Direction,
+        pixels: f64,
+    },
+    /// Split the selection's parent 1.618:1 between its first child and the
+    /// rest, or 1:1.618 when `main_first` is false.
+    GoldenRatio {
+        main_first: bool,
+    },
+    /// Rebuild the layout as the first window beside a vertical column of
+    /// the rest.
+    TwoColumn {
+        master_on_left: bool,
+    },
+    /// Split the selection into `count` equal tiles of a `kind` container,
+    /// reusing the parent if it already has that many children. Layouts
+    /// other than traditional do a plain split.
+    SplitInto {
+        kind: LayoutKind,
+        count: usize,
+    },
 
     NextWorkspace(Option<bool>),
     PrevWorkspace(Option<bool>),
@@ -65,12 +163,24 @@ pub enum LayoutCommand {
     },
     CreateWorkspace,
     SwitchToLastWorkspace,
+    /// Move every window of workspace `from` into workspace `into` and
+    /// remove `from`.
+    MergeWorkspaces {
+        from: usize,
+        into: usize,
+    },
+    /// Move the workspace at index `from` to index `to`.
+    ReorderWorkspace {
+        from: usize,
+        to: usize,
+    },
 
     SwapWindows(crate::actor::app::WindowId, crate::actor::app::WindowId),
 }
 
 #[non_exhaustive]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, strum_macros::IntoStaticStr, strum_macros::VariantNames)]
+#[strum(serialize_all = "snake_case")]
 pub enum LayoutEvent {
     WindowsOnScreenUpdated(
         SpaceId,
@@ -81,6 +191,13 @@ pub enum LayoutEvent {
     AppClosed(pid_t),
     WindowAdded(SpaceId, WindowId),
     WindowRemoved(WindowId),
+    /// A managed window's frame now lies on another display. `from` is the
+    /// space the reactor last saw it on, if any.
+    WindowMovedToSpace {
+        wid: WindowId,
+        from: Option<SpaceId>,
+        to: SpaceId,
+    },
     WindowFocused(SpaceId, WindowId),
     WindowResized {
         wid: WindowId,
@@ -97,6 +214,67 @@ pub struct EventResponse {
     pub raise_windows: Vec<WindowId>,
     pub focus_window: Option<WindowId>,
     pub workspace_changed_to: Option<VirtualWorkspaceId>,
+    /// Whether a command changed any active layout. When false the reactor
+    /// can skip re-applying frames.
+    pub layout_changed: bool,
+    /// Fullscreen windows of a workspace that was just switched to, and
+    /// whether each is fullscreen within gaps.
+    pub fullscreen_windows: Vec<(WindowId, bool)>,
+}
+
+impl EventResponse {
+    /// Folds in the response to a later event, as if both had been handled
+    /// as one: windows to raise accumulate, and the later focus or workspace
+    /// switch wins.
+    pub fn merge(&mut self, later: EventResponse) {
+        for wid in later.raise_windows {
+            if !self.raise_windows.contains(&wid) {
+                self.raise_windows.push(wid);
+            }
+        }
+        self.focus_window = later.focus_window.or(self.focus_window);
+        self.workspace_changed_to = later.workspace_changed_to.or(self.workspace_changed_to);
+        self.layout_changed |= later.layout_changed;
+        for entry in later.fullscreen_windows {
+            if !self.fullscreen_windows.contains(&entry) {
+                self.fullscreen_windows.push(entry);
+            }
+        }
+    }
+}
+
+/// What the engine held when it was shut down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownState {
+    pub spaces: usize,
+    pub layouts: usize,
+    pub windows: usize,
+}
+
+/// The active layout of a space drawn as `rift query tree` shows it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TreeSnapshot {
+    pub ascii: String,
+    /// Changes whenever the layout's structure, shares or windows do.
+    pub fingerprint: u64,
+}
+
+/// Why a command naming specific windows was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum WindowCommandError {
+    #[error("Window is not managed: {0:?}")]
+    UnknownWindow(WindowId),
+    #[error("{window:?} is on {space:?} but {other:?} is on {other_space:?}")]
+    CrossSpace {
+        window: WindowId,
+        space: SpaceId,
+        other: WindowId,
+        other_space: SpaceId,
+    },
+    #[error("{window:?} and {other:?} are on different workspaces")]
+    CrossWorkspace { window: WindowId, other: WindowId },
+    #[error("Window is not tiled: {0:?}")]
+    NotTiled(WindowId),
 }
 
 #[derive(Serialize, Deserialize)]
@@ -115,18 +293,161 @@ pub struct LayoutEngine {
     space_display_map: HashMap<SpaceId, Option<String>>,
     #[serde(skip)]
     display_last_space: HashMap<String, SpaceId>,
+    #[serde(skip)]
+    app_bundle_ids: HashMap<pid_t, String>,
+    /// The last `WindowsOnScreenUpdated` payload seen per space and app. Apps
+    /// repeat identical lists while launching; those are dropped. Anything
+    /// else that can change where the app's windows belong forgets entries.
+    #[serde(skip)]
+    last_app_windows: HashMap<(SpaceId, pid_t), AppWindowsSeen>,
+    /// What app rules match each known window on, as last reported. Kept
+    /// until the window goes away, so rules can be re-evaluated at any time.
+    #[serde(skip)]
+    window_rule_inputs: HashMap<WindowId, RuleInputs>,
+    /// Spaces whose broadcasts are held back while the reactor runs a command
+    /// batch. `None` outside a batch, when broadcasts go out immediately.
+    #[serde(skip)]
+    deferred_broadcasts: Option<DeferredBroadcasts>,
+    /// Window events for spaces that have not been exposed yet, replayed
+    /// when they are.
+    #[serde(skip)]
+    pending_space_events: PendingSpaceEvents,
+    /// Spaces whose active workspace lost a window since the last
+    /// `switch_from_emptied_workspaces`, with that workspace.
+    #[serde(skip)]
+    maybe_emptied: BTreeMap<SpaceId, VirtualWorkspaceId>,
+    /// A window reported focused before it was added to a layout. Only a
+    /// window that actually took focus becomes the selection when added.
+    #[serde(skip)]
+    focused_before_added: Option<(WindowId, Instant)>,
+    #[serde(skip)]
+    metrics: Metrics,
+    /// Recent events and commands, kept while `journal.enabled` is set.
+    #[serde(skip)]
+    journal: Option<Journal>,
+    /// Frames the tiled windows of each layout got in the last layout pass,
+    /// used to put a window that stops floating back near where it is.
+    #[serde(skip)]
+    tiled_frames: HashMap<LayoutId, Vec<(WindowId, CGRect)>>,
+    /// Inputs of the last layout pass over each layout, so structural
+    /// changes can keep preserved sizes without waiting for the next pass.
+    #[serde(skip)]
+    layout_inputs: HashMap<LayoutId, LayoutInputs>,
+    /// Layouts whose root `auto_stack_threshold` already stacked. They are
+    /// not stacked again, so unstacking one by hand sticks.
+    #[serde(skip)]
+    auto_stacked: HashSet<LayoutId>,
+    /// Tiled frames computed by `frame_of_window`, per space, with the
+    /// layout, its fingerprint and the inputs they were computed for. Layout
+    /// settings are not part of that key, so changing them clears it.
+    #[serde(skip)]
+    frame_cache: HashMap<SpaceId, CachedFrames>,
+    /// Opacity and always-on-top hints app rules gave windows. Windows
+    /// without any are left out.
+    #[serde(skip)]
+    window_display_hints: HashMap<WindowId, DisplayHints>,
+    /// Set by `enable_autosave`; events and commands mark the state dirty.
+    #[serde(skip)]
+    autosave: Option<Autosave>,
+    /// The layout and fingerprint each space's tree was last broadcast for.
+    #[serde(skip)]
+    broadcast_trees: HashMap<SpaceId, (LayoutId, u64)>,
+}
+
+/// Where `LayoutEngine::autosave_if_due` writes the engine state, and how
+/// often.
+#[derive(Debug)]
+struct Autosave {
+    path: PathBuf,
+    format: PersistenceFormat,
+    /// False while `persistence.autosave_secs` is 0.
+    enabled: bool,
+    debounce: SaveDebounce,
+}
+
+impl Autosave {
+    fn configure(&mut self, settings: &PersistenceSettings) {
+        self.format = settings.format;
+        self.enabled = settings.autosave_secs > 0.0;
+        if self.enabled {
+            self.debounce.set_interval(Duration::from_secs_f64(settings.autosave_secs));
+        }
+    }
+}
+
+/// One layout pass kept for `LayoutEngine::frame_of_window`.
+#[derive(Debug)]
+struct CachedFrames {
+    layout: LayoutId,
+    fingerprint: u64,
+    inputs: LayoutInputs,
+    frames: HashMap<WindowId, CGRect>,
+}
+
+/// What the last layout pass over a layout was computed from.
+#[derive(Debug, Clone, PartialEq)]
+struct LayoutInputs {
+    screen: CGRect,
+    gaps: crate::common::config::GapSettings,
+    stack_line_thickness: f64,
+    stack_line_horiz: crate::common::config::HorizontalPlacement,
+    stack_line_vert: crate::common::config::VerticalPlacement,
+}
+
+/// Spaces whose active workspace or window list changed during a batch.
+#[derive(Debug, Default)]
+struct DeferredBroadcasts {
+    workspace: BTreeSet<SpaceId>,
+    windows: BTreeSet<SpaceId>,
+    /// `(space, window, workspace, by_rule)` for each window assigned.
+    assigned: Vec<(SpaceId, WindowId, VirtualWorkspaceId, bool)>,
+}
+
+/// App identity and window list (with the title and AX attributes that app
+/// rules match on) from one `WindowsOnScreenUpdated` event.
+type AppWindowsSeen = (
+    Option<(Option<String>, Option<String>)>,
+    Vec<(WindowId, Option<String>, Option<String>, Option<String>)>,
+);
+
+/// The app identity, title and AX attributes app rules match a window on.
+#[derive(Debug, Clone, Default)]
+struct RuleInputs {
+    bundle_id: Option<String>,
+    app_name: Option<String>,
+    title: Option<String>,
+    ax_role: Option<String>,
+    ax_subrole: Option<String>,
 }
 
 impl LayoutEngine {
     pub fn set_layout_settings(&mut self, settings: &LayoutSettings) {
         self.layout_settings = settings.clone();
+        self.frame_cache.clear();
+        Journal::configure(&mut self.journal, &settings.journal);
     }
 
+    /// Applies new workspace settings, then the app rules among them to the
+    /// windows already known. Returns the response for each space whose
+    /// windows the rules moved.
     pub fn update_virtual_workspace_settings(
         &mut self,
         settings: &crate::common::config::VirtualWorkspaceSettings,
-    ) {
-        self.virtual_workspace_manager.update_settings(settings);
+    ) -> Vec<(SpaceId, EventResponse)> {
+        let removed = self.virtual_workspace_manager.update_settings(settings);
+        for (space, workspace_id) in removed {
+            self.workspace_layouts.remove_workspace(space, workspace_id, &mut self.tree);
+        }
+        let mut responses = Vec::new();
+        for space in self.workspace_layouts.spaces() {
+            let response = self.reapply_app_rules(space, false);
+            if response.layout_changed {
+                self.broadcast_windows_changed(space);
+                responses.push((space, response));
+            }
+        }
+        self.last_app_windows.clear();
+        responses
     }
 
     pub fn layout_mode(&self) -> &'static str {
@@ -150,6 +471,45 @@ impl LayoutEngine {
         self.filter_active_workspace_windows(space, self.tree.visible_windows_in_layout(layout))
     }
 
+    /// Windows shown fullscreen in the active workspace of `space`, with
+    /// whether each is fullscreen within gaps.
+    pub fn fullscreen_windows(&self, space: SpaceId) -> Vec<(WindowId, bool)> {
+        match self.virtual_workspace_manager.active_workspace(space) {
+            Some(workspace_id) => self.fullscreen_windows_in_workspace(space, workspace_id),
+            None => Vec::new(),
+        }
+    }
+
+    /// Tiled windows of the active workspace of `space` that are hidden
+    /// behind another window of their stack.
+    pub fn occluded_windows(&self, space: SpaceId) -> Vec<WindowId> {
+        let Some(workspace_id) = self.virtual_workspace_manager.active_workspace(space) else {
+            return Vec::new();
+        };
+        let Some(layout) = self.workspace_layouts.active(space, workspace_id) else {
+            return Vec::new();
+        };
+        self.filter_active_workspace_windows(space, self.tree.occluded_windows(layout))
+    }
+
+    fn fullscreen_windows_in_workspace(
+        &self,
+        space: SpaceId,
+        workspace_id: VirtualWorkspaceId,
+    ) -> Vec<(WindowId, bool)> {
+        let Some(layout) = self.workspace_layouts.active(space, workspace_id) else {
+            return Vec::new();
+        };
+        self.tree
+            .fullscreen_windows(layout)
+            .into_iter()
+            .filter(|(wid, _)| {
+                self.virtual_workspace_manager.workspace_for_window(space, *wid)
+                    == Some(workspace_id)
+            })
+            .collect()
+    }
+
     fn active_floating_windows_in_workspace(&self, space: SpaceId) -> Vec<WindowId> {
         self.floating
             .active_flat(space)
@@ -185,19 +545,20 @@ impl LayoutEngine {
             focus_window = selected.or(visible);
         }
 
+        let floating_windows = self.active_floating_windows_in_workspace(space);
         if focus_window.is_none() {
-            let floating_windows = self.active_floating_windows_in_workspace(space);
             let floating_focus =
                 self.floating.last_focus().filter(|wid| floating_windows.contains(wid));
             focus_window = floating_focus.or_else(|| floating_windows.first().copied());
         }
+        let raise_windows = self.floating.z_ordered(workspace_id, &floating_windows);
 
         if let Some(wid) = focus_window {
             self.focused_window = Some(wid);
             self.virtual_workspace_manager
                 .set_last_focused_window(space, workspace_id, Some(wid));
             if self.floating.is_floating(wid) {
-                self.floating.set_last_focus(Some(wid));
+                self.note_floating_focus(space, wid);
             } else if let Some(layout) = self.workspace_layouts.active(space, workspace_id) {
                 let _ = self.tree.select_window(layout, wid);
             }
@@ -209,8 +570,110 @@ impl LayoutEngine {
 
         EventResponse {
             focus_window,
-            raise_windows: vec![],
+            raise_windows,
             workspace_changed_to: None,
+            layout_changed: false,
+            fullscreen_windows: self.fullscreen_windows_in_workspace(space, workspace_id),
+        }
+    }
+
+    fn was_focused_before_added(&mut self, wid: WindowId) -> bool {
+        match self.focused_before_added.take() {
+            Some((focused, at)) if focused == wid => at.elapsed() < FOCUS_BEFORE_ADD,
+            other => {
+                self.focused_before_added = other;
+                false
+            }
+        }
+    }
+
+    /// Records the space whose active workspace `wid` is about to leave, so
+    /// `switch_from_emptied_workspaces` can check whether it ends up empty.
+    fn note_window_leaving(&mut self, wid: WindowId) {
+        if let Some((space, workspace_id)) = self.window_owner(wid)
+            && self.virtual_workspace_manager.active_workspace(space) == Some(workspace_id)
+        {
+            self.maybe_emptied.insert(space, workspace_id);
+        }
+    }
+
+    fn active_workspace_is_empty(&self, space: SpaceId) -> bool {
+        self.visible_windows_in_space(space).is_empty()
+            && self.active_floating_windows_in_workspace(space).is_empty()
+    }
+
+    /// Applies `virtual_workspaces.on_empty` to every space whose active
+    /// workspace lost its last window since the previous call. Called once a
+    /// burst of events has been handled, so a window closing while another
+    /// opens does not switch away. A workspace that was switched to while
+    /// already empty is left alone.
+    pub fn switch_from_emptied_workspaces(&mut self) -> Vec<(SpaceId, EventResponse)> {
+        let spaces = std::mem::take(&mut self.maybe_emptied);
+        let mut responses = Vec::new();
+        for (space, emptied) in spaces {
+            let Some(current) = self.virtual_workspace_manager.active_workspace(space) else {
+                continue;
+            };
+            if current != emptied || !self.active_workspace_is_empty(space) {
+                continue;
+            }
+            let target = match self.virtual_workspace_manager.on_empty() {
+                OnEmptyWorkspace::Stay => None,
+                OnEmptyWorkspace::SwitchToPrevious => {
+                    self.virtual_workspace_manager.last_workspace(space)
+                }
+                OnEmptyWorkspace::SwitchToNearestNonempty => {
+                    self.nearest_nonempty_workspace(space, current)
+                }
+            };
+            let Some(target) = target.filter(|&target| target != current) else {
+                continue;
+            };
+            debug!(?space, ?current, ?target, "Active workspace emptied; switching");
+            if !self.virtual_workspace_manager.set_active_workspace(space, target) {
+                continue;
+            }
+            self.update_active_floating_windows(space);
+            self.broadcast_workspace_changed(space);
+            self.broadcast_windows_changed(space);
+            self.mark_dirty();
+            let mut response = self.refocus_workspace(space, target);
+            response.workspace_changed_to = Some(target);
+            responses.push((space, response));
+        }
+        responses
+    }
+
+    /// The workspace of `space` closest to `current` in list order that has
+    /// windows, preferring the earlier one on a tie.
+    fn nearest_nonempty_workspace(
+        &mut self,
+        space: SpaceId,
+        current: VirtualWorkspaceId,
+    ) -> Option<VirtualWorkspaceId> {
+        let workspaces = self.virtual_workspace_manager.list_workspaces(space);
+        let current_idx = workspaces.iter().position(|(id, _)| *id == current)?;
+        workspaces
+            .iter()
+            .enumerate()
+            .filter(|(_, (id, _))| {
+                *id != current
+                    && !self.virtual_workspace_manager.workspace_windows(space, *id).is_empty()
+            })
+            .min_by_key(|(idx, _)| (idx.abs_diff(current_idx), *idx))
+            .map(|(_, (id, _))| *id)
+    }
+
+    /// Remembers `wid` as the focused floating window and as the topmost
+    /// floating window of its workspace.
+    fn note_floating_focus(&mut self, space: SpaceId, wid: WindowId) {
+        self.floating.set_last_focus(Some(wid));
+        if let Some(workspace_id) = self
+            .virtual_workspace_manager
+            .workspace_for_window(space, wid)
+            .or_else(|| self.virtual_workspace_manager.active_workspace(space))
+        {
+            self.floating.note_raised(workspace_id, wid);
         }
     }
 
@@ -218,6 +681,10 @@ impl LayoutEngine {
         self.focused_window = Some(window_id);
     }
 
+    pub fn focused_window(&self) -> Option<WindowId> {
+        self.focused_window
+    }
+
     fn filter_active_workspace_windows(
         &self,
         space: SpaceId,
@@ -229,6 +696,47 @@ impl LayoutEngine {
             .collect()
     }
 
+    /// Orders `raise_windows` back to front, so that raising them in sequence
+    /// leaves `front` on top. Only the traditional layout has stacks whose
+    /// members can cover each other.
+    fn order_raise_windows(
+        &self,
+        layout: LayoutId,
+        front: Option<WindowId>,
+        raise_windows: Vec<WindowId>,
+    ) -> Vec<WindowId> {
+        let (LayoutSystemKind::Traditional(s), Some(front)) = (&self.tree, front) else {
+            return raise_windows;
+        };
+        let ordered = s.visible_windows_ordered(layout, front);
+        let (mut back, mut rest): (Vec<_>, Vec<_>) =
+            raise_windows.into_iter().partition(|wid| !ordered.contains(wid));
+        rest.sort_by_key(|wid| std::cmp::Reverse(ordered.iter().position(|w| w == wid)));
+        back.append(&mut rest);
+        back
+    }
+
+    /// Puts every window of the stacks `focus` sits in ahead of
+    /// `raise_windows` when `stack.raise_all_on_focus` is set.
+    /// `order_raise_windows` keeps the hidden members in the cascade order
+    /// given here.
+    fn with_whole_stack(
+        &self,
+        layout: LayoutId,
+        focus: Option<WindowId>,
+        raise_windows: Vec<WindowId>,
+    ) -> Vec<WindowId> {
+        let (LayoutSystemKind::Traditional(s), Some(focus), true) =
+            (&self.tree, focus, self.layout_settings.stack.raise_all_on_focus)
+        else {
+            return raise_windows;
+        };
+        let mut windows = s.stack_raise_order(layout, focus);
+        let rest: Vec<_> = raise_windows.into_iter().filter(|wid| !windows.contains(wid)).collect();
+        windows.extend(rest);
+        windows
+    }
+
     fn filter_active_workspace_window(
         &self,
         space: SpaceId,
@@ -237,15 +745,38 @@ impl LayoutEngine {
         window.filter(|wid| self.is_window_in_active_workspace(space, *wid))
     }
 
+    /// Drags the border between two tiled windows on `space`'s active
+    /// layout; see
+    /// [`crate::layout_engine::TraditionalLayoutSystem::resize_boundary_between`].
+    /// Always false for other layout systems.
+    pub fn resize_boundary_between(
+        &mut self,
+        space: SpaceId,
+        a: WindowId,
+        b: WindowId,
+        delta: f64,
+    ) -> bool {
+        let layout = self.layout(space);
+        let resized = match &mut self.tree {
+            LayoutSystemKind::Traditional(s) => s.resize_boundary_between(layout, a, b, delta),
+            _ => false,
+        };
+        if resized {
+            self.enforce_preserved_sizes(layout);
+        }
+        resized
+    }
+
     pub fn resize_selection(&mut self, layout: LayoutId, resize_amount: f64) {
         self.tree.resize_selection_by(layout, resize_amount);
+        self.enforce_preserved_sizes(layout);
     }
 
     fn apply_focus_response(&mut self, space: SpaceId, layout: LayoutId, response: &EventResponse) {
         if let Some(wid) = response.focus_window {
             self.focused_window = Some(wid);
             if self.floating.is_floating(wid) {
-                self.floating.set_last_focus(Some(wid));
+                self.note_floating_focus(space, wid);
             } else {
                 let _ = self.tree.select_window(layout, wid);
                 if let Some(wsid) = self.virtual_workspace_manager.active_workspace(space) {
@@ -255,6 +786,33 @@ impl LayoutEngine {
         }
     }
 
+    /// The response to toggling fullscreen on `layout`'s selection, which
+    /// was `selected` beforehand. Focus stays on that window, raised last,
+    /// so entering or leaving fullscreen never moves it elsewhere.
+    fn fullscreen_toggled(
+        &mut self,
+        space: SpaceId,
+        layout: LayoutId,
+        selected: Option<WindowId>,
+        raise_windows: Vec<WindowId>,
+    ) -> EventResponse {
+        let raise_windows = self.filter_active_workspace_windows(space, raise_windows);
+        if raise_windows.is_empty() {
+            return EventResponse::default();
+        }
+        let focus_window = selected.filter(|wid| raise_windows.contains(wid));
+        let raise_windows = self.order_raise_windows(layout, focus_window, raise_windows);
+        let response = EventResponse {
+            raise_windows,
+            focus_window,
+            workspace_changed_to: None,
+            layout_changed: false,
+            fullscreen_windows: Vec::new(),
+        };
+        self.apply_focus_response(space, layout, &response);
+        response
+    }
+
     fn move_focus_internal(
         &mut self,
         space: SpaceId,
@@ -266,6 +824,29 @@ impl LayoutEngine {
         let layout = self.layout(space);
 
         if is_floating {
+            let centers = self.active_window_centers(space);
+            if let Some(current) = self.focused_window
+                && let Some(&(_, from)) = centers.iter().find(|(wid, _)| *wid == current)
+            {
+                let target = Self::nearest_in_direction(
+                    centers.iter().filter(|(wid, _)| *wid != current).copied(),
+                    from,
+                    direction,
+                );
+                debug!(?target, "Floating navigation by geometry");
+                return match target {
+                    Some(target) => self.focus_in_place(space, layout, target),
+                    None => self
+                        .focus_space_in_direction(
+                            space,
+                            direction,
+                            visible_spaces,
+                            visible_space_centers,
+                        )
+                        .unwrap_or_default(),
+                };
+            }
+
             let floating_windows = self.active_floating_windows_in_workspace(space);
             debug!(
                 "Floating navigation: found {} floating windows: {:?}",
@@ -305,6 +886,8 @@ impl LayoutEngine {
                                 focus_window,
                                 raise_windows: vec![],
                                 workspace_changed_to: None,
+                                layout_changed: false,
+                                fullscreen_windows: Vec::new(),
                             };
                             self.apply_focus_response(space, layout, &response);
                             return response;
@@ -333,25 +916,41 @@ impl LayoutEngine {
                     focus_window: tiled_windows.first().copied(),
                     raise_windows: tiled_windows,
                     workspace_changed_to: None,
+                    layout_changed: false,
+                    fullscreen_windows: Vec::new(),
                 };
                 self.apply_focus_response(space, layout, &response);
                 return response;
             }
 
-            debug!("No windows to navigate to, returning default");
-            return EventResponse::default();
+            debug!("No windows to navigate to, trying the next space");
+            return self
+                .focus_space_in_direction(space, direction, visible_spaces, visible_space_centers)
+                .unwrap_or_default();
+        }
+
+        // A floating window nearer than any tile in `direction` takes focus.
+        // Tiled targets are left to the tree, which knows about stacks.
+        if let Some(target) = self.window_in_direction_by_geometry(space, direction, |_| true)
+            && self.floating.is_floating(target)
+        {
+            return self.focus_in_place(space, layout, target);
         }
 
         let previous_selection = self.tree.selected_window(layout);
 
         let (focus_window_raw, raise_windows) = self.tree.move_focus(layout, direction);
         let focus_window = self.filter_active_workspace_window(space, focus_window_raw);
+        let raise_windows = self.with_whole_stack(layout, focus_window, raise_windows);
         let raise_windows = self.filter_active_workspace_windows(space, raise_windows);
+        let raise_windows = self.order_raise_windows(layout, focus_window, raise_windows);
         if focus_window.is_some() {
             let response = EventResponse {
                 focus_window,
                 raise_windows,
                 workspace_changed_to: None,
+                layout_changed: false,
+                fullscreen_windows: Vec::new(),
             };
             self.apply_focus_response(space, layout, &response);
             response
@@ -359,33 +958,18 @@ impl LayoutEngine {
             if let Some(prev_wid) = previous_selection {
                 let _ = self.tree.select_window(layout, prev_wid);
             }
-            if let Some(new_space) = self.next_space_for_direction(
+            if let Some(target) = self.window_in_direction_by_geometry(space, direction, |wid| {
+                self.floating.is_floating(wid)
+            }) {
+                return self.focus_in_place(space, layout, target);
+            }
+            if let Some(response) = self.focus_space_in_direction(
                 space,
                 direction,
                 visible_spaces,
                 visible_space_centers,
             ) {
-                let new_layout = self.layout(new_space);
-                let windows_in_new_space = self.filter_active_workspace_windows(
-                    new_space,
-                    self.tree.visible_windows_in_layout(new_layout),
-                );
-                if let Some(target_window) = self
-                    .filter_active_workspace_window(
-                        new_space,
-                        self.tree.window_in_direction(new_layout, direction),
-                    )
-                    .or_else(|| windows_in_new_space.first().copied())
-                {
-                    let _ = self.tree.select_window(new_layout, target_window);
-                    let response = EventResponse {
-                        focus_window: Some(target_window),
-                        raise_windows: windows_in_new_space,
-                        workspace_changed_to: None,
-                    };
-                    self.apply_focus_response(new_space, new_layout, &response);
-                    return response;
-                }
+                return response;
             }
 
             let floating_windows = self.active_floating_windows_in_workspace(space);
@@ -396,6 +980,8 @@ impl LayoutEngine {
                     focus_window,
                     raise_windows: vec![],
                     workspace_changed_to: None,
+                    layout_changed: false,
+                    fullscreen_windows: Vec::new(),
                 };
                 self.apply_focus_response(space, layout, &response);
                 return response;
@@ -414,6 +1000,8 @@ impl LayoutEngine {
                     focus_window: Some(fallback_focus),
                     raise_windows: visible_windows,
                     workspace_changed_to: None,
+                    layout_changed: false,
+                    fullscreen_windows: Vec::new(),
                 };
                 self.apply_focus_response(space, layout, &response);
                 return response;
@@ -423,6 +1011,139 @@ impl LayoutEngine {
         }
     }
 
+    /// Focuses a tiled window of the space next to `space` in `direction`,
+    /// preferring the one on the side facing `space`. Returns `None` if there
+    /// is no such space or it has no tiled windows.
+    fn focus_space_in_direction(
+        &mut self,
+        space: SpaceId,
+        direction: Direction,
+        visible_spaces: &[SpaceId],
+        visible_space_centers: &HashMap<SpaceId, CGPoint>,
+    ) -> Option<EventResponse> {
+        let new_space =
+            self.next_space_for_direction(space, direction, visible_spaces, visible_space_centers)?;
+        let new_layout = self.layout(new_space);
+        let windows_in_new_space = self.filter_active_workspace_windows(
+            new_space,
+            self.tree.visible_windows_in_layout(new_layout),
+        );
+        let target_window = self
+            .filter_active_workspace_window(
+                new_space,
+                self.tree.window_in_direction(new_layout, direction),
+            )
+            .or_else(|| windows_in_new_space.first().copied())?;
+        let _ = self.tree.select_window(new_layout, target_window);
+        let response = EventResponse {
+            focus_window: Some(target_window),
+            raise_windows: windows_in_new_space,
+            workspace_changed_to: None,
+            layout_changed: false,
+            fullscreen_windows: Vec::new(),
+        };
+        self.apply_focus_response(new_space, new_layout, &response);
+        Some(response)
+    }
+
+    /// Handles a command issued while the active layout has no tiled windows.
+    ///
+    /// Window cycling goes straight to floating navigation and structural
+    /// commands are no-ops. Returns `None` when the command should take the
+    /// regular path, e.g. directional focus, which may leave the space, workspace
+    /// commands or cycling with no floating window to focus.
+    fn handle_command_on_empty_layout(
+        &mut self,
+        space: SpaceId,
+        layout: LayoutId,
+        command: &LayoutCommand,
+    ) -> Option<EventResponse> {
+        let forward = match command {
+            LayoutCommand::NextWindow => true,
+            LayoutCommand::PrevWindow => false,
+            LayoutCommand::Ascend
+            | LayoutCommand::Descend
+            | LayoutCommand::MoveNode(_)
+            | LayoutCommand::PromoteToMain
+            | LayoutCommand::FlipContainer
+            | LayoutCommand::JoinWindow(_)
+            | LayoutCommand::ToggleStack
+            | LayoutCommand::ToggleTabbed
+            | LayoutCommand::ToggleOrientation
+            | LayoutCommand::RotateSubtree
+            | LayoutCommand::RotateLayout { .. }
+            | LayoutCommand::MirrorLayout { .. }
+            | LayoutCommand::UnjoinWindows
+            | LayoutCommand::ToggleFullscreen
+            | LayoutCommand::ToggleFullscreenWithinGaps
+            | LayoutCommand::IsolateAndMaximize
+            | LayoutCommand::TogglePreserveSize
+            | LayoutCommand::ToggleContainerLock
+            | LayoutCommand::Undo
+            | LayoutCommand::Redo
+            | LayoutCommand::ResizeWindowGrow
+            | LayoutCommand::ResizeWindowShrink
+            | LayoutCommand::ResizeWindowBy { .. }
+            | LayoutCommand::ResizeWindowPx { .. }
+            | LayoutCommand::GoldenRatio { .. }
+            | LayoutCommand::TwoColumn { .. }
+            | LayoutCommand::SplitInto { .. }
+            | LayoutCommand::SwapWindows(..)
+            | LayoutCommand::FocusNth { .. } => {
+                debug!(?command, "Layout has no tiled windows; command is a no-op");
+                return Some(EventResponse::default());
+            }
+            _ => return None,
+        };
+
+        let floating_windows = self.active_floating_windows_in_workspace(space);
+        if floating_windows.is_empty() {
+            return None;
+        }
+
+        let len = floating_windows.len();
+        let current = self
+            .focused_window
+            .or_else(|| self.floating.last_focus())
+            .and_then(|wid| floating_windows.iter().position(|&w| w == wid));
+        let next_idx = match current {
+            Some(idx) if forward => (idx + 1) % len,
+            Some(idx) => (idx + len - 1) % len,
+            None => 0,
+        };
+        debug!(
+            "Layout has no tiled windows; focusing floating window {:?}",
+            floating_windows[next_idx]
+        );
+
+        let response = EventResponse {
+            focus_window: Some(floating_windows[next_idx]),
+            raise_windows: vec![],
+            workspace_changed_to: None,
+            layout_changed: false,
+            fullscreen_windows: Vec::new(),
+        };
+        self.apply_focus_response(space, layout, &response);
+        Some(response)
+    }
+
+    /// Converts a resize by `pixels` to the screen fraction `ResizeWindowBy`
+    /// takes, using the width of `space` for left and right and its height
+    /// for up and down.
+    fn resize_amount_for_pixels(
+        &self,
+        space: SpaceId,
+        direction: Direction,
+        pixels: f64,
+    ) -> Option<f64> {
+        let size = self.workspace_layouts.active_size(space)?;
+        let extent = match direction.orientation() {
+            Orientation::Horizontal => size.width,
+            Orientation::Vertical => size.height,
+        };
+        (extent > 0.0).then(|| pixels / extent)
+    }
+
     fn next_space_for_direction(
         &self,
         current_space: SpaceId,
@@ -464,6 +1185,143 @@ impl LayoutEngine {
         }
     }
 
+    /// The space a `MoveFocus` in `direction` would hand focus to, or `None`
+    /// if the move stays on `space` (or would not move focus at all). Only
+    /// predicts; the selection is left alone.
+    pub fn would_cross_space(
+        &self,
+        space: SpaceId,
+        direction: Direction,
+        visible_spaces: &[SpaceId],
+        visible_space_centers: &HashMap<SpaceId, CGPoint>,
+    ) -> Option<SpaceId> {
+        if let Some(current) = self.focused_window
+            && self.floating.is_floating(current)
+        {
+            // Floating focus moves by geometry and only leaves the space when
+            // no window lies in `direction`. Without a known frame it cycles
+            // the floating windows in place.
+            let centers = self.active_window_centers(space);
+            let &(_, from) = centers.iter().find(|(wid, _)| *wid == current)?;
+            let others = centers.iter().filter(|(wid, _)| *wid != current).copied();
+            if Self::nearest_in_direction(others, from, direction).is_some() {
+                return None;
+            }
+        } else {
+            let workspace = self.virtual_workspace_manager.active_workspace(space)?;
+            let layout = self.workspace_layouts.active(space, workspace)?;
+            let target = self.tree.focus_target_in_direction(layout, direction);
+            if self.filter_active_workspace_window(space, target).is_some()
+                || self
+                    .window_in_direction_by_geometry(space, direction, |wid| {
+                        self.floating.is_floating(wid)
+                    })
+                    .is_some()
+            {
+                return None;
+            }
+        }
+        let new_space =
+            self.next_space_for_direction(space, direction, visible_spaces, visible_space_centers)?;
+        let new_workspace = self.virtual_workspace_manager.active_workspace(new_space)?;
+        let new_layout = self.workspace_layouts.active(new_space, new_workspace)?;
+        let has_target = !self
+            .filter_active_workspace_windows(
+                new_space,
+                self.tree.visible_windows_in_layout(new_layout),
+            )
+            .is_empty();
+        has_target.then_some(new_space)
+    }
+
+    /// Centers of the windows on the active workspace of `space`: tiled ones
+    /// from the last layout pass, floating ones from their stored positions.
+    /// Windows without a known frame are left out.
+    fn active_window_centers(&self, space: SpaceId) -> Vec<(WindowId, CGPoint)> {
+        let Some(workspace) = self.virtual_workspace_manager.active_workspace(space) else {
+            return Vec::new();
+        };
+        let mut centers = Vec::new();
+        if let Some(layout) = self.workspace_layouts.active(space, workspace)
+            && let Some(frames) = self.tiled_frames.get(&layout)
+        {
+            let visible = self.tree.visible_windows_in_layout(layout);
+            centers.extend(
+                frames
+                    .iter()
+                    .filter(|(wid, _)| visible.contains(wid))
+                    .map(|&(wid, frame)| (wid, frame.mid())),
+            );
+        }
+        let floating = self.active_floating_windows_in_workspace(space);
+        centers.extend(
+            self.virtual_workspace_manager
+                .get_workspace_floating_positions(space, workspace)
+                .into_iter()
+                .filter(|(wid, _)| floating.contains(wid))
+                .map(|(wid, frame)| (wid, frame.mid())),
+        );
+        centers
+    }
+
+    /// The window nearest the focused window of `space` in `direction` among
+    /// those `accept` lets through, if the focused window's frame is known.
+    fn window_in_direction_by_geometry(
+        &self,
+        space: SpaceId,
+        direction: Direction,
+        accept: impl Fn(WindowId) -> bool,
+    ) -> Option<WindowId> {
+        let current = self.focused_window?;
+        let centers = self.active_window_centers(space);
+        let &(_, from) = centers.iter().find(|(wid, _)| *wid == current)?;
+        Self::nearest_in_direction(
+            centers.into_iter().filter(|&(wid, _)| wid != current && accept(wid)),
+            from,
+            direction,
+        )
+    }
+
+    /// The candidate whose center is nearest `from` in `direction`, scored like
+    /// spaces are. Ties go to the candidate best aligned with `from`.
+    fn nearest_in_direction(
+        candidates: impl IntoIterator<Item = (WindowId, CGPoint)>,
+        from: CGPoint,
+        direction: Direction,
+    ) -> Option<WindowId> {
+        candidates
+            .into_iter()
+            .filter_map(|(wid, center)| {
+                let delta = Self::directional_delta(direction, &from, &center)?;
+                let offset = match direction.orientation() {
+                    Orientation::Horizontal => (center.y - from.y).abs(),
+                    Orientation::Vertical => (center.x - from.x).abs(),
+                };
+                Some((wid, delta, offset))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1).then(a.2.total_cmp(&b.2)))
+            .map(|(wid, ..)| wid)
+    }
+
+    /// Focuses `wid` on `space` without leaving it. A tiled window is raised
+    /// so it comes up over the floating ones.
+    fn focus_in_place(&mut self, space: SpaceId, layout: LayoutId, wid: WindowId) -> EventResponse {
+        let raise_windows = if self.floating.is_floating(wid) {
+            vec![]
+        } else {
+            vec![wid]
+        };
+        let response = EventResponse {
+            focus_window: Some(wid),
+            raise_windows,
+            ..EventResponse::default()
+        };
+        self.apply_focus_response(space, layout, &response);
+        response
+    }
+
+    // Frames and screen centers are in Quartz coordinates, where y grows
+    // downward.
     fn directional_delta(
         direction: Direction,
         current: &CGPoint,
@@ -479,11 +1337,11 @@ impl LayoutEngine {
                 if delta > 0.0 { Some(delta) } else { None }
             }
             Direction::Up => {
-                let delta = candidate.y - current.y;
+                let delta = current.y - candidate.y;
                 if delta > 0.0 { Some(delta) } else { None }
             }
             Direction::Down => {
-                let delta = current.y - candidate.y;
+                let delta = candidate.y - current.y;
                 if delta > 0.0 { Some(delta) } else { None }
             }
         }
@@ -505,6 +1363,60 @@ impl LayoutEngine {
         None
     }
 
+    /// The space and workspace `wid` is assigned to, searching every known
+    /// space rather than only the active workspaces.
+    fn window_owner(&self, wid: WindowId) -> Option<(SpaceId, VirtualWorkspaceId)> {
+        self.space_with_window(wid)
+            .into_iter()
+            .chain(self.workspace_layouts.spaces())
+            .find_map(|space| {
+                self.virtual_workspace_manager
+                    .workspace_for_window(space, wid)
+                    .map(|workspace| (space, workspace))
+            })
+    }
+
+    /// Resolves the layout `SwapWindows(a, b)` operates on. Both windows must
+    /// be tiled in the same workspace of the same space.
+    pub fn validate_swap(&self, a: WindowId, b: WindowId) -> Result<LayoutId, WindowCommandError> {
+        let (a_space, a_workspace) =
+            self.window_owner(a).ok_or(WindowCommandError::UnknownWindow(a))?;
+        let (b_space, b_workspace) =
+            self.window_owner(b).ok_or(WindowCommandError::UnknownWindow(b))?;
+        if a_space != b_space {
+            return Err(WindowCommandError::CrossSpace {
+                window: a,
+                space: a_space,
+                other: b,
+                other_space: b_space,
+            });
+        }
+        if a_workspace != b_workspace {
+            return Err(WindowCommandError::CrossWorkspace { window: a, other: b });
+        }
+        let layout = self
+            .workspace_layouts
+            .active(a_space, a_workspace)
+            .ok_or(WindowCommandError::UnknownWindow(a))?;
+        for wid in [a, b] {
+            if self.floating.is_floating(wid) || !self.tree.contains_window(layout, wid) {
+                return Err(WindowCommandError::NotTiled(wid));
+            }
+        }
+        Ok(layout)
+    }
+
+    /// Finds the window with index `idx`, preferring `space` but falling back
+    /// to every other known space.
+    fn find_window_by_idx(&self, space: SpaceId, idx: u32) -> Option<WindowId> {
+        self.virtual_workspace_manager.find_window_by_idx(space, idx).or_else(|| {
+            self.workspace_layouts
+                .spaces()
+                .into_iter()
+                .find_map(|space| self.virtual_workspace_manager.find_window_by_idx(space, idx))
+        })
+    }
+
     fn active_workspace_id_and_name(
         &self,
         space_id: SpaceId,
@@ -519,6 +1431,11 @@ impl LayoutEngine {
     }
 
     pub fn update_space_display(&mut self, space: SpaceId, display_uuid: Option<String>) {
+        let removed =
+            self.virtual_workspace_manager.set_space_display(space, display_uuid.as_deref());
+        for workspace_id in removed {
+            self.workspace_layouts.remove_workspace(space, workspace_id, &mut self.tree);
+        }
         if let Some(uuid) = display_uuid {
             self.space_display_map.insert(space, Some(uuid.clone()));
             self.display_last_space.insert(uuid, space);
@@ -562,6 +1479,7 @@ impl LayoutEngine {
         if let Some(uuid) = self.space_display_map.remove(&old_space) {
             self.space_display_map.insert(new_space, uuid);
         }
+        self.broadcast_trees.remove(&old_space);
 
         for (_uuid, space) in self.display_last_space.iter_mut() {
             if *space == old_space {
@@ -578,6 +1496,8 @@ impl LayoutEngine {
         self.space_display_map.retain(|_, uuid_opt| {
             uuid_opt.as_ref().map(|uuid| active.contains(uuid.as_str())).unwrap_or(false)
         });
+        self.broadcast_trees
+            .retain(|space, _| self.space_display_map.contains_key(space));
     }
 
     pub fn new(
@@ -592,9 +1512,9 @@ impl LayoutEngine {
             crate::common::config::LayoutMode::Traditional => LayoutSystemKind::Traditional(
                 crate::layout_engine::TraditionalLayoutSystem::default(),
             ),
-            crate::common::config::LayoutMode::Bsp => {
-                LayoutSystemKind::Bsp(crate::layout_engine::BspLayoutSystem::default())
-            }
+            crate::common::config::LayoutMode::Bsp => LayoutSystemKind::Bsp(
+                crate::layout_engine::BspLayoutSystem::new(layout_settings.bsp.clone()),
+            ),
             crate::common::config::LayoutMode::Dwindle => {
                 LayoutSystemKind::Dwindle(crate::layout_engine::systems::DwindleLayoutSystem::new(
                     layout_settings.dwindle.clone(),
@@ -602,6 +1522,9 @@ impl LayoutEngine {
             }
         };
 
+        let mut journal = None;
+        Journal::configure(&mut journal, &layout_settings.journal);
+
         LayoutEngine {
             tree,
             workspace_layouts: WorkspaceLayouts::default(),
@@ -612,6 +1535,22 @@ impl LayoutEngine {
             broadcast_tx,
             space_display_map: HashMap::default(),
             display_last_space: HashMap::default(),
+            app_bundle_ids: HashMap::default(),
+            last_app_windows: HashMap::default(),
+            window_rule_inputs: HashMap::default(),
+            deferred_broadcasts: None,
+            pending_space_events: PendingSpaceEvents::default(),
+            maybe_emptied: BTreeMap::new(),
+            focused_before_added: None,
+            metrics: Metrics::default(),
+            journal,
+            tiled_frames: HashMap::default(),
+            layout_inputs: HashMap::default(),
+            auto_stacked: HashSet::default(),
+            frame_cache: HashMap::default(),
+            window_display_hints: HashMap::default(),
+            autosave: None,
+            broadcast_trees: HashMap::default(),
         }
     }
 
@@ -619,6 +1558,52 @@ impl LayoutEngine {
         self.debug_tree_desc(space, "", false);
     }
 
+    pub fn draw_tree_with_options(
+        &self,
+        space: SpaceId,
+        options: &TreeRenderOptions,
+    ) -> Option<String> {
+        let workspace_id = self.virtual_workspace_manager.active_workspace(space)?;
+        let layout = self.workspace_layouts.active(space, workspace_id)?;
+        Some(self.tree.draw_tree_with_options(layout, options))
+    }
+
+    /// The active layout of `space` with its fingerprint.
+    pub fn tree_snapshot(&self, space: SpaceId) -> Option<TreeSnapshot> {
+        let workspace_id = self.virtual_workspace_manager.active_workspace(space)?;
+        let layout = self.workspace_layouts.active(space, workspace_id)?;
+        Some(TreeSnapshot {
+            ascii: self.tree.draw_tree(layout),
+            fingerprint: self.tree.layout_fingerprint(layout),
+        })
+    }
+
+    /// Broadcasts the tree of `space` if its active layout changed since the
+    /// last broadcast. Only the fingerprint is computed when it has not, so
+    /// this is cheap enough to call after every layout pass.
+    pub fn broadcast_tree_if_changed(&mut self, space: SpaceId) {
+        let Some(ref broadcast_tx) = self.broadcast_tx else {
+            return;
+        };
+        let Some(layout) = self
+            .virtual_workspace_manager
+            .active_workspace(space)
+            .and_then(|workspace_id| self.workspace_layouts.active(space, workspace_id))
+        else {
+            return;
+        };
+        let fingerprint = self.tree.layout_fingerprint(layout);
+        if self.broadcast_trees.insert(space, (layout, fingerprint)) == Some((layout, fingerprint))
+        {
+            return;
+        }
+        broadcast_tx.send(BroadcastEvent::LayoutTreeChanged {
+            space_id: space,
+            tree: self.tree.draw_tree(layout),
+            fingerprint,
+        });
+    }
+
     pub fn debug_tree_desc(&self, space: SpaceId, desc: &'static str, print: bool) {
         if let Some(workspace_id) = self.virtual_workspace_manager.active_workspace(space) {
             if let Some(layout) = self.workspace_layouts.active(space, workspace_id) {
@@ -635,22 +1620,140 @@ impl LayoutEngine {
         }
     }
 
+    /// Ensures every workspace in `workspaces` has an active layout on
+    /// `space`, giving layouts created from scratch the configured root kind.
+    fn ensure_active_layouts(
+        &mut self,
+        space: SpaceId,
+        size: CGSize,
+        workspaces: impl IntoIterator<Item = VirtualWorkspaceId>,
+    ) {
+        let created =
+            self.workspace_layouts
+                .ensure_active_for_space(space, size, workspaces, &mut self.tree);
+        if let LayoutSystemKind::Traditional(s) = &mut self.tree {
+            for layout in created {
+                s.set_root_kind(layout, self.layout_settings.default_root_kind);
+            }
+        }
+    }
+
     pub fn handle_event(&mut self, event: LayoutEvent) -> EventResponse {
+        // Focus is not saved, so a focus change leaves nothing to write.
+        if !matches!(event, LayoutEvent::WindowFocused(..)) {
+            self.mark_dirty();
+        }
+        let kind: &'static str = (&event).into();
+        self.metrics.count_event(kind);
+        let _span = self
+            .layout_settings
+            .trace_timings
+            .then(|| tracing::debug_span!("layout_event", kind).entered());
+        let start = Instant::now();
+        let space = self.event_space(&event);
+        let detail = self.journal.as_ref().map(|journal| journal.describe_event(&event));
+        let response = self.handle_event_internal(event);
+        if let Some(space) = space {
+            self.enforce_preserved_sizes_on(space);
+        }
+        if let Some(detail) = detail {
+            self.record_in_journal(JournalEntryKind::Event, kind, space, detail);
+        }
+        if self.layout_settings.trace_timings {
+            debug!(kind, elapsed = ?start.elapsed(), "Handled layout event");
+        }
+        response
+    }
+
+    /// The space `event` applies to, for the journal.
+    fn event_space(&self, event: &LayoutEvent) -> Option<SpaceId> {
+        match event {
+            LayoutEvent::SpaceExposed(space, _) => Some(*space),
+            LayoutEvent::WindowRemoved(wid) | LayoutEvent::WindowResized { wid, .. } => {
+                self.window_owner(*wid).map(|(space, _)| space)
+            }
+            _ => PendingSpaceEvents::space_of(event),
+        }
+    }
+
+    fn record_in_journal(
+        &mut self,
+        kind: JournalEntryKind,
+        name: &'static str,
+        space: Option<SpaceId>,
+        detail: String,
+    ) {
+        let fingerprint = space.and_then(|space| {
+            let workspace = self.virtual_workspace_manager.active_workspace(space)?;
+            let layout = self.workspace_layouts.active(space, workspace)?;
+            Some(self.tree.layout_fingerprint(layout))
+        });
+        if let Some(journal) = &mut self.journal {
+            journal.record(JournalEntry {
+                timestamp_ms: timestamp_ms(),
+                space,
+                kind,
+                name: name.to_string(),
+                detail,
+                fingerprint,
+            });
+        }
+    }
+
+    /// The journaled events and commands for `space`, oldest first, along
+    /// with those that applied to every space. Empty while the journal is off.
+    pub fn dump_journal(&self, space: SpaceId) -> Vec<JournalEntry> {
+        self.journal
+            .as_ref()
+            .map(|journal| journal.entries_for(space))
+            .unwrap_or_default()
+    }
+
+    fn handle_event_internal(&mut self, event: LayoutEvent) -> EventResponse {
         debug!(?event);
+        if let Some(space) = PendingSpaceEvents::space_of(&event)
+            && !self.workspace_layouts.contains_space(space)
+        {
+            debug!(?space, "Space not exposed yet; holding event until it is");
+            self.pending_space_events.push(space, event, Instant::now());
+            return EventResponse::default();
+        }
         match event {
             LayoutEvent::SpaceExposed(space, size) => {
                 self.debug_tree(space);
+                self.last_app_windows.retain(|&(s, _), _| s != space);
 
                 let workspaces =
                     self.virtual_workspace_manager_mut().list_workspaces(space).to_vec();
-                self.workspace_layouts.ensure_active_for_space(
-                    space,
-                    size,
-                    workspaces.into_iter().map(|(id, _)| id),
-                    &mut self.tree,
-                );
+                self.ensure_active_layouts(space, size, workspaces.into_iter().map(|(id, _)| id));
+
+                let mut response = EventResponse::default();
+                for pending in self.pending_space_events.take(space, Instant::now()) {
+                    response.merge(self.handle_event(pending));
+                }
+                return response;
             }
             LayoutEvent::WindowsOnScreenUpdated(space, pid, windows_with_titles, app_info) => {
+                for (wid, title, ax_role, ax_subrole) in &windows_with_titles {
+                    let inputs = RuleInputs {
+                        bundle_id: app_info.as_ref().and_then(|i| i.bundle_id.clone()),
+                        app_name: app_info.as_ref().and_then(|i| i.localized_name.clone()),
+                        title: title.clone(),
+                        ax_role: ax_role.clone(),
+                        ax_subrole: ax_subrole.clone(),
+                    };
+                    self.window_rule_inputs.insert(*wid, inputs);
+                }
+                let seen = (
+                    app_info.as_ref().map(|i| (i.bundle_id.clone(), i.localized_name.clone())),
+                    windows_with_titles.clone(),
+                );
+                if self.last_app_windows.get(&(space, pid)) == Some(&seen) {
+                    trace!(?space, pid, "Window list unchanged; skipping");
+                    return EventResponse::default();
+                }
+                self.last_app_windows.insert((space, pid), seen);
+
                 self.debug_tree(space);
                 self.floating.clear_active_for_app(space, pid);
 
@@ -663,6 +1766,9 @@ impl LayoutEngine {
                     Some(info) => (info.bundle_id.as_deref(), info.localized_name.as_deref()),
                     None => (None, None),
                 };
+                if let Some(bundle_id) = app_bundle_id {
+                    self.app_bundle_ids.insert(pid, bundle_id.to_string());
+                }
 
                 for (wid, title_opt, ax_role_opt, ax_subrole_opt) in windows_with_titles {
                     let title_ref = title_opt.as_deref();
@@ -670,6 +1776,8 @@ impl LayoutEngine {
                     let ax_subrole_ref = ax_subrole_opt.as_deref();
 
                     let was_floating = self.floating.is_floating(wid);
+                    let is_new =
+                        self.virtual_workspace_manager.workspace_for_window(space, wid).is_none();
                     let assignment = match self
                         .virtual_workspace_manager
                         .assign_window_with_app_info(
@@ -687,8 +1795,11 @@ impl LayoutEngine {
                             match self.virtual_workspace_manager.auto_assign_window(wid, space) {
                                 Ok(ws) => Some(AppRuleAssignment {
                                     workspace_id: ws,
+                                    by_rule: false,
                                     floating: was_floating,
                                     prev_rule_decision: false,
+                                    opacity: None,
+                                    always_on_top: false,
                                 }),
                                 Err(_) => {
                                     warn!(
@@ -701,14 +1812,24 @@ impl LayoutEngine {
                         }
                     };
 
+                    let Some(assignment) = assignment else {
+                        continue;
+                    };
+                    if is_new {
+                        self.broadcast_window_assigned(
+                            space,
+                            wid,
+                            assignment.workspace_id,
+                            assignment.by_rule,
+                        );
+                    }
+                    self.set_display_hints(space, wid, assignment.display_hints());
                     let AppRuleAssignment {
                         workspace_id: assigned_workspace,
                         floating: rule_says_float,
                         prev_rule_decision,
-                    } = match assignment {
-                        Some(assign) => assign,
-                        None => continue,
-                    };
+                        ..
+                    } = assignment;
 
                     let should_float = rule_says_float || (!prev_rule_decision && was_floating);
 
@@ -720,7 +1841,10 @@ impl LayoutEngine {
                     }
 
                     if !self.floating.is_floating(wid) {
-                        windows_by_workspace.entry(assigned_workspace).or_default().push(wid);
+                        let windows = windows_by_workspace.entry(assigned_workspace).or_default();
+                        if !windows.contains(&wid) {
+                            windows.push(wid);
+                        }
                     }
 
                     self.virtual_workspace_manager_mut().set_last_rule_decision(
@@ -768,24 +1892,58 @@ impl LayoutEngine {
 
                     if current_sorted != desired_sorted {
                         any_changed = true;
-                        self.tree.set_windows_for_app(layout, pid, desired);
+                        let start = Instant::now();
+                        if self.layout_settings.insert_near_app_windows
+                            && let LayoutSystemKind::Traditional(s) = &mut self.tree
+                        {
+                            s.set_windows_for_app_near_app(layout, pid, desired);
+                        } else {
+                            self.tree.set_windows_for_app(layout, pid, desired);
+                        }
+                        self.metrics.set_windows_for_app.record_since(start);
+                        // Adding windows never moves the selection by itself;
+                        // only a window that got focus before it arrived does.
+                        if let Some((focused, _)) = self.focused_before_added
+                            && focused.pid == pid
+                            && self.tree.contains_window(layout, focused)
+                            && self.was_focused_before_added(focused)
+                        {
+                            self.tree.select_window(layout, focused);
+                        }
+                        self.auto_stack(layout);
                     }
                 }
 
                 if any_changed {
                     self.broadcast_windows_changed(space);
-                    self.rebalance_all_layouts();
+                    self.rebalance_space(space);
                 }
             }
             LayoutEvent::AppClosed(pid) => {
+                self.last_app_windows.retain(|&(_, p), _| p != pid);
+                self.window_rule_inputs.retain(|wid, _| wid.pid != pid);
+                self.pending_space_events.forget_app(pid);
+                let app_windows: Vec<_> = self
+                    .virtual_workspace_manager
+                    .window_to_workspace
+                    .keys()
+                    .filter(|(_, wid)| wid.pid == pid)
+                    .map(|&(_, wid)| wid)
+                    .collect();
+                for wid in app_windows {
+                    self.note_window_leaving(wid);
+                }
                 self.tree.remove_windows_for_app(pid);
                 self.floating.remove_all_for_pid(pid);
 
                 self.virtual_workspace_manager.remove_windows_for_app(pid);
                 self.virtual_workspace_manager.remove_app_floating_positions(pid);
+                self.app_bundle_ids.remove(&pid);
+                self.window_display_hints.retain(|wid, _| wid.pid != pid);
             }
             LayoutEvent::WindowAdded(space, wid) => {
                 self.debug_tree(space);
+                self.last_app_windows.remove(&(space, wid.pid));
 
                 let assigned_workspace =
                     match self.virtual_workspace_manager.workspace_for_window(space, wid) {
@@ -810,7 +1968,14 @@ impl LayoutEngine {
                     self.workspace_layouts.active(space, assigned_workspace)
                 {
                     if !self.tree.contains_window(layout, wid) {
-                        self.tree.add_window_after_selection(layout, wid);
+                        if self.was_focused_before_added(wid) {
+                            self.tree.add_window_after_selection(layout, wid);
+                        } else {
+                            // Background windows must not move where the next
+                            // split or stack lands.
+                            self.tree.insert_window_after_selection(layout, wid);
+                        }
+                        self.auto_stack(layout);
                     }
                 } else {
                     warn!(
@@ -822,7 +1987,12 @@ impl LayoutEngine {
                 self.broadcast_windows_changed(space);
             }
             LayoutEvent::WindowRemoved(wid) => {
+                self.last_app_windows.retain(|&(_, p), _| p != wid.pid);
+                self.window_rule_inputs.remove(&wid);
+                self.pending_space_events.forget_window(wid);
                 let affected_space: Option<SpaceId> = self.space_with_window(wid);
+                let owner = self.window_owner(wid);
+                self.note_window_leaving(wid);
 
                 self.tree.remove_window(wid);
 
@@ -831,6 +2001,8 @@ impl LayoutEngine {
                 self.virtual_workspace_manager.remove_window(wid);
 
                 self.virtual_workspace_manager.remove_floating_position(wid);
+                self.virtual_workspace_manager.set_manage_override(wid, None);
+                self.window_display_hints.remove(&wid);
 
                 if self.focused_window == Some(wid) {
                     self.focused_window = None;
@@ -840,15 +2012,55 @@ impl LayoutEngine {
                     self.broadcast_windows_changed(space);
                 }
 
-                self.rebalance_all_layouts();
+                // Without a rebalance, removal has already folded the window's
+                // share into its siblings in proportion to their sizes.
+                if self.layout_settings.rebalance_on_remove
+                    && let Some((space, workspace)) = owner
+                {
+                    self.rebalance_workspace(space, workspace);
+                }
+            }
+            LayoutEvent::WindowMovedToSpace { wid, from, to } => {
+                let Some(from) = from.or_else(|| self.window_owner(wid).map(|(space, _)| space))
+                else {
+                    return self.handle_event_internal(LayoutEvent::WindowAdded(to, wid));
+                };
+                if from == to {
+                    return EventResponse::default();
+                }
+                let Some(size) = self.workspace_layouts.active_size(to) else {
+                    warn!(?to, "No layouts for destination space; not moving {wid:?}");
+                    return EventResponse::default();
+                };
+
+                self.note_window_leaving(wid);
+                let moved = self.move_window_to_space(from, to, size, wid);
+                if moved.focus_window.is_none() {
+                    return moved;
+                }
+                if self.layout_settings.rebalance_on_remove {
+                    self.rebalance_space(from);
+                    self.rebalance_space(to);
+                }
+
+                // Everything visible on the destination shifts to make room,
+                // so all of it needs its frame re-applied.
+                return EventResponse {
+                    raise_windows: self.visible_windows_in_space(to),
+                    focus_window: moved.focus_window,
+                    layout_changed: true,
+                    ..EventResponse::default()
+                };
             }
             LayoutEvent::WindowFocused(space, wid) => {
                 self.focused_window = Some(wid);
                 if self.floating.is_floating(wid) {
-                    self.floating.set_last_focus(Some(wid));
+                    self.note_floating_focus(space, wid);
                 } else {
                     let layout = self.layout(space);
-                    let _ = self.tree.select_window(layout, wid);
+                    if !self.tree.select_window(layout, wid) {
+                        self.focused_before_added = Some((wid, Instant::now()));
+                    }
                     let workspace_id = self
                         .virtual_workspace_manager
                         .workspace_for_window(space, wid)
@@ -896,10 +2108,245 @@ impl LayoutEngine {
         visible_spaces: &[SpaceId],
         visible_space_centers: &HashMap<SpaceId, CGPoint>,
         command: LayoutCommand,
+    ) -> EventResponse {
+        self.last_app_windows.clear();
+        let kind: &'static str = (&command).into();
+        self.metrics.count_command(kind);
+        let _span = self
+            .layout_settings
+            .trace_timings
+            .then(|| tracing::debug_span!("layout_command", kind).entered());
+        let start = Instant::now();
+        let detail = self.journal.as_ref().map(|journal| journal.describe_command(&command));
+        let before = self.layout_fingerprints();
+        let mut response =
+            self.handle_command_internal(space, visible_spaces, visible_space_centers, command);
+        let after = self.layout_fingerprints();
+        if after != before {
+            for &(layout, fingerprint) in &after {
+                if !before.contains(&(layout, fingerprint)) {
+                    self.limit_depth(layout);
+                    self.enforce_preserved_sizes(layout);
+                }
+            }
+        }
+        response.layout_changed = after != before;
+        if response.layout_changed || response.workspace_changed_to.is_some() {
+            self.mark_dirty();
+        }
+        if let Some(detail) = detail {
+            self.record_in_journal(JournalEntryKind::Command, kind, space, detail);
+        }
+        if self.layout_settings.trace_timings {
+            debug!(kind, elapsed = ?start.elapsed(), "Handled layout command");
+        }
+        response
+    }
+
+    /// Flips the always-floating override for the focused window's app and
+    /// applies it to the app's windows right away. Returns the app's bundle id
+    /// and its new state, or `None` if the focused app has no known bundle id.
+    pub fn toggle_focused_app_always_floating(&mut self) -> Option<(String, bool)> {
+        let pid = self.focused_window?.pid;
+        let bundle_id = self.app_bundle_ids.get(&pid)?.clone();
+        let floating = !self.virtual_workspace_manager.is_app_always_floating(&bundle_id);
+        self.virtual_workspace_manager
+            .set_app_always_floating(bundle_id.clone(), floating);
+        self.apply_app_floating(pid, floating);
+        Some((bundle_id, floating))
+    }
+
+    /// Floats every tiled window of `pid`, or re-tiles the ones app rules
+    /// floated, mirroring what the next `WindowsOnScreenUpdated` would do.
+    fn apply_app_floating(&mut self, pid: pid_t, floating: bool) {
+        self.last_app_windows.retain(|&(_, p), _| p != pid);
+        let windows: Vec<_> = self
+            .virtual_workspace_manager
+            .window_to_workspace
+            .iter()
+            .filter(|((_, wid), _)| wid.pid == pid)
+            .map(|(&(space, wid), &workspace)| (space, wid, workspace))
+            .collect();
+        let mut spaces = HashSet::default();
+        for (space, wid, workspace) in windows {
+            if self.apply_rule_floating(space, workspace, wid, floating) {
+                spaces.insert(space);
+            }
+        }
+        for space in spaces {
+            self.broadcast_windows_changed(space);
+        }
+    }
+
+    /// Floats `wid`, which is on `workspace` of `space`, or re-tiles it if the
+    /// app rules floated it. Returns whether anything changed.
+    fn apply_rule_floating(
+        &mut self,
+        space: SpaceId,
+        workspace: VirtualWorkspaceId,
+        wid: WindowId,
+        floating: bool,
+    ) -> bool {
+        let is_floating = self.floating.is_floating(wid);
+        let is_active = self.virtual_workspace_manager.active_workspace(space) == Some(workspace);
+        if floating && !is_floating {
+            self.tree.remove_window(wid);
+            self.floating.add_floating(wid);
+            if is_active {
+                self.floating.add_active(space, wid.pid, wid);
+            }
+        } else if !floating
+            && is_floating
+            && self.virtual_workspace_manager.last_rule_decision(space, wid)
+        {
+            self.floating.remove_floating(wid);
+            self.floating.remove_active(space, wid.pid, wid);
+            if let Some(layout) = self.workspace_layouts.active(space, workspace) {
+                self.tree.add_window_after_selection(layout, wid);
+            }
+        } else {
+            return false;
+        }
+        self.virtual_workspace_manager.set_last_rule_decision(space, wid, floating);
+        true
+    }
+
+    /// Moves `wid` from workspace `from` of `space` to `to`, out of the layout
+    /// or floating set of one and into the other's. Returns false, leaving
+    /// the window where it was, if it cannot be assigned to `to`.
+    fn transfer_window(
+        &mut self,
+        space: SpaceId,
+        wid: WindowId,
+        from: VirtualWorkspaceId,
+        to: VirtualWorkspaceId,
+    ) -> bool {
+        let is_floating = self.floating.is_floating(wid);
+
+        if is_floating {
+            self.floating.remove_active(space, wid.pid, wid);
+        } else if self.workspace_layouts.active(space, from).is_some() {
+            self.tree.remove_window(wid);
+        }
+
+        if !self.virtual_workspace_manager.assign_window_to_workspace(space, wid, to) {
+            if is_floating {
+                self.floating.add_active(space, wid.pid, wid);
+            } else if let Some(prev_layout) = self.workspace_layouts.active(space, from) {
+                self.tree.add_window_after_selection(prev_layout, wid);
+            }
+            return false;
+        }
+
+        // A workspace that was never shown may not have a layout yet.
+        if !is_floating
+            && self.workspace_layouts.active(space, to).is_none()
+            && let Some(size) = self.workspace_layouts.active_size(space)
+        {
+            self.ensure_active_layouts(space, size, [to]);
+        }
+        if !is_floating && let Some(target_layout) = self.workspace_layouts.active(space, to) {
+            self.tree.add_window_after_selection(target_layout, wid);
+        }
+        if is_floating && self.virtual_workspace_manager.active_workspace(space) == Some(to) {
+            self.floating.add_active(space, wid.pid, wid);
+        }
+        true
+    }
+
+    /// Re-evaluates the app rules for the known windows of `space`, moving each one whose rule now puts it on another workspace
+    /// and floating or re-tiling it as the rule says. Windows placed by hand
+    /// since the rules last placed them are left alone unless `force` is set.
+    /// The response raises the changed windows on the active workspace.
+    pub fn reapply_app_rules(&mut self, space: SpaceId, force: bool) -> EventResponse {
+        let mut windows: Vec<_> = self
+            .window_rule_inputs
+            .iter()
+            .map(|(&wid, inputs)| (wid, inputs.clone()))
+            .collect();
+        windows.sort_by_key(|&(wid, _)| wid);
+        let Some(active) = self.virtual_workspace_manager.active_workspace(space) else {
+            return EventResponse::default();
+        };
+        let mut response = EventResponse::default();
+        let mut focus_left = false;
+        for (wid, inputs) in windows {
+            let Some(current) = self.virtual_workspace_manager.workspace_for_window(space, wid)
+            else {
+                continue;
+            };
+            if !force && self.virtual_workspace_manager.is_placed_by_hand(space, wid) {
+                continue;
+            }
+            let Some(placement) = self.virtual_workspace_manager.rule_placement(
+                space,
+                wid,
+                inputs.bundle_id.as_deref(),
+                inputs.app_name.as_deref(),
+                inputs.title.as_deref(),
+                inputs.ax_role.as_deref(),
+                inputs.ax_subrole.as_deref(),
+            ) else {
+                continue;
+            };
+            self.set_display_hints(space, wid, placement.hints);
+
+            let target = placement.workspace.unwrap_or(current);
+            let moved = target != current && self.transfer_window(space, wid, current, target);
+            let workspace = if moved { target } else { current };
+            if force {
+                // Treat floating set by hand as the rules' doing, so the
+                // rules can undo it.
+                let is_floating = self.floating.is_floating(wid);
+                self.virtual_workspace_manager.set_last_rule_decision(space, wid, is_floating);
+            }
+            let refloated = self.apply_rule_floating(space, workspace, wid, placement.floating);
+            if !moved && !refloated {
+                continue;
+            }
+            self.virtual_workspace_manager.set_placed_by_hand(space, wid, false);
+            if workspace == active {
+                response.raise_windows.push(wid);
+            } else if self.focused_window == Some(wid) {
+                focus_left = true;
+            }
+        }
+        if focus_left {
+            self.focused_window = None;
+            let refocus = self.refocus_workspace(space, active);
+            response.focus_window = refocus.focus_window;
+        }
+        if !response.raise_windows.is_empty() || focus_left {
+            response.layout_changed = true;
+        }
+        response
+    }
+
+    /// Fingerprints of every active layout, ordered by layout.
+    fn layout_fingerprints(&self) -> Vec<(LayoutId, u64)> {
+        let mut fingerprints = Vec::new();
+        self.workspace_layouts.for_each_active(|layout| {
+            fingerprints.push((layout, self.tree.layout_fingerprint(layout)))
+        });
+        fingerprints.sort_unstable();
+        fingerprints
+    }
+
+    fn handle_command_internal(
+        &mut self,
+        space: Option<SpaceId>,
+        visible_spaces: &[SpaceId],
+        visible_space_centers: &HashMap<SpaceId, CGPoint>,
+        command: LayoutCommand,
     ) -> EventResponse {
         if let Some(space) = space {
             let layout = self.layout(space);
-            debug!("Tree:\n{}", self.tree.draw_tree(layout).trim());
+            debug!(
+                "Tree:\n{}",
+                self.tree
+                    .draw_tree_with_options(layout, &TreeRenderOptions::focus_path())
+                    .trim()
+            );
             debug!(selection_window = ?self.tree.selected_window(layout));
         }
         let is_floating = if let Some(focus) = self.focused_window {
@@ -913,6 +2360,9 @@ impl LayoutEngine {
             let Some(wid) = self.focused_window else {
                 return EventResponse::default();
             };
+            if let Some(space) = space {
+                self.virtual_workspace_manager.set_placed_by_hand(space, wid, true);
+            }
             if is_floating {
                 if let Some(space) = space {
                     let assigned_workspace = self
@@ -925,7 +2375,22 @@ impl LayoutEngine {
                         });
 
                     if let Some(layout) = self.workspace_layouts.active(space, assigned_workspace) {
+                        let anchor = self
+                            .virtual_workspace_manager
+                            .get_floating_position(space, assigned_workspace, wid)
+                            .and_then(|frame| {
+                                let point = frame.mid();
+                                self.nearest_tiled_window(layout, point)
+                                    .map(|(anchor, rect)| (anchor, Self::lies_before(point, rect)))
+                            });
+                        if let Some((anchor, _)) = anchor {
+                            self.tree.select_window(layout, anchor);
+                        }
                         self.tree.add_window_after_selection(layout, wid);
+                        if let Some((anchor, true)) = anchor {
+                            self.tree.swap_windows(layout, wid, anchor);
+                            self.tree.select_window(layout, wid);
+                        }
                         debug!(
                             "Re-added floating window {:?} to tiling tree in workspace {:?}",
                             wid, assigned_workspace
@@ -937,12 +2402,13 @@ impl LayoutEngine {
                 self.floating.remove_floating(wid);
                 self.floating.set_last_focus(None);
             } else {
-                if let Some(space) = space {
-                    self.floating.add_active(space, wid.pid, wid);
-                }
                 self.tree.remove_window(wid);
                 self.floating.add_floating(wid);
                 self.floating.set_last_focus(Some(wid));
+                if let Some(space) = space {
+                    self.floating.add_active(space, wid.pid, wid);
+                    self.note_floating_focus(space, wid);
+                }
                 debug!("Removed window {:?} from tiling tree, now floating", wid);
             }
             return EventResponse::default();
@@ -978,6 +2444,8 @@ impl LayoutEngine {
                     raise_windows,
                     focus_window,
                     workspace_changed_to: None,
+                    layout_changed: false,
+                    fullscreen_windows: Vec::new(),
                 };
                 self.apply_focus_response(space, layout, &response);
                 return response;
@@ -994,19 +2462,31 @@ impl LayoutEngine {
                     raise_windows,
                     focus_window,
                     workspace_changed_to: None,
+                    layout_changed: false,
+                    fullscreen_windows: Vec::new(),
                 };
                 self.apply_focus_response(space, layout, &response);
                 return response;
             }
         }
 
+        if self.tree.visible_windows_in_layout(layout).is_empty()
+            && let Some(response) = self.handle_command_on_empty_layout(space, layout, &command)
+        {
+            return response;
+        }
+
         match command {
             LayoutCommand::ToggleWindowFloating => unreachable!(),
             LayoutCommand::ToggleFocusFloating => unreachable!(),
 
             LayoutCommand::SwapWindows(a, b) => {
-                let layout = self.layout(space);
-                let _ = self.tree.swap_windows(layout, a, b);
+                match self.validate_swap(a, b) {
+                    Ok(layout) => {
+                        let _ = self.tree.swap_windows(layout, a, b);
+                    }
+                    Err(err) => warn!("Rejecting window swap: {err}"),
+                }
 
                 EventResponse::default()
             }
@@ -1038,6 +2518,18 @@ impl LayoutEngine {
                     is_floating,
                 )
             }
+            LayoutCommand::FocusNth { index } => {
+                let Some(wid) = self.tree.nth_visible_window(layout, index) else {
+                    debug!(index, "FocusNth index out of range");
+                    return EventResponse::default();
+                };
+                let response = EventResponse {
+                    focus_window: Some(wid),
+                    ..EventResponse::default()
+                };
+                self.apply_focus_response(space, layout, &response);
+                response
+            }
             LayoutCommand::Ascend => {
                 if is_floating {
                     return EventResponse::default();
@@ -1065,63 +2557,190 @@ impl LayoutEngine {
                 EventResponse::default()
             }
             LayoutCommand::ToggleFullscreen => {
+                let selected = self.tree.selected_window(layout);
                 let raise_windows = self.tree.toggle_fullscreen_of_selection(layout);
-                if raise_windows.is_empty() {
-                    EventResponse::default()
-                } else {
-                    EventResponse {
-                        raise_windows,
-                        focus_window: None,
-                        workspace_changed_to: None,
-                    }
-                }
+                self.fullscreen_toggled(space, layout, selected, raise_windows)
             }
             LayoutCommand::ToggleFullscreenWithinGaps => {
+                let selected = self.tree.selected_window(layout);
                 let raise_windows = self.tree.toggle_fullscreen_within_gaps_of_selection(layout);
-                if raise_windows.is_empty() {
-                    EventResponse::default()
-                } else {
-                    EventResponse {
-                        raise_windows,
-                        focus_window: None,
-                        workspace_changed_to: None,
-                    }
-                }
+                self.fullscreen_toggled(space, layout, selected, raise_windows)
             }
-            // handled by upper reactor
-            LayoutCommand::NextWorkspace(_)
-            | LayoutCommand::PrevWorkspace(_)
-            | LayoutCommand::SwitchToWorkspace(_)
-            | LayoutCommand::MoveWindowToWorkspace { .. }
-            | LayoutCommand::CreateWorkspace
-            | LayoutCommand::SwitchToLastWorkspace => EventResponse::default(),
-            LayoutCommand::JoinWindow(direction) => {
+            LayoutCommand::IsolateAndMaximize => {
+                if is_floating {
+                    return EventResponse::default();
+                }
                 self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
-                self.tree.join_selection_with_direction(layout, direction);
-                EventResponse::default()
+                let selected = self.tree.selected_window(layout);
+                let raise_windows = match &mut self.tree {
+                    LayoutSystemKind::Traditional(s) => s.isolate_and_maximize_selection(layout),
+                    // Without containers to lift the window out of, only
+                    // maximize it, leaving it maximized if it already is.
+                    tree => match selected {
+                        Some(wid) if tree.fullscreen_windows(layout).contains(&(wid, true)) => {
+                            vec![wid]
+                        }
+                        _ => tree.toggle_fullscreen_within_gaps_of_selection(layout),
+                    },
+                };
+                self.fullscreen_toggled(space, layout, selected, raise_windows)
             }
-            LayoutCommand::ToggleStack => {
-                self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
-                let default_orientation: crate::common::config::StackDefaultOrientation =
-                    self.layout_settings.stack.default_orientation;
-                let unstacked_windows =
-                    self.tree.unstack_parent_of_selection(layout, default_orientation);
-
-                if !unstacked_windows.is_empty() {
-                    return EventResponse {
-                        raise_windows: unstacked_windows,
-                        focus_window: None,
-                        workspace_changed_to: None,
-                    };
+            LayoutCommand::ManageWindow => {
+                let Some(wid) = self.focused_window else {
+                    return EventResponse::default();
+                };
+                if self.is_managed(space, wid) {
+                    return EventResponse::default();
                 }
-
-                let stacked_windows =
-                    self.tree.apply_stacking_to_parent_of_selection(layout, default_orientation);
-                if !stacked_windows.is_empty() {
-                    return EventResponse {
-                        raise_windows: stacked_windows,
+                self.virtual_workspace_manager.set_manage_override(wid, Some(true));
+                self.virtual_workspace_manager
+                    .assign_window_to_workspace(space, wid, workspace_id);
+                self.tree.add_window_after_selection(layout, wid);
+                self.tree.select_window(layout, wid);
+                self.broadcast_windows_changed(space);
+                EventResponse {
+                    raise_windows: self.tree.visible_windows_in_layout(layout),
+                    focus_window: Some(wid),
+                    ..EventResponse::default()
+                }
+            }
+            LayoutCommand::UnmanageWindow => {
+                let Some(wid) = self.focused_window else {
+                    return EventResponse::default();
+                };
+                let Some(owner) = self.window_owner(wid) else {
+                    return EventResponse::default();
+                };
+                self.note_window_leaving(wid);
+                self.tree.remove_window(wid);
+                self.floating.remove_floating(wid);
+                self.virtual_workspace_manager.remove_window(wid);
+                self.virtual_workspace_manager.remove_floating_position(wid);
+                self.virtual_workspace_manager.set_manage_override(wid, Some(false));
+                self.window_display_hints.remove(&wid);
+                self.broadcast_windows_changed(owner.0);
+                if self.layout_settings.rebalance_on_remove {
+                    self.rebalance_workspace(owner.0, owner.1);
+                }
+                EventResponse {
+                    raise_windows: self.tree.visible_windows_in_layout(layout),
+                    ..EventResponse::default()
+                }
+            }
+            LayoutCommand::SplitInto { kind, count } => {
+                if is_floating {
+                    return EventResponse::default();
+                }
+                if count < 2 {
+                    warn!(count, "Rejecting split into fewer than two tiles");
+                    return EventResponse::default();
+                }
+                self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
+                match &mut self.tree {
+                    LayoutSystemKind::Traditional(s) => s.split_selection_into(layout, kind, count),
+                    tree => tree.split_selection(layout, kind),
+                }
+                EventResponse::default()
+            }
+            LayoutCommand::TogglePreserveSize => {
+                if let LayoutSystemKind::Traditional(s) = &mut self.tree {
+                    let preserved = s.toggle_preserve_size_of_selection(layout);
+                    debug!(preserved, "Toggled size preservation");
+                    self.enforce_preserved_sizes(layout);
+                }
+                EventResponse::default()
+            }
+            LayoutCommand::PromoteToMain => {
+                if let LayoutSystemKind::Traditional(s) = &mut self.tree
+                    && s.promote_selection_to_main(layout)
+                {
+                    self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
+                }
+                EventResponse::default()
+            }
+            LayoutCommand::FlipContainer => {
+                if let LayoutSystemKind::Traditional(s) = &mut self.tree
+                    && s.flip_container_children(layout)
+                {
+                    self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
+                }
+                EventResponse::default()
+            }
+            LayoutCommand::ToggleContainerLock => {
+                if let LayoutSystemKind::Traditional(s) = &mut self.tree {
+                    let locked = s.toggle_lock_of_selection_parent(layout);
+                    debug!(locked, "Toggled container lock");
+                }
+                EventResponse::default()
+            }
+            LayoutCommand::Undo | LayoutCommand::Redo => {
+                let LayoutSystemKind::Traditional(s) = &mut self.tree else {
+                    return EventResponse::default();
+                };
+                let changed = if matches!(command, LayoutCommand::Undo) {
+                    s.undo(layout)
+                } else {
+                    s.redo(layout)
+                };
+                if !changed {
+                    debug!(?command, "Nothing to revert");
+                    return EventResponse::default();
+                }
+                self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
+                EventResponse {
+                    focus_window: s.selected_window(layout),
+                    ..Default::default()
+                }
+            }
+            // handled by upper reactor
+            LayoutCommand::NextWorkspace(_)
+            | LayoutCommand::PrevWorkspace(_)
+            | LayoutCommand::SwitchToWorkspace(_)
+            | LayoutCommand::MoveWindowToWorkspace { .. }
+            | LayoutCommand::CreateWorkspace
+            | LayoutCommand::SwitchToLastWorkspace
+            | LayoutCommand::MergeWorkspaces { .. }
+            | LayoutCommand::ReorderWorkspace { .. } => EventResponse::default(),
+            LayoutCommand::JoinWindow(direction) => {
+                self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
+                self.tree.join_selection_with_direction(layout, direction);
+                EventResponse::default()
+            }
+            LayoutCommand::ToggleStack => {
+                self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
+                let default_orientation: crate::common::config::StackDefaultOrientation =
+                    self.layout_settings.stack.default_orientation;
+                let toggled_windows = match self.layout_settings.stack.toggle_mode {
+                    StackToggleMode::UnstackFirst => {
+                        let unstacked =
+                            self.tree.unstack_parent_of_selection(layout, default_orientation);
+                        if unstacked.is_empty() {
+                            self.tree
+                                .apply_stacking_to_parent_of_selection(layout, default_orientation)
+                        } else {
+                            unstacked
+                        }
+                    }
+                    StackToggleMode::StackFirst => {
+                        let stacked = self
+                            .tree
+                            .stack_parent_of_selection_if_unstacked(layout, default_orientation);
+                        if stacked.is_empty() {
+                            self.tree.unstack_parent_of_selection(layout, default_orientation)
+                        } else {
+                            stacked
+                        }
+                    }
+                    StackToggleMode::Cycle => self.tree.cycle_stack_of_parent_of_selection(layout),
+                };
+
+                if !toggled_windows.is_empty() {
+                    return EventResponse {
+                        raise_windows: toggled_windows,
                         focus_window: None,
                         workspace_changed_to: None,
+                        layout_changed: false,
+                        fullscreen_windows: Vec::new(),
                     };
                 }
 
@@ -1131,11 +2750,25 @@ impl LayoutEngine {
                         raise_windows: vec![],
                         focus_window: None,
                         workspace_changed_to: None,
+                        layout_changed: false,
+                        fullscreen_windows: Vec::new(),
                     }
                 } else {
                     EventResponse::default()
                 }
             }
+            LayoutCommand::ToggleTabbed => {
+                let LayoutSystemKind::Traditional(s) = &mut self.tree else {
+                    return EventResponse::default();
+                };
+                self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
+                let raise_windows =
+                    s.toggle_tabbed(layout, self.layout_settings.stack.default_orientation);
+                EventResponse {
+                    raise_windows,
+                    ..EventResponse::default()
+                }
+            }
             LayoutCommand::UnjoinWindows => {
                 self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
                 self.tree.unjoin_selection(layout);
@@ -1156,6 +2789,8 @@ impl LayoutEngine {
                                     raise_windows: vec![],
                                     focus_window: None,
                                     workspace_changed_to: None,
+                                    layout_changed: false,
+                                    fullscreen_windows: Vec::new(),
                                 }
                             } else {
                                 EventResponse::default()
@@ -1175,6 +2810,21 @@ impl LayoutEngine {
                     }
                 }
             }
+            LayoutCommand::RotateSubtree => {
+                self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
+                self.tree.rotate_subtree(layout);
+                EventResponse::default()
+            }
+            LayoutCommand::RotateLayout { clockwise } => {
+                self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
+                self.tree.rotate_layout(layout, *clockwise);
+                EventResponse::default()
+            }
+            LayoutCommand::MirrorLayout { axis } => {
+                self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
+                self.tree.mirror_layout(layout, *axis);
+                EventResponse::default()
+            }
             LayoutCommand::ResizeWindowGrow => {
                 if is_floating {
                     return EventResponse::default();
@@ -1199,11 +2849,54 @@ impl LayoutEngine {
                 if is_floating {
                     return EventResponse::default();
                 }
+                if !amount.is_finite() || amount.abs() > MAX_RESIZE_AMOUNT {
+                    warn!(
+                        amount,
+                        "Rejecting resize: amount must be within ±{MAX_RESIZE_AMOUNT}"
+                    );
+                    return EventResponse::default();
+                }
 
                 self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
                 self.tree.resize_selection_by(layout, amount);
                 EventResponse::default()
             }
+            LayoutCommand::ResizeWindowPx { direction, pixels } => {
+                if is_floating {
+                    return EventResponse::default();
+                }
+                let Some(amount) = self.resize_amount_for_pixels(space, direction, pixels) else {
+                    warn!(?space, "Rejecting resize: screen size of the space is unknown");
+                    return EventResponse::default();
+                };
+                if !amount.is_finite() || amount.abs() > MAX_RESIZE_AMOUNT {
+                    warn!(pixels, amount, "Rejecting resize: more than half the screen");
+                    return EventResponse::default();
+                }
+
+                self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
+                self.tree.resize_selection_in_direction(layout, direction, amount);
+                EventResponse::default()
+            }
+            LayoutCommand::GoldenRatio { main_first } => {
+                if is_floating {
+                    return EventResponse::default();
+                }
+
+                if let LayoutSystemKind::Traditional(s) = &mut self.tree {
+                    self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
+                    s.set_golden_ratio_of_selection_parent(layout, main_first);
+                }
+                EventResponse::default()
+            }
+            LayoutCommand::TwoColumn { master_on_left } => {
+                if let LayoutSystemKind::Traditional(s) = &mut self.tree
+                    && s.arrange_two_column(layout, master_on_left)
+                {
+                    self.workspace_layouts.mark_last_saved(space, workspace_id, layout);
+                }
+                EventResponse::default()
+            }
         }
     }
 
@@ -1217,10 +2910,17 @@ impl LayoutEngine {
         stack_line_vert: crate::common::config::VerticalPlacement,
     ) -> Vec<(WindowId, CGRect)> {
         let layout = self.layout(space);
-        self.tree.calculate_layout(
+        self.record_layout_inputs(
+            layout,
+            screen,
+            gaps,
+            stack_line_thickness,
+            stack_line_horiz,
+            stack_line_vert,
+        );
+        self.tiled_layout(
             layout,
             screen,
-            self.layout_settings.stack.stack_offset,
             gaps,
             stack_line_thickness,
             stack_line_horiz,
@@ -1228,6 +2928,240 @@ impl LayoutEngine {
         )
     }
 
+    /// The tiled window of `layout` whose frame in the last layout pass is
+    /// closest to `point`, or the one containing it, with that frame.
+    fn nearest_tiled_window(&self, layout: LayoutId, point: CGPoint) -> Option<(WindowId, CGRect)> {
+        let distance = |rect: &CGRect| {
+            let (min, max) = (rect.min(), rect.max());
+            let dx = (min.x - point.x).max(point.x - max.x).max(0.0);
+            let dy = (min.y - point.y).max(point.y - max.y).max(0.0);
+            dx.hypot(dy)
+        };
+        self.tiled_frames
+            .get(&layout)?
+            .iter()
+            .filter(|(wid, _)| self.tree.contains_window(layout, *wid))
+            .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+            .copied()
+    }
+
+    /// Whether `point` sits before `rect` in tiling order: left of it, or
+    /// above it when it is further off `rect`'s centre vertically than
+    /// horizontally, relative to `rect`'s size. Frames are in Quartz
+    /// coordinates, where y grows downward.
+    fn lies_before(point: CGPoint, rect: CGRect) -> bool {
+        let center = rect.mid();
+        let dx = (point.x - center.x) / rect.size.width.max(1.0);
+        let dy = (point.y - center.y) / rect.size.height.max(1.0);
+        if dx.abs() >= dy.abs() {
+            dx < 0.0
+        } else {
+            dy < 0.0
+        }
+    }
+
+    /// The frame `wid` gets on `space`: its stored frame if it floats,
+    /// otherwise its frame in a layout pass over `screen`. The pass is cached
+    /// until the layout, its fingerprint or any of the inputs change, so
+    /// asking for one window after another does not recompute the layout.
+    pub fn frame_of_window(
+        &mut self,
+        space: SpaceId,
+        wid: WindowId,
+        screen: CGRect,
+        gaps: &crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+        stack_line_horiz: crate::common::config::HorizontalPlacement,
+        stack_line_vert: crate::common::config::VerticalPlacement,
+    ) -> Option<CGRect> {
+        if self.floating.is_floating(wid) {
+            let workspace = self.virtual_workspace_manager.workspace_for_window(space, wid)?;
+            return self.virtual_workspace_manager.get_floating_position(space, workspace, wid);
+        }
+        let layout = self.layout(space);
+        let fingerprint = self.tree.layout_fingerprint(layout);
+        let inputs = LayoutInputs {
+            screen,
+            gaps: gaps.clone(),
+            stack_line_thickness,
+            stack_line_horiz,
+            stack_line_vert,
+        };
+        let cached = self.frame_cache.get(&space).is_some_and(|cache| {
+            cache.layout == layout && cache.fingerprint == fingerprint && cache.inputs == inputs
+        });
+        if !cached {
+            let frames = self
+                .calculate_layout(
+                    space,
+                    screen,
+                    gaps,
+                    stack_line_thickness,
+                    stack_line_horiz,
+                    stack_line_vert,
+                )
+                .into_iter()
+                .collect();
+            self.frame_cache.insert(
+                space,
+                CachedFrames {
+                    layout,
+                    fingerprint,
+                    inputs,
+                    frames,
+                },
+            );
+        }
+        self.frame_cache[&space].frames.get(&wid).copied()
+    }
+
+    /// The frame a window opened now would be tiled at on `space`, so an
+    /// outline can be drawn before it appears. A placeholder is added after
+    /// the selection in a copy of the layout, stacking the root as a new
+    /// window would. Returns `None` while a window on the space is
+    /// fullscreen, or outside the traditional layout, whose copies are cheap.
+    pub fn predict_insertion_frame(
+        &mut self,
+        space: SpaceId,
+        screen: CGRect,
+        gaps: &crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+        stack_line_horiz: crate::common::config::HorizontalPlacement,
+        stack_line_vert: crate::common::config::VerticalPlacement,
+    ) -> Option<CGRect> {
+        if !matches!(self.tree, LayoutSystemKind::Traditional(_)) {
+            return None;
+        }
+        let layout = self.layout(space);
+        if !self.tree.fullscreen_windows(layout).is_empty() {
+            return None;
+        }
+        let placeholder = WindowId::new(pid_t::MAX, u32::MAX);
+        let preview = self.tree.clone_layout(layout);
+        self.tree.add_window_after_selection(preview, placeholder);
+        if !self.auto_stacked.contains(&layout) {
+            self.auto_stack(preview);
+            self.auto_stacked.remove(&preview);
+        }
+        let frame = self
+            .tiled_layout(
+                preview,
+                screen,
+                gaps,
+                stack_line_thickness,
+                stack_line_horiz,
+                stack_line_vert,
+            )
+            .into_iter()
+            .find_map(|(wid, frame)| (wid == placeholder).then_some(frame));
+        self.tree.remove_layout(preview);
+        frame
+    }
+
+    /// Frames of the tiled windows of `layout`, timed for the metrics.
+    fn tiled_layout(
+        &self,
+        layout: LayoutId,
+        screen: CGRect,
+        gaps: &crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+        stack_line_horiz: crate::common::config::HorizontalPlacement,
+        stack_line_vert: crate::common::config::VerticalPlacement,
+    ) -> Vec<(WindowId, CGRect)> {
+        let start = Instant::now();
+        let (gaps, stack_line_thickness) = self.layout_gaps(layout, gaps, stack_line_thickness);
+        let frames = self.tree.calculate_layout(
+            layout,
+            screen,
+            self.layout_settings.stack.stack_offset,
+            self.layout_settings.stack.focus_pop_px,
+            &gaps,
+            stack_line_thickness,
+            stack_line_horiz,
+            stack_line_vert,
+            None,
+        );
+        self.metrics.calculate_layout.record_since(start);
+        frames
+    }
+
+    /// The gaps and stack line reservation `layout` is laid out with. Smart
+    /// gaps drop both while the layout holds a single tiled window, keeping
+    /// the outer gaps unless `smart_outer` is set.
+    fn layout_gaps<'a>(
+        &self,
+        layout: LayoutId,
+        gaps: &'a crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+    ) -> (Cow<'a, crate::common::config::GapSettings>, f64) {
+        if gaps.smart
+            && self.tree.visible_windows_in_layout(layout).len() == 1
+            && self.tree.occluded_windows(layout).is_empty()
+        {
+            let mut lone = crate::common::config::GapSettings::default();
+            if !gaps.smart_outer {
+                lone.outer = gaps.outer.clone();
+            }
+            (Cow::Owned(lone), 0.0)
+        } else {
+            (Cow::Borrowed(gaps), stack_line_thickness)
+        }
+    }
+
+    fn record_layout_inputs(
+        &mut self,
+        layout: LayoutId,
+        screen: CGRect,
+        gaps: &crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+        stack_line_horiz: crate::common::config::HorizontalPlacement,
+        stack_line_vert: crate::common::config::VerticalPlacement,
+    ) {
+        let inputs = LayoutInputs {
+            screen,
+            gaps: gaps.clone(),
+            stack_line_thickness,
+            stack_line_horiz,
+            stack_line_vert,
+        };
+        if self.layout_inputs.get(&layout) != Some(&inputs) {
+            self.layout_inputs.insert(layout, inputs);
+        }
+    }
+
+    /// Corrects the shares around size-preserved windows of `layout` after a
+    /// structural change, measuring them with the inputs of its last layout
+    /// pass. A layout that was never laid out has no sizes to keep yet.
+    fn enforce_preserved_sizes(&mut self, layout: LayoutId) {
+        let Some(inputs) = self.layout_inputs.get(&layout).cloned() else {
+            return;
+        };
+        let (gaps, stack_line_thickness) =
+            self.layout_gaps(layout, &inputs.gaps, inputs.stack_line_thickness);
+        let gaps = gaps.into_owned();
+        if let LayoutSystemKind::Traditional(s) = &mut self.tree {
+            s.enforce_preserved_sizes(
+                layout,
+                inputs.screen,
+                self.layout_settings.stack.stack_offset,
+                self.layout_settings.stack.focus_pop_px,
+                &gaps,
+                stack_line_thickness,
+                inputs.stack_line_horiz,
+                inputs.stack_line_vert,
+            );
+        }
+    }
+
+    /// Keeps preserved sizes on the active layout of `space`.
+    fn enforce_preserved_sizes_on(&mut self, space: SpaceId) {
+        if let Some(workspace) = self.virtual_workspace_manager.active_workspace(space)
+            && let Some(layout) = self.workspace_layouts.active(space, workspace)
+        {
+            self.enforce_preserved_sizes(layout);
+        }
+    }
+
     pub fn calculate_layout_with_virtual_workspaces<F>(
         &mut self,
         space: SpaceId,
@@ -1297,18 +3231,26 @@ impl LayoutEngine {
 
         if let Some(active_workspace_id) = self.virtual_workspace_manager.active_workspace(space) {
             if let Some(layout) = self.workspace_layouts.active(space, active_workspace_id) {
-                let tiled_positions = self.tree.calculate_layout(
+                self.record_layout_inputs(
                     layout,
                     screen,
-                    self.layout_settings.stack.stack_offset,
                     gaps,
                     stack_line_thickness,
                     stack_line_horiz,
                     stack_line_vert,
                 );
-                for (wid, rect) in tiled_positions {
+                let tiled_positions = self.tiled_layout(
+                    layout,
+                    screen,
+                    gaps,
+                    stack_line_thickness,
+                    stack_line_horiz,
+                    stack_line_vert,
+                );
+                for &(wid, rect) in &tiled_positions {
                     positions.insert(wid, rect);
                 }
+                self.tiled_frames.insert(layout, tiled_positions);
             }
 
             let floating_positions = self
@@ -1396,12 +3338,14 @@ impl LayoutEngine {
         stack_line_vert: crate::common::config::VerticalPlacement,
     ) -> Vec<GroupContainerInfo> {
         let layout_id = self.layout(space);
+        let (gaps, stack_line_thickness) = self.layout_gaps(layout_id, gaps, stack_line_thickness);
         match &self.tree {
             LayoutSystemKind::Traditional(s) => s.collect_group_containers_in_selection_path(
                 layout_id,
                 screen,
                 self.layout_settings.stack.stack_offset,
-                gaps,
+                self.layout_settings.stack.focus_pop_px,
+                &gaps,
                 stack_line_thickness,
                 stack_line_horiz,
                 stack_line_vert,
@@ -1410,23 +3354,151 @@ impl LayoutEngine {
         }
     }
 
-    pub fn calculate_layout_for_workspace(
-        &self,
+    /// Every stack visible in the active workspace of `space`, wherever the
+    /// selection is. See [`LayoutSystem::stacked_containers`] for the order
+    /// of each stack's members.
+    pub fn stacked_containers(
+        &mut self,
         space: SpaceId,
-        workspace_id: crate::model::VirtualWorkspaceId,
         screen: CGRect,
         gaps: &crate::common::config::GapSettings,
         stack_line_thickness: f64,
         stack_line_horiz: crate::common::config::HorizontalPlacement,
         stack_line_vert: crate::common::config::VerticalPlacement,
-    ) -> Vec<(WindowId, CGRect)> {
-        let mut positions = HashMap::with_capacity_and_hasher(32, Default::default());
+    ) -> Vec<GroupContainerInfo> {
+        let layout = self.layout(space);
+        let (gaps, stack_line_thickness) = self.layout_gaps(layout, gaps, stack_line_thickness);
+        self.tree.stacked_containers(
+            layout,
+            screen,
+            self.layout_settings.stack.stack_offset,
+            self.layout_settings.stack.focus_pop_px,
+            &gaps,
+            stack_line_thickness,
+            stack_line_horiz,
+            stack_line_vert,
+        )
+    }
+
+    /// Selects the `index`th member of the stack `container` on `space`, as
+    /// picked from the stack quick-jump picker. The response focuses the
+    /// member and raises the windows the stack now shows.
+    pub fn select_stack_member(
+        &mut self,
+        space: SpaceId,
+        container: crate::model::tree::NodeId,
+        index: usize,
+    ) -> EventResponse {
+        let layout = self.layout(space);
+        let LayoutSystemKind::Traditional(s) = &mut self.tree else {
+            return EventResponse::default();
+        };
+        let Some((focus_window, raise_windows)) = s.select_stack_member(layout, container, index)
+        else {
+            return EventResponse::default();
+        };
+        self.focused_window = Some(focus_window);
+        EventResponse {
+            raise_windows,
+            focus_window: Some(focus_window),
+            workspace_changed_to: None,
+            layout_changed: true,
+            fullscreen_windows: Vec::new(),
+        }
+    }
+
+    /// Orientation a `JoinWindow(direction)` would produce on `space`, without
+    /// performing it. Only the traditional layout can predict joins.
+    pub fn predict_join_orientation(
+        &self,
+        space: SpaceId,
+        direction: Direction,
+    ) -> Option<Orientation> {
+        let workspace_id = self.virtual_workspace_manager.active_workspace(space)?;
+        let layout = self.workspace_layouts.active(space, workspace_id)?;
+        match &self.tree {
+            LayoutSystemKind::Traditional(s) => s.predict_join_orientation(layout, direction),
+            _ => None,
+        }
+    }
+
+    /// Selects the deepest container under `point` on `space`. Only the
+    /// traditional layout has containers to select.
+    pub fn select_container_at_point(
+        &mut self,
+        space: SpaceId,
+        point: CGPoint,
+        screen: CGRect,
+        gaps: &crate::common::config::GapSettings,
+    ) -> bool {
+        let layout = self.layout(space);
+        match &mut self.tree {
+            LayoutSystemKind::Traditional(s) => {
+                s.select_container_at_point(layout, point, screen, gaps)
+            }
+            _ => false,
+        }
+    }
+
+    /// Windows of `workspace_id` on `space`, each with the frame it would get
+    /// on `screen`. The active workspace is already on screen, so its windows
+    /// come without a frame; so does every window when `screen` is `None`.
+    /// Only reads state, so querying a workspace never creates or switches it.
+    pub fn windows_in_workspace(
+        &self,
+        space: SpaceId,
+        workspace_id: crate::model::VirtualWorkspaceId,
+        screen: Option<CGRect>,
+        gaps: &crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+        stack_line_horiz: crate::common::config::HorizontalPlacement,
+        stack_line_vert: crate::common::config::VerticalPlacement,
+    ) -> Vec<(WindowId, Option<CGRect>)> {
+        if self.virtual_workspace_manager.active_workspace(space) == Some(workspace_id) {
+            return self
+                .windows_in_active_workspace(space)
+                .into_iter()
+                .map(|wid| (wid, None))
+                .collect();
+        }
+        let Some(workspace) = self.virtual_workspace_manager.workspace_info(space, workspace_id)
+        else {
+            return Vec::new();
+        };
+        let predicted: HashMap<WindowId, CGRect> = match screen {
+            Some(screen) => self
+                .calculate_layout_for_workspace(
+                    space,
+                    workspace_id,
+                    screen,
+                    gaps,
+                    stack_line_thickness,
+                    stack_line_horiz,
+                    stack_line_vert,
+                )
+                .into_iter()
+                .collect(),
+            None => HashMap::default(),
+        };
+        workspace.windows().map(|wid| (wid, predicted.get(&wid).copied())).collect()
+    }
+
+    pub fn calculate_layout_for_workspace(
+        &self,
+        space: SpaceId,
+        workspace_id: crate::model::VirtualWorkspaceId,
+        screen: CGRect,
+        gaps: &crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+        stack_line_horiz: crate::common::config::HorizontalPlacement,
+        stack_line_vert: crate::common::config::VerticalPlacement,
+    ) -> Vec<(WindowId, CGRect)> {
+        let mut positions = HashMap::with_capacity_and_hasher(32, Default::default());
 
         if let Some(layout) = self.workspace_layouts.active(space, workspace_id) {
-            let tiled_positions = self.tree.calculate_layout(
+            let tiled_positions = self.tiled_layout(
                 layout,
                 screen,
-                self.layout_settings.stack.stack_offset,
                 gaps,
                 stack_line_thickness,
                 stack_line_horiz,
@@ -1492,12 +3564,7 @@ impl LayoutEngine {
                 .into_iter()
                 .map(|(id, _)| id);
             let default_size = CGSize::new(1000.0, 1000.0);
-            self.workspace_layouts.ensure_active_for_space(
-                space,
-                default_size,
-                workspaces,
-                &mut self.tree,
-            );
+            self.ensure_active_layouts(space, default_size, workspaces);
 
             // After ensuring an active layout exists, return it. If something
             // unexpected happened, surface an informative panic.
@@ -1507,20 +3574,129 @@ impl LayoutEngine {
         }
     }
 
+    /// Loads an engine saved by [`save`](Self::save) in either format,
+    /// falling back to the previous save if the file is unreadable.
     pub fn load(path: PathBuf) -> anyhow::Result<Self> {
-        let mut buf = String::new();
-        File::open(path)?.read_to_string(&mut buf)?;
-        Ok(ron::from_str(&buf)?)
+        let mut engine: Self = persistence::read_with_backup(&path)?;
+        if let LayoutSystemKind::Traditional(s) = &mut engine.tree {
+            let dropped = s.validate_window_bindings();
+            if dropped > 0 {
+                warn!("Dropped {} duplicate window nodes from saved layout", dropped);
+            }
+        }
+        Ok(engine)
+    }
+
+    /// Writes the engine state to `path`, replacing it atomically.
+    pub fn save(&self, path: PathBuf, format: PersistenceFormat) -> anyhow::Result<()> {
+        let start = Instant::now();
+        persistence::write_atomically(&path, &persistence::encode(format, self)?)?;
+        self.metrics.save.record_since(start);
+        Ok(())
+    }
+
+    /// Saves the state to `path` as it changes, at most once per
+    /// `settings.autosave_secs`.
+    pub fn enable_autosave(&mut self, path: PathBuf, settings: &PersistenceSettings) {
+        let mut autosave = Autosave {
+            path,
+            format: settings.format,
+            enabled: false,
+            debounce: SaveDebounce::new(Duration::ZERO),
+        };
+        autosave.configure(settings);
+        self.autosave = Some(autosave);
+    }
+
+    /// Applies new persistence settings to autosaving, if it is enabled.
+    pub fn update_autosave_settings(&mut self, settings: &PersistenceSettings) {
+        if let Some(autosave) = &mut self.autosave {
+            autosave.configure(settings);
+        }
     }
 
-    pub fn save(&self, path: PathBuf) -> std::io::Result<()> {
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
+    fn mark_dirty(&mut self) {
+        if let Some(autosave) = &mut self.autosave
+            && autosave.enabled
+        {
+            autosave.debounce.mark_dirty();
+        }
+    }
+
+    /// Writes unsaved changes if the autosave interval has passed since the
+    /// last save. Returns whether anything was written.
+    pub fn autosave_if_due(&mut self, now: Instant) -> anyhow::Result<bool> {
+        let Some(autosave) = &self.autosave else {
+            return Ok(false);
+        };
+        if !autosave.enabled || !autosave.debounce.is_due(now) {
+            return Ok(false);
+        }
+        let result = self.save(autosave.path.clone(), autosave.format);
+        if let Some(autosave) = &mut self.autosave {
+            autosave.debounce.saved(now);
+            // Retry a failed write after the interval rather than every tick.
+            if result.is_err() {
+                autosave.debounce.mark_dirty();
+            }
+        }
+        result.map(|()| true)
+    }
+
+    /// Writes unsaved changes to the autosave file right away, whatever the
+    /// interval. Does nothing if the state is unchanged since the last save
+    /// or autosaving was never enabled.
+    pub fn save_now(&mut self) -> anyhow::Result<()> {
+        let Some(autosave) = &self.autosave else {
+            return Ok(());
+        };
+        if !autosave.debounce.is_dirty() {
+            return Ok(());
         }
-        File::create(path)?.write_all(self.serialize_to_string().as_bytes())?;
+        self.save(autosave.path.clone(), autosave.format)?;
+        self.mark_saved();
         Ok(())
     }
 
+    /// Records that the state was just written to the autosave file, so
+    /// nothing is left for `save_now` or `shutdown` to write.
+    pub fn mark_saved(&mut self) {
+        if let Some(autosave) = &mut self.autosave {
+            autosave.debounce.saved(Instant::now());
+        }
+    }
+
+    /// Counts of what [`shutdown`](Self::shutdown) would tear down.
+    pub fn shutdown_state(&self) -> ShutdownState {
+        let mut layouts = 0;
+        self.workspace_layouts.for_each_active(|_| layouts += 1);
+        ShutdownState {
+            spaces: self.workspace_layouts.spaces().len(),
+            layouts,
+            windows: self.virtual_workspace_manager.all_window_ids().len(),
+        }
+    }
+
+    /// Tears the engine down before exit: sends pending broadcasts and a
+    /// final `ShuttingDown`, writes unsaved changes to the autosave file, then
+    /// removes every layout. The engine must not be used afterwards.
+    pub fn shutdown(&mut self) -> ShutdownState {
+        let state = self.shutdown_state();
+        self.flush_deferred_broadcasts();
+        if let Some(ref broadcast_tx) = self.broadcast_tx {
+            broadcast_tx.send(BroadcastEvent::ShuttingDown);
+        }
+        if let Err(e) = self.save_now() {
+            warn!("Could not save layout state on shutdown: {e}");
+        }
+        self.workspace_layouts = WorkspaceLayouts::default();
+        self.tiled_frames.clear();
+        self.layout_inputs.clear();
+        self.frame_cache.clear();
+        self.tree.shutdown();
+        state
+    }
+
     pub fn serialize_to_string(&self) -> String {
         ron::ser::to_string(&self).unwrap()
     }
@@ -1536,6 +3712,115 @@ impl LayoutEngine {
         space: SpaceId,
         command: &LayoutCommand,
     ) -> EventResponse {
+        self.mark_dirty();
+        let detail = self.journal.as_ref().map(|journal| journal.describe_command(command));
+        let response = self.virtual_workspace_command(space, command);
+        if let Some(detail) = detail {
+            self.record_in_journal(JournalEntryKind::Command, command.into(), Some(space), detail);
+        }
+        response
+    }
+
+    /// The index of the workspace a workspace switching `command` would show
+    /// on `space`, so that `WorkspaceScope::Global` can switch every display
+    /// to it. `None` if the command is not a switch or would stay put.
+    pub fn workspace_switch_index(&self, space: SpaceId, command: &LayoutCommand) -> Option<usize> {
+        let manager = &self.virtual_workspace_manager;
+        let current = manager.active_workspace(space);
+        let target = match command {
+            LayoutCommand::SwitchToWorkspace(index) => return Some(*index),
+            LayoutCommand::NextWorkspace(skip_empty) => {
+                manager.next_workspace(space, current?, *skip_empty)?
+            }
+            LayoutCommand::PrevWorkspace(skip_empty) => {
+                manager.prev_workspace(space, current?, *skip_empty)?
+            }
+            LayoutCommand::SwitchToLastWorkspace => manager.last_workspace(space)?,
+            _ => return None,
+        };
+        if Some(target) == current {
+            return None;
+        }
+        manager.workspace_index(space, target)
+    }
+
+    /// Switches every visible space to the workspace at `workspace_index`, for
+    /// `WorkspaceScope::Global`. Spaces with fewer workspaces get new ones.
+    /// `space` is switched last so that its window keeps focus; the responses
+    /// are in the order they should be applied.
+    pub fn switch_to_workspace_globally(
+        &mut self,
+        space: SpaceId,
+        visible_spaces: &[SpaceId],
+        workspace_index: usize,
+    ) -> Vec<(SpaceId, EventResponse)> {
+        let command = LayoutCommand::SwitchToWorkspace(workspace_index);
+        let detail = self.journal.as_ref().map(|journal| journal.describe_command(&command));
+        self.last_app_windows.clear();
+        self.mark_dirty();
+
+        let mut index = workspace_index;
+        if self.virtual_workspace_manager.active_workspace_idx(space) == Some(index as u64)
+            && self.virtual_workspace_manager.workspace_auto_back_and_forth()
+            && let Some(last) = self.virtual_workspace_manager.last_workspace(space)
+            && let Some(last_index) = self
+                .virtual_workspace_manager_mut()
+                .list_workspaces(space)
+                .iter()
+                .position(|(id, _)| *id == last)
+        {
+            index = last_index;
+        }
+
+        let targets = visible_spaces.iter().copied().filter(|&s| s != space).chain([space]);
+        let mut responses = Vec::new();
+        for target in targets {
+            let Some(workspace_id) =
+                self.virtual_workspace_manager.workspace_at_index_or_create(target, index)
+            else {
+                warn!(?target, index, "Cannot create workspace for global switch");
+                continue;
+            };
+            if self.virtual_workspace_manager.active_workspace(target) == Some(workspace_id) {
+                continue;
+            }
+            responses.push((target, self.activate_workspace(target, workspace_id)));
+        }
+
+        if let Some(detail) = detail {
+            self.record_in_journal(
+                JournalEntryKind::Command,
+                (&command).into(),
+                Some(space),
+                detail,
+            );
+        }
+        responses
+    }
+
+    /// Makes `workspace_id` the active workspace of `space` and focuses the
+    /// window it last had focused.
+    fn activate_workspace(
+        &mut self,
+        space: SpaceId,
+        workspace_id: VirtualWorkspaceId,
+    ) -> EventResponse {
+        self.virtual_workspace_manager.set_active_workspace(space, workspace_id);
+
+        self.update_active_floating_windows(space);
+
+        self.broadcast_workspace_changed(space);
+        self.broadcast_windows_changed(space);
+
+        self.refocus_workspace(space, workspace_id)
+    }
+
+    fn virtual_workspace_command(
+        &mut self,
+        space: SpaceId,
+        command: &LayoutCommand,
+    ) -> EventResponse {
+        self.last_app_windows.clear();
         match command {
             LayoutCommand::NextWorkspace(skip_empty) => {
                 if let Some(current_workspace) =
@@ -1546,14 +3831,7 @@ impl LayoutEngine {
                         *skip_empty,
                     )
                 {
-                    self.virtual_workspace_manager.set_active_workspace(space, next_workspace);
-
-                    self.update_active_floating_windows(space);
-
-                    self.broadcast_workspace_changed(space);
-                    self.broadcast_windows_changed(space);
-
-                    return self.refocus_workspace(space, next_workspace);
+                    return self.activate_workspace(space, next_workspace);
                 }
                 EventResponse::default()
             }
@@ -1566,14 +3844,7 @@ impl LayoutEngine {
                         *skip_empty,
                     )
                 {
-                    self.virtual_workspace_manager.set_active_workspace(space, prev_workspace);
-
-                    self.update_active_floating_windows(space);
-
-                    self.broadcast_workspace_changed(space);
-                    self.broadcast_windows_changed(space);
-
-                    return self.refocus_workspace(space, prev_workspace);
+                    return self.activate_workspace(space, prev_workspace);
                 }
                 EventResponse::default()
             }
@@ -1589,24 +3860,12 @@ impl LayoutEngine {
                             if let Some(last_workspace) =
                                 self.virtual_workspace_manager.last_workspace(space)
                             {
-                                self.virtual_workspace_manager
-                                    .set_active_workspace(space, last_workspace);
-                                self.update_active_floating_windows(space);
-                                self.broadcast_workspace_changed(space);
-                                self.broadcast_windows_changed(space);
-                                return self.refocus_workspace(space, last_workspace);
+                                return self.activate_workspace(space, last_workspace);
                             }
                         }
                         return EventResponse::default();
                     }
-                    self.virtual_workspace_manager.set_active_workspace(space, workspace_id);
-
-                    self.update_active_floating_windows(space);
-
-                    self.broadcast_workspace_changed(space);
-                    self.broadcast_windows_changed(space);
-
-                    return self.refocus_workspace(space, workspace_id);
+                    return self.activate_workspace(space, workspace_id);
                 }
                 EventResponse::default()
             }
@@ -1615,7 +3874,7 @@ impl LayoutEngine {
                 window_id: maybe_id,
             } => {
                 let focused_window = if let Some(spec_u32) = maybe_id {
-                    match self.virtual_workspace_manager.find_window_by_idx(space, *spec_u32) {
+                    match self.find_window_by_idx(space, *spec_u32) {
                         Some(w) => w,
                         None => return EventResponse::default(),
                     }
@@ -1626,21 +3885,32 @@ impl LayoutEngine {
                     }
                 };
 
-                let inferred_space = self.space_with_window(focused_window);
-                let op_space = if inferred_space == Some(space) {
-                    space
-                } else {
-                    inferred_space.unwrap_or(space)
-                };
+                self.note_window_leaving(focused_window);
+
+                // Workspace indices refer to `space`. A window owned by another
+                // space is first handed over to it, so the workspace bookkeeping
+                // of both spaces stays consistent.
+                if let Some((owner, _)) = self.window_owner(focused_window)
+                    && owner != space
+                {
+                    let Some(size) = self.workspace_layouts.active_size(space) else {
+                        warn!(
+                            ?space,
+                            "No layouts for target space; not moving {focused_window:?}"
+                        );
+                        return EventResponse::default();
+                    };
+                    let _ = self.move_window_to_space(owner, space, size, focused_window);
+                }
 
-                let workspaces = self.virtual_workspace_manager_mut().list_workspaces(op_space);
+                let workspaces = self.virtual_workspace_manager_mut().list_workspaces(space);
                 let Some((target_workspace_id, _)) = workspaces.get(*workspace_index) else {
                     return EventResponse::default();
                 };
                 let target_workspace_id = *target_workspace_id;
 
                 let Some(current_workspace_id) =
-                    self.virtual_workspace_manager.workspace_for_window(op_space, focused_window)
+                    self.virtual_workspace_manager.workspace_for_window(space, focused_window)
                 else {
                     return EventResponse::default();
                 };
@@ -1649,84 +3919,53 @@ impl LayoutEngine {
                     return EventResponse::default();
                 }
 
-                let is_floating = self.floating.is_floating(focused_window);
-
-                if is_floating {
-                    self.floating.remove_active(op_space, focused_window.pid, focused_window);
-                } else if let Some(_layout) =
-                    self.workspace_layouts.active(op_space, current_workspace_id)
-                {
-                    self.tree.remove_window(focused_window);
-                }
-
-                let assigned = self.virtual_workspace_manager.assign_window_to_workspace(
-                    op_space,
+                if !self.transfer_window(
+                    space,
                     focused_window,
+                    current_workspace_id,
                     target_workspace_id,
-                );
-                if !assigned {
-                    if is_floating {
-                        self.floating.add_active(op_space, focused_window.pid, focused_window);
-                    } else if let Some(prev_layout) =
-                        self.workspace_layouts.active(op_space, current_workspace_id)
-                    {
-                        self.tree.add_window_after_selection(prev_layout, focused_window);
-                    }
+                ) {
                     return EventResponse::default();
                 }
+                self.virtual_workspace_manager.set_placed_by_hand(space, focused_window, true);
 
-                if !is_floating
-                    && let Some(target_layout) =
-                        self.workspace_layouts.active(op_space, target_workspace_id)
-                {
-                    self.tree.add_window_after_selection(target_layout, focused_window);
-                }
-
-                let active_workspace = self.virtual_workspace_manager.active_workspace(op_space);
+                let active_workspace = self.virtual_workspace_manager.active_workspace(space);
 
                 if Some(target_workspace_id) == active_workspace {
-                    if is_floating {
-                        self.floating.add_active(op_space, focused_window.pid, focused_window);
-                    }
                     return EventResponse {
                         focus_window: Some(focused_window),
                         raise_windows: vec![],
                         workspace_changed_to: None,
+                        layout_changed: false,
+                        fullscreen_windows: Vec::new(),
                     };
                 }
 
                 self.focused_window = None;
                 self.virtual_workspace_manager.set_last_focused_window(
-                    op_space,
+                    space,
                     current_workspace_id,
                     None,
                 );
 
-                let remaining_windows =
-                    self.virtual_workspace_manager.windows_in_active_workspace(op_space);
-
-                if Some(target_workspace_id) != active_workspace {
-                    self.virtual_workspace_manager.set_last_focused_window(
-                        op_space,
-                        target_workspace_id,
-                        Some(focused_window),
-                    );
-                    return EventResponse {
-                        workspace_changed_to: Some(target_workspace_id),
-                        focus_window: Some(focused_window),
-                        raise_windows: vec![],
-                    };
+                if !self.virtual_workspace_manager.move_follows_window() {
+                    self.broadcast_windows_changed(space);
+                    return self.refocus_workspace(space, current_workspace_id);
                 }
 
-                if let Some(&new_focus) = remaining_windows.first() {
-                    return EventResponse {
-                        focus_window: Some(new_focus),
-                        raise_windows: vec![],
-                        workspace_changed_to: None,
-                    };
+                // Switch in the same command so the window never disappears
+                // between the move and the switch.
+                self.virtual_workspace_manager.set_last_focused_window(
+                    space,
+                    target_workspace_id,
+                    Some(focused_window),
+                );
+                let mut response = self.activate_workspace(space, target_workspace_id);
+                response.workspace_changed_to = Some(target_workspace_id);
+                if !response.raise_windows.contains(&focused_window) {
+                    response.raise_windows.push(focused_window);
                 }
-
-                EventResponse::default()
+                response
             }
             LayoutCommand::CreateWorkspace => {
                 match self.virtual_workspace_manager.create_workspace(space, None) {
@@ -1741,14 +3980,22 @@ impl LayoutEngine {
             }
             LayoutCommand::SwitchToLastWorkspace => {
                 if let Some(last_workspace) = self.virtual_workspace_manager.last_workspace(space) {
-                    self.virtual_workspace_manager.set_active_workspace(space, last_workspace);
-
-                    self.update_active_floating_windows(space);
-
+                    return self.activate_workspace(space, last_workspace);
+                }
+                EventResponse::default()
+            }
+            LayoutCommand::MergeWorkspaces { from, into } => {
+                let workspaces = self.virtual_workspace_manager_mut().list_workspaces(space);
+                let (Some(&(src, _)), Some(&(dst, _))) =
+                    (workspaces.get(*from), workspaces.get(*into))
+                else {
+                    return EventResponse::default();
+                };
+                self.merge_workspaces(space, src, dst)
+            }
+            LayoutCommand::ReorderWorkspace { from, to } => {
+                if self.virtual_workspace_manager.move_workspace(space, *from, *to) {
                     self.broadcast_workspace_changed(space);
-                    self.broadcast_windows_changed(space);
-
-                    return self.refocus_workspace(space, last_workspace);
                 }
                 EventResponse::default()
             }
@@ -1756,6 +4003,77 @@ impl LayoutEngine {
         }
     }
 
+    /// Moves every window of `src` into `dst` and removes `src` from `space`.
+    /// Tiled windows join `dst`'s layout after its selection and floating
+    /// windows keep their stored positions. If `src` was active, `dst` takes
+    /// its place.
+    pub fn merge_workspaces(
+        &mut self,
+        space: SpaceId,
+        src: VirtualWorkspaceId,
+        dst: VirtualWorkspaceId,
+    ) -> EventResponse {
+        if src == dst
+            || self.virtual_workspace_manager.workspace_info(space, src).is_none()
+            || self.virtual_workspace_manager.workspace_info(space, dst).is_none()
+        {
+            return EventResponse::default();
+        }
+        let Some(size) = self.workspace_layouts.active_size(space) else {
+            warn!(?space, "No layouts for space; not merging workspaces");
+            return EventResponse::default();
+        };
+        self.ensure_active_layouts(space, size, [src, dst]);
+        let (Some(src_layout), Some(dst_layout)) = (
+            self.workspace_layouts.active(space, src),
+            self.workspace_layouts.active(space, dst),
+        ) else {
+            return EventResponse::default();
+        };
+
+        let was_active = self.virtual_workspace_manager.active_workspace(space) == Some(src);
+        let src_focus = self.virtual_workspace_manager.last_focused_window(space, src);
+        for wid in self.virtual_workspace_manager.workspace_windows(space, src) {
+            let tiled = self.tree.contains_window(src_layout, wid);
+            let floating_frame =
+                self.virtual_workspace_manager.get_floating_position(space, src, wid);
+            if !self.virtual_workspace_manager.assign_window_to_workspace(space, wid, dst) {
+                warn!(?wid, "Could not move window while merging workspaces");
+                continue;
+            }
+            self.virtual_workspace_manager.set_placed_by_hand(space, wid, true);
+            if tiled {
+                self.tree.remove_window(wid);
+                self.tree.add_window_after_selection(dst_layout, wid);
+            } else if let Some(frame) = floating_frame {
+                self.virtual_workspace_manager.store_floating_position(space, dst, wid, frame);
+            }
+        }
+
+        if was_active {
+            self.virtual_workspace_manager.set_active_workspace(space, dst);
+            if src_focus.is_some() {
+                self.virtual_workspace_manager.set_last_focused_window(space, dst, src_focus);
+            }
+        }
+        if self.virtual_workspace_manager.remove_workspace(space, src) {
+            self.workspace_layouts.remove_workspace(space, src, &mut self.tree);
+        } else {
+            warn!(?space, ?src, "Merged workspace still has windows; keeping it");
+        }
+        self.update_active_floating_windows(space);
+        self.broadcast_workspace_changed(space);
+        self.broadcast_windows_changed(space);
+
+        if was_active {
+            return self.refocus_workspace(space, dst);
+        }
+        EventResponse {
+            layout_changed: true,
+            ..Default::default()
+        }
+    }
+
     pub fn virtual_workspace_manager(&self) -> &VirtualWorkspaceManager {
         &self.virtual_workspace_manager
     }
@@ -1779,11 +4097,14 @@ impl LayoutEngine {
         target_screen_size: CGSize,
         window_id: WindowId,
     ) -> EventResponse {
+        self.last_app_windows.retain(|&(_, p), _| p != window_id.pid);
         if source_space == target_space {
             return EventResponse {
                 raise_windows: vec![window_id],
                 focus_window: Some(window_id),
                 workspace_changed_to: None,
+                layout_changed: false,
+                fullscreen_windows: Vec::new(),
             };
         }
 
@@ -1841,17 +4162,20 @@ impl LayoutEngine {
 
         {
             let workspace_ids = self.virtual_workspace_manager.list_workspaces(target_space);
-            self.workspace_layouts.ensure_active_for_space(
+            self.ensure_active_layouts(
                 target_space,
                 target_screen_size,
                 workspace_ids.iter().map(|(id, _)| *id),
-                &mut self.tree,
             );
         }
 
+        self.virtual_workspace_manager
+            .set_placed_by_hand(source_space, window_id, false);
+        self.virtual_workspace_manager.set_placed_by_hand(target_space, window_id, true);
+
         if was_floating {
             self.floating.add_active(target_space, window_id.pid, window_id);
-            self.floating.set_last_focus(Some(window_id));
+            self.note_floating_focus(target_space, window_id);
         } else if let Some(target_layout) =
             self.workspace_layouts.active(target_space, target_workspace_id)
         {
@@ -1888,6 +4212,8 @@ impl LayoutEngine {
             raise_windows: vec![window_id],
             focus_window: Some(window_id),
             workspace_changed_to: None,
+            layout_changed: false,
+            fullscreen_windows: Vec::new(),
         }
     }
 
@@ -1920,6 +4246,12 @@ impl LayoutEngine {
         self.virtual_workspace_manager.remove_app_floating_positions(pid);
     }
 
+    /// Whether `window_id` belongs to a workspace on `space`, tiled or
+    /// floating, rather than being left alone.
+    pub fn is_managed(&self, space: SpaceId, window_id: WindowId) -> bool {
+        self.virtual_workspace_manager.workspace_for_window(space, window_id).is_some()
+    }
+
     pub fn is_window_floating(&self, window_id: WindowId) -> bool {
         self.floating.is_floating(window_id)
     }
@@ -1939,22 +4271,162 @@ impl LayoutEngine {
             .store_current_floating_positions(space, floating_positions);
     }
 
-    pub fn broadcast_workspace_changed(&self, space_id: SpaceId) {
-        if let Some(ref broadcast_tx) = self.broadcast_tx
-            && let Some((active_workspace_id, active_workspace_name)) =
-                self.active_workspace_id_and_name(space_id)
-        {
-            let display_uuid = self.display_uuid_for_space(space_id);
-            broadcast_tx.send(BroadcastEvent::WorkspaceChanged {
-                workspace_id: active_workspace_id,
-                workspace_name: active_workspace_name.clone(),
-                space_id,
-                display_uuid,
-            });
+    /// Holds back workspace and window broadcasts until
+    /// [`flush_deferred_broadcasts`](Self::flush_deferred_broadcasts).
+    pub fn defer_broadcasts(&mut self) {
+        self.deferred_broadcasts.get_or_insert_with(Default::default);
+    }
+
+    /// Sends at most one of each broadcast per space that changed since
+    /// [`defer_broadcasts`](Self::defer_broadcasts), describing its state now.
+    pub fn flush_deferred_broadcasts(&mut self) {
+        let Some(deferred) = self.deferred_broadcasts.take() else {
+            return;
+        };
+        for (space, wid, workspace, by_rule) in deferred.assigned {
+            self.broadcast_window_assigned(space, wid, workspace, by_rule);
+        }
+        for space in deferred.workspace {
+            self.broadcast_workspace_changed(space);
+        }
+        for space in deferred.windows {
+            self.broadcast_windows_changed(space);
+        }
+    }
+
+    /// Opacity and always-on-top hints app rules gave windows.
+    pub fn window_display_hints(&self) -> &HashMap<WindowId, DisplayHints> {
+        &self.window_display_hints
+    }
+
+    /// Re-evaluates the display hints of `wid` against the app rules, for
+    /// rules that match on a title that just changed.
+    pub fn refresh_display_hints(
+        &mut self,
+        wid: WindowId,
+        app_info: Option<&AppInfo>,
+        title: Option<&str>,
+        ax_role: Option<&str>,
+        ax_subrole: Option<&str>,
+    ) {
+        if let Some(inputs) = self.window_rule_inputs.get_mut(&wid) {
+            inputs.title = title.map(str::to_owned);
+        }
+        let Some((space, _)) = self.window_owner(wid) else {
+            return;
+        };
+        let hints = self.virtual_workspace_manager.display_hints_for(
+            space,
+            app_info.and_then(|info| info.bundle_id.as_deref()),
+            app_info.and_then(|info| info.localized_name.as_deref()),
+            title,
+            ax_role,
+            ax_subrole,
+        );
+        self.set_display_hints(space, wid, hints);
+    }
+
+    fn set_display_hints(&mut self, space: SpaceId, wid: WindowId, hints: DisplayHints) {
+        let previous = self.window_display_hints.get(&wid).copied().unwrap_or_default();
+        if previous == hints {
+            return;
+        }
+        if hints.is_default() {
+            self.window_display_hints.remove(&wid);
+        } else {
+            self.window_display_hints.insert(wid, hints);
+        }
+        if let Some(ref broadcast_tx) = self.broadcast_tx {
+            broadcast_tx.send(BroadcastEvent::WindowDisplayHintsChanged {
+                window_id: wid,
+                space_id: space,
+                hints,
+            });
+        }
+    }
+
+    fn broadcast_window_assigned(
+        &mut self,
+        space_id: SpaceId,
+        window_id: WindowId,
+        workspace_id: VirtualWorkspaceId,
+        by_rule: bool,
+    ) {
+        if let Some(deferred) = self.deferred_broadcasts.as_mut() {
+            deferred.assigned.push((space_id, window_id, workspace_id, by_rule));
+            return;
+        }
+        if let Some(ref broadcast_tx) = self.broadcast_tx {
+            broadcast_tx.send(BroadcastEvent::WindowAssigned {
+                window_id,
+                workspace_id,
+                by_rule,
+                space_id,
+            });
+        }
+    }
+
+    pub fn broadcast_workspace_changed(&mut self, space_id: SpaceId) {
+        if let Some(deferred) = self.deferred_broadcasts.as_mut() {
+            deferred.workspace.insert(space_id);
+            return;
+        }
+        if let Some(ref broadcast_tx) = self.broadcast_tx
+            && let Some((active_workspace_id, active_workspace_name)) =
+                self.active_workspace_id_and_name(space_id)
+        {
+            let display_uuid = self.display_uuid_for_space(space_id);
+            broadcast_tx.send(BroadcastEvent::WorkspaceChanged {
+                workspace_id: active_workspace_id,
+                workspace_name: active_workspace_name.clone(),
+                space_id,
+                display_uuid,
+            });
         }
     }
 
-    pub fn broadcast_windows_changed(&self, space_id: SpaceId) {
+    /// Every window on `space`, on any workspace, ordered by window id.
+    fn window_details(&self, space: SpaceId) -> Vec<WindowDetails> {
+        let mut names: HashMap<VirtualWorkspaceId, String> = HashMap::default();
+        let active = self.virtual_workspace_manager.active_workspace(space);
+        let mut windows: Vec<_> = self
+            .virtual_workspace_manager
+            .window_to_workspace
+            .iter()
+            .filter(|((s, _), _)| *s == space)
+            .map(|(&(_, wid), &workspace_id)| (wid, workspace_id))
+            .collect();
+        windows.sort_unstable_by_key(|&(wid, _)| wid);
+        windows
+            .into_iter()
+            .map(|(wid, workspace_id)| {
+                let workspace_name = names
+                    .entry(workspace_id)
+                    .or_insert_with(|| {
+                        self.virtual_workspace_manager
+                            .workspace_info(space, workspace_id)
+                            .map(|ws| ws.name.clone())
+                            .unwrap_or_else(|| format!("Workspace {:?}", workspace_id))
+                    })
+                    .clone();
+                WindowDetails {
+                    id: wid.to_debug_string(),
+                    pid: wid.pid,
+                    workspace_id,
+                    workspace_name,
+                    is_floating: self.floating.is_floating(wid),
+                    is_focused: self.focused_window == Some(wid),
+                    is_active_workspace: active == Some(workspace_id),
+                }
+            })
+            .collect()
+    }
+
+    pub fn broadcast_windows_changed(&mut self, space_id: SpaceId) {
+        if let Some(deferred) = self.deferred_broadcasts.as_mut() {
+            deferred.windows.insert(space_id);
+            return;
+        }
         if let Some(ref broadcast_tx) = self.broadcast_tx
             && let Some((workspace_id, workspace_name)) =
                 self.active_workspace_id_and_name(space_id)
@@ -1973,6 +4445,7 @@ impl LayoutEngine {
                 windows,
                 space_id,
                 display_uuid,
+                window_details: self.window_details(space_id),
             };
 
             broadcast_tx.send(event);
@@ -2018,8 +4491,86 @@ impl LayoutEngine {
         }
     }
 
-    fn rebalance_all_layouts(&mut self) {
+    /// Rebalances every active layout of every workspace, for a full
+    /// refresh. Events only touch one space, so they use
+    /// [`rebalance_space`](Self::rebalance_space).
+    pub fn rebalance_all_layouts(&mut self) {
+        let start = Instant::now();
         self.workspace_layouts.for_each_active(|layout| self.tree.rebalance(layout));
+        self.metrics.rebalance.record_since(start);
+    }
+
+    /// Rebalances the active layouts of the workspaces on `space`.
+    fn rebalance_space(&mut self, space: SpaceId) {
+        let start = Instant::now();
+        for (_, layout) in self.workspace_layouts.active_layouts_for_space(space) {
+            self.tree.rebalance(layout);
+        }
+        self.metrics.rebalance.record_since(start);
+    }
+
+    /// Rebalances the active layout of one workspace.
+    fn rebalance_workspace(&mut self, space: SpaceId, workspace: VirtualWorkspaceId) {
+        let start = Instant::now();
+        if let Some(layout) = self.workspace_layouts.active(space, workspace) {
+            self.tree.rebalance(layout);
+        }
+        self.metrics.rebalance.record_since(start);
+    }
+
+    /// Stacks the root of `layout` once it holds more tiled windows than
+    /// `auto_stack_threshold`. Roots whose kind differs from
+    /// `default_root_kind` were set up by hand and are left alone.
+    fn auto_stack(&mut self, layout: LayoutId) {
+        let threshold = self.layout_settings.auto_stack_threshold;
+        if threshold == 0 || self.auto_stacked.contains(&layout) {
+            return;
+        }
+        let LayoutSystemKind::Traditional(s) = &mut self.tree else {
+            return;
+        };
+        if s.root_kind(layout) != self.layout_settings.default_root_kind
+            || s.root_kind(layout).is_stacked()
+            || s.visible_windows_in_layout(layout).len() <= threshold
+        {
+            return;
+        }
+        s.stack_root(layout, self.layout_settings.stack.default_orientation);
+        self.auto_stacked.insert(layout);
+        debug!(?layout, threshold, "Stacked crowded layout");
+    }
+
+    /// Collapses needless containers in `layout` once its windows are nested
+    /// deeper than `max_depth`.
+    fn limit_depth(&mut self, layout: LayoutId) {
+        let Some(max_depth) = self.layout_settings.max_depth else {
+            return;
+        };
+        let LayoutSystemKind::Traditional(s) = &mut self.tree else {
+            return;
+        };
+        if s.max_depth(layout) <= max_depth {
+            return;
+        }
+        let removed = s.flatten_to_depth(layout, max_depth);
+        debug!(?layout, max_depth, removed, "Flattened deep layout");
+    }
+
+    /// Counts and timings of the engine's work so far, with the size of every
+    /// active layout.
+    pub fn metrics_snapshot(&self) -> EngineMetrics {
+        let mut layouts = Vec::new();
+        for space in self.workspace_layouts.spaces() {
+            for (workspace, layout) in self.workspace_layouts.active_layouts_for_space(space) {
+                layouts.push(LayoutTreeSize {
+                    space: space.get(),
+                    workspace: workspace.to_string(),
+                    windows: self.tree.visible_windows_in_layout(layout).len(),
+                });
+            }
+        }
+        layouts.sort_by(|a, b| (a.space, &a.workspace).cmp(&(b.space, &b.workspace)));
+        self.metrics.snapshot(layouts)
     }
 
     pub fn is_window_in_active_workspace(&self, space: SpaceId, window_id: WindowId) -> bool {
@@ -2029,6 +4580,8 @@ impl LayoutEngine {
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
+
     use objc2_core_foundation::CGPoint;
 
     use super::*;
@@ -2081,6 +4634,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn next_space_for_direction_handles_stacked_displays() {
+        let engine = test_engine();
+        let top = SpaceId::new(1);
+        let bottom = SpaceId::new(2);
+        let mut centers = HashMap::default();
+        centers.insert(top, CGPoint::new(960.0, -540.0));
+        centers.insert(bottom, CGPoint::new(960.0, 540.0));
+        let visible_spaces = vec![top, bottom];
+
+        assert_eq!(
+            engine.next_space_for_direction(bottom, Direction::Up, &visible_spaces, &centers),
+            Some(top)
+        );
+        assert_eq!(
+            engine.next_space_for_direction(top, Direction::Down, &visible_spaces, &centers),
+            Some(bottom)
+        );
+        assert_eq!(
+            engine.next_space_for_direction(top, Direction::Up, &visible_spaces, &centers),
+            None
+        );
+        assert_eq!(
+            engine.next_space_for_direction(bottom, Direction::Down, &visible_spaces, &centers),
+            None
+        );
+    }
+
     #[test]
     fn test_layout_engine_initial_state() {
         let settings = crate::common::config::VirtualWorkspaceSettings::default();
@@ -2114,4 +4695,2566 @@ mod tests {
         let stats = engine.virtual_workspace_manager.get_stats();
         let _ = stats.total_workspaces;
     }
+
+    struct WarnCounter(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for WarnCounter {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if *event.metadata().level() <= tracing::Level::WARN {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+    }
+
+    fn engine_with_only_floating_windows(space: SpaceId) -> (LayoutEngine, Vec<WindowId>) {
+        let mut engine = test_engine();
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+
+        let floats = vec![WindowId::new(1, 1), WindowId::new(1, 2)];
+        for &wid in &floats {
+            let _ = engine.handle_event(LayoutEvent::WindowAdded(space, wid));
+            let _ = engine.handle_event(LayoutEvent::WindowFocused(space, wid));
+            let _ = engine.handle_command(
+                Some(space),
+                &[space],
+                &HashMap::default(),
+                LayoutCommand::ToggleWindowFloating,
+            );
+        }
+        assert!(engine.visible_windows_in_space(space).is_empty());
+        (engine, floats)
+    }
+
+    fn all_commands(floats: &[WindowId]) -> Vec<LayoutCommand> {
+        let mut commands = vec![
+            LayoutCommand::NextWindow,
+            LayoutCommand::PrevWindow,
+            LayoutCommand::Ascend,
+            LayoutCommand::Descend,
+            LayoutCommand::ToggleStack,
+            LayoutCommand::ToggleTabbed,
+            LayoutCommand::ToggleOrientation,
+            LayoutCommand::RotateSubtree,
+            LayoutCommand::RotateLayout { clockwise: true },
+            LayoutCommand::MirrorLayout { axis: Orientation::Horizontal },
+            LayoutCommand::UnjoinWindows,
+            LayoutCommand::ToggleFullscreen,
+            LayoutCommand::ToggleFullscreenWithinGaps,
+            LayoutCommand::IsolateAndMaximize,
+            LayoutCommand::TogglePreserveSize,
+            LayoutCommand::ToggleContainerLock,
+            LayoutCommand::PromoteToMain,
+            LayoutCommand::FlipContainer,
+            LayoutCommand::Undo,
+            LayoutCommand::Redo,
+            LayoutCommand::ResizeWindowGrow,
+            LayoutCommand::ResizeWindowShrink,
+            LayoutCommand::ResizeWindowBy { amount: 0.1 },
+            LayoutCommand::GoldenRatio { main_first: true },
+            LayoutCommand::TwoColumn { master_on_left: true },
+            LayoutCommand::SplitInto {
+                kind: LayoutKind::Vertical,
+                count: 3,
+            },
+            LayoutCommand::SwapWindows(floats[0], floats[1]),
+            LayoutCommand::ToggleFocusFloating,
+        ];
+        for direction in [
+            Direction::Left,
+            Direction::Right,
+            Direction::Up,
+            Direction::Down,
+        ] {
+            commands.push(LayoutCommand::MoveFocus(direction));
+            commands.push(LayoutCommand::MoveNode(direction));
+            commands.push(LayoutCommand::JoinWindow(direction));
+        }
+        commands
+    }
+
+    #[test]
+    fn commands_on_empty_layout_with_floats_do_not_warn_or_panic() {
+        let space = SpaceId::new(1);
+        let warnings = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let subscriber = tracing_subscriber::layer::SubscriberExt::with(
+            tracing_subscriber::registry(),
+            WarnCounter(warnings.clone()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let (mut engine, floats) = engine_with_only_floating_windows(space);
+            for command in all_commands(&floats) {
+                let response =
+                    engine.handle_command(Some(space), &[space], &HashMap::default(), command);
+                assert!(response.raise_windows.iter().all(|wid| floats.contains(wid)));
+                if let Some(focus) = response.focus_window {
+                    assert!(floats.contains(&focus));
+                }
+                assert!(engine.visible_windows_in_space(space).is_empty());
+            }
+        });
+
+        assert_eq!(warnings.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn focus_commands_on_empty_layout_cycle_floating_windows() {
+        let space = SpaceId::new(1);
+        let (mut engine, floats) = engine_with_only_floating_windows(space);
+        engine.set_focused_window(floats[0]);
+
+        let response = engine.handle_command(
+            Some(space),
+            &[space],
+            &HashMap::default(),
+            LayoutCommand::MoveFocus(Direction::Right),
+        );
+        assert_eq!(response.focus_window, Some(floats[1]));
+
+        let response = engine.handle_command(
+            Some(space),
+            &[space],
+            &HashMap::default(),
+            LayoutCommand::NextWindow,
+        );
+        assert_eq!(response.focus_window, Some(floats[0]));
+
+        let response = engine.handle_command(
+            Some(space),
+            &[space],
+            &HashMap::default(),
+            LayoutCommand::PrevWindow,
+        );
+        assert_eq!(response.focus_window, Some(floats[1]));
+    }
+
+    #[test]
+    fn directional_focus_on_empty_layout_moves_to_the_next_space() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let right_screen = CGRect::new(CGPoint::new(1000.0, 0.0), CGSize::new(1000.0, 800.0));
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0), right_screen]);
+        let float = world.add_app(1, 1)[0];
+        world.focus(float);
+        world.run_command(LayoutCommand::ToggleWindowFloating);
+        let tiled = world.add_app_on(world.space(1), 2, 1)[0];
+
+        world.focus(float);
+        let response = world.run_command(LayoutCommand::MoveFocus(Direction::Right));
+        assert_eq!(response.focus_window, Some(tiled));
+        assert_eq!(world.command_space(), world.space(1));
+
+        world.focus(float);
+        let response = world.run_command(LayoutCommand::MoveFocus(Direction::Down));
+        assert_eq!(response.focus_window, None);
+    }
+
+    #[test]
+    fn structural_commands_on_empty_layout_are_noops() {
+        let space = SpaceId::new(1);
+        let (mut engine, _floats) = engine_with_only_floating_windows(space);
+
+        for command in [
+            LayoutCommand::JoinWindow(Direction::Left),
+            LayoutCommand::MoveNode(Direction::Right),
+            LayoutCommand::ResizeWindowGrow,
+            LayoutCommand::ToggleStack,
+        ] {
+            let response =
+                engine.handle_command(Some(space), &[space], &HashMap::default(), command);
+            assert_eq!(response, EventResponse::default());
+        }
+    }
+
+    #[test]
+    fn insertion_after_empty_period_lands_at_root() {
+        let space = SpaceId::new(1);
+        let (mut engine, _floats) = engine_with_only_floating_windows(space);
+
+        for command in [
+            LayoutCommand::Ascend,
+            LayoutCommand::ToggleStack,
+            LayoutCommand::JoinWindow(Direction::Left),
+        ] {
+            let _ = engine.handle_command(Some(space), &[space], &HashMap::default(), command);
+        }
+
+        let tiled = WindowId::new(2, 1);
+        let _ = engine.handle_event(LayoutEvent::WindowAdded(space, tiled));
+        assert_eq!(engine.visible_windows_in_space(space), vec![tiled]);
+        assert_eq!(engine.selected_window(space), Some(tiled));
+    }
+
+    fn engine_with_toggle_mode(space: SpaceId, mode: StackToggleMode) -> LayoutEngine {
+        let mut layout_settings = LayoutSettings::default();
+        layout_settings.stack.toggle_mode = mode;
+        let mut engine =
+            LayoutEngine::new(&VirtualWorkspaceSettings::default(), &layout_settings, None);
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+        for idx in 1..=2 {
+            let _ = engine.handle_event(LayoutEvent::WindowAdded(space, WindowId::new(1, idx)));
+        }
+        engine
+    }
+
+    fn toggle_stack(engine: &mut LayoutEngine, space: SpaceId) -> (EventResponse, bool) {
+        let response = engine.handle_command(
+            Some(space),
+            &[space],
+            &HashMap::default(),
+            LayoutCommand::ToggleStack,
+        );
+        let layout = engine.layout(space);
+        (response, engine.tree.parent_of_selection_is_stacked(layout))
+    }
+
+    #[test]
+    fn toggle_stack_modes_alternate_stacking() {
+        let space = SpaceId::new(1);
+        for mode in [StackToggleMode::UnstackFirst, StackToggleMode::StackFirst] {
+            let mut engine = engine_with_toggle_mode(space, mode);
+            for expected_stacked in [true, false, true] {
+                let (response, stacked) = toggle_stack(&mut engine, space);
+                assert_eq!(stacked, expected_stacked, "{mode:?}");
+                assert_eq!(response.raise_windows.len(), 2, "{mode:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn toggle_stack_cycle_mode_unstacks_every_third_press() {
+        let space = SpaceId::new(1);
+        let mut engine = engine_with_toggle_mode(space, StackToggleMode::Cycle);
+        for expected_stacked in [true, true, false, true] {
+            let (response, stacked) = toggle_stack(&mut engine, space);
+            assert_eq!(stacked, expected_stacked);
+            assert_eq!(response.raise_windows.len(), 2);
+        }
+    }
+
+    #[test]
+    fn select_stack_member_focuses_and_reveals_member() {
+        use crate::common::config::{GapSettings, HorizontalPlacement, VerticalPlacement};
+
+        let space = SpaceId::new(1);
+        let mut engine = engine_with_toggle_mode(space, StackToggleMode::StackFirst);
+        let _ = engine.handle_event(LayoutEvent::WindowAdded(space, WindowId::new(1, 3)));
+        let (_, stacked) = toggle_stack(&mut engine, space);
+        assert!(stacked);
+
+        let screen = CGRect::new(CGPoint::new(0.0, 0.0), CGSize::new(1920.0, 1080.0));
+        let groups = engine.collect_group_containers_in_selection_path(
+            space,
+            screen,
+            &GapSettings::default(),
+            6.0,
+            HorizontalPlacement::Top,
+            VerticalPlacement::Right,
+        );
+        let stack = groups.last().expect("stack in selection path");
+        assert_eq!(stack.total_count, 3);
+
+        let target = stack.window_ids[0];
+        let response = engine.select_stack_member(space, stack.node_id, 0);
+        assert_eq!(response.focus_window, Some(target));
+        assert_eq!(response.raise_windows, vec![target]);
+        assert_eq!(engine.selected_window(space), Some(target));
+
+        assert_eq!(
+            engine.select_stack_member(space, stack.node_id, 3),
+            EventResponse::default()
+        );
+        assert_eq!(engine.selected_window(space), Some(target));
+    }
+
+    #[test]
+    fn background_windows_do_not_take_the_selection() {
+        let space = SpaceId::new(1);
+        let mut engine = test_engine();
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+        let (main, background) = (WindowId::new(1, 1), WindowId::new(1, 2));
+        let _ = engine.handle_event(LayoutEvent::WindowAdded(space, main));
+        let _ = engine.handle_event(LayoutEvent::WindowFocused(space, main));
+
+        let _ = engine.handle_event(LayoutEvent::WindowAdded(space, background));
+
+        assert_eq!(engine.visible_windows_in_space(space).len(), 2);
+        assert_eq!(engine.selected_window(space), Some(main));
+    }
+
+    #[test]
+    fn background_app_window_lists_do_not_take_the_selection() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let space = world.space(0);
+        let windows = world.add_app(1, 2);
+        world.focus(windows[1]);
+
+        world.add_app(2, 2);
+        assert_eq!(world.engine_mut().selected_window(space), Some(windows[1]));
+        world.add_app(1, 1);
+        assert_eq!(world.engine_mut().selected_window(space), Some(windows[1]));
+        assert_eq!(world.visible_windows().len(), 5);
+    }
+
+    #[test]
+    fn windows_moved_to_another_display_join_its_active_workspace() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0), screen(1000.0, 800.0)]);
+        let (left, right) = (world.space(0), world.space(1));
+        let moved = world.add_app_on(left, 1, 2);
+        let resident = world.add_app_on(right, 2, 1);
+
+        let response = world.engine_mut().handle_event(LayoutEvent::WindowMovedToSpace {
+            wid: moved[1],
+            from: None,
+            to: right,
+        });
+
+        assert!(response.layout_changed);
+        assert_eq!(response.raise_windows, vec![resident[0], moved[1]]);
+        let engine = world.engine();
+        assert_eq!(engine.visible_windows_in_space(left), vec![moved[0]]);
+        let manager = engine.virtual_workspace_manager();
+        let workspace = engine.active_workspace(right).unwrap();
+        assert_eq!(manager.workspace_for_window(left, moved[1]), None);
+        assert_eq!(manager.workspace_for_window(right, moved[1]), Some(workspace));
+        assert_eq!(manager.last_focused_window(right, workspace), Some(moved[1]));
+    }
+
+    #[test]
+    fn windows_focused_around_their_addition_are_selected() {
+        let space = SpaceId::new(1);
+        let mut engine = test_engine();
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+        let windows = [
+            WindowId::new(1, 1),
+            WindowId::new(1, 2),
+            WindowId::new(1, 3),
+        ];
+        let _ = engine.handle_event(LayoutEvent::WindowAdded(space, windows[0]));
+
+        let _ = engine.handle_event(LayoutEvent::WindowFocused(space, windows[1]));
+        let _ = engine.handle_event(LayoutEvent::WindowAdded(space, windows[1]));
+        assert_eq!(engine.selected_window(space), Some(windows[1]));
+
+        let _ = engine.handle_event(LayoutEvent::WindowAdded(space, windows[2]));
+        let _ = engine.handle_event(LayoutEvent::WindowFocused(space, windows[2]));
+        assert_eq!(engine.selected_window(space), Some(windows[2]));
+    }
+
+    #[test]
+    fn window_events_wait_for_their_space_to_be_exposed() {
+        let space = SpaceId::new(1);
+        let mut engine = test_engine();
+        let windows = [WindowId::new(1, 1), WindowId::new(1, 2)];
+        for wid in windows {
+            let _ = engine.handle_event(LayoutEvent::WindowAdded(space, wid));
+        }
+        let _ = engine.handle_event(LayoutEvent::WindowFocused(space, windows[0]));
+        assert!(engine.visible_windows_in_space(space).is_empty());
+
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+
+        assert_eq!(engine.visible_windows_in_space(space), windows);
+        let active = engine.active_workspace(space);
+        assert!(active.is_some());
+        for wid in windows {
+            assert_eq!(
+                engine.virtual_workspace_manager().workspace_for_window(space, wid),
+                active
+            );
+        }
+        assert_eq!(engine.selected_window(space), Some(windows[0]));
+    }
+
+    #[test]
+    fn exposure_reports_the_responses_of_replayed_events() {
+        let (left, right) = (SpaceId::new(1), SpaceId::new(2));
+        let mut engine = test_engine();
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(left, CGSize::new(1920.0, 1080.0)));
+        let windows = [WindowId::new(1, 1), WindowId::new(1, 2)];
+        for wid in windows {
+            let _ = engine.handle_event(LayoutEvent::WindowAdded(left, wid));
+        }
+        let held = engine.handle_event(LayoutEvent::WindowMovedToSpace {
+            wid: windows[1],
+            from: Some(left),
+            to: right,
+        });
+        assert_eq!(held, EventResponse::default());
+
+        let response =
+            engine.handle_event(LayoutEvent::SpaceExposed(right, CGSize::new(1920.0, 1080.0)));
+
+        assert!(response.layout_changed);
+        assert_eq!(response.focus_window, Some(windows[1]));
+        assert_eq!(response.raise_windows, vec![windows[1]]);
+        assert_eq!(engine.visible_windows_in_space(left), vec![windows[0]]);
+    }
+
+    #[test]
+    fn app_windows_reported_before_exposure_are_tiled_once_exposed() {
+        let space = SpaceId::new(1);
+        let mut engine = test_engine();
+        let windows = [WindowId::new(7, 1), WindowId::new(7, 2)];
+        app_windows_on_screen(&mut engine, space, 7, &windows);
+
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+
+        assert_eq!(engine.visible_windows_in_space(space), windows);
+    }
+
+    #[test]
+    fn windows_removed_before_exposure_are_not_replayed() {
+        let space = SpaceId::new(1);
+        let mut engine = test_engine();
+        let (closed, kept) = (WindowId::new(1, 1), WindowId::new(2, 1));
+        let _ = engine.handle_event(LayoutEvent::WindowAdded(space, closed));
+        let _ = engine.handle_event(LayoutEvent::WindowAdded(space, kept));
+        let _ = engine.handle_event(LayoutEvent::WindowRemoved(closed));
+
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+
+        assert_eq!(engine.visible_windows_in_space(space), vec![kept]);
+    }
+
+    fn engine_with_two_spaces() -> (LayoutEngine, SpaceId, SpaceId) {
+        let mut engine = test_engine();
+        let (left, right) = (SpaceId::new(1), SpaceId::new(2));
+        for space in [left, right] {
+            let _ =
+                engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+        }
+        for wid in [WindowId::new(1, 1), WindowId::new(1, 2)] {
+            let _ = engine.handle_event(LayoutEvent::WindowAdded(left, wid));
+        }
+        for wid in [WindowId::new(2, 3), WindowId::new(2, 4)] {
+            let _ = engine.handle_event(LayoutEvent::WindowAdded(right, wid));
+        }
+        (engine, left, right)
+    }
+
+    #[test]
+    fn predicts_moves_that_cross_to_another_space() {
+        let (mut engine, left, right) = engine_with_two_spaces();
+        let spaces = [left, right];
+        let centers: HashMap<_, _> = [
+            (left, CGPoint::new(960.0, 540.0)),
+            (right, CGPoint::new(2880.0, 540.0)),
+        ]
+        .into_iter()
+        .collect();
+        let edge = WindowId::new(1, 2);
+        let _ = engine.handle_event(LayoutEvent::WindowFocused(left, edge));
+
+        assert_eq!(
+            engine.would_cross_space(left, Direction::Right, &spaces, &centers),
+            Some(right)
+        );
+        assert_eq!(
+            engine.would_cross_space(left, Direction::Left, &spaces, &centers),
+            None
+        );
+        assert_eq!(
+            engine.would_cross_space(left, Direction::Up, &spaces, &centers),
+            None
+        );
+        let layout = engine.layout(left);
+        assert_eq!(engine.tree.selected_window(layout), Some(edge));
+    }
+
+    fn swap(engine: &mut LayoutEngine, space: SpaceId, a: WindowId, b: WindowId) {
+        let _ = engine.handle_command(
+            Some(space),
+            &[space],
+            &HashMap::default(),
+            LayoutCommand::SwapWindows(a, b),
+        );
+    }
+
+    #[test]
+    fn cross_space_swap_is_rejected() {
+        let (mut engine, left, right) = engine_with_two_spaces();
+        let (a, b) = (WindowId::new(1, 1), WindowId::new(2, 3));
+
+        assert_eq!(
+            engine.validate_swap(a, b),
+            Err(WindowCommandError::CrossSpace {
+                window: a,
+                space: left,
+                other: b,
+                other_space: right,
+            })
+        );
+        swap(&mut engine, left, a, b);
+        let (left_layout, right_layout) = (engine.layout(left), engine.layout(right));
+        assert_eq!(
+            engine.tree.visible_windows_in_layout(left_layout),
+            vec![a, WindowId::new(1, 2)]
+        );
+        assert_eq!(
+            engine.tree.visible_windows_in_layout(right_layout),
+            vec![b, WindowId::new(2, 4)]
+        );
+    }
+
+    #[test]
+    fn swap_with_floating_window_is_rejected() {
+        let (mut engine, left, _) = engine_with_two_spaces();
+        let (a, b) = (WindowId::new(1, 1), WindowId::new(1, 2));
+        let _ = engine.handle_event(LayoutEvent::WindowFocused(left, b));
+        let _ = engine.handle_command(
+            Some(left),
+            &[left],
+            &HashMap::default(),
+            LayoutCommand::ToggleWindowFloating,
+        );
+
+        assert_eq!(engine.validate_swap(a, b), Err(WindowCommandError::NotTiled(b)));
+    }
+
+    #[test]
+    fn swap_acts_on_the_layout_owning_the_windows() {
+        let (mut engine, left, right) = engine_with_two_spaces();
+        let (a, b) = (WindowId::new(2, 3), WindowId::new(2, 4));
+
+        swap(&mut engine, left, a, b);
+        let right_layout = engine.layout(right);
+        assert_eq!(engine.tree.visible_windows_in_layout(right_layout), vec![b, a]);
+    }
+
+    #[test]
+    fn cross_space_move_to_workspace_hands_window_over() {
+        let (mut engine, left, right) = engine_with_two_spaces();
+        let wid = WindowId::new(1, 1);
+        let target = engine.virtual_workspace_manager_mut().list_workspaces(right)[1].0;
+
+        let response = engine.handle_virtual_workspace_command(
+            right,
+            &LayoutCommand::MoveWindowToWorkspace {
+                workspace: 1,
+                window_id: Some(wid.idx.get()),
+            },
+        );
+
+        assert_eq!(response.workspace_changed_to, Some(target));
+        assert_eq!(
+            engine.virtual_workspace_manager.workspace_for_window(left, wid),
+            None
+        );
+        assert_eq!(
+            engine.virtual_workspace_manager.workspace_for_window(right, wid),
+            Some(target)
+        );
+        let left_layout = engine.layout(left);
+        assert!(!engine.tree.contains_window(left_layout, wid));
+        let target_layout = engine.workspace_layouts.active(right, target).unwrap();
+        assert!(engine.tree.contains_window(target_layout, wid));
+    }
+
+    mod move_to_workspace {
+        use super::*;
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        fn world(move_follows_window: bool) -> SimulatedWorld {
+            let settings = VirtualWorkspaceSettings {
+                move_follows_window,
+                ..VirtualWorkspaceSettings::default()
+            };
+            SimulatedWorld::with_settings(
+                vec![screen(1000.0, 800.0)],
+                &LayoutSettings::default(),
+                &settings,
+            )
+        }
+
+        fn move_focused(world: &mut SimulatedWorld, workspace: usize) -> EventResponse {
+            world.run_command(LayoutCommand::MoveWindowToWorkspace { workspace, window_id: None })
+        }
+
+        #[test]
+        fn following_switches_in_the_same_command() {
+            let mut world = world(true);
+            let windows = world.add_app(1, 2);
+            world.focus(windows[1]);
+
+            let response = move_focused(&mut world, 1);
+            let space = world.space(0);
+            let target =
+                world.engine_mut().virtual_workspace_manager_mut().list_workspaces(space)[1].0;
+            assert_eq!(response.workspace_changed_to, Some(target));
+            assert_eq!(response.focus_window, Some(windows[1]));
+            assert!(response.raise_windows.contains(&windows[1]));
+            assert_eq!(world.engine().active_workspace(space), Some(target));
+            assert_eq!(world.visible_windows(), vec![windows[1]]);
+        }
+
+        #[test]
+        fn windows_in_workspace_predicts_frames_without_switching() {
+            use crate::common::config::{GapSettings, HorizontalPlacement, VerticalPlacement};
+
+            let mut world = world(false);
+            let windows = world.add_app(1, 3);
+            world.focus(windows[2]);
+            move_focused(&mut world, 1);
+            world.focus(windows[1]);
+            move_focused(&mut world, 1);
+
+            let space = world.space(0);
+            let engine = world.engine();
+            let active = engine.active_workspace(space).unwrap();
+            let other = engine.virtual_workspace_manager().workspace_at_index(space, 1).unwrap();
+            let windows_in = |workspace, screen| {
+                engine.windows_in_workspace(
+                    space,
+                    workspace,
+                    screen,
+                    &GapSettings::default(),
+                    0.0,
+                    HorizontalPlacement::Top,
+                    VerticalPlacement::Right,
+                )
+            };
+
+            assert_eq!(
+                windows_in(active, Some(screen(1000.0, 800.0))),
+                vec![(windows[0], None)]
+            );
+            let predicted = windows_in(other, Some(screen(1000.0, 800.0)));
+            let mut ids: Vec<_> = predicted.iter().map(|(wid, _)| *wid).collect();
+            ids.sort();
+            assert_eq!(ids, vec![windows[1], windows[2]]);
+            assert!(predicted.iter().all(|(_, frame)| frame.is_some_and(|f| f.size.width > 0.0)));
+            assert!(windows_in(other, None).iter().all(|(_, frame)| frame.is_none()));
+            assert_eq!(engine.active_workspace(space), Some(active));
+        }
+
+        #[test]
+        fn without_following_focus_stays_behind() {
+            let mut world = world(false);
+            let windows = world.add_app(1, 2);
+            world.focus(windows[1]);
+            let space = world.space(0);
+            let source = world.engine().active_workspace(space);
+
+            let response = move_focused(&mut world, 1);
+            assert_eq!(response.workspace_changed_to, None);
+            assert_eq!(response.focus_window, Some(windows[0]));
+            assert_eq!(world.engine().active_workspace(space), source);
+            assert_eq!(world.visible_windows(), vec![windows[0]]);
+        }
+
+        #[test]
+        fn moving_to_a_new_workspace_creates_its_layout() {
+            let mut world = world(true);
+            let windows = world.add_app(1, 2);
+            world.run_command(LayoutCommand::CreateWorkspace);
+            world.focus(windows[0]);
+
+            let space = world.space(0);
+            let last =
+                world.engine_mut().virtual_workspace_manager_mut().list_workspaces(space).len() - 1;
+            let response = move_focused(&mut world, last);
+            assert!(response.workspace_changed_to.is_some());
+            assert_eq!(world.visible_windows(), vec![windows[0]]);
+        }
+    }
+
+    #[test]
+    fn commands_report_whether_the_layout_changed() {
+        let (mut engine, left, _) = engine_with_two_spaces();
+        let mut run = |command| {
+            engine
+                .handle_command(Some(left), &[left], &HashMap::default(), command)
+                .layout_changed
+        };
+
+        assert!(!run(LayoutCommand::MoveFocus(Direction::Left)));
+        assert!(run(LayoutCommand::JoinWindow(Direction::Right)));
+        assert!(run(LayoutCommand::Undo));
+    }
+
+    fn app_windows_on_screen(
+        engine: &mut LayoutEngine,
+        space: SpaceId,
+        pid: pid_t,
+        windows: &[WindowId],
+    ) {
+        let info = AppInfo {
+            bundle_id: Some("com.example.picker".to_string()),
+            localized_name: None,
+        };
+        let _ = engine.handle_event(LayoutEvent::WindowsOnScreenUpdated(
+            space,
+            pid,
+            windows.iter().map(|&wid| (wid, None, None, None)).collect(),
+            Some(info),
+        ));
+    }
+
+    #[test]
+    fn window_added_before_screen_update_gets_one_node() {
+        for insert_near_app_windows in [false, true] {
+            let settings = LayoutSettings {
+                insert_near_app_windows,
+                ..LayoutSettings::default()
+            };
+            let mut engine =
+                LayoutEngine::new(&VirtualWorkspaceSettings::default(), &settings, None);
+            let space = SpaceId::new(1);
+            let _ =
+                engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+            let (first, second) = (WindowId::new(7, 1), WindowId::new(7, 2));
+            app_windows_on_screen(&mut engine, space, 7, &[first]);
+
+            // The add lands first, then the app's window list reports the new
+            // window (twice, as a list racing an add can) in another order.
+            let _ = engine.handle_event(LayoutEvent::WindowAdded(space, second));
+            app_windows_on_screen(&mut engine, space, 7, &[second, first, second]);
+
+            let mut visible = engine.visible_windows_in_space(space);
+            visible.sort();
+            assert_eq!(visible, vec![first, second]);
+            let LayoutSystemKind::Traditional(s) = &mut engine.tree else {
+                unreachable!()
+            };
+            assert_eq!(s.validate_window_bindings(), 0);
+        }
+    }
+
+    #[test]
+    fn always_floating_app_toggles_for_focused_app() {
+        let space = SpaceId::new(1);
+        let mut engine = test_engine();
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+        let windows = [WindowId::new(7, 1), WindowId::new(7, 2)];
+        app_windows_on_screen(&mut engine, space, 7, &windows);
+        let _ = engine.handle_event(LayoutEvent::WindowFocused(space, windows[0]));
+        assert_eq!(engine.visible_windows_in_space(space).len(), 2);
+
+        assert_eq!(
+            engine.toggle_focused_app_always_floating(),
+            Some(("com.example.picker".to_string(), true))
+        );
+        assert!(engine.visible_windows_in_space(space).is_empty());
+        assert!(windows.iter().all(|&wid| engine.floating.is_floating(wid)));
+
+        // The override keeps applying when the app's windows are re-evaluated.
+        app_windows_on_screen(&mut engine, space, 7, &windows);
+        assert!(engine.visible_windows_in_space(space).is_empty());
+
+        assert_eq!(
+            engine.toggle_focused_app_always_floating(),
+            Some(("com.example.picker".to_string(), false))
+        );
+        assert_eq!(engine.visible_windows_in_space(space).len(), 2);
+        app_windows_on_screen(&mut engine, space, 7, &windows);
+        assert_eq!(engine.visible_windows_in_space(space).len(), 2);
+    }
+
+    #[test]
+    fn identical_windows_on_screen_updates_are_coalesced() {
+        let (tx, mut rx) = crate::actor::channel();
+        let mut engine = LayoutEngine::new(
+            &VirtualWorkspaceSettings::default(),
+            &LayoutSettings::default(),
+            Some(tx),
+        );
+        let space = SpaceId::new(1);
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+        let layout = engine.layout(space);
+        let mut broadcasts = || {
+            std::iter::from_fn(|| rx.try_recv().ok())
+                .filter(|(_, event)| matches!(event, BroadcastEvent::WindowsChanged { .. }))
+                .count()
+        };
+        let windows = [
+            WindowId::new(3, 1),
+            WindowId::new(3, 2),
+            WindowId::new(3, 3),
+        ];
+
+        let empty = engine.tree.layout_fingerprint(layout);
+        app_windows_on_screen(&mut engine, space, 3, &windows[..2]);
+        let after_first = engine.tree.layout_fingerprint(layout);
+        assert_ne!(after_first, empty);
+        app_windows_on_screen(&mut engine, space, 3, &windows[..2]);
+        assert_eq!(engine.tree.layout_fingerprint(layout), after_first);
+        assert_eq!(broadcasts(), 1);
+
+        // A genuinely different list is still applied.
+        app_windows_on_screen(&mut engine, space, 3, &windows);
+        assert_eq!(engine.visible_windows_in_space(space).len(), 3);
+        assert_eq!(broadcasts(), 1);
+    }
+
+    #[test]
+    fn deferred_broadcasts_flush_once_per_space() {
+        let (tx, mut rx) = crate::actor::channel();
+        let mut engine = LayoutEngine::new(
+            &VirtualWorkspaceSettings::default(),
+            &LayoutSettings::default(),
+            Some(tx),
+        );
+        let left = SpaceId::new(1);
+        let right = SpaceId::new(2);
+        for space in [left, right] {
+            let _ =
+                engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+        }
+        while rx.try_recv().is_ok() {}
+
+        engine.defer_broadcasts();
+        app_windows_on_screen(&mut engine, left, 3, &[WindowId::new(3, 1)]);
+        app_windows_on_screen(&mut engine, left, 4, &[WindowId::new(4, 1)]);
+        app_windows_on_screen(&mut engine, right, 5, &[WindowId::new(5, 1)]);
+        assert!(rx.try_recv().is_err());
+
+        engine.flush_deferred_broadcasts();
+        let mut assigned = 0;
+        let mut sent: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|(_, event)| match event {
+                BroadcastEvent::WindowsChanged { space_id, windows, .. } => {
+                    Some((space_id, windows.len()))
+                }
+                BroadcastEvent::WindowAssigned { .. } => {
+                    assigned += 1;
+                    None
+                }
+                other => panic!("unexpected broadcast {other:?}"),
+            })
+            .collect();
+        sent.sort();
+        assert_eq!(sent, vec![(left, 2), (right, 1)]);
+        assert_eq!(assigned, 3);
+
+        // Once flushed, broadcasts go out immediately again: the new
+        // window's assignment and the space's window list.
+        app_windows_on_screen(
+            &mut engine,
+            right,
+            5,
+            &[WindowId::new(5, 1), WindowId::new(5, 2)],
+        );
+        assert_eq!(std::iter::from_fn(|| rx.try_recv().ok()).count(), 2);
+    }
+
+    #[test]
+    fn first_assignment_of_each_window_is_broadcast() {
+        use crate::common::config::{AppWorkspaceRule, WorkspaceSelector};
+
+        let (tx, mut rx) = crate::actor::channel();
+        let settings = VirtualWorkspaceSettings {
+            app_rules: vec![AppWorkspaceRule {
+                app_id: Some("com.example.picker".to_string()),
+                workspace: Some(WorkspaceSelector::Index(1)),
+                floating: false,
+                manage: true,
+                app_name: None,
+                title_regex: None,
+                title_substring: None,
+                ax_role: None,
+                ax_subrole: None,
+                opacity: None,
+                always_on_top: false,
+            }],
+            ..VirtualWorkspaceSettings::default()
+        };
+        let mut engine = LayoutEngine::new(&settings, &LayoutSettings::default(), Some(tx));
+        let space = SpaceId::new(1);
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+        let workspaces: Vec<_> = engine
+            .virtual_workspace_manager_mut()
+            .list_workspaces(space)
+            .iter()
+            .map(|(id, _)| *id)
+            .collect();
+        let mut assigned = || -> Vec<_> {
+            std::iter::from_fn(|| rx.try_recv().ok())
+                .filter_map(|(_, event)| match event {
+                    BroadcastEvent::WindowAssigned {
+                        window_id,
+                        workspace_id,
+                        by_rule,
+                        ..
+                    } => Some((window_id, workspace_id, by_rule)),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let ruled = WindowId::new(3, 1);
+        let plain = WindowId::new(4, 1);
+        app_windows_on_screen(&mut engine, space, 3, &[ruled]);
+        let _ = engine.handle_event(LayoutEvent::WindowsOnScreenUpdated(
+            space,
+            4,
+            vec![(plain, None, None, None)],
+            None,
+        ));
+        assert_eq!(
+            assigned(),
+            vec![(ruled, workspaces[1], true), (plain, workspaces[0], false)]
+        );
+
+        // Windows already placed are not announced again.
+        let second = WindowId::new(3, 2);
+        app_windows_on_screen(&mut engine, space, 3, &[ruled, second]);
+        engine.calculate_layout_with_virtual_workspaces(
+            space,
+            CGRect::new(CGPoint::new(0.0, 0.0), CGSize::new(1920.0, 1080.0)),
+            &crate::common::config::GapSettings::default(),
+            0.0,
+            crate::common::config::HorizontalPlacement::Top,
+            crate::common::config::VerticalPlacement::Left,
+            |_| None,
+        );
+        assert_eq!(assigned(), vec![(second, workspaces[1], true)]);
+    }
+
+    #[test]
+    fn windows_changed_details_every_window_on_the_space() {
+        let (tx, mut rx) = crate::actor::channel();
+        let mut engine = LayoutEngine::new(
+            &VirtualWorkspaceSettings::default(),
+            &LayoutSettings::default(),
+            Some(tx),
+        );
+        let space = SpaceId::new(1);
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+        let workspaces = engine.virtual_workspace_manager_mut().list_workspaces(space);
+        let windows = [
+            WindowId::new(3, 1),
+            WindowId::new(3, 2),
+            WindowId::new(3, 3),
+        ];
+        app_windows_on_screen(&mut engine, space, 3, &windows);
+        let _ = engine.handle_event(LayoutEvent::WindowFocused(space, windows[1]));
+        let _ = engine.handle_command(
+            Some(space),
+            &[space],
+            &HashMap::default(),
+            LayoutCommand::ToggleWindowFloating,
+        );
+        let _ = engine.handle_virtual_workspace_command(
+            space,
+            &LayoutCommand::MoveWindowToWorkspace {
+                workspace: 1,
+                window_id: Some(3),
+            },
+        );
+        let _ = engine.handle_event(LayoutEvent::WindowFocused(space, windows[0]));
+        while rx.try_recv().is_ok() {}
+
+        engine.broadcast_windows_changed(space);
+        let Ok((_, BroadcastEvent::WindowsChanged { window_details, .. })) = rx.try_recv() else {
+            panic!("expected a windows_changed event");
+        };
+        let details = |wid: WindowId, workspace: usize, is_floating, is_focused| WindowDetails {
+            id: wid.to_debug_string(),
+            pid: 3,
+            workspace_id: workspaces[workspace].0,
+            workspace_name: workspaces[workspace].1.clone(),
+            is_floating,
+            is_focused,
+            is_active_workspace: workspace == 0,
+        };
+        assert_eq!(
+            window_details,
+            vec![
+                details(windows[0], 0, false, true),
+                details(windows[1], 0, true, false),
+                details(windows[2], 1, false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn tree_is_broadcast_only_when_its_fingerprint_changes() {
+        let (tx, mut rx) = crate::actor::channel();
+        let mut engine = LayoutEngine::new(
+            &VirtualWorkspaceSettings::default(),
+            &LayoutSettings::default(),
+            Some(tx),
+        );
+        let space = SpaceId::new(1);
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+        let mut trees = || -> Vec<_> {
+            std::iter::from_fn(|| rx.try_recv().ok())
+                .filter_map(|(_, event)| match event {
+                    BroadcastEvent::LayoutTreeChanged { tree, fingerprint, .. } => {
+                        Some((tree, fingerprint))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+
+        engine.broadcast_tree_if_changed(space);
+        assert_eq!(trees().len(), 1);
+
+        app_windows_on_screen(
+            &mut engine,
+            space,
+            3,
+            &[WindowId::new(3, 1), WindowId::new(3, 2)],
+        );
+        engine.broadcast_tree_if_changed(space);
+        engine.broadcast_tree_if_changed(space);
+        let snapshot = engine.tree_snapshot(space).unwrap();
+        assert_eq!(trees(), vec![(snapshot.ascii, snapshot.fingerprint)]);
+
+        // A lock shows in the tree, so toggling one is broadcast too.
+        let _ = engine.handle_command(
+            Some(space),
+            &[space],
+            &HashMap::default(),
+            LayoutCommand::ToggleContainerLock,
+        );
+        engine.broadcast_tree_if_changed(space);
+        assert_eq!(trees().len(), 1);
+
+        // Spaces that go away take their last broadcast with them.
+        let remapped = SpaceId::new(2);
+        engine.remap_space(space, remapped);
+        assert!(!engine.broadcast_trees.contains_key(&space));
+        engine.broadcast_tree_if_changed(remapped);
+        assert_eq!(trees().len(), 1);
+        engine.update_space_display(remapped, Some("display".to_string()));
+        engine.prune_display_state(&[]);
+        assert!(engine.broadcast_trees.is_empty());
+    }
+
+    #[test]
+    fn focus_into_a_stack_can_raise_all_of_it() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        for raise_all_on_focus in [false, true] {
+            let mut settings = LayoutSettings::default();
+            settings.stack.raise_all_on_focus = raise_all_on_focus;
+            let mut world = SimulatedWorld::with_settings(
+                vec![screen(1000.0, 800.0)],
+                &settings,
+                &VirtualWorkspaceSettings::default(),
+            );
+            let windows = world.add_app(1, 4);
+            world.focus(windows[0]);
+            world.run_command(LayoutCommand::ToggleStack);
+
+            let response = world.run_command(LayoutCommand::MoveFocus(Direction::Down));
+            assert_eq!(response.focus_window, Some(windows[1]));
+            if raise_all_on_focus {
+                assert_eq!(
+                    response.raise_windows,
+                    vec![windows[3], windows[2], windows[0], windows[1]]
+                );
+            } else {
+                assert_eq!(response.raise_windows, vec![windows[1]]);
+            }
+        }
+    }
+
+    #[test]
+    fn new_workspace_layouts_use_default_root_kind() {
+        let settings = LayoutSettings {
+            default_root_kind: crate::layout_engine::LayoutKind::Vertical,
+            ..Default::default()
+        };
+        let mut engine = LayoutEngine::new(&VirtualWorkspaceSettings::default(), &settings, None);
+        let space = SpaceId::new(1);
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+        let windows = [WindowId::new(3, 1), WindowId::new(3, 2)];
+        app_windows_on_screen(&mut engine, space, 3, &windows);
+        let _ = engine.handle_event(LayoutEvent::WindowFocused(space, windows[0]));
+
+        let layout = engine.layout(space);
+        assert_eq!(
+            engine.tree.window_in_direction(layout, Direction::Down),
+            Some(windows[1])
+        );
+        assert_eq!(engine.tree.window_in_direction(layout, Direction::Right), None);
+    }
+
+    #[test]
+    fn workspace_switch_raises_floating_windows_in_stacking_order() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let small = world.add_app(1, 1)[0];
+        let large = world.add_app(2, 1)[0];
+        let other = world.add_app(3, 1)[0];
+        for wid in [small, large, other] {
+            world.focus(wid);
+            world.run_command(LayoutCommand::ToggleWindowFloating);
+        }
+        world.focus(large);
+        world.focus(small);
+
+        world.run_command(LayoutCommand::SwitchToWorkspace(1));
+        let response = world.run_command(LayoutCommand::SwitchToWorkspace(0));
+
+        assert_eq!(response.raise_windows, vec![other, large, small]);
+    }
+
+    #[test]
+    fn unfloated_window_rejoins_next_to_the_nearest_tiled_window() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let windows = world.add_app(1, 3);
+        world.focus(windows[0]);
+        world.run_command(LayoutCommand::ToggleWindowFloating);
+        assert_eq!(world.visible_windows(), vec![windows[1], windows[2]]);
+
+        let space = world.space(0);
+        let workspace = world.engine().virtual_workspace_manager().active_workspace(space).unwrap();
+        let dragged = CGRect::new(CGPoint::new(800.0, 300.0), CGSize::new(150.0, 150.0));
+        world
+            .engine_mut()
+            .virtual_workspace_manager_mut()
+            .store_floating_position(space, workspace, windows[0], dragged);
+        world.run_command(LayoutCommand::ToggleWindowFloating);
+
+        assert_eq!(world.visible_windows(), vec![windows[1], windows[2], windows[0]]);
+        assert_eq!(world.engine_mut().selected_window(space), Some(windows[0]));
+
+        // Dropped over the left half of the first window, it goes before it.
+        world.run_command(LayoutCommand::ToggleWindowFloating);
+        let dragged = CGRect::new(CGPoint::new(100.0, 300.0), CGSize::new(150.0, 150.0));
+        world
+            .engine_mut()
+            .virtual_workspace_manager_mut()
+            .store_floating_position(space, workspace, windows[0], dragged);
+        world.run_command(LayoutCommand::ToggleWindowFloating);
+
+        assert_eq!(world.visible_windows(), vec![windows[0], windows[1], windows[2]]);
+        assert_eq!(world.engine_mut().selected_window(space), Some(windows[0]));
+    }
+
+    #[test]
+    fn shutdown_removes_every_layout() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let right = CGRect::new(CGPoint::new(1000.0, 0.0), CGSize::new(1000.0, 800.0));
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0), right]);
+        world.add_app(1, 3);
+        let second = world.space(1);
+        world.add_app_on(second, 2, 1);
+        world.run_command(LayoutCommand::SwitchToWorkspace(1));
+
+        let before = world.engine().shutdown_state();
+        assert_eq!((before.spaces, before.windows), (2, 4));
+
+        assert_eq!(world.engine_mut().shutdown(), before);
+        let after = world.engine().shutdown_state();
+        assert_eq!((after.spaces, after.layouts), (0, 0));
+    }
+
+    #[test]
+    fn directional_focus_from_floating_windows_follows_geometry() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let windows = world.add_app(1, 3);
+        let (left, right, float) = (windows[0], windows[1], windows[2]);
+        world.focus(float);
+        world.run_command(LayoutCommand::ToggleWindowFloating);
+
+        let space = world.space(0);
+        let workspace = world.engine().virtual_workspace_manager().active_workspace(space).unwrap();
+        let above = CGRect::new(CGPoint::new(400.0, 50.0), CGSize::new(200.0, 100.0));
+        world
+            .engine_mut()
+            .virtual_workspace_manager_mut()
+            .store_floating_position(space, workspace, float, above);
+        world.relayout();
+
+        let focus = |world: &mut SimulatedWorld, direction| {
+            world.run_command(LayoutCommand::MoveFocus(direction)).focus_window
+        };
+        assert_eq!(focus(&mut world, Direction::Left), Some(left));
+        // The float's center is nearer than the right tile's.
+        assert_eq!(focus(&mut world, Direction::Right), Some(float));
+        assert_eq!(focus(&mut world, Direction::Right), Some(right));
+        assert_eq!(focus(&mut world, Direction::Up), Some(float));
+        assert_eq!(focus(&mut world, Direction::Right), Some(right));
+        assert_eq!(focus(&mut world, Direction::Left), Some(float));
+
+        world.focus(float);
+        assert_eq!(focus(&mut world, Direction::Up), None);
+        assert_eq!(world.engine().focused_window, Some(float));
+    }
+
+    #[test]
+    fn predict_join_orientation_reads_the_active_layout() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let windows = world.add_app(1, 2);
+        world.focus(windows[0]);
+        let before = world.engine().tree_snapshot(world.space(0));
+
+        let engine = world.engine();
+        assert_eq!(
+            engine.predict_join_orientation(world.space(0), Direction::Right),
+            Some(Orientation::Horizontal)
+        );
+        assert_eq!(
+            engine.predict_join_orientation(SpaceId::new(99), Direction::Right),
+            None
+        );
+        assert_eq!(engine.tree_snapshot(world.space(0)), before);
+    }
+
+    #[test]
+    fn preserved_sizes_are_kept_when_windows_are_added_not_when_laid_out() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1200.0, 800.0)]);
+        let windows = world.add_app(1, 2);
+        world.focus(windows[0]);
+        world.run_command(LayoutCommand::TogglePreserveSize);
+        let width = world.frame(windows[0]).unwrap().size.width;
+
+        let space = world.space(0);
+        let before = world.engine().tree_snapshot(space);
+        world.relayout();
+        assert_eq!(world.engine().tree_snapshot(space), before);
+
+        world.add_app(2, 1);
+        let kept = world.frame(windows[0]).unwrap().size.width;
+        assert!((kept - width).abs() <= 2.0, "{kept} != {width}");
+    }
+
+    #[test]
+    fn app_rules_give_windows_display_hints() {
+        use crate::common::config::AppWorkspaceRule;
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let rule = |app_id: &str, title_substring: Option<&str>| AppWorkspaceRule {
+            app_id: Some(app_id.to_string()),
+            workspace: None,
+            floating: true,
+            manage: true,
+            app_name: None,
+            title_regex: None,
+            title_substring: title_substring.map(str::to_string),
+            ax_role: None,
+            ax_subrole: None,
+            opacity: Some(0.8),
+            always_on_top: true,
+        };
+        let settings = VirtualWorkspaceSettings {
+            app_rules: vec![
+                rule("com.example.app1", None),
+                rule("com.example.app3", Some("Notes")),
+            ],
+            ..VirtualWorkspaceSettings::default()
+        };
+        let mut world = SimulatedWorld::with_settings(
+            vec![screen(1000.0, 800.0)],
+            &LayoutSettings::default(),
+            &settings,
+        );
+        let expected = DisplayHints {
+            opacity: Some(0.8),
+            always_on_top: true,
+        };
+
+        let notes = world.add_app(1, 1)[0];
+        let plain = world.add_app(2, 1)[0];
+        let titled = world.add_app(3, 1)[0];
+        let hints = world.engine().window_display_hints();
+        assert_eq!(hints.get(&notes), Some(&expected));
+        assert_eq!(hints.get(&plain), None);
+        assert_eq!(hints.get(&titled), None);
+
+        let info = AppInfo {
+            bundle_id: Some("com.example.app3".to_string()),
+            localized_name: Some("App3".to_string()),
+        };
+        world
+            .engine_mut()
+            .refresh_display_hints(titled, Some(&info), Some("My Notes"), None, None);
+        assert_eq!(
+            world.engine().window_display_hints().get(&titled),
+            Some(&expected)
+        );
+        world
+            .engine_mut()
+            .refresh_display_hints(titled, Some(&info), Some("Inbox"), None, None);
+        assert_eq!(world.engine().window_display_hints().get(&titled), None);
+
+        world.remove_window(notes);
+        assert!(world.engine().window_display_hints().is_empty());
+    }
+
+    #[test]
+    fn changed_app_rules_apply_to_existing_windows() {
+        use crate::common::config::{AppWorkspaceRule, WorkspaceSelector};
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let space = world.space(0);
+        let moved = world.add_app(1, 2);
+        let floated = world.add_app(2, 1)[0];
+        world.focus(moved[1]);
+        let _ = world
+            .run_command(LayoutCommand::MoveWindowToWorkspace { workspace: 2, window_id: None });
+
+        let rule = |app_id: &str, workspace, floating| AppWorkspaceRule {
+            app_id: Some(app_id.to_string()),
+            workspace,
+            floating,
+            manage: true,
+            app_name: None,
+            title_regex: None,
+            title_substring: None,
+            ax_role: None,
+            ax_subrole: None,
+            opacity: None,
+            always_on_top: false,
+        };
+        let settings = VirtualWorkspaceSettings {
+            app_rules: vec![
+                rule("com.example.app1", Some(WorkspaceSelector::Index(1)), false),
+                rule("com.example.app2", None, true),
+            ],
+            ..VirtualWorkspaceSettings::default()
+        };
+        let responses = world.engine_mut().update_virtual_workspace_settings(&settings);
+        assert_eq!(responses.len(), 1);
+        assert!(responses[0].1.raise_windows.contains(&floated));
+
+        let workspaces: Vec<_> = world
+            .engine_mut()
+            .virtual_workspace_manager_mut()
+            .list_workspaces(space)
+            .iter()
+            .map(|(id, _)| *id)
+            .collect();
+        let workspace_of = |world: &SimulatedWorld, wid| {
+            world.engine().virtual_workspace_manager().workspace_for_window(space, wid)
+        };
+        assert_eq!(workspace_of(&world, moved[0]), Some(workspaces[1]));
+        // Moved by hand, so the rules leave it alone.
+        assert_eq!(workspace_of(&world, moved[1]), Some(workspaces[2]));
+        assert!(world.engine().is_window_floating(floated));
+        world.relayout();
+        assert!(world.visible_windows().is_empty());
+
+        let _ = world.engine_mut().reapply_app_rules(space, true);
+        assert_eq!(workspace_of(&world, moved[1]), Some(workspaces[1]));
+    }
+
+    #[test]
+    fn changed_app_rules_match_the_latest_title() {
+        use crate::common::config::{AppWorkspaceRule, WorkspaceSelector};
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let space = world.space(0);
+        let windows = world.add_app(1, 2);
+        let info = AppInfo {
+            bundle_id: Some("com.example.app1".to_string()),
+            localized_name: Some("App1".to_string()),
+        };
+        world
+            .engine_mut()
+            .refresh_display_hints(windows[0], Some(&info), Some("Chat"), None, None);
+
+        let settings = VirtualWorkspaceSettings {
+            app_rules: vec![AppWorkspaceRule {
+                app_id: None,
+                workspace: Some(WorkspaceSelector::Index(1)),
+                floating: false,
+                manage: true,
+                app_name: None,
+                title_regex: None,
+                title_substring: Some("Chat".to_string()),
+                ax_role: None,
+                ax_subrole: None,
+                opacity: None,
+                always_on_top: false,
+            }],
+            ..VirtualWorkspaceSettings::default()
+        };
+        let _ = world.engine_mut().update_virtual_workspace_settings(&settings);
+
+        let engine = world.engine();
+        let active = engine.active_workspace(space);
+        let workspace_of =
+            |wid| engine.virtual_workspace_manager().workspace_for_window(space, wid);
+        assert_ne!(workspace_of(windows[0]), active);
+        assert_eq!(workspace_of(windows[1]), active);
+    }
+
+    #[test]
+    fn app_rules_leave_windows_moved_between_displays() {
+        use crate::common::config::{AppWorkspaceRule, WorkspaceSelector};
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let right_screen = CGRect::new(CGPoint::new(1000.0, 0.0), CGSize::new(1000.0, 800.0));
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0), right_screen]);
+        let (left, right) = (world.space(0), world.space(1));
+        let windows = world.add_app(1, 2);
+        let stray = world.add_app(2, 1)[0];
+        let _ = world.engine_mut().handle_event(LayoutEvent::WindowMovedToSpace {
+            wid: windows[0],
+            from: Some(left),
+            to: right,
+        });
+        world.focus(stray);
+        let _ = world
+            .run_command(LayoutCommand::MoveWindowToWorkspace { workspace: 2, window_id: None });
+
+        let rule = |app_id: &str, index| AppWorkspaceRule {
+            app_id: Some(app_id.to_string()),
+            workspace: Some(WorkspaceSelector::Index(index)),
+            floating: false,
+            manage: true,
+            app_name: None,
+            title_regex: None,
+            title_substring: None,
+            ax_role: None,
+            ax_subrole: None,
+            opacity: None,
+            always_on_top: false,
+        };
+        let settings = VirtualWorkspaceSettings {
+            app_rules: vec![rule("com.example.app1", 1), rule("com.example.app2", 99)],
+            ..VirtualWorkspaceSettings::default()
+        };
+        let _ = world.engine_mut().update_virtual_workspace_settings(&settings);
+
+        let workspace_of = |world: &SimulatedWorld, space, wid| {
+            world.engine().virtual_workspace_manager().workspace_for_window(space, wid)
+        };
+        let engine = world.engine();
+        assert_eq!(
+            workspace_of(&world, right, windows[0]),
+            engine.active_workspace(right)
+        );
+        assert_ne!(
+            workspace_of(&world, left, windows[1]),
+            engine.active_workspace(left)
+        );
+
+        // A rule naming a missing workspace sends the window to the default one.
+        let _ = world.engine_mut().reapply_app_rules(left, true);
+        assert_eq!(
+            workspace_of(&world, left, stray),
+            world.engine().active_workspace(left)
+        );
+    }
+
+    #[test]
+    fn global_workspace_switch_changes_every_display() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0), screen(1000.0, 800.0)]);
+        let (left, right) = (world.space(0), world.space(1));
+        let windows = world.add_app(1, 1);
+        let count = world.engine_mut().virtual_workspace_manager_mut().list_workspaces(right).len();
+
+        let responses =
+            world.engine_mut().switch_to_workspace_globally(left, &[left, right], count);
+        assert_eq!(
+            responses.iter().map(|(space, _)| *space).collect::<Vec<_>>(),
+            vec![right, left]
+        );
+        for space in [left, right] {
+            assert_eq!(
+                world.engine().virtual_workspace_manager().active_workspace_idx(space),
+                Some(count as u64)
+            );
+        }
+
+        let responses = world.engine_mut().switch_to_workspace_globally(left, &[left, right], 0);
+        let (_, left_response) = responses.iter().find(|(space, _)| *space == left).unwrap();
+        assert_eq!(left_response.focus_window, Some(windows[0]));
+        assert_eq!(
+            world.engine().virtual_workspace_manager().active_workspace_idx(right),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn global_workspace_steps_resolve_from_the_command_space() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0), screen(1000.0, 800.0)]);
+        let (left, right) = (world.space(0), world.space(1));
+        let _ = world.engine_mut().switch_to_workspace_globally(left, &[left, right], 2);
+        let _ = world
+            .engine_mut()
+            .handle_virtual_workspace_command(right, &LayoutCommand::SwitchToWorkspace(0));
+
+        let engine = world.engine();
+        let index = |space, command| engine.workspace_switch_index(space, &command);
+        assert_eq!(index(left, LayoutCommand::NextWorkspace(None)), Some(3));
+        assert_eq!(index(left, LayoutCommand::PrevWorkspace(None)), Some(1));
+        assert_eq!(index(left, LayoutCommand::SwitchToLastWorkspace), Some(0));
+        assert_eq!(index(right, LayoutCommand::NextWorkspace(None)), Some(1));
+        assert_eq!(index(left, LayoutCommand::SwitchToWorkspace(4)), Some(4));
+        assert_eq!(index(left, LayoutCommand::CreateWorkspace), None);
+
+        let index = world
+            .engine()
+            .workspace_switch_index(left, &LayoutCommand::NextWorkspace(None))
+            .unwrap();
+        let _ = world.engine_mut().switch_to_workspace_globally(left, &[left, right], index);
+        for space in [left, right] {
+            assert_eq!(
+                world.engine().virtual_workspace_manager().active_workspace_idx(space),
+                Some(3)
+            );
+        }
+    }
+
+    #[test]
+    fn focus_nth_focuses_windows_in_tree_order() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let space = world.space(0);
+        let windows = world.add_app(1, 3);
+        world.focus(windows[0]);
+
+        let response = world.run_command(LayoutCommand::FocusNth { index: 2 });
+        assert_eq!(response.focus_window, Some(windows[2]));
+        assert_eq!(world.engine_mut().selected_window(space), Some(windows[2]));
+
+        let response = world.run_command(LayoutCommand::FocusNth { index: 3 });
+        assert_eq!(response.focus_window, None);
+        assert_eq!(world.engine_mut().selected_window(space), Some(windows[2]));
+    }
+
+    #[test]
+    fn merging_workspaces_keeps_every_window() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let space = world.space(0);
+        let first = world.add_app(1, 2);
+        world.run_command(LayoutCommand::SwitchToWorkspace(1));
+        let second = world.add_app(2, 2);
+        world.focus(second[1]);
+        world.run_command(LayoutCommand::ToggleWindowFloating);
+
+        let before = world.engine_mut().virtual_workspace_manager_mut().list_workspaces(space);
+        let (src, dst) = (before[1].0, before[0].0);
+        world.run_command(LayoutCommand::MergeWorkspaces { from: 1, into: 0 });
+
+        let after = world.engine_mut().virtual_workspace_manager_mut().list_workspaces(space);
+        assert_eq!(after.len(), before.len() - 1);
+        assert!(after.iter().all(|(id, _)| *id != src));
+        assert_eq!(world.engine().active_workspace(space), Some(dst));
+        for wid in first.iter().chain(&second) {
+            assert_eq!(
+                world.engine().virtual_workspace_manager().workspace_for_window(space, *wid),
+                Some(dst)
+            );
+        }
+        let mut tiled = world.visible_windows();
+        tiled.sort();
+        assert_eq!(tiled, vec![first[0], first[1], second[0]]);
+        assert!(world.engine().is_window_floating(second[1]));
+    }
+
+    #[test]
+    fn crowded_layouts_stack_their_root() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let settings = LayoutSettings {
+            auto_stack_threshold: 3,
+            ..LayoutSettings::default()
+        };
+        let mut world = SimulatedWorld::with_settings(
+            vec![screen(1000.0, 800.0)],
+            &settings,
+            &VirtualWorkspaceSettings::default(),
+        );
+        let windows = world.add_app(1, 3);
+        let space = world.space(0);
+        let layout = world.engine_mut().layout(space);
+        let root_kind = |world: &SimulatedWorld| match &world.engine().tree {
+            LayoutSystemKind::Traditional(s) => s.root_kind(layout),
+            _ => unreachable!(),
+        };
+        assert_eq!(root_kind(&world), crate::layout_engine::LayoutKind::Horizontal);
+
+        world.add_app(2, 1);
+        assert!(root_kind(&world).is_stacked());
+
+        // Unstacking by hand sticks.
+        world.focus(windows[0]);
+        world.run_command(LayoutCommand::ToggleStack);
+        assert_eq!(root_kind(&world), crate::layout_engine::LayoutKind::Horizontal);
+        world.add_app(3, 1);
+        assert_eq!(root_kind(&world), crate::layout_engine::LayoutKind::Horizontal);
+    }
+
+    #[test]
+    fn commands_cannot_nest_past_max_depth() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let settings = LayoutSettings {
+            max_depth: Some(2),
+            ..LayoutSettings::default()
+        };
+        let mut world = SimulatedWorld::with_settings(
+            vec![screen(1000.0, 800.0)],
+            &settings,
+            &VirtualWorkspaceSettings::default(),
+        );
+        let windows = world.add_app(1, 2);
+        let space = world.space(0);
+        let layout = world.engine_mut().layout(space);
+        world.focus(windows[1]);
+        for _ in 0..4 {
+            world.run_command(LayoutCommand::SplitInto {
+                kind: crate::layout_engine::LayoutKind::Vertical,
+                count: 3,
+            });
+        }
+
+        let LayoutSystemKind::Traditional(s) = &world.engine().tree else {
+            unreachable!()
+        };
+        assert_eq!(s.max_depth(layout), 2);
+        let mut tiled = world.visible_windows();
+        tiled.sort();
+        assert_eq!(tiled, windows);
+    }
+
+    mod on_empty {
+        use super::*;
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        fn world(on_empty: OnEmptyWorkspace) -> SimulatedWorld {
+            let settings = VirtualWorkspaceSettings {
+                default_workspace_count: 4,
+                on_empty,
+                ..VirtualWorkspaceSettings::default()
+            };
+            SimulatedWorld::with_settings(
+                vec![screen(1000.0, 800.0)],
+                &LayoutSettings::default(),
+                &settings,
+            )
+        }
+
+        fn workspace(world: &mut SimulatedWorld, index: usize) -> VirtualWorkspaceId {
+            let space = world.space(0);
+            world.engine_mut().virtual_workspace_manager_mut().list_workspaces(space)[index].0
+        }
+
+        #[test]
+        fn switches_to_previous_workspace() {
+            let mut world = world(OnEmptyWorkspace::SwitchToPrevious);
+            let first = world.add_app(1, 1)[0];
+            world.run_command(LayoutCommand::SwitchToWorkspace(1));
+            let second = world.add_app(2, 1)[0];
+
+            world.remove_window(second);
+            let switches = world.engine_mut().switch_from_emptied_workspaces();
+
+            let expected = workspace(&mut world, 0);
+            assert_eq!(switches.len(), 1);
+            let (space, response) = &switches[0];
+            assert_eq!(*space, world.space(0));
+            assert_eq!(response.workspace_changed_to, Some(expected));
+            assert_eq!(response.focus_window, Some(first));
+            assert_eq!(world.engine().active_workspace(world.space(0)), Some(expected));
+        }
+
+        #[test]
+        fn switches_to_nearest_nonempty_workspace() {
+            let mut world = world(OnEmptyWorkspace::SwitchToNearestNonempty);
+            world.add_app(1, 1);
+            world.run_command(LayoutCommand::SwitchToWorkspace(3));
+            let fourth = world.add_app(2, 1)[0];
+            world.run_command(LayoutCommand::SwitchToWorkspace(0));
+            world.run_command(LayoutCommand::SwitchToWorkspace(2));
+            let third = world.add_app(3, 1)[0];
+
+            world.remove_window(third);
+            let switches = world.engine_mut().switch_from_emptied_workspaces();
+
+            let expected = workspace(&mut world, 3);
+            assert_eq!(switches.len(), 1);
+            assert_eq!(switches[0].1.workspace_changed_to, Some(expected));
+            assert_eq!(switches[0].1.focus_window, Some(fourth));
+        }
+
+        #[test]
+        fn window_replaced_in_the_same_burst_stays() {
+            let mut world = world(OnEmptyWorkspace::SwitchToPrevious);
+            world.add_app(1, 1);
+            world.run_command(LayoutCommand::SwitchToWorkspace(1));
+            let second = world.add_app(2, 1)[0];
+
+            world.remove_window(second);
+            world.add_app(3, 1);
+            assert!(world.engine_mut().switch_from_emptied_workspaces().is_empty());
+
+            let expected = workspace(&mut world, 1);
+            assert_eq!(world.engine().active_workspace(world.space(0)), Some(expected));
+        }
+
+        #[test]
+        fn already_empty_workspace_does_not_switch() {
+            let mut world = world(OnEmptyWorkspace::SwitchToPrevious);
+            let first = world.add_app(1, 2);
+            world.run_command(LayoutCommand::MoveWindowToWorkspace {
+                workspace: 2,
+                window_id: Some(first[1].idx.get()),
+            });
+            world.run_command(LayoutCommand::SwitchToWorkspace(1));
+
+            world.remove_window(first[1]);
+            assert!(world.engine_mut().switch_from_emptied_workspaces().is_empty());
+
+            let expected = workspace(&mut world, 1);
+            assert_eq!(world.engine().active_workspace(world.space(0)), Some(expected));
+        }
+
+        #[test]
+        fn closing_the_last_window_then_switching_to_an_empty_workspace_stays() {
+            let mut world = world(OnEmptyWorkspace::SwitchToPrevious);
+            world.add_app(1, 1);
+            world.run_command(LayoutCommand::SwitchToWorkspace(1));
+            let second = world.add_app(2, 1)[0];
+
+            // The closed window empties workspace 1, but workspace 2 was
+            // already empty when it became active in the same burst.
+            world.remove_window(second);
+            world.run_command(LayoutCommand::SwitchToWorkspace(2));
+            assert!(world.engine_mut().switch_from_emptied_workspaces().is_empty());
+
+            let expected = workspace(&mut world, 2);
+            assert_eq!(world.engine().active_workspace(world.space(0)), Some(expected));
+        }
+
+        #[test]
+        fn stay_keeps_the_empty_workspace() {
+            let mut world = world(OnEmptyWorkspace::Stay);
+            world.add_app(1, 1);
+            world.run_command(LayoutCommand::SwitchToWorkspace(1));
+            let second = world.add_app(2, 1)[0];
+
+            world.remove_window(second);
+            assert!(world.engine_mut().switch_from_emptied_workspaces().is_empty());
+        }
+    }
+
+    #[test]
+    fn metrics_count_events_and_commands() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let windows = world.add_app(1, 2);
+        world.focus(windows[0]);
+        world.run_command(LayoutCommand::NextWindow);
+        world.remove_window(windows[1]);
+
+        let metrics = world.engine().metrics_snapshot();
+        assert_eq!(metrics.events.get("windows_on_screen_updated"), Some(&1));
+        assert_eq!(metrics.events.get("window_removed"), Some(&1));
+        assert!(metrics.events.get("window_focused").is_some_and(|&n| n >= 1));
+        assert_eq!(metrics.commands.get("next_window"), Some(&1));
+        assert_eq!(metrics.set_windows_for_app.count, 1);
+        assert!(metrics.rebalance.count >= 2);
+        assert!(metrics.calculate_layout.count >= 4);
+        assert_eq!(metrics.layouts.iter().map(|l| l.windows).sum::<usize>(), 1);
+    }
+
+    fn journaling_settings(capacity: usize, scrub_titles: bool) -> LayoutSettings {
+        let mut settings = LayoutSettings::default();
+        settings.journal.enabled = true;
+        settings.journal.capacity = capacity;
+        settings.journal.scrub_titles = scrub_titles;
+        settings
+    }
+
+    #[test]
+    fn journal_records_events_and_commands() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::with_settings(
+            vec![screen(1000.0, 800.0)],
+            &journaling_settings(256, false),
+            &VirtualWorkspaceSettings::default(),
+        );
+        let space = world.space(0);
+        let windows = world.add_app(1, 2);
+        world.focus(windows[0]);
+        world.run_command(LayoutCommand::NextWindow);
+        world.run_command(LayoutCommand::SwitchToWorkspace(1));
+        world.run_command(LayoutCommand::SwitchToWorkspace(0));
+        world.remove_window(windows[1]);
+
+        let journal = world.engine().dump_journal(space);
+        let names: Vec<_> = journal
+            .iter()
+            .filter(|entry| entry.name != "window_focused")
+            .map(|entry| (entry.kind, entry.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (JournalEntryKind::Event, "space_exposed"),
+                (JournalEntryKind::Event, "windows_on_screen_updated"),
+                (JournalEntryKind::Command, "next_window"),
+                (JournalEntryKind::Command, "switch_to_workspace"),
+                (JournalEntryKind::Command, "switch_to_workspace"),
+                (JournalEntryKind::Event, "window_removed"),
+            ]
+        );
+        assert!(journal.iter().all(|entry| entry.space == Some(space)));
+        assert!(journal.windows(2).all(|w| w[0].timestamp_ms <= w[1].timestamp_ms));
+
+        let removed = journal.last().unwrap();
+        assert!(removed.detail.contains("WindowRemoved"), "{}", removed.detail);
+        let layout = world.engine_mut().layout(space);
+        assert_eq!(
+            removed.fingerprint,
+            Some(world.engine().tree.layout_fingerprint(layout))
+        );
+        assert!(world.engine().dump_journal(SpaceId::new(99)).is_empty());
+    }
+
+    #[test]
+    fn journal_keeps_the_newest_entries_and_can_scrub_titles() {
+        let space = SpaceId::new(1);
+        let mut engine = LayoutEngine::new(
+            &VirtualWorkspaceSettings::default(),
+            &journaling_settings(2, true),
+            None,
+        );
+        let _ = engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+        let wid = WindowId::new(1, 1);
+        let _ = engine.handle_event(LayoutEvent::WindowsOnScreenUpdated(
+            space,
+            1,
+            vec![(
+                wid,
+                Some("Secret plans".to_string()),
+                Some("AXWindow".to_string()),
+                None,
+            )],
+            None,
+        ));
+        let _ = engine.handle_event(LayoutEvent::AppClosed(1));
+
+        let journal = engine.dump_journal(space);
+        let names: Vec<_> = journal.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!["windows_on_screen_updated", "app_closed"]);
+        assert!(
+            !journal[0].detail.contains("Secret plans"),
+            "{}",
+            journal[0].detail
+        );
+        assert!(journal[0].detail.contains("AXWindow"), "{}", journal[0].detail);
+        assert_eq!(journal[1].space, None);
+        assert_eq!(journal[1].fingerprint, None);
+
+        // Turning the journal off drops it.
+        engine.set_layout_settings(&LayoutSettings::default());
+        assert!(engine.journal.is_none());
+        let _ = engine.handle_event(LayoutEvent::AppClosed(1));
+        assert!(engine.dump_journal(space).is_empty());
+    }
+
+    #[test]
+    fn frame_of_window_reuses_the_layout_pass() {
+        use crate::common::config::{HorizontalPlacement, VerticalPlacement};
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let space = world.space(0);
+        let windows = world.add_app(1, 2);
+        let gaps = LayoutSettings::default().gaps;
+        let frame_of = |world: &mut SimulatedWorld, wid| {
+            world.engine_mut().frame_of_window(
+                space,
+                wid,
+                screen(1000.0, 800.0),
+                &gaps,
+                0.0,
+                HorizontalPlacement::Top,
+                VerticalPlacement::Left,
+            )
+        };
+
+        assert_eq!(frame_of(&mut world, windows[0]), world.frame(windows[0]));
+        let passes = world.engine().metrics_snapshot().calculate_layout.count;
+        assert_eq!(frame_of(&mut world, windows[1]), world.frame(windows[1]));
+        assert_eq!(world.engine().metrics_snapshot().calculate_layout.count, passes);
+
+        // Changes made outside events and commands are not answered from
+        // the cache, and neither are other gaps.
+        let layout = world.engine_mut().layout(space);
+        world.engine_mut().resize_selection(layout, 0.1);
+        let resized = frame_of(&mut world, windows[1]).unwrap();
+        assert_ne!(Some(resized), world.frame(windows[1]));
+        let mut outer = gaps.clone();
+        outer.outer.left = 20.0;
+        let shifted = world
+            .engine_mut()
+            .frame_of_window(
+                space,
+                windows[0],
+                screen(1000.0, 800.0),
+                &outer,
+                0.0,
+                HorizontalPlacement::Top,
+                VerticalPlacement::Left,
+            )
+            .unwrap();
+        assert_eq!(shifted.origin.x, 20.0);
+
+        world.remove_window(windows[1]);
+        let frame = frame_of(&mut world, windows[0]).unwrap();
+        assert_eq!(Some(frame), world.frame(windows[0]));
+        assert_eq!(frame.size.width, 1000.0);
+    }
+
+    #[test]
+    fn insertion_preview_matches_the_opened_window() {
+        use crate::common::config::{HorizontalPlacement, VerticalPlacement};
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let space = world.space(0);
+        let windows = world.add_app(1, 2);
+        world.focus(windows[0]);
+        let gaps = LayoutSettings::default().gaps;
+        let predict = |world: &mut SimulatedWorld| {
+            world.engine_mut().predict_insertion_frame(
+                space,
+                screen(1000.0, 800.0),
+                &gaps,
+                0.0,
+                HorizontalPlacement::Top,
+                VerticalPlacement::Left,
+            )
+        };
+
+        let predicted = predict(&mut world);
+        assert!(predicted.is_some());
+        assert_eq!(world.visible_windows(), windows);
+
+        let opened = world.add_app(2, 1)[0];
+        assert_eq!(predicted, world.frame(opened));
+
+        world.focus(opened);
+        world.run_command(LayoutCommand::ToggleFullscreen);
+        assert_eq!(predict(&mut world), None);
+    }
+
+    #[test]
+    fn workspace_switch_reports_fullscreen_windows() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let space = world.space(0);
+        let windows = world.add_app(1, 2);
+        world.focus(windows[0]);
+        world.run_command(LayoutCommand::ToggleFullscreenWithinGaps);
+        assert_eq!(
+            world.engine().fullscreen_windows(space),
+            vec![(windows[0], true)]
+        );
+
+        let response = world.run_command(LayoutCommand::SwitchToWorkspace(1));
+        assert!(response.fullscreen_windows.is_empty());
+        assert!(world.engine().fullscreen_windows(space).is_empty());
+
+        let response = world.run_command(LayoutCommand::SwitchToWorkspace(0));
+        assert_eq!(response.fullscreen_windows, vec![(windows[0], true)]);
+        assert_eq!(
+            world.engine().fullscreen_windows(space),
+            vec![(windows[0], true)]
+        );
+    }
+
+    #[test]
+    fn leaving_fullscreen_keeps_focus_on_the_same_window() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let space = world.space(0);
+        let windows = world.add_app(1, 3);
+        world.focus(windows[1]);
+
+        let response = world.run_command(LayoutCommand::ToggleFullscreen);
+        assert_eq!(response.focus_window, Some(windows[1]));
+        let response = world.run_command(LayoutCommand::ToggleFullscreen);
+        assert_eq!(response.focus_window, Some(windows[1]));
+        assert_eq!(response.raise_windows.last(), Some(&windows[1]));
+
+        let layout = world.engine_mut().layout(space);
+        assert_eq!(world.engine().tree.selected_window(layout), Some(windows[1]));
+    }
+
+    #[test]
+    fn isolate_and_maximize_keeps_the_window_maximized_and_focused() {
+        use crate::common::config::LayoutMode;
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        for mode in [
+            LayoutMode::Traditional,
+            LayoutMode::Bsp,
+            LayoutMode::Dwindle,
+        ] {
+            let settings = LayoutSettings {
+                mode: mode.clone(),
+                ..LayoutSettings::default()
+            };
+            let mut world = SimulatedWorld::with_settings(
+                vec![screen(1000.0, 800.0)],
+                &settings,
+                &VirtualWorkspaceSettings::default(),
+            );
+            let space = world.space(0);
+            let windows = world.add_app(1, 3);
+            world.focus(windows[1]);
+
+            for _ in 0..2 {
+                let response = world.run_command(LayoutCommand::IsolateAndMaximize);
+                assert_eq!(response.focus_window, Some(windows[1]), "{mode:?}");
+                assert_eq!(response.raise_windows.last(), Some(&windows[1]), "{mode:?}");
+                let layout = world.engine_mut().layout(space);
+                assert_eq!(
+                    world.engine().tree.fullscreen_windows(layout),
+                    vec![(windows[1], true)],
+                    "{mode:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fullscreen_raise_list_stays_on_its_workspace() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let space = world.space(0);
+        let first = world.add_app(1, 2);
+        world.focus(first[0]);
+        let response = world.run_command(LayoutCommand::ToggleFullscreen);
+        assert_eq!(response.raise_windows, vec![first[0]]);
+
+        world.run_command(LayoutCommand::SwitchToWorkspace(1));
+        let second = world.add_app(2, 2);
+        let response =
+            world.engine_mut().handle_event(LayoutEvent::WindowFocused(space, second[1]));
+        assert!(response.raise_windows.iter().all(|wid| !first.contains(wid)));
+        assert!(response.fullscreen_windows.is_empty());
+
+        let response = world.run_command(LayoutCommand::ToggleFullscreen);
+        assert_eq!(response.raise_windows, vec![second[1]]);
+        assert_eq!(
+            world.engine().fullscreen_windows(space),
+            vec![(second[1], false)]
+        );
+    }
+
+    #[test]
+    fn removal_without_rebalance_keeps_sibling_ratio() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let settings = LayoutSettings {
+            rebalance_on_remove: false,
+            ..Default::default()
+        };
+        let mut world = SimulatedWorld::with_settings(
+            vec![screen(1000.0, 800.0)],
+            &settings,
+            &VirtualWorkspaceSettings::default(),
+        );
+        let windows = world.add_app(1, 3);
+        world.focus(windows[0]);
+        world.run_command(LayoutCommand::GoldenRatio { main_first: true });
+
+        world.remove_window(windows[2]);
+        let main = world.frame(windows[0]).unwrap().size.width;
+        let side = world.frame(windows[1]).unwrap().size.width;
+        assert_eq!((main, side), (764.0, 236.0));
+
+        // The parent's total still matches its children, so resizes move the
+        // edge by exactly the requested share of the screen.
+        world.focus(windows[0]);
+        world.run_command(LayoutCommand::ResizeWindowBy { amount: 0.1 });
+        assert_eq!(world.frame(windows[0]).unwrap().size.width, main + 100.0);
+    }
+
+    #[test]
+    fn unmanaged_windows_stay_out_until_managed_again() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let space = world.space(0);
+        let windows = world.add_app(1, 2);
+        world.focus(windows[1]);
+        world.run_command(LayoutCommand::UnmanageWindow);
+        assert!(!world.engine().is_managed(space, windows[1]));
+        assert_eq!(world.visible_windows(), vec![windows[0]]);
+
+        // Rediscovering the app's windows does not tile it again.
+        let added = world.add_app(1, 1);
+        assert!(world.visible_windows().contains(&added[0]));
+        assert!(!world.visible_windows().contains(&windows[1]));
+
+        let bytes = persistence::encode(PersistenceFormat::Ron, world.engine()).unwrap();
+        let restored: LayoutEngine = persistence::decode(&bytes).unwrap();
+        assert_eq!(
+            restored.virtual_workspace_manager().manage_override(windows[1]),
+            Some(false)
+        );
+
+        world.engine_mut().set_focused_window(windows[1]);
+        let response = world.run_command(LayoutCommand::ManageWindow);
+        assert_eq!(response.focus_window, Some(windows[1]));
+        assert!(world.engine().is_managed(space, windows[1]));
+        assert_eq!(world.visible_windows().len(), 3);
+
+        // The override dies with the window.
+        world.remove_window(windows[1]);
+        assert_eq!(
+            world.engine().virtual_workspace_manager().manage_override(windows[1]),
+            None
+        );
+    }
+
+    #[test]
+    fn removal_only_rebalances_the_window_workspace() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0), screen(1000.0, 800.0)]);
+        let left = world.add_app_on(world.space(0), 1, 2);
+        let right = world.add_app_on(world.space(1), 2, 3);
+        world.focus(left[0]);
+        world.run_command(LayoutCommand::ResizeWindowBy { amount: 0.1 });
+        let resized = world.frame(left[0]).unwrap().size.width;
+        assert_eq!(resized, 600.0);
+
+        world.remove_window(right[2]);
+        assert_eq!(world.frame(left[0]).unwrap().size.width, resized);
+        assert_eq!(world.frame(right[0]).unwrap().size.width, 500.0);
+    }
+
+    #[test]
+    fn pixel_resizes_are_measured_against_the_space_screen() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let world = SimulatedWorld::new(vec![screen(1000.0, 800.0), screen(2000.0, 1000.0)]);
+        let (small, large) = (world.space(0), world.space(1));
+        let engine = world.engine();
+
+        assert_eq!(
+            engine.resize_amount_for_pixels(small, Direction::Right, 100.0),
+            Some(0.1)
+        );
+        assert_eq!(
+            engine.resize_amount_for_pixels(small, Direction::Up, 200.0),
+            Some(0.25)
+        );
+        assert_eq!(
+            engine.resize_amount_for_pixels(large, Direction::Left, 100.0),
+            Some(0.05)
+        );
+        assert_eq!(
+            engine.resize_amount_for_pixels(large, Direction::Down, -200.0),
+            Some(-0.2)
+        );
+        assert_eq!(
+            engine.resize_amount_for_pixels(SpaceId::new(99), Direction::Up, 1.0),
+            None
+        );
+    }
+
+    #[test]
+    fn pixel_resize_moves_the_edge_by_that_many_pixels() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0), screen(2000.0, 1000.0)]);
+        let left = world.add_app_on(world.space(0), 1, 2);
+        let right = world.add_app_on(world.space(1), 2, 2);
+
+        for wid in [left[0], right[0]] {
+            world.focus(wid);
+            let before = world.frame(wid).unwrap().size.width;
+            world.run_command(LayoutCommand::ResizeWindowPx {
+                direction: Direction::Right,
+                pixels: 100.0,
+            });
+            assert_eq!(world.frame(wid).unwrap().size.width, before + 100.0);
+        }
+    }
+
+    #[test]
+    fn pixel_resize_stays_on_the_requested_axis() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let windows = world.add_app(1, 2);
+        world.focus(windows[0]);
+        let before = world.frame(windows[0]);
+
+        // Side by side, there is no vertical edge to move.
+        world.run_command(LayoutCommand::ResizeWindowPx {
+            direction: Direction::Up,
+            pixels: 100.0,
+        });
+        assert_eq!(world.frame(windows[0]), before);
+
+        world.run_command(LayoutCommand::ToggleOrientation);
+        let before = world.frame(windows[0]).unwrap();
+        world.run_command(LayoutCommand::ResizeWindowPx {
+            direction: Direction::Down,
+            pixels: 100.0,
+        });
+        let after = world.frame(windows[0]).unwrap();
+        assert_eq!(after.size.height, before.size.height + 100.0);
+        assert_eq!(after.size.width, before.size.width);
+    }
+
+    #[test]
+    fn oversized_resizes_are_rejected() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let windows = world.add_app(1, 2);
+        world.focus(windows[0]);
+        let before = world.frame(windows[0]);
+
+        world.run_command(LayoutCommand::ResizeWindowBy { amount: 50.0 });
+        assert_eq!(world.frame(windows[0]), before);
+        world.run_command(LayoutCommand::ResizeWindowPx {
+            direction: Direction::Right,
+            pixels: 600.0,
+        });
+        assert_eq!(world.frame(windows[0]), before);
+    }
+
+    fn smart_gaps_world(smart_outer: bool) -> crate::layout_engine::testing::SimulatedWorld {
+        use crate::common::config::{InnerGaps, OuterGaps};
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let mut settings = LayoutSettings::default();
+        settings.gaps.smart = true;
+        settings.gaps.smart_outer = smart_outer;
+        settings.gaps.outer = OuterGaps::uniform(10.0);
+        settings.gaps.inner = InnerGaps { horizontal: 8.0, vertical: 8.0 };
+        SimulatedWorld::with_settings(
+            vec![screen(1000.0, 800.0)],
+            &settings,
+            &VirtualWorkspaceSettings::default(),
+        )
+    }
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> CGRect {
+        CGRect::new(CGPoint::new(x, y), CGSize::new(width, height))
+    }
+
+    #[test]
+    fn smart_gaps_only_drop_gaps_for_a_lone_window() {
+        use crate::layout_engine::testing::screen;
+
+        let mut world = smart_gaps_world(true);
+        let first = world.add_app(1, 1)[0];
+        assert_eq!(world.frame(first), Some(screen(1000.0, 800.0)));
+
+        let second = world.add_app(2, 1)[0];
+        assert_eq!(world.frame(first), Some(rect(10.0, 10.0, 486.0, 780.0)));
+        assert_eq!(world.frame(second), Some(rect(504.0, 10.0, 486.0, 780.0)));
+
+        world.remove_window(second);
+        assert_eq!(world.frame(first), Some(screen(1000.0, 800.0)));
+    }
+
+    #[test]
+    fn smart_gaps_keep_gaps_and_stack_line_for_stacked_windows() {
+        use crate::common::config::{HorizontalPlacement, VerticalPlacement};
+        use crate::layout_engine::testing::screen;
+
+        let mut world = smart_gaps_world(true);
+        let space = world.space(0);
+        let windows = world.add_app(1, 2);
+        world.focus(windows[1]);
+        world.run_command(LayoutCommand::ToggleStack);
+        assert_eq!(world.visible_windows(), vec![windows[1]]);
+
+        let gaps = world.engine().layout_settings.gaps.clone();
+        let layout = |world: &mut crate::layout_engine::testing::SimulatedWorld, thickness| {
+            world.engine_mut().calculate_layout(
+                space,
+                screen(1000.0, 800.0),
+                &gaps,
+                thickness,
+                HorizontalPlacement::Top,
+                VerticalPlacement::Left,
+            )
+        };
+        // The covered stack member still counts, so the stack keeps its gaps
+        // and the room for its bar.
+        let frames = layout(&mut world, 20.0);
+        assert_ne!(frames, layout(&mut world, 0.0));
+        assert!(frames.iter().all(|(_, frame)| *frame != screen(1000.0, 800.0)));
+
+        world.remove_window(windows[0]);
+        assert_eq!(
+            layout(&mut world, 20.0),
+            vec![(windows[1], screen(1000.0, 800.0))]
+        );
+    }
+
+    #[test]
+    fn smart_gaps_apply_to_fullscreen_within_gaps() {
+        use crate::layout_engine::testing::screen;
+
+        let mut world = smart_gaps_world(true);
+        let windows = world.add_app(1, 2);
+        world.focus(windows[0]);
+        world.run_command(LayoutCommand::ToggleFullscreenWithinGaps);
+        assert_eq!(world.frame(windows[0]), Some(rect(10.0, 10.0, 980.0, 780.0)));
+
+        world.remove_window(windows[1]);
+        assert_eq!(world.frame(windows[0]), Some(screen(1000.0, 800.0)));
+    }
+
+    #[test]
+    fn smart_gaps_can_keep_the_outer_gaps() {
+        let mut world = smart_gaps_world(false);
+        let first = world.add_app(1, 1)[0];
+        assert_eq!(world.frame(first), Some(rect(10.0, 10.0, 980.0, 780.0)));
+
+        let second = world.add_app(2, 1)[0];
+        assert_eq!(world.frame(first), Some(rect(10.0, 10.0, 486.0, 780.0)));
+        assert_eq!(world.frame(second), Some(rect(504.0, 10.0, 486.0, 780.0)));
+
+        world.remove_window(second);
+        assert_eq!(world.frame(first), Some(rect(10.0, 10.0, 980.0, 780.0)));
+    }
+
+    /// An engine spread over `spaces` spaces with `workspaces` workspaces
+    /// each, every workspace holding `windows` windows, some of them joined
+    /// into containers.
+    fn large_engine(spaces: u64, workspaces: usize, windows: u32) -> LayoutEngine {
+        let settings = VirtualWorkspaceSettings {
+            default_workspace_count: workspaces,
+            ..VirtualWorkspaceSettings::default()
+        };
+        let mut engine = LayoutEngine::new(&settings, &LayoutSettings::default(), None);
+        let mut pid = 0;
+        for space in (1..=spaces).map(SpaceId::new) {
+            let _ =
+                engine.handle_event(LayoutEvent::SpaceExposed(space, CGSize::new(1920.0, 1080.0)));
+            for workspace in 0..workspaces {
+                let _ = engine.handle_virtual_workspace_command(
+                    space,
+                    &LayoutCommand::SwitchToWorkspace(workspace),
+                );
+                pid += 1;
+                for idx in 1..=windows {
+                    let wid = WindowId::new(pid, idx);
+                    let _ = engine.handle_event(LayoutEvent::WindowAdded(space, wid));
+                    let _ = engine.handle_event(LayoutEvent::WindowFocused(space, wid));
+                    if idx % 3 == 0 {
+                        let _ = engine.handle_command(
+                            Some(space),
+                            &[space],
+                            &HashMap::default(),
+                            LayoutCommand::JoinWindow(Direction::Left),
+                        );
+                    }
+                }
+            }
+        }
+        engine
+    }
+
+    /// What a restored engine has to reproduce: every active layout's tree
+    /// and the workspace of every window.
+    fn persisted_summary(engine: &LayoutEngine) -> (Vec<String>, Vec<String>) {
+        let mut trees = Vec::new();
+        for space in engine.workspace_layouts.spaces() {
+            let mut layouts = engine.workspace_layouts.active_layouts_for_space(space);
+            layouts.sort_unstable();
+            for (workspace, layout) in layouts {
+                trees.push(format!(
+                    "{space:?} {workspace:?}\n{}",
+                    engine.tree.draw_tree(layout)
+                ));
+            }
+        }
+        let mut assignments: Vec<_> = engine
+            .virtual_workspace_manager
+            .window_to_workspace
+            .iter()
+            .map(|(key, workspace)| format!("{key:?} -> {workspace:?}"))
+            .collect();
+        assignments.sort_unstable();
+        (trees, assignments)
+    }
+
+    #[test]
+    fn saved_state_round_trips_in_every_format() {
+        let engine = large_engine(2, 3, 5);
+        let expected = persisted_summary(&engine);
+        let dir = tempfile::tempdir().unwrap();
+
+        for format in [
+            PersistenceFormat::Ron,
+            PersistenceFormat::Json,
+            PersistenceFormat::Binary,
+        ] {
+            let path = dir.path().join(format!("{format:?}"));
+            engine.save(path.clone(), format).unwrap();
+            let restored = LayoutEngine::load(path).unwrap();
+            assert_eq!(persisted_summary(&restored), expected, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn saved_state_is_rewritten_in_the_configured_format() {
+        let engine = large_engine(1, 2, 4);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("layout.ron");
+        engine.save(path.clone(), PersistenceFormat::Ron).unwrap();
+
+        for format in [PersistenceFormat::Json, PersistenceFormat::Binary] {
+            let restored = LayoutEngine::load(path.clone()).unwrap();
+            restored.save(path.clone(), format).unwrap();
+
+            let bytes = fs::read(&path).unwrap();
+            assert_eq!(PersistenceFormat::detect(&bytes), format);
+            assert_eq!(
+                persisted_summary(&LayoutEngine::load(path.clone()).unwrap()),
+                persisted_summary(&engine)
+            );
+        }
+    }
+
+    #[test]
+    fn autosave_writes_at_most_once_per_interval() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("layout.ron");
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let settings = PersistenceSettings {
+            autosave_secs: 5.0,
+            ..Default::default()
+        };
+        world.engine_mut().enable_autosave(path.clone(), &settings);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert!(!world.engine_mut().autosave_if_due(at(0)).unwrap());
+        let windows = world.add_app(1, 3);
+        assert!(world.engine_mut().autosave_if_due(at(0)).unwrap());
+
+        // A burst of changes within the interval is written once, at its end.
+        world.focus(windows[1]);
+        world.remove_window(windows[2]);
+        for secs in 1..5 {
+            assert!(!world.engine_mut().autosave_if_due(at(secs)).unwrap());
+            assert!(LayoutEngine::load(path.clone()).is_ok());
+        }
+        assert!(world.engine_mut().autosave_if_due(at(5)).unwrap());
+        assert!(!world.engine_mut().autosave_if_due(at(60)).unwrap());
+        assert_eq!(world.engine().metrics_snapshot().save.count, 2);
+
+        let restored = LayoutEngine::load(path.clone()).unwrap();
+        assert_eq!(persisted_summary(&restored), persisted_summary(world.engine()));
+        assert!(!dir.path().join("layout.ron.tmp").exists());
+
+        // Saving right away writes only what changed since the last save, and
+        // focus alone changes nothing that is saved.
+        world.engine_mut().save_now().unwrap();
+        assert_eq!(world.engine().metrics_snapshot().save.count, 2);
+        world.focus(windows[0]);
+        world.engine_mut().save_now().unwrap();
+        assert_eq!(world.engine().metrics_snapshot().save.count, 2);
+        world.run_command(LayoutCommand::MoveNode(Direction::Right));
+        world.engine_mut().save_now().unwrap();
+        assert_eq!(world.engine().metrics_snapshot().save.count, 3);
+        world.engine_mut().shutdown();
+        assert_eq!(world.engine().metrics_snapshot().save.count, 3);
+    }
+
+    #[test]
+    fn zero_autosave_interval_disables_autosaving() {
+        use crate::layout_engine::testing::{SimulatedWorld, screen};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("layout.ron");
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let settings = PersistenceSettings {
+            autosave_secs: 0.0,
+            ..Default::default()
+        };
+        world.engine_mut().enable_autosave(path.clone(), &settings);
+
+        world.add_app(1, 2);
+        assert!(!world.engine_mut().autosave_if_due(Instant::now()).unwrap());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn corrupt_state_loads_the_previous_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("layout.ron");
+        let previous = large_engine(1, 2, 3);
+        previous.save(path.clone(), PersistenceFormat::Ron).unwrap();
+        large_engine(1, 2, 4).save(path.clone(), PersistenceFormat::Ron).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+
+        let restored = LayoutEngine::load(path).unwrap();
+        assert_eq!(persisted_summary(&restored), persisted_summary(&previous));
+    }
+
+    #[test]
+    #[ignore = "benchmark; run with `cargo test persistence_formats -- --ignored --nocapture`"]
+    fn persistence_formats_benchmark() {
+        const ROUNDS: u32 = 20;
+        let engine = large_engine(4, 16, 12);
+
+        for format in [
+            PersistenceFormat::Ron,
+            PersistenceFormat::Json,
+            PersistenceFormat::Binary,
+        ] {
+            let start = Instant::now();
+            let mut bytes = Vec::new();
+            for _ in 0..ROUNDS {
+                bytes = persistence::encode(format, &engine).unwrap();
+            }
+            let encode = start.elapsed() / ROUNDS;
+
+            let start = Instant::now();
+            for _ in 0..ROUNDS {
+                let _: LayoutEngine = persistence::decode(&bytes).unwrap();
+            }
+            let decode = start.elapsed() / ROUNDS;
+
+            println!(
+                "{format:?}: {} bytes, encode {encode:?}, decode {decode:?}",
+                bytes.len()
+            );
+        }
+    }
 }
@@ -1,15 +1,24 @@
 use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
 
 use crate::actor::app::{WindowId, pid_t};
 use crate::common::collections::{BTreeExt, BTreeSet, HashMap, HashSet};
+use crate::common::persistence::MapEntries;
+use crate::model::VirtualWorkspaceId;
 use crate::sys::screen::SpaceId;
 
+#[serde_as]
 #[derive(Serialize, Deserialize, Default)]
 pub(crate) struct FloatingManager {
     floating_windows: BTreeSet<WindowId>,
     #[serde(skip)]
     active_floating_windows: HashMap<SpaceId, HashMap<pid_t, HashSet<WindowId>>>,
     last_floating_focus: Option<WindowId>,
+    /// Floating windows per workspace in the order they were last raised,
+    /// bottom to top.
+    #[serde(default)]
+    #[serde_as(as = "MapEntries")]
+    z_order: HashMap<VirtualWorkspaceId, Vec<WindowId>>,
 }
 
 impl FloatingManager {
@@ -39,6 +48,7 @@ impl FloatingManager {
         if self.last_floating_focus == Some(window_id) {
             self.last_floating_focus = None;
         }
+        self.forget_z_order(|wid| wid == window_id);
     }
 
     pub(crate) fn clear_active_for_app(&mut self, space: SpaceId, pid: pid_t) {
@@ -94,6 +104,36 @@ impl FloatingManager {
         {
             self.last_floating_focus = None;
         }
+        self.forget_z_order(|wid| wid.pid == pid);
+    }
+
+    /// Records `wid` as the topmost floating window of `workspace`, dropping
+    /// it from wherever it was before.
+    pub(crate) fn note_raised(&mut self, workspace: VirtualWorkspaceId, wid: WindowId) {
+        self.forget_z_order(|w| w == wid);
+        self.z_order.entry(workspace).or_default().push(wid);
+    }
+
+    /// `windows` sorted bottom to top by when they were last raised in
+    /// `workspace`. Windows never raised there go underneath, in the order
+    /// given.
+    pub(crate) fn z_ordered(
+        &self,
+        workspace: VirtualWorkspaceId,
+        windows: &[WindowId],
+    ) -> Vec<WindowId> {
+        let order = self.z_order.get(&workspace).map(Vec::as_slice).unwrap_or_default();
+        let mut ordered: Vec<WindowId> =
+            windows.iter().copied().filter(|wid| !order.contains(wid)).collect();
+        ordered.extend(order.iter().copied().filter(|wid| windows.contains(wid)));
+        ordered
+    }
+
+    fn forget_z_order(&mut self, mut matches: impl FnMut(WindowId) -> bool) {
+        self.z_order.retain(|_, order| {
+            order.retain(|&wid| !matches(wid));
+            !order.is_empty()
+        });
     }
 
     pub(crate) fn rebuild_active_for_workspace(
@@ -313,4 +353,41 @@ mod tests {
         assert_eq!(active1[0], w(1, 1));
         assert_eq!(active2[0], w(1, 2));
     }
+
+    fn workspace_ids() -> (VirtualWorkspaceId, VirtualWorkspaceId) {
+        let mut ids = slotmap::SlotMap::<VirtualWorkspaceId, ()>::with_key();
+        (ids.insert(()), ids.insert(()))
+    }
+
+    #[test]
+    fn test_floating_manager_z_order_follows_raises() {
+        let mut manager = FloatingManager::new();
+        let (ws, _) = workspace_ids();
+        let windows = [w(1, 1), w(2, 1), w(3, 1)];
+
+        manager.note_raised(ws, w(2, 1));
+        manager.note_raised(ws, w(1, 1));
+        manager.note_raised(ws, w(2, 1));
+
+        assert_eq!(manager.z_ordered(ws, &windows), vec![w(3, 1), w(1, 1), w(2, 1)]);
+    }
+
+    #[test]
+    fn test_floating_manager_z_order_prunes_removed_and_moved_windows() {
+        let mut manager = FloatingManager::new();
+        let (ws1, ws2) = workspace_ids();
+        for wid in [w(1, 1), w(1, 2), w(2, 1)] {
+            manager.add_floating(wid);
+            manager.note_raised(ws1, wid);
+        }
+
+        manager.note_raised(ws2, w(1, 2));
+        manager.remove_floating(w(2, 1));
+
+        assert_eq!(manager.z_order[&ws1], vec![w(1, 1)]);
+        assert_eq!(manager.z_order[&ws2], vec![w(1, 2)]);
+
+        manager.remove_all_for_pid(1);
+        assert!(manager.z_order.is_empty());
+    }
 }
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Orientation {
     Horizontal,
@@ -57,7 +57,7 @@ impl Direction {
 }
 
 #[allow(unused)]
-#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum LayoutKind {
     #[default]
@@ -65,6 +65,10 @@ pub enum LayoutKind {
     Vertical,
     HorizontalStack,
     VerticalStack,
+    /// A stack showing only its selected member, at the container's full
+    /// size, with the others hidden behind it.
+    HorizontalTabbed,
+    VerticalTabbed,
 }
 
 impl LayoutKind {
@@ -82,8 +86,23 @@ impl LayoutKind {
         }
     }
 
+    pub fn tabbed(orientation: Orientation) -> Self {
+        match orientation {
+            Orientation::Horizontal => LayoutKind::HorizontalTabbed,
+            Orientation::Vertical => LayoutKind::VerticalTabbed,
+        }
+    }
+
     pub fn is_stacked(self) -> bool {
-        matches!(self, LayoutKind::HorizontalStack | LayoutKind::VerticalStack)
+        use LayoutKind::*;
+        matches!(
+            self,
+            HorizontalStack | VerticalStack | HorizontalTabbed | VerticalTabbed
+        )
+    }
+
+    pub fn is_tabbed(self) -> bool {
+        matches!(self, LayoutKind::HorizontalTabbed | LayoutKind::VerticalTabbed)
     }
 
     pub fn orientation(self) -> Orientation {
@@ -91,13 +110,26 @@ impl LayoutKind {
         match self {
             Horizontal => Orientation::Horizontal,
             Vertical => Orientation::Vertical,
-            HorizontalStack => Orientation::Horizontal,
-            VerticalStack => Orientation::Vertical,
+            HorizontalStack | HorizontalTabbed => Orientation::Horizontal,
+            VerticalStack | VerticalTabbed => Orientation::Vertical,
         }
     }
 
     pub fn is_group(self) -> bool {
-        matches!(self, LayoutKind::HorizontalStack | LayoutKind::VerticalStack)
+        self.is_stacked()
+    }
+
+    /// The same kind laid out along the other axis.
+    pub fn rotated(self) -> Self {
+        use LayoutKind::*;
+        match self {
+            Horizontal => Vertical,
+            Vertical => Horizontal,
+            HorizontalStack => VerticalStack,
+            VerticalStack => HorizontalStack,
+            HorizontalTabbed => VerticalTabbed,
+            VerticalTabbed => HorizontalTabbed,
+        }
     }
 }
 
@@ -145,6 +177,11 @@ mod tests {
                 Orientation::Horizontal
             );
             assert_eq!(LayoutKind::VerticalStack.orientation(), Orientation::Vertical);
+            assert_eq!(
+                LayoutKind::HorizontalTabbed.orientation(),
+                Orientation::Horizontal
+            );
+            assert_eq!(LayoutKind::VerticalTabbed.orientation(), Orientation::Vertical);
         }
 
         #[test]
@@ -153,6 +190,29 @@ mod tests {
             assert!(!LayoutKind::Vertical.is_stacked());
             assert!(LayoutKind::HorizontalStack.is_stacked());
             assert!(LayoutKind::VerticalStack.is_stacked());
+            assert!(LayoutKind::HorizontalTabbed.is_stacked());
+            assert!(LayoutKind::VerticalTabbed.is_stacked());
+        }
+
+        #[test]
+        fn only_tabbed_kinds_are_tabbed() {
+            assert!(!LayoutKind::HorizontalStack.is_tabbed());
+            assert!(!LayoutKind::Vertical.is_tabbed());
+            assert!(LayoutKind::HorizontalTabbed.is_tabbed());
+            assert_eq!(
+                LayoutKind::VerticalTabbed.rotated(),
+                LayoutKind::HorizontalTabbed
+            );
+        }
+
+        #[test]
+        fn tabbed_kinds_round_trip_through_serde() {
+            let json = serde_json::to_string(&LayoutKind::VerticalTabbed).unwrap();
+            assert_eq!(json, "\"vertical_tabbed\"");
+            assert_eq!(
+                serde_json::from_str::<LayoutKind>(&json).unwrap(),
+                LayoutKind::VerticalTabbed
+            );
         }
 
         #[test]
@@ -0,0 +1,109 @@
+//! A bounded record of the events and commands the engine handled, so a bug
+//! report can show the exact sequence that scrambled a layout.
+//!
+//! The journal is off by default. While it is off the engine holds no
+//! journal at all and recording costs a single branch; nothing is formatted.
+
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::{LayoutCommand, LayoutEvent};
+use crate::common::config::JournalSettings;
+use crate::sys::screen::SpaceId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalEntryKind {
+    Event,
+    Command,
+}
+
+/// One event or command, as the engine saw it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    /// The space it applied to, or `None` for ones that apply to every
+    /// space, like an app closing.
+    pub space: Option<SpaceId>,
+    pub kind: JournalEntryKind,
+    /// The event or command variant, as counted in the metrics.
+    pub name: String,
+    /// The full event or command, with window titles left out if
+    /// `scrub_titles` is set.
+    pub detail: String,
+    /// Fingerprint of the space's active layout once it was handled.
+    pub fingerprint: Option<u64>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Journal {
+    capacity: usize,
+    scrub_titles: bool,
+    entries: VecDeque<JournalEntry>,
+}
+
+impl Journal {
+    /// Reconciles `journal` with `settings`, keeping recorded entries that
+    /// still fit.
+    pub(crate) fn configure(journal: &mut Option<Journal>, settings: &JournalSettings) {
+        if !settings.enabled || settings.capacity == 0 {
+            *journal = None;
+            return;
+        }
+        let journal = journal.get_or_insert_with(|| Journal {
+            capacity: settings.capacity,
+            scrub_titles: settings.scrub_titles,
+            entries: VecDeque::new(),
+        });
+        journal.capacity = settings.capacity;
+        journal.scrub_titles = settings.scrub_titles;
+        while journal.entries.len() > journal.capacity {
+            journal.entries.pop_front();
+        }
+    }
+
+    pub(crate) fn describe_event(&self, event: &LayoutEvent) -> String {
+        match event {
+            LayoutEvent::WindowsOnScreenUpdated(space, pid, windows, info) if self.scrub_titles => {
+                let windows = windows
+                    .iter()
+                    .map(|(wid, _, role, subrole)| (*wid, None, role.clone(), subrole.clone()))
+                    .collect();
+                format!(
+                    "{:?}",
+                    LayoutEvent::WindowsOnScreenUpdated(*space, *pid, windows, info.clone())
+                )
+            }
+            _ => format!("{event:?}"),
+        }
+    }
+
+    pub(crate) fn describe_command(&self, command: &LayoutCommand) -> String {
+        format!("{command:?}")
+    }
+
+    pub(crate) fn record(&mut self, entry: JournalEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Entries for `space`, and those for every space, oldest first.
+    pub(crate) fn entries_for(&self, space: SpaceId) -> Vec<JournalEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.space.is_none_or(|s| s == space))
+            .cloned()
+            .collect()
+    }
+}
+
+pub(crate) fn timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
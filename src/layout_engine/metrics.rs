@@ -0,0 +1,141 @@
+//! Counters and timers for diagnosing slow layout work.
+//!
+//! Recording is always on and kept cheap: there is an atomic counter for
+//! every event and command kind, created up front and looked up by the
+//! static variant name, and timers are a few atomic adds. Nothing is
+//! formatted until a [`EngineMetrics`] snapshot is taken.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use strum::VariantNames;
+
+use crate::common::collections::{BTreeMap, HashMap};
+use crate::layout_engine::{LayoutCommand, LayoutEvent};
+
+/// Accumulated durations of one kind of work.
+#[derive(Debug, Default)]
+pub(crate) struct Timer {
+    count: AtomicU64,
+    total_ns: AtomicU64,
+    max_ns: AtomicU64,
+}
+
+impl Timer {
+    /// Adds the time since `start`.
+    pub(crate) fn record_since(&self, start: Instant) {
+        let ns = start.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_ns.fetch_add(ns, Ordering::Relaxed);
+        self.max_ns.fetch_max(ns, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> TimerSnapshot {
+        TimerSnapshot {
+            count: self.count.load(Ordering::Relaxed),
+            total_us: self.total_ns.load(Ordering::Relaxed) / 1_000,
+            max_us: self.max_ns.load(Ordering::Relaxed) / 1_000,
+        }
+    }
+}
+
+/// One counter per variant name.
+#[derive(Debug)]
+struct Counters(HashMap<&'static str, AtomicU64>);
+
+impl Counters {
+    fn new(kinds: &[&'static str]) -> Self {
+        Counters(kinds.iter().map(|&kind| (kind, AtomicU64::new(0))).collect())
+    }
+
+    fn count(&self, kind: &'static str) {
+        if let Some(counter) = self.0.get(kind) {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The kinds seen at least once.
+    fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.0
+            .iter()
+            .map(|(kind, counter)| (kind.to_string(), counter.load(Ordering::Relaxed)))
+            .filter(|&(_, count)| count > 0)
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Metrics {
+    events: Counters,
+    commands: Counters,
+    pub(crate) set_windows_for_app: Timer,
+    pub(crate) calculate_layout: Timer,
+    pub(crate) rebalance: Timer,
+    pub(crate) save: Timer,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            events: Counters::new(LayoutEvent::VARIANTS),
+            commands: Counters::new(LayoutCommand::VARIANTS),
+            set_windows_for_app: Timer::default(),
+            calculate_layout: Timer::default(),
+            rebalance: Timer::default(),
+            save: Timer::default(),
+        }
+    }
+}
+
+impl Metrics {
+    pub(crate) fn count_event(&self, kind: &'static str) {
+        self.events.count(kind);
+    }
+
+    pub(crate) fn count_command(&self, kind: &'static str) {
+        self.commands.count(kind);
+    }
+
+    pub(crate) fn snapshot(&self, layouts: Vec<LayoutTreeSize>) -> EngineMetrics {
+        EngineMetrics {
+            events: self.events.snapshot(),
+            commands: self.commands.snapshot(),
+            set_windows_for_app: self.set_windows_for_app.snapshot(),
+            calculate_layout: self.calculate_layout.snapshot(),
+            rebalance: self.rebalance.snapshot(),
+            save: self.save.snapshot(),
+            layouts,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimerSnapshot {
+    pub count: u64,
+    pub total_us: u64,
+    pub max_us: u64,
+}
+
+/// The size of a workspace's active layout.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayoutTreeSize {
+    pub space: u64,
+    pub workspace: String,
+    pub windows: usize,
+}
+
+/// What the engine has done since it was created.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EngineMetrics {
+    /// Events handled, by event kind.
+    pub events: BTreeMap<String, u64>,
+    /// Commands handled, by command kind.
+    pub commands: BTreeMap<String, u64>,
+    pub set_windows_for_app: TimerSnapshot,
+    pub calculate_layout: TimerSnapshot,
+    pub rebalance: TimerSnapshot,
+    /// Writes of the saved state, including autosaves.
+    pub save: TimerSnapshot,
+    pub layouts: Vec<LayoutTreeSize>,
+}
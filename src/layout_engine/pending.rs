@@ -0,0 +1,187 @@
+//! Layout events that arrive for a space before its `SpaceExposed`.
+//!
+//! macOS does not order window notifications against space changes, so a
+//! window can be reported on a space the engine has no layouts for yet. Those
+//! events are held here and replayed once the space is exposed.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use super::LayoutEvent;
+use crate::actor::app::{WindowId, pid_t};
+use crate::common::collections::HashMap;
+use crate::sys::screen::SpaceId;
+
+/// Events held per space; the oldest are dropped past this.
+const MAX_PENDING_PER_SPACE: usize = 64;
+/// How long an event waits for its space before it is discarded.
+const PENDING_EXPIRY: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default)]
+pub(crate) struct PendingSpaceEvents {
+    queues: HashMap<SpaceId, VecDeque<(Instant, LayoutEvent)>>,
+}
+
+impl PendingSpaceEvents {
+    /// The space an event has to wait for, if it is one that can be held.
+    pub(crate) fn space_of(event: &LayoutEvent) -> Option<SpaceId> {
+        match event {
+            LayoutEvent::WindowsOnScreenUpdated(space, ..)
+            | LayoutEvent::WindowAdded(space, _)
+            | LayoutEvent::WindowFocused(space, _) => Some(*space),
+            LayoutEvent::WindowMovedToSpace { to, .. } => Some(*to),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn push(&mut self, space: SpaceId, event: LayoutEvent, now: Instant) {
+        self.expire(now);
+        let queue = self.queues.entry(space).or_default();
+        if queue.len() == MAX_PENDING_PER_SPACE {
+            let (_, dropped) = queue.pop_front().expect("queue is full");
+            debug!(
+                ?space,
+                ?dropped,
+                "Too many events waiting for space; dropping oldest"
+            );
+        }
+        queue.push_back((now, event));
+    }
+
+    /// Removes and returns the unexpired events for `space`, oldest first.
+    pub(crate) fn take(&mut self, space: SpaceId, now: Instant) -> Vec<LayoutEvent> {
+        self.expire(now);
+        self.queues
+            .remove(&space)
+            .map(|queue| queue.into_iter().map(|(_, event)| event).collect())
+            .unwrap_or_default()
+    }
+
+    /// Drops held events about `wid`, which no longer exists.
+    pub(crate) fn forget_window(&mut self, wid: WindowId) {
+        self.retain(|event| match event {
+            LayoutEvent::WindowAdded(_, w) | LayoutEvent::WindowFocused(_, w) => *w != wid,
+            _ => true,
+        });
+    }
+
+    /// Drops held events about any window of `pid`.
+    pub(crate) fn forget_app(&mut self, pid: pid_t) {
+        self.retain(|event| match event {
+            LayoutEvent::WindowsOnScreenUpdated(_, p, ..) => *p != pid,
+            LayoutEvent::WindowAdded(_, w) | LayoutEvent::WindowFocused(_, w) => w.pid != pid,
+            _ => true,
+        });
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.queues.values().map(VecDeque::len).sum()
+    }
+
+    fn expire(&mut self, now: Instant) {
+        self.queues.retain(|space, queue| {
+            let before = queue.len();
+            queue.retain(|(at, _)| now.saturating_duration_since(*at) < PENDING_EXPIRY);
+            if queue.len() != before {
+                debug!(
+                    ?space,
+                    expired = before - queue.len(),
+                    "Space never exposed; dropping events"
+                );
+            }
+            !queue.is_empty()
+        });
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(&LayoutEvent) -> bool) {
+        self.queues.retain(|_, queue| {
+            queue.retain(|(_, event)| keep(event));
+            !queue.is_empty()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn added(space: u64, idx: u32) -> LayoutEvent {
+        LayoutEvent::WindowAdded(SpaceId::new(space), WindowId::new(1, idx))
+    }
+
+    fn added_windows(events: Vec<LayoutEvent>) -> Vec<WindowId> {
+        events
+            .into_iter()
+            .map(|event| match event {
+                LayoutEvent::WindowAdded(_, wid) => wid,
+                other => panic!("unexpected event {other:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn takes_events_for_one_space_in_order() {
+        let mut pending = PendingSpaceEvents::default();
+        let now = Instant::now();
+        pending.push(SpaceId::new(1), added(1, 1), now);
+        pending.push(SpaceId::new(2), added(2, 2), now);
+        pending.push(SpaceId::new(1), added(1, 3), now);
+
+        assert_eq!(
+            added_windows(pending.take(SpaceId::new(1), now)),
+            vec![WindowId::new(1, 1), WindowId::new(1, 3)]
+        );
+        assert!(pending.take(SpaceId::new(1), now).is_empty());
+        assert_eq!(pending.len(), 1);
+    }
+
+    #[test]
+    fn events_expire() {
+        let mut pending = PendingSpaceEvents::default();
+        let start = Instant::now();
+        pending.push(SpaceId::new(1), added(1, 1), start);
+        pending.push(SpaceId::new(2), added(2, 2), start + PENDING_EXPIRY / 2);
+
+        let later = start + PENDING_EXPIRY;
+        assert!(pending.take(SpaceId::new(1), later).is_empty());
+        assert_eq!(pending.len(), 1);
+        assert_eq!(
+            added_windows(pending.take(SpaceId::new(2), later)),
+            vec![WindowId::new(1, 2)]
+        );
+    }
+
+    #[test]
+    fn queues_are_bounded() {
+        let mut pending = PendingSpaceEvents::default();
+        let now = Instant::now();
+        let count = MAX_PENDING_PER_SPACE as u32 + 2;
+        for idx in 0..count {
+            pending.push(SpaceId::new(1), added(1, idx), now);
+        }
+
+        let windows = added_windows(pending.take(SpaceId::new(1), now));
+        assert_eq!(windows.len(), MAX_PENDING_PER_SPACE);
+        assert_eq!(windows.first(), Some(&WindowId::new(1, 2)));
+    }
+
+    #[test]
+    fn forgets_removed_windows_and_closed_apps() {
+        let mut pending = PendingSpaceEvents::default();
+        let now = Instant::now();
+        let space = SpaceId::new(1);
+        pending.push(space, added(1, 1), now);
+        pending.push(space, added(1, 2), now);
+        pending.push(space, LayoutEvent::WindowAdded(space, WindowId::new(2, 1)), now);
+
+        pending.forget_window(WindowId::new(1, 1));
+        assert_eq!(pending.len(), 2);
+        pending.forget_app(1);
+        assert_eq!(
+            added_windows(pending.take(space, now)),
+            vec![WindowId::new(2, 1)]
+        );
+    }
+}
@@ -5,34 +5,221 @@ use objc2_core_foundation::CGRect;
 use serde::{Deserialize, Serialize};
 
 use crate::actor::app::{WindowId, pid_t};
-use crate::layout_engine::{Direction, LayoutKind};
+use crate::layout_engine::{Direction, LayoutKind, Orientation};
+use crate::model::tree::{NodeId, Observer, Tree};
 
 slotmap::new_key_type! { pub struct LayoutId; }
 
+/// Controls how much of a layout tree `draw_tree_with_options` renders.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TreeRenderOptions {
+    /// Containers deeper than this are summarized instead of expanded.
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// Only expand containers on the path from the root to the selection.
+    #[serde(default)]
+    pub focus_path_only: bool,
+    /// Stop after rendering this many nodes and append a truncation notice.
+    #[serde(default)]
+    pub max_nodes: Option<usize>,
+}
+
+impl TreeRenderOptions {
+    pub fn focus_path() -> Self {
+        Self {
+            focus_path_only: true,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn should_expand(&self, depth: usize, on_focus_path: bool) -> bool {
+        (on_focus_path || !self.focus_path_only) && self.max_depth.is_none_or(|max| depth < max)
+    }
+}
+
+/// Tracks the node cap while rendering a tree.
+pub(crate) struct TreeRenderBudget {
+    remaining: Option<usize>,
+    truncated: bool,
+}
+
+impl TreeRenderBudget {
+    pub(crate) fn new(options: &TreeRenderOptions) -> Self {
+        Self {
+            remaining: options.max_nodes,
+            truncated: false,
+        }
+    }
+
+    /// Reserves one node, returning false once the cap has been reached.
+    pub(crate) fn take(&mut self) -> bool {
+        match &mut self.remaining {
+            None => true,
+            Some(0) => {
+                self.truncated = true;
+                false
+            }
+            Some(n) => {
+                *n -= 1;
+                true
+            }
+        }
+    }
+
+    pub(crate) fn finish(self, options: &TreeRenderOptions, out: &mut String) {
+        if self.truncated
+            && let Some(max) = options.max_nodes
+        {
+            if !out.is_empty() && !out.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push_str(&format!("… truncated after {max} nodes\n"));
+        }
+    }
+}
+
+/// Hasher for `layout_fingerprint`. Fingerprints leave the process through
+/// the journal and `layout_tree_changed`, so they must not depend on the
+/// standard library's unspecified, per-release algorithm.
+pub(crate) fn fingerprint_hasher() -> rustc_hash::FxHasher {
+    rustc_hash::FxHasher::default()
+}
+
+/// Quantizes a share or ratio for `layout_fingerprint`, so float noise far
+/// below a pixel doesn't read as a change.
+pub(crate) fn quantize_share(share: f32) -> i32 {
+    (share * 1000.0).round() as i32
+}
+
+pub(crate) fn collapsed_tree_summary(windows: usize) -> String {
+    match windows {
+        1 => "… 1 window".to_string(),
+        n => format!("… {n} windows"),
+    }
+}
+
+/// A node of a binary split tree, as the rotate and mirror helpers shared by
+/// the bsp and dwindle systems see it.
+pub(crate) trait SplitKind {
+    /// The axis of a split, or `None` for a leaf.
+    fn split_orientation(&self) -> Option<Orientation>;
+    /// Turns a split to the other axis.
+    fn turn_split(&mut self);
+    /// Gives each side of a split the other's share, for when they swap.
+    fn reverse_split_ratio(&mut self);
+}
+
+/// The splits under `root` in preorder, with their axes.
+pub(crate) fn split_nodes<O: Observer, K: SplitKind>(
+    tree: &Tree<O>,
+    kind: &slotmap::SecondaryMap<NodeId, K>,
+    root: NodeId,
+) -> Vec<(NodeId, Orientation)> {
+    root.traverse_preorder(&tree.map)
+        .filter_map(|node| Some((node, kind.get(node)?.split_orientation()?)))
+        .collect()
+}
+
+/// Reverses the two sides of split `node`, each keeping its share.
+pub(crate) fn reverse_split<O: Observer, K: SplitKind>(
+    tree: &mut Tree<O>,
+    kind: &mut slotmap::SecondaryMap<NodeId, K>,
+    node: NodeId,
+) {
+    let children: Vec<_> = node.children(&tree.map).collect();
+    for child in children.into_iter().rev() {
+        child.detach(tree).push_back(node).finish();
+    }
+    if let Some(split) = kind.get_mut(node) {
+        split.reverse_split_ratio();
+    }
+}
+
+/// Rotates the splits under `root` a quarter turn.
+pub(crate) fn rotate_splits<O: Observer, K: SplitKind>(
+    tree: &mut Tree<O>,
+    kind: &mut slotmap::SecondaryMap<NodeId, K>,
+    root: NodeId,
+    clockwise: bool,
+) {
+    // Clockwise, the top edge turns to the right, so vertical splits come
+    // out right to left; counter-clockwise it is horizontal ones.
+    let reversed = if clockwise {
+        Orientation::Vertical
+    } else {
+        Orientation::Horizontal
+    };
+    for (node, orientation) in split_nodes(tree, kind, root) {
+        if orientation == reversed {
+            reverse_split(tree, kind, node);
+        }
+        if let Some(split) = kind.get_mut(node) {
+            split.turn_split();
+        }
+    }
+}
+
+/// Mirrors the splits under `root` along `axis`.
+pub(crate) fn mirror_splits<O: Observer, K: SplitKind>(
+    tree: &mut Tree<O>,
+    kind: &mut slotmap::SecondaryMap<NodeId, K>,
+    root: NodeId,
+    axis: Orientation,
+) {
+    for (node, orientation) in split_nodes(tree, kind, root) {
+        if orientation == axis {
+            reverse_split(tree, kind, node);
+        }
+    }
+}
+
 pub trait LayoutLifecycle: Send + Serialize + for<'de> Deserialize<'de> {
     fn create_layout(&mut self) -> LayoutId;
     fn clone_layout(&mut self, layout: LayoutId) -> LayoutId;
     fn remove_layout(&mut self, layout: LayoutId);
+    /// Removes every layout, leaving the system empty. Called once, on exit.
+    fn shutdown(&mut self);
 }
 
 pub trait LayoutCore: Send + Serialize + for<'de> Deserialize<'de> {
     fn draw_tree(&self, layout: LayoutId) -> String;
+    fn draw_tree_with_options(&self, layout: LayoutId, options: &TreeRenderOptions) -> String;
+    /// Frames for the windows of `layout`. Windows `is_positionable` rejects,
+    /// such as minimized ones, keep their share of the space but get no frame.
     fn calculate_layout(
         &self,
         layout: LayoutId,
         screen: CGRect,
         stack_offset: f64,
+        focus_pop: f64,
         gaps: &crate::common::config::GapSettings,
         stack_line_thickness: f64,
         stack_line_horiz: crate::common::config::HorizontalPlacement,
         stack_line_vert: crate::common::config::VerticalPlacement,
+        is_positionable: Option<&dyn Fn(WindowId) -> bool>,
     ) -> Vec<(WindowId, CGRect)>;
+    /// Stable hash over everything in `layout` that decides window frames:
+    /// structure, kinds, quantized sizes, window ids and fullscreen flags,
+    /// plus locks and which member each stack shows where a system has them.
+    /// Built from traversal order rather than node keys, so the same logical
+    /// tree hashes the same across restarts.
+    fn layout_fingerprint(&self, layout: LayoutId) -> u64;
+    /// Visible windows of `layout` in preorder, so the order is stable for a
+    /// given tree.
     fn visible_windows_in_layout(&self, layout: LayoutId) -> Vec<WindowId>;
+    /// The `n`th window (zero-based) of `visible_windows_in_layout`.
+    fn nth_visible_window(&self, layout: LayoutId, n: usize) -> Option<WindowId> {
+        self.visible_windows_in_layout(layout).get(n).copied()
+    }
     fn visible_windows_under_selection(&self, layout: LayoutId) -> Vec<WindowId>;
     fn selected_window(&self, layout: LayoutId) -> Option<WindowId>;
     fn contains_window(&self, layout: LayoutId, wid: WindowId) -> bool;
     fn select_window(&mut self, layout: LayoutId, wid: WindowId) -> bool;
+    /// Inserts `wid` after the selection and selects it.
     fn add_window_after_selection(&mut self, layout: LayoutId, wid: WindowId);
+    /// Inserts `wid` after the selection without selecting it, unless the
+    /// layout had no windows.
+    fn insert_window_after_selection(&mut self, layout: LayoutId, wid: WindowId);
     fn remove_window(&mut self, wid: WindowId);
     fn remove_windows_for_app(&mut self, pid: pid_t);
     fn set_windows_for_app(&mut self, layout: LayoutId, pid: pid_t, desired: Vec<WindowId>);
@@ -57,22 +244,50 @@ pub trait LayoutNavigation {
         direction: Direction,
     ) -> (Option<WindowId>, Vec<WindowId>);
     fn window_in_direction(&self, layout: LayoutId, direction: Direction) -> Option<WindowId>;
+    /// The window `move_focus` would focus, leaving the selection alone.
+    fn focus_target_in_direction(&self, layout: LayoutId, direction: Direction)
+    -> Option<WindowId>;
     fn move_selection(&mut self, layout: LayoutId, direction: Direction) -> bool;
     fn move_selection_to_layout_after_selection(
         &mut self,
         from_layout: LayoutId,
         to_layout: LayoutId,
     );
+    /// The child indices leading from the layout's root to its selection.
+    fn selection_path(&self, layout: LayoutId) -> Vec<usize>;
+    /// Selects the node reached by following `path` from the root. Returns
+    /// false, leaving the selection alone, if the path does not exist.
+    fn select_by_path(&mut self, layout: LayoutId, path: &[usize]) -> bool;
 }
 
 pub trait LayoutResizable {
     fn resize_selection_by(&mut self, layout: LayoutId, amount: f64);
+    /// Grows (positive) or shrinks the selection by `amount` of the screen
+    /// along `direction`'s axis only. The edge facing `direction` moves if
+    /// it has a neighbour, the opposite edge otherwise.
+    fn resize_selection_in_direction(
+        &mut self,
+        layout: LayoutId,
+        direction: Direction,
+        amount: f64,
+    );
     fn rebalance(&mut self, layout: LayoutId);
 }
 
 pub trait LayoutSplittable {
     fn split_selection(&mut self, layout: LayoutId, kind: LayoutKind);
     fn toggle_tile_orientation(&mut self, layout: LayoutId);
+    /// Flips the axis of every container under the selection's parent (or
+    /// the selection itself if it is a container), keeping sizes and
+    /// stacked-ness.
+    fn rotate_subtree(&mut self, layout: LayoutId);
+    /// Turns the whole layout a quarter turn: every container switches axis,
+    /// and those the turn runs backwards reverse their children, each child
+    /// keeping its share. The selection stays on the same window.
+    fn rotate_layout(&mut self, layout: LayoutId, clockwise: bool);
+    /// Reverses the children, and with them the shares, of every container
+    /// of the layout along `axis`.
+    fn mirror_layout(&mut self, layout: LayoutId, axis: Orientation);
     fn join_selection_with_direction(&mut self, layout: LayoutId, direction: Direction);
     fn unjoin_selection(&mut self, layout: LayoutId);
 }
@@ -89,6 +304,30 @@ pub trait LayoutStacking {
         default_orientation: crate::common::config::StackDefaultOrientation,
     ) -> Vec<WindowId>;
     fn parent_of_selection_is_stacked(&self, layout: LayoutId) -> bool;
+    fn stack_parent_of_selection_if_unstacked(
+        &mut self,
+        layout: LayoutId,
+        default_orientation: crate::common::config::StackDefaultOrientation,
+    ) -> Vec<WindowId>;
+    fn cycle_stack_of_parent_of_selection(&mut self, layout: LayoutId) -> Vec<WindowId>;
+    /// Windows hidden behind another member of a stack, at any depth.
+    fn occluded_windows(&self, layout: LayoutId) -> Vec<WindowId>;
+    /// Every stack visible in `layout`, outermost first, with the frame it is
+    /// laid out in. Each stack's `window_ids` and `selected_index` follow its
+    /// children in tree order, which is also the order they are drawn in:
+    /// left to right for horizontal stacks and top to bottom for vertical
+    /// ones. Activating `window_ids[i]` selects the `i`th member.
+    fn stacked_containers(
+        &self,
+        layout: LayoutId,
+        screen: CGRect,
+        stack_offset: f64,
+        focus_pop: f64,
+        gaps: &crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+        stack_line_horiz: crate::common::config::HorizontalPlacement,
+        stack_line_vert: crate::common::config::VerticalPlacement,
+    ) -> Vec<crate::layout_engine::engine::GroupContainerInfo>;
 }
 
 pub trait LayoutSwappable {
@@ -98,6 +337,9 @@ pub trait LayoutSwappable {
 pub trait LayoutFullscreen {
     fn toggle_fullscreen_of_selection(&mut self, layout: LayoutId) -> Vec<WindowId>;
     fn toggle_fullscreen_within_gaps_of_selection(&mut self, layout: LayoutId) -> Vec<WindowId>;
+    /// Windows shown fullscreen in `layout`, with whether they are fullscreen
+    /// within gaps.
+    fn fullscreen_windows(&self, layout: LayoutId) -> Vec<(WindowId, bool)>;
 }
 
 #[enum_dispatch]
@@ -105,22 +347,28 @@ pub trait LayoutSystem: Serialize + for<'de> Deserialize<'de> {
     fn create_layout(&mut self) -> LayoutId;
     fn clone_layout(&mut self, layout: LayoutId) -> LayoutId;
     fn remove_layout(&mut self, layout: LayoutId);
+    fn shutdown(&mut self);
 
     fn draw_tree(&self, layout: LayoutId) -> String;
+    fn draw_tree_with_options(&self, layout: LayoutId, options: &TreeRenderOptions) -> String;
 
     fn calculate_layout(
         &self,
         layout: LayoutId,
         screen: CGRect,
         stack_offset: f64,
+        focus_pop: f64,
         gaps: &crate::common::config::GapSettings,
         stack_line_thickness: f64,
         stack_line_horiz: crate::common::config::HorizontalPlacement,
         stack_line_vert: crate::common::config::VerticalPlacement,
+        is_positionable: Option<&dyn Fn(WindowId) -> bool>,
     ) -> Vec<(WindowId, CGRect)>;
 
+    fn layout_fingerprint(&self, layout: LayoutId) -> u64;
     fn selected_window(&self, layout: LayoutId) -> Option<WindowId>;
     fn visible_windows_in_layout(&self, layout: LayoutId) -> Vec<WindowId>;
+    fn nth_visible_window(&self, layout: LayoutId, n: usize) -> Option<WindowId>;
     fn visible_windows_under_selection(&self, layout: LayoutId) -> Vec<WindowId>;
     fn ascend_selection(&mut self, layout: LayoutId) -> bool;
     fn descend_selection(&mut self, layout: LayoutId) -> bool;
@@ -130,7 +378,10 @@ pub trait LayoutSystem: Serialize + for<'de> Deserialize<'de> {
         direction: Direction,
     ) -> (Option<WindowId>, Vec<WindowId>);
     fn window_in_direction(&self, layout: LayoutId, direction: Direction) -> Option<WindowId>;
+    fn focus_target_in_direction(&self, layout: LayoutId, direction: Direction)
+    -> Option<WindowId>;
     fn add_window_after_selection(&mut self, layout: LayoutId, wid: WindowId);
+    fn insert_window_after_selection(&mut self, layout: LayoutId, wid: WindowId);
     fn remove_window(&mut self, wid: WindowId);
     fn remove_windows_for_app(&mut self, pid: pid_t);
     fn set_windows_for_app(&mut self, layout: LayoutId, pid: pid_t, desired: Vec<WindowId>);
@@ -155,10 +406,13 @@ pub trait LayoutSystem: Serialize + for<'de> Deserialize<'de> {
         from_layout: LayoutId,
         to_layout: LayoutId,
     );
+    fn selection_path(&self, layout: LayoutId) -> Vec<usize>;
+    fn select_by_path(&mut self, layout: LayoutId, path: &[usize]) -> bool;
     fn split_selection(&mut self, layout: LayoutId, kind: LayoutKind);
 
     fn toggle_fullscreen_of_selection(&mut self, layout: LayoutId) -> Vec<WindowId>;
     fn toggle_fullscreen_within_gaps_of_selection(&mut self, layout: LayoutId) -> Vec<WindowId>;
+    fn fullscreen_windows(&self, layout: LayoutId) -> Vec<(WindowId, bool)>;
 
     fn join_selection_with_direction(&mut self, layout: LayoutId, direction: Direction);
     fn apply_stacking_to_parent_of_selection(
@@ -172,10 +426,37 @@ pub trait LayoutSystem: Serialize + for<'de> Deserialize<'de> {
         default_orientation: crate::common::config::StackDefaultOrientation,
     ) -> Vec<WindowId>;
     fn parent_of_selection_is_stacked(&self, layout: LayoutId) -> bool;
+    fn stack_parent_of_selection_if_unstacked(
+        &mut self,
+        layout: LayoutId,
+        default_orientation: crate::common::config::StackDefaultOrientation,
+    ) -> Vec<WindowId>;
+    fn cycle_stack_of_parent_of_selection(&mut self, layout: LayoutId) -> Vec<WindowId>;
+    fn occluded_windows(&self, layout: LayoutId) -> Vec<WindowId>;
+    fn stacked_containers(
+        &self,
+        layout: LayoutId,
+        screen: CGRect,
+        stack_offset: f64,
+        focus_pop: f64,
+        gaps: &crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+        stack_line_horiz: crate::common::config::HorizontalPlacement,
+        stack_line_vert: crate::common::config::VerticalPlacement,
+    ) -> Vec<crate::layout_engine::engine::GroupContainerInfo>;
     fn unjoin_selection(&mut self, _layout: LayoutId);
     fn resize_selection_by(&mut self, layout: LayoutId, amount: f64);
+    fn resize_selection_in_direction(
+        &mut self,
+        layout: LayoutId,
+        direction: Direction,
+        amount: f64,
+    );
     fn rebalance(&mut self, layout: LayoutId);
     fn toggle_tile_orientation(&mut self, layout: LayoutId);
+    fn rotate_subtree(&mut self, layout: LayoutId);
+    fn rotate_layout(&mut self, layout: LayoutId, clockwise: bool);
+    fn mirror_layout(&mut self, layout: LayoutId, axis: Orientation);
 }
 
 impl<
@@ -198,39 +479,55 @@ impl<
     fn remove_layout(&mut self, layout: LayoutId) {
         LayoutLifecycle::remove_layout(self, layout)
     }
+    fn shutdown(&mut self) {
+        LayoutLifecycle::shutdown(self)
+    }
 
     fn draw_tree(&self, layout: LayoutId) -> String {
         LayoutCore::draw_tree(self, layout)
     }
+    fn draw_tree_with_options(&self, layout: LayoutId, options: &TreeRenderOptions) -> String {
+        LayoutCore::draw_tree_with_options(self, layout, options)
+    }
 
     fn calculate_layout(
         &self,
         layout: LayoutId,
         screen: CGRect,
         stack_offset: f64,
+        focus_pop: f64,
         gaps: &crate::common::config::GapSettings,
         stack_line_thickness: f64,
         stack_line_horiz: crate::common::config::HorizontalPlacement,
         stack_line_vert: crate::common::config::VerticalPlacement,
+        is_positionable: Option<&dyn Fn(WindowId) -> bool>,
     ) -> Vec<(WindowId, CGRect)> {
         LayoutCore::calculate_layout(
             self,
             layout,
             screen,
             stack_offset,
+            focus_pop,
             gaps,
             stack_line_thickness,
             stack_line_horiz,
             stack_line_vert,
+            is_positionable,
         )
     }
 
+    fn layout_fingerprint(&self, layout: LayoutId) -> u64 {
+        LayoutCore::layout_fingerprint(self, layout)
+    }
     fn selected_window(&self, layout: LayoutId) -> Option<WindowId> {
         LayoutCore::selected_window(self, layout)
     }
     fn visible_windows_in_layout(&self, layout: LayoutId) -> Vec<WindowId> {
         LayoutCore::visible_windows_in_layout(self, layout)
     }
+    fn nth_visible_window(&self, layout: LayoutId, n: usize) -> Option<WindowId> {
+        LayoutCore::nth_visible_window(self, layout, n)
+    }
     fn visible_windows_under_selection(&self, layout: LayoutId) -> Vec<WindowId> {
         LayoutCore::visible_windows_under_selection(self, layout)
     }
@@ -250,9 +547,19 @@ impl<
     fn window_in_direction(&self, layout: LayoutId, direction: Direction) -> Option<WindowId> {
         LayoutNavigation::window_in_direction(self, layout, direction)
     }
+    fn focus_target_in_direction(
+        &self,
+        layout: LayoutId,
+        direction: Direction,
+    ) -> Option<WindowId> {
+        LayoutNavigation::focus_target_in_direction(self, layout, direction)
+    }
     fn add_window_after_selection(&mut self, layout: LayoutId, wid: WindowId) {
         LayoutCore::add_window_after_selection(self, layout, wid)
     }
+    fn insert_window_after_selection(&mut self, layout: LayoutId, wid: WindowId) {
+        LayoutCore::insert_window_after_selection(self, layout, wid)
+    }
     fn remove_window(&mut self, wid: WindowId) {
         LayoutCore::remove_window(self, wid)
     }
@@ -297,6 +604,12 @@ impl<
     ) {
         LayoutNavigation::move_selection_to_layout_after_selection(self, from_layout, to_layout)
     }
+    fn selection_path(&self, layout: LayoutId) -> Vec<usize> {
+        LayoutNavigation::selection_path(self, layout)
+    }
+    fn select_by_path(&mut self, layout: LayoutId, path: &[usize]) -> bool {
+        LayoutNavigation::select_by_path(self, layout, path)
+    }
     fn split_selection(&mut self, layout: LayoutId, kind: LayoutKind) {
         LayoutSplittable::split_selection(self, layout, kind)
     }
@@ -307,6 +620,9 @@ impl<
     fn toggle_fullscreen_within_gaps_of_selection(&mut self, layout: LayoutId) -> Vec<WindowId> {
         LayoutFullscreen::toggle_fullscreen_within_gaps_of_selection(self, layout)
     }
+    fn fullscreen_windows(&self, layout: LayoutId) -> Vec<(WindowId, bool)> {
+        LayoutFullscreen::fullscreen_windows(self, layout)
+    }
 
     fn join_selection_with_direction(&mut self, layout: LayoutId, direction: Direction) {
         LayoutSplittable::join_selection_with_direction(self, layout, direction)
@@ -328,18 +644,71 @@ impl<
     fn parent_of_selection_is_stacked(&self, layout: LayoutId) -> bool {
         LayoutStacking::parent_of_selection_is_stacked(self, layout)
     }
+    fn stack_parent_of_selection_if_unstacked(
+        &mut self,
+        layout: LayoutId,
+        default_orientation: crate::common::config::StackDefaultOrientation,
+    ) -> Vec<WindowId> {
+        LayoutStacking::stack_parent_of_selection_if_unstacked(self, layout, default_orientation)
+    }
+    fn cycle_stack_of_parent_of_selection(&mut self, layout: LayoutId) -> Vec<WindowId> {
+        LayoutStacking::cycle_stack_of_parent_of_selection(self, layout)
+    }
+    fn occluded_windows(&self, layout: LayoutId) -> Vec<WindowId> {
+        LayoutStacking::occluded_windows(self, layout)
+    }
+    fn stacked_containers(
+        &self,
+        layout: LayoutId,
+        screen: CGRect,
+        stack_offset: f64,
+        focus_pop: f64,
+        gaps: &crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+        stack_line_horiz: crate::common::config::HorizontalPlacement,
+        stack_line_vert: crate::common::config::VerticalPlacement,
+    ) -> Vec<crate::layout_engine::engine::GroupContainerInfo> {
+        LayoutStacking::stacked_containers(
+            self,
+            layout,
+            screen,
+            stack_offset,
+            focus_pop,
+            gaps,
+            stack_line_thickness,
+            stack_line_horiz,
+            stack_line_vert,
+        )
+    }
     fn unjoin_selection(&mut self, layout: LayoutId) {
         LayoutSplittable::unjoin_selection(self, layout)
     }
     fn resize_selection_by(&mut self, layout: LayoutId, amount: f64) {
         LayoutResizable::resize_selection_by(self, layout, amount)
     }
+    fn resize_selection_in_direction(
+        &mut self,
+        layout: LayoutId,
+        direction: Direction,
+        amount: f64,
+    ) {
+        LayoutResizable::resize_selection_in_direction(self, layout, direction, amount)
+    }
     fn rebalance(&mut self, layout: LayoutId) {
         LayoutResizable::rebalance(self, layout)
     }
     fn toggle_tile_orientation(&mut self, layout: LayoutId) {
         LayoutSplittable::toggle_tile_orientation(self, layout)
     }
+    fn rotate_subtree(&mut self, layout: LayoutId) {
+        LayoutSplittable::rotate_subtree(self, layout)
+    }
+    fn rotate_layout(&mut self, layout: LayoutId, clockwise: bool) {
+        LayoutSplittable::rotate_layout(self, layout, clockwise)
+    }
+    fn mirror_layout(&mut self, layout: LayoutId, axis: Orientation) {
+        LayoutSplittable::mirror_layout(self, layout, axis)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -1,11 +1,16 @@
 use objc2_core_foundation::{CGPoint, CGRect, CGSize};
 use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
 
 use crate::actor::app::{WindowId, pid_t};
 use crate::common::collections::{HashMap, HashSet};
+use crate::common::config::BspSettings;
+use crate::common::persistence::MapEntries;
 use crate::layout_engine::systems::{
     LayoutCore, LayoutFullscreen, LayoutLifecycle, LayoutNavigation, LayoutResizable,
-    LayoutSplittable, LayoutStacking, LayoutSwappable,
+    LayoutSplittable, LayoutStacking, LayoutSwappable, SplitKind, TreeRenderBudget,
+    TreeRenderOptions, collapsed_tree_summary, fingerprint_hasher, mirror_splits, quantize_share,
+    rotate_splits,
 };
 use crate::layout_engine::utils::compute_tiling_area;
 use crate::layout_engine::{Direction, LayoutId, LayoutKind, Orientation};
@@ -26,31 +31,144 @@ enum NodeKind {
     },
 }
 
+impl SplitKind for NodeKind {
+    fn split_orientation(&self) -> Option<Orientation> {
+        match self {
+            NodeKind::Split { orientation, .. } => Some(*orientation),
+            NodeKind::Leaf { .. } => None,
+        }
+    }
+
+    fn turn_split(&mut self) {
+        if let NodeKind::Split { orientation, .. } = self {
+            *orientation = match *orientation {
+                Orientation::Horizontal => Orientation::Vertical,
+                Orientation::Vertical => Orientation::Horizontal,
+            };
+        }
+    }
+
+    fn reverse_split_ratio(&mut self) {
+        if let NodeKind::Split { ratio, .. } = self {
+            *ratio = 1.0 - *ratio;
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy)]
 struct LayoutState {
     root: NodeId,
 }
 
+#[serde_as]
 #[derive(Serialize, Deserialize)]
 pub struct BspLayoutSystem {
     layouts: slotmap::SlotMap<LayoutId, LayoutState>,
     tree: Tree<Components>,
     kind: slotmap::SecondaryMap<NodeId, NodeKind>,
+    #[serde_as(as = "MapEntries")]
     window_to_node: HashMap<WindowId, NodeId>,
+    #[serde(default)]
+    settings: BspSettings,
 }
 
 impl Default for BspLayoutSystem {
     fn default() -> Self {
+        Self::new(BspSettings::default())
+    }
+}
+
+impl BspLayoutSystem {
+    pub fn new(settings: BspSettings) -> Self {
         Self {
             layouts: Default::default(),
             tree: Tree::with_observer(Components::default()),
             kind: Default::default(),
             window_to_node: Default::default(),
+            settings,
+        }
+    }
+
+    /// Moves the ratio of the closest split above the selection, or of the
+    /// closest one split along `axis` when given, so the selection grows by
+    /// `amount`.
+    fn resize_enclosing_split(&mut self, layout: LayoutId, amount: f64, axis: Option<Orientation>) {
+        let sel_snapshot = self.selection_of_layout(layout);
+        let Some(mut node) = sel_snapshot else {
+            return;
+        };
+        while let Some(parent) = node.parent(&self.tree.map) {
+            if let Some(NodeKind::Split { orientation, ratio, .. }) = self.kind.get_mut(parent)
+                && axis.is_none_or(|axis| axis == *orientation)
+            {
+                let is_first = Some(node) == parent.first_child(&self.tree.map);
+                let delta = (amount as f32) * 0.5;
+                if is_first {
+                    *ratio = (*ratio + delta).clamp(0.05, 0.95);
+                } else {
+                    *ratio = (*ratio - delta).clamp(0.05, 0.95);
+                }
+                break;
+            }
+            node = parent;
+        }
+    }
+
+    /// Sets every split ratio under `node` so that each leaf gets the same
+    /// area. Returns the number of leaves under `node`.
+    pub fn balance_subtree(&mut self, node: NodeId) -> usize {
+        if !matches!(self.kind.get(node), Some(NodeKind::Split { .. })) {
+            return 1;
+        }
+        let children: Vec<_> = node.children(&self.tree.map).collect();
+        let counts: Vec<_> = children.iter().map(|&child| self.balance_subtree(child)).collect();
+        self.set_ratio_from_counts(node, &counts);
+        counts.iter().sum()
+    }
+
+    /// The leaf the next `add_window_after_selection` would split, and the
+    /// orientation of that split, so an overlay can show where a window will
+    /// open. `None` if the window would fill an empty leaf instead.
+    pub fn bsp_insertion_preview(&self, layout: LayoutId) -> Option<(NodeId, Orientation)> {
+        let leaf = self.descend_to_leaf(self.selection_of_layout(layout)?);
+        match self.kind.get(leaf)? {
+            NodeKind::Leaf {
+                preselected: Some(direction), ..
+            } => Some((leaf, direction.orientation())),
+            NodeKind::Leaf { window: Some(_), .. } => Some((leaf, Orientation::Horizontal)),
+            _ => None,
+        }
+    }
+
+    /// Balances the subtree at `node`, then fixes the ratio of each of its
+    /// ancestors for the new leaf count without rebalancing their other side.
+    fn balance_around(&mut self, node: NodeId) {
+        self.balance_subtree(node);
+        let ancestors: Vec<_> = node.ancestors(&self.tree.map).skip(1).collect();
+        for ancestor in ancestors {
+            let counts: Vec<_> =
+                ancestor.children(&self.tree.map).map(|child| self.leaf_count(child)).collect();
+            self.set_ratio_from_counts(ancestor, &counts);
+        }
+    }
+
+    fn set_ratio_from_counts(&mut self, node: NodeId, counts: &[usize]) {
+        if let [first, second] = *counts
+            && let Some(NodeKind::Split { ratio, .. }) = self.kind.get_mut(node)
+        {
+            *ratio = first as f32 / (first + second) as f32;
+        }
+    }
+
+    fn leaf_count(&self, node: NodeId) -> usize {
+        match self.kind.get(node) {
+            Some(NodeKind::Split { .. }) => {
+                node.children(&self.tree.map).map(|child| self.leaf_count(child)).sum()
+            }
+            _ => 1,
         }
     }
-}
 
-impl BspLayoutSystem {
     fn find_neighbor_leaf(&self, from_leaf: NodeId, direction: Direction) -> Option<NodeId> {
         let mut current = from_leaf;
         while let Some(parent) = current.parent(&self.tree.map) {
@@ -296,6 +414,21 @@ impl BspLayoutSystem {
             .map(|s| self.tree.data.selection.current_selection(s.root))
     }
 
+    /// The leaf a directional focus move from the selection lands on, with its window.
+    fn focus_target_leaf(
+        &self,
+        layout: LayoutId,
+        direction: Direction,
+    ) -> Option<(NodeId, Option<WindowId>)> {
+        let current_leaf = self.descend_to_leaf(self.selection_of_layout(layout)?);
+        let next_leaf = self.find_neighbor_leaf(current_leaf, direction)?;
+        let window = match self.kind.get(next_leaf) {
+            Some(NodeKind::Leaf { window, .. }) => *window,
+            _ => None,
+        };
+        Some((next_leaf, window))
+    }
+
     fn insert_window_at_selection(&mut self, layout: LayoutId, wid: WindowId) {
         let Some(state) = self.layouts.get(layout).copied() else {
             return;
@@ -354,6 +487,9 @@ impl BspLayoutSystem {
             }
             self.window_to_node.remove(&wid);
             let fallback = self.cleanup_after_removal(node_id);
+            if self.settings.auto_balance {
+                self.balance_around(fallback);
+            }
             let sel_snapshot = self
                 .layouts
                 .get(layout)
@@ -393,8 +529,8 @@ impl BspLayoutSystem {
             Some(NodeKind::Leaf { window: None, .. }) => {}
             Some(NodeKind::Split { orientation, ratio }) => match orientation {
                 Orientation::Horizontal => {
-                    let gap = gaps.inner.horizontal;
                     let total = rect.size.width;
+                    let gap = gaps.inner_gap(true, total, 2);
                     let available = (total - gap).max(0.0);
                     let first_w_f = available * (*ratio as f64);
                     let first_w = first_w_f.max(0.0);
@@ -413,8 +549,8 @@ impl BspLayoutSystem {
                     }
                 }
                 Orientation::Vertical => {
-                    let gap = gaps.inner.vertical;
                     let total = rect.size.height;
+                    let gap = gaps.inner_gap(false, total, 2);
                     let available = (total - gap).max(0.0);
                     let first_h_f = available * (*ratio as f64);
                     let first_h = first_h_f.max(0.0);
@@ -593,11 +729,35 @@ impl LayoutLifecycle for BspLayoutSystem {
             state.root.remove_root(&mut self.tree);
         }
     }
+
+    fn shutdown(&mut self) {
+        let layouts: Vec<_> = self.layouts.keys().collect();
+        for layout in layouts {
+            self.remove_layout(layout);
+        }
+        debug_assert!(
+            self.tree.map.is_empty(),
+            "{} nodes outlived their layouts",
+            self.tree.map.len()
+        );
+    }
 }
 
 impl LayoutCore for BspLayoutSystem {
     fn draw_tree(&self, layout: LayoutId) -> String {
-        fn write_node(this: &BspLayoutSystem, node: NodeId, out: &mut String, indent: usize) {
+        self.draw_tree_with_options(layout, &TreeRenderOptions::default())
+    }
+
+    fn draw_tree_with_options(&self, layout: LayoutId, options: &TreeRenderOptions) -> String {
+        fn write_node(
+            this: &BspLayoutSystem,
+            node: NodeId,
+            out: &mut String,
+            indent: usize,
+            options: &TreeRenderOptions,
+            focus_path: &[NodeId],
+            budget: &mut TreeRenderBudget,
+        ) {
             for _ in 0..indent {
                 out.push_str("  ");
             }
@@ -607,41 +767,90 @@ impl LayoutCore for BspLayoutSystem {
                 }
                 Some(NodeKind::Split { orientation, ratio }) => {
                     out.push_str(&format!("Split {:?} {:.2}\n", orientation, ratio));
-                    let mut it = node.children(&this.tree.map);
-                    if let Some(first) = it.next() {
-                        write_node(this, first, out, indent + 1);
+                    if !options.should_expand(indent, focus_path.contains(&node)) {
+                        for _ in 0..=indent {
+                            out.push_str("  ");
+                        }
+                        let mut windows = Vec::new();
+                        this.collect_windows_under(node, &mut windows);
+                        out.push_str(&collapsed_tree_summary(windows.len()));
+                        out.push('\n');
+                        return;
                     }
-                    if let Some(second) = it.next() {
-                        write_node(this, second, out, indent + 1);
+                    for child in node.children(&this.tree.map).take(2) {
+                        if !budget.take() {
+                            return;
+                        }
+                        write_node(this, child, out, indent + 1, options, focus_path, budget);
                     }
                 }
                 None => {}
             }
         }
         if let Some(state) = self.layouts.get(layout).copied() {
+            let selection = self.tree.data.selection.current_selection(state.root);
+            let focus_path: Vec<NodeId> = selection.ancestors(&self.tree.map).collect();
+            let mut budget = TreeRenderBudget::new(options);
             let mut s = String::new();
-            write_node(self, state.root, &mut s, 0);
+            if budget.take() {
+                write_node(self, state.root, &mut s, 0, options, &focus_path, &mut budget);
+            }
+            budget.finish(options, &mut s);
             s
         } else {
             "<empty bsp>".to_string()
         }
     }
 
+    fn layout_fingerprint(&self, layout: LayoutId) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = fingerprint_hasher();
+        if let Some(state) = self.layouts.get(layout) {
+            for node in state.root.traverse_preorder(&self.tree.map) {
+                match self.kind.get(node) {
+                    Some(NodeKind::Split { orientation, ratio }) => {
+                        0u8.hash(&mut hasher);
+                        orientation.hash(&mut hasher);
+                        quantize_share(*ratio).hash(&mut hasher);
+                    }
+                    Some(NodeKind::Leaf {
+                        window,
+                        fullscreen,
+                        fullscreen_within_gaps,
+                        ..
+                    }) => {
+                        1u8.hash(&mut hasher);
+                        window.hash(&mut hasher);
+                        fullscreen.hash(&mut hasher);
+                        fullscreen_within_gaps.hash(&mut hasher);
+                    }
+                    None => 2u8.hash(&mut hasher),
+                }
+            }
+        }
+        hasher.finish()
+    }
+
     fn calculate_layout(
         &self,
         layout: LayoutId,
         screen: CGRect,
         _stack_offset: f64,
+        _focus_pop: f64,
         gaps: &crate::common::config::GapSettings,
         _stack_line_thickness: f64,
         _stack_line_horiz: crate::common::config::HorizontalPlacement,
         _stack_line_vert: crate::common::config::VerticalPlacement,
+        is_positionable: Option<&dyn Fn(WindowId) -> bool>,
     ) -> Vec<(WindowId, CGRect)> {
         let mut out = Vec::new();
         if let Some(state) = self.layouts.get(layout).copied() {
             let rect = Self::apply_outer_gaps(screen, gaps);
             self.calculate_layout_recursive(state.root, rect, screen, gaps, &mut out);
         }
+        if let Some(is_positionable) = is_positionable {
+            out.retain(|&(wid, _)| is_positionable(wid));
+        }
         out
     }
 
@@ -697,6 +906,20 @@ impl LayoutCore for BspLayoutSystem {
         if self.layouts.get(layout).is_some() && !self.smart_insert_window(layout, wid) {
             self.insert_window_at_selection(layout, wid);
         }
+        if self.settings.auto_balance
+            && let Some(split) =
+                self.window_to_node.get(&wid).and_then(|node| node.parent(&self.tree.map))
+        {
+            self.balance_around(split);
+        }
+    }
+
+    fn insert_window_after_selection(&mut self, layout: LayoutId, wid: WindowId) {
+        let previous = self.selected_window(layout);
+        self.add_window_after_selection(layout, wid);
+        if let Some(previous) = previous {
+            self.select_window(layout, previous);
+        }
     }
 
     fn remove_window(&mut self, wid: WindowId) {
@@ -749,7 +972,7 @@ impl LayoutCore for BspLayoutSystem {
         }
         for w in desired {
             if !current_set.contains(&w) {
-                self.add_window_after_selection(layout, w);
+                self.insert_window_after_selection(layout, w);
             }
         }
     }
@@ -843,19 +1066,10 @@ impl LayoutNavigation for BspLayoutSystem {
         if raise_windows.is_empty() {
             return (None, vec![]);
         }
-        let sel_snapshot = self.selection_of_layout(layout);
-        let Some(current_sel) = sel_snapshot else {
-            return (None, vec![]);
-        };
-        let current_leaf = self.descend_to_leaf(current_sel);
-        let Some(next_leaf) = self.find_neighbor_leaf(current_leaf, direction) else {
+        let Some((next_leaf, focus)) = self.focus_target_leaf(layout, direction) else {
             return (None, vec![]);
         };
         self.tree.data.selection.select(&self.tree.map, next_leaf);
-        let focus = match self.kind.get(next_leaf) {
-            Some(NodeKind::Leaf { window, .. }) => *window,
-            _ => None,
-        };
         (focus, raise_windows)
     }
 
@@ -865,6 +1079,14 @@ impl LayoutNavigation for BspLayoutSystem {
             .and_then(|state| self.window_in_direction_from(state.root, direction))
     }
 
+    fn focus_target_in_direction(
+        &self,
+        layout: LayoutId,
+        direction: Direction,
+    ) -> Option<WindowId> {
+        self.focus_target_leaf(layout, direction)?.1
+    }
+
     fn move_selection(&mut self, layout: LayoutId, direction: Direction) -> bool {
         let sel_snapshot = self.selection_of_layout(layout);
         let Some(sel) = sel_snapshot else {
@@ -911,27 +1133,39 @@ impl LayoutNavigation for BspLayoutSystem {
             self.add_window_after_selection(to_layout, w);
         }
     }
+
+    fn selection_path(&self, layout: LayoutId) -> Vec<usize> {
+        let (Some(state), Some(sel)) = (self.layouts.get(layout), self.selection_of_layout(layout))
+        else {
+            return Vec::new();
+        };
+        sel.path_from(state.root, &self.tree.map).unwrap_or_default()
+    }
+
+    fn select_by_path(&mut self, layout: LayoutId, path: &[usize]) -> bool {
+        let Some(state) = self.layouts.get(layout) else {
+            return false;
+        };
+        let Some(node) = state.root.descendant_at(path, &self.tree.map) else {
+            return false;
+        };
+        self.tree.data.selection.select(&self.tree.map, node);
+        true
+    }
 }
 
 impl LayoutResizable for BspLayoutSystem {
     fn resize_selection_by(&mut self, layout: LayoutId, amount: f64) {
-        let sel_snapshot = self.selection_of_layout(layout);
-        let Some(mut node) = sel_snapshot else {
-            return;
-        };
-        while let Some(parent) = node.parent(&self.tree.map) {
-            if let Some(NodeKind::Split { ratio, .. }) = self.kind.get_mut(parent) {
-                let is_first = Some(node) == parent.first_child(&self.tree.map);
-                let delta = (amount as f32) * 0.5;
-                if is_first {
-                    *ratio = (*ratio + delta).clamp(0.05, 0.95);
-                } else {
-                    *ratio = (*ratio - delta).clamp(0.05, 0.95);
-                }
-                break;
-            }
-            node = parent;
-        }
+        self.resize_enclosing_split(layout, amount, None);
+    }
+
+    fn resize_selection_in_direction(
+        &mut self,
+        layout: LayoutId,
+        direction: Direction,
+        amount: f64,
+    ) {
+        self.resize_enclosing_split(layout, amount, Some(direction.orientation()));
     }
 
     fn rebalance(&mut self, _layout: LayoutId) {}
@@ -996,6 +1230,36 @@ impl LayoutSplittable for BspLayoutSystem {
         }
     }
 
+    fn rotate_subtree(&mut self, layout: LayoutId) {
+        let Some(state) = self.layouts.get(layout) else {
+            return;
+        };
+        let root = state.root;
+        let start = match self.selection_of_layout(layout) {
+            Some(sel) if matches!(self.kind.get(sel), Some(NodeKind::Split { .. })) => sel,
+            Some(sel) => sel.parent(&self.tree.map).unwrap_or(root),
+            None => root,
+        };
+        let nodes: Vec<_> = start.traverse_preorder(&self.tree.map).collect();
+        for node in nodes {
+            if let Some(split) = self.kind.get_mut(node) {
+                split.turn_split();
+            }
+        }
+    }
+
+    fn rotate_layout(&mut self, layout: LayoutId, clockwise: bool) {
+        if let Some(state) = self.layouts.get(layout) {
+            rotate_splits(&mut self.tree, &mut self.kind, state.root, clockwise);
+        }
+    }
+
+    fn mirror_layout(&mut self, layout: LayoutId, axis: Orientation) {
+        if let Some(state) = self.layouts.get(layout) {
+            mirror_splits(&mut self.tree, &mut self.kind, state.root, axis);
+        }
+    }
+
     fn join_selection_with_direction(&mut self, layout: LayoutId, direction: Direction) {
         let Some(sel) = self.selection_of_layout(layout) else {
             return;
@@ -1083,6 +1347,33 @@ impl LayoutStacking for BspLayoutSystem {
     fn parent_of_selection_is_stacked(&self, _: LayoutId) -> bool {
         false
     }
+    fn stack_parent_of_selection_if_unstacked(
+        &mut self,
+        _: LayoutId,
+        _: crate::common::config::StackDefaultOrientation,
+    ) -> Vec<WindowId> {
+        vec![]
+    }
+    fn cycle_stack_of_parent_of_selection(&mut self, _: LayoutId) -> Vec<WindowId> {
+        vec![]
+    }
+    fn occluded_windows(&self, _: LayoutId) -> Vec<WindowId> {
+        vec![]
+    }
+
+    fn stacked_containers(
+        &self,
+        _: LayoutId,
+        _: CGRect,
+        _: f64,
+        _: f64,
+        _: &crate::common::config::GapSettings,
+        _: f64,
+        _: crate::common::config::HorizontalPlacement,
+        _: crate::common::config::VerticalPlacement,
+    ) -> Vec<crate::layout_engine::engine::GroupContainerInfo> {
+        vec![]
+    }
 }
 
 impl LayoutSwappable for BspLayoutSystem {
@@ -1170,6 +1461,25 @@ impl LayoutFullscreen for BspLayoutSystem {
         }
         vec![]
     }
+
+    fn fullscreen_windows(&self, layout: LayoutId) -> Vec<(WindowId, bool)> {
+        let Some(state) = self.layouts.get(layout) else {
+            return vec![];
+        };
+        state
+            .root
+            .traverse_preorder(&self.tree.map)
+            .filter_map(|node| match self.kind.get(node) {
+                Some(NodeKind::Leaf {
+                    window: Some(w),
+                    fullscreen,
+                    fullscreen_within_gaps,
+                    ..
+                }) if *fullscreen || *fullscreen_within_gaps => Some((*w, *fullscreen_within_gaps)),
+                _ => None,
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -1250,6 +1560,49 @@ mod tests {
             assert_eq!(system.selected_window(layout), Some(w(1, 3)));
         }
 
+        #[test]
+        fn insertion_preview_matches_the_split_made() {
+            let mut system = BspLayoutSystem::default();
+            let layout = system.create_layout();
+            for i in 1..=3 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+            let node = |system: &BspLayoutSystem, wid| system.window_to_node[&wid];
+
+            for wid in [w(1, 1), w(1, 3)] {
+                system.select_window(layout, wid);
+                assert_eq!(
+                    system.bsp_insertion_preview(layout),
+                    Some((node(&system, wid), Orientation::Horizontal))
+                );
+            }
+
+            system.select_window(layout, w(1, 2));
+            let leaf = node(&system, w(1, 2));
+            if let Some(NodeKind::Leaf { preselected, .. }) = system.kind.get_mut(leaf) {
+                *preselected = Some(Direction::Down);
+            }
+            let before = system.draw_tree(layout);
+            assert_eq!(
+                system.bsp_insertion_preview(layout),
+                Some((leaf, Orientation::Vertical))
+            );
+            assert_eq!(system.draw_tree(layout), before);
+
+            system.add_window_after_selection(layout, w(1, 4));
+            assert!(matches!(
+                system.kind.get(leaf),
+                Some(NodeKind::Split {
+                    orientation: Orientation::Vertical,
+                    ..
+                })
+            ));
+            assert_eq!(node(&system, w(1, 4)).parent(&system.tree.map), Some(leaf));
+
+            system.split_selection(layout, LayoutKind::Horizontal);
+            assert_eq!(system.bsp_insertion_preview(layout), None);
+        }
+
         #[test]
         fn add_duplicate_window_id_is_allowed() {
             let mut system = BspLayoutSystem::default();
@@ -1278,6 +1631,44 @@ mod tests {
         }
     }
 
+    mod tree_rendering {
+        use super::*;
+
+        #[test]
+        fn max_depth_summarizes_subtrees() {
+            let mut system = BspLayoutSystem::default();
+            let layout = system.create_layout();
+            for i in 1..=4 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+
+            let options = TreeRenderOptions {
+                max_depth: Some(0),
+                ..Default::default()
+            };
+            let out = system.draw_tree_with_options(layout, &options);
+            assert_eq!(out.lines().count(), 2, "{out}");
+            assert_eq!(out.lines().nth(1), Some("  … 4 windows"));
+        }
+
+        #[test]
+        fn max_nodes_truncates_with_notice() {
+            let mut system = BspLayoutSystem::default();
+            let layout = system.create_layout();
+            for i in 1..=4 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+
+            let options = TreeRenderOptions {
+                max_nodes: Some(1),
+                ..Default::default()
+            };
+            let out = system.draw_tree_with_options(layout, &options);
+            assert_eq!(out.lines().count(), 2, "{out}");
+            assert!(out.ends_with("… truncated after 1 nodes\n"), "{out}");
+        }
+    }
+
     mod window_removal {
         use super::*;
 
@@ -1503,10 +1894,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             assert!(result.is_empty());
@@ -1522,10 +1915,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             assert_eq!(result.len(), 1);
@@ -1541,10 +1936,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             for (_, rect) in &result {
@@ -1572,10 +1969,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps,
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             for (_, rect) in &result {
@@ -1585,6 +1984,73 @@ mod tests {
         }
     }
 
+    mod auto_balance {
+        use super::*;
+        use crate::common::config::BspSettings;
+
+        fn areas(system: &BspLayoutSystem, layout: LayoutId) -> Vec<f64> {
+            system
+                .calculate_layout(
+                    layout,
+                    screen(),
+                    0.0,
+                    0.0,
+                    &gaps(),
+                    0.0,
+                    crate::common::config::HorizontalPlacement::Top,
+                    crate::common::config::VerticalPlacement::Left,
+                    None,
+                )
+                .into_iter()
+                .map(|(_, frame)| frame.size.width * frame.size.height)
+                .collect()
+        }
+
+        fn assert_even(areas: &[f64]) {
+            let mean = areas.iter().sum::<f64>() / areas.len() as f64;
+            for area in areas {
+                assert!((area - mean).abs() < mean * 0.01, "{areas:?}");
+            }
+        }
+
+        #[test]
+        fn adds_and_removes_keep_areas_even() {
+            let mut system = BspLayoutSystem::new(BspSettings { auto_balance: true });
+            let layout = system.create_layout();
+            for i in 1..=4 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+            assert_even(&areas(&system, layout));
+
+            system.select_window(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 5));
+            system.add_window_after_selection(layout, w(1, 6));
+            assert_even(&areas(&system, layout));
+
+            system.remove_window(w(1, 3));
+            system.remove_window(w(1, 5));
+            system.add_window_after_selection(layout, w(1, 7));
+            let areas = areas(&system, layout);
+            assert_eq!(areas.len(), 5);
+            assert_even(&areas);
+        }
+
+        #[test]
+        fn off_by_default_and_available_on_demand() {
+            let mut system = BspLayoutSystem::default();
+            let layout = system.create_layout();
+            for i in 1..=3 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+            let uneven = areas(&system, layout);
+            assert!(uneven[0] > uneven[2] * 1.5, "{uneven:?}");
+
+            let root = system.layouts[layout].root;
+            assert_eq!(system.balance_subtree(root), 3);
+            assert_even(&areas(&system, layout));
+        }
+    }
+
     mod layout_clone {
         use super::*;
 
@@ -1725,6 +2191,134 @@ mod tests {
 
             assert_ne!(before, after);
         }
+
+        #[test]
+        fn rotate_subtree_flips_every_split() {
+            let mut system = BspLayoutSystem::default();
+            let layout = system.create_layout();
+            for i in 1..=4 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+            system.toggle_tile_orientation(layout);
+            system.select_window(layout, w(1, 1));
+            let before = system.draw_tree(layout);
+            assert_eq!(before.matches("Split").count(), 3);
+
+            system.rotate_subtree(layout);
+            let expected = before
+                .replace("Horizontal", "\0")
+                .replace("Vertical", "Horizontal")
+                .replace('\0', "Vertical");
+            assert_eq!(system.draw_tree(layout), expected);
+
+            system.rotate_subtree(layout);
+            assert_eq!(system.draw_tree(layout), before);
+        }
+    }
+
+    mod rotate_and_mirror {
+        use super::*;
+
+        /// w1 over w3 on the left, w2 over w4 on the right.
+        fn grid() -> (BspLayoutSystem, LayoutId) {
+            let mut system = BspLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            for (top, bottom) in [(w(1, 1), w(1, 3)), (w(1, 2), w(1, 4))] {
+                system.select_window(layout, top);
+                system.split_selection(layout, LayoutKind::Vertical);
+                system.add_window_after_selection(layout, bottom);
+            }
+            (system, layout)
+        }
+
+        fn frames(system: &BspLayoutSystem, layout: LayoutId) -> HashMap<WindowId, CGRect> {
+            system
+                .calculate_layout(
+                    layout,
+                    screen(),
+                    0.0,
+                    0.0,
+                    &gaps(),
+                    0.0,
+                    crate::common::config::HorizontalPlacement::Top,
+                    crate::common::config::VerticalPlacement::Left,
+                    None,
+                )
+                .into_iter()
+                .collect()
+        }
+
+        #[test]
+        fn mirroring_reverses_sides_and_ratios_along_the_axis() {
+            let mut system = BspLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            let root = system.layouts[layout].root;
+            if let Some(NodeKind::Split { ratio, .. }) = system.kind.get_mut(root) {
+                *ratio = 0.7;
+            }
+            let tree = |first, second, ratio| {
+                format!(
+                    "Split Horizontal {ratio}\n  Leaf {:?}\n  Leaf {:?}\n",
+                    Some(first),
+                    Some(second)
+                )
+            };
+
+            system.mirror_layout(layout, Orientation::Vertical);
+            assert_eq!(system.draw_tree(layout), tree(w(1, 1), w(1, 2), "0.70"));
+
+            system.mirror_layout(layout, Orientation::Horizontal);
+            assert_eq!(system.draw_tree(layout), tree(w(1, 2), w(1, 1), "0.30"));
+            assert_eq!(system.selected_window(layout), Some(w(1, 2)));
+        }
+
+        #[test]
+        fn rotating_flips_every_split_and_reverses_the_turned_ones() {
+            let (mut system, layout) = grid();
+            let leaf = |wid| format!("Leaf {:?}", Some(wid));
+            let expected = [
+                "Split Vertical 0.50".to_string(),
+                "  Split Horizontal 0.50".to_string(),
+                format!("    {}", leaf(w(1, 3))),
+                format!("    {}", leaf(w(1, 1))),
+                "  Split Horizontal 0.50".to_string(),
+                format!("    {}", leaf(w(1, 4))),
+                format!("    {}", leaf(w(1, 2))),
+            ];
+
+            system.rotate_layout(layout, true);
+            assert_eq!(system.draw_tree(layout), expected.join("\n") + "\n");
+            assert_eq!(system.selected_window(layout), Some(w(1, 4)));
+
+            system.rotate_layout(layout, false);
+            let (unrotated, unrotated_layout) = grid();
+            assert_eq!(system.draw_tree(layout), unrotated.draw_tree(unrotated_layout));
+        }
+
+        #[test]
+        fn clockwise_rotation_moves_each_window_one_quadrant_on() {
+            let (mut system, layout) = grid();
+            let before = frames(&system, layout);
+
+            system.rotate_layout(layout, true);
+            let after = frames(&system, layout);
+            // Top left goes to top right, top right to bottom right, and so on.
+            for (from, to) in [
+                (w(1, 1), w(1, 2)),
+                (w(1, 2), w(1, 4)),
+                (w(1, 4), w(1, 3)),
+                (w(1, 3), w(1, 1)),
+            ] {
+                assert_eq!(
+                    after[&from], before[&to],
+                    "{from:?} should take {to:?}'s quadrant"
+                );
+            }
+        }
     }
 
     mod window_swap {
@@ -1826,10 +2420,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             system.resize_selection_by(layout, 0.1);
@@ -1838,10 +2434,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             assert_eq!(initial.len(), after.len());
@@ -1862,10 +2460,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             assert_eq!(result.len(), 2);
@@ -1887,10 +2487,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             assert_eq!(result.len(), 2);
@@ -1912,10 +2514,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             assert_eq!(result.len(), 2);
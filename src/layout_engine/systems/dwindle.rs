@@ -10,13 +10,17 @@
 
 use objc2_core_foundation::{CGPoint, CGRect, CGSize};
 use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
 
 use crate::actor::app::{WindowId, pid_t};
 use crate::common::collections::{HashMap, HashSet};
 use crate::common::config::{DwindleForceSplit, DwindleSettings};
+use crate::common::persistence::MapEntries;
 use crate::layout_engine::systems::{
     LayoutCore, LayoutFullscreen, LayoutLifecycle, LayoutNavigation, LayoutResizable,
-    LayoutSplittable, LayoutStacking, LayoutSwappable,
+    LayoutSplittable, LayoutStacking, LayoutSwappable, SplitKind, TreeRenderBudget,
+    TreeRenderOptions, collapsed_tree_summary, fingerprint_hasher, mirror_splits, quantize_share,
+    rotate_splits,
 };
 use crate::layout_engine::utils::compute_tiling_area;
 use crate::layout_engine::{Direction, LayoutId, LayoutKind, Orientation};
@@ -39,16 +43,46 @@ enum NodeKind {
     },
 }
 
+impl SplitKind for NodeKind {
+    fn split_orientation(&self) -> Option<Orientation> {
+        match self {
+            NodeKind::Split { orientation, .. } => Some(*orientation),
+            NodeKind::Leaf { .. } => None,
+        }
+    }
+
+    // Turned and mirrored splits are marked preserved so rebalancing keeps
+    // the axes and sides the user chose.
+    fn turn_split(&mut self) {
+        if let NodeKind::Split { orientation, preserved, .. } = self {
+            *orientation = match *orientation {
+                Orientation::Horizontal => Orientation::Vertical,
+                Orientation::Vertical => Orientation::Horizontal,
+            };
+            *preserved = true;
+        }
+    }
+
+    fn reverse_split_ratio(&mut self) {
+        if let NodeKind::Split { ratio, preserved, .. } = self {
+            *ratio = 1.0 - *ratio;
+            *preserved = true;
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy)]
 struct LayoutState {
     root: NodeId,
 }
 
+#[serde_as]
 #[derive(Serialize, Deserialize)]
 pub struct DwindleLayoutSystem {
     layouts: slotmap::SlotMap<LayoutId, LayoutState>,
     tree: Tree<Components>,
     kind: slotmap::SecondaryMap<NodeId, NodeKind>,
+    #[serde_as(as = "MapEntries")]
     window_to_node: HashMap<WindowId, NodeId>,
     #[serde(default)]
     settings: DwindleSettings,
@@ -74,6 +108,37 @@ impl DwindleLayoutSystem {
         }
     }
 
+    /// Moves the ratio of the closest split above the selection, or of the
+    /// closest one split along `axis` when given, so the selection grows by
+    /// `amount`.
+    fn resize_enclosing_split(&mut self, layout: LayoutId, amount: f64, axis: Option<Orientation>) {
+        let sel_snapshot = self.selection_of_layout(layout);
+        let Some(mut node) = sel_snapshot else {
+            return;
+        };
+        while let Some(parent) = node.parent(&self.tree.map) {
+            if let Some(NodeKind::Split {
+                orientation, ratio, preserved, ..
+            }) = self.kind.get_mut(parent)
+                && axis.is_none_or(|axis| axis == *orientation)
+            {
+                let is_first = Some(node) == parent.first_child(&self.tree.map);
+                let delta = (amount as f32) * 0.5;
+                if is_first {
+                    *ratio = (*ratio + delta).clamp(0.05, 0.95);
+                } else {
+                    *ratio = (*ratio - delta).clamp(0.05, 0.95);
+                }
+                // Mark as preserved when user manually resizes
+                if self.settings.preserve_split {
+                    *preserved = true;
+                }
+                break;
+            }
+            node = parent;
+        }
+    }
+
     /// Determine orientation based on area dimensions (core dwindle algorithm)
     fn determine_orientation(&self, rect: CGRect) -> Orientation {
         match self.settings.force_split {
@@ -365,6 +430,21 @@ impl DwindleLayoutSystem {
             .map(|s| self.tree.data.selection.current_selection(s.root))
     }
 
+    /// The leaf a directional focus move from the selection lands on, with its window.
+    fn focus_target_leaf(
+        &self,
+        layout: LayoutId,
+        direction: Direction,
+    ) -> Option<(NodeId, Option<WindowId>)> {
+        let current_leaf = self.descend_to_leaf(self.selection_of_layout(layout)?);
+        let next_leaf = self.find_neighbor_leaf(current_leaf, direction)?;
+        let window = match self.kind.get(next_leaf) {
+            Some(NodeKind::Leaf { window, .. }) => *window,
+            _ => None,
+        };
+        Some((next_leaf, window))
+    }
+
     /// Insert window using dwindle algorithm - orientation determined by rect dimensions
     fn insert_window_at_selection_with_rect(
         &mut self,
@@ -529,8 +609,8 @@ impl DwindleLayoutSystem {
             Some(NodeKind::Leaf { window: None, .. }) => {}
             Some(NodeKind::Split { orientation, ratio, .. }) => match orientation {
                 Orientation::Horizontal => {
-                    let gap = gaps.inner.horizontal;
                     let total = rect.size.width;
+                    let gap = gaps.inner_gap(true, total, 2);
                     let available = (total - gap).max(0.0);
                     let first_w_f = available * (*ratio as f64);
                     let first_w = first_w_f.max(0.0);
@@ -549,8 +629,8 @@ impl DwindleLayoutSystem {
                     }
                 }
                 Orientation::Vertical => {
-                    let gap = gaps.inner.vertical;
                     let total = rect.size.height;
+                    let gap = gaps.inner_gap(false, total, 2);
                     let available = (total - gap).max(0.0);
                     let first_h_f = available * (*ratio as f64);
                     let first_h = first_h_f.max(0.0);
@@ -733,11 +813,35 @@ impl LayoutLifecycle for DwindleLayoutSystem {
             state.root.remove_root(&mut self.tree);
         }
     }
+
+    fn shutdown(&mut self) {
+        let layouts: Vec<_> = self.layouts.keys().collect();
+        for layout in layouts {
+            self.remove_layout(layout);
+        }
+        debug_assert!(
+            self.tree.map.is_empty(),
+            "{} nodes outlived their layouts",
+            self.tree.map.len()
+        );
+    }
 }
 
 impl LayoutCore for DwindleLayoutSystem {
     fn draw_tree(&self, layout: LayoutId) -> String {
-        fn write_node(this: &DwindleLayoutSystem, node: NodeId, out: &mut String, indent: usize) {
+        self.draw_tree_with_options(layout, &TreeRenderOptions::default())
+    }
+
+    fn draw_tree_with_options(&self, layout: LayoutId, options: &TreeRenderOptions) -> String {
+        fn write_node(
+            this: &DwindleLayoutSystem,
+            node: NodeId,
+            out: &mut String,
+            indent: usize,
+            options: &TreeRenderOptions,
+            focus_path: &[NodeId],
+            budget: &mut TreeRenderBudget,
+        ) {
             for _ in 0..indent {
                 out.push_str("  ");
             }
@@ -752,41 +856,90 @@ impl LayoutCore for DwindleLayoutSystem {
                         ratio,
                         if *preserved { " [preserved]" } else { "" }
                     ));
-                    let mut it = node.children(&this.tree.map);
-                    if let Some(first) = it.next() {
-                        write_node(this, first, out, indent + 1);
+                    if !options.should_expand(indent, focus_path.contains(&node)) {
+                        for _ in 0..=indent {
+                            out.push_str("  ");
+                        }
+                        let mut windows = Vec::new();
+                        this.collect_windows_under(node, &mut windows);
+                        out.push_str(&collapsed_tree_summary(windows.len()));
+                        out.push('\n');
+                        return;
                     }
-                    if let Some(second) = it.next() {
-                        write_node(this, second, out, indent + 1);
+                    for child in node.children(&this.tree.map).take(2) {
+                        if !budget.take() {
+                            return;
+                        }
+                        write_node(this, child, out, indent + 1, options, focus_path, budget);
                     }
                 }
                 None => {}
             }
         }
         if let Some(state) = self.layouts.get(layout).copied() {
+            let selection = self.tree.data.selection.current_selection(state.root);
+            let focus_path: Vec<NodeId> = selection.ancestors(&self.tree.map).collect();
+            let mut budget = TreeRenderBudget::new(options);
             let mut s = String::new();
-            write_node(self, state.root, &mut s, 0);
+            if budget.take() {
+                write_node(self, state.root, &mut s, 0, options, &focus_path, &mut budget);
+            }
+            budget.finish(options, &mut s);
             s
         } else {
             "<empty dwindle>".to_string()
         }
     }
 
+    fn layout_fingerprint(&self, layout: LayoutId) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = fingerprint_hasher();
+        if let Some(state) = self.layouts.get(layout) {
+            for node in state.root.traverse_preorder(&self.tree.map) {
+                match self.kind.get(node) {
+                    Some(NodeKind::Split { orientation, ratio, .. }) => {
+                        0u8.hash(&mut hasher);
+                        orientation.hash(&mut hasher);
+                        quantize_share(*ratio).hash(&mut hasher);
+                    }
+                    Some(NodeKind::Leaf {
+                        window,
+                        fullscreen,
+                        fullscreen_within_gaps,
+                        ..
+                    }) => {
+                        1u8.hash(&mut hasher);
+                        window.hash(&mut hasher);
+                        fullscreen.hash(&mut hasher);
+                        fullscreen_within_gaps.hash(&mut hasher);
+                    }
+                    None => 2u8.hash(&mut hasher),
+                }
+            }
+        }
+        hasher.finish()
+    }
+
     fn calculate_layout(
         &self,
         layout: LayoutId,
         screen: CGRect,
         _stack_offset: f64,
+        _focus_pop: f64,
         gaps: &crate::common::config::GapSettings,
         _stack_line_thickness: f64,
         _stack_line_horiz: crate::common::config::HorizontalPlacement,
         _stack_line_vert: crate::common::config::VerticalPlacement,
+        is_positionable: Option<&dyn Fn(WindowId) -> bool>,
     ) -> Vec<(WindowId, CGRect)> {
         let mut out = Vec::new();
         if let Some(state) = self.layouts.get(layout).copied() {
             let rect = Self::apply_outer_gaps(screen, gaps);
             self.calculate_layout_recursive(state.root, rect, screen, gaps, &mut out);
         }
+        if let Some(is_positionable) = is_positionable {
+            out.retain(|&(wid, _)| is_positionable(wid));
+        }
         out
     }
 
@@ -844,6 +997,14 @@ impl LayoutCore for DwindleLayoutSystem {
         }
     }
 
+    fn insert_window_after_selection(&mut self, layout: LayoutId, wid: WindowId) {
+        let previous = self.selected_window(layout);
+        self.add_window_after_selection(layout, wid);
+        if let Some(previous) = previous {
+            self.select_window(layout, previous);
+        }
+    }
+
     fn remove_window(&mut self, wid: WindowId) {
         if let Some(&node_id) = self.window_to_node.get(&wid) {
             if self.kind.get(node_id).is_none() {
@@ -894,7 +1055,7 @@ impl LayoutCore for DwindleLayoutSystem {
         }
         for w in desired {
             if !current_set.contains(&w) {
-                self.add_window_after_selection(layout, w);
+                self.insert_window_after_selection(layout, w);
             }
         }
     }
@@ -988,19 +1149,10 @@ impl LayoutNavigation for DwindleLayoutSystem {
         if raise_windows.is_empty() {
             return (None, vec![]);
         }
-        let sel_snapshot = self.selection_of_layout(layout);
-        let Some(current_sel) = sel_snapshot else {
-            return (None, vec![]);
-        };
-        let current_leaf = self.descend_to_leaf(current_sel);
-        let Some(next_leaf) = self.find_neighbor_leaf(current_leaf, direction) else {
+        let Some((next_leaf, focus)) = self.focus_target_leaf(layout, direction) else {
             return (None, vec![]);
         };
         self.tree.data.selection.select(&self.tree.map, next_leaf);
-        let focus = match self.kind.get(next_leaf) {
-            Some(NodeKind::Leaf { window, .. }) => *window,
-            _ => None,
-        };
         // Optimization: Only raise the focused window to prevent flickering
         let raise_windows = if let Some(w) = focus { vec![w] } else { vec![] };
         (focus, raise_windows)
@@ -1012,6 +1164,14 @@ impl LayoutNavigation for DwindleLayoutSystem {
             .and_then(|state| self.window_in_direction_from(state.root, direction))
     }
 
+    fn focus_target_in_direction(
+        &self,
+        layout: LayoutId,
+        direction: Direction,
+    ) -> Option<WindowId> {
+        self.focus_target_leaf(layout, direction)?.1
+    }
+
     fn move_selection(&mut self, layout: LayoutId, direction: Direction) -> bool {
         let sel_snapshot = self.selection_of_layout(layout);
         let Some(sel) = sel_snapshot else {
@@ -1058,31 +1218,39 @@ impl LayoutNavigation for DwindleLayoutSystem {
             self.add_window_after_selection(to_layout, w);
         }
     }
+
+    fn selection_path(&self, layout: LayoutId) -> Vec<usize> {
+        let (Some(state), Some(sel)) = (self.layouts.get(layout), self.selection_of_layout(layout))
+        else {
+            return Vec::new();
+        };
+        sel.path_from(state.root, &self.tree.map).unwrap_or_default()
+    }
+
+    fn select_by_path(&mut self, layout: LayoutId, path: &[usize]) -> bool {
+        let Some(state) = self.layouts.get(layout) else {
+            return false;
+        };
+        let Some(node) = state.root.descendant_at(path, &self.tree.map) else {
+            return false;
+        };
+        self.tree.data.selection.select(&self.tree.map, node);
+        true
+    }
 }
 
 impl LayoutResizable for DwindleLayoutSystem {
     fn resize_selection_by(&mut self, layout: LayoutId, amount: f64) {
-        let sel_snapshot = self.selection_of_layout(layout);
-        let Some(mut node) = sel_snapshot else {
-            return;
-        };
-        while let Some(parent) = node.parent(&self.tree.map) {
-            if let Some(NodeKind::Split { ratio, preserved, .. }) = self.kind.get_mut(parent) {
-                let is_first = Some(node) == parent.first_child(&self.tree.map);
-                let delta = (amount as f32) * 0.5;
-                if is_first {
-                    *ratio = (*ratio + delta).clamp(0.05, 0.95);
-                } else {
-                    *ratio = (*ratio - delta).clamp(0.05, 0.95);
-                }
-                // Mark as preserved when user manually resizes
-                if self.settings.preserve_split {
-                    *preserved = true;
-                }
-                break;
-            }
-            node = parent;
-        }
+        self.resize_enclosing_split(layout, amount, None);
+    }
+
+    fn resize_selection_in_direction(
+        &mut self,
+        layout: LayoutId,
+        direction: Direction,
+        amount: f64,
+    ) {
+        self.resize_enclosing_split(layout, amount, Some(direction.orientation()));
     }
 
     fn rebalance(&mut self, _layout: LayoutId) {}
@@ -1157,6 +1325,36 @@ impl LayoutSplittable for DwindleLayoutSystem {
         }
     }
 
+    fn rotate_subtree(&mut self, layout: LayoutId) {
+        let Some(state) = self.layouts.get(layout) else {
+            return;
+        };
+        let root = state.root;
+        let start = match self.selection_of_layout(layout) {
+            Some(sel) if matches!(self.kind.get(sel), Some(NodeKind::Split { .. })) => sel,
+            Some(sel) => sel.parent(&self.tree.map).unwrap_or(root),
+            None => root,
+        };
+        let nodes: Vec<_> = start.traverse_preorder(&self.tree.map).collect();
+        for node in nodes {
+            if let Some(split) = self.kind.get_mut(node) {
+                split.turn_split();
+            }
+        }
+    }
+
+    fn rotate_layout(&mut self, layout: LayoutId, clockwise: bool) {
+        if let Some(state) = self.layouts.get(layout) {
+            rotate_splits(&mut self.tree, &mut self.kind, state.root, clockwise);
+        }
+    }
+
+    fn mirror_layout(&mut self, layout: LayoutId, axis: Orientation) {
+        if let Some(state) = self.layouts.get(layout) {
+            mirror_splits(&mut self.tree, &mut self.kind, state.root, axis);
+        }
+    }
+
     fn join_selection_with_direction(&mut self, layout: LayoutId, direction: Direction) {
         let Some(sel) = self.selection_of_layout(layout) else {
             return;
@@ -1244,6 +1442,33 @@ impl LayoutStacking for DwindleLayoutSystem {
     fn parent_of_selection_is_stacked(&self, _: LayoutId) -> bool {
         false
     }
+    fn stack_parent_of_selection_if_unstacked(
+        &mut self,
+        _: LayoutId,
+        _: crate::common::config::StackDefaultOrientation,
+    ) -> Vec<WindowId> {
+        vec![]
+    }
+    fn cycle_stack_of_parent_of_selection(&mut self, _: LayoutId) -> Vec<WindowId> {
+        vec![]
+    }
+    fn occluded_windows(&self, _: LayoutId) -> Vec<WindowId> {
+        vec![]
+    }
+
+    fn stacked_containers(
+        &self,
+        _: LayoutId,
+        _: CGRect,
+        _: f64,
+        _: f64,
+        _: &crate::common::config::GapSettings,
+        _: f64,
+        _: crate::common::config::HorizontalPlacement,
+        _: crate::common::config::VerticalPlacement,
+    ) -> Vec<crate::layout_engine::engine::GroupContainerInfo> {
+        vec![]
+    }
 }
 
 impl LayoutSwappable for DwindleLayoutSystem {
@@ -1331,6 +1556,25 @@ impl LayoutFullscreen for DwindleLayoutSystem {
         }
         vec![]
     }
+
+    fn fullscreen_windows(&self, layout: LayoutId) -> Vec<(WindowId, bool)> {
+        let Some(state) = self.layouts.get(layout) else {
+            return vec![];
+        };
+        state
+            .root
+            .traverse_preorder(&self.tree.map)
+            .filter_map(|node| match self.kind.get(node) {
+                Some(NodeKind::Leaf {
+                    window: Some(w),
+                    fullscreen,
+                    fullscreen_within_gaps,
+                    ..
+                }) if *fullscreen || *fullscreen_within_gaps => Some((*w, *fullscreen_within_gaps)),
+                _ => None,
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -1474,14 +1718,30 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             assert_eq!(result.len(), 2);
         }
+
+        #[test]
+        fn mirrored_splits_are_preserved() {
+            let mut system = DwindleLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            assert!(!system.draw_tree(layout).contains("[preserved]"));
+
+            system.mirror_layout(layout, Orientation::Horizontal);
+            let tree = system.draw_tree(layout);
+            assert!(tree.contains("[preserved]"), "{tree}");
+            assert_eq!(system.visible_windows_in_layout(layout), vec![w(1, 2), w(1, 1)]);
+        }
     }
 
     mod navigation {
@@ -1528,10 +1788,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             // 3. Add second window. Should split Horizontal (Wide -> H)
@@ -1549,10 +1811,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             // 5. Add third window. Should split Vertical (Tall -> V)
@@ -1582,10 +1846,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 Default::default(),
                 Default::default(),
+                None,
             );
 
             // Window 2: Right-Top
@@ -1594,10 +1860,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 Default::default(),
                 Default::default(),
+                None,
             );
 
             // Window 3: Right-Bottom-Left
@@ -1606,10 +1874,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 Default::default(),
                 Default::default(),
+                None,
             );
 
             // Window 4: Right-Bottom-Right
@@ -1618,10 +1888,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 Default::default(),
                 Default::default(),
+                None,
             );
 
             // Verify initial state (4 windows)
@@ -1635,10 +1907,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 Default::default(),
                 Default::default(),
+                None,
             );
 
             // 3. Verify Result
@@ -1647,10 +1921,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                0.0,
                 &gaps(),
                 0.0,
                 Default::default(),
                 Default::default(),
+                None,
             );
             let frame_3 = result.iter().find(|(id, _)| *id == w(1, 3)).unwrap().1;
 
@@ -1,24 +1,64 @@
 #![allow(clippy::too_many_arguments)]
 
-use objc2_core_foundation::CGRect;
+use std::collections::VecDeque;
+
+use objc2_core_foundation::{CGPoint, CGRect};
 use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
 use tracing::warn;
 
 use crate::actor::app::{WindowId, pid_t};
+use crate::common::persistence::MapEntries;
 use crate::layout_engine::systems::{
     LayoutCore, LayoutFullscreen, LayoutLifecycle, LayoutNavigation, LayoutResizable,
-    LayoutSplittable, LayoutStacking, LayoutSwappable,
+    LayoutSplittable, LayoutStacking, LayoutSwappable, TreeRenderBudget, TreeRenderOptions,
+    collapsed_tree_summary, fingerprint_hasher, quantize_share,
 };
 use crate::layout_engine::utils::compute_tiling_area;
 use crate::layout_engine::{Direction, LayoutId, LayoutKind, Orientation};
 use crate::model::selection::*;
 use crate::model::tree::{self, NodeId, NodeMap, OwnedNode, Tree};
-use crate::sys::geometry::Round;
+use crate::sys::geometry::{CGRectExt, Round};
 
 #[derive(Serialize, Deserialize)]
 pub struct TraditionalLayoutSystem {
     tree: Tree<Components>,
     layout_roots: slotmap::SlotMap<LayoutId, OwnedNode>,
+    /// Undo/redo snapshots per layout. They only live for the session.
+    #[serde(skip)]
+    history: slotmap::SecondaryMap<LayoutId, LayoutHistory>,
+}
+
+#[derive(Default)]
+struct LayoutHistory {
+    undo: VecDeque<LayoutSnapshot>,
+    redo: Vec<LayoutSnapshot>,
+}
+
+/// A layout's tree copied by value, in preorder. Snapshots hold no node ids
+/// and bind no windows, so they stay out of the tree and are never laid out,
+/// validated or saved.
+#[derive(Clone)]
+struct LayoutSnapshot(Vec<SnapshotNode>);
+
+#[derive(Clone)]
+struct SnapshotNode {
+    info: LayoutInfo,
+    window: Option<WindowId>,
+    children: usize,
+    /// Index of the locally selected child, and whether the selection stops
+    /// at this node.
+    selected: Option<(usize, bool)>,
+}
+
+impl LayoutSnapshot {
+    /// Whether both trees have the same structure, layout kinds and windows.
+    fn same_shape(&self, other: &LayoutSnapshot) -> bool {
+        self.0.len() == other.0.len()
+            && std::iter::zip(&self.0, &other.0).all(|(a, b)| {
+                a.info.kind == b.info.kind && a.window == b.window && a.children == b.children
+            })
+    }
 }
 
 impl Default for TraditionalLayoutSystem {
@@ -26,30 +66,145 @@ impl Default for TraditionalLayoutSystem {
         Self {
             tree: Tree::with_observer(Components::default()),
             layout_roots: Default::default(),
+            history: Default::default(),
         }
     }
 }
 
 impl TraditionalLayoutSystem {
-    fn find_best_focus_target(&self, node: NodeId) -> Option<(NodeId, WindowId)> {
-        if let Some(wid) = self.tree.data.window.at(node) {
-            return Some((node, wid));
+    /// Runs the structural mutation `f` on `layout`, recording the previous
+    /// tree on the undo stack if `f` changed its shape. A new entry clears the
+    /// redo stack.
+    fn with_undo<R>(&mut self, layout: LayoutId, f: impl FnOnce(&mut Self) -> R) -> R {
+        let before = self.snapshot(layout);
+        let result = f(self);
+        if before.same_shape(&self.snapshot(layout)) {
+            return result;
+        }
+        let history = self.history.entry(layout).unwrap().or_default();
+        history.undo.push_back(before);
+        history.redo.clear();
+        while history.undo.len() > UNDO_DEPTH {
+            history.undo.pop_front();
+        }
+        result
+    }
+
+    /// Restores the tree `layout` had before its last recorded structural
+    /// change. Returns false if there is nothing to undo.
+    pub fn undo(&mut self, layout: LayoutId) -> bool {
+        let Some(snapshot) = self.history.get_mut(layout).and_then(|h| h.undo.pop_back()) else {
+            return false;
+        };
+        let current = self.snapshot(layout);
+        self.restore_snapshot(layout, &snapshot);
+        self.history[layout].redo.push(current);
+        true
+    }
+
+    /// Re-applies the last change reverted by `undo`. Returns false if there
+    /// is nothing to redo.
+    pub fn redo(&mut self, layout: LayoutId) -> bool {
+        let Some(snapshot) = self.history.get_mut(layout).and_then(|h| h.redo.pop()) else {
+            return false;
+        };
+        let current = self.snapshot(layout);
+        self.restore_snapshot(layout, &snapshot);
+        self.history[layout].undo.push_back(current);
+        true
+    }
+
+    fn snapshot(&self, layout: LayoutId) -> LayoutSnapshot {
+        let map = self.map();
+        let selection = &self.tree.data.selection;
+        let nodes = self
+            .root(layout)
+            .traverse_preorder(map)
+            .map(|node| SnapshotNode {
+                info: self.tree.data.layout.info[node],
+                window: self.window_at(node),
+                children: node.children(map).count(),
+                selected: selection.local_selection_info(node).and_then(|(child, stop_here)| {
+                    node.children(map).position(|c| c == child).map(|idx| (idx, stop_here))
+                }),
+            })
+            .collect();
+        LayoutSnapshot(nodes)
+    }
+
+    /// Rebuilds the tree of `layout` from `snapshot` under its existing root.
+    /// Windows that were added to or removed from `layout` since the snapshot
+    /// was taken keep their current membership.
+    fn restore_snapshot(&mut self, layout: LayoutId, snapshot: &LayoutSnapshot) {
+        let root = self.root(layout);
+        let current: crate::common::collections::HashSet<WindowId> =
+            self.windows_under(root).map(|(wid, _)| wid).collect();
+        let children: Vec<_> = root.children(self.map()).collect();
+        for child in children {
+            child.detach(&mut self.tree).remove();
+        }
+
+        // Containers still waiting for children, with how many are left.
+        let mut open: Vec<(NodeId, usize)> = Vec::new();
+        let mut nodes = Vec::with_capacity(snapshot.0.len());
+        for entry in &snapshot.0 {
+            let node = match open.last_mut() {
+                None => root,
+                Some((parent, remaining)) => {
+                    *remaining -= 1;
+                    let parent = *parent;
+                    self.tree.mk_node().push_back(parent)
+                }
+            };
+            while open.last().is_some_and(|&(_, remaining)| remaining == 0) {
+                open.pop();
+            }
+            if let Some(wid) = entry.window {
+                self.tree.data.window.set_window(layout, node, wid);
+            }
+            if entry.children > 0 {
+                open.push((node, entry.children));
+            }
+            nodes.push(node);
         }
-        let children: Vec<_> = node.children(self.map()).collect();
-        if children.is_empty() {
-            return None;
+        for (&node, entry) in std::iter::zip(&nodes, &snapshot.0) {
+            self.tree.data.layout.info[node] = entry.info;
+            let selected = entry.selected.and_then(|(idx, stop_here)| {
+                node.children(&self.tree.map).nth(idx).map(|child| (child, stop_here))
+            });
+            self.tree.data.selection.set_local_selection_info(node, selected);
         }
-        if let Some(selected) = self.tree.data.selection.local_selection(self.map(), node)
-            && let Some(target) = self.find_best_focus_target(selected)
-        {
-            return Some(target);
+
+        let restored: Vec<_> = self.windows_under(root).collect();
+        for &(wid, node) in &restored {
+            if !current.contains(&wid) {
+                node.detach(&mut self.tree).remove();
+            }
         }
-        for &child in &children {
-            if let Some(target) = self.find_best_focus_target(child) {
-                return Some(target);
+        for wid in current {
+            if !restored.iter().any(|&(restored, _)| restored == wid) {
+                self.add_window_after_selection(layout, wid);
             }
         }
-        None
+    }
+
+    fn windows_under(&self, node: NodeId) -> impl Iterator<Item = (WindowId, NodeId)> + '_ {
+        node.traverse_preorder(self.map())
+            .filter_map(|node| self.window_at(node).map(|wid| (wid, node)))
+    }
+
+    fn find_best_focus_target(&self, node: NodeId) -> Option<(NodeId, WindowId)> {
+        self.tree.data.find_best_focus_target(self.map(), node)
+    }
+
+    /// The node and window a directional focus move from the selection lands on.
+    fn focus_target_node(
+        &self,
+        layout: LayoutId,
+        direction: Direction,
+    ) -> Option<(NodeId, WindowId)> {
+        let new_node = self.traverse_internal(self.selection(layout), direction)?;
+        self.find_best_focus_target(new_node)
     }
 
     fn smart_window_insertion(
@@ -58,11 +213,14 @@ impl TraditionalLayoutSystem {
         selection: NodeId,
         wid: WindowId,
     ) -> NodeId {
+        if let Some(existing) = self.tree.data.window.node_for(layout, wid) {
+            return existing;
+        }
         let parent = selection.parent(self.map());
         if let Some(parent) = parent {
             let parent_layout = self.layout(parent);
             let sibling_count = parent.children(self.map()).count();
-            if sibling_count >= 4 && !parent_layout.is_group() {
+            if sibling_count >= 4 && !parent_layout.is_group() && !self.is_locked(parent) {
                 let sub_container =
                     self.nest_in_container_internal(layout, selection, parent_layout);
                 let node = self.tree.mk_node().push_back(sub_container);
@@ -75,6 +233,11 @@ impl TraditionalLayoutSystem {
         node
     }
 
+    /// Inserts `wid` after `node` and leaves the selection alone.
+    fn add_window_after_node(&mut self, layout: LayoutId, node: NodeId, wid: WindowId) -> NodeId {
+        self.smart_window_insertion(layout, node, wid)
+    }
+
     fn find_or_create_smart_common_parent(
         &mut self,
         layout: LayoutId,
@@ -82,2409 +245,5907 @@ impl TraditionalLayoutSystem {
         node2: NodeId,
         direction: Direction,
     ) -> NodeId {
-        let parent1 = node1.parent(self.map());
-        let parent2 = node2.parent(self.map());
-        if let (Some(p1), Some(p2)) = (parent1, parent2)
+        if let Some(parent) = self.existing_smart_common_parent(node1, node2, direction) {
+            return parent;
+        }
+        self.find_or_create_common_parent_internal(layout, node1, node2)
+    }
+
+    /// The node `find_or_create_smart_common_parent` would reuse for `node1` and
+    /// `node2`, or `None` if it would have to create a new container.
+    fn existing_smart_common_parent(
+        &self,
+        node1: NodeId,
+        node2: NodeId,
+        direction: Direction,
+    ) -> Option<NodeId> {
+        let map = self.map();
+        if let (Some(p1), Some(p2)) = (node1.parent(map), node2.parent(map))
             && p1 == p2
         {
             let parent_layout = self.layout(p1);
-            let sibling_count = p1.children(self.map()).count();
+            let sibling_count = p1.children(map).count();
             if parent_layout.orientation() == direction.orientation()
                 && !parent_layout.is_group()
                 && sibling_count == 2
             {
-                return p1;
+                return Some(p1);
             }
         }
-        self.find_or_create_common_parent_internal(layout, node1, node2)
-    }
-
-    fn root(&self, layout: LayoutId) -> NodeId {
-        self.layout_roots[layout].id()
-    }
-    fn selection(&self, layout: LayoutId) -> NodeId {
-        self.tree.data.selection.current_selection(self.root(layout))
-    }
-    fn map(&self) -> &NodeMap {
-        &self.tree.map
-    }
-    fn layout(&self, node: NodeId) -> LayoutKind {
-        self.tree.data.layout.kind(node)
-    }
-    fn set_layout(&mut self, node: NodeId, kind: LayoutKind) {
-        self.tree.data.layout.set_kind(node, kind);
+        if node1 == node2 || node2.ancestors(map).any(|ancestor| ancestor == node1) {
+            return Some(node1);
+        }
+        if node1.ancestors(map).any(|ancestor| ancestor == node2) {
+            return Some(node2);
+        }
+        None
     }
 
-    fn find_natural_join_target(&self, from: NodeId, direction: Direction) -> Option<NodeId> {
-        if let Some(parent) = from.parent(self.map()) {
-            let parent_layout = self.layout(parent);
-            if parent_layout.orientation() == direction.orientation() && !parent_layout.is_group() {
-                let child_count = parent.children(self.map()).count();
-                if child_count == 2
-                    && let Some(neighbor) = match direction {
-                        Direction::Right | Direction::Down => parent.next_sibling(self.map()),
-                        Direction::Left | Direction::Up => parent.prev_sibling(self.map()),
-                    }
-                {
-                    return Some(neighbor);
-                }
-                let is_edge = match direction {
-                    Direction::Right | Direction::Down => from.next_sibling(self.map()).is_none(),
-                    Direction::Left | Direction::Up => from.prev_sibling(self.map()).is_none(),
-                };
-                if is_edge {
-                    let neighbor = match direction {
-                        Direction::Right | Direction::Down => parent.next_sibling(self.map()),
-                        Direction::Left | Direction::Up => parent.prev_sibling(self.map()),
-                    };
-                    if let Some(neighbor) = neighbor {
-                        return Some(neighbor);
-                    }
-                }
-            }
+    /// Orientation the container resulting from joining the selection in
+    /// `direction` would have, or `None` if there is nothing to join with.
+    /// Mirrors the decisions of `join_selection_with_direction` without
+    /// touching the tree.
+    pub fn predict_join_orientation(
+        &self,
+        layout: LayoutId,
+        direction: Direction,
+    ) -> Option<Orientation> {
+        let selection = self.selection(layout);
+        let target = self.find_natural_join_target(selection, direction)?.node();
+        let selection = self.join_source(selection, target, direction);
+        let (selection, target) = (
+            self.join_unit(selection, target),
+            self.join_unit(target, selection),
+        );
+        if self.can_absorb_join_target(selection, target, direction) {
+            return Some(self.layout(selection).orientation());
         }
-        if let Some(sibling) = self.find_direct_sibling_target(from, direction) {
-            return Some(sibling);
+
+        let map = self.map();
+        let selection_parent = selection.parent(map);
+        let target_parent = target.parent(map);
+        let selection_stack_parent =
+            selection_parent.filter(|&parent| self.layout(parent).is_stacked());
+        let target_stack_parent = target_parent.filter(|&parent| self.layout(parent).is_stacked());
+        if selection_stack_parent
+            .or(target_stack_parent)
+            .is_some_and(|p| self.is_locked(p))
+        {
+            return None;
         }
-        if let Some(stack_neighbor) = self.find_stack_neighbor_target(from, direction) {
-            return Some(stack_neighbor);
+        match (selection_stack_parent, target_stack_parent) {
+            (Some(stack_parent), None) | (None, Some(stack_parent)) => {
+                return Some(self.layout(stack_parent).orientation());
+            }
+            _ => {}
         }
-        if let Some(traversed) = self.traverse_internal(from, direction) {
-            if self.tree.data.window.at(traversed).is_some() {
-                return Some(traversed);
+        match (selection_parent, target_parent) {
+            (Some(sp), Some(tp)) if sp == tp && self.layout(sp).is_stacked() => {
+                Some(direction.orientation())
             }
-            if let Some(target_child) =
-                self.find_best_container_child_for_joining(traversed, direction)
+            (Some(sp), Some(tp))
+                if sp != tp && self.are_containers_mergeable(sp, tp, direction) =>
             {
-                return Some(target_child);
+                Some(direction.orientation())
+            }
+            _ => {
+                let common_parent = self.existing_smart_common_parent(selection, target, direction);
+                match common_parent {
+                    Some(parent) if self.layout(parent).is_stacked() || self.is_locked(parent) => {
+                        Some(self.layout(parent).orientation())
+                    }
+                    _ => Some(direction.orientation()),
+                }
             }
-            return Some(traversed);
         }
-        self.find_hierarchical_join_target(from, direction)
     }
 
-    fn find_stack_neighbor_target(&self, from: NodeId, direction: Direction) -> Option<NodeId> {
-        let parent = from.parent(self.map())?;
-        let parent_layout = self.layout(parent);
-        if !parent_layout.is_stacked() {
-            return None;
+    /// Selects the deepest container whose frame contains `point`, stopping
+    /// above leaf windows. Stacks descend into their visible child. Returns
+    /// whether the selection changed.
+    pub fn select_container_at_point(
+        &mut self,
+        layout: LayoutId,
+        point: CGPoint,
+        screen: CGRect,
+        gaps: &crate::common::config::GapSettings,
+    ) -> bool {
+        let map = self.map();
+        let mut node = self.root(layout);
+        let mut rect = compute_tiling_area(screen, gaps);
+        if !rect.contains(point) {
+            return false;
         }
-        let children: Vec<_> = parent.children(self.map()).collect();
-        let current_idx = children.iter().position(|&c| c == from)?;
-        match direction {
-            Direction::Right | Direction::Down => children.get(current_idx + 1).copied(),
-            Direction::Left | Direction::Up => {
-                if current_idx > 0 {
-                    children.get(current_idx - 1).copied()
-                } else {
-                    None
+        loop {
+            let next = if self.layout(node).is_stacked() {
+                self.tree
+                    .data
+                    .selection
+                    .local_selection(map, node)
+                    .or_else(|| node.first_child(map))
+            } else {
+                node.children(map).find(|&child| {
+                    self.calculate_child_frame_in_container(node, child, rect, gaps).contains(point)
+                })
+            };
+            match next {
+                Some(child) if self.window_at(child).is_none() && !child.is_empty(map) => {
+                    rect = self.calculate_child_frame_in_container(node, child, rect, gaps);
+                    node = child;
                 }
+                _ => break,
             }
         }
-    }
-
-    fn find_direct_sibling_target(&self, from: NodeId, direction: Direction) -> Option<NodeId> {
-        let _parent = from.parent(self.map())?;
-        match direction {
-            Direction::Right | Direction::Down => from.next_sibling(self.map()),
-            Direction::Left | Direction::Up => from.prev_sibling(self.map()),
+        if self.selection(layout) == node {
+            return false;
         }
+        self.select(node);
+        true
     }
 
-    fn find_best_container_child_for_joining(
-        &self,
+    /// Selects the `index`th child of the stack `container` in `layout`.
+    /// Returns the window to focus and the windows revealed by the change, or
+    /// `None` if `container` is not a stack of this layout or `index` is out
+    /// of range.
+    pub fn select_stack_member(
+        &mut self,
+        layout: LayoutId,
         container: NodeId,
-        direction: Direction,
-    ) -> Option<NodeId> {
-        let children: Vec<_> = container.children(self.map()).collect();
-        if children.is_empty() {
+        index: usize,
+    ) -> Option<(WindowId, Vec<WindowId>)> {
+        let map = self.map();
+        if !map.contains(container)
+            || container.ancestors(map).last() != Some(self.root(layout))
+            || !self.layout(container).is_group()
+        {
             return None;
         }
-        let container_layout = self.layout(container);
-        if container_layout.orientation() == direction.orientation() {
-            return match direction {
-                Direction::Left | Direction::Up => children.first().copied(),
-                Direction::Right | Direction::Down => children.last().copied(),
-            };
+        let child = container.children(map).nth(index)?;
+        let (focus_node, focus_window) = self.find_best_focus_target(child)?;
+        self.select(focus_node);
+        Some((focus_window, self.visible_windows_under_internal(container)))
+    }
+
+    /// Like [`LayoutCore::set_windows_for_app`], but each new window goes next
+    /// to a window of the same app instead of after the selection, unless the
+    /// app has no window in the layout yet.
+    pub fn set_windows_for_app_near_app(
+        &mut self,
+        layout: LayoutId,
+        pid: pid_t,
+        desired: Vec<WindowId>,
+    ) {
+        self.sync_windows_for_app(layout, pid, desired, true);
+    }
+
+    /// Moves the boundary between the windows `a` and `b` by transferring
+    /// `delta` (a fraction of the container they share) from `b`'s side to
+    /// `a`'s. Only the two children of their lowest common container are
+    /// resized, so nested siblings elsewhere keep their shares. Returns false
+    /// if the windows do not sit on either side of one boundary.
+    pub fn resize_boundary_between(
+        &mut self,
+        layout: LayoutId,
+        a: WindowId,
+        b: WindowId,
+        delta: f64,
+    ) -> bool {
+        let map = &self.tree.map;
+        let (Some(node_a), Some(node_b)) = (
+            self.tree.data.window.node_for(layout, a),
+            self.tree.data.window.node_for(layout, b),
+        ) else {
+            return false;
+        };
+        let ancestors_b: Vec<_> = node_b.ancestors(map).collect();
+        let Some(common) = node_a.ancestors(map).find(|node| ancestors_b.contains(node)) else {
+            return false;
+        };
+        if common == node_a || common == node_b || self.tree.data.layout.kind(common).is_group() {
+            return false;
         }
-        if let Some(selected) = self.tree.data.selection.local_selection(self.map(), container) {
-            return Some(selected);
+        let child_of_common =
+            |node: NodeId| node.ancestors(map).find(|n| n.parent(map) == Some(common));
+        let (Some(side_a), Some(side_b)) = (child_of_common(node_a), child_of_common(node_b))
+        else {
+            return false;
+        };
+        if side_a.next_sibling(map) != Some(side_b) && side_b.next_sibling(map) != Some(side_a) {
+            return false;
         }
-        children.first().copied()
+        let share = delta * f64::from(self.tree.data.layout.info[common].total);
+        self.tree.data.layout.take_share(map, side_a, side_b, share as f32);
+        true
     }
 
-    fn find_hierarchical_join_target(&self, from: NodeId, direction: Direction) -> Option<NodeId> {
-        for ancestor in from.ancestors(self.map()).skip(1) {
-            if let Some(target) = self.find_direct_sibling_target(ancestor, direction) {
-                return self.find_best_container_child_for_joining(target, direction.opposite());
-            }
-        }
-        None
+    /// The container kind of `layout`'s root.
+    pub fn root_kind(&self, layout: LayoutId) -> LayoutKind {
+        self.layout(self.root(layout))
     }
 
-    fn perform_natural_join(
+    /// Turns `layout`'s root into a stack, oriented as `stack` would orient
+    /// it for a selection directly under the root.
+    pub fn stack_root(
         &mut self,
         layout: LayoutId,
-        selection: NodeId,
-        target: NodeId,
-        direction: Direction,
+        default_orientation: crate::common::config::StackDefaultOrientation,
     ) {
-        let selection_parent = selection.parent(self.map());
-        let target_parent = target.parent(self.map());
-        let selection_stack_parent =
-            selection_parent.filter(|&parent| self.layout(parent).is_stacked());
-        let target_stack_parent = target_parent.filter(|&parent| self.layout(parent).is_stacked());
-        match (selection_stack_parent, target_stack_parent) {
-            (Some(stack_parent), None) => {
-                target.detach(&mut self.tree).push_back(stack_parent);
-                self.select(stack_parent);
-                return;
-            }
-            (None, Some(stack_parent)) => {
-                selection.detach(&mut self.tree).push_back(stack_parent);
-                self.select(stack_parent);
-                return;
-            }
-            _ => {}
+        let root = self.root(layout);
+        let kind = self.layout(root);
+        if !kind.is_stacked() {
+            self.set_layout(root, stacked_kind(kind, default_orientation));
         }
-        match (selection_parent, target_parent) {
-            (Some(sp), Some(tp)) if sp == tp => {
-                if self.layout(sp).is_stacked() {
-                    let new_layout = match direction.orientation() {
-                        Orientation::Horizontal => LayoutKind::Horizontal,
-                        Orientation::Vertical => LayoutKind::Vertical,
-                    };
-                    self.set_layout(sp, new_layout);
-                    self.select(sp);
-                    return;
-                }
-                let common_parent =
-                    self.find_or_create_smart_common_parent(layout, selection, target, direction);
-                let container_layout = LayoutKind::from(direction.orientation());
-                let new_layout = if self.layout(common_parent).is_stacked() {
-                    self.layout(common_parent)
-                } else {
-                    container_layout
-                };
-                self.set_layout(common_parent, new_layout);
-                self.select(common_parent);
-            }
-            (Some(sp), Some(tp)) if self.are_containers_mergeable(sp, tp, direction) => {
-                self.merge_compatible_containers(layout, sp, tp, direction);
-            }
-            _ => {
-                let common_parent =
-                    self.find_or_create_smart_common_parent(layout, selection, target, direction);
-                let container_layout = LayoutKind::from(direction.orientation());
-                let new_layout = if self.layout(common_parent).is_stacked() {
-                    self.layout(common_parent)
-                } else {
-                    container_layout
-                };
-                self.set_layout(common_parent, new_layout);
-                self.select(common_parent);
-            }
+    }
+
+    /// Sets the container kind of `layout`'s root.
+    pub fn set_root_kind(&mut self, layout: LayoutId, kind: LayoutKind) {
+        let root = self.root(layout);
+        self.tree.data.layout.set_kind(root, kind);
+    }
+
+    /// Splits the selection's parent in the golden ratio: the first child
+    /// against the rest at 1.618:1 when `main_first`, 1:1.618 otherwise.
+    /// With more than two children the rest share their side evenly.
+    pub fn set_golden_ratio_of_selection_parent(&mut self, layout: LayoutId, main_first: bool) {
+        let map = self.map();
+        let Some(parent) = self.selection(layout).parent(map) else {
+            return;
+        };
+        let children: Vec<_> = parent.children(map).collect();
+        let Some((&first, rest)) = children.split_first() else {
+            return;
+        };
+        if rest.is_empty() {
+            return;
         }
+        let (first_share, rest_share) = if main_first {
+            (GOLDEN_RATIO, 1.0)
+        } else {
+            (1.0, GOLDEN_RATIO)
+        };
+        let rest_each = rest_share / rest.len() as f32;
+        let info = &mut self.tree.data.layout.info;
+        info[first].size = first_share;
+        for &child in rest {
+            info[child].size = rest_each;
+        }
+        info[parent].total = children.iter().map(|&child| info[child].size).sum();
     }
 
-    fn are_containers_mergeable(
-        &self,
-        container1: NodeId,
-        container2: NodeId,
-        direction: Direction,
-    ) -> bool {
-        let layout1 = self.layout(container1);
-        let layout2 = self.layout(container2);
-        layout1.orientation() == direction.orientation()
-            && layout2.orientation() == direction.orientation()
-            && !layout1.is_group()
-            && !layout2.is_group()
+    /// Visible windows of `layout` with `front` first and the rest in preorder.
+    /// Raising the list back to front leaves `front` on top without hidden
+    /// stack members or siblings landing over it.
+    pub fn visible_windows_ordered(&self, layout: LayoutId, front: WindowId) -> Vec<WindowId> {
+        let visible: crate::common::collections::HashSet<WindowId> =
+            self.visible_windows_in_layout(layout).into_iter().collect();
+        let rest = self
+            .root(layout)
+            .traverse_preorder(self.map())
+            .filter_map(|node| self.window_at(node))
+            .filter(|wid| *wid != front && visible.contains(wid));
+        visible.contains(&front).then_some(front).into_iter().chain(rest).collect()
     }
 
-    fn merge_compatible_containers(
-        &mut self,
-        layout: LayoutId,
-        container1: NodeId,
-        container2: NodeId,
-        direction: Direction,
-    ) {
-        let common_parent =
-            self.find_or_create_smart_common_parent(layout, container1, container2, direction);
-        let container_layout = LayoutKind::from(direction.orientation());
-        self.set_layout(common_parent, container_layout);
-        self.select(common_parent);
+    /// Every window of the stacks `wid` sits in, bottom to top with `wid`
+    /// last. Members after the one holding `wid` come farthest first and
+    /// those before it nearest last, so each keeps its offset edge showing.
+    /// Members of an enclosing stack go under those of the stack inside it.
+    /// Empty if `wid` is not in a stack.
+    pub fn stack_raise_order(&self, layout: LayoutId, wid: WindowId) -> Vec<WindowId> {
+        let Some(node) = self.tree.data.window.node_for(layout, wid) else {
+            return vec![];
+        };
+        let map = self.map();
+        let mut order = vec![wid];
+        let mut stacked = false;
+        for (node, parent) in node.ancestors_with_parent(map) {
+            let Some(parent) = parent else { break };
+            if !self.layout(parent).is_stacked() {
+                continue;
+            }
+            stacked = true;
+            let members: Vec<NodeId> = parent.children(map).collect();
+            let Some(index) = members.iter().position(|&member| member == node) else {
+                continue;
+            };
+            let mut below: Vec<WindowId> = members[index + 1..]
+                .iter()
+                .rev()
+                .chain(&members[..index])
+                .flat_map(|&member| self.visible_windows_under_internal(member))
+                .collect();
+            below.append(&mut order);
+            order = below;
+        }
+        if stacked { order } else { vec![] }
     }
-}
 
-impl Drop for TraditionalLayoutSystem {
-    fn drop(&mut self) {
-        for (_, node) in self.layout_roots.drain() {
-            std::mem::forget(node);
+    /// Toggles size preservation on the selected window and returns the new
+    /// state. A preserved window records its absolute size the next time
+    /// `enforce_preserved_sizes` runs, which then keeps it there as siblings
+    /// come and go.
+    pub fn toggle_preserve_size_of_selection(&mut self, layout: LayoutId) -> bool {
+        let node = self.selection(layout);
+        if self.window_at(node).is_none() {
+            return false;
         }
+        let info = &mut self.tree.data.layout.info[node];
+        info.preserve_size = !info.preserve_size;
+        info.preserved_size = None;
+        info.preserve_size
     }
-}
 
-impl LayoutLifecycle for TraditionalLayoutSystem {
-    fn create_layout(&mut self) -> LayoutId {
-        let root = OwnedNode::new_root_in(&mut self.tree, "layout_root");
-        self.layout_roots.insert(root)
+    /// Reverses the children of the selection's parent, which swaps them
+    /// when there are two. Each child keeps its share and the selection stays
+    /// on the same node. Returns false if the parent has a single child or
+    /// the selection is the root.
+    pub fn flip_container_children(&mut self, layout: LayoutId) -> bool {
+        self.with_undo(layout, |this| {
+            let Some(parent) = this.selection(layout).parent(this.map()) else {
+                return false;
+            };
+            if parent.children(this.map()).nth(1).is_none() {
+                return false;
+            }
+            this.reverse_children(parent);
+            true
+        })
     }
 
-    fn clone_layout(&mut self, layout: LayoutId) -> LayoutId {
-        let source_root = self.layout_roots[layout].id();
-        let cloned = source_root.deep_copy(&mut self.tree).make_root("layout_root");
-        let cloned_root = cloned.id();
-        let dest_layout = self.layout_roots.insert(cloned);
-        for (src, dest) in std::iter::zip(
-            source_root.traverse_preorder(&self.tree.map),
-            cloned_root.traverse_preorder(&self.tree.map),
-        ) {
-            self.tree
-                .data
-                .dispatch_event(&self.tree.map, TreeEvent::Copied { src, dest, dest_layout });
+    /// Moves the selection to the front of the root container, shifting the
+    /// other children back so the previous main window becomes second.
+    /// Returns false if the selection is already main or is the root.
+    pub fn promote_selection_to_main(&mut self, layout: LayoutId) -> bool {
+        self.with_undo(layout, |this| {
+            let root = this.root(layout);
+            let node = this.selection(layout);
+            let Some(first) = root.first_child(this.map()) else {
+                return false;
+            };
+            if node == root || node == first {
+                return false;
+            }
+            node.detach(&mut this.tree).insert_before(first).finish();
+            this.select(node);
+            true
+        })
+    }
+
+    /// Lifts the selected window out of every container it is nested in, to
+    /// a direct child of the root right after its former top-level
+    /// container, and makes it fullscreen within gaps. Returns the windows
+    /// to raise, empty if the selection is not a window.
+    pub fn isolate_and_maximize_selection(&mut self, layout: LayoutId) -> Vec<WindowId> {
+        let node = self.selection(layout);
+        if self.window_at(node).is_none() {
+            return vec![];
+        }
+        self.with_undo(layout, |this| {
+            let root = this.root(layout);
+            if let Some(top) =
+                node.ancestors(this.map()).find(|&n| n.parent(this.map()) == Some(root))
+                && top != node
+            {
+                node.detach(&mut this.tree).insert_after(top).finish();
+                this.select(node);
+            }
+        });
+        self.tree.data.layout.set_fullscreen_within_gaps(node, true);
+        self.visible_windows_under_internal(node)
+    }
+
+    /// Splits the selection into `count` equal tiles laid out as `kind`. A
+    /// parent that already holds at least `count` children is switched to
+    /// `kind` and divided evenly. Otherwise the selection is nested in a new
+    /// `kind` container that stays evenly divided as windows are added,
+    /// until it holds `count` of them.
+    pub fn split_selection_into(&mut self, layout: LayoutId, kind: LayoutKind, count: usize) {
+        self.with_undo(layout, |this| {
+            let selection = this.selection(layout);
+            if let Some(parent) = selection.parent(this.map())
+                && parent.children(this.map()).count() >= count
+            {
+                this.set_layout(parent, kind);
+                this.tree.data.layout.equalize(&this.tree.map, parent);
+                return;
+            }
+            let container = this.nest_in_container_internal(layout, selection, kind);
+            let layout_info = &mut this.tree.data.layout;
+            layout_info.info[container].expected_children = count as u32;
+            layout_info.equalize(&this.tree.map, container);
+        })
+    }
+
+    /// How many containers deep the most deeply nested window of `layout`
+    /// sits. A window directly under the root is at depth 1.
+    pub fn max_depth(&self, layout: LayoutId) -> usize {
+        let map = self.map();
+        self.root(layout)
+            .traverse_preorder(map)
+            .filter(|&node| self.window_at(node).is_some())
+            .map(|node| node.ancestors(map).count() - 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Collapses single-child containers above windows nested deeper than
+    /// `max_depth`, deepest first, until the layout fits or no such container
+    /// is left. Locked containers and the root are kept, and windows keep
+    /// their order. Returns how many containers were removed.
+    pub fn flatten_to_depth(&mut self, layout: LayoutId, max_depth: usize) -> usize {
+        let mut removed = 0;
+        while self.max_depth(layout) > max_depth {
+            let map = self.map();
+            let root = self.root(layout);
+            let candidate = root
+                .traverse_preorder(map)
+                .filter(|&node| {
+                    self.window_at(node).is_some() && node.ancestors(map).count() - 1 > max_depth
+                })
+                .flat_map(|window| window.ancestors(map).skip(1))
+                .filter(|&node| {
+                    node != root && !self.is_locked(node) && node.children(map).nth(1).is_none()
+                })
+                .max_by_key(|&node| node.ancestors(map).count());
+            let Some(container) = candidate else { break };
+            self.remove_unnecessary_container_internal(container);
+            removed += 1;
         }
-        dest_layout
+        removed
     }
 
-    fn remove_layout(&mut self, layout: LayoutId) {
-        self.layout_roots.remove(layout).unwrap().remove(&mut self.tree)
+    /// Rebuilds `layout` as two columns side by side: the first window on its
+    /// own and every other window in a vertical container beside it, in their
+    /// current order. The main column is on the left when `master_on_left`.
+    /// Returns false if the layout has fewer than two windows.
+    pub fn arrange_two_column(&mut self, layout: LayoutId, master_on_left: bool) -> bool {
+        self.with_undo(layout, |this| {
+            let root = this.root(layout);
+            let leaves: Vec<_> = root
+                .traverse_preorder(this.map())
+                .filter(|&node| this.window_at(node).is_some())
+                .collect();
+            let Some((&master, rest)) = leaves.split_first() else {
+                return false;
+            };
+            if rest.is_empty() {
+                return false;
+            }
+            let selection = this.selection(layout);
+            let selection = this.window_at(selection).map(|_| selection);
+
+            // A lone second window needs no container of its own.
+            let stack = match rest {
+                [single] => *single,
+                _ => {
+                    let stack = this.tree.mk_node().push_back(root);
+                    this.set_layout(stack, LayoutKind::Vertical);
+                    for &leaf in rest {
+                        leaf.detach(&mut this.tree).push_back(stack).finish();
+                    }
+                    stack
+                }
+            };
+            let columns = if master_on_left {
+                [master, stack]
+            } else {
+                [stack, master]
+            };
+            for column in columns {
+                column.detach(&mut this.tree).push_back(root).finish();
+            }
+            this.set_layout(root, LayoutKind::Horizontal);
+
+            let info = &mut this.tree.data.layout.info;
+            for column in columns {
+                info[column].size = 1.0;
+            }
+            info[root].total = 2.0;
+            if stack != rest[0] {
+                for &leaf in rest {
+                    info[leaf].size = 1.0;
+                }
+                info[stack].total = rest.len() as f32;
+            }
+
+            this.select(selection.unwrap_or(master));
+            true
+        })
     }
-}
 
-impl LayoutCore for TraditionalLayoutSystem {
-    fn draw_tree(&self, layout: LayoutId) -> String {
-        let tree = self.get_ascii_tree(self.root(layout));
-        let mut out = String::new();
-        ascii_tree::write_tree(&mut out, &tree).unwrap();
-        out
+    /// Toggles the lock on the selection's parent and returns the new state.
+    /// Returns false if the selection has no parent.
+    pub fn toggle_lock_of_selection_parent(&mut self, layout: LayoutId) -> bool {
+        let Some(parent) = self.selection(layout).parent(self.map()) else {
+            return false;
+        };
+        let info = &mut self.tree.data.layout.info[parent];
+        info.locked = !info.locked;
+        info.locked
     }
 
-    fn calculate_layout(
-        &self,
+    pub fn is_size_preserved(&self, layout: LayoutId, wid: WindowId) -> bool {
+        self.tree
+            .data
+            .window
+            .node_for(layout, wid)
+            .is_some_and(|node| self.tree.data.layout.info[node].preserve_size)
+    }
+
+    /// Re-applies the shares of every preserved window in `layout` whose
+    /// width or height drifted more than `PRESERVED_SIZE_TOLERANCE` from the
+    /// size it recorded. Windows without a recorded size record their current
+    /// one instead. Returns whether any share changed.
+    pub fn enforce_preserved_sizes(
+        &mut self,
         layout: LayoutId,
         screen: CGRect,
         stack_offset: f64,
+        focus_pop: f64,
         gaps: &crate::common::config::GapSettings,
         stack_line_thickness: f64,
         stack_line_horiz: crate::common::config::HorizontalPlacement,
         stack_line_vert: crate::common::config::VerticalPlacement,
-    ) -> Vec<(WindowId, CGRect)> {
-        let mut sizes = Vec::with_capacity(16);
-        let tiling_area = compute_tiling_area(screen, gaps);
-        self.tree.data.layout.apply_with_gaps(
-            &self.tree.map,
-            &self.tree.data.window,
-            self.root(layout),
-            tiling_area,
-            screen,
-            &mut sizes,
-            stack_offset,
-            gaps,
-            stack_line_thickness,
-            stack_line_horiz,
-            stack_line_vert,
-        );
-        sizes
-    }
-
-    fn visible_windows_in_layout(&self, layout: LayoutId) -> Vec<WindowId> {
+    ) -> bool {
         let root = self.root(layout);
-        self.visible_windows_under_internal(root)
-    }
+        let preserved: Vec<(NodeId, WindowId)> = root
+            .traverse_preorder(self.map())
+            .filter(|&node| self.tree.data.layout.info[node].preserve_size)
+            .filter_map(|node| self.window_at(node).map(|wid| (node, wid)))
+            .collect();
+        if preserved.is_empty() {
+            return false;
+        }
+        let frames: crate::common::collections::HashMap<WindowId, CGRect> = self
+            .calculate_layout(
+                layout,
+                screen,
+                stack_offset,
+                focus_pop,
+                gaps,
+                stack_line_thickness,
+                stack_line_horiz,
+                stack_line_vert,
+                None,
+            )
+            .into_iter()
+            .collect();
 
-    fn visible_windows_under_selection(&self, layout: LayoutId) -> Vec<WindowId> {
-        let selection = self.selection(layout);
-        self.visible_windows_under_internal(selection)
+        let mut changed = false;
+        for (node, wid) in preserved {
+            let Some(frame) = frames.get(&wid) else { continue };
+            if self.tree.data.layout.is_effectively_fullscreen(node) {
+                continue;
+            }
+            let Some((width, height)) = self.tree.data.layout.info[node].preserved_size else {
+                self.tree.data.layout.info[node].preserved_size =
+                    Some((frame.size.width, frame.size.height));
+                continue;
+            };
+            for (orientation, current, target) in [
+                (Orientation::Horizontal, frame.size.width, width),
+                (Orientation::Vertical, frame.size.height, height),
+            ] {
+                if current <= 0.0 || (current - target).abs() <= PRESERVED_SIZE_TOLERANCE {
+                    continue;
+                }
+                if let Some(sharing) = self.share_holder(node, orientation) {
+                    changed |= self.tree.data.layout.scale_share(
+                        &self.tree.map,
+                        sharing,
+                        target / current,
+                    );
+                }
+            }
+        }
+        changed
     }
 
-    fn selected_window(&self, layout: LayoutId) -> Option<WindowId> {
-        let selection = self.selection(layout);
-        self.tree.data.window.at(selection)
+    /// Forgets the sizes preserved windows recorded, so they adopt whatever
+    /// size they have after the change. Explicit resizes go through here.
+    fn rebaseline_preserved_sizes(&mut self, layout: LayoutId) {
+        let nodes: Vec<_> = self.root(layout).traverse_preorder(self.map()).collect();
+        for node in nodes {
+            self.tree.data.layout.info[node].preserved_size = None;
+        }
     }
 
-    fn contains_window(&self, layout: LayoutId, wid: WindowId) -> bool {
-        self.tree.data.window.node_for(layout, wid).is_some()
+    /// The nearest node at or above `node` whose parent splits space along
+    /// `orientation` between several children; its share decides how big
+    /// `node` is on that axis.
+    fn share_holder(&self, node: NodeId, orientation: Orientation) -> Option<NodeId> {
+        let map = self.map();
+        node.ancestors(map).find(|&ancestor| {
+            ancestor.parent(map).is_some_and(|parent| {
+                let kind = self.layout(parent);
+                !kind.is_group()
+                    && kind.orientation() == orientation
+                    && parent.children(map).nth(1).is_some()
+            })
+        })
     }
 
-    fn select_window(&mut self, layout: LayoutId, wid: WindowId) -> bool {
-        if let Some(node) = self.tree.data.window.node_for(layout, wid) {
-            self.select(node);
-            true
+    /// The node that actually joins `target`: the selection itself, or its
+    /// parent when the selection sits on the joining edge of a container.
+    fn join_source(&self, selection: NodeId, target: NodeId, direction: Direction) -> NodeId {
+        let map = self.map();
+        let Some(parent) = selection.parent(map) else {
+            return selection;
+        };
+        let parent_layout = self.layout(parent);
+        let is_edge = match direction {
+            Direction::Right | Direction::Down => selection.next_sibling(map).is_none(),
+            Direction::Left | Direction::Up => selection.prev_sibling(map).is_none(),
+        };
+        if parent_layout.orientation() == direction.orientation()
+            && !parent_layout.is_group()
+            && (is_edge || parent.children(map).count() == 2)
+            && target.parent(map) != Some(parent)
+            && !target.ancestors(map).any(|a| a == parent)
+        {
+            parent
         } else {
-            false
+            selection
         }
     }
 
-    fn add_window_after_selection(&mut self, layout: LayoutId, wid: WindowId) {
-        let selection = self.selection(layout);
-        let node = if selection.parent(self.map()).is_none() {
-            self.add_window_under(layout, selection, wid)
-        } else {
-            self.smart_window_insertion(layout, selection, wid)
-        };
-        self.select(node);
+    fn is_locked(&self, node: NodeId) -> bool {
+        self.tree.data.layout.info[node].locked
     }
 
-    fn remove_window(&mut self, wid: WindowId) {
-        let nodes: Vec<_> =
-            self.tree.data.window.take_nodes_for(wid).map(|(_, node)| node).collect();
-        for node in nodes {
-            node.detach(&mut self.tree).remove();
-        }
+    /// What joining `node` with `other` actually moves: the outermost locked
+    /// container around `node` that does not also hold `other`, so locked
+    /// containers are only ever joined as a whole.
+    fn join_unit(&self, node: NodeId, other: NodeId) -> NodeId {
+        let map = self.map();
+        node.ancestors(map)
+            .filter(|&a| self.is_locked(a) && !other.ancestors(map).any(|b| b == a))
+            .last()
+            .unwrap_or(node)
     }
 
-    fn remove_windows_for_app(&mut self, pid: pid_t) {
-        let nodes: Vec<_> =
-            self.tree.data.window.take_nodes_for_app(pid).map(|(_, _, node)| node).collect();
-        for node in nodes {
-            node.detach(&mut self.tree).remove();
-        }
+    /// Whether `selection` is a container that can take `target` in directly.
+    fn can_absorb_join_target(
+        &self,
+        selection: NodeId,
+        target: NodeId,
+        direction: Direction,
+    ) -> bool {
+        let map = self.map();
+        let selection_layout = self.layout(selection);
+        self.window_at(selection).is_none()
+            && !self.is_locked(selection)
+            && selection_layout.orientation() == direction.orientation()
+            && !selection_layout.is_group()
+            && !target.ancestors(map).any(|a| a == selection)
+            && !selection.ancestors(map).any(|a| a == target)
+            && target.parent(map) != Some(selection)
     }
 
-    fn set_windows_for_app(&mut self, layout: LayoutId, pid: pid_t, mut desired: Vec<WindowId>) {
-        let root = self.root(layout);
-        let mut current = root
-            .traverse_postorder(self.map())
-            .filter_map(|node| self.window_at(node).map(|wid| (wid, node)))
-            .filter(|(wid, _)| wid.pid == pid)
-            .collect::<Vec<_>>();
-        desired.sort_unstable();
-        current.sort_unstable();
-        debug_assert!(desired.iter().all(|wid| wid.pid == pid));
-        let mut desired = desired.into_iter().peekable();
-        let mut current = current.into_iter().peekable();
-        loop {
-            match (desired.peek(), current.peek()) {
-                (Some(des), Some((cur, _))) if des == cur => {
-                    desired.next();
-                    current.next();
-                }
-                (Some(des), None) => {
-                    self.add_window_after_selection(layout, *des);
-                    desired.next();
-                }
-                (Some(des), Some((cur, _))) if des < cur => {
-                    self.add_window_after_selection(layout, *des);
-                    desired.next();
+    fn root(&self, layout: LayoutId) -> NodeId {
+        self.layout_roots[layout].id()
+    }
+    fn selection(&self, layout: LayoutId) -> NodeId {
+        self.tree.data.selection.current_selection(self.root(layout))
+    }
+    fn map(&self) -> &NodeMap {
+        &self.tree.map
+    }
+    fn layout(&self, node: NodeId) -> LayoutKind {
+        self.tree.data.layout.kind(node)
+    }
+    fn set_layout(&mut self, node: NodeId, kind: LayoutKind) {
+        self.tree.data.layout.set_kind(node, kind);
+    }
+
+    /// Switches `node` to `kind` for an orientation or stacking command.
+    /// Returns false, leaving it as it is, if `node` is locked.
+    fn change_layout(&mut self, node: NodeId, kind: LayoutKind) -> bool {
+        if self.is_locked(node) {
+            return false;
+        }
+        self.set_layout(node, kind);
+        true
+    }
+
+    fn find_natural_join_target(&self, from: NodeId, direction: Direction) -> Option<JoinTarget> {
+        if let Some(parent) = from.parent(self.map()) {
+            let parent_layout = self.layout(parent);
+            if parent_layout.orientation() == direction.orientation() && !parent_layout.is_group() {
+                let child_count = parent.children(self.map()).count();
+                if child_count == 2
+                    && let Some(neighbor) = match direction {
+                        Direction::Right | Direction::Down => parent.next_sibling(self.map()),
+                        Direction::Left | Direction::Up => parent.prev_sibling(self.map()),
+                    }
+                {
+                    return Some(JoinTarget::ParentNeighbor(neighbor));
                 }
-                (_, Some((_, node))) => {
-                    if self.tree.data.layout.info[*node].is_fullscreen {
-                        current.next();
-                    } else {
-                        node.detach(&mut self.tree).remove();
-                        current.next();
+                let is_edge = match direction {
+                    Direction::Right | Direction::Down => from.next_sibling(self.map()).is_none(),
+                    Direction::Left | Direction::Up => from.prev_sibling(self.map()).is_none(),
+                };
+                if is_edge {
+                    let neighbor = match direction {
+                        Direction::Right | Direction::Down => parent.next_sibling(self.map()),
+                        Direction::Left | Direction::Up => parent.prev_sibling(self.map()),
+                    };
+                    if let Some(neighbor) = neighbor {
+                        return Some(JoinTarget::ParentNeighbor(neighbor));
                     }
                 }
-                (None, None) => break,
             }
         }
+        if let Some(sibling) = self.find_direct_sibling_target(from, direction) {
+            return Some(JoinTarget::Sibling(sibling));
+        }
+        if let Some(stack_neighbor) = self.find_stack_neighbor_target(from, direction) {
+            return Some(JoinTarget::StackNeighbor(stack_neighbor));
+        }
+        self.find_hierarchical_join_target(from, direction)
+            .map(JoinTarget::Hierarchical)
     }
 
-    fn has_windows_for_app(&self, layout: LayoutId, pid: pid_t) -> bool {
-        self.root(layout)
-            .traverse_postorder(self.map())
-            .filter_map(|node| self.window_at(node))
-            .any(|wid| wid.pid == pid)
-    }
-
-    fn on_window_resized(
-        &mut self,
-        layout: LayoutId,
-        wid: WindowId,
-        old_frame: CGRect,
-        new_frame: CGRect,
-        screen: CGRect,
-        gaps: &crate::common::config::GapSettings,
-    ) {
-        if let Some(node) = self.tree.data.window.node_for(layout, wid) {
-            if new_frame == screen {
-                self.tree.data.layout.set_fullscreen(node, true);
-            } else if old_frame == screen {
-                self.tree.data.layout.set_fullscreen(node, false);
-            } else {
-                let tiling = compute_tiling_area(screen, gaps);
-                if new_frame == tiling {
-                    self.tree.data.layout.set_fullscreen_within_gaps(node, true);
-                } else if old_frame == tiling {
-                    self.tree.data.layout.set_fullscreen_within_gaps(node, false);
+    fn find_stack_neighbor_target(&self, from: NodeId, direction: Direction) -> Option<NodeId> {
+        let parent = from.parent(self.map())?;
+        let parent_layout = self.layout(parent);
+        if !parent_layout.is_stacked() {
+            return None;
+        }
+        let children: Vec<_> = parent.children(self.map()).collect();
+        let current_idx = children.iter().position(|&c| c == from)?;
+        match direction {
+            Direction::Right | Direction::Down => children.get(current_idx + 1).copied(),
+            Direction::Left | Direction::Up => {
+                if current_idx > 0 {
+                    children.get(current_idx - 1).copied()
                 } else {
-                    self.set_frame_from_resize(node, old_frame, new_frame, screen);
+                    None
                 }
             }
         }
     }
-}
 
-impl LayoutNavigation for TraditionalLayoutSystem {
-    fn ascend_selection(&mut self, layout: LayoutId) -> bool {
-        if let Some(parent) = self.selection(layout).parent(self.map()) {
-            self.select(parent);
-            return true;
+    fn find_direct_sibling_target(&self, from: NodeId, direction: Direction) -> Option<NodeId> {
+        let _parent = from.parent(self.map())?;
+        match direction {
+            Direction::Right | Direction::Down => from.next_sibling(self.map()),
+            Direction::Left | Direction::Up => from.prev_sibling(self.map()),
         }
-        false
     }
 
-    fn descend_selection(&mut self, layout: LayoutId) -> bool {
-        if let Some(child) =
-            self.tree.data.selection.last_selection(self.map(), self.selection(layout))
-        {
-            self.select(child);
-            return true;
-        }
-        false
+    /// The child of `container` nearest to a node joining it from the
+    /// `direction` side: its first child when joining right or down, its
+    /// last when joining left or up. A window is its own nearest child.
+    fn nearest_child_for_joining(&self, container: NodeId, direction: Direction) -> NodeId {
+        let nearest = match direction {
+            Direction::Right | Direction::Down => container.first_child(self.map()),
+            Direction::Left | Direction::Up => container.last_child(self.map()),
+        };
+        nearest.unwrap_or(container)
     }
 
-    fn move_focus(
+    /// Joins across levels: the neighbor of the nearest ancestor that has one,
+    /// preferring ancestors whose parent lies along `direction`'s axis.
+    fn find_hierarchical_join_target(&self, from: NodeId, direction: Direction) -> Option<NodeId> {
+        let map = self.map();
+        let neighbor = from
+            .ancestors(map)
+            .skip(1)
+            .find_map(|ancestor| self.move_over(ancestor, direction))
+            .or_else(|| {
+                from.ancestors(map)
+                    .skip(1)
+                    .find_map(|ancestor| self.find_direct_sibling_target(ancestor, direction))
+            })?;
+        Some(self.nearest_child_for_joining(neighbor, direction))
+    }
+
+    fn perform_natural_join(
         &mut self,
         layout: LayoutId,
+        selection: NodeId,
+        target: NodeId,
         direction: Direction,
-    ) -> (Option<WindowId>, Vec<WindowId>) {
-        let selection = self.selection(layout);
-        if let Some(new_node) = self.traverse_internal(selection, direction) {
-            let focus_target = self.find_best_focus_target(new_node);
-            let Some((focus_node, focus_window)) = focus_target else {
-                return (None, vec![]);
-            };
-            let map = &self.tree.map;
-            let mut highest_revealed = focus_node;
-            for (node, parent) in focus_node.ancestors_with_parent(map) {
-                let Some(parent) = parent else { break };
-                let parent_layout = self.layout(parent);
-                if parent_layout.is_stacked()
-                    && parent_layout.orientation() != direction.orientation()
-                {
-                    continue;
+    ) {
+        let selection_parent = selection.parent(self.map());
+        let target_parent = target.parent(self.map());
+        let selection_stack_parent =
+            selection_parent.filter(|&parent| self.layout(parent).is_stacked());
+        let target_stack_parent = target_parent.filter(|&parent| self.layout(parent).is_stacked());
+        if selection_stack_parent
+            .or(target_stack_parent)
+            .is_some_and(|p| self.is_locked(p))
+        {
+            return;
+        }
+        match (selection_stack_parent, target_stack_parent) {
+            (Some(stack_parent), None) => {
+                target.detach(&mut self.tree).push_back(stack_parent);
+                self.select(stack_parent);
+                return;
+            }
+            (None, Some(stack_parent)) => {
+                selection.detach(&mut self.tree).push_back(stack_parent);
+                self.select(stack_parent);
+                return;
+            }
+            _ => {}
+        }
+        match (selection_parent, target_parent) {
+            (Some(sp), Some(tp)) if sp == tp => {
+                if self.layout(sp).is_stacked() {
+                    let new_layout = match direction.orientation() {
+                        Orientation::Horizontal => LayoutKind::Horizontal,
+                        Orientation::Vertical => LayoutKind::Vertical,
+                    };
+                    self.set_layout(sp, new_layout);
+                    self.select(sp);
+                    return;
                 }
-                if self.tree.data.selection.select_locally(map, node) && parent_layout.is_group() {
-                    highest_revealed = node;
+                let common_parent =
+                    self.find_or_create_smart_common_parent(layout, selection, target, direction);
+                let container_layout = LayoutKind::from(direction.orientation());
+                let new_layout = if self.layout(common_parent).is_stacked() {
+                    self.layout(common_parent)
+                } else {
+                    container_layout
+                };
+                if !self.is_locked(common_parent) {
+                    self.set_layout(common_parent, new_layout);
                 }
+                self.select(common_parent);
+            }
+            (Some(sp), Some(tp)) if self.are_containers_mergeable(sp, tp, direction) => {
+                self.merge_compatible_containers(layout, sp, tp, direction);
+            }
+            _ => {
+                let common_parent =
+                    self.find_or_create_smart_common_parent(layout, selection, target, direction);
+                let container_layout = LayoutKind::from(direction.orientation());
+                let new_layout = if self.layout(common_parent).is_stacked() {
+                    self.layout(common_parent)
+                } else {
+                    container_layout
+                };
+                if !self.is_locked(common_parent) {
+                    self.set_layout(common_parent, new_layout);
+                }
+                self.select(common_parent);
             }
-            let raise_windows = self.visible_windows_under_internal(highest_revealed);
-            (Some(focus_window), raise_windows)
-        } else {
-            (None, vec![])
         }
     }
 
-    fn window_in_direction(&self, layout: LayoutId, direction: Direction) -> Option<WindowId> {
-        self.window_in_direction_from(self.root(layout), direction)
-    }
-
-    fn move_selection(&mut self, layout: LayoutId, direction: Direction) -> bool {
-        let selection = self.selection(layout);
-        self.move_node(layout, selection, direction)
+    fn are_containers_mergeable(
+        &self,
+        container1: NodeId,
+        container2: NodeId,
+        direction: Direction,
+    ) -> bool {
+        let layout1 = self.layout(container1);
+        let layout2 = self.layout(container2);
+        layout1.orientation() == direction.orientation()
+            && layout2.orientation() == direction.orientation()
+            && !layout1.is_group()
+            && !layout2.is_group()
+            && !self.is_locked(container1)
+            && !self.is_locked(container2)
     }
 
-    fn move_selection_to_layout_after_selection(
+    fn merge_compatible_containers(
         &mut self,
-        from_layout: LayoutId,
-        to_layout: LayoutId,
+        layout: LayoutId,
+        container1: NodeId,
+        container2: NodeId,
+        direction: Direction,
     ) {
-        let from_sel = self.selection(from_layout);
-        let to_sel = self.selection(to_layout);
-        let map = &self.tree.map;
-        let Some(old_parent) = from_sel.parent(map) else { return };
-        let is_selection =
-            self.tree.data.selection.local_selection(map, old_parent) == Some(from_sel);
-        if to_sel.parent(self.map()).is_none() {
-            from_sel.detach(&mut self.tree).push_back(to_sel);
-        } else {
-            from_sel.detach(&mut self.tree).insert_after(to_sel);
+        let common_parent =
+            self.find_or_create_smart_common_parent(layout, container1, container2, direction);
+        let container_layout = LayoutKind::from(direction.orientation());
+        self.set_layout(common_parent, container_layout);
+        self.select(common_parent);
+    }
+}
+
+impl Drop for TraditionalLayoutSystem {
+    fn drop(&mut self) {
+        // Only reached with layouts left when `shutdown` was skipped. Forgetting
+        // the roots leaks their nodes but avoids the `OwnedNode` drop panic.
+        if !self.layout_roots.is_empty() {
+            warn!(
+                layouts = self.layout_roots.len(),
+                "Forgetting layouts that were never removed"
+            );
         }
-        if is_selection {
-            for node in from_sel.ancestors(&self.tree.map) {
-                if node == old_parent {
-                    break;
-                }
-                self.tree.data.selection.select_locally(&self.tree.map, node);
-            }
+        for (_, node) in self.layout_roots.drain() {
+            std::mem::forget(node);
         }
     }
 }
 
-impl LayoutResizable for TraditionalLayoutSystem {
-    fn resize_selection_by(&mut self, layout: LayoutId, amount: f64) {
-        let selection = self.selection(layout);
-        if let Some(_focused_window) = self.window_at(selection) {
-            let candidates = selection
-                .ancestors(self.map())
-                .filter(|&node| {
-                    if let Some(parent) = node.parent(self.map()) {
-                        !self.layout(parent).is_group()
-                    } else {
-                        false
-                    }
-                })
-                .collect::<Vec<_>>();
-            let resized = candidates.iter().any(|&node| {
-                self.resize_internal(node, amount, crate::layout_engine::Direction::Right)
-            }) || candidates.iter().any(|&node| {
-                self.resize_internal(node, amount, crate::layout_engine::Direction::Down)
-            });
-            if !resized {
-                let _ = candidates.iter().any(|&node| {
-                    self.resize_internal(node, amount, crate::layout_engine::Direction::Left)
-                }) || candidates.iter().any(|&node| {
-                    self.resize_internal(node, amount, crate::layout_engine::Direction::Up)
-                });
-            }
+impl LayoutLifecycle for TraditionalLayoutSystem {
+    fn create_layout(&mut self) -> LayoutId {
+        let root = OwnedNode::new_root_in(&mut self.tree, "layout_root");
+        self.layout_roots.insert(root)
+    }
+
+    fn clone_layout(&mut self, layout: LayoutId) -> LayoutId {
+        let source_root = self.layout_roots[layout].id();
+        let cloned = source_root.deep_copy(&mut self.tree).make_root("layout_root");
+        let cloned_root = cloned.id();
+        let dest_layout = self.layout_roots.insert(cloned);
+        for (src, dest) in std::iter::zip(
+            source_root.traverse_preorder(&self.tree.map),
+            cloned_root.traverse_preorder(&self.tree.map),
+        ) {
+            self.tree
+                .data
+                .dispatch_event(&self.tree.map, TreeEvent::Copied { src, dest, dest_layout });
         }
+        dest_layout
     }
 
-    fn rebalance(&mut self, layout: LayoutId) {
-        let root = self.root(layout);
-        self.rebalance_node(root)
+    fn remove_layout(&mut self, layout: LayoutId) {
+        self.history.remove(layout);
+        self.layout_roots.remove(layout).unwrap().remove(&mut self.tree)
+    }
+
+    fn shutdown(&mut self) {
+        let layouts: Vec<_> = self.layout_roots.keys().collect();
+        for layout in layouts {
+            self.remove_layout(layout);
+        }
+        debug_assert!(
+            self.tree.map.is_empty(),
+            "{} nodes outlived their layouts",
+            self.tree.map.len()
+        );
     }
 }
 
-impl LayoutSplittable for TraditionalLayoutSystem {
-    fn split_selection(&mut self, layout: LayoutId, kind: LayoutKind) {
-        let selection = self.selection(layout);
-        self.nest_in_container_internal(layout, selection, kind);
+impl LayoutCore for TraditionalLayoutSystem {
+    fn draw_tree(&self, layout: LayoutId) -> String {
+        self.draw_tree_with_options(layout, &TreeRenderOptions::default())
     }
 
-    fn toggle_tile_orientation(&mut self, layout: LayoutId) {
-        use crate::layout_engine::LayoutKind;
-        let map = self.map();
-        let selection_node = self.selection(layout);
-        let target_node = match selection_node.parent(map) {
-            Some(p) => p,
-            None => self.root(layout),
-        };
-        let current_kind = self.layout(target_node);
-        if current_kind.is_group() {
-            return;
+    fn draw_tree_with_options(&self, layout: LayoutId, options: &TreeRenderOptions) -> String {
+        let root = self.root(layout);
+        let focus_path: Vec<NodeId> = self.selection(layout).ancestors(self.map()).collect();
+        let mut budget = TreeRenderBudget::new(options);
+        let mut out = String::new();
+        if budget.take() {
+            let tree = self.get_ascii_tree(root, 0, options, &focus_path, &mut budget);
+            ascii_tree::write_tree(&mut out, &tree).unwrap();
         }
-        let new_kind = match current_kind {
-            LayoutKind::Horizontal => LayoutKind::Vertical,
-            LayoutKind::Vertical => LayoutKind::Horizontal,
-            other => other,
-        };
-        self.set_layout(target_node, new_kind);
-        self.rebalance(layout);
+        budget.finish(options, &mut out);
+        out
     }
 
-    fn join_selection_with_direction(&mut self, layout: LayoutId, direction: Direction) {
-        let mut selection = self.selection(layout);
-        if let Some(target) = self.find_natural_join_target(selection, direction) {
-            let map = self.map();
-            if let Some(parent) = selection.parent(map) {
-                let parent_layout = self.layout(parent);
-                let is_edge = match direction {
-                    Direction::Right | Direction::Down => selection.next_sibling(map).is_none(),
-                    Direction::Left | Direction::Up => selection.prev_sibling(map).is_none(),
-                };
-                if parent_layout.orientation() == direction.orientation()
-                    && !parent_layout.is_group()
-                    && (is_edge || parent.children(map).count() == 2)
-                    && target.parent(map) != Some(parent)
-                    && !target.ancestors(map).any(|a| a == parent)
-                {
-                    selection = parent;
-                }
-            }
-            let selection_layout = self.layout(selection);
-            let target_is_ancestor = target.ancestors(map).any(|a| a == selection);
-            let selection_is_ancestor = selection.ancestors(map).any(|a| a == target);
-            if self.window_at(selection).is_none()
-                && selection_layout.orientation() == direction.orientation()
-                && !selection_layout.is_group()
-                && !target_is_ancestor
-                && !selection_is_ancestor
-                && target.parent(map) != Some(selection)
-            {
-                match direction {
-                    Direction::Right | Direction::Down => {
-                        target.detach(&mut self.tree).push_back(selection);
-                    }
-                    Direction::Left | Direction::Up => {
-                        if let Some(first) = selection.first_child(map) {
-                            target.detach(&mut self.tree).insert_before(first);
-                        } else {
-                            target.detach(&mut self.tree).push_back(selection);
-                        }
-                    }
-                }
-                self.select(selection);
-                return;
-            }
-            self.perform_natural_join(layout, selection, target, direction);
-            if self.tree.data.window.at(selection).is_some() {
-                self.select(selection);
-            } else {
-                let _ = self.descend_selection(layout);
+    fn layout_fingerprint(&self, layout: LayoutId) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = fingerprint_hasher();
+        let map = self.map();
+        for node in self.root(layout).traverse_preorder(map) {
+            let info = &self.tree.data.layout.info[node];
+            info.kind.hash(&mut hasher);
+            node.children(map).count().hash(&mut hasher);
+            quantize_share(info.size).hash(&mut hasher);
+            quantize_share(info.total).hash(&mut hasher);
+            self.window_at(node).hash(&mut hasher);
+            info.is_fullscreen.hash(&mut hasher);
+            info.is_fullscreen_within_gaps.hash(&mut hasher);
+            info.locked.hash(&mut hasher);
+            if info.kind.is_group() {
+                // The selected member is the one a stack shows on top.
+                let selected = self.tree.data.selection.local_selection(map, node);
+                selected
+                    .and_then(|selected| node.children(map).position(|child| child == selected))
+                    .hash(&mut hasher);
             }
         }
+        hasher.finish()
     }
 
-    fn unjoin_selection(&mut self, layout: LayoutId) {
+    fn calculate_layout(
+        &self,
+        layout: LayoutId,
+        screen: CGRect,
+        stack_offset: f64,
+        focus_pop: f64,
+        gaps: &crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+        stack_line_horiz: crate::common::config::HorizontalPlacement,
+        stack_line_vert: crate::common::config::VerticalPlacement,
+        is_positionable: Option<&dyn Fn(WindowId) -> bool>,
+    ) -> Vec<(WindowId, CGRect)> {
+        let mut sizes = Vec::with_capacity(16);
+        let tiling_area = compute_tiling_area(screen, gaps);
+        self.tree.data.layout.apply_with_gaps(
+            &self.tree.map,
+            &self.tree.data.window,
+            self.root(layout),
+            tiling_area,
+            screen,
+            &mut sizes,
+            stack_offset,
+            focus_pop,
+            gaps,
+            stack_line_thickness,
+            stack_line_horiz,
+            stack_line_vert,
+            is_positionable,
+        );
+        sizes
+    }
+
+    fn visible_windows_in_layout(&self, layout: LayoutId) -> Vec<WindowId> {
+        let root = self.root(layout);
+        self.visible_windows_under_internal(root)
+    }
+
+    fn visible_windows_under_selection(&self, layout: LayoutId) -> Vec<WindowId> {
         let selection = self.selection(layout);
-        if let Some(parent) = selection.parent(&self.tree.map) {
-            if let Some(grandparent) = parent.parent(&self.tree.map) {
-                let children: Vec<_> = parent.children(&self.tree.map).collect();
-                if children.is_empty() {
-                    return;
-                }
-                let local_selected_child =
-                    self.tree.data.selection.local_selection(&self.tree.map, parent);
-                for child in children.iter() {
-                    child.detach(&mut self.tree).push_back(grandparent);
-                }
-                parent.detach(&mut self.tree).remove();
-                if let Some(sel_child) = local_selected_child {
-                    self.select(sel_child);
-                } else if let Some(first_child) = grandparent.first_child(&self.tree.map) {
-                    self.select(first_child);
-                }
-            } else {
-                let children: Vec<_> = parent.children(&self.tree.map).collect();
-                if children.len() == 2 {
-                    self.remove_unnecessary_container_internal(parent);
-                }
-            }
-        }
+        self.visible_windows_under_internal(selection)
     }
-}
 
-impl LayoutStacking for TraditionalLayoutSystem {
-    fn apply_stacking_to_parent_of_selection(
-        &mut self,
-        layout: LayoutId,
-        default_orientation: crate::common::config::StackDefaultOrientation,
-    ) -> Vec<WindowId> {
+    fn selected_window(&self, layout: LayoutId) -> Option<WindowId> {
         let selection = self.selection(layout);
-        let target_container = if self.tree.data.window.at(selection).is_some() {
-            selection.parent(self.map())
+        self.tree.data.window.at(selection)
+    }
+
+    fn contains_window(&self, layout: LayoutId, wid: WindowId) -> bool {
+        self.tree.data.window.node_for(layout, wid).is_some()
+    }
+
+    fn select_window(&mut self, layout: LayoutId, wid: WindowId) -> bool {
+        if let Some(node) = self.tree.data.window.node_for(layout, wid) {
+            self.select(node);
+            true
         } else {
-            Some(selection)
-        };
-        if let Some(container) = target_container {
-            let current_layout = self.layout(container);
-            let new_layout = match current_layout {
-                LayoutKind::HorizontalStack => Some(LayoutKind::VerticalStack),
-                LayoutKind::VerticalStack => Some(LayoutKind::HorizontalStack),
-                LayoutKind::Horizontal => match default_orientation {
-                    crate::common::config::StackDefaultOrientation::Perpendicular => {
-                        Some(LayoutKind::VerticalStack)
-                    }
-                    crate::common::config::StackDefaultOrientation::Same => {
-                        Some(LayoutKind::HorizontalStack)
-                    }
-                    crate::common::config::StackDefaultOrientation::Horizontal => {
-                        Some(LayoutKind::HorizontalStack)
-                    }
-                    crate::common::config::StackDefaultOrientation::Vertical => {
-                        Some(LayoutKind::VerticalStack)
-                    }
-                },
-                LayoutKind::Vertical => match default_orientation {
-                    crate::common::config::StackDefaultOrientation::Perpendicular => {
-                        Some(LayoutKind::HorizontalStack)
-                    }
-                    crate::common::config::StackDefaultOrientation::Same => {
-                        Some(LayoutKind::VerticalStack)
-                    }
-                    crate::common::config::StackDefaultOrientation::Horizontal => {
-                        Some(LayoutKind::HorizontalStack)
-                    }
-                    crate::common::config::StackDefaultOrientation::Vertical => {
-                        Some(LayoutKind::VerticalStack)
-                    }
-                },
-            };
-            if let Some(nl) = new_layout {
-                self.set_layout(container, nl);
-                if nl.is_stacked()
-                    && let Some(first_child) = container.first_child(self.map())
-                {
-                    self.select(first_child);
-                }
-                return self.visible_windows_under_internal(container);
-            }
+            false
         }
-        vec![]
     }
 
-    fn unstack_parent_of_selection(
+    fn add_window_after_selection(&mut self, layout: LayoutId, wid: WindowId) {
+        let (node, _) = self.insert_after_selection_internal(layout, wid);
+        self.select(node);
+    }
+
+    fn insert_window_after_selection(&mut self, layout: LayoutId, wid: WindowId) {
+        let (node, was_empty) = self.insert_after_selection_internal(layout, wid);
+        if was_empty {
+            self.select(node);
+        }
+    }
+
+    fn remove_window(&mut self, wid: WindowId) {
+        let nodes: Vec<_> =
+            self.tree.data.window.take_nodes_for(wid).map(|(_, node)| node).collect();
+        for node in nodes {
+            node.detach(&mut self.tree).remove();
+        }
+    }
+
+    fn remove_windows_for_app(&mut self, pid: pid_t) {
+        let nodes: Vec<_> =
+            self.tree.data.window.take_nodes_for_app(pid).map(|(_, _, node)| node).collect();
+        for node in nodes {
+            node.detach(&mut self.tree).remove();
+        }
+    }
+
+    fn set_windows_for_app(&mut self, layout: LayoutId, pid: pid_t, desired: Vec<WindowId>) {
+        self.sync_windows_for_app(layout, pid, desired, false);
+    }
+
+    fn has_windows_for_app(&self, layout: LayoutId, pid: pid_t) -> bool {
+        self.root(layout)
+            .traverse_postorder(self.map())
+            .filter_map(|node| self.window_at(node))
+            .any(|wid| wid.pid == pid)
+    }
+
+    fn on_window_resized(
         &mut self,
         layout: LayoutId,
-        default_orientation: crate::common::config::StackDefaultOrientation,
-    ) -> Vec<WindowId> {
-        let selection = self.selection(layout);
-        let target_container = if self.tree.data.window.at(selection).is_some() {
-            let map = self.map();
-            selection
-                .ancestors(map)
-                .skip(1)
-                .find(|&ancestor| self.layout(ancestor).is_stacked())
-        } else {
-            let selection_layout = self.layout(selection);
-            if selection_layout.is_stacked() {
-                Some(selection)
+        wid: WindowId,
+        old_frame: CGRect,
+        new_frame: CGRect,
+        screen: CGRect,
+        gaps: &crate::common::config::GapSettings,
+    ) {
+        if let Some(node) = self.tree.data.window.node_for(layout, wid) {
+            if new_frame == screen {
+                self.tree.data.layout.set_fullscreen(node, true);
+            } else if old_frame == screen {
+                self.tree.data.layout.set_fullscreen(node, false);
             } else {
-                let map = self.map();
-                selection.children(map).find(|&child| self.layout(child).is_stacked())
-            }
-        };
-        if let Some(container) = target_container {
-            let new_layout = match self.layout(container) {
-                LayoutKind::HorizontalStack => match default_orientation {
-                    crate::common::config::StackDefaultOrientation::Perpendicular => {
-                        Some(LayoutKind::Vertical)
-                    }
-                    crate::common::config::StackDefaultOrientation::Same => {
-                        Some(LayoutKind::Horizontal)
-                    }
-                    crate::common::config::StackDefaultOrientation::Horizontal => {
-                        Some(LayoutKind::Horizontal)
-                    }
-                    crate::common::config::StackDefaultOrientation::Vertical => {
-                        Some(LayoutKind::Vertical)
-                    }
-                },
-                LayoutKind::VerticalStack => match default_orientation {
-                    crate::common::config::StackDefaultOrientation::Perpendicular => {
-                        Some(LayoutKind::Horizontal)
-                    }
-                    crate::common::config::StackDefaultOrientation::Same => {
-                        Some(LayoutKind::Vertical)
-                    }
-                    crate::common::config::StackDefaultOrientation::Horizontal => {
-                        Some(LayoutKind::Horizontal)
-                    }
-                    crate::common::config::StackDefaultOrientation::Vertical => {
-                        Some(LayoutKind::Vertical)
-                    }
-                },
-                _ => None,
-            };
-            if let Some(nl) = new_layout {
-                self.set_layout(container, nl);
-                return self.visible_windows_under_internal(container);
+                let tiling = compute_tiling_area(screen, gaps);
+                if new_frame == tiling {
+                    self.tree.data.layout.set_fullscreen_within_gaps(node, true);
+                } else if old_frame == tiling {
+                    self.tree.data.layout.set_fullscreen_within_gaps(node, false);
+                } else {
+                    self.rebaseline_preserved_sizes(layout);
+                    self.set_frame_from_resize(node, old_frame, new_frame, screen);
+                }
             }
         }
-        vec![]
     }
+}
 
-    fn parent_of_selection_is_stacked(&self, layout: LayoutId) -> bool {
-        let selection = self.selection(layout);
-        if self.tree.data.window.at(selection).is_some() {
-            let map = self.map();
-            return selection
-                .ancestors(map)
-                .skip(1)
-                .any(|ancestor| self.layout(ancestor).is_stacked());
-        }
-        if self.layout(selection).is_stacked() {
+impl LayoutNavigation for TraditionalLayoutSystem {
+    fn ascend_selection(&mut self, layout: LayoutId) -> bool {
+        if let Some(parent) = self.selection(layout).parent(self.map()) {
+            self.select(parent);
             return true;
         }
-        let map = self.map();
-        selection.children(map).any(|child| self.layout(child).is_stacked())
+        false
     }
-}
 
-impl LayoutSwappable for TraditionalLayoutSystem {
-    fn swap_windows(&mut self, layout: LayoutId, a: WindowId, b: WindowId) -> bool {
-        let node_a = match self.tree.data.window.node_for(layout, a) {
-            Some(n) => n,
-            None => return false,
-        };
-        let node_b = match self.tree.data.window.node_for(layout, b) {
-            Some(n) => n,
-            None => return false,
-        };
-        if node_a == node_b {
-            return false;
-        }
-        let wa = self.tree.data.window.at(node_a);
-        let wb = self.tree.data.window.at(node_b);
-        match (wa, wb) {
-            (None, None) => return false,
-            _ => {
-                if let Some(w) = wa {
-                    self.tree.data.window.windows.insert(node_b, w);
-                } else {
-                    self.tree.data.window.windows.remove(node_b);
-                }
-                if let Some(w) = wb {
-                    self.tree.data.window.windows.insert(node_a, w);
-                } else {
-                    self.tree.data.window.windows.remove(node_a);
-                }
-            }
+    fn descend_selection(&mut self, layout: LayoutId) -> bool {
+        if let Some(child) =
+            self.tree.data.selection.last_selection(self.map(), self.selection(layout))
+        {
+            self.select(child);
+            return true;
         }
-        if let Some(infos) = self.tree.data.window.window_nodes.get_mut(&a) {
-            for info in &mut infos.0 {
-                if info.layout == layout {
-                    info.node = node_b;
+        false
+    }
+
+    fn move_focus(
+        &mut self,
+        layout: LayoutId,
+        direction: Direction,
+    ) -> (Option<WindowId>, Vec<WindowId>) {
+        if let Some((focus_node, focus_window)) = self.focus_target_node(layout, direction) {
+            let map = &self.tree.map;
+            let mut highest_revealed = focus_node;
+            for (node, parent) in focus_node.ancestors_with_parent(map) {
+                let Some(parent) = parent else { break };
+                let parent_layout = self.layout(parent);
+                if parent_layout.is_stacked()
+                    && !Self::enters_stack_at_edge(parent_layout, direction)
+                {
+                    continue;
                 }
-            }
-        }
-        if let Some(infos) = self.tree.data.window.window_nodes.get_mut(&b) {
-            for info in &mut infos.0 {
-                if info.layout == layout {
-                    info.node = node_a;
+                if self.tree.data.selection.select_locally(map, node) && parent_layout.is_group() {
+                    highest_revealed = node;
                 }
             }
-        }
-        true
-    }
-}
-
-impl LayoutFullscreen for TraditionalLayoutSystem {
-    fn toggle_fullscreen_of_selection(&mut self, layout: LayoutId) -> Vec<WindowId> {
-        let node = self.selection(layout);
-        if self.tree.data.layout.toggle_fullscreen(node) {
-            self.visible_windows_under_internal(node)
+            let raise_windows = self.visible_windows_under_internal(highest_revealed);
+            (Some(focus_window), raise_windows)
         } else {
-            vec![]
+            (None, vec![])
         }
     }
 
-    fn toggle_fullscreen_within_gaps_of_selection(&mut self, layout: LayoutId) -> Vec<WindowId> {
-        let node = self.selection(layout);
-        if self.tree.data.layout.toggle_fullscreen_within_gaps(node) {
-            self.visible_windows_under_internal(node)
-        } else {
-            vec![]
-        }
+    fn window_in_direction(&self, layout: LayoutId, direction: Direction) -> Option<WindowId> {
+        self.window_in_direction_from(self.root(layout), direction)
     }
-}
 
-impl TraditionalLayoutSystem {
-    pub(crate) fn collect_group_containers_in_selection_path(
+    fn focus_target_in_direction(
         &self,
         layout: LayoutId,
-        screen: CGRect,
-        stack_offset: f64,
-        gaps: &crate::common::config::GapSettings,
-        stack_line_thickness: f64,
-        stack_line_horiz: crate::common::config::HorizontalPlacement,
-        stack_line_vert: crate::common::config::VerticalPlacement,
-    ) -> Vec<crate::layout_engine::engine::GroupContainerInfo> {
-        use self::StackLayoutResult;
-        use crate::layout_engine::LayoutKind::*;
-        let mut out = Vec::new();
+        direction: Direction,
+    ) -> Option<WindowId> {
+        self.focus_target_node(layout, direction).map(|(_, wid)| wid)
+    }
+
+    fn move_selection(&mut self, layout: LayoutId, direction: Direction) -> bool {
+        self.with_undo(layout, |this| this.move_selection_internal(layout, direction))
+    }
+
+    fn move_selection_to_layout_after_selection(
+        &mut self,
+        from_layout: LayoutId,
+        to_layout: LayoutId,
+    ) {
+        let from_sel = self.selection(from_layout);
+        let to_sel = self.selection(to_layout);
         let map = &self.tree.map;
-        let tiling_area = compute_tiling_area(screen, gaps);
-        let mut node = self.root(layout);
-        let mut rect = tiling_area;
-        loop {
-            if self.tree.data.layout.is_effectively_fullscreen(node) {
-                out.clear();
-                break;
-            }
-            let kind = self.tree.data.layout.kind(node);
-            let children: Vec<_> = node.children(map).collect();
-            if matches!(kind, HorizontalStack | VerticalStack) {
-                if children.is_empty() {
-                    break;
-                }
-                let local_sel =
-                    self.tree.data.selection.local_selection(map, node).unwrap_or(children[0]);
-                let selected_index = children.iter().position(|&c| c == local_sel).unwrap_or(0);
-                if self.tree.data.layout.is_effectively_fullscreen(local_sel) {
-                    out.clear();
+        let Some(old_parent) = from_sel.parent(map) else { return };
+        let is_selection =
+            self.tree.data.selection.local_selection(map, old_parent) == Some(from_sel);
+        if to_sel.parent(self.map()).is_none() {
+            from_sel.detach(&mut self.tree).push_back(to_sel);
+        } else {
+            from_sel.detach(&mut self.tree).insert_after(to_sel);
+        }
+        if is_selection {
+            for node in from_sel.ancestors(&self.tree.map) {
+                if node == old_parent {
                     break;
                 }
-                let ui_selected_index = if matches!(kind, VerticalStack) {
-                    children.len().saturating_sub(1).saturating_sub(selected_index)
-                } else {
-                    selected_index
-                };
-                out.push(crate::layout_engine::engine::GroupContainerInfo {
-                    node_id: node,
-                    container_kind: kind,
-                    frame: rect,
-                    total_count: children.len(),
-                    selected_index: ui_selected_index,
-                    window_ids: children
-                        .iter()
-                        .filter_map(|&child| self.window_at(child))
-                        .collect(),
-                });
-                let mut container_rect = rect;
-                let reserve = stack_line_thickness.max(0.0);
-                let is_horizontal = matches!(kind, HorizontalStack);
-                container_rect = adjust_stack_container_rect(
-                    container_rect,
-                    is_horizontal,
-                    reserve,
-                    stack_line_horiz,
-                    stack_line_vert,
-                );
-                let layout_res = StackLayoutResult::new(
-                    container_rect,
-                    children.len(),
-                    stack_offset,
-                    is_horizontal,
-                );
-                rect = layout_res.get_focused_frame_for_index(selected_index, selected_index);
-                node = local_sel;
-                continue;
-            }
-            if let Some(next) = self
-                .tree
-                .data
-                .selection
-                .local_selection(map, node)
-                .or_else(|| node.first_child(map))
-            {
-                rect = self.calculate_child_frame_in_container(node, next, rect, gaps);
-                node = next;
-                continue;
+                self.tree.data.selection.select_locally(&self.tree.map, node);
             }
-            break;
         }
-        out
     }
 
-    fn calculate_child_frame_in_axis(
-        &self,
-        parent_rect: CGRect,
-        siblings: &[NodeId],
-        child_index: usize,
-        horizontal: bool,
-        gaps: &crate::common::config::GapSettings,
-    ) -> CGRect {
-        use objc2_core_foundation::{CGPoint, CGSize};
-        if siblings.is_empty() || child_index >= siblings.len() {
-            return parent_rect;
-        }
-        let total: f32 = siblings.iter().map(|&child| self.tree.data.layout.info[child].size).sum();
-        let inner_gap = if horizontal {
-            gaps.inner.horizontal
-        } else {
-            gaps.inner.vertical
-        };
-        let axis_len = if horizontal {
-            parent_rect.size.width
-        } else {
-            parent_rect.size.height
+    fn selection_path(&self, layout: LayoutId) -> Vec<usize> {
+        self.selection(layout)
+            .path_from(self.root(layout), self.map())
+            .unwrap_or_default()
+    }
+
+    fn select_by_path(&mut self, layout: LayoutId, path: &[usize]) -> bool {
+        let Some(node) = self.root(layout).descendant_at(path, self.map()) else {
+            return false;
         };
-        let total_gap = (siblings.len().saturating_sub(1)) as f64 * inner_gap;
-        let usable_axis = if inner_gap == 0.0 {
-            axis_len
-        } else {
-            (axis_len - total_gap).max(0.0)
+        self.select(node);
+        true
+    }
+}
+
+impl LayoutResizable for TraditionalLayoutSystem {
+    fn resize_selection_by(&mut self, layout: LayoutId, amount: f64) {
+        if let Some(candidates) = self.resize_candidates(layout) {
+            let resized = candidates.iter().any(|&node| {
+                self.resize_internal(node, amount, crate::layout_engine::Direction::Right)
+            }) || candidates.iter().any(|&node| {
+                self.resize_internal(node, amount, crate::layout_engine::Direction::Down)
+            });
+            if !resized {
+                let _ = candidates.iter().any(|&node| {
+                    self.resize_internal(node, amount, crate::layout_engine::Direction::Left)
+                }) || candidates.iter().any(|&node| {
+                    self.resize_internal(node, amount, crate::layout_engine::Direction::Up)
+                });
+            }
+        }
+    }
+
+    fn resize_selection_in_direction(
+        &mut self,
+        layout: LayoutId,
+        direction: Direction,
+        amount: f64,
+    ) {
+        if let Some(candidates) = self.resize_candidates(layout) {
+            let _ = candidates.iter().any(|&node| self.resize_internal(node, amount, direction))
+                || candidates
+                    .iter()
+                    .any(|&node| self.resize_internal(node, amount, direction.opposite()));
+        }
+    }
+
+    fn rebalance(&mut self, layout: LayoutId) {
+        let root = self.root(layout);
+        self.rebalance_node(root)
+    }
+}
+
+impl LayoutSplittable for TraditionalLayoutSystem {
+    fn split_selection(&mut self, layout: LayoutId, kind: LayoutKind) {
+        self.with_undo(layout, |this| this.split_selection_internal(layout, kind))
+    }
+
+    fn toggle_tile_orientation(&mut self, layout: LayoutId) {
+        use crate::layout_engine::LayoutKind;
+        let map = self.map();
+        let selection_node = self.selection(layout);
+        let target_node = match selection_node.parent(map) {
+            Some(p) => p,
+            None => self.root(layout),
         };
-        let mut offset = if horizontal {
-            parent_rect.origin.x
-        } else {
-            parent_rect.origin.y
+        let current_kind = self.layout(target_node);
+        if current_kind.is_group() {
+            return;
+        }
+        let new_kind = match current_kind {
+            LayoutKind::Horizontal => LayoutKind::Vertical,
+            LayoutKind::Vertical => LayoutKind::Horizontal,
+            other => other,
         };
-        for i in 0..child_index {
-            let ratio = f64::from(self.tree.data.layout.info[siblings[i]].size) / f64::from(total);
-            let seg_len = usable_axis * ratio;
-            offset += seg_len;
-            if i < siblings.len() - 1 {
-                offset += inner_gap;
-            }
+        if self.change_layout(target_node, new_kind) {
+            self.rebalance(layout);
         }
-        let ratio =
-            f64::from(self.tree.data.layout.info[siblings[child_index]].size) / f64::from(total);
-        let seg_len = usable_axis * ratio;
-        if horizontal {
-            CGRect::new(
-                CGPoint::new(offset, parent_rect.origin.y),
-                CGSize::new(seg_len, parent_rect.size.height),
-            )
+    }
+
+    fn rotate_subtree(&mut self, layout: LayoutId) {
+        let map = self.map();
+        let selection = self.selection(layout);
+        let start = if selection.is_empty(map) {
+            selection.parent(map).unwrap_or_else(|| self.root(layout))
         } else {
-            CGRect::new(
-                CGPoint::new(parent_rect.origin.x, offset),
-                CGSize::new(parent_rect.size.width, seg_len),
-            )
+            selection
+        };
+        let containers: Vec<NodeId> = start
+            .traverse_preorder(map)
+            .filter(|&node| !node.is_empty(map) && !self.is_locked(node))
+            .collect();
+        for node in containers {
+            self.tree.data.layout.rotate_kind(node);
         }
     }
 
-    fn calculate_child_frame_in_container(
-        &self,
-        parent_node: NodeId,
-        child_node: NodeId,
-        parent_rect: CGRect,
-        gaps: &crate::common::config::GapSettings,
-    ) -> CGRect {
-        let parent_kind = self.tree.data.layout.kind(parent_node);
-        let map = &self.tree.map;
-        let siblings: Vec<_> = parent_node.children(map).collect();
-        let child_index = siblings.iter().position(|&n| n == child_node).unwrap_or(0);
-        match parent_kind {
-            crate::layout_engine::LayoutKind::Horizontal => {
-                self.calculate_child_frame_in_axis(parent_rect, &siblings, child_index, true, gaps)
+    fn rotate_layout(&mut self, layout: LayoutId, clockwise: bool) {
+        // Clockwise, the top edge turns to the right, so vertical containers
+        // come out right to left; counter-clockwise it is horizontal ones.
+        // Stacks only change axis, keeping their member order. Locked
+        // containers keep both their kind and their order.
+        let reversed = if clockwise {
+            Orientation::Vertical
+        } else {
+            Orientation::Horizontal
+        };
+        self.with_undo(layout, |this| {
+            for node in this.tiled_containers_along(layout, reversed) {
+                if !this.is_locked(node) {
+                    this.reverse_children(node);
+                }
             }
-            crate::layout_engine::LayoutKind::Vertical => {
-                self.calculate_child_frame_in_axis(parent_rect, &siblings, child_index, false, gaps)
+            let map = this.map();
+            let containers: Vec<NodeId> = this
+                .root(layout)
+                .traverse_preorder(map)
+                .filter(|&n| !n.is_empty(map) && !this.is_locked(n))
+                .collect();
+            for node in containers {
+                this.tree.data.layout.rotate_kind(node);
             }
-            crate::layout_engine::LayoutKind::HorizontalStack
-            | crate::layout_engine::LayoutKind::VerticalStack => parent_rect,
-        }
+        })
     }
 
-    fn get_ascii_tree(&self, node: NodeId) -> ascii_tree::Tree {
-        let status = match node.parent(&self.tree.map) {
-            None => "",
-            Some(parent)
-                if self.tree.data.selection.local_selection(&self.tree.map, parent)
-                    == Some(node) =>
-            {
-                "☒ "
+    fn mirror_layout(&mut self, layout: LayoutId, axis: Orientation) {
+        self.with_undo(layout, |this| {
+            for node in this.tiled_containers_along(layout, axis) {
+                this.reverse_children(node);
             }
-            _ => "☐ ",
-        };
-        let desc = format!("{status}{node:?}");
-        let desc = match self.window_at(node) {
-            Some(wid) => format!("{desc} {:?} {}", wid, self.tree.data.layout.debug(node, false)),
-            None => format!("{desc} {}", self.tree.data.layout.debug(node, true)),
-        };
-        let children: Vec<_> =
-            node.children(&self.tree.map).map(|c| self.get_ascii_tree(c)).collect();
-        if children.is_empty() {
-            ascii_tree::Tree::Leaf(vec![desc])
-        } else {
-            ascii_tree::Tree::Node(desc, children)
-        }
+        })
     }
 
-    fn add_window_under(&mut self, layout: LayoutId, parent: NodeId, wid: WindowId) -> NodeId {
-        let node = self.tree.mk_node().push_back(parent);
-        self.tree.data.window.set_window(layout, node, wid);
-        node
+    fn join_selection_with_direction(&mut self, layout: LayoutId, direction: Direction) {
+        self.with_undo(layout, |this| this.join_selection_internal(layout, direction))
     }
 
-    fn window_at(&self, node: NodeId) -> Option<WindowId> {
-        self.tree.data.window.at(node)
+    fn unjoin_selection(&mut self, layout: LayoutId) {
+        self.with_undo(layout, |this| this.unjoin_selection_internal(layout))
     }
+}
 
-    fn window_in_direction_from(&self, node: NodeId, direction: Direction) -> Option<WindowId> {
-        if let Some(window) = self.window_at(node) {
-            return Some(window);
-        }
-        let mut children: Vec<_> = node.children(self.map()).collect();
-        match direction {
-            Direction::Left | Direction::Up => children.reverse(),
-            Direction::Right | Direction::Down => {}
-        }
-        for child in children {
-            if let Some(window) = self.window_in_direction_from(child, direction) {
-                return Some(window);
-            }
-        }
-        None
+impl LayoutStacking for TraditionalLayoutSystem {
+    fn apply_stacking_to_parent_of_selection(
+        &mut self,
+        layout: LayoutId,
+        default_orientation: crate::common::config::StackDefaultOrientation,
+    ) -> Vec<WindowId> {
+        self.with_undo(layout, |this| {
+            this.apply_stacking_internal(layout, default_orientation)
+        })
     }
 
-    fn rebalance_node(&mut self, node: NodeId) {
-        let map = &self.tree.map;
-        let children: Vec<_> = node.children(map).collect();
-        let count = children.len() as f32;
-        if count == 0.0 {
-            return;
+    fn unstack_parent_of_selection(
+        &mut self,
+        layout: LayoutId,
+        default_orientation: crate::common::config::StackDefaultOrientation,
+    ) -> Vec<WindowId> {
+        self.with_undo(layout, |this| this.unstack_internal(layout, default_orientation))
+    }
+
+    fn parent_of_selection_is_stacked(&self, layout: LayoutId) -> bool {
+        let selection = self.selection(layout);
+        if self.tree.data.window.at(selection).is_some() {
+            let map = self.map();
+            return selection
+                .ancestors(map)
+                .skip(1)
+                .any(|ancestor| self.layout(ancestor).is_stacked());
         }
-        self.tree.data.layout.info[node].total = count;
-        for &child in &children {
-            if self.tree.data.layout.info[child].size == 0.0 {
-                self.tree.data.layout.info[child].size = 1.0;
-            }
+        if self.layout(selection).is_stacked() {
+            return true;
         }
-        for child in children {
-            self.rebalance_node(child);
+        let map = self.map();
+        selection.children(map).any(|child| self.layout(child).is_stacked())
+    }
+
+    fn stack_parent_of_selection_if_unstacked(
+        &mut self,
+        layout: LayoutId,
+        default_orientation: crate::common::config::StackDefaultOrientation,
+    ) -> Vec<WindowId> {
+        match self.stack_target_container(layout) {
+            Some(container) if !self.layout(container).is_stacked() => self
+                .with_undo(layout, |this| {
+                    this.apply_stacking_internal(layout, default_orientation)
+                }),
+            _ => vec![],
         }
     }
 
-    fn select(&mut self, selection: NodeId) {
-        self.tree.data.selection.select(&self.tree.map, selection)
+    fn cycle_stack_of_parent_of_selection(&mut self, layout: LayoutId) -> Vec<WindowId> {
+        self.with_undo(layout, |this| this.cycle_stack_internal(layout))
     }
 
-    fn traverse_internal(&self, from: NodeId, direction: Direction) -> Option<NodeId> {
-        let map = &self.tree.map;
-        if let Some(sibling) = self.move_over(from, direction) {
-            return Some(sibling);
-        }
-        let node = from.ancestors(map).skip(1).find_map(|ancestor| {
-            self.move_over(ancestor, direction)
-                .map(|target| self.descend_into_target(target, direction, map))
-        });
-        node.flatten()
+    fn occluded_windows(&self, layout: LayoutId) -> Vec<WindowId> {
+        // Only the local selection of each stack is drawn, so anything not
+        // reached that way is covered.
+        let root = self.root(layout);
+        let visible = self.visible_windows_under_internal(root);
+        root.traverse_preorder(self.map())
+            .filter_map(|node| self.window_at(node))
+            .filter(|wid| !visible.contains(wid))
+            .collect()
     }
 
-    fn descend_into_target(
+    fn stacked_containers(
         &self,
-        target: NodeId,
-        direction: Direction,
-        map: &NodeMap,
-    ) -> Option<NodeId> {
-        let mut current = target;
-        loop {
-            let children: Vec<_> = current.children(map).collect();
+        layout: LayoutId,
+        screen: CGRect,
+        stack_offset: f64,
+        focus_pop: f64,
+        gaps: &crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+        stack_line_horiz: crate::common::config::HorizontalPlacement,
+        stack_line_vert: crate::common::config::VerticalPlacement,
+    ) -> Vec<crate::layout_engine::engine::GroupContainerInfo> {
+        let map = self.map();
+        let layout_data = &self.tree.data.layout;
+        let mut out = Vec::new();
+        let mut pending = vec![(self.root(layout), compute_tiling_area(screen, gaps))];
+        while let Some((node, rect)) = pending.pop() {
+            // A fullscreen window covers every bar, as in the selection path.
+            if layout_data.is_effectively_fullscreen(node) {
+                return Vec::new();
+            }
+            let children = layout_data.child_rects(
+                map,
+                &self.tree.data.window,
+                node,
+                rect,
+                stack_offset,
+                focus_pop,
+                gaps,
+                stack_line_thickness,
+                stack_line_horiz,
+                stack_line_vert,
+            );
             if children.is_empty() {
-                return Some(current);
+                continue;
             }
-            let layout_kind = self.tree.data.layout.kind(current);
-            if let Some(selected) = self.tree.data.selection.local_selection(map, current) {
-                match (layout_kind, direction) {
-                    (LayoutKind::Horizontal, Direction::Up | Direction::Down)
-                    | (LayoutKind::Vertical, Direction::Left | Direction::Right) => {
-                        current = selected;
-                        continue;
-                    }
-                    _ if layout_kind.is_stacked() => {
-                        current = selected;
-                        continue;
-                    }
-                    _ => {}
-                }
-            }
-            let next_child = match (layout_kind, direction) {
-                (LayoutKind::Horizontal, Direction::Left) => self
-                    .tree
-                    .data
-                    .selection
-                    .local_selection(map, current)
-                    .or(children.first().copied()),
-                (LayoutKind::Horizontal, Direction::Right) => self
-                    .tree
-                    .data
-                    .selection
-                    .local_selection(map, current)
-                    .or(children.last().copied()),
-                (LayoutKind::Horizontal, Direction::Up) => self
-                    .tree
-                    .data
-                    .selection
-                    .local_selection(map, current)
-                    .or(children.first().copied()),
-                (LayoutKind::Horizontal, Direction::Down) => self
-                    .tree
-                    .data
-                    .selection
-                    .local_selection(map, current)
-                    .or(children.last().copied()),
-                (LayoutKind::Vertical, Direction::Up) => self
-                    .tree
-                    .data
-                    .selection
-                    .local_selection(map, current)
-                    .or(children.first().copied()),
-                (LayoutKind::Vertical, Direction::Down) => self
-                    .tree
-                    .data
-                    .selection
-                    .local_selection(map, current)
-                    .or(children.last().copied()),
-                (LayoutKind::Vertical, Direction::Left) => self
-                    .tree
-                    .data
-                    .selection
-                    .local_selection(map, current)
-                    .or(children.first().copied()),
-                (LayoutKind::Vertical, Direction::Right) => self
-                    .tree
-                    .data
-                    .selection
-                    .local_selection(map, current)
-                    .or(children.last().copied()),
-                _ if layout_kind.is_stacked() => self
-                    .tree
-                    .data
-                    .selection
-                    .local_selection(map, current)
-                    .or(children.first().copied()),
-                _ => None,
-            };
-            match next_child {
-                Some(child) => current = child,
-                None => return Some(current),
+            if layout_data.kind(node).is_group() {
+                // Only the selected member shows; the rest are behind it.
+                let nodes: Vec<_> = children.iter().map(|&(child, _)| child).collect();
+                let info = self.stack_info(node, rect, &nodes);
+                pending.push(children[info.selected_index]);
+                out.push(info);
+            } else {
+                pending.extend(children.into_iter().rev());
             }
         }
+        out
     }
+}
 
-    fn visible_windows_under_internal(&self, node: NodeId) -> Vec<WindowId> {
-        let mut stack = Vec::with_capacity(16);
-        stack.push(node);
-        let mut windows = Vec::with_capacity(16);
-        while let Some(node) = stack.pop() {
-            if self.layout(node).is_group() {
-                stack.extend(self.tree.data.selection.local_selection(self.map(), node));
-            } else {
-                stack.extend(node.children(self.map()));
-            }
-            windows.extend(self.window_at(node));
+impl LayoutSwappable for TraditionalLayoutSystem {
+    fn swap_windows(&mut self, layout: LayoutId, a: WindowId, b: WindowId) -> bool {
+        self.drop_duplicate_bindings(layout, a);
+        self.drop_duplicate_bindings(layout, b);
+        let node_a = match self.tree.data.window.node_for(layout, a) {
+            Some(n) => n,
+            None => return false,
+        };
+        let node_b = match self.tree.data.window.node_for(layout, b) {
+            Some(n) => n,
+            None => return false,
+        };
+        if node_a == node_b {
+            return false;
         }
-        windows
+        let window = &mut self.tree.data.window;
+        window.windows.insert(node_a, b);
+        window.windows.insert(node_b, a);
+        window.rebind(layout, a, node_b);
+        window.rebind(layout, b, node_a);
+        // Fullscreen belongs to the window, not the slot it occupies.
+        self.tree.data.layout.swap_fullscreen(node_a, node_b);
+        true
     }
+}
 
-    fn move_over(&self, from: NodeId, direction: Direction) -> Option<NodeId> {
-        let parent = from.parent(&self.tree.map)?;
-        if self.tree.data.layout.kind(parent).orientation() == direction.orientation() {
-            match direction {
-                Direction::Left | Direction::Up => from.prev_sibling(&self.tree.map),
-                Direction::Right | Direction::Down => from.next_sibling(&self.tree.map),
-            }
+impl LayoutFullscreen for TraditionalLayoutSystem {
+    fn toggle_fullscreen_of_selection(&mut self, layout: LayoutId) -> Vec<WindowId> {
+        let node = self.selection(layout);
+        if self.tree.data.layout.toggle_fullscreen(node) {
+            self.visible_windows_under_internal(node)
         } else {
-            None
+            vec![]
         }
     }
 
-    fn move_node(&mut self, layout: LayoutId, moving_node: NodeId, direction: Direction) -> bool {
-        let map = &self.tree.map;
-        let Some(old_parent) = moving_node.parent(map) else {
-            return false;
-        };
-        let is_selection =
-            self.tree.data.selection.local_selection(map, old_parent) == Some(moving_node);
-        let moved = self.move_node_inner(layout, moving_node, direction);
-        if moved && is_selection {
-            for node in moving_node.ancestors(&self.tree.map) {
-                if node == old_parent {
-                    break;
+    fn toggle_fullscreen_within_gaps_of_selection(&mut self, layout: LayoutId) -> Vec<WindowId> {
+        let node = self.selection(layout);
+        if self.tree.data.layout.toggle_fullscreen_within_gaps(node) {
+            self.visible_windows_under_internal(node)
+        } else {
+            vec![]
+        }
+    }
+
+    fn fullscreen_windows(&self, layout: LayoutId) -> Vec<(WindowId, bool)> {
+        let mut windows: Vec<(WindowId, bool)> = Vec::new();
+        for node in self.root(layout).traverse_preorder(self.map()) {
+            if !self.tree.data.layout.is_effectively_fullscreen(node) {
+                continue;
+            }
+            let within_gaps = self.tree.data.layout.info[node].is_fullscreen_within_gaps;
+            // An enclosing fullscreen container decides how its windows show.
+            for wid in self.visible_windows_under_internal(node) {
+                if !windows.iter().any(|&(w, _)| w == wid) {
+                    windows.push((wid, within_gaps));
                 }
-                self.tree.data.selection.select_locally(&self.tree.map, node);
             }
         }
-        moved
+        windows
     }
+}
 
-    fn move_node_inner(
+impl TraditionalLayoutSystem {
+    /// Makes `desired` the windows of `pid` in `layout`, adding and removing
+    /// windows as needed.
+    fn sync_windows_for_app(
         &mut self,
         layout: LayoutId,
-        moving_node: NodeId,
-        direction: Direction,
-    ) -> bool {
-        enum Destination {
-            Ahead(NodeId),
-            Behind(NodeId),
-        }
-        let map = &self.tree.map;
-        let destination;
-        if let Some(sibling) = self.move_over(moving_node, direction) {
-            let mut node = sibling;
-            let target = loop {
-                let Some(next) =
-                    self.tree.data.selection.local_selection(map, node).or(node.first_child(map))
-                else {
-                    break node;
-                };
-                if self.tree.data.layout.kind(node).orientation() == direction.orientation() {
-                    break next;
+        pid: pid_t,
+        mut desired: Vec<WindowId>,
+        near_app_windows: bool,
+    ) {
+        let root = self.root(layout);
+        let mut current = root
+            .traverse_postorder(self.map())
+            .filter_map(|node| self.window_at(node).map(|wid| (wid, node)))
+            .filter(|(wid, _)| wid.pid == pid)
+            .collect::<Vec<_>>();
+        desired.sort_unstable();
+        desired.dedup();
+        current.sort_unstable();
+        debug_assert!(desired.iter().all(|wid| wid.pid == pid));
+        // New windows go after the last window of the app that stays, then
+        // after each other, so they end up next to the app's other windows.
+        // None of this moves the selection: the app may be in the background,
+        // and the engine selects windows itself when they get focus.
+        let mut anchor = near_app_windows
+            .then(|| {
+                root.traverse_preorder(self.map())
+                    .filter(|&node| {
+                        self.window_at(node).is_some_and(|wid| desired.binary_search(&wid).is_ok())
+                    })
+                    .last()
+            })
+            .flatten();
+        let mut add = |this: &mut Self, wid: WindowId| {
+            // A `WindowAdded` racing this update may have placed it already.
+            if this.tree.data.window.node_for(layout, wid).is_some() {
+                return;
+            }
+            let node = match anchor {
+                Some(node) => this.add_window_after_node(layout, node, wid),
+                None => {
+                    let (node, was_empty) = this.insert_after_selection_internal(layout, wid);
+                    if was_empty {
+                        this.select(node);
+                    }
+                    node
                 }
-                node = next;
             };
-            if target == sibling {
-                destination = Destination::Ahead(sibling);
-            } else {
-                destination = Destination::Behind(target);
+            anchor = Some(node);
+        };
+        let mut desired = desired.into_iter().peekable();
+        let mut current = current.into_iter().peekable();
+        loop {
+            match (desired.peek(), current.peek()) {
+                (Some(des), Some((cur, _))) if des == cur => {
+                    desired.next();
+                    current.next();
+                }
+                (Some(des), None) => {
+                    add(self, *des);
+                    desired.next();
+                }
+                (Some(des), Some((cur, _))) if des < cur => {
+                    add(self, *des);
+                    desired.next();
+                }
+                (_, Some((_, node))) => {
+                    if self.tree.data.layout.info[*node].is_fullscreen {
+                        current.next();
+                    } else {
+                        node.detach(&mut self.tree).remove();
+                        current.next();
+                    }
+                }
+                (None, None) => break,
             }
+        }
+    }
+
+    /// Inserts `wid` after the selection, returning its node and whether
+    /// the layout had no windows before.
+    fn insert_after_selection_internal(
+        &mut self,
+        layout: LayoutId,
+        wid: WindowId,
+    ) -> (NodeId, bool) {
+        let root = self.root(layout);
+        // After the layout has been emptied the selection may still point at a
+        // leftover container; always insert at the root in that case.
+        let was_empty = self.visible_windows_under_internal(root).is_empty();
+        let selection = if was_empty {
+            root
         } else {
-            let target_ancestor = moving_node.ancestors_with_parent(&self.tree.map).skip(1).find(
-                |(_node, parent)| {
-                    parent
-                        .map(|p| self.layout(p).orientation() == direction.orientation())
-                        .unwrap_or(false)
-                },
+            self.selection(layout)
+        };
+        let node = if selection.parent(self.map()).is_none() {
+            self.add_window_under(layout, selection, wid)
+        } else {
+            self.smart_window_insertion(layout, selection, wid)
+        };
+        (node, was_empty)
+    }
+
+    fn move_selection_internal(&mut self, layout: LayoutId, direction: Direction) -> bool {
+        let selection = self.selection(layout);
+        self.move_node(layout, selection, direction)
+    }
+
+    fn split_selection_internal(&mut self, layout: LayoutId, kind: LayoutKind) {
+        let selection = self.selection(layout);
+        self.nest_in_container_internal(layout, selection, kind);
+    }
+
+    fn join_selection_internal(&mut self, layout: LayoutId, direction: Direction) {
+        let selection = self.selection(layout);
+        if let Some(target) = self.find_natural_join_target(selection, direction) {
+            let target = target.node();
+            let selection = self.join_source(selection, target, direction);
+            let (selection, target) = (
+                self.join_unit(selection, target),
+                self.join_unit(target, selection),
             );
-            if let Some((target, _parent)) = target_ancestor {
-                destination = Destination::Ahead(target);
-            } else {
-                let old_root = moving_node.ancestors(map).last().unwrap();
-                if self.tree.data.layout.kind(old_root).orientation() == direction.orientation() {
-                    let is_edge_move = match direction {
-                        Direction::Left | Direction::Up => moving_node.prev_sibling(map).is_none(),
-                        Direction::Right | Direction::Down => {
-                            moving_node.next_sibling(map).is_none()
+            if self.can_absorb_join_target(selection, target, direction) {
+                let map = self.map();
+                match direction {
+                    Direction::Right | Direction::Down => {
+                        target.detach(&mut self.tree).push_back(selection);
+                    }
+                    Direction::Left | Direction::Up => {
+                        if let Some(first) = selection.first_child(map) {
+                            target.detach(&mut self.tree).insert_before(first);
+                        } else {
+                            target.detach(&mut self.tree).push_back(selection);
                         }
-                    };
-                    if !is_edge_move {
-                        return false;
                     }
                 }
-                let new_container_kind = LayoutKind::from(direction.orientation());
-                self.nest_in_container_internal(layout, old_root, new_container_kind);
-                destination = Destination::Ahead(old_root);
-            }
-        }
-        match (destination, direction) {
-            (Destination::Ahead(target), Direction::Right | Direction::Down) => {
-                moving_node.detach(&mut self.tree).insert_after(target);
-            }
-            (Destination::Behind(target), Direction::Right | Direction::Down) => {
-                moving_node.detach(&mut self.tree).insert_before(target);
-            }
-            (Destination::Ahead(target), Direction::Left | Direction::Up) => {
-                moving_node.detach(&mut self.tree).insert_before(target);
+                self.select(selection);
+                return;
             }
-            (Destination::Behind(target), Direction::Left | Direction::Up) => {
-                moving_node.detach(&mut self.tree).insert_after(target);
+            self.perform_natural_join(layout, selection, target, direction);
+            if self.tree.data.window.at(selection).is_some() {
+                self.select(selection);
+            } else {
+                let _ = self.descend_selection(layout);
             }
         }
-        true
     }
 
-    fn resize_internal(&mut self, node: NodeId, screen_ratio: f64, direction: Direction) -> bool {
-        let can_resize = |&node: &NodeId| -> bool {
-            let Some(parent) = node.parent(&self.tree.map) else {
-                return false;
-            };
-            !self.tree.data.layout.kind(parent).is_group()
-                && self.move_over(node, direction).is_some()
-        };
-        let Some(resizing_node) = node.ancestors(&self.tree.map).find(can_resize) else {
-            return false;
-        };
-        let sibling = self.move_over(resizing_node, direction).unwrap();
-        let exchange_rate = resizing_node
-            .ancestors(&self.tree.map)
-            .skip(1)
-            .try_fold(1.0, |r, node| match node.parent(&self.tree.map) {
-                Some(parent)
-                    if self.tree.data.layout.kind(parent).orientation()
-                        == direction.orientation()
-                        && !self.tree.data.layout.kind(parent).is_group() =>
-                {
-                    self.tree.data.layout.proportion(&self.tree.map, node).map(|p| r * p)
+    fn unjoin_selection_internal(&mut self, layout: LayoutId) {
+        let selection = self.selection(layout);
+        if let Some(parent) = selection.parent(&self.tree.map) {
+            if let Some(grandparent) = parent.parent(&self.tree.map) {
+                let children: Vec<_> = parent.children(&self.tree.map).collect();
+                if children.is_empty() {
+                    return;
                 }
-                _ => Some(r),
-            })
-            .unwrap_or(1.0);
-        let local_ratio = screen_ratio
-            * f64::from(
-                self.tree.data.layout.info[resizing_node.parent(&self.tree.map).unwrap()].total,
-            )
-            / exchange_rate;
-        self.tree.data.layout.take_share(
-            &self.tree.map,
-            resizing_node,
-            sibling,
-            local_ratio as f32,
-        );
-        true
-    }
-
-    fn set_frame_from_resize(
-        &mut self,
-        node: NodeId,
-        old_frame: CGRect,
-        new_frame: CGRect,
-        screen: CGRect,
-    ) {
-        let mut check_or_resize = |resize: bool| {
-            let mut count = 0;
-            let mut first_direction: Option<Direction> = None;
-            let mut good = true;
-            let deltas = [
-                (
-                    Direction::Left,
-                    old_frame.min().x - new_frame.min().x,
-                    screen.size.width,
-                ),
-                (
-                    Direction::Right,
-                    new_frame.max().x - old_frame.max().x,
-                    screen.size.width,
-                ),
-                (
-                    Direction::Up,
-                    old_frame.min().y - new_frame.min().y,
-                    screen.size.height,
-                ),
-                (
-                    Direction::Down,
-                    new_frame.max().y - old_frame.max().y,
-                    screen.size.height,
-                ),
-            ];
-            for (direction, delta, whole) in deltas {
-                if delta != 0.0 {
-                    count += 1;
-                    if count > 2 {
-                        good = false;
-                    }
-                    if let Some(first) = first_direction {
-                        if first.orientation() == direction.orientation() {
-                            good = false;
-                        }
-                    } else {
-                        first_direction = Some(direction);
-                    }
-                    if resize {
-                        self.resize_internal(node, delta / whole, direction);
-                    }
+                let local_selected_child =
+                    self.tree.data.selection.local_selection(&self.tree.map, parent);
+                for child in children.iter() {
+                    child.detach(&mut self.tree).push_back(grandparent);
+                }
+                // Moving the second-to-last child out collapses `parent` on its
+                // own, so it may already be gone by now.
+                if self.tree.map.contains(parent) {
+                    parent.detach(&mut self.tree).remove();
+                }
+                let target = local_selected_child
+                    .filter(|&node| self.tree.map.contains(node))
+                    .or_else(|| self.find_best_focus_target(grandparent).map(|(node, _)| node))
+                    .or_else(|| grandparent.first_child(&self.tree.map));
+                if let Some(target) = target {
+                    self.select(target);
+                }
+            } else {
+                let children: Vec<_> = parent.children(&self.tree.map).collect();
+                if children.len() == 2 {
+                    self.remove_unnecessary_container_internal(parent);
                 }
             }
-            good
-        };
-        if !check_or_resize(false) {
-            warn!(
-                "Only resizing in 2 directions is supported, but was asked to resize from {old_frame:?} to {new_frame:?}"
-            );
-            return;
         }
-        check_or_resize(true);
     }
 
-    fn nest_in_container_internal(
+    fn apply_stacking_internal(
         &mut self,
         layout: LayoutId,
-        node: NodeId,
-        kind: LayoutKind,
-    ) -> NodeId {
-        let old_parent = node.parent(&self.tree.map);
-        let parent = if node.prev_sibling(&self.tree.map).is_none()
-            && node.next_sibling(&self.tree.map).is_none()
-        {
-            old_parent
-        } else {
-            None
-        }
-        .unwrap_or_else(|| {
-            let new_parent = if let Some(old_parent) = old_parent {
-                let is_selection =
-                    self.tree.data.selection.local_selection(self.map(), old_parent) == Some(node);
-                let new_parent = self.tree.mk_node().insert_before(node);
-                self.tree.data.layout.assume_size_of(new_parent, node, &self.tree.map);
-                node.detach(&mut self.tree).push_back(new_parent);
-                if is_selection {
-                    self.tree.data.selection.select_locally(&self.tree.map, new_parent);
-                }
-                new_parent
+        default_orientation: crate::common::config::StackDefaultOrientation,
+    ) -> Vec<WindowId> {
+        if let Some(container) = self.stack_target_container(layout) {
+            let current_layout = self.layout(container);
+            let new_layout = if current_layout.is_stacked() {
+                current_layout.rotated()
             } else {
-                let layout_root = self.layout_roots.get_mut(layout).unwrap();
-                layout_root.replace(self.tree.mk_node()).push_back(layout_root.id());
-                layout_root.id()
+                stacked_kind(current_layout, default_orientation)
             };
-            self.tree.data.selection.select_locally(&self.tree.map, node);
-            new_parent
-        });
-        self.tree.data.layout.set_kind(parent, kind);
-        parent
+            if !self.change_layout(container, new_layout) {
+                return vec![];
+            }
+            if let Some(first_child) = container.first_child(self.map()) {
+                self.select(first_child);
+            }
+            return self.visible_windows_under_internal(container);
+        }
+        vec![]
     }
 
-    fn find_or_create_common_parent_internal(
+    /// Switches the stack around the selection between peeking and tabbed,
+    /// keeping its orientation. A container that is not stacked becomes a
+    /// tabbed stack oriented as stacking it would be. Returns the container's
+    /// windows.
+    pub fn toggle_tabbed(
         &mut self,
-        _layout: LayoutId,
-        node1: NodeId,
-        node2: NodeId,
-    ) -> NodeId {
-        let map = self.map();
-        if node1 == node2 {
-            return node1;
-        }
-        if node1.ancestors(map).any(|ancestor| ancestor == node2) {
-            return node2;
-        }
-        if node2.ancestors(map).any(|ancestor| ancestor == node1) {
-            return node1;
+        layout: LayoutId,
+        default_orientation: crate::common::config::StackDefaultOrientation,
+    ) -> Vec<WindowId> {
+        let Some(container) = self.stack_target_container(layout) else {
+            return vec![];
+        };
+        let kind = self.layout(container);
+        let new_kind = if kind.is_tabbed() {
+            LayoutKind::stack_with_offset(kind.orientation())
+        } else if kind.is_stacked() {
+            LayoutKind::tabbed(kind.orientation())
+        } else {
+            LayoutKind::tabbed(stacked_kind(kind, default_orientation).orientation())
+        };
+        if !self.change_layout(container, new_kind) {
+            return vec![];
         }
-        let parent1 = node1.parent(self.map());
-        let parent2 = node2.parent(self.map());
-        if let (Some(p1), Some(p2)) = (parent1, parent2)
-            && p1 == p2
+        if !kind.is_stacked()
+            && let Some(first_child) = container.first_child(self.map())
+            && self.tree.data.window.at(first_child).is_some()
         {
-            let new_parent = self.tree.mk_node().insert_before(node1);
-            self.tree.data.layout.assume_size_of(new_parent, node1, &self.tree.map);
-            self.tree.data.layout.assume_size_of(new_parent, node2, &self.tree.map);
-            node1.detach(&mut self.tree).push_back(new_parent);
-            node2.detach(&mut self.tree).push_back(new_parent);
-            return new_parent;
-        }
-        let ancestors1: Vec<_> = node1.ancestors(self.map()).collect();
-        let ancestors2: Vec<_> = node2.ancestors(self.map()).collect();
-        for &ancestor in &ancestors1 {
-            if ancestors2.contains(&ancestor) {
-                let container = {
-                    let node = self.tree.mk_node().push_back(ancestor);
-                    self.tree.data.layout.set_kind(node, LayoutKind::Horizontal);
-                    node
-                };
-                node1.detach(&mut self.tree).push_back(container);
-                node2.detach(&mut self.tree).push_back(container);
-                return container;
-            }
+            self.select(first_child);
         }
-        panic!("Nodes are not in the same tree, cannot find common parent");
+        self.visible_windows_under_internal(container)
     }
 
-    fn remove_unnecessary_container_internal(&mut self, container: NodeId) {
-        let children: Vec<_> = container.children(self.map()).collect();
-        if children.len() <= 1 {
-            let parent = container.parent(self.map());
-            for child in children {
-                let detached = child.detach(&mut self.tree);
-                if let Some(parent) = parent {
-                    detached.push_back(parent);
-                } else {
-                    detached.remove();
+    fn unstack_internal(
+        &mut self,
+        layout: LayoutId,
+        default_orientation: crate::common::config::StackDefaultOrientation,
+    ) -> Vec<WindowId> {
+        let selection = self.selection(layout);
+        let target_container = if self.tree.data.window.at(selection).is_some() {
+            let map = self.map();
+            selection
+                .ancestors(map)
+                .skip(1)
+                .find(|&ancestor| self.layout(ancestor).is_stacked())
+        } else {
+            let selection_layout = self.layout(selection);
+            if selection_layout.is_stacked() {
+                Some(selection)
+            } else {
+                let map = self.map();
+                selection.children(map).find(|&child| self.layout(child).is_stacked())
+            }
+        };
+        if let Some(container) = target_container {
+            let new_layout = match self.layout(container) {
+                LayoutKind::HorizontalStack | LayoutKind::HorizontalTabbed => {
+                    match default_orientation {
+                        crate::common::config::StackDefaultOrientation::Perpendicular => {
+                            Some(LayoutKind::Vertical)
+                        }
+                        crate::common::config::StackDefaultOrientation::Same => {
+                            Some(LayoutKind::Horizontal)
+                        }
+                        crate::common::config::StackDefaultOrientation::Horizontal => {
+                            Some(LayoutKind::Horizontal)
+                        }
+                        crate::common::config::StackDefaultOrientation::Vertical => {
+                            Some(LayoutKind::Vertical)
+                        }
+                    }
                 }
+                LayoutKind::VerticalStack | LayoutKind::VerticalTabbed => match default_orientation
+                {
+                    crate::common::config::StackDefaultOrientation::Perpendicular => {
+                        Some(LayoutKind::Horizontal)
+                    }
+                    crate::common::config::StackDefaultOrientation::Same => {
+                        Some(LayoutKind::Vertical)
+                    }
+                    crate::common::config::StackDefaultOrientation::Horizontal => {
+                        Some(LayoutKind::Horizontal)
+                    }
+                    crate::common::config::StackDefaultOrientation::Vertical => {
+                        Some(LayoutKind::Vertical)
+                    }
+                },
+                _ => None,
+            };
+            if let Some(nl) = new_layout
+                && self.change_layout(container, nl)
+            {
+                return self.visible_windows_under_internal(container);
             }
-            container.detach(&mut self.tree).remove();
         }
+        vec![]
     }
-}
 
-#[derive(Default, Serialize, Deserialize)]
-struct Components {
-    selection: Selection,
-    layout: Layout,
-    window: Window,
-}
+    fn cycle_stack_internal(&mut self, layout: LayoutId) -> Vec<WindowId> {
+        let Some(container) = self.stack_target_container(layout) else {
+            return vec![];
+        };
+        // Vertical containers join the cycle at its first stacked step.
+        let new_layout = match self.layout(container) {
+            LayoutKind::Horizontal | LayoutKind::Vertical => LayoutKind::HorizontalStack,
+            LayoutKind::HorizontalStack => LayoutKind::VerticalStack,
+            LayoutKind::VerticalStack => LayoutKind::Horizontal,
+            // Tabbed stacks leave the cycle the way peeking ones do.
+            LayoutKind::HorizontalTabbed => LayoutKind::VerticalTabbed,
+            LayoutKind::VerticalTabbed => LayoutKind::Horizontal,
+        };
+        let was_stacked = self.layout(container).is_stacked();
+        if !self.change_layout(container, new_layout) {
+            return vec![];
+        }
+        // Only move the selection onto a window so the next press targets the same container.
+        if new_layout.is_stacked()
+            && !was_stacked
+            && let Some(first_child) = container.first_child(self.map())
+            && self.tree.data.window.at(first_child).is_some()
+        {
+            self.select(first_child);
+        }
+        self.visible_windows_under_internal(container)
+    }
 
-impl tree::Observer for Components {
-    fn added_to_forest(&mut self, map: &NodeMap, node: NodeId) {
-        self.dispatch_event(map, TreeEvent::AddedToForest(node))
-    }
-    fn added_to_parent(&mut self, map: &NodeMap, node: NodeId) {
-        self.dispatch_event(map, TreeEvent::AddedToParent(node))
-    }
-    fn removing_from_parent(&mut self, map: &NodeMap, node: NodeId) {
-        self.dispatch_event(map, TreeEvent::RemovingFromParent(node))
-    }
-    fn removed_child(tree: &mut Tree<Self>, parent: NodeId) {
-        if parent.parent(&tree.map).is_none() {
-            return;
+    /// Checks the structural invariants of `layout` and returns every
+    /// violation found. Unlike the debug assertions this also runs in release
+    /// builds, so it can vet restored state or a tree reported in a bug.
+    pub fn validate(&self, layout: LayoutId) -> Result<(), Vec<String>> {
+        let map = self.map();
+        let root = self.root(layout);
+        let info = &self.tree.data.layout.info;
+        let mut errors = Vec::new();
+        for node in root.traverse_preorder(map) {
+            let has_children = !node.is_empty(map);
+            match self.window_at(node) {
+                Some(wid) if has_children => {
+                    errors.push(format!("container {node:?} holds window {wid:?}"));
+                }
+                Some(wid) => match self.tree.data.window.node_for(layout, wid) {
+                    Some(indexed) if indexed == node => {}
+                    Some(indexed) => {
+                        errors.push(format!("window {wid:?} at {node:?} is indexed at {indexed:?}"))
+                    }
+                    None => errors.push(format!("window {wid:?} at {node:?} is not indexed")),
+                },
+                None if !has_children && node != root => {
+                    errors.push(format!("leaf {node:?} holds no window"));
+                }
+                None => {}
+            }
+            if has_children {
+                let sum: f32 = node.children(map).map(|child| info[child].size).sum();
+                let total = info[node].total;
+                if (sum - total).abs() > SIZE_TOLERANCE * total.abs().max(1.0) {
+                    errors.push(format!(
+                        "container {node:?} has total {total} but its children sum to {sum}"
+                    ));
+                }
+            }
+            if let Some(child) = self.tree.data.selection.last_selection(map, node)
+                && child.parent(map) != Some(node)
+            {
+                errors.push(format!("{node:?} selects {child:?}, which is not its child"));
+            }
         }
-        if parent.is_empty(&tree.map) {
-            parent.detach(tree).remove();
-        } else if parent.first_child(&tree.map) == parent.last_child(&tree.map) {
-            let child = parent.first_child(&tree.map).unwrap();
-            child
-                .detach(tree)
-                .insert_after(parent)
-                .with(|child_id, tree| tree.data.layout.assume_size_of(child_id, parent, &tree.map))
-                .finish();
+        for (&wid, nodes) in &self.tree.data.window.window_nodes {
+            for entry in nodes.0.iter().filter(|entry| entry.layout == layout) {
+                let node = entry.node;
+                let in_layout = map.contains(node) && node.ancestors(map).any(|a| a == root);
+                if !in_layout || self.window_at(node) != Some(wid) {
+                    errors.push(format!(
+                        "index binds {wid:?} to {node:?}, which does not hold it"
+                    ));
+                }
+            }
+        }
+        let selection = self.selection(layout);
+        if !map.contains(selection) || !selection.ancestors(map).any(|a| a == root) {
+            errors.push(format!("selection {selection:?} is not in the layout"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
     }
-    fn removed_from_forest(&mut self, map: &NodeMap, node: NodeId) {
-        self.dispatch_event(map, TreeEvent::RemovedFromForest(node))
-    }
-}
-
-impl Components {
-    fn dispatch_event(&mut self, map: &NodeMap, event: TreeEvent) {
-        self.selection.handle_event(map, event);
-        self.layout.handle_event(map, event);
-        self.window.handle_event(map, event);
-    }
-}
-
-#[derive(Default, Serialize, Deserialize)]
-struct Window {
-    windows: slotmap::SecondaryMap<NodeId, WindowId>,
-    window_nodes: crate::common::collections::BTreeMap<WindowId, WindowNodeInfoVec>,
-}
-
-#[derive(Serialize, Deserialize)]
-struct WindowNodeInfo {
-    layout: LayoutId,
-    node: NodeId,
-}
 
-#[derive(Serialize, Deserialize, Default)]
-struct WindowNodeInfoVec(Vec<WindowNodeInfo>);
+    /// Ensures every window is bound to at most one node per layout. When a
+    /// window appears more than once (e.g. from a hand-edited save file), the
+    /// first node in preorder is kept and later nodes are removed. The window
+    /// index is then rebuilt from the tree. Returns the number of removed nodes.
+    pub(crate) fn validate_window_bindings(&mut self) -> usize {
+        let layouts: Vec<_> = self.layout_roots.keys().collect();
+        let mut duplicates = Vec::new();
+        for &layout in &layouts {
+            let mut seen = crate::common::collections::HashSet::default();
+            for node in self.root(layout).traverse_preorder(self.map()) {
+                if let Some(wid) = self.window_at(node)
+                    && !seen.insert(wid)
+                {
+                    duplicates.push((layout, wid, node));
+                }
+            }
+        }
+        for &(layout, wid, node) in &duplicates {
+            warn!("Dropping duplicate node {node:?} for {wid:?} in {layout:?}");
+            node.detach(&mut self.tree).remove();
+        }
 
-impl Window {
-    fn at(&self, node: NodeId) -> Option<WindowId> {
-        self.windows.get(node).copied()
-    }
-    fn node_for(&self, layout: LayoutId, wid: WindowId) -> Option<NodeId> {
-        self.window_nodes.get(&wid).and_then(|nodes| {
-            nodes.0.iter().find(|info| info.layout == layout).map(|info| info.node)
-        })
-    }
-    fn set_window(&mut self, layout: LayoutId, node: NodeId, wid: WindowId) {
-        let existing = self.windows.insert(node, wid);
-        assert!(
-            existing.is_none(),
-            "Attempted to overwrite window for node {node:?} from {existing:?} to {wid:?}"
-        );
-        self.window_nodes
-            .entry(wid)
-            .or_default()
-            .0
-            .push(WindowNodeInfo { layout, node });
-    }
-    fn take_nodes_for(&mut self, wid: WindowId) -> impl Iterator<Item = (LayoutId, NodeId)> {
-        self.window_nodes
-            .remove(&wid)
-            .unwrap_or_default()
-            .0
-            .into_iter()
-            .map(|info| (info.layout, info.node))
+        self.tree.data.window.window_nodes.clear();
+        for layout in layouts {
+            let bindings: Vec<_> = self
+                .root(layout)
+                .traverse_preorder(self.map())
+                .filter_map(|node| self.window_at(node).map(|wid| (wid, node)))
+                .collect();
+            for (wid, node) in bindings {
+                self.tree.data.window.rebind(layout, wid, node);
+            }
+        }
+        duplicates.len()
     }
-    fn take_nodes_for_app(
-        &mut self,
-        pid: pid_t,
-    ) -> impl Iterator<Item = (WindowId, LayoutId, NodeId)> {
-        use crate::common::collections::BTreeExt;
-        let removed = self.window_nodes.remove_all_for_pid(pid);
-        removed.into_iter().flat_map(|(wid, infos)| {
-            infos.0.into_iter().map(move |info| (wid, info.layout, info.node))
-        })
+
+    /// Removes all but the first node bound to `wid` in `layout`.
+    fn drop_duplicate_bindings(&mut self, layout: LayoutId, wid: WindowId) {
+        let nodes: Vec<_> = self
+            .root(layout)
+            .traverse_preorder(self.map())
+            .filter(|&node| self.window_at(node) == Some(wid))
+            .collect();
+        let Some((&keep, rest)) = nodes.split_first() else {
+            return;
+        };
+        for &node in rest {
+            node.detach(&mut self.tree).remove();
+        }
+        if !rest.is_empty() {
+            self.tree.data.window.rebind(layout, wid, keep);
+        }
     }
-    fn handle_event(&mut self, map: &NodeMap, event: TreeEvent) {
-        match event {
-            TreeEvent::AddedToForest(_) => (),
-            TreeEvent::AddedToParent(node) => debug_assert!(
-                self.windows.get(node.parent(map).unwrap()).is_none(),
-                "Window nodes are not allowed to have children: {:?}/{:?}",
-                node.parent(map).unwrap(),
-                node
-            ),
-            TreeEvent::Copied { src, dest, dest_layout } => {
-                if let Some(&wid) = self.windows.get(src) {
-                    self.set_window(dest_layout, dest, wid);
-                }
+
+    pub(crate) fn collect_group_containers_in_selection_path(
+        &self,
+        layout: LayoutId,
+        screen: CGRect,
+        stack_offset: f64,
+        focus_pop: f64,
+        gaps: &crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+        stack_line_horiz: crate::common::config::HorizontalPlacement,
+        stack_line_vert: crate::common::config::VerticalPlacement,
+    ) -> Vec<crate::layout_engine::engine::GroupContainerInfo> {
+        use self::StackLayoutResult;
+        let mut out = Vec::new();
+        let map = &self.tree.map;
+        let tiling_area = compute_tiling_area(screen, gaps);
+        let mut node = self.root(layout);
+        let mut rect = tiling_area;
+        loop {
+            if self.tree.data.layout.is_effectively_fullscreen(node) {
+                out.clear();
+                break;
             }
-            TreeEvent::RemovingFromParent(_) => (),
-            TreeEvent::RemovedFromForest(node) => {
-                if let Some(wid) = self.windows.remove(node)
-                    && let Some(window_nodes) = self.window_nodes.get_mut(&wid)
-                {
-                    window_nodes.0.retain(|info| info.node != node);
-                    if window_nodes.0.is_empty() {
-                        self.window_nodes.remove(&wid);
-                    }
+            let kind = self.tree.data.layout.kind(node);
+            let children: Vec<_> = node.children(map).collect();
+            if kind.is_stacked() {
+                if children.is_empty() {
+                    break;
+                }
+                let info = self.stack_info(node, rect, &children);
+                let selected_index = info.selected_index;
+                let local_sel = children[selected_index];
+                if self.tree.data.layout.is_effectively_fullscreen(local_sel) {
+                    out.clear();
+                    break;
                 }
+                out.push(info);
+                let mut container_rect = rect;
+                let reserve = stack_line_thickness.max(0.0);
+                let is_horizontal = kind.orientation() == Orientation::Horizontal;
+                container_rect = adjust_stack_container_rect(
+                    container_rect,
+                    is_horizontal,
+                    reserve,
+                    stack_line_horiz,
+                    stack_line_vert,
+                );
+                rect = if kind.is_tabbed() {
+                    container_rect
+                } else {
+                    StackLayoutResult::new(
+                        container_rect,
+                        children.len(),
+                        stack_offset,
+                        focus_pop,
+                        is_horizontal,
+                    )
+                    .get_focused_frame_for_index(selected_index, selected_index)
+                };
+                node = local_sel;
+                continue;
+            }
+            if let Some(next) = self
+                .tree
+                .data
+                .selection
+                .local_selection(map, node)
+                .or_else(|| node.first_child(map))
+            {
+                rect = self.calculate_child_frame_in_container(node, next, rect, gaps);
+                node = next;
+                continue;
             }
+            break;
         }
+        out
     }
-}
 
-struct StackLayoutResult {
-    container_rect: CGRect,
-    stack_offset: f64,
-    is_horizontal: bool,
-    window_width: f64,
-    window_height: f64,
-}
+    /// Describes the stack `node`, laid out in `rect`, for its stack bar.
+    /// `children` are the stack's children; each contributes the window it
+    /// would focus to `window_ids`, so `selected_index` indexes both.
+    fn stack_info(
+        &self,
+        node: NodeId,
+        rect: CGRect,
+        children: &[NodeId],
+    ) -> crate::layout_engine::engine::GroupContainerInfo {
+        let map = self.map();
+        let local_sel = self.tree.data.selection.local_selection(map, node);
+        crate::layout_engine::engine::GroupContainerInfo {
+            node_id: node,
+            container_kind: self.layout(node),
+            frame: rect,
+            total_count: children.len(),
+            selected_index: local_sel
+                .and_then(|sel| children.iter().position(|&child| child == sel))
+                .unwrap_or(0),
+            window_ids: children
+                .iter()
+                .filter_map(|&child| {
+                    self.window_at(child)
+                        .or_else(|| self.find_best_focus_target(child).map(|(_, wid)| wid))
+                })
+                .collect(),
+        }
+    }
 
-impl StackLayoutResult {
-    fn new(
-        container_rect: CGRect,
-        window_count: usize,
-        stack_offset: f64,
-        is_horizontal: bool,
-    ) -> Self {
-        let total_offset_space = if window_count > 0 {
-            (window_count - 1) as f64 * stack_offset
+    fn calculate_child_frame_in_axis(
+        &self,
+        parent_rect: CGRect,
+        siblings: &[NodeId],
+        child_index: usize,
+        horizontal: bool,
+        gaps: &crate::common::config::GapSettings,
+    ) -> CGRect {
+        use objc2_core_foundation::{CGPoint, CGSize};
+        if siblings.is_empty() || child_index >= siblings.len() {
+            return parent_rect;
+        }
+        let (origin, axis_len) = if horizontal {
+            (parent_rect.origin.x, parent_rect.size.width)
         } else {
-            0.0
+            (parent_rect.origin.y, parent_rect.size.height)
         };
-        let (window_width, window_height) = if is_horizontal {
-            (
-                (container_rect.size.width - total_offset_space).max(100.0),
-                container_rect.size.height.max(100.0),
+        let inner_gap = gaps.inner_gap(horizontal, axis_len, siblings.len());
+        let weights: Vec<f64> = siblings
+            .iter()
+            .map(|&child| f64::from(self.tree.data.layout.info[child].size))
+            .collect();
+        let (offset, seg_len) = axis_segments(origin, axis_len, &weights, inner_gap)[child_index];
+        if horizontal {
+            CGRect::new(
+                CGPoint::new(offset, parent_rect.origin.y),
+                CGSize::new(seg_len, parent_rect.size.height),
             )
         } else {
-            (
-                container_rect.size.width.max(100.0),
-                (container_rect.size.height - total_offset_space).max(100.0),
+            CGRect::new(
+                CGPoint::new(parent_rect.origin.x, offset),
+                CGSize::new(parent_rect.size.width, seg_len),
             )
-        };
-        Self {
-            container_rect,
-            stack_offset,
-            is_horizontal,
-            window_width,
-            window_height,
         }
     }
 
-    fn get_frame_for_index(&self, index: usize) -> CGRect {
-        use objc2_core_foundation::{CGPoint, CGSize};
-        let offset_amount = index as f64 * self.stack_offset;
-        let (x_offset, y_offset) = if self.is_horizontal {
-            (offset_amount, 0.0)
-        } else {
-            (0.0, offset_amount)
-        };
-        CGRect::new(
-            CGPoint::new(
-                self.container_rect.origin.x + x_offset,
-                self.container_rect.origin.y + y_offset,
-            ),
-            CGSize::new(self.window_width, self.window_height),
-        )
-        .round()
+    fn calculate_child_frame_in_container(
+        &self,
+        parent_node: NodeId,
+        child_node: NodeId,
+        parent_rect: CGRect,
+        gaps: &crate::common::config::GapSettings,
+    ) -> CGRect {
+        let parent_kind = self.tree.data.layout.kind(parent_node);
+        let map = &self.tree.map;
+        let siblings: Vec<_> = parent_node.children(map).collect();
+        let child_index = siblings.iter().position(|&n| n == child_node).unwrap_or(0);
+        match parent_kind {
+            crate::layout_engine::LayoutKind::Horizontal => {
+                self.calculate_child_frame_in_axis(parent_rect, &siblings, child_index, true, gaps)
+            }
+            crate::layout_engine::LayoutKind::Vertical => {
+                self.calculate_child_frame_in_axis(parent_rect, &siblings, child_index, false, gaps)
+            }
+            crate::layout_engine::LayoutKind::HorizontalStack
+            | crate::layout_engine::LayoutKind::VerticalStack
+            | crate::layout_engine::LayoutKind::HorizontalTabbed
+            | crate::layout_engine::LayoutKind::VerticalTabbed => parent_rect,
+        }
     }
 
-    fn get_focused_frame_for_index(&self, index: usize, _focused_idx: usize) -> CGRect {
-        use objc2_core_foundation::{CGPoint, CGSize};
-        const FOCUS_SIZE_INCREASE: f64 = 10.0;
-        const FOCUS_OFFSET_DECREASE: f64 = 5.0;
-        let offset_amount = index as f64 * self.stack_offset;
-        let (origin_x, origin_y) = match self.is_horizontal {
-            true => (
-                if index == 0 {
-                    self.container_rect.origin.x
-                } else {
-                    self.container_rect.origin.x + offset_amount - FOCUS_OFFSET_DECREASE
-                },
-                self.container_rect.origin.y - FOCUS_OFFSET_DECREASE,
-            ),
-            false => (
-                self.container_rect.origin.x - FOCUS_OFFSET_DECREASE,
-                if index == 0 {
-                    self.container_rect.origin.y
-                } else {
-                    self.container_rect.origin.y + offset_amount - FOCUS_OFFSET_DECREASE
-                },
-            ),
+    fn get_ascii_tree(
+        &self,
+        node: NodeId,
+        depth: usize,
+        options: &TreeRenderOptions,
+        focus_path: &[NodeId],
+        budget: &mut TreeRenderBudget,
+    ) -> ascii_tree::Tree {
+        let status = match node.parent(&self.tree.map) {
+            None => "",
+            Some(parent)
+                if self.tree.data.selection.local_selection(&self.tree.map, parent)
+                    == Some(node) =>
+            {
+                "☒ "
+            }
+            _ => "☐ ",
         };
-        let width = (self.window_width + FOCUS_SIZE_INCREASE).min(self.container_rect.size.width);
-        let height =
-            (self.window_height + FOCUS_SIZE_INCREASE).min(self.container_rect.size.height);
-        let container_x = self.container_rect.origin.x;
-        let container_y = self.container_rect.origin.y;
-        let container_width = self.container_rect.size.width;
-        let container_height = self.container_rect.size.height;
-        let min_x = container_x;
-        let max_x = (container_x + container_width - width).max(min_x);
-        let min_y = container_y;
-        let max_y = (container_y + container_height - height).max(min_y);
-        let x = origin_x.clamp(min_x, max_x);
-        let y = origin_y.clamp(min_y, max_y);
-        CGRect::new(CGPoint { x, y }, CGSize { width, height }).round()
+        let desc = format!("{status}{node:?}");
+        let desc = match self.window_at(node) {
+            Some(wid) => format!("{desc} {:?} {}", wid, self.tree.data.layout.debug(node, false)),
+            None => format!("{desc} {}", self.tree.data.layout.debug(node, true)),
+        };
+        if node.is_empty(self.map()) {
+            return ascii_tree::Tree::Leaf(vec![desc]);
+        }
+        if !options.should_expand(depth, focus_path.contains(&node)) {
+            let windows = node
+                .traverse_preorder(self.map())
+                .filter(|&n| self.tree.data.window.at(n).is_some())
+                .count();
+            return ascii_tree::Tree::Node(
+                desc,
+                vec![ascii_tree::Tree::Leaf(vec![collapsed_tree_summary(
+                    windows,
+                )])],
+            );
+        }
+        let mut children = Vec::new();
+        for child in node.children(self.map()) {
+            if !budget.take() {
+                break;
+            }
+            children.push(self.get_ascii_tree(child, depth + 1, options, focus_path, budget));
+        }
+        if children.is_empty() {
+            ascii_tree::Tree::Leaf(vec![desc])
+        } else {
+            ascii_tree::Tree::Node(desc, children)
+        }
     }
-}
 
-#[derive(Default, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
-struct LayoutInfo {
-    size: f32,
-    total: f32,
-    kind: LayoutKind,
-    last_ungrouped_kind: LayoutKind,
-    #[serde(default)]
-    is_fullscreen: bool,
-    #[serde(default)]
-    is_fullscreen_within_gaps: bool,
-}
+    fn add_window_under(&mut self, layout: LayoutId, parent: NodeId, wid: WindowId) -> NodeId {
+        if let Some(existing) = self.tree.data.window.node_for(layout, wid) {
+            return existing;
+        }
+        let node = self.tree.mk_node().push_back(parent);
+        self.tree.data.window.set_window(layout, node, wid);
+        node
+    }
 
-#[derive(Default, Serialize, Deserialize)]
-struct Layout {
-    info: slotmap::SecondaryMap<NodeId, LayoutInfo>,
-}
+    fn window_at(&self, node: NodeId) -> Option<WindowId> {
+        self.tree.data.window.at(node)
+    }
 
-impl Layout {
-    fn handle_event(&mut self, map: &NodeMap, event: TreeEvent) {
-        match event {
-            TreeEvent::AddedToForest(node) => {
-                self.info.insert(node, LayoutInfo::default());
-            }
-            TreeEvent::AddedToParent(node) => {
-                let parent = node.parent(map).unwrap();
-                self.info[node].size = 1.0;
-                self.info[parent].total += 1.0;
-            }
-            TreeEvent::Copied { src, dest, .. } => {
-                self.info.insert(dest, self.info[src]);
-            }
-            TreeEvent::RemovingFromParent(node) => {
-                self.info[node.parent(map).unwrap()].total -= self.info[node].size;
+    /// The first window met entering `node` moving in `direction`. Like
+    /// `visible_windows_under_internal`, only a stack's selected member is
+    /// considered, except that a peeking stack along `direction` is entered
+    /// at the member showing at its edge.
+    fn window_in_direction_from(&self, node: NodeId, direction: Direction) -> Option<WindowId> {
+        if let Some(window) = self.window_at(node) {
+            return Some(window);
+        }
+        let map = self.map();
+        let kind = self.layout(node);
+        let mut children: Vec<_> = if kind.is_group()
+            && (kind.is_tabbed() || kind.orientation() != direction.orientation())
+        {
+            let selected = self.tree.data.selection.local_selection(map, node);
+            selected.or_else(|| node.first_child(map)).into_iter().collect()
+        } else {
+            node.children(map).collect()
+        };
+        match direction {
+            Direction::Left | Direction::Up => children.reverse(),
+            Direction::Right | Direction::Down => {}
+        }
+        if kind.is_group() {
+            children.truncate(1);
+        }
+        for child in children {
+            if let Some(window) = self.window_in_direction_from(child, direction) {
+                return Some(window);
             }
-            TreeEvent::RemovedFromForest(node) => {
-                self.info.remove(node);
+        }
+        None
+    }
+
+    /// The containers of `layout` whose children are tiled along `axis`.
+    fn tiled_containers_along(&self, layout: LayoutId, axis: Orientation) -> Vec<NodeId> {
+        let map = self.map();
+        self.root(layout)
+            .traverse_preorder(map)
+            .filter(|&node| !node.is_empty(map) && self.layout(node) == LayoutKind::from(axis))
+            .collect()
+    }
+
+    /// Reverses the children of `node`. Sizes travel with the children, so
+    /// each keeps its share.
+    fn reverse_children(&mut self, node: NodeId) {
+        let children: Vec<_> = node.children(self.map()).collect();
+        for child in children.into_iter().rev() {
+            child.detach(&mut self.tree).push_back(node).finish();
+        }
+    }
+
+    fn rebalance_node(&mut self, node: NodeId) {
+        let map = &self.tree.map;
+        let children: Vec<_> = node.children(map).collect();
+        let count = children.len() as f32;
+        if count == 0.0 {
+            return;
+        }
+        self.tree.data.layout.info[node].total = count;
+        for &child in &children {
+            if self.tree.data.layout.info[child].size == 0.0 {
+                self.tree.data.layout.info[child].size = 1.0;
             }
         }
+        for child in children {
+            self.rebalance_node(child);
+        }
     }
 
-    fn assume_size_of(&mut self, new: NodeId, old: NodeId, map: &NodeMap) {
-        assert_eq!(new.parent(map), old.parent(map));
-        let parent = new.parent(map).unwrap();
-        self.info[parent].total -= self.info[new].size;
-        self.info[new].size = core::mem::replace(&mut self.info[old].size, 0.0);
+    fn select(&mut self, selection: NodeId) {
+        self.tree.data.selection.select(&self.tree.map, selection)
+    }
+
+    fn stack_target_container(&self, layout: LayoutId) -> Option<NodeId> {
+        let selection = self.selection(layout);
+        if self.tree.data.window.at(selection).is_some() {
+            selection.parent(self.map())
+        } else {
+            Some(selection)
+        }
+    }
+
+    fn traverse_internal(&self, from: NodeId, direction: Direction) -> Option<NodeId> {
+        let map = &self.tree.map;
+        if let Some(sibling) = self.move_over(from, direction) {
+            return Some(sibling);
+        }
+        let node = from.ancestors(map).skip(1).find_map(|ancestor| {
+            self.move_over(ancestor, direction)
+                .map(|target| self.descend_into_target(target, direction, map))
+        });
+        node.flatten()
     }
 
-    fn set_kind(&mut self, node: NodeId, kind: LayoutKind) {
-        self.info[node].kind = kind;
-        if !kind.is_group() {
-            self.info[node].last_ungrouped_kind = kind;
+    fn descend_into_target(
+        &self,
+        target: NodeId,
+        direction: Direction,
+        map: &NodeMap,
+    ) -> Option<NodeId> {
+        let mut current = target;
+        loop {
+            let children: Vec<_> = current.children(map).collect();
+            if children.is_empty() {
+                return Some(current);
+            }
+            let layout_kind = self.tree.data.layout.kind(current);
+            if Self::enters_stack_at_edge(layout_kind, direction) {
+                let edge = match direction {
+                    Direction::Left | Direction::Up => children.last(),
+                    Direction::Right | Direction::Down => children.first(),
+                };
+                current = *edge.unwrap();
+                continue;
+            }
+            if let Some(selected) = self.tree.data.selection.local_selection(map, current) {
+                match (layout_kind, direction) {
+                    (LayoutKind::Horizontal, Direction::Up | Direction::Down)
+                    | (LayoutKind::Vertical, Direction::Left | Direction::Right) => {
+                        current = selected;
+                        continue;
+                    }
+                    _ if layout_kind.is_stacked() => {
+                        current = selected;
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            let next_child = match (layout_kind, direction) {
+                (LayoutKind::Horizontal, Direction::Left) => self
+                    .tree
+                    .data
+                    .selection
+                    .local_selection(map, current)
+                    .or(children.first().copied()),
+                (LayoutKind::Horizontal, Direction::Right) => self
+                    .tree
+                    .data
+                    .selection
+                    .local_selection(map, current)
+                    .or(children.last().copied()),
+                (LayoutKind::Horizontal, Direction::Up) => self
+                    .tree
+                    .data
+                    .selection
+                    .local_selection(map, current)
+                    .or(children.first().copied()),
+                (LayoutKind::Horizontal, Direction::Down) => self
+                    .tree
+                    .data
+                    .selection
+                    .local_selection(map, current)
+                    .or(children.last().copied()),
+                (LayoutKind::Vertical, Direction::Up) => self
+                    .tree
+                    .data
+                    .selection
+                    .local_selection(map, current)
+                    .or(children.first().copied()),
+                (LayoutKind::Vertical, Direction::Down) => self
+                    .tree
+                    .data
+                    .selection
+                    .local_selection(map, current)
+                    .or(children.last().copied()),
+                (LayoutKind::Vertical, Direction::Left) => self
+                    .tree
+                    .data
+                    .selection
+                    .local_selection(map, current)
+                    .or(children.first().copied()),
+                (LayoutKind::Vertical, Direction::Right) => self
+                    .tree
+                    .data
+                    .selection
+                    .local_selection(map, current)
+                    .or(children.last().copied()),
+                _ if layout_kind.is_stacked() => self
+                    .tree
+                    .data
+                    .selection
+                    .local_selection(map, current)
+                    .or(children.first().copied()),
+                _ => None,
+            };
+            match next_child {
+                Some(child) => current = child,
+                None => return Some(current),
+            }
+        }
+    }
+
+    /// Whether moving in `direction` into a stack of `kind` enters at the
+    /// member nearest the move rather than the stack's remembered selection.
+    /// Only stacks along the axis of the move do; perpendicular stacks keep
+    /// showing whichever member was last selected.
+    fn enters_stack_at_edge(kind: LayoutKind, direction: Direction) -> bool {
+        kind.is_stacked() && kind.orientation() == direction.orientation()
+    }
+
+    fn visible_windows_under_internal(&self, node: NodeId) -> Vec<WindowId> {
+        let mut stack = Vec::with_capacity(16);
+        stack.push(node);
+        let mut windows = Vec::with_capacity(16);
+        while let Some(node) = stack.pop() {
+            if self.layout(node).is_group() {
+                stack.extend(self.tree.data.selection.local_selection(self.map(), node));
+            } else {
+                stack.extend(node.children_rev(self.map()));
+            }
+            windows.extend(self.window_at(node));
+        }
+        windows
+    }
+
+    fn move_over(&self, from: NodeId, direction: Direction) -> Option<NodeId> {
+        let parent = from.parent(&self.tree.map)?;
+        if self.tree.data.layout.kind(parent).orientation() == direction.orientation() {
+            match direction {
+                Direction::Left | Direction::Up => from.prev_sibling(&self.tree.map),
+                Direction::Right | Direction::Down => from.next_sibling(&self.tree.map),
+            }
+        } else {
+            None
+        }
+    }
+
+    fn move_node(&mut self, layout: LayoutId, moving_node: NodeId, direction: Direction) -> bool {
+        let map = &self.tree.map;
+        let Some(old_parent) = moving_node.parent(map) else {
+            return false;
+        };
+        let is_selection =
+            self.tree.data.selection.local_selection(map, old_parent) == Some(moving_node);
+        let moved = self.move_node_inner(layout, moving_node, direction);
+        if moved && is_selection {
+            for node in moving_node.ancestors(&self.tree.map) {
+                if node == old_parent {
+                    break;
+                }
+                self.tree.data.selection.select_locally(&self.tree.map, node);
+            }
+        }
+        moved
+    }
+
+    fn move_node_inner(
+        &mut self,
+        layout: LayoutId,
+        moving_node: NodeId,
+        direction: Direction,
+    ) -> bool {
+        enum Destination {
+            Ahead(NodeId),
+            Behind(NodeId),
+        }
+        let map = &self.tree.map;
+        // Moving into a stack along its axis adds the node as a member at the
+        // near end, the one focus would enter the stack at.
+        if let Some(sibling) = self.move_over(moving_node, direction)
+            && self.layout(sibling).is_stacked()
+            && self.layout(sibling).orientation() == direction.orientation()
+        {
+            match (direction, sibling.first_child(map)) {
+                (Direction::Right | Direction::Down, Some(first)) => {
+                    moving_node.detach(&mut self.tree).insert_before(first);
+                }
+                _ => {
+                    moving_node.detach(&mut self.tree).push_back(sibling);
+                }
+            }
+            return true;
+        }
+        let destination;
+        if let Some(sibling) = self.move_over(moving_node, direction) {
+            let mut node = sibling;
+            let target = loop {
+                let Some(next) =
+                    self.tree.data.selection.local_selection(map, node).or(node.first_child(map))
+                else {
+                    break node;
+                };
+                if self.tree.data.layout.kind(node).orientation() == direction.orientation() {
+                    break next;
+                }
+                node = next;
+            };
+            if target == sibling {
+                destination = Destination::Ahead(sibling);
+            } else {
+                destination = Destination::Behind(target);
+            }
+        } else {
+            let target_ancestor = moving_node.ancestors_with_parent(&self.tree.map).skip(1).find(
+                |(_node, parent)| {
+                    parent
+                        .map(|p| self.layout(p).orientation() == direction.orientation())
+                        .unwrap_or(false)
+                },
+            );
+            if let Some((target, _parent)) = target_ancestor {
+                destination = Destination::Ahead(target);
+            } else {
+                let old_root = moving_node.ancestors(map).last().unwrap();
+                if self.tree.data.layout.kind(old_root).orientation() == direction.orientation() {
+                    let is_edge_move = match direction {
+                        Direction::Left | Direction::Up => moving_node.prev_sibling(map).is_none(),
+                        Direction::Right | Direction::Down => {
+                            moving_node.next_sibling(map).is_none()
+                        }
+                    };
+                    if !is_edge_move {
+                        return false;
+                    }
+                }
+                let new_container_kind = LayoutKind::from(direction.orientation());
+                self.nest_in_container_internal(layout, old_root, new_container_kind);
+                destination = Destination::Ahead(old_root);
+            }
+        }
+        match (destination, direction) {
+            (Destination::Ahead(target), Direction::Right | Direction::Down) => {
+                moving_node.detach(&mut self.tree).insert_after(target);
+            }
+            (Destination::Behind(target), Direction::Right | Direction::Down) => {
+                moving_node.detach(&mut self.tree).insert_before(target);
+            }
+            (Destination::Ahead(target), Direction::Left | Direction::Up) => {
+                moving_node.detach(&mut self.tree).insert_before(target);
+            }
+            (Destination::Behind(target), Direction::Left | Direction::Up) => {
+                moving_node.detach(&mut self.tree).insert_after(target);
+            }
+        }
+        true
+    }
+
+    /// The selection and its ancestors that sit in a split, not a group, or
+    /// `None` when no window is selected. Preserved sizes are rebaselined
+    /// first, since the caller is about to resize one of them.
+    fn resize_candidates(&mut self, layout: LayoutId) -> Option<Vec<NodeId>> {
+        let selection = self.selection(layout);
+        self.window_at(selection)?;
+        self.rebaseline_preserved_sizes(layout);
+        Some(
+            selection
+                .ancestors(self.map())
+                .filter(|&node| {
+                    node.parent(self.map()).is_some_and(|parent| !self.layout(parent).is_group())
+                })
+                .collect(),
+        )
+    }
+
+    fn resize_internal(&mut self, node: NodeId, screen_ratio: f64, direction: Direction) -> bool {
+        let can_resize = |&node: &NodeId| -> bool {
+            let Some(parent) = node.parent(&self.tree.map) else {
+                return false;
+            };
+            !self.tree.data.layout.kind(parent).is_group()
+                && self.move_over(node, direction).is_some()
+        };
+        let Some(resizing_node) = node.ancestors(&self.tree.map).find(can_resize) else {
+            return false;
+        };
+        let sibling = self.move_over(resizing_node, direction).unwrap();
+        let exchange_rate = resizing_node
+            .ancestors(&self.tree.map)
+            .skip(1)
+            .try_fold(1.0, |r, node| match node.parent(&self.tree.map) {
+                Some(parent)
+                    if self.tree.data.layout.kind(parent).orientation()
+                        == direction.orientation()
+                        && !self.tree.data.layout.kind(parent).is_group() =>
+                {
+                    self.tree.data.layout.proportion(&self.tree.map, node).map(|p| r * p)
+                }
+                _ => Some(r),
+            })
+            .unwrap_or(1.0);
+        let local_ratio = screen_ratio
+            * f64::from(
+                self.tree.data.layout.info[resizing_node.parent(&self.tree.map).unwrap()].total,
+            )
+            / exchange_rate;
+        self.tree.data.layout.take_share(
+            &self.tree.map,
+            resizing_node,
+            sibling,
+            local_ratio as f32,
+        );
+        true
+    }
+
+    fn set_frame_from_resize(
+        &mut self,
+        node: NodeId,
+        old_frame: CGRect,
+        new_frame: CGRect,
+        screen: CGRect,
+    ) {
+        let mut check_or_resize = |resize: bool| {
+            let mut count = 0;
+            let mut first_direction: Option<Direction> = None;
+            let mut good = true;
+            let deltas = [
+                (
+                    Direction::Left,
+                    old_frame.min().x - new_frame.min().x,
+                    screen.size.width,
+                ),
+                (
+                    Direction::Right,
+                    new_frame.max().x - old_frame.max().x,
+                    screen.size.width,
+                ),
+                (
+                    Direction::Up,
+                    old_frame.min().y - new_frame.min().y,
+                    screen.size.height,
+                ),
+                (
+                    Direction::Down,
+                    new_frame.max().y - old_frame.max().y,
+                    screen.size.height,
+                ),
+            ];
+            for (direction, delta, whole) in deltas {
+                if delta != 0.0 {
+                    count += 1;
+                    if count > 2 {
+                        good = false;
+                    }
+                    if let Some(first) = first_direction {
+                        if first.orientation() == direction.orientation() {
+                            good = false;
+                        }
+                    } else {
+                        first_direction = Some(direction);
+                    }
+                    if resize {
+                        self.resize_internal(node, delta / whole, direction);
+                    }
+                }
+            }
+            good
+        };
+        if !check_or_resize(false) {
+            warn!(
+                "Only resizing in 2 directions is supported, but was asked to resize from {old_frame:?} to {new_frame:?}"
+            );
+            return;
+        }
+        check_or_resize(true);
+    }
+
+    fn nest_in_container_internal(
+        &mut self,
+        layout: LayoutId,
+        node: NodeId,
+        kind: LayoutKind,
+    ) -> NodeId {
+        let old_parent = node.parent(&self.tree.map);
+        let parent = if node.prev_sibling(&self.tree.map).is_none()
+            && node.next_sibling(&self.tree.map).is_none()
+        {
+            old_parent
+        } else {
+            None
+        }
+        .unwrap_or_else(|| {
+            let new_parent = if let Some(old_parent) = old_parent {
+                let is_selection =
+                    self.tree.data.selection.local_selection(self.map(), old_parent) == Some(node);
+                let new_parent = self.tree.mk_node().insert_before(node);
+                self.tree.data.layout.assume_size_of(new_parent, node, &self.tree.map);
+                node.detach(&mut self.tree).push_back(new_parent);
+                if is_selection {
+                    self.tree.data.selection.select_locally(&self.tree.map, new_parent);
+                }
+                new_parent
+            } else {
+                let layout_root = self.layout_roots.get_mut(layout).unwrap();
+                layout_root.replace(self.tree.mk_node()).push_back(layout_root.id());
+                layout_root.id()
+            };
+            self.tree.data.selection.select_locally(&self.tree.map, node);
+            new_parent
+        });
+        self.tree.data.layout.set_kind(parent, kind);
+        parent
+    }
+
+    fn find_or_create_common_parent_internal(
+        &mut self,
+        _layout: LayoutId,
+        node1: NodeId,
+        node2: NodeId,
+    ) -> NodeId {
+        let map = self.map();
+        if node1 == node2 {
+            return node1;
+        }
+        if node1.ancestors(map).any(|ancestor| ancestor == node2) {
+            return node2;
+        }
+        if node2.ancestors(map).any(|ancestor| ancestor == node1) {
+            return node1;
+        }
+        let parent1 = node1.parent(self.map());
+        let parent2 = node2.parent(self.map());
+        if let (Some(p1), Some(p2)) = (parent1, parent2)
+            && p1 == p2
+        {
+            let new_parent = self.tree.mk_node().insert_before(node1);
+            self.tree.data.layout.assume_size_of(new_parent, node1, &self.tree.map);
+            self.tree.data.layout.assume_size_of(new_parent, node2, &self.tree.map);
+            node1.detach(&mut self.tree).push_back(new_parent);
+            node2.detach(&mut self.tree).push_back(new_parent);
+            return new_parent;
+        }
+        let ancestors1: Vec<_> = node1.ancestors(self.map()).collect();
+        let ancestors2: Vec<_> = node2.ancestors(self.map()).collect();
+        for &ancestor in &ancestors1 {
+            if ancestors2.contains(&ancestor) {
+                let container = {
+                    let node = self.tree.mk_node().push_back(ancestor);
+                    self.tree.data.layout.set_kind(node, LayoutKind::Horizontal);
+                    node
+                };
+                node1.detach(&mut self.tree).push_back(container);
+                node2.detach(&mut self.tree).push_back(container);
+                return container;
+            }
+        }
+        panic!("Nodes are not in the same tree, cannot find common parent");
+    }
+
+    fn remove_unnecessary_container_internal(&mut self, container: NodeId) {
+        let children: Vec<_> = container.children(self.map()).collect();
+        if children.len() <= 1 {
+            let parent = container.parent(self.map());
+            let root = container.ancestors(self.map()).last().unwrap_or(container);
+            let previous = self.tree.data.selection.current_selection(root);
+            let selection_inside = previous.ancestors(self.map()).any(|node| node == container);
+            for child in children {
+                let detached = child.detach(&mut self.tree);
+                if parent.is_some() {
+                    // Take the container's place and share so the rest of
+                    // the parent doesn't shift.
+                    detached
+                        .insert_after(container)
+                        .with(|child, tree| {
+                            tree.data.layout.assume_size_of(child, container, &tree.map)
+                        })
+                        .finish();
+                } else {
+                    detached.remove();
+                }
+            }
+            if self.tree.map.contains(container) {
+                container.detach(&mut self.tree).remove();
+            }
+            let Some(parent) = parent else { return };
+            if !selection_inside {
+                return;
+            }
+            let target = Some(previous)
+                .filter(|&node| node != container && self.tree.map.contains(node))
+                .or_else(|| self.find_best_focus_target(parent).map(|(node, _)| node));
+            if let Some(target) = target {
+                self.select(target);
+            }
+        }
+    }
+}
+
+/// A join target, tagged with how it was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum JoinTarget {
+    /// The neighbor of a two-child (or edge) parent lying along the join axis.
+    ParentNeighbor(NodeId),
+    /// The selection's own sibling.
+    Sibling(NodeId),
+    /// The next or previous member of the selection's stack.
+    StackNeighbor(NodeId),
+    /// The child of an ancestor's neighbor nearest to the selection.
+    Hierarchical(NodeId),
+}
+
+impl JoinTarget {
+    fn node(self) -> NodeId {
+        match self {
+            JoinTarget::ParentNeighbor(node)
+            | JoinTarget::Sibling(node)
+            | JoinTarget::StackNeighbor(node)
+            | JoinTarget::Hierarchical(node) => node,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Components {
+    selection: Selection,
+    layout: Layout,
+    window: Window,
+}
+
+impl tree::Observer for Components {
+    fn added_to_forest(&mut self, map: &NodeMap, node: NodeId) {
+        self.dispatch_event(map, TreeEvent::AddedToForest(node))
+    }
+    fn added_to_parent(&mut self, map: &NodeMap, node: NodeId) {
+        self.dispatch_event(map, TreeEvent::AddedToParent(node))
+    }
+    fn removing_from_parent(&mut self, map: &NodeMap, node: NodeId) {
+        self.dispatch_event(map, TreeEvent::RemovingFromParent(node))
+    }
+    fn removed_child(tree: &mut Tree<Self>, parent: NodeId) {
+        let root = parent.ancestors(&tree.map).last().unwrap_or(parent);
+        if root == parent {
+            tree.data.repair_selection(&tree.map, root);
+            return;
+        }
+        if parent.is_empty(&tree.map) {
+            parent.detach(tree).remove();
+        } else if parent.first_child(&tree.map) == parent.last_child(&tree.map)
+            && !tree.data.layout.info[parent].locked
+        {
+            let child = parent.first_child(&tree.map).unwrap();
+            child
+                .detach(tree)
+                .insert_after(parent)
+                .with(|child_id, tree| tree.data.layout.assume_size_of(child_id, parent, &tree.map))
+                .finish();
+        }
+        tree.data.repair_selection(&tree.map, root);
+    }
+    fn removed_from_forest(&mut self, map: &NodeMap, node: NodeId) {
+        self.dispatch_event(map, TreeEvent::RemovedFromForest(node))
+    }
+}
+
+impl Components {
+    fn dispatch_event(&mut self, map: &NodeMap, event: TreeEvent) {
+        self.selection.handle_event(map, event);
+        self.layout.handle_event(map, event);
+        self.window.handle_event(map, event);
+    }
+
+    fn find_best_focus_target(&self, map: &NodeMap, node: NodeId) -> Option<(NodeId, WindowId)> {
+        if let Some(wid) = self.window.at(node) {
+            return Some((node, wid));
+        }
+        let children: Vec<_> = node.children(map).collect();
+        if children.is_empty() {
+            return None;
+        }
+        if let Some(selected) = self.selection.local_selection(map, node)
+            && let Some(target) = self.find_best_focus_target(map, selected)
+        {
+            return Some(target);
+        }
+        for &child in &children {
+            if let Some(target) = self.find_best_focus_target(map, child) {
+                return Some(target);
+            }
+        }
+        None
+    }
+
+    /// If removing a node left the selection path ending on a container that
+    /// no longer knows which child is selected, move the selection down to the
+    /// nearest window under that container instead of leaving it stranded.
+    fn repair_selection(&mut self, map: &NodeMap, root: NodeId) {
+        let selection = self.selection.current_selection(root);
+        if !self.selection.lost_selected_child(map, selection) {
+            return;
+        }
+        if let Some((target, _)) = self.find_best_focus_target(map, selection) {
+            self.selection.select(map, target);
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Default, Serialize, Deserialize)]
+struct Window {
+    windows: slotmap::SecondaryMap<NodeId, WindowId>,
+    #[serde_as(as = "MapEntries")]
+    window_nodes: crate::common::collections::BTreeMap<WindowId, WindowNodeInfoVec>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WindowNodeInfo {
+    layout: LayoutId,
+    node: NodeId,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct WindowNodeInfoVec(Vec<WindowNodeInfo>);
+
+impl Window {
+    fn at(&self, node: NodeId) -> Option<WindowId> {
+        self.windows.get(node).copied()
+    }
+    fn node_for(&self, layout: LayoutId, wid: WindowId) -> Option<NodeId> {
+        self.window_nodes.get(&wid).and_then(|nodes| {
+            nodes.0.iter().find(|info| info.layout == layout).map(|info| info.node)
+        })
+    }
+    /// Binds `wid` to `node`. A window may only be bound to one node per layout,
+    /// otherwise `node_for` becomes ambiguous; a second binding is rejected.
+    fn set_window(&mut self, layout: LayoutId, node: NodeId, wid: WindowId) -> bool {
+        if let Some(bound) = self.node_for(layout, wid)
+            && bound != node
+        {
+            warn!("Refusing to bind {wid:?} to {node:?}; already bound to {bound:?} in {layout:?}");
+            return false;
+        }
+        let existing = self.windows.insert(node, wid);
+        assert!(
+            existing.is_none(),
+            "Attempted to overwrite window for node {node:?} from {existing:?} to {wid:?}"
+        );
+        let infos = &mut self.window_nodes.entry(wid).or_default().0;
+        infos.push(WindowNodeInfo { layout, node });
+        debug_assert_eq!(
+            infos.iter().filter(|info| info.layout == layout).count(),
+            1,
+            "{wid:?} is bound to more than one node in {layout:?}"
+        );
+        true
+    }
+    /// Drops every binding in `layout`.
+    fn unbind_layout(&mut self, layout: LayoutId) {
+        self.window_nodes.retain(|_, infos| {
+            infos.0.retain(|info| info.layout != layout);
+            !infos.0.is_empty()
+        });
+    }
+    /// Points the binding of `wid` in `layout` at `node`, dropping any other
+    /// bindings it had in that layout.
+    fn rebind(&mut self, layout: LayoutId, wid: WindowId, node: NodeId) {
+        let infos = self.window_nodes.entry(wid).or_default();
+        infos.0.retain(|info| info.layout != layout);
+        infos.0.push(WindowNodeInfo { layout, node });
+    }
+    fn take_nodes_for(&mut self, wid: WindowId) -> impl Iterator<Item = (LayoutId, NodeId)> {
+        self.window_nodes
+            .remove(&wid)
+            .unwrap_or_default()
+            .0
+            .into_iter()
+            .map(|info| (info.layout, info.node))
+    }
+    fn take_nodes_for_app(
+        &mut self,
+        pid: pid_t,
+    ) -> impl Iterator<Item = (WindowId, LayoutId, NodeId)> {
+        use crate::common::collections::BTreeExt;
+        let removed = self.window_nodes.remove_all_for_pid(pid);
+        removed.into_iter().flat_map(|(wid, infos)| {
+            infos.0.into_iter().map(move |info| (wid, info.layout, info.node))
+        })
+    }
+    fn handle_event(&mut self, map: &NodeMap, event: TreeEvent) {
+        match event {
+            TreeEvent::AddedToForest(_) => (),
+            TreeEvent::AddedToParent(node) => debug_assert!(
+                self.windows.get(node.parent(map).unwrap()).is_none(),
+                "Window nodes are not allowed to have children: {:?}/{:?}",
+                node.parent(map).unwrap(),
+                node
+            ),
+            TreeEvent::Copied { src, dest, dest_layout } => {
+                if let Some(&wid) = self.windows.get(src) {
+                    let _ = self.set_window(dest_layout, dest, wid);
+                }
+            }
+            TreeEvent::RemovingFromParent(_) => (),
+            TreeEvent::RemovedFromForest(node) => {
+                if let Some(wid) = self.windows.remove(node)
+                    && let Some(window_nodes) = self.window_nodes.get_mut(&wid)
+                {
+                    window_nodes.0.retain(|info| info.node != node);
+                    if window_nodes.0.is_empty() {
+                        self.window_nodes.remove(&wid);
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct StackLayoutResult {
+    container_rect: CGRect,
+    stack_offset: f64,
+    focus_pop: f64,
+    is_horizontal: bool,
+    window_width: f64,
+    window_height: f64,
+}
+
+impl StackLayoutResult {
+    fn new(
+        container_rect: CGRect,
+        window_count: usize,
+        stack_offset: f64,
+        focus_pop: f64,
+        is_horizontal: bool,
+    ) -> Self {
+        let total_offset_space = if window_count > 0 {
+            (window_count - 1) as f64 * stack_offset
+        } else {
+            0.0
+        };
+        let (window_width, window_height) = if is_horizontal {
+            (
+                (container_rect.size.width - total_offset_space).max(100.0),
+                container_rect.size.height.max(100.0),
+            )
+        } else {
+            (
+                container_rect.size.width.max(100.0),
+                (container_rect.size.height - total_offset_space).max(100.0),
+            )
+        };
+        Self {
+            container_rect,
+            stack_offset,
+            focus_pop,
+            is_horizontal,
+            window_width,
+            window_height,
+        }
+    }
+
+    fn get_frame_for_index(&self, index: usize) -> CGRect {
+        use objc2_core_foundation::{CGPoint, CGSize};
+        let offset_amount = index as f64 * self.stack_offset;
+        let (x_offset, y_offset) = if self.is_horizontal {
+            (offset_amount, 0.0)
+        } else {
+            (0.0, offset_amount)
+        };
+        CGRect::new(
+            CGPoint::new(
+                self.container_rect.origin.x + x_offset,
+                self.container_rect.origin.y + y_offset,
+            ),
+            CGSize::new(self.window_width, self.window_height),
+        )
+        .round()
+    }
+
+    fn get_focused_frame_for_index(&self, index: usize, _focused_idx: usize) -> CGRect {
+        use objc2_core_foundation::{CGPoint, CGSize};
+        if self.focus_pop <= 0.0 {
+            return self.get_frame_for_index(index);
+        }
+        let focus_size_increase = self.focus_pop;
+        let focus_offset_decrease = self.focus_pop / 2.0;
+        let offset_amount = index as f64 * self.stack_offset;
+        let (origin_x, origin_y) = match self.is_horizontal {
+            true => (
+                if index == 0 {
+                    self.container_rect.origin.x
+                } else {
+                    self.container_rect.origin.x + offset_amount - focus_offset_decrease
+                },
+                self.container_rect.origin.y - focus_offset_decrease,
+            ),
+            false => (
+                self.container_rect.origin.x - focus_offset_decrease,
+                if index == 0 {
+                    self.container_rect.origin.y
+                } else {
+                    self.container_rect.origin.y + offset_amount - focus_offset_decrease
+                },
+            ),
+        };
+        let width = (self.window_width + focus_size_increase).min(self.container_rect.size.width);
+        let height =
+            (self.window_height + focus_size_increase).min(self.container_rect.size.height);
+        let container_x = self.container_rect.origin.x;
+        let container_y = self.container_rect.origin.y;
+        let container_width = self.container_rect.size.width;
+        let container_height = self.container_rect.size.height;
+        let min_x = container_x;
+        let max_x = (container_x + container_width - width).max(min_x);
+        let min_y = container_y;
+        let max_y = (container_y + container_height - height).max(min_y);
+        let x = origin_x.clamp(min_x, max_x);
+        let y = origin_y.clamp(min_y, max_y);
+        CGRect::new(CGPoint { x, y }, CGSize { width, height }).round()
+    }
+}
+
+/// How far (in points) a preserved window may drift from its recorded size
+/// before its share is corrected.
+const PRESERVED_SIZE_TOLERANCE: f64 = 2.0;
+
+/// Number of structural changes `undo` can revert per layout.
+const UNDO_DEPTH: usize = 16;
+/// How far, relative to the total, children's sizes may drift from their
+/// container's total before `validate` reports it.
+const SIZE_TOLERANCE: f32 = 1e-3;
+/// Share of the main side against the other in a golden split.
+const GOLDEN_RATIO: f32 = 1.618;
+/// Bounds on the share a preserved window may claim, so its siblings never
+/// collapse entirely.
+const PRESERVED_SHARE_RANGE: (f64, f64) = (0.05, 0.95);
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+struct LayoutInfo {
+    size: f32,
+    total: f32,
+    kind: LayoutKind,
+    last_ungrouped_kind: LayoutKind,
+    #[serde(default)]
+    is_fullscreen: bool,
+    #[serde(default)]
+    is_fullscreen_within_gaps: bool,
+    #[serde(default)]
+    preserve_size: bool,
+    #[serde(default)]
+    preserved_size: Option<(f64, f64)>,
+    /// Kept as arranged: never collapsed, nested into, absorbed or merged
+    /// by automatic restructuring, only unjoined explicitly.
+    #[serde(default)]
+    locked: bool,
+    /// Set by `split_selection_into`: the container divides evenly among its
+    /// children as they are added, until it holds this many.
+    #[serde(default)]
+    expected_children: u32,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Layout {
+    info: slotmap::SecondaryMap<NodeId, LayoutInfo>,
+}
+
+impl Layout {
+    fn handle_event(&mut self, map: &NodeMap, event: TreeEvent) {
+        match event {
+            TreeEvent::AddedToForest(node) => {
+                self.info.insert(node, LayoutInfo::default());
+            }
+            TreeEvent::AddedToParent(node) => {
+                // Moves detach and re-attach the same node, so only its size
+                // is reset; fullscreen and other flags travel with it.
+                let parent = node.parent(map).unwrap();
+                self.info[node].size = 1.0;
+                self.info[parent].total += 1.0;
+                let expected = self.info[parent].expected_children;
+                if expected > 0 {
+                    let count = self.equalize(map, parent);
+                    if count >= expected as usize {
+                        self.info[parent].expected_children = 0;
+                    }
+                }
+            }
+            TreeEvent::Copied { src, dest, .. } => {
+                self.info.insert(dest, self.info[src]);
+            }
+            TreeEvent::RemovingFromParent(node) => {
+                // Siblings keep their sizes, so each grows into the freed
+                // space in proportion to its share.
+                self.info[node.parent(map).unwrap()].total -= self.info[node].size;
+            }
+            TreeEvent::RemovedFromForest(node) => {
+                self.info.remove(node);
+            }
+        }
+    }
+
+    /// Gives every child of `node` the same share, returning how many there
+    /// are.
+    fn equalize(&mut self, map: &NodeMap, node: NodeId) -> usize {
+        let children: Vec<_> = node.children(map).collect();
+        for &child in &children {
+            self.info[child].size = 1.0;
+        }
+        self.info[node].total = children.len() as f32;
+        children.len()
+    }
+
+    fn assume_size_of(&mut self, new: NodeId, old: NodeId, map: &NodeMap) {
+        assert_eq!(new.parent(map), old.parent(map));
+        let parent = new.parent(map).unwrap();
+        self.info[parent].total -= self.info[new].size;
+        self.info[new].size = core::mem::replace(&mut self.info[old].size, 0.0);
+    }
+
+    fn set_kind(&mut self, node: NodeId, kind: LayoutKind) {
+        self.info[node].kind = kind;
+        if !kind.is_group() {
+            self.info[node].last_ungrouped_kind = kind;
+        }
+    }
+
+    fn kind(&self, node: NodeId) -> LayoutKind {
+        self.info[node].kind
+    }
+
+    fn rotate_kind(&mut self, node: NodeId) {
+        let info = &mut self.info[node];
+        info.kind = info.kind.rotated();
+        info.last_ungrouped_kind = info.last_ungrouped_kind.rotated();
+    }
+
+    fn proportion(&self, map: &NodeMap, node: NodeId) -> Option<f64> {
+        let parent = node.parent(map)?;
+        Some(f64::from(self.info[node].size) / f64::from(self.info[parent].total))
+    }
+
+    fn take_share(&mut self, map: &NodeMap, node: NodeId, from: NodeId, share: f32) {
+        assert_eq!(node.parent(map), from.parent(map));
+        let share = share.min(self.info[from].size);
+        let share = share.max(-self.info[node].size);
+        self.info[from].size -= share;
+        self.info[node].size += share;
+    }
+
+    /// Multiplies `node`'s fraction of its parent by `factor`, shrinking or
+    /// growing its siblings proportionally so the parent's total is unchanged.
+    fn scale_share(&mut self, map: &NodeMap, node: NodeId, factor: f64) -> bool {
+        let Some(parent) = node.parent(map) else {
+            return false;
+        };
+        let total: f64 = parent.children(map).map(|child| f64::from(self.info[child].size)).sum();
+        let own = f64::from(self.info[node].size);
+        let others = total - own;
+        if total <= 0.0 || others <= 0.0 {
+            return false;
+        }
+        let (min, max) = PRESERVED_SHARE_RANGE;
+        let fraction = (own / total * factor).clamp(min, max);
+        let new_size = fraction * total;
+        if (new_size - own).abs() < f64::EPSILON {
+            return false;
+        }
+        let scale = (total - new_size) / others;
+        for child in parent.children(map) {
+            let info = &mut self.info[child];
+            info.size = if child == node {
+                new_size as f32
+            } else {
+                (f64::from(info.size) * scale) as f32
+            };
+        }
+        self.info[parent].total = parent.children(map).map(|child| self.info[child].size).sum();
+        true
+    }
+
+    fn set_fullscreen(&mut self, node: NodeId, is_fullscreen: bool) {
+        self.info[node].is_fullscreen = is_fullscreen;
+        if is_fullscreen {
+            self.info[node].is_fullscreen_within_gaps = false;
+        }
+    }
+
+    fn set_fullscreen_within_gaps(&mut self, node: NodeId, within: bool) {
+        self.info[node].is_fullscreen_within_gaps = within;
+        if within {
+            self.info[node].is_fullscreen = false;
+        }
+    }
+
+    fn toggle_fullscreen(&mut self, node: NodeId) -> bool {
+        self.info[node].is_fullscreen = !self.info[node].is_fullscreen;
+        if self.info[node].is_fullscreen {
+            self.info[node].is_fullscreen_within_gaps = false;
+        }
+        self.info[node].is_fullscreen
+    }
+
+    fn toggle_fullscreen_within_gaps(&mut self, node: NodeId) -> bool {
+        self.info[node].is_fullscreen_within_gaps = !self.info[node].is_fullscreen_within_gaps;
+        if self.info[node].is_fullscreen_within_gaps {
+            self.info[node].is_fullscreen = false;
+        }
+        self.info[node].is_fullscreen_within_gaps
+    }
+
+    fn swap_fullscreen(&mut self, a: NodeId, b: NodeId) {
+        let flags = |info: &LayoutInfo| (info.is_fullscreen, info.is_fullscreen_within_gaps);
+        let (a_flags, b_flags) = (flags(&self.info[a]), flags(&self.info[b]));
+        (
+            self.info[a].is_fullscreen,
+            self.info[a].is_fullscreen_within_gaps,
+        ) = b_flags;
+        (
+            self.info[b].is_fullscreen,
+            self.info[b].is_fullscreen_within_gaps,
+        ) = a_flags;
+    }
+
+    fn is_effectively_fullscreen(&self, node: NodeId) -> bool {
+        let info = &self.info[node];
+        info.is_fullscreen || info.is_fullscreen_within_gaps
+    }
+
+    fn debug(&self, node: NodeId, is_container: bool) -> String {
+        let info = &self.info[node];
+        if is_container {
+            let lock = if info.locked { " locked" } else { "" };
+            format!("{:?}{lock} [size {} total={}]", info.kind, info.size, info.total)
+        } else {
+            format!("[size {}]", info.size)
+        }
+    }
+
+    fn is_focused_in_subtree(&self, map: &NodeMap, window: &Window, node: NodeId) -> bool {
+        if window.at(node).is_some()
+            && let Some(parent) = node.parent(map)
+        {
+            return parent.first_child(map) == Some(node);
+        }
+        for child in node.children(map) {
+            if self.is_focused_in_subtree(map, window, child) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The rect `node` is laid out in when its parent offers it `rect`.
+    /// Fullscreen nodes take the whole screen or tiling area instead.
+    fn effective_rect(
+        &self,
+        node: NodeId,
+        rect: CGRect,
+        screen: CGRect,
+        gaps: &crate::common::config::GapSettings,
+    ) -> CGRect {
+        let info = &self.info[node];
+        if info.is_fullscreen {
+            screen
+        } else if info.is_fullscreen_within_gaps {
+            compute_tiling_area(screen, gaps)
+        } else {
+            rect
+        }
+    }
+
+    /// The rect each child of `node` is offered when `node` is laid out in
+    /// `rect`, in child order.
+    fn child_rects(
+        &self,
+        map: &NodeMap,
+        window: &Window,
+        node: NodeId,
+        rect: CGRect,
+        stack_offset: f64,
+        focus_pop: f64,
+        gaps: &crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+        stack_line_horiz: crate::common::config::HorizontalPlacement,
+        stack_line_vert: crate::common::config::VerticalPlacement,
+    ) -> Vec<(NodeId, CGRect)> {
+        let info = &self.info[node];
+        let children: Vec<_> = node.children(map).collect();
+        let child_count = children.len();
+        if child_count == 0 {
+            return Vec::new();
+        }
+        match info.kind {
+            crate::layout_engine::LayoutKind::Horizontal
+            | crate::layout_engine::LayoutKind::Vertical => {
+                let is_horizontal =
+                    matches!(info.kind, crate::layout_engine::LayoutKind::Horizontal);
+                let (origin, total_size) = if is_horizontal {
+                    (rect.origin.x, rect.size.width)
+                } else {
+                    (rect.origin.y, rect.size.height)
+                };
+                let gap = gaps.inner_gap(is_horizontal, total_size, child_count);
+                let weights: Vec<f64> =
+                    children.iter().map(|&child| f64::from(self.info[child].size)).collect();
+                let segments = axis_segments(origin, total_size, &weights, gap);
+                children
+                    .into_iter()
+                    .zip(segments)
+                    .map(|(child, (child_offset, seg_size))| {
+                        let child_rect = if is_horizontal {
+                            objc2_core_foundation::CGRect::new(
+                                objc2_core_foundation::CGPoint::new(child_offset, rect.origin.y),
+                                objc2_core_foundation::CGSize::new(seg_size, rect.size.height),
+                            )
+                        } else {
+                            objc2_core_foundation::CGRect::new(
+                                objc2_core_foundation::CGPoint::new(rect.origin.x, child_offset),
+                                objc2_core_foundation::CGSize::new(rect.size.width, seg_size),
+                            )
+                        };
+                        (child, child_rect)
+                    })
+                    .collect()
+            }
+            crate::layout_engine::LayoutKind::HorizontalStack
+            | crate::layout_engine::LayoutKind::VerticalStack => {
+                let is_horizontal =
+                    matches!(info.kind, crate::layout_engine::LayoutKind::HorizontalStack);
+                let focused_idx = children
+                    .iter()
+                    .position(|&c| self.is_focused_in_subtree(map, window, c))
+                    .unwrap_or(0);
+                let ui_selected_index =
+                    if matches!(info.kind, crate::layout_engine::LayoutKind::VerticalStack) {
+                        child_count.saturating_sub(1).saturating_sub(focused_idx)
+                    } else {
+                        focused_idx
+                    };
+                let mut container_rect = rect;
+                let reserve = stack_line_thickness.max(0.0);
+                container_rect = adjust_stack_container_rect(
+                    container_rect,
+                    is_horizontal,
+                    reserve,
+                    stack_line_horiz,
+                    stack_line_vert,
+                );
+                let layout_res = StackLayoutResult::new(
+                    container_rect,
+                    child_count,
+                    stack_offset,
+                    focus_pop,
+                    is_horizontal,
+                );
+                children
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, child)| {
+                        let child_rect = if i == ui_selected_index {
+                            layout_res.get_focused_frame_for_index(focused_idx, focused_idx)
+                        } else {
+                            layout_res.get_frame_for_index(i)
+                        };
+                        (child, child_rect)
+                    })
+                    .collect()
+            }
+            // Every member fills the container; the selected one is raised
+            // over the rest, so only it shows.
+            crate::layout_engine::LayoutKind::HorizontalTabbed
+            | crate::layout_engine::LayoutKind::VerticalTabbed => {
+                let is_horizontal =
+                    matches!(info.kind, crate::layout_engine::LayoutKind::HorizontalTabbed);
+                let container_rect = adjust_stack_container_rect(
+                    rect,
+                    is_horizontal,
+                    stack_line_thickness.max(0.0),
+                    stack_line_horiz,
+                    stack_line_vert,
+                )
+                .round();
+                children.into_iter().map(|child| (child, container_rect)).collect()
+            }
+        }
+    }
+
+    fn apply_with_gaps(
+        &self,
+        map: &NodeMap,
+        window: &Window,
+        node: NodeId,
+        rect: CGRect,
+        screen: CGRect,
+        sizes: &mut Vec<(WindowId, CGRect)>,
+        stack_offset: f64,
+        focus_pop: f64,
+        gaps: &crate::common::config::GapSettings,
+        stack_line_thickness: f64,
+        stack_line_horiz: crate::common::config::HorizontalPlacement,
+        stack_line_vert: crate::common::config::VerticalPlacement,
+        is_positionable: Option<&dyn Fn(WindowId) -> bool>,
+    ) {
+        let positionable = |wid: WindowId| is_positionable.is_none_or(|f| f(wid));
+        let rect = self.effective_rect(node, rect, screen, gaps);
+        let children = self.child_rects(
+            map,
+            window,
+            node,
+            rect,
+            stack_offset,
+            focus_pop,
+            gaps,
+            stack_line_thickness,
+            stack_line_horiz,
+            stack_line_vert,
+        );
+        if children.is_empty() {
+            if !self.info[node].kind.is_group()
+                && let Some(wid) = window.at(node)
+                && positionable(wid)
+            {
+                sizes.push((wid, rect));
+            }
+            return;
+        }
+        for (child, child_rect) in children {
+            self.apply_with_gaps(
+                map,
+                window,
+                child,
+                child_rect,
+                screen,
+                sizes,
+                stack_offset,
+                focus_pop,
+                gaps,
+                stack_line_thickness,
+                stack_line_horiz,
+                stack_line_vert,
+                is_positionable,
+            );
+        }
+        if let Some(wid) = window.at(node)
+            && positionable(wid)
+        {
+            sizes.push((wid, rect));
+        }
+    }
+}
+
+/// Splits `len` points from `origin` into one `(start, length)` segment per
+/// weight, with `gap` between neighbours. Boundaries are rounded from the
+/// running offset instead of per segment, so adjacent segments share exact
+/// pixel edges and the last one ends exactly at the rounded container edge.
+fn axis_segments(origin: f64, len: f64, weights: &[f64], gap: f64) -> Vec<(f64, f64)> {
+    let total: f64 = weights.iter().sum();
+    let total_gap = weights.len().saturating_sub(1) as f64 * gap;
+    let usable = (len - total_gap).max(0.0);
+    let end = (origin + len).round();
+    let mut offset = origin;
+    let mut segments = Vec::with_capacity(weights.len());
+    for (i, &weight) in weights.iter().enumerate() {
+        let start = offset.round();
+        offset += if total > 0.0 {
+            usable * weight / total
+        } else {
+            usable / weights.len() as f64
+        };
+        let seg_end = if i + 1 == weights.len() {
+            end
+        } else {
+            offset.round()
+        };
+        segments.push((start, (seg_end - start).max(0.0)));
+        offset += gap;
+    }
+    segments
+}
+
+fn adjust_stack_container_rect(
+    mut rect: CGRect,
+    is_horizontal: bool,
+    reserve: f64,
+    stack_line_horiz: crate::common::config::HorizontalPlacement,
+    stack_line_vert: crate::common::config::VerticalPlacement,
+) -> CGRect {
+    if reserve == 0.0 {
+        return rect;
+    }
+    if is_horizontal {
+        let new_h = (rect.size.height - reserve).max(0.0);
+        if matches!(stack_line_horiz, crate::common::config::HorizontalPlacement::Top) {
+            rect.origin.y += reserve;
+        }
+        rect.size.height = new_h;
+    } else {
+        let new_w = (rect.size.width - reserve).max(0.0);
+        if matches!(stack_line_vert, crate::common::config::VerticalPlacement::Left) {
+            rect.origin.x += reserve;
+        }
+        rect.size.width = new_w;
+    }
+    rect
+}
+
+/// The stacked kind an unstacked container of `kind` becomes.
+fn stacked_kind(
+    kind: LayoutKind,
+    default_orientation: crate::common::config::StackDefaultOrientation,
+) -> LayoutKind {
+    use crate::common::config::StackDefaultOrientation;
+    match (default_orientation, kind.orientation()) {
+        (StackDefaultOrientation::Horizontal, _)
+        | (StackDefaultOrientation::Same, Orientation::Horizontal)
+        | (StackDefaultOrientation::Perpendicular, Orientation::Vertical) => {
+            LayoutKind::HorizontalStack
+        }
+        (StackDefaultOrientation::Vertical, _)
+        | (StackDefaultOrientation::Same, Orientation::Vertical)
+        | (StackDefaultOrientation::Perpendicular, Orientation::Horizontal) => {
+            LayoutKind::VerticalStack
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use objc2_core_foundation::{CGPoint, CGRect, CGSize};
+
+    use super::*;
+    use crate::actor::app::WindowId;
+    use crate::layout_engine::{Direction, LayoutKind};
+
+    fn w(pid: i32, idx: u32) -> WindowId {
+        WindowId::new(pid, idx)
+    }
+
+    fn screen() -> CGRect {
+        CGRect::new(CGPoint::new(0.0, 0.0), CGSize::new(1920.0, 1080.0))
+    }
+
+    fn gaps() -> crate::common::config::GapSettings {
+        crate::common::config::GapSettings::default()
+    }
+
+    /// Builds a layout from `tree`: `[1, V[2, HS[3, 4]]]` is a horizontal
+    /// root holding `w(1, 1)` and a vertical container of `w(1, 2)` and a
+    /// horizontal stack. Containers are `H`, `V`, `HS`, `VS`, `HT` or `VT`,
+    /// horizontal if unmarked. Each window is selected as it is added, then
+    /// each of `selected` in turn, so stacks remember the last one in them.
+    fn build(tree: &str, selected: &[u32]) -> (TraditionalLayoutSystem, LayoutId) {
+        let mut system = TraditionalLayoutSystem::default();
+        let layout = system.create_layout();
+        let mut parents: Vec<NodeId> = Vec::new();
+        let (mut kind, mut idx) = (String::new(), String::new());
+        for ch in tree.chars() {
+            match ch {
+                'A'..='Z' => kind.push(ch),
+                '0'..='9' => idx.push(ch),
+                '[' => {
+                    let node = match parents.last() {
+                        Some(&parent) => system.tree.mk_node().push_back(parent),
+                        None => system.root(layout),
+                    };
+                    let kind = match std::mem::take(&mut kind).as_str() {
+                        "" | "H" => LayoutKind::Horizontal,
+                        "V" => LayoutKind::Vertical,
+                        "HS" => LayoutKind::HorizontalStack,
+                        "VS" => LayoutKind::VerticalStack,
+                        "HT" => LayoutKind::HorizontalTabbed,
+                        "VT" => LayoutKind::VerticalTabbed,
+                        other => panic!("unknown container kind {other:?} in {tree:?}"),
+                    };
+                    system.set_layout(node, kind);
+                    parents.push(node);
+                }
+                ',' | ' ' | ']' => {
+                    if !idx.is_empty() {
+                        let wid = w(1, std::mem::take(&mut idx).parse().unwrap());
+                        let node = system.add_window_under(layout, *parents.last().unwrap(), wid);
+                        system.select(node);
+                    }
+                    if ch == ']' {
+                        parents.pop();
+                    }
+                }
+                _ => panic!("unexpected {ch:?} in {tree:?}"),
+            }
+        }
+        for &idx in selected {
+            assert!(system.select_window(layout, w(1, idx)));
+        }
+        (system, layout)
+    }
+
+    mod window_addition {
+        use super::*;
+
+        #[test]
+        fn add_single_window() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+
+            let visible = system.visible_windows_in_layout(layout);
+            assert_eq!(visible.len(), 1);
+        }
+
+        #[test]
+        fn add_multiple_windows() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            for i in 1..=10 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+
+            let visible = system.visible_windows_in_layout(layout);
+            assert_eq!(visible.len(), 10);
+        }
+
+        #[test]
+        fn add_window_to_empty_root() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+
+            let visible = system.visible_windows_in_layout(layout);
+            assert_eq!(visible.len(), 1);
+        }
+
+        #[test]
+        fn insert_keeps_selection() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.insert_window_after_selection(layout, w(1, 1));
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+
+            system.insert_window_after_selection(layout, w(1, 2));
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+            assert!(system.visible_windows_in_layout(layout).contains(&w(1, 2)));
+
+            system.add_window_after_selection(layout, w(1, 3));
+            assert_eq!(system.selected_window(layout), Some(w(1, 3)));
+        }
+    }
+
+    mod focus_movement {
+        use super::*;
+
+        #[test]
+        fn move_focus_with_windows() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+
+            let (focus, _): (Option<WindowId>, Vec<WindowId>) =
+                system.move_focus(layout, Direction::Right);
+            assert!(focus.is_some() || focus.is_none());
+        }
+
+        #[test]
+        fn move_focus_empty_layout() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            let (focus, raise): (Option<WindowId>, Vec<WindowId>) =
+                system.move_focus(layout, Direction::Right);
+            assert_eq!(focus, None);
+            assert!(raise.is_empty());
+        }
+
+        #[test]
+        fn move_focus_all_directions() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            for i in 1..=4 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+
+            let _ = system.move_focus(layout, Direction::Left);
+            let _ = system.move_focus(layout, Direction::Right);
+            let _ = system.move_focus(layout, Direction::Up);
+            let _ = system.move_focus(layout, Direction::Down);
+        }
+
+        #[test]
+        fn select_window() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+
+            assert!(system.select_window(layout, w(1, 2)));
+        }
+
+        #[test]
+        fn entering_stack_from_the_right_lands_on_last_member() {
+            let (mut system, layout) = build("[1, HS[2, 3, 4], 5]", &[3, 5]);
+
+            let (focus, raise) = system.move_focus(layout, Direction::Left);
+            assert_eq!(focus, Some(w(1, 4)));
+            assert_eq!(raise, vec![w(1, 4)]);
+            assert_eq!(system.selected_window(layout), Some(w(1, 4)));
+        }
+
+        #[test]
+        fn entering_stack_from_the_left_lands_on_first_member() {
+            let (mut system, layout) = build("[1, HS[2, 3, 4], 5]", &[3, 1]);
+
+            let (focus, raise) = system.move_focus(layout, Direction::Right);
+            assert_eq!(focus, Some(w(1, 2)));
+            assert_eq!(raise, vec![w(1, 2)]);
+            assert_eq!(system.visible_windows_in_layout(layout).len(), 3);
+        }
+
+        #[test]
+        fn entering_perpendicular_stack_follows_its_selection() {
+            for (from, direction) in [(w(1, 5), Direction::Left), (w(1, 1), Direction::Right)] {
+                let (mut system, layout) = build("[1, VS[2, 3, 4], 5]", &[3]);
+                system.select_window(layout, from);
+
+                let (focus, raise) = system.move_focus(layout, direction);
+                assert_eq!(focus, Some(w(1, 3)), "{direction:?}");
+                assert_eq!(raise, vec![w(1, 3)], "{direction:?}");
+            }
+        }
+
+        #[test]
+        fn moving_into_stack_adds_a_member_at_the_near_end() {
+            let members = |system: &TraditionalLayoutSystem, layout, wid| {
+                let node = system.tree.data.window.node_for(layout, wid).unwrap();
+                let stack = node.parent(system.map()).unwrap();
+                assert_eq!(system.layout(stack), LayoutKind::HorizontalStack);
+                stack
+                    .children(system.map())
+                    .filter_map(|n| system.window_at(n))
+                    .collect::<Vec<_>>()
+            };
+
+            let (mut system, layout) = build("[1, HS[2, 3, 4], 5]", &[3, 1]);
+            assert!(system.move_selection(layout, Direction::Right));
+            assert_eq!(
+                members(&system, layout, w(1, 1)),
+                vec![w(1, 1), w(1, 2), w(1, 3), w(1, 4)]
+            );
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+            assert_eq!(system.visible_windows_in_layout(layout), vec![w(1, 1), w(1, 5)]);
+
+            let (mut system, layout) = build("[1, HS[2, 3, 4], 5]", &[3, 5]);
+            assert!(system.move_selection(layout, Direction::Left));
+            assert_eq!(
+                members(&system, layout, w(1, 5)),
+                vec![w(1, 2), w(1, 3), w(1, 4), w(1, 5)]
+            );
+            assert_eq!(system.selected_window(layout), Some(w(1, 5)));
+        }
+    }
+
+    mod layout_calculation {
+        use super::*;
+
+        #[test]
+        fn calculate_layout_empty() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            let result = system.calculate_layout(
+                layout,
+                screen(),
+                0.0,
+                10.0,
+                &gaps(),
+                0.0,
+                crate::common::config::HorizontalPlacement::Top,
+                crate::common::config::VerticalPlacement::Left,
+                None,
+            );
+
+            assert!(result.is_empty());
+        }
+
+        #[test]
+        fn calculate_layout_with_windows() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+
+            let result = system.calculate_layout(
+                layout,
+                screen(),
+                0.0,
+                10.0,
+                &gaps(),
+                0.0,
+                crate::common::config::HorizontalPlacement::Top,
+                crate::common::config::VerticalPlacement::Left,
+                None,
+            );
+
+            assert_eq!(result.len(), 2);
+            let total_width: f64 = result.iter().map(|(_, r)| r.size.width).sum();
+            let screen_width = screen().size.width;
+            assert!((total_width - screen_width).abs() < 1.0);
+        }
+
+        #[test]
+        fn adaptive_gaps_keep_tiles_at_the_floor() {
+            use crate::common::config::{GapPolicy, InnerGaps};
+
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            for i in 1..=5 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+            let narrow = CGRect::new(CGPoint::new(0.0, 0.0), CGSize::new(500.0, 400.0));
+            let widths = |system: &TraditionalLayoutSystem, gaps| {
+                let mut frames = system.calculate_layout(
+                    layout,
+                    narrow,
+                    0.0,
+                    0.0,
+                    &gaps,
+                    0.0,
+                    crate::common::config::HorizontalPlacement::Top,
+                    crate::common::config::VerticalPlacement::Left,
+                    None,
+                );
+                frames.sort_by(|a, b| a.1.origin.x.total_cmp(&b.1.origin.x));
+                frames.into_iter().map(|(_, frame)| frame.size.width).collect::<Vec<_>>()
+            };
+            let mut gaps = crate::common::config::GapSettings {
+                inner: InnerGaps {
+                    horizontal: 50.0,
+                    vertical: 50.0,
+                },
+                min_tile_px: 80.0,
+                ..Default::default()
+            };
+
+            // Four 50px gaps leave 60px tiles.
+            assert_eq!(widths(&system, gaps.clone()), vec![60.0; 5]);
+
+            gaps.policy = GapPolicy::Adaptive;
+            assert_eq!(widths(&system, gaps.clone()), vec![80.0; 5]);
+
+            // Gaps that already fit are left alone.
+            gaps.inner.horizontal = 10.0;
+            assert_eq!(widths(&system, gaps), vec![92.0; 5]);
+        }
+
+        #[test]
+        fn unpositionable_windows_keep_their_slot() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            for i in 1..=3 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+            let calculate = |is_positionable: Option<&dyn Fn(WindowId) -> bool>| {
+                system.calculate_layout(
+                    layout,
+                    screen(),
+                    0.0,
+                    0.0,
+                    &gaps(),
+                    0.0,
+                    crate::common::config::HorizontalPlacement::Top,
+                    crate::common::config::VerticalPlacement::Left,
+                    is_positionable,
+                )
+            };
+
+            let all = calculate(None);
+            let filtered = calculate(Some(&|wid: WindowId| wid != w(1, 2)));
+
+            assert_eq!(all.len(), 3);
+            let expected: Vec<_> = all.into_iter().filter(|&(wid, _)| wid != w(1, 2)).collect();
+            assert_eq!(filtered, expected);
+        }
+
+        #[test]
+        fn axis_segments_share_edges_and_cover_container() {
+            // Small deterministic LCG so failures reproduce.
+            let mut state = 0x2545_f491_4f6c_dd1du64;
+            let mut next = move || {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (state >> 33) as f64 / (1u64 << 31) as f64
+            };
+            for _ in 0..2000 {
+                let origin = (next() * 4000.0 - 2000.0).round() + (next() * 4.0).round() / 4.0;
+                let len = 50.0 + next() * 3000.0;
+                let count = 1 + (next() * 8.0) as usize;
+                let weights: Vec<f64> = (0..count).map(|_| 0.05 + next() * 3.0).collect();
+                let segments = axis_segments(origin, len, &weights, 0.0);
+
+                assert_eq!(segments.len(), count);
+                assert_eq!(segments[0].0, origin.round());
+                let (last_start, last_len) = segments[count - 1];
+                assert_eq!(last_start + last_len, (origin + len).round());
+                for pair in segments.windows(2) {
+                    let (start, len) = pair[0];
+                    assert_eq!(start + len, pair[1].0, "seam in {segments:?}");
+                }
+                for (start, len) in segments {
+                    assert_eq!(start.fract(), 0.0);
+                    assert_eq!(len.fract(), 0.0);
+                }
+            }
+        }
+
+        #[test]
+        fn axis_segments_keep_gaps_between_neighbours() {
+            let segments = axis_segments(0.0, 1000.0, &[1.0, 1.0, 1.0], 10.0);
+            assert_eq!(segments, vec![(0.0, 327.0), (337.0, 326.0), (673.0, 327.0)]);
+        }
+
+        #[test]
+        fn adjacent_windows_share_edges_without_gaps() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            for idx in 1..=3 {
+                system.add_window_after_selection(layout, w(1, idx));
+            }
+            let screen = CGRect::new(CGPoint::new(0.0, 0.0), CGSize::new(1001.0, 700.0));
+
+            let mut result = system.calculate_layout(
+                layout,
+                screen,
+                0.0,
+                10.0,
+                &gaps(),
+                0.0,
+                crate::common::config::HorizontalPlacement::Top,
+                crate::common::config::VerticalPlacement::Left,
+                None,
+            );
+            result.sort_by(|a, b| a.1.origin.x.total_cmp(&b.1.origin.x));
+
+            assert_eq!(result[0].1.origin.x, 0.0);
+            for pair in result.windows(2) {
+                assert_eq!(pair[0].1.max().x, pair[1].1.origin.x);
+            }
+            assert_eq!(result[2].1.max().x, 1001.0);
+        }
+    }
+
+    mod move_selection {
+        use super::*;
+
+        #[test]
+        fn move_selection_all_directions() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            for i in 1..=5 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+
+            let _ = system.move_selection(layout, Direction::Right);
+            let _ = system.move_selection(layout, Direction::Left);
+            let _ = system.move_selection(layout, Direction::Up);
+            let _ = system.move_selection(layout, Direction::Down);
+        }
+
+        #[test]
+        fn move_selection_empty_layout() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            assert!(!system.move_selection(layout, Direction::Right));
+        }
+    }
+
+    mod validate {
+        use super::*;
+
+        fn node(system: &TraditionalLayoutSystem, layout: LayoutId, wid: WindowId) -> NodeId {
+            system.tree.data.window.node_for(layout, wid).unwrap()
+        }
+
+        #[test]
+        fn healthy_layouts_pass() {
+            let (mut system, layout) = build("[1, V[2, 3]]", &[]);
+            assert_eq!(system.validate(layout), Ok(()));
+
+            system.resize_selection_by(layout, 0.1);
+            system.apply_stacking_to_parent_of_selection(
+                layout,
+                crate::common::config::StackDefaultOrientation::Horizontal,
+            );
+            system.remove_window(w(1, 2));
+            assert_eq!(system.validate(layout), Ok(()));
+
+            let empty = system.create_layout();
+            assert_eq!(system.validate(empty), Ok(()));
+        }
+
+        #[test]
+        fn container_holding_a_window() {
+            let (mut system, layout) = build("[1, V[2, 3]]", &[]);
+            let container = node(&system, layout, w(1, 2)).parent(system.map()).unwrap();
+            system.tree.data.window.windows.insert(container, w(9, 9));
+
+            assert_eq!(
+                system.validate(layout),
+                Err(vec![format!(
+                    "container {container:?} holds window {:?}",
+                    w(9, 9)
+                )])
+            );
+        }
+
+        #[test]
+        fn sizes_that_do_not_add_up() {
+            let (mut system, layout) = build("[1, V[2, 3]]", &[]);
+            let root = system.root(layout);
+            system.tree.data.layout.info[root].total = 5.0;
+
+            assert_eq!(
+                system.validate(layout),
+                Err(vec![format!(
+                    "container {root:?} has total 5 but its children sum to 2"
+                )])
+            );
+        }
+
+        #[test]
+        fn windows_missing_from_the_index() {
+            let (mut system, layout) = build("[1, V[2, 3]]", &[]);
+            let leaf = node(&system, layout, w(1, 1));
+            system.tree.data.window.window_nodes.remove(&w(1, 1));
+
+            assert_eq!(
+                system.validate(layout),
+                Err(vec![format!("window {:?} at {leaf:?} is not indexed", w(1, 1))])
+            );
+        }
+
+        #[test]
+        fn leaf_without_a_window() {
+            let (mut system, layout) = build("[1, V[2, 3]]", &[]);
+            let leaf = node(&system, layout, w(1, 1));
+            system.tree.data.window.windows.remove(leaf);
+
+            assert_eq!(
+                system.validate(layout),
+                Err(vec![
+                    format!("leaf {leaf:?} holds no window"),
+                    format!("index binds {:?} to {leaf:?}, which does not hold it", w(1, 1)),
+                ])
+            );
+        }
+
+        #[test]
+        fn selection_left_on_a_detached_node() {
+            let (mut system, layout) = build("[1, V[2, 3]]", &[]);
+            let leaf = node(&system, layout, w(1, 3));
+            let container = leaf.parent(system.map()).unwrap();
+            system.tree.map.unlink(leaf);
+
+            let errors = system.validate(layout).unwrap_err();
+            for expected in [
+                format!("{container:?} selects {leaf:?}, which is not its child"),
+                format!("index binds {:?} to {leaf:?}, which does not hold it", w(1, 3)),
+                format!("selection {leaf:?} is not in the layout"),
+            ] {
+                assert!(errors.contains(&expected), "{expected} not in {errors:?}");
+            }
+        }
+    }
+
+    mod promote_to_main {
+        use super::*;
+
+        #[test]
+        fn previous_main_becomes_second() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            for i in 1..=3 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+
+            assert!(system.promote_selection_to_main(layout));
+            assert_eq!(
+                system.visible_windows_in_layout(layout),
+                vec![w(1, 3), w(1, 1), w(1, 2)]
+            );
+            assert_eq!(system.selected_window(layout), Some(w(1, 3)));
+
+            assert!(!system.promote_selection_to_main(layout));
+            assert!(system.undo(layout));
+            assert_eq!(
+                system.visible_windows_in_layout(layout),
+                vec![w(1, 1), w(1, 2), w(1, 3)]
+            );
+        }
+
+        #[test]
+        fn nested_window_leaves_its_container() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.split_selection(layout, LayoutKind::Vertical);
+            system.add_window_after_selection(layout, w(1, 3));
+
+            assert!(system.promote_selection_to_main(layout));
+            let root = system.root(layout);
+            assert_eq!(root.children(system.map()).count(), 3);
+            assert_eq!(
+                system.visible_windows_in_layout(layout),
+                vec![w(1, 3), w(1, 1), w(1, 2)]
+            );
+            assert_eq!(system.selected_window(layout), Some(w(1, 3)));
+        }
+
+        #[test]
+        fn empty_layout_is_a_noop() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            assert!(!system.promote_selection_to_main(layout));
+        }
+    }
+
+    mod flip_container {
+        use super::*;
+
+        fn frames(system: &TraditionalLayoutSystem, layout: LayoutId) -> Vec<(WindowId, CGRect)> {
+            let mut frames = system.calculate_layout(
+                layout,
+                screen(),
+                0.0,
+                0.0,
+                &gaps(),
+                0.0,
+                crate::common::config::HorizontalPlacement::Top,
+                crate::common::config::VerticalPlacement::Left,
+                None,
+            );
+            frames.sort_by_key(|&(wid, _)| wid);
+            frames
+        }
+
+        #[test]
+        fn two_children_swap_frames() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.resize_selection_by(layout, 0.1);
+            let before = frames(&system, layout);
+
+            assert!(system.flip_container_children(layout));
+            let after = frames(&system, layout);
+            assert_eq!(system.selected_window(layout), Some(w(1, 2)));
+            let (left, right) = (before[0].1, before[1].1);
+            assert_eq!(after[1].1.origin.x, left.origin.x);
+            assert_eq!(after[1].1.size.width, right.size.width);
+            assert_eq!(after[0].1.size.width, left.size.width);
+            assert_eq!(after[0].1.max().x, right.max().x);
+        }
+
+        #[test]
+        fn more_children_are_reversed() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            for i in 1..=3 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+            system.select_window(layout, w(1, 1));
+
+            assert!(system.flip_container_children(layout));
+            assert_eq!(
+                system.visible_windows_in_layout(layout),
+                vec![w(1, 3), w(1, 2), w(1, 1)]
+            );
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+        }
+
+        #[test]
+        fn single_child_is_a_noop() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+
+            assert!(!system.flip_container_children(layout));
+        }
+    }
+
+    mod two_column {
+        use super::*;
+
+        #[test]
+        fn first_window_sits_beside_the_rest() {
+            let (mut system, layout) = build("[1, V[2, H[3, 4]]]", &[3]);
+
+            assert!(system.arrange_two_column(layout, true));
+            let map = system.map();
+            let root = system.root(layout);
+            assert_eq!(system.layout(root), LayoutKind::Horizontal);
+            let columns: Vec<_> = root.children(map).collect();
+            assert_eq!(columns.len(), 2);
+            assert_eq!(system.window_at(columns[0]), Some(w(1, 1)));
+            assert_eq!(system.layout(columns[1]), LayoutKind::Vertical);
+            let stacked: Vec<_> =
+                columns[1].children(map).map(|node| system.window_at(node)).collect();
+            assert_eq!(stacked, vec![Some(w(1, 2)), Some(w(1, 3)), Some(w(1, 4))]);
+            assert_eq!(system.selected_window(layout), Some(w(1, 3)));
+
+            for column in columns {
+                assert_eq!(system.tree.data.layout.proportion(map, column), Some(0.5));
+            }
+        }
+
+        #[test]
+        fn growing_the_master_takes_from_the_column() {
+            let (mut system, layout) = build("[1, V[2, H[3, 4]]]", &[3]);
+            assert!(system.arrange_two_column(layout, true));
+
+            system.select_window(layout, w(1, 1));
+            system.resize_selection_by(layout, 0.1);
+            let map = system.map();
+            let master = system.tree.data.window.node_for(layout, w(1, 1)).unwrap();
+            let column = master.next_sibling(map).unwrap();
+            let share = system.tree.data.layout.proportion(map, master).unwrap();
+            assert!((share - 0.6).abs() < 1e-6, "{share}");
+            let rest = system.tree.data.layout.proportion(map, column).unwrap();
+            assert!((rest - 0.4).abs() < 1e-6, "{rest}");
+        }
+
+        #[test]
+        fn master_can_go_on_the_right() {
+            let (mut system, layout) = build("[1, V[2, H[3, 4]]]", &[3]);
+
+            assert!(system.arrange_two_column(layout, false));
+            let root = system.root(layout);
+            let last = root.last_child(system.map()).unwrap();
+            assert_eq!(system.window_at(last), Some(w(1, 1)));
+            assert_eq!(
+                system.visible_windows_in_layout(layout),
+                vec![w(1, 2), w(1, 3), w(1, 4), w(1, 1)]
+            );
+        }
+
+        #[test]
+        fn undo_restores_the_previous_tree() {
+            let (mut system, layout) = build("[1, V[2, H[3, 4]]]", &[3]);
+            let before = system.draw_tree(layout);
+
+            assert!(system.arrange_two_column(layout, true));
+            assert!(system.undo(layout));
+            assert_eq!(system.draw_tree(layout), before);
+        }
+
+        #[test]
+        fn single_window_is_left_alone() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+
+            assert!(!system.arrange_two_column(layout, true));
+        }
+    }
+
+    mod orientation {
+        use super::*;
+
+        #[test]
+        fn toggle_orientation() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+
+            system.toggle_tile_orientation(layout);
+        }
+
+        fn swap_axes(tree: &str) -> String {
+            tree.replace("Horizontal", "\0")
+                .replace("Vertical", "Horizontal")
+                .replace('\0', "Vertical")
+        }
+
+        #[test]
+        fn rotate_subtree_flips_every_container() {
+            let (mut system, layout) = build("[1, V[2, H[3, 4]]]", &[1]);
+            system.resize_selection_by(layout, 0.1);
+            let before = system.draw_tree(layout);
+
+            system.rotate_subtree(layout);
+            assert_eq!(system.draw_tree(layout), swap_axes(&before));
+
+            system.rotate_subtree(layout);
+            assert_eq!(system.draw_tree(layout), before);
+        }
+
+        #[test]
+        fn rotate_subtree_starts_at_the_selection_parent() {
+            let (mut system, layout) = build("[1, V[2, H[3, 4]]]", &[1]);
+            system.select_window(layout, w(1, 3));
+            let inner = system.tree.data.window.node_for(layout, w(1, 3)).unwrap();
+            let inner = inner.parent(system.map()).unwrap();
+            let middle = inner.parent(system.map()).unwrap();
+
+            system.rotate_subtree(layout);
+
+            assert_eq!(system.layout(system.root(layout)), LayoutKind::Horizontal);
+            assert_eq!(system.layout(middle), LayoutKind::Vertical);
+            assert_eq!(system.layout(inner), LayoutKind::Vertical);
+        }
+
+        #[test]
+        fn rotate_subtree_keeps_stacks_stacked() {
+            let (mut system, layout) = build("[1, V[2, H[3, 4]]]", &[1]);
+            system.split_selection(layout, LayoutKind::HorizontalStack);
+            let stack = system.tree.data.window.node_for(layout, w(1, 1)).unwrap();
+            let stack = stack.parent(system.map()).unwrap();
+
+            system.rotate_subtree(layout);
+
+            assert_eq!(system.layout(stack), LayoutKind::VerticalStack);
+            assert_eq!(system.layout(system.root(layout)), LayoutKind::Horizontal);
+        }
+
+        #[test]
+        fn mirror_layout_reverses_children_and_their_shares() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.select_window(layout, w(1, 1));
+            system.resize_selection_by(layout, 0.2);
+            let shares = |system: &TraditionalLayoutSystem| -> Vec<f64> {
+                let map = system.map();
+                system
+                    .root(layout)
+                    .children(map)
+                    .map(|child| system.tree.data.layout.proportion(map, child).unwrap())
+                    .collect()
+            };
+            let before = shares(&system);
+            assert!(before[0] > before[1]);
+
+            system.mirror_layout(layout, Orientation::Vertical);
+            assert_eq!(system.visible_windows_in_layout(layout), [w(1, 1), w(1, 2)]);
+
+            system.mirror_layout(layout, Orientation::Horizontal);
+            assert_eq!(system.visible_windows_in_layout(layout), [w(1, 2), w(1, 1)]);
+            assert_eq!(shares(&system), [before[1], before[0]]);
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+        }
+
+        #[test]
+        fn rotate_layout_turns_the_whole_tree() {
+            let (mut system, layout) = build("[1, V[2, H[3, 4]]]", &[1]);
+            let before = system.draw_tree(layout);
+
+            system.rotate_layout(layout, true);
+            let root = system.root(layout);
+            let middle = system.tree.data.window.node_for(layout, w(1, 2)).unwrap();
+            let middle = middle.parent(system.map()).unwrap();
+            let inner = system.tree.data.window.node_for(layout, w(1, 3)).unwrap();
+            let inner = inner.parent(system.map()).unwrap();
+            assert_eq!(system.layout(root), LayoutKind::Vertical);
+            assert_eq!(system.layout(middle), LayoutKind::Horizontal);
+            assert_eq!(system.layout(inner), LayoutKind::Vertical);
+            // The vertical container turned into a row running right to left.
+            assert_eq!(
+                system.visible_windows_in_layout(layout),
+                [w(1, 1), w(1, 3), w(1, 4), w(1, 2)]
+            );
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+
+            system.rotate_layout(layout, false);
+            assert_eq!(system.draw_tree(layout), before);
+        }
+    }
+
+    mod golden_ratio {
+        use super::*;
+
+        fn root_shares(system: &TraditionalLayoutSystem, layout: LayoutId) -> Vec<f64> {
+            let map = system.map();
+            system
+                .root(layout)
+                .children(map)
+                .map(|child| system.tree.data.layout.proportion(map, child).unwrap())
+                .collect()
+        }
+
+        fn assert_shares(actual: Vec<f64>, expected: &[f64]) {
+            assert_eq!(actual.len(), expected.len(), "{actual:?} vs {expected:?}");
+            for (a, e) in actual.iter().zip(expected) {
+                assert!((a - e).abs() < 1e-3, "{actual:?} vs {expected:?}");
+            }
+        }
+
+        #[test]
+        fn two_children_split_either_way() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+
+            system.set_golden_ratio_of_selection_parent(layout, true);
+            assert_shares(root_shares(&system, layout), &[0.618, 0.382]);
+
+            system.set_golden_ratio_of_selection_parent(layout, false);
+            assert_shares(root_shares(&system, layout), &[0.382, 0.618]);
+        }
+
+        #[test]
+        fn larger_containers_split_first_child_against_the_rest() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            for i in 1..=3 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+
+            system.set_golden_ratio_of_selection_parent(layout, true);
+            assert_shares(root_shares(&system, layout), &[0.618, 0.191, 0.191]);
+
+            system.set_golden_ratio_of_selection_parent(layout, false);
+            assert_shares(root_shares(&system, layout), &[0.382, 0.309, 0.309]);
+        }
+
+        #[test]
+        fn single_child_is_left_alone() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+
+            system.set_golden_ratio_of_selection_parent(layout, true);
+            assert_shares(root_shares(&system, layout), &[1.0]);
+        }
+    }
+
+    mod split_selection {
+        use super::*;
+
+        #[test]
+        fn split_selection_horizontal() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            system.add_window_after_selection(layout, w(1, 1));
+            system.split_selection(layout, LayoutKind::Horizontal);
+
+            let visible = system.visible_windows_in_layout(layout);
+            assert!(!visible.is_empty());
+        }
+
+        #[test]
+        fn split_selection_vertical() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            system.add_window_after_selection(layout, w(1, 1));
+            system.split_selection(layout, LayoutKind::Vertical);
+
+            let visible = system.visible_windows_in_layout(layout);
+            assert!(!visible.is_empty());
+        }
+
+        fn share(system: &TraditionalLayoutSystem, layout: LayoutId, wid: WindowId) -> f64 {
+            let node = system.tree.data.window.node_for(layout, wid).unwrap();
+            system.tree.data.layout.proportion(&system.tree.map, node).unwrap()
+        }
+
+        /// Doubles `wid`'s size within its parent.
+        fn grow(system: &mut TraditionalLayoutSystem, layout: LayoutId, wid: WindowId) {
+            let node = system.tree.data.window.node_for(layout, wid).unwrap();
+            let parent = node.parent(&system.tree.map).unwrap();
+            let info = &mut system.tree.data.layout.info;
+            info[parent].total += info[node].size;
+            info[node].size *= 2.0;
+        }
+
+        #[test]
+        fn split_into_fills_evenly_until_full() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.select_window(layout, w(1, 1));
+
+            system.split_selection_into(layout, LayoutKind::Vertical, 3);
+            system.add_window_after_selection(layout, w(1, 3));
+            grow(&mut system, layout, w(1, 3));
+            system.add_window_after_selection(layout, w(1, 4));
+            for i in [1, 3, 4] {
+                assert!((share(&system, layout, w(1, i)) - 1.0 / 3.0).abs() < 1e-6);
+            }
+            assert_eq!(share(&system, layout, w(1, 2)), 0.5);
+
+            // Once full, the container sizes new windows as usual.
+            grow(&mut system, layout, w(1, 4));
+            system.add_window_after_selection(layout, w(1, 5));
+            assert!(share(&system, layout, w(1, 4)) > share(&system, layout, w(1, 1)));
+        }
+
+        #[test]
+        fn split_into_equalizes_a_parent_with_enough_children() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            for i in 1..=3 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+            grow(&mut system, layout, w(1, 1));
+
+            system.split_selection_into(layout, LayoutKind::Vertical, 3);
+            assert_eq!(system.root_kind(layout), LayoutKind::Vertical);
+            for i in 1..=3 {
+                assert!((share(&system, layout, w(1, i)) - 1.0 / 3.0).abs() < 1e-6);
+            }
+        }
+    }
+
+    mod max_depth {
+        use super::*;
+
+        #[test]
+        fn flattening_collapses_lone_containers_and_keeps_windows() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            for i in 1..=3 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+            system.select_window(layout, w(1, 2));
+            for _ in 0..4 {
+                system.split_selection(layout, LayoutKind::Vertical);
+            }
+            assert_eq!(system.max_depth(layout), 5);
+
+            assert_eq!(system.flatten_to_depth(layout, 2), 3);
+            assert_eq!(system.max_depth(layout), 2);
+            assert_eq!(
+                system.visible_windows_in_layout(layout),
+                vec![w(1, 1), w(1, 2), w(1, 3)]
+            );
+            assert_eq!(system.selected_window(layout), Some(w(1, 2)));
+        }
+
+        #[test]
+        fn flattening_stops_at_containers_it_cannot_remove() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.split_selection(layout, LayoutKind::Vertical);
+            system.add_window_after_selection(layout, w(1, 3));
+            system.split_selection(layout, LayoutKind::Horizontal);
+            system.add_window_after_selection(layout, w(1, 4));
+
+            assert_eq!(system.max_depth(layout), 3);
+            assert_eq!(system.flatten_to_depth(layout, 1), 0);
+            assert_eq!(system.visible_windows_in_layout(layout).len(), 4);
+        }
+    }
+
+    mod fingerprint {
+        use super::*;
+
+        #[test]
+        fn identical_trees_share_a_fingerprint() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.split_selection(layout, LayoutKind::Vertical);
+            system.add_window_after_selection(layout, w(1, 3));
+
+            let copy = system.clone_layout(layout);
+            assert_eq!(
+                system.layout_fingerprint(copy),
+                system.layout_fingerprint(layout)
+            );
+        }
+
+        #[test]
+        fn structural_changes_alter_the_fingerprint() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            for idx in 1..=3 {
+                system.add_window_after_selection(layout, w(1, idx));
+            }
+            let initial = system.layout_fingerprint(layout);
+
+            system.select_window(layout, w(1, 3));
+            assert_eq!(system.layout_fingerprint(layout), initial);
+
+            system.join_selection_with_direction(layout, Direction::Left);
+            assert_ne!(system.layout_fingerprint(layout), initial);
+            assert!(system.undo(layout));
+            assert_eq!(system.layout_fingerprint(layout), initial);
+
+            system.toggle_fullscreen_of_selection(layout);
+            assert_ne!(system.layout_fingerprint(layout), initial);
+        }
+
+        #[test]
+        fn stack_selection_and_locks_alter_the_fingerprint() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.split_selection(layout, LayoutKind::VerticalStack);
+            system.add_window_after_selection(layout, w(1, 3));
+            let initial = system.layout_fingerprint(layout);
+
+            system.select_window(layout, w(1, 2));
+            let reselected = system.layout_fingerprint(layout);
+            assert_ne!(reselected, initial);
+            system.select_window(layout, w(1, 1));
+            system.select_window(layout, w(1, 2));
+            assert_eq!(system.layout_fingerprint(layout), reselected);
+
+            assert!(system.toggle_lock_of_selection_parent(layout));
+            assert_ne!(system.layout_fingerprint(layout), reselected);
+        }
+    }
+
+    mod undo {
+        use super::*;
+
+        #[test]
+        fn undo_join_restores_tree() {
+            let (mut system, layout) = build("[1, 2, 3]", &[1]);
+            let before = system.snapshot(layout);
+
+            system.join_selection_with_direction(layout, Direction::Right);
+            assert!(!before.same_shape(&system.snapshot(layout)));
+
+            assert!(system.undo(layout));
+            assert!(before.same_shape(&system.snapshot(layout)));
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+            assert!(!system.undo(layout));
+        }
+
+        #[test]
+        fn redo_reapplies_join() {
+            let (mut system, layout) = build("[1, 2, 3]", &[1]);
+            system.join_selection_with_direction(layout, Direction::Right);
+            let joined = system.snapshot(layout);
+
+            assert!(system.undo(layout));
+            assert!(system.redo(layout));
+            assert!(joined.same_shape(&system.snapshot(layout)));
+            assert!(!system.redo(layout));
+        }
+
+        #[test]
+        fn new_change_clears_redo() {
+            let (mut system, layout) = build("[1, 2, 3]", &[1]);
+            system.join_selection_with_direction(layout, Direction::Right);
+            assert!(system.undo(layout));
+
+            system.move_selection(layout, Direction::Right);
+            assert!(!system.redo(layout));
+        }
+
+        #[test]
+        fn changes_that_do_nothing_are_not_recorded() {
+            let (mut system, layout) = build("[1, 2, 3]", &[1]);
+            system.unjoin_selection(layout);
+            assert!(!system.undo(layout));
+        }
+
+        #[test]
+        fn undo_keeps_current_window_membership() {
+            let (mut system, layout) = build("[1, 2, 3]", &[1]);
+            system.join_selection_with_direction(layout, Direction::Right);
+            system.add_window_after_selection(layout, w(1, 4));
+            system.remove_window(w(1, 3));
+
+            assert!(system.undo(layout));
+            let mut windows = system.visible_windows_in_layout(layout);
+            windows.sort();
+            assert_eq!(windows, vec![w(1, 1), w(1, 2), w(1, 4)]);
+            for wid in windows {
+                let node = system.tree.data.window.node_for(layout, wid).unwrap();
+                assert_eq!(system.window_at(node), Some(wid));
+            }
+        }
+
+        #[test]
+        fn history_is_bounded() {
+            let (mut system, layout) = build("[1, 2, 3]", &[1]);
+            for i in 0..UNDO_DEPTH + 4 {
+                let direction = if i % 2 == 0 {
+                    Direction::Right
+                } else {
+                    Direction::Left
+                };
+                system.move_selection(layout, direction);
+            }
+            let mut undone = 0;
+            while system.undo(layout) {
+                undone += 1;
+            }
+            assert_eq!(undone, UNDO_DEPTH);
+        }
+
+        #[test]
+        fn removing_layout_drops_snapshots() {
+            let (mut system, layout) = build("[1, 2, 3]", &[1]);
+            system.join_selection_with_direction(layout, Direction::Right);
+            system.remove_layout(layout);
+            assert!(system.layout_roots.is_empty());
+            assert!(system.history.is_empty());
+        }
+
+        #[test]
+        fn snapshots_stay_out_of_the_tree() {
+            let (mut system, layout) = build("[1, 2, 3]", &[1]);
+            system.join_selection_with_direction(layout, Direction::Right);
+            system.move_selection(layout, Direction::Left);
+            let in_layout = |system: &TraditionalLayoutSystem| {
+                system.root(layout).traverse_preorder(system.map()).count()
+            };
+
+            assert_eq!(system.layout_roots.len(), 1);
+            assert_eq!(system.tree.map.len(), in_layout(&system));
+            assert!(system.undo(layout));
+            assert!(system.undo(layout));
+            assert_eq!(system.tree.map.len(), in_layout(&system));
+            assert_eq!(system.validate(layout), Ok(()));
+        }
+
+        #[test]
+        fn undo_restores_the_selection() {
+            let (mut system, layout) = build("[1, 2, 3]", &[1]);
+            system.select_window(layout, w(1, 2));
+            system.join_selection_with_direction(layout, Direction::Right);
+            system.select_window(layout, w(1, 1));
+
+            assert!(system.undo(layout));
+            assert_eq!(system.selected_window(layout), Some(w(1, 2)));
+            assert!(system.redo(layout));
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+        }
+
+        #[test]
+        fn shutdown_removes_layouts_and_snapshots() {
+            let (mut system, layout) = build("[1, 2, 3]", &[1]);
+            system.join_selection_with_direction(layout, Direction::Right);
+            let _copy = system.clone_layout(layout);
+            system.shutdown();
+            assert!(system.layout_roots.is_empty());
+            assert!(system.tree.map.is_empty());
+        }
+    }
+
+    mod preserve_size {
+        use super::*;
+
+        fn enforce(system: &mut TraditionalLayoutSystem, layout: LayoutId) -> bool {
+            system.enforce_preserved_sizes(
+                layout,
+                screen(),
+                0.0,
+                0.0,
+                &gaps(),
+                0.0,
+                Default::default(),
+                Default::default(),
+            )
+        }
+
+        fn width_of(system: &TraditionalLayoutSystem, layout: LayoutId, wid: WindowId) -> f64 {
+            system
+                .calculate_layout(
+                    layout,
+                    screen(),
+                    0.0,
+                    0.0,
+                    &gaps(),
+                    0.0,
+                    Default::default(),
+                    Default::default(),
+                    None,
+                )
+                .into_iter()
+                .find(|(id, _)| *id == wid)
+                .map(|(_, frame)| frame.size.width)
+                .unwrap()
+        }
+
+        /// Two side-by-side windows with the first one preserved at half width.
+        fn preserved_pair() -> (TraditionalLayoutSystem, LayoutId, f64) {
+            let (mut system, layout) = build("[1, 2]", &[1]);
+            assert!(system.toggle_preserve_size_of_selection(layout));
+            assert!(!enforce(&mut system, layout));
+            let width = width_of(&system, layout, w(1, 1));
+            (system, layout, width)
+        }
+
+        #[test]
+        fn width_survives_siblings_being_added_and_removed() {
+            let (mut system, layout, width) = preserved_pair();
+
+            system.add_window_after_selection(layout, w(1, 3));
+            assert!(width_of(&system, layout, w(1, 1)) < width - PRESERVED_SIZE_TOLERANCE);
+            assert!(enforce(&mut system, layout));
+            assert!((width_of(&system, layout, w(1, 1)) - width).abs() <= PRESERVED_SIZE_TOLERANCE);
+            assert!(!enforce(&mut system, layout));
+
+            system.remove_window(w(1, 2));
+            assert!(enforce(&mut system, layout));
+            assert!((width_of(&system, layout, w(1, 1)) - width).abs() <= PRESERVED_SIZE_TOLERANCE);
+        }
+
+        #[test]
+        fn width_holds_when_nested_siblings_are_unjoined() {
+            let (mut system, layout, width) = preserved_pair();
+
+            assert!(system.select_window(layout, w(1, 2)));
+            system.split_selection(layout, LayoutKind::Vertical);
+            system.add_window_after_selection(layout, w(1, 3));
+            system.add_window_after_selection(layout, w(1, 4));
+            assert!(system.select_window(layout, w(1, 2)));
+            system.unjoin_selection(layout);
+            assert!(enforce(&mut system, layout));
+
+            assert!((width_of(&system, layout, w(1, 1)) - width).abs() <= PRESERVED_SIZE_TOLERANCE);
+            assert!(system.is_size_preserved(layout, w(1, 1)));
+        }
+
+        #[test]
+        fn toggling_again_releases_the_window() {
+            let (mut system, layout, width) = preserved_pair();
+
+            assert!(system.select_window(layout, w(1, 1)));
+            assert!(!system.toggle_preserve_size_of_selection(layout));
+            assert!(!system.is_size_preserved(layout, w(1, 1)));
+
+            system.add_window_after_selection(layout, w(1, 3));
+            assert!(!enforce(&mut system, layout));
+            assert!(width_of(&system, layout, w(1, 1)) < width - PRESERVED_SIZE_TOLERANCE);
+        }
+
+        #[test]
+        fn explicit_resize_records_new_size() {
+            let (mut system, layout, width) = preserved_pair();
+
+            assert!(system.select_window(layout, w(1, 1)));
+            system.resize_selection_by(layout, 0.1);
+            let resized = width_of(&system, layout, w(1, 1));
+            assert!(resized > width + PRESERVED_SIZE_TOLERANCE);
+            assert!(!enforce(&mut system, layout));
+
+            system.add_window_after_selection(layout, w(1, 3));
+            assert!(enforce(&mut system, layout));
+            assert!(
+                (width_of(&system, layout, w(1, 1)) - resized).abs() <= PRESERVED_SIZE_TOLERANCE
+            );
+        }
+    }
+
+    mod container_at_point {
+        use super::*;
+
+        #[test]
+        fn selects_deepest_container_under_point() {
+            let (mut system, layout) = build("[1, V[2, 3]]", &[]);
+            let nested = system.selection(layout).parent(system.map()).unwrap();
+            let point = CGPoint::new(1500.0, 200.0);
+
+            assert!(system.select_container_at_point(layout, point, screen(), &gaps()));
+            assert_eq!(system.selection(layout), nested);
+            assert_eq!(system.layout(nested), LayoutKind::Vertical);
+
+            assert!(!system.select_container_at_point(layout, point, screen(), &gaps()));
+        }
+
+        #[test]
+        fn window_directly_under_root_selects_root() {
+            let (mut system, layout) = build("[1, V[2, 3]]", &[]);
+            let point = CGPoint::new(100.0, 100.0);
+
+            assert!(system.select_container_at_point(layout, point, screen(), &gaps()));
+            assert_eq!(system.selection(layout), system.root(layout));
+            assert_eq!(system.selected_window(layout), None);
+        }
+
+        #[test]
+        fn point_outside_screen_is_ignored() {
+            let (mut system, layout) = build("[1, V[2, 3]]", &[]);
+            let before = system.selection(layout);
+            let point = CGPoint::new(-10.0, 100.0);
+
+            assert!(!system.select_container_at_point(layout, point, screen(), &gaps()));
+            assert_eq!(system.selection(layout), before);
+        }
+    }
+
+    mod tree_rendering {
+        use super::*;
+
+        #[test]
+        fn default_options_match_draw_tree() {
+            let (system, layout) = build("[1, V[2, 3, 4]]", &[]);
+            assert_eq!(
+                system.draw_tree_with_options(layout, &TreeRenderOptions::default()),
+                system.draw_tree(layout)
+            );
+            assert!(!system.draw_tree(layout).contains('…'));
+        }
+
+        #[test]
+        fn max_depth_summarizes_deeper_containers() {
+            let (system, layout) = build("[1, V[2, 3, 4]]", &[]);
+
+            let options = TreeRenderOptions {
+                max_depth: Some(0),
+                ..Default::default()
+            };
+            let out = system.draw_tree_with_options(layout, &options);
+            assert_eq!(out.lines().count(), 2, "{out}");
+            assert!(out.contains("… 4 windows"), "{out}");
+
+            let options = TreeRenderOptions {
+                max_depth: Some(1),
+                ..Default::default()
+            };
+            let out = system.draw_tree_with_options(layout, &options);
+            assert!(out.contains("… 3 windows"), "{out}");
+            assert!(out.contains(&format!("{:?}", w(1, 1))), "{out}");
+        }
+
+        #[test]
+        fn focus_path_only_expands_ancestors_of_selection() {
+            let (mut system, layout) = build("[1, V[2, 3, 4]]", &[]);
+
+            let out = system.draw_tree_with_options(layout, &TreeRenderOptions::focus_path());
+            assert!(!out.contains('…'), "{out}");
+
+            system.select_window(layout, w(1, 1));
+            let out = system.draw_tree_with_options(layout, &TreeRenderOptions::focus_path());
+            assert!(out.contains("… 3 windows"), "{out}");
+            assert!(!out.contains(&format!("{:?}", w(1, 3))), "{out}");
+        }
+
+        #[test]
+        fn max_nodes_truncates_with_notice() {
+            let (system, layout) = build("[1, V[2, 3, 4]]", &[]);
+
+            let options = TreeRenderOptions {
+                max_nodes: Some(2),
+                ..Default::default()
+            };
+            let out = system.draw_tree_with_options(layout, &options);
+            let lines: Vec<_> = out.lines().collect();
+            assert_eq!(lines.len(), 3, "{out}");
+            assert_eq!(lines[2], "… truncated after 2 nodes");
+
+            let options = TreeRenderOptions {
+                max_nodes: Some(100),
+                ..Default::default()
+            };
+            assert!(!system.draw_tree_with_options(layout, &options).contains("truncated"));
+        }
+    }
+
+    mod visible_order {
+        use super::*;
+
+        #[test]
+        fn front_first_then_preorder() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            for i in 1..=3 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+
+            assert_eq!(
+                system.visible_windows_ordered(layout, w(1, 2)),
+                vec![w(1, 2), w(1, 1), w(1, 3)]
+            );
+        }
+
+        #[test]
+        fn hidden_stack_members_are_left_out() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.split_selection(layout, LayoutKind::Vertical);
+            system.add_window_after_selection(layout, w(1, 3));
+            system.apply_stacking_to_parent_of_selection(
+                layout,
+                crate::common::config::StackDefaultOrientation::Horizontal,
+            );
+
+            // Stacking selects the first member, so w(1, 3) is hidden.
+            assert_eq!(
+                system.visible_windows_ordered(layout, w(1, 2)),
+                vec![w(1, 2), w(1, 1)]
+            );
+            assert_eq!(
+                system.visible_windows_ordered(layout, w(1, 1)),
+                vec![w(1, 1), w(1, 2)]
+            );
+            assert_eq!(
+                system.visible_windows_ordered(layout, w(1, 3)),
+                vec![w(1, 1), w(1, 2)]
+            );
+        }
+
+        #[test]
+        fn visible_windows_follow_tree_order() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.split_selection(layout, LayoutKind::Vertical);
+            system.add_window_after_selection(layout, w(1, 3));
+            system.select_window(layout, w(1, 1));
+
+            assert_eq!(
+                system.visible_windows_in_layout(layout),
+                vec![w(1, 1), w(1, 2), w(1, 3)]
+            );
+            assert_eq!(system.nth_visible_window(layout, 2), Some(w(1, 3)));
+            assert_eq!(system.nth_visible_window(layout, 3), None);
+        }
+    }
+
+    mod stacking {
+        use super::*;
+
+        #[test]
+        fn apply_stacking() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.add_window_after_selection(layout, w(1, 3));
+
+            let affected = system.apply_stacking_to_parent_of_selection(
+                layout,
+                crate::common::config::StackDefaultOrientation::Horizontal,
+            );
+
+            assert!(!affected.is_empty());
+        }
+
+        #[test]
+        fn unstack_selection() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.add_window_after_selection(layout, w(1, 3));
+
+            system.apply_stacking_to_parent_of_selection(
+                layout,
+                crate::common::config::StackDefaultOrientation::Horizontal,
+            );
+            let affected = system.unstack_parent_of_selection(
+                layout,
+                crate::common::config::StackDefaultOrientation::Horizontal,
+            );
+
+            assert!(!affected.is_empty());
         }
-    }
 
-    fn kind(&self, node: NodeId) -> LayoutKind {
-        self.info[node].kind
-    }
-
-    fn proportion(&self, map: &NodeMap, node: NodeId) -> Option<f64> {
-        let parent = node.parent(map)?;
-        Some(f64::from(self.info[node].size) / f64::from(self.info[parent].total))
-    }
+        #[test]
+        fn parent_of_selection_is_stacked() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
 
-    fn take_share(&mut self, map: &NodeMap, node: NodeId, from: NodeId, share: f32) {
-        assert_eq!(node.parent(map), from.parent(map));
-        let share = share.min(self.info[from].size);
-        let share = share.max(-self.info[node].size);
-        self.info[from].size -= share;
-        self.info[node].size += share;
-    }
+            system.add_window_after_selection(layout, w(1, 1));
+            assert!(!system.parent_of_selection_is_stacked(layout));
 
-    fn set_fullscreen(&mut self, node: NodeId, is_fullscreen: bool) {
-        self.info[node].is_fullscreen = is_fullscreen;
-        if is_fullscreen {
-            self.info[node].is_fullscreen_within_gaps = false;
+            system.add_window_after_selection(layout, w(1, 2));
+            system.apply_stacking_to_parent_of_selection(
+                layout,
+                crate::common::config::StackDefaultOrientation::Horizontal,
+            );
+            assert!(system.parent_of_selection_is_stacked(layout));
         }
-    }
 
-    fn set_fullscreen_within_gaps(&mut self, node: NodeId, within: bool) {
-        self.info[node].is_fullscreen_within_gaps = within;
-        if within {
-            self.info[node].is_fullscreen = false;
-        }
-    }
+        #[test]
+        fn cycle_walks_horizontal_then_stacks_then_back() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            let root = system.root(layout);
 
-    fn toggle_fullscreen(&mut self, node: NodeId) -> bool {
-        self.info[node].is_fullscreen = !self.info[node].is_fullscreen;
-        if self.info[node].is_fullscreen {
-            self.info[node].is_fullscreen_within_gaps = false;
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            assert_eq!(system.layout(root), LayoutKind::Horizontal);
+
+            for expected in [
+                LayoutKind::HorizontalStack,
+                LayoutKind::VerticalStack,
+                LayoutKind::Horizontal,
+                LayoutKind::HorizontalStack,
+            ] {
+                let affected = system.cycle_stack_of_parent_of_selection(layout);
+                assert_eq!(affected.len(), 2);
+                assert_eq!(system.layout(root), expected);
+            }
         }
-        self.info[node].is_fullscreen
-    }
 
-    fn toggle_fullscreen_within_gaps(&mut self, node: NodeId) -> bool {
-        self.info[node].is_fullscreen_within_gaps = !self.info[node].is_fullscreen_within_gaps;
-        if self.info[node].is_fullscreen_within_gaps {
-            self.info[node].is_fullscreen = false;
-        }
-        self.info[node].is_fullscreen_within_gaps
-    }
+        #[test]
+        fn stack_if_unstacked_leaves_existing_stack_alone() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            let root = system.root(layout);
 
-    fn is_effectively_fullscreen(&self, node: NodeId) -> bool {
-        let info = &self.info[node];
-        info.is_fullscreen || info.is_fullscreen_within_gaps
-    }
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
 
-    fn debug(&self, node: NodeId, is_container: bool) -> String {
-        let info = &self.info[node];
-        if is_container {
-            format!("{:?} [size {} total={}]", info.kind, info.size, info.total)
-        } else {
-            format!("[size {}]", info.size)
-        }
-    }
+            let orientation = crate::common::config::StackDefaultOrientation::Horizontal;
+            let affected = system.stack_parent_of_selection_if_unstacked(layout, orientation);
+            assert_eq!(affected.len(), 2);
+            assert_eq!(system.layout(root), LayoutKind::HorizontalStack);
 
-    fn is_focused_in_subtree(&self, map: &NodeMap, window: &Window, node: NodeId) -> bool {
-        if window.at(node).is_some()
-            && let Some(parent) = node.parent(map)
-        {
-            return parent.first_child(map) == Some(node);
+            let affected = system.stack_parent_of_selection_if_unstacked(layout, orientation);
+            assert!(affected.is_empty());
+            assert_eq!(system.layout(root), LayoutKind::HorizontalStack);
         }
-        for child in node.children(map) {
-            if self.is_focused_in_subtree(map, window, child) {
-                return true;
-            }
-        }
-        false
-    }
 
-    fn apply_with_gaps(
-        &self,
-        map: &NodeMap,
-        window: &Window,
-        node: NodeId,
-        rect: CGRect,
-        screen: CGRect,
-        sizes: &mut Vec<(WindowId, CGRect)>,
-        stack_offset: f64,
-        gaps: &crate::common::config::GapSettings,
-        stack_line_thickness: f64,
-        stack_line_horiz: crate::common::config::HorizontalPlacement,
-        stack_line_vert: crate::common::config::VerticalPlacement,
-    ) {
-        let info = &self.info[node];
-        let rect = if info.is_fullscreen {
-            screen
-        } else if info.is_fullscreen_within_gaps {
-            compute_tiling_area(screen, gaps)
-        } else {
-            rect
-        };
-        let children: Vec<_> = node.children(map).collect();
-        let child_count = children.len();
-        match info.kind {
-            crate::layout_engine::LayoutKind::Horizontal
-            | crate::layout_engine::LayoutKind::Vertical => {
-                if child_count == 0 {
-                    if let Some(wid) = window.at(node) {
-                        sizes.push((wid, rect));
-                    }
-                    return;
-                }
-                let total: f32 = children.iter().map(|&child| self.info[child].size).sum();
-                let is_horizontal =
-                    matches!(info.kind, crate::layout_engine::LayoutKind::Horizontal);
-                let gap = if is_horizontal {
-                    gaps.inner.horizontal
-                } else {
-                    gaps.inner.vertical
-                };
-                let total_gap = ((child_count as i32) - 1).max(0) as f64 * gap;
-                let total_size = if is_horizontal {
-                    rect.size.width
-                } else {
-                    rect.size.height
-                };
-                let available_size = (total_size - total_gap).max(0.0);
-                for (i, &child) in children.iter().enumerate() {
-                    let ratio = f64::from(self.info[child].size) / f64::from(total);
-                    let seg_size = available_size * ratio;
-                    let offset = if is_horizontal {
-                        rect.origin.x
-                    } else {
-                        rect.origin.y
-                    };
-                    let mut child_offset = offset;
-                    for (_, sibling) in children.iter().enumerate().take(i) {
-                        let sibling_ratio = f64::from(self.info[*sibling].size) / f64::from(total);
-                        child_offset += available_size * sibling_ratio;
-                        child_offset += gap;
-                    }
-                    let child_rect = if is_horizontal {
-                        objc2_core_foundation::CGRect::new(
-                            objc2_core_foundation::CGPoint::new(child_offset, rect.origin.y),
-                            objc2_core_foundation::CGSize::new(seg_size, rect.size.height),
-                        )
-                    } else {
-                        objc2_core_foundation::CGRect::new(
-                            objc2_core_foundation::CGPoint::new(rect.origin.x, child_offset),
-                            objc2_core_foundation::CGSize::new(rect.size.width, seg_size),
-                        )
-                    };
-                    self.apply_with_gaps(
-                        map,
-                        window,
-                        child,
-                        child_rect,
-                        screen,
-                        sizes,
-                        stack_offset,
-                        gaps,
-                        stack_line_thickness,
-                        stack_line_horiz,
-                        stack_line_vert,
-                    );
-                }
-            }
-            crate::layout_engine::LayoutKind::HorizontalStack
-            | crate::layout_engine::LayoutKind::VerticalStack => {
-                if child_count == 0 {
-                    return;
-                }
-                let is_horizontal =
-                    matches!(info.kind, crate::layout_engine::LayoutKind::HorizontalStack);
-                let focused_idx = children
-                    .iter()
-                    .position(|&c| self.is_focused_in_subtree(map, window, c))
-                    .unwrap_or(0);
-                let ui_selected_index =
-                    if matches!(info.kind, crate::layout_engine::LayoutKind::VerticalStack) {
-                        child_count.saturating_sub(1).saturating_sub(focused_idx)
-                    } else {
-                        focused_idx
-                    };
-                let mut container_rect = rect;
-                let reserve = stack_line_thickness.max(0.0);
-                container_rect = adjust_stack_container_rect(
-                    container_rect,
-                    is_horizontal,
-                    reserve,
-                    stack_line_horiz,
-                    stack_line_vert,
-                );
-                let layout_res = StackLayoutResult::new(
-                    container_rect,
-                    child_count,
-                    stack_offset,
-                    is_horizontal,
-                );
-                for (i, &child) in children.iter().enumerate() {
-                    let child_rect = if i == ui_selected_index {
-                        layout_res.get_focused_frame_for_index(focused_idx, focused_idx)
-                    } else {
-                        layout_res.get_frame_for_index(i)
-                    };
-                    self.apply_with_gaps(
-                        map,
-                        window,
-                        child,
-                        child_rect,
-                        screen,
-                        sizes,
-                        stack_offset,
-                        gaps,
-                        stack_line_thickness,
-                        stack_line_horiz,
-                        stack_line_vert,
-                    );
-                }
-            }
-        }
-        if let Some(wid) = window.at(node) {
-            sizes.push((wid, rect));
-        }
-    }
-}
+        #[test]
+        fn occluded_windows_cover_nested_stacks() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            let orientation = crate::common::config::StackDefaultOrientation::Horizontal;
 
-fn adjust_stack_container_rect(
-    mut rect: CGRect,
-    is_horizontal: bool,
-    reserve: f64,
-    stack_line_horiz: crate::common::config::HorizontalPlacement,
-    stack_line_vert: crate::common::config::VerticalPlacement,
-) -> CGRect {
-    if reserve == 0.0 {
-        return rect;
-    }
-    if is_horizontal {
-        let new_h = (rect.size.height - reserve).max(0.0);
-        if matches!(stack_line_horiz, crate::common::config::HorizontalPlacement::Top) {
-            rect.origin.y += reserve;
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.add_window_after_selection(layout, w(1, 3));
+            assert!(system.occluded_windows(layout).is_empty());
+
+            system.split_selection(layout, LayoutKind::Vertical);
+            system.add_window_after_selection(layout, w(1, 4));
+            system.apply_stacking_to_parent_of_selection(layout, orientation);
+            assert_eq!(system.occluded_windows(layout), vec![w(1, 3)]);
+
+            system.select_window(layout, w(1, 1));
+            system.apply_stacking_to_parent_of_selection(layout, orientation);
+            assert_eq!(system.occluded_windows(layout), vec![w(1, 2), w(1, 3), w(1, 4)]);
+
+            system.select_window(layout, w(1, 3));
+            assert_eq!(system.occluded_windows(layout), vec![w(1, 1), w(1, 2), w(1, 4)]);
         }
-        rect.size.height = new_h;
-    } else {
-        let new_w = (rect.size.width - reserve).max(0.0);
-        if matches!(stack_line_vert, crate::common::config::VerticalPlacement::Left) {
-            rect.origin.x += reserve;
+
+        fn stacks(
+            system: &TraditionalLayoutSystem,
+            layout: LayoutId,
+        ) -> Vec<crate::layout_engine::engine::GroupContainerInfo> {
+            system.stacked_containers(
+                layout,
+                screen(),
+                0.0,
+                0.0,
+                &gaps(),
+                0.0,
+                Default::default(),
+                Default::default(),
+            )
         }
-        rect.size.width = new_w;
-    }
-    rect
-}
 
-#[cfg(test)]
-mod tests {
-    use objc2_core_foundation::{CGPoint, CGRect, CGSize};
+        #[test]
+        fn stacked_containers_reports_stacks_off_the_selection_path() {
+            let (system, layout) = build("[1, HS[2, 3], VS[4, 5, 6]]", &[4, 1]);
+            let on_path = system.collect_group_containers_in_selection_path(
+                layout,
+                screen(),
+                0.0,
+                0.0,
+                &gaps(),
+                0.0,
+                Default::default(),
+                Default::default(),
+            );
+            assert!(on_path.is_empty());
 
-    use super::*;
-    use crate::actor::app::WindowId;
-    use crate::layout_engine::{Direction, LayoutKind};
+            let groups = stacks(&system, layout);
+            let kinds: Vec<_> = groups.iter().map(|g| g.container_kind).collect();
+            assert_eq!(
+                kinds,
+                vec![LayoutKind::HorizontalStack, LayoutKind::VerticalStack]
+            );
+            assert_eq!(groups[0].window_ids, vec![w(1, 2), w(1, 3)]);
+            assert_eq!(groups[1].window_ids, vec![w(1, 4), w(1, 5), w(1, 6)]);
 
-    fn w(pid: i32, idx: u32) -> WindowId {
-        WindowId::new(pid, idx)
-    }
+            let frames = system.calculate_layout(
+                layout,
+                screen(),
+                0.0,
+                0.0,
+                &gaps(),
+                0.0,
+                Default::default(),
+                Default::default(),
+                None,
+            );
+            let frame_of = |wid| frames.iter().find(|(id, _)| *id == wid).unwrap().1;
+            assert_eq!(groups[0].frame, frame_of(w(1, 3)));
+            assert_eq!(groups[1].frame, frame_of(w(1, 4)));
+        }
 
-    fn screen() -> CGRect {
-        CGRect::new(CGPoint::new(0.0, 0.0), CGSize::new(1920.0, 1080.0))
-    }
+        #[test]
+        fn selected_index_follows_tree_order_in_both_orientations() {
+            let (mut system, layout) = build("[1, HS[2, 3], VS[4, 5, 6]]", &[4, 1]);
+            let selected = |system: &TraditionalLayoutSystem| {
+                stacks(system, layout).iter().map(|g| g.selected_index).collect::<Vec<_>>()
+            };
+            assert_eq!(selected(&system), vec![1, 0]);
+
+            system.select_window(layout, w(1, 6));
+            assert_eq!(selected(&system), vec![1, 2]);
+            let groups = stacks(&system, layout);
+            assert_eq!(groups[1].window_ids[groups[1].selected_index], w(1, 6));
 
-    fn gaps() -> crate::common::config::GapSettings {
-        crate::common::config::GapSettings::default()
+            let on_path = system.collect_group_containers_in_selection_path(
+                layout,
+                screen(),
+                0.0,
+                0.0,
+                &gaps(),
+                0.0,
+                Default::default(),
+                Default::default(),
+            );
+            assert_eq!(on_path.len(), 1);
+            assert_eq!(on_path[0].selected_index, 2);
+        }
     }
 
-    mod window_addition {
+    mod swap {
         use super::*;
 
         #[test]
-        fn add_single_window() {
+        fn swap_windows() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
+
             system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
 
-            let visible = system.visible_windows_in_layout(layout);
-            assert_eq!(visible.len(), 1);
+            assert!(system.swap_windows(layout, w(1, 1), w(1, 2)));
         }
 
         #[test]
-        fn add_multiple_windows() {
+        fn swap_same_window_fails() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
 
-            for i in 1..=10 {
-                system.add_window_after_selection(layout, w(1, i));
+            system.add_window_after_selection(layout, w(1, 1));
+
+            assert!(!system.swap_windows(layout, w(1, 1), w(1, 1)));
+        }
+    }
+
+    mod window_bindings {
+        use super::*;
+        use crate::common::collections::HashSet;
+
+        fn assert_bindings_consistent(system: &TraditionalLayoutSystem) {
+            for (layout, _) in system.layout_roots.iter() {
+                let mut seen = HashSet::default();
+                for node in system.root(layout).traverse_preorder(system.map()) {
+                    if let Some(wid) = system.window_at(node) {
+                        assert!(seen.insert(wid), "{wid:?} bound twice in {layout:?}");
+                        assert_eq!(system.tree.data.window.node_for(layout, wid), Some(node));
+                    }
+                }
+                let indexed = system
+                    .tree
+                    .data
+                    .window
+                    .window_nodes
+                    .values()
+                    .flat_map(|infos| infos.0.iter())
+                    .filter(|info| info.layout == layout)
+                    .count();
+                assert_eq!(indexed, seen.len());
             }
+        }
 
-            let visible = system.visible_windows_in_layout(layout);
-            assert_eq!(visible.len(), 10);
+        /// Simulates a corrupted tree by binding `wid` to a second node behind
+        /// `set_window`'s back.
+        fn bind_duplicate(
+            system: &mut TraditionalLayoutSystem,
+            layout: LayoutId,
+            wid: WindowId,
+        ) -> NodeId {
+            let root = system.root(layout);
+            let node = system.tree.mk_node().push_back(root);
+            system.tree.data.window.windows.insert(node, wid);
+            system
+                .tree
+                .data
+                .window
+                .window_nodes
+                .entry(wid)
+                .or_default()
+                .0
+                .push(WindowNodeInfo { layout, node });
+            node
         }
 
         #[test]
-        fn add_window_to_empty_root() {
+        fn set_window_rejects_second_binding_in_same_layout() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
             system.add_window_after_selection(layout, w(1, 1));
 
-            let visible = system.visible_windows_in_layout(layout);
-            assert_eq!(visible.len(), 1);
+            let root = system.root(layout);
+            let node = system.tree.mk_node().push_back(root);
+            assert!(!system.tree.data.window.set_window(layout, node, w(1, 1)));
+            assert_eq!(system.window_at(node), None);
         }
-    }
-
-    mod focus_movement {
-        use super::*;
 
         #[test]
-        fn move_focus_with_windows() {
+        fn adding_existing_window_reuses_its_node() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
 
             system.add_window_after_selection(layout, w(1, 1));
             system.add_window_after_selection(layout, w(1, 2));
+            system.add_window_after_selection(layout, w(1, 1));
 
-            let (focus, _): (Option<WindowId>, Vec<WindowId>) =
-                system.move_focus(layout, Direction::Right);
-            assert!(focus.is_some() || focus.is_none());
+            assert_eq!(system.visible_windows_in_layout(layout).len(), 2);
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+            assert_bindings_consistent(&system);
         }
 
         #[test]
-        fn move_focus_empty_layout() {
-            let mut system = TraditionalLayoutSystem::default();
-            let layout = system.create_layout();
+        fn syncing_a_window_list_with_repeats_adds_each_once() {
+            for near_app in [false, true] {
+                let mut system = TraditionalLayoutSystem::default();
+                let layout = system.create_layout();
+                system.add_window_after_selection(layout, w(1, 1));
+                system.add_window_after_selection(layout, w(1, 2));
+
+                let desired = vec![w(1, 3), w(1, 2), w(1, 3), w(1, 1)];
+                if near_app {
+                    system.set_windows_for_app_near_app(layout, 1, desired);
+                } else {
+                    system.set_windows_for_app(layout, 1, desired);
+                }
 
-            let (focus, raise): (Option<WindowId>, Vec<WindowId>) =
-                system.move_focus(layout, Direction::Right);
-            assert_eq!(focus, None);
-            assert!(raise.is_empty());
+                assert_eq!(system.visible_windows_in_layout(layout).len(), 3);
+                assert_eq!(system.validate_window_bindings(), 0);
+                assert_bindings_consistent(&system);
+            }
         }
 
         #[test]
-        fn move_focus_all_directions() {
+        fn validate_drops_later_duplicate_nodes() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            let duplicate = bind_duplicate(&mut system, layout, w(1, 1));
 
-            for i in 1..=4 {
-                system.add_window_after_selection(layout, w(1, i));
-            }
+            assert_eq!(system.validate_window_bindings(), 1);
 
-            let _ = system.move_focus(layout, Direction::Left);
-            let _ = system.move_focus(layout, Direction::Right);
-            let _ = system.move_focus(layout, Direction::Up);
-            let _ = system.move_focus(layout, Direction::Down);
+            assert_eq!(system.window_at(duplicate), None);
+            let mut visible = system.visible_windows_in_layout(layout);
+            visible.sort();
+            assert_eq!(visible, vec![w(1, 1), w(1, 2)]);
+            assert_bindings_consistent(&system);
         }
 
         #[test]
-        fn select_window() {
+        fn validate_keeps_bindings_in_cloned_layouts() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
-
             system.add_window_after_selection(layout, w(1, 1));
             system.add_window_after_selection(layout, w(1, 2));
+            let clone = system.clone_layout(layout);
 
-            assert!(system.select_window(layout, w(1, 2)));
-        }
-    }
+            assert_eq!(system.validate_window_bindings(), 0);
 
-    mod layout_calculation {
-        use super::*;
+            assert!(system.contains_window(layout, w(1, 1)));
+            assert!(system.contains_window(clone, w(1, 1)));
+            assert_bindings_consistent(&system);
+        }
 
         #[test]
-        fn calculate_layout_empty() {
+        fn swap_with_duplicate_binding_keeps_index_consistent() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            let node_a = system.tree.data.window.node_for(layout, w(1, 1)).unwrap();
+            let node_b = system.tree.data.window.node_for(layout, w(1, 2)).unwrap();
+            bind_duplicate(&mut system, layout, w(1, 1));
 
-            let result = system.calculate_layout(
-                layout,
-                screen(),
-                0.0,
-                &gaps(),
-                0.0,
-                crate::common::config::HorizontalPlacement::Top,
-                crate::common::config::VerticalPlacement::Left,
-            );
+            assert!(system.swap_windows(layout, w(1, 1), w(1, 2)));
 
-            assert!(result.is_empty());
+            assert_eq!(system.tree.data.window.node_for(layout, w(1, 1)), Some(node_b));
+            assert_eq!(system.tree.data.window.node_for(layout, w(1, 2)), Some(node_a));
+            assert_bindings_consistent(&system);
         }
 
         #[test]
-        fn calculate_layout_with_windows() {
+        fn swap_leaves_bindings_in_other_layouts_alone() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
-
             system.add_window_after_selection(layout, w(1, 1));
             system.add_window_after_selection(layout, w(1, 2));
+            let clone = system.clone_layout(layout);
+            let node_for = |system: &TraditionalLayoutSystem, layout, wid| {
+                system.tree.data.window.node_for(layout, wid).unwrap()
+            };
+            let (node_a, node_b) = (
+                node_for(&system, layout, w(1, 1)),
+                node_for(&system, layout, w(1, 2)),
+            );
+            let cloned = (
+                node_for(&system, clone, w(1, 1)),
+                node_for(&system, clone, w(1, 2)),
+            );
+            bind_duplicate(&mut system, layout, w(1, 1));
+            assert_eq!(system.tree.data.window.window_nodes[&w(1, 1)].0.len(), 3);
 
-            let result = system.calculate_layout(
-                layout,
-                screen(),
-                0.0,
-                &gaps(),
-                0.0,
-                crate::common::config::HorizontalPlacement::Top,
-                crate::common::config::VerticalPlacement::Left,
+            assert!(system.swap_windows(layout, w(1, 1), w(1, 2)));
+
+            assert_eq!(node_for(&system, layout, w(1, 1)), node_b);
+            assert_eq!(node_for(&system, layout, w(1, 2)), node_a);
+            assert_eq!(
+                (
+                    node_for(&system, clone, w(1, 1)),
+                    node_for(&system, clone, w(1, 2))
+                ),
+                cloned
             );
+            for wid in [w(1, 1), w(1, 2)] {
+                assert_eq!(system.tree.data.window.window_nodes[&wid].0.len(), 2);
+            }
+            assert_bindings_consistent(&system);
+        }
 
-            assert_eq!(result.len(), 2);
-            let total_width: f64 = result.iter().map(|(_, r)| r.size.width).sum();
-            let screen_width = screen().size.width;
-            assert!((total_width - screen_width).abs() < 1.0);
+        #[test]
+        fn random_operations_preserve_invariant() {
+            let mut system = TraditionalLayoutSystem::default();
+            let mut layouts = vec![system.create_layout()];
+            let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+            let mut next = |n: u64| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state % n
+            };
+
+            for step in 0..1000 {
+                let layout = layouts[next(layouts.len() as u64) as usize];
+                let wid = w(1, next(12) as u32);
+                match next(5) {
+                    0 | 1 => system.add_window_after_selection(layout, wid),
+                    2 => system.remove_window(wid),
+                    3 => {
+                        let other = w(1, next(12) as u32);
+                        let _ = system.swap_windows(layout, wid, other);
+                    }
+                    _ => {
+                        if system.select_window(layout, wid) {
+                            let direction = [
+                                Direction::Left,
+                                Direction::Right,
+                                Direction::Up,
+                                Direction::Down,
+                            ][next(4) as usize];
+                            let _ = system.move_selection(layout, direction);
+                        }
+                    }
+                }
+                if step % 250 == 0 {
+                    layouts.push(system.clone_layout(layout));
+                }
+                assert_bindings_consistent(&system);
+            }
         }
     }
 
-    mod move_selection {
+    mod unjoin {
         use super::*;
 
         #[test]
-        fn move_selection_all_directions() {
+        fn unjoin_selection() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
 
-            for i in 1..=5 {
-                system.add_window_after_selection(layout, w(1, i));
-            }
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.split_selection(layout, LayoutKind::Horizontal);
+            system.unjoin_selection(layout);
+        }
 
-            let _ = system.move_selection(layout, Direction::Right);
-            let _ = system.move_selection(layout, Direction::Left);
-            let _ = system.move_selection(layout, Direction::Up);
-            let _ = system.move_selection(layout, Direction::Down);
+        #[test]
+        fn unjoin_wrapped_pair_keeps_first_window_selected() {
+            let (mut system, layout) = build("[H[1, 2]]", &[1]);
+
+            system.unjoin_selection(layout);
+
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+            let (focus, _) = system.move_focus(layout, Direction::Right);
+            assert_eq!(focus, Some(w(1, 2)));
+            assert_eq!(system.selected_window(layout), Some(w(1, 2)));
         }
 
         #[test]
-        fn move_selection_empty_layout() {
+        fn unjoin_wrapped_pair_keeps_second_window_selected() {
+            let (mut system, layout) = build("[H[1, 2]]", &[2]);
+
+            system.unjoin_selection(layout);
+
+            assert_eq!(system.selected_window(layout), Some(w(1, 2)));
+            let (focus, _) = system.move_focus(layout, Direction::Left);
+            assert_eq!(focus, Some(w(1, 1)));
+        }
+
+        #[test]
+        fn unjoin_at_root_leaves_selection_usable() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
 
-            assert!(!system.move_selection(layout, Direction::Right));
+            system.unjoin_selection(layout);
+
+            assert_eq!(system.selected_window(layout), Some(w(1, 2)));
+            let (focus, _) = system.move_focus(layout, Direction::Left);
+            assert_eq!(focus, Some(w(1, 1)));
+        }
+
+        #[test]
+        fn removing_last_child_of_selected_path_falls_back_to_window() {
+            let (mut system, layout) = build("[H[1, 2]]", &[2]);
+
+            system.remove_window(w(1, 2));
+
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+            let (focus, _) = system.move_focus(layout, Direction::Right);
+            assert_eq!(focus, None);
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
         }
     }
 
-    mod orientation {
+    mod join {
         use super::*;
 
         #[test]
-        fn toggle_orientation() {
+        fn join_selection_with_direction() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
 
             system.add_window_after_selection(layout, w(1, 1));
             system.add_window_after_selection(layout, w(1, 2));
 
-            system.toggle_tile_orientation(layout);
+            system.join_selection_with_direction(layout, Direction::Right);
         }
-    }
 
-    mod split_selection {
-        use super::*;
+        fn parent_orientation(
+            system: &TraditionalLayoutSystem,
+            layout: LayoutId,
+            wid: WindowId,
+        ) -> Orientation {
+            let node = system.tree.data.window.node_for(layout, wid).unwrap();
+            let parent = node.parent(system.map()).unwrap();
+            system.layout(parent).orientation()
+        }
 
         #[test]
-        fn split_selection_horizontal() {
+        fn predict_join_orientation_without_target_is_none() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
-
             system.add_window_after_selection(layout, w(1, 1));
-            system.split_selection(layout, LayoutKind::Horizontal);
 
-            let visible = system.visible_windows_in_layout(layout);
-            assert!(!visible.is_empty());
+            assert_eq!(system.predict_join_orientation(layout, Direction::Right), None);
         }
 
         #[test]
-        fn split_selection_vertical() {
+        fn predict_join_orientation_does_not_mutate_tree() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
-
             system.add_window_after_selection(layout, w(1, 1));
-            system.split_selection(layout, LayoutKind::Vertical);
+            system.add_window_after_selection(layout, w(1, 2));
+            system.add_window_after_selection(layout, w(1, 3));
+            let before = system.draw_tree(layout);
+            let selected = system.selected_window(layout);
+
+            for direction in [
+                Direction::Left,
+                Direction::Right,
+                Direction::Up,
+                Direction::Down,
+            ] {
+                let _ = system.predict_join_orientation(layout, direction);
+            }
 
-            let visible = system.visible_windows_in_layout(layout);
-            assert!(!visible.is_empty());
+            assert_eq!(system.draw_tree(layout), before);
+            assert_eq!(system.selected_window(layout), selected);
         }
-    }
-
-    mod stacking {
-        use super::*;
 
         #[test]
-        fn apply_stacking() {
+        fn predict_join_orientation_matches_join_across_axis() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
-
             system.add_window_after_selection(layout, w(1, 1));
             system.add_window_after_selection(layout, w(1, 2));
             system.add_window_after_selection(layout, w(1, 3));
+            system.select_window(layout, w(1, 2));
 
-            let affected = system.apply_stacking_to_parent_of_selection(
-                layout,
-                crate::common::config::StackDefaultOrientation::Horizontal,
-            );
+            let predicted = system.predict_join_orientation(layout, Direction::Down);
+            system.join_selection_with_direction(layout, Direction::Down);
 
-            assert!(!affected.is_empty());
+            assert_eq!(predicted, Some(Orientation::Vertical));
+            assert_eq!(
+                parent_orientation(&system, layout, w(1, 2)),
+                Orientation::Vertical
+            );
         }
 
         #[test]
-        fn unstack_selection() {
+        fn predict_join_orientation_matches_join_along_axis() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
-
             system.add_window_after_selection(layout, w(1, 1));
             system.add_window_after_selection(layout, w(1, 2));
             system.add_window_after_selection(layout, w(1, 3));
+            system.select_window(layout, w(1, 1));
 
-            system.apply_stacking_to_parent_of_selection(
-                layout,
-                crate::common::config::StackDefaultOrientation::Horizontal,
-            );
-            let affected = system.unstack_parent_of_selection(
-                layout,
-                crate::common::config::StackDefaultOrientation::Horizontal,
-            );
+            let predicted = system.predict_join_orientation(layout, Direction::Right);
+            system.join_selection_with_direction(layout, Direction::Right);
 
-            assert!(!affected.is_empty());
+            assert_eq!(predicted, Some(Orientation::Horizontal));
+            assert_eq!(
+                parent_orientation(&system, layout, w(1, 1)),
+                Orientation::Horizontal
+            );
         }
 
         #[test]
-        fn parent_of_selection_is_stacked() {
+        fn predict_join_orientation_next_to_stack_matches_join() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
-
             system.add_window_after_selection(layout, w(1, 1));
-            assert!(!system.parent_of_selection_is_stacked(layout));
-
             system.add_window_after_selection(layout, w(1, 2));
-            system.apply_stacking_to_parent_of_selection(
-                layout,
-                crate::common::config::StackDefaultOrientation::Horizontal,
-            );
-            assert!(system.parent_of_selection_is_stacked(layout));
+            system.split_selection(layout, LayoutKind::VerticalStack);
+            system.add_window_after_selection(layout, w(1, 3));
+            system.select_window(layout, w(1, 1));
+
+            let predicted = system.predict_join_orientation(layout, Direction::Right);
+            system.join_selection_with_direction(layout, Direction::Right);
+
+            assert_eq!(predicted, Some(parent_orientation(&system, layout, w(1, 1))));
         }
-    }
 
-    mod swap {
-        use super::*;
+        fn join_target(
+            system: &TraditionalLayoutSystem,
+            layout: LayoutId,
+            wid: WindowId,
+            direction: Direction,
+        ) -> Option<JoinTarget> {
+            let node = system.tree.data.window.node_for(layout, wid).unwrap();
+            system.find_natural_join_target(node, direction)
+        }
+
+        fn node(system: &TraditionalLayoutSystem, layout: LayoutId, wid: WindowId) -> NodeId {
+            system.tree.data.window.node_for(layout, wid).unwrap()
+        }
 
         #[test]
-        fn swap_windows() {
-            let mut system = TraditionalLayoutSystem::default();
-            let layout = system.create_layout();
+        fn hierarchical_join_right_takes_the_nearest_child() {
+            let (mut system, layout) = build("[V[1, 2], V[3, 4]]", &[]);
 
-            system.add_window_after_selection(layout, w(1, 1));
-            system.add_window_after_selection(layout, w(1, 2));
+            let c = node(&system, layout, w(1, 3));
+            assert_eq!(
+                join_target(&system, layout, w(1, 2), Direction::Right),
+                Some(JoinTarget::Hierarchical(c))
+            );
 
-            assert!(system.swap_windows(layout, w(1, 1), w(1, 2)));
+            system.select_window(layout, w(1, 2));
+            system.join_selection_with_direction(layout, Direction::Right);
+            let parent = |wid| node(&system, layout, wid).parent(system.map());
+            assert_eq!(parent(w(1, 2)), parent(w(1, 3)));
+            assert_ne!(parent(w(1, 2)), parent(w(1, 4)));
+        }
+
+        #[test]
+        fn hierarchical_join_left_takes_the_nearest_child() {
+            let (system, layout) = build("[V[1, 2], V[3, 4]]", &[]);
+
+            let b = node(&system, layout, w(1, 2));
+            assert_eq!(
+                join_target(&system, layout, w(1, 3), Direction::Left),
+                Some(JoinTarget::Hierarchical(b))
+            );
         }
 
         #[test]
-        fn swap_same_window_fails() {
+        fn join_target_reports_how_it_was_found() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
+            for idx in 1..=3 {
+                system.add_window_after_selection(layout, w(1, idx));
+            }
 
-            system.add_window_after_selection(layout, w(1, 1));
+            let third = node(&system, layout, w(1, 3));
+            assert_eq!(
+                join_target(&system, layout, w(1, 2), Direction::Right),
+                Some(JoinTarget::Sibling(third))
+            );
+            assert_eq!(join_target(&system, layout, w(1, 3), Direction::Right), None);
 
-            assert!(!system.swap_windows(layout, w(1, 1), w(1, 1)));
+            let (system, layout) = build("[V[1, 2], V[3, 4]]", &[]);
+            let column = node(&system, layout, w(1, 3)).parent(system.map()).unwrap();
+            assert_eq!(
+                join_target(&system, layout, w(1, 1), Direction::Down),
+                Some(JoinTarget::ParentNeighbor(column))
+            );
+            assert_eq!(join_target(&system, layout, w(1, 4), Direction::Right), None);
         }
     }
 
-    mod unjoin {
+    mod container_lock {
         use super::*;
 
+        fn parent_of(system: &TraditionalLayoutSystem, layout: LayoutId, wid: WindowId) -> NodeId {
+            let node = system.tree.data.window.node_for(layout, wid).unwrap();
+            node.parent(system.map()).unwrap()
+        }
+
         #[test]
-        fn unjoin_selection() {
-            let mut system = TraditionalLayoutSystem::default();
-            let layout = system.create_layout();
+        fn locked_container_survives_losing_a_child() {
+            let (mut system, layout) = build("[1, V[2, 3]]", &[]);
+            assert!(system.toggle_lock_of_selection_parent(layout));
+            let column = parent_of(&system, layout, w(1, 3));
 
-            system.add_window_after_selection(layout, w(1, 1));
-            system.add_window_after_selection(layout, w(1, 2));
-            system.split_selection(layout, LayoutKind::Horizontal);
+            system.remove_window(w(1, 3));
+
+            assert_eq!(parent_of(&system, layout, w(1, 2)), column);
+            assert!(system.draw_tree(layout).contains("Vertical locked"));
+
+            system.select_window(layout, w(1, 2));
             system.unjoin_selection(layout);
+            assert_eq!(parent_of(&system, layout, w(1, 2)), system.root(layout));
         }
-    }
 
-    mod join {
-        use super::*;
+        #[test]
+        fn joins_move_locked_containers_whole() {
+            let (mut system, layout) = build("[1, V[2, 3]]", &[]);
+            assert!(system.toggle_lock_of_selection_parent(layout));
+            let column = parent_of(&system, layout, w(1, 3));
+
+            system.select_window(layout, w(1, 1));
+            system.join_selection_with_direction(layout, Direction::Right);
+
+            assert_eq!(parent_of(&system, layout, w(1, 2)), column);
+            assert_eq!(parent_of(&system, layout, w(1, 3)), column);
+            assert_eq!(column.children(system.map()).count(), 2);
+            assert_eq!(system.layout(column), LayoutKind::Vertical);
+        }
 
         #[test]
-        fn join_selection_with_direction() {
+        fn insertion_does_not_nest_into_locked_container() {
             let mut system = TraditionalLayoutSystem::default();
             let layout = system.create_layout();
+            for idx in 1..=4 {
+                system.add_window_after_selection(layout, w(1, idx));
+            }
+            assert!(system.toggle_lock_of_selection_parent(layout));
 
-            system.add_window_after_selection(layout, w(1, 1));
-            system.add_window_after_selection(layout, w(1, 2));
+            system.add_window_after_selection(layout, w(1, 5));
 
-            system.join_selection_with_direction(layout, Direction::Right);
+            let root = system.root(layout);
+            assert_eq!(parent_of(&system, layout, w(1, 5)), root);
+            assert_eq!(root.children(system.map()).count(), 5);
+        }
+
+        #[test]
+        fn locked_container_keeps_its_kind() {
+            let (mut system, layout) = build("[1, V[2, 3]]", &[]);
+            assert!(system.toggle_lock_of_selection_parent(layout));
+            let column = parent_of(&system, layout, w(1, 3));
+            let default = crate::common::config::StackDefaultOrientation::Perpendicular;
+
+            system.toggle_tile_orientation(layout);
+            assert!(system.apply_stacking_to_parent_of_selection(layout, default).is_empty());
+            assert!(system.cycle_stack_of_parent_of_selection(layout).is_empty());
+            assert!(system.toggle_tabbed(layout, default).is_empty());
+            system.rotate_subtree(layout);
+            system.rotate_layout(layout, true);
+            assert_eq!(system.layout(column), LayoutKind::Vertical);
+            assert_eq!(system.layout(system.root(layout)), LayoutKind::Vertical);
+            let windows: Vec<_> =
+                column.children(system.map()).map(|n| system.window_at(n)).collect();
+            assert_eq!(windows, vec![Some(w(1, 2)), Some(w(1, 3))]);
+
+            assert!(!system.toggle_lock_of_selection_parent(layout));
+            system.toggle_tile_orientation(layout);
+            assert_eq!(system.layout(column), LayoutKind::Horizontal);
         }
     }
 
@@ -2529,6 +6190,46 @@ mod tests {
             system.resize_selection_by(layout, 0.9);
             system.resize_selection_by(layout, -0.9);
         }
+
+        fn share(system: &TraditionalLayoutSystem, layout: LayoutId, wid: WindowId) -> f64 {
+            let node = system.tree.data.window.node_for(layout, wid).unwrap();
+            system.tree.data.layout.proportion(&system.tree.map, node).unwrap()
+        }
+
+        #[test]
+        fn boundary_between_nested_windows() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            // [w1, vertical[w2, w3]]
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.split_selection(layout, LayoutKind::Vertical);
+            system.add_window_after_selection(layout, w(1, 3));
+
+            assert!(system.resize_boundary_between(layout, w(1, 1), w(1, 3), 0.25));
+            assert!((share(&system, layout, w(1, 1)) - 0.75).abs() < 1e-6);
+            assert!((share(&system, layout, w(1, 2)) - 0.5).abs() < 1e-6);
+
+            assert!(system.resize_boundary_between(layout, w(1, 3), w(1, 2), 0.1));
+            assert!((share(&system, layout, w(1, 3)) - 0.6).abs() < 1e-6);
+            assert!((share(&system, layout, w(1, 1)) - 0.75).abs() < 1e-6);
+        }
+
+        #[test]
+        fn boundary_requires_adjacent_windows() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.add_window_after_selection(layout, w(1, 3));
+
+            assert!(!system.resize_boundary_between(layout, w(1, 1), w(1, 3), 0.1));
+            assert!(!system.resize_boundary_between(layout, w(1, 1), w(1, 1), 0.1));
+            assert!(!system.resize_boundary_between(layout, w(1, 1), w(1, 9), 0.1));
+            assert!((share(&system, layout, w(1, 1)) - 1.0 / 3.0).abs() < 1e-6);
+        }
     }
 
     mod fullscreen {
@@ -2555,6 +6256,61 @@ mod tests {
 
             assert!(affected.contains(&w(1, 1)));
         }
+
+        #[test]
+        fn isolate_and_maximize_lifts_the_window_to_the_root() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.split_selection(layout, LayoutKind::Vertical);
+            system.add_window_after_selection(layout, w(1, 3));
+            system.split_selection(layout, LayoutKind::Horizontal);
+            system.add_window_after_selection(layout, w(1, 4));
+            system.select_window(layout, w(1, 3));
+
+            let raised = system.isolate_and_maximize_selection(layout);
+
+            assert_eq!(raised, vec![w(1, 3)]);
+            let root = system.root(layout);
+            let node = system.tree.data.window.node_for(layout, w(1, 3)).unwrap();
+            assert_eq!(node.parent(system.map()), Some(root));
+            assert_eq!(system.fullscreen_windows(layout), vec![(w(1, 3), true)]);
+            assert_eq!(system.selected_window(layout), Some(w(1, 3)));
+            assert_eq!(
+                system.visible_windows_in_layout(layout),
+                vec![w(1, 1), w(1, 2), w(1, 4), w(1, 3)]
+            );
+        }
+
+        #[test]
+        fn fullscreen_follows_moved_window() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.add_window_after_selection(layout, w(1, 3));
+            system.select_window(layout, w(1, 1));
+            system.toggle_fullscreen_of_selection(layout);
+
+            assert!(system.move_selection(layout, Direction::Right));
+            assert_eq!(system.fullscreen_windows(layout), vec![(w(1, 1), false)]);
+
+            system.join_selection_with_direction(layout, Direction::Right);
+            assert_eq!(system.fullscreen_windows(layout), vec![(w(1, 1), false)]);
+        }
+
+        #[test]
+        fn fullscreen_follows_swapped_window() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.toggle_fullscreen_within_gaps_of_selection(layout);
+
+            assert!(system.swap_windows(layout, w(1, 1), w(1, 2)));
+            assert_eq!(system.fullscreen_windows(layout), vec![(w(1, 2), true)]);
+        }
     }
 
     mod ascend_descend {
@@ -2591,6 +6347,41 @@ mod tests {
         }
     }
 
+    mod selection_path {
+        use super::*;
+
+        #[test]
+        fn round_trips_through_nested_containers() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.split_selection(layout, LayoutKind::Vertical);
+            system.add_window_after_selection(layout, w(1, 3));
+
+            assert_eq!(system.selection_path(layout), vec![1, 1]);
+            assert!(system.select_by_path(layout, &[0]));
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+            assert!(system.select_by_path(layout, &[1, 1]));
+            assert_eq!(system.selected_window(layout), Some(w(1, 3)));
+        }
+
+        #[test]
+        fn invalid_paths_leave_the_selection_alone() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+
+            assert!(!system.select_by_path(layout, &[2]));
+            assert!(!system.select_by_path(layout, &[0, 0]));
+            assert_eq!(system.selection_path(layout), vec![1]);
+            assert_eq!(system.selected_window(layout), Some(w(1, 2)));
+        }
+    }
+
     mod app_management {
         use super::*;
 
@@ -2621,6 +6412,88 @@ mod tests {
             assert_eq!(visible.len(), 1);
             assert_eq!(visible[0], w(2, 1));
         }
+
+        fn window_order(system: &TraditionalLayoutSystem, layout: LayoutId) -> Vec<WindowId> {
+            system
+                .root(layout)
+                .traverse_preorder(system.map())
+                .filter_map(|node| system.window_at(node))
+                .collect()
+        }
+
+        #[test]
+        fn new_windows_join_their_app() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(2, 1));
+            system.add_window_after_selection(layout, w(3, 1));
+
+            system.set_windows_for_app_near_app(layout, 1, vec![w(1, 1), w(1, 2), w(1, 3)]);
+            assert_eq!(
+                window_order(&system, layout),
+                vec![w(1, 1), w(1, 2), w(1, 3), w(2, 1), w(3, 1)]
+            );
+            assert_eq!(system.selected_window(layout), Some(w(3, 1)));
+
+            // An app with no windows yet starts after the selection.
+            system.set_windows_for_app_near_app(layout, 4, vec![w(4, 1), w(4, 2)]);
+            assert_eq!(
+                window_order(&system, layout),
+                vec![
+                    w(1, 1),
+                    w(1, 2),
+                    w(1, 3),
+                    w(2, 1),
+                    w(3, 1),
+                    w(4, 1),
+                    w(4, 2)
+                ]
+            );
+            assert_eq!(system.selected_window(layout), Some(w(3, 1)));
+        }
+
+        #[test]
+        fn background_app_churn_keeps_the_selection() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(2, 1));
+            system.add_window_after_selection(layout, w(2, 2));
+            system.select_window(layout, w(1, 1));
+
+            system.set_windows_for_app(layout, 2, vec![w(2, 2), w(2, 3), w(2, 4)]);
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+            system.set_windows_for_app_near_app(layout, 2, vec![w(2, 4), w(2, 5)]);
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+            system.set_windows_for_app(layout, 3, vec![w(3, 1), w(3, 2)]);
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+            assert_eq!(
+                window_order(&system, layout),
+                vec![w(1, 1), w(3, 1), w(3, 2), w(2, 4), w(2, 5)]
+            );
+        }
+
+        #[test]
+        fn first_windows_of_an_empty_layout_select_the_first() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            system.set_windows_for_app(layout, 1, vec![w(1, 1), w(1, 2)]);
+            assert_eq!(window_order(&system, layout), vec![w(1, 1), w(1, 2)]);
+            assert_eq!(system.selected_window(layout), Some(w(1, 1)));
+        }
+
+        #[test]
+        fn new_windows_cluster_at_selection_by_default() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(2, 1));
+
+            system.set_windows_for_app(layout, 1, vec![w(1, 1), w(1, 2)]);
+            assert_eq!(window_order(&system, layout), vec![w(1, 1), w(2, 1), w(1, 2)]);
+        }
     }
 
     mod window_removal {
@@ -2700,6 +6573,37 @@ mod tests {
                 Some(w(1, 1))
             );
         }
+
+        #[test]
+        fn skips_hidden_members_of_a_crosswise_stack() {
+            let (system, layout) = build("[VS[1, 2, 3], 4]", &[2, 4]);
+            assert_eq!(
+                system.window_in_direction(layout, Direction::Right),
+                Some(w(1, 2))
+            );
+        }
+
+        #[test]
+        fn enters_a_stack_along_the_direction_at_its_edge() {
+            let (system, layout) = build("[HS[1, 2, 3], 4]", &[2, 4]);
+            assert_eq!(
+                system.window_in_direction(layout, Direction::Right),
+                Some(w(1, 1))
+            );
+            assert_eq!(
+                system.window_in_direction(layout, Direction::Left),
+                Some(w(1, 4))
+            );
+        }
+
+        #[test]
+        fn enters_a_tabbed_stack_at_its_selected_member() {
+            let (system, layout) = build("[HT[1, 2, 3], 4]", &[2, 4]);
+            assert_eq!(
+                system.window_in_direction(layout, Direction::Right),
+                Some(w(1, 2))
+            );
+        }
     }
 
     mod move_over {
@@ -2746,6 +6650,46 @@ mod tests {
     mod stack_layout {
         use super::*;
 
+        #[test]
+        fn stack_raise_order_cascades_to_the_focused_member() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            let root = system.root(layout);
+            system.set_layout(root, LayoutKind::VerticalStack);
+            for idx in 1..=4 {
+                system.add_window_after_selection(layout, w(1, idx));
+            }
+            system.select_window(layout, w(1, 2));
+
+            assert_eq!(
+                system.stack_raise_order(layout, w(1, 2)),
+                vec![w(1, 4), w(1, 3), w(1, 1), w(1, 2)]
+            );
+
+            system.set_layout(root, LayoutKind::Horizontal);
+            assert!(system.stack_raise_order(layout, w(1, 2)).is_empty());
+        }
+
+        #[test]
+        fn stack_raise_order_puts_the_outer_stack_underneath() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            let root = system.root(layout);
+            system.set_layout(root, LayoutKind::VerticalStack);
+            for idx in 1..=3 {
+                system.add_window_after_selection(layout, w(1, idx));
+            }
+            system.split_selection(layout, LayoutKind::HorizontalStack);
+            system.add_window_after_selection(layout, w(1, 4));
+            system.add_window_after_selection(layout, w(1, 5));
+            system.select_window(layout, w(1, 4));
+
+            assert_eq!(
+                system.stack_raise_order(layout, w(1, 4)),
+                vec![w(1, 1), w(1, 2), w(1, 5), w(1, 3), w(1, 4)]
+            );
+        }
+
         #[test]
         fn test_stack_default_orientation_behavior() {
             let mut system = TraditionalLayoutSystem::default();
@@ -2819,6 +6763,102 @@ mod tests {
 
             assert!(before || after);
         }
+
+        #[test]
+        fn focused_frame_pops_by_configured_amount() {
+            let focus_pop = 10.0;
+            let res = StackLayoutResult::new(screen(), 3, 40.0, focus_pop, true);
+
+            let plain = res.get_frame_for_index(1);
+            let focused = res.get_focused_frame_for_index(1, 1);
+
+            assert_eq!(focused.size.height, screen().size.height);
+            assert_eq!(focused.size.width, plain.size.width + focus_pop);
+            assert_eq!(focused.origin.x, plain.origin.x - focus_pop / 2.0);
+        }
+
+        #[test]
+        fn zero_focus_pop_uses_plain_frame() {
+            let res = StackLayoutResult::new(screen(), 3, 40.0, 0.0, true);
+
+            for i in 0..3 {
+                assert_eq!(res.get_focused_frame_for_index(i, i), res.get_frame_for_index(i));
+            }
+        }
+
+        #[test]
+        fn zero_focus_pop_keeps_stacked_frames_stable() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+
+            system.add_window_after_selection(layout, w(1, 1));
+            system.add_window_after_selection(layout, w(1, 2));
+            system.apply_stacking_to_parent_of_selection(
+                layout,
+                crate::common::config::StackDefaultOrientation::Horizontal,
+            );
+
+            let frames = |system: &TraditionalLayoutSystem| {
+                let mut frames = system.calculate_layout(
+                    layout,
+                    screen(),
+                    40.0,
+                    0.0,
+                    &gaps(),
+                    0.0,
+                    crate::common::config::HorizontalPlacement::Top,
+                    crate::common::config::VerticalPlacement::Left,
+                    None,
+                );
+                frames.sort_by_key(|(wid, _)| *wid);
+                frames.into_iter().map(|(_, frame)| frame.size).collect::<Vec<_>>()
+            };
+
+            let before = frames(&system);
+            system.select_window(layout, w(1, 1));
+            let after = frames(&system);
+
+            assert_eq!(before, after);
+        }
+
+        #[test]
+        fn tabbed_members_all_fill_the_container() {
+            let mut system = TraditionalLayoutSystem::default();
+            let layout = system.create_layout();
+            for i in 1..=3 {
+                system.add_window_after_selection(layout, w(1, i));
+            }
+            let orientation = crate::common::config::StackDefaultOrientation::Horizontal;
+
+            system.toggle_tabbed(layout, orientation);
+            assert_eq!(system.root_kind(layout), LayoutKind::HorizontalTabbed);
+
+            let frames = system.calculate_layout(
+                layout,
+                screen(),
+                40.0,
+                10.0,
+                &gaps(),
+                0.0,
+                crate::common::config::HorizontalPlacement::Top,
+                crate::common::config::VerticalPlacement::Left,
+                None,
+            );
+            assert_eq!(frames.len(), 3);
+            let first = frames[0].1;
+            assert!(frames.iter().all(|(_, frame)| *frame == first));
+            assert_eq!(first.size.width, screen().size.width);
+
+            // Tabbed stacks navigate like any other stack.
+            system.select_window(layout, w(1, 1));
+            let (focus, _) = system.move_focus(layout, Direction::Right);
+            assert_eq!(focus, Some(w(1, 2)));
+
+            system.toggle_tabbed(layout, orientation);
+            assert_eq!(system.root_kind(layout), LayoutKind::HorizontalStack);
+            system.toggle_tabbed(layout, orientation);
+            assert_eq!(system.root_kind(layout), LayoutKind::HorizontalTabbed);
+        }
     }
 
     mod frame_resize_edge_cases {
@@ -2836,10 +6876,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                10.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             system.resize_selection_by(layout, 0.1);
@@ -2848,10 +6890,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                10.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             assert_eq!(before.len(), after.len());
@@ -2872,10 +6916,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                10.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             assert_eq!(result.len(), 2);
@@ -2898,10 +6944,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                10.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             assert_eq!(result.len(), 2);
@@ -2924,10 +6972,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                10.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             assert_eq!(result.len(), 2);
@@ -2948,10 +6998,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                10.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             assert_eq!(result.len(), 2);
@@ -2973,10 +7025,12 @@ mod tests {
                 layout,
                 screen(),
                 0.0,
+                10.0,
                 &gaps(),
                 0.0,
                 crate::common::config::HorizontalPlacement::Top,
                 crate::common::config::VerticalPlacement::Left,
+                None,
             );
 
             assert_eq!(result.len(), 2);
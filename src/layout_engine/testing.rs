@@ -0,0 +1,305 @@
+//! A pure-Rust harness that drives [`LayoutEngine`] the way the reactor does,
+//! without AX, the window server or real displays.
+//!
+//! [`SimulatedWorld`] owns an engine, a set of synthetic screens (one space
+//! each) and the frames from the last layout pass. Events and commands are
+//! fed in through small helpers, and every helper that can change the layout
+//! runs a fresh pass afterwards, so tests only assert on [`frames`].
+//!
+//! ```ignore
+//! let screen = CGRect::new(CGPoint::new(0.0, 0.0), CGSize::new(1000.0, 800.0));
+//! let mut world = SimulatedWorld::new(vec![screen]);
+//! let [a, b] = world.add_app(1, 2)[..] else { unreachable!() };
+//! world.focus(a);
+//! world.run_command(LayoutCommand::MoveNode(Direction::Right));
+//! assert_eq!(world.frame(b).unwrap().origin.x, 0.0);
+//! ```
+//!
+//! [`frames`]: SimulatedWorld::frames
+
+use objc2_core_foundation::{CGPoint, CGRect, CGSize};
+
+use super::{EventResponse, LayoutCommand, LayoutEngine, LayoutEvent};
+use crate::actor::app::{AppInfo, WindowId, pid_t};
+use crate::common::collections::{BTreeMap, HashMap};
+use crate::common::config::{
+    GapSettings, HorizontalPlacement, LayoutSettings, VerticalPlacement, VirtualWorkspaceSettings,
+};
+use crate::sys::screen::SpaceId;
+
+pub struct SimulatedWorld {
+    engine: LayoutEngine,
+    /// Screens in the order given, each showing its own space.
+    screens: Vec<(SpaceId, CGRect)>,
+    /// The space commands act on, following focus like the reactor does.
+    command_space: SpaceId,
+    gaps: GapSettings,
+    next_window: HashMap<pid_t, u32>,
+    app_windows: HashMap<(SpaceId, pid_t), Vec<WindowId>>,
+    frames: BTreeMap<SpaceId, BTreeMap<WindowId, CGRect>>,
+}
+
+impl SimulatedWorld {
+    /// A world with default settings and one space per screen, numbered from
+    /// 1 in the order given. Commands act on the first screen until focus
+    /// moves elsewhere.
+    pub fn new(screens: Vec<CGRect>) -> Self {
+        Self::with_settings(
+            screens,
+            &LayoutSettings::default(),
+            &VirtualWorkspaceSettings::default(),
+        )
+    }
+
+    pub fn with_settings(
+        screens: Vec<CGRect>,
+        layout_settings: &LayoutSettings,
+        workspace_settings: &VirtualWorkspaceSettings,
+    ) -> Self {
+        assert!(
+            !screens.is_empty(),
+            "a simulated world needs at least one screen"
+        );
+        let screens: Vec<_> = screens
+            .into_iter()
+            .enumerate()
+            .map(|(idx, frame)| (SpaceId::new(idx as u64 + 1), frame))
+            .collect();
+        let mut world = SimulatedWorld {
+            engine: LayoutEngine::new(workspace_settings, layout_settings, None),
+            command_space: screens[0].0,
+            screens,
+            gaps: layout_settings.gaps.clone(),
+            next_window: HashMap::default(),
+            app_windows: HashMap::default(),
+            frames: BTreeMap::new(),
+        };
+        for (space, frame) in world.screens.clone() {
+            let _ = world.engine.handle_event(LayoutEvent::SpaceExposed(space, frame.size));
+        }
+        world.relayout();
+        world
+    }
+
+    pub fn engine(&self) -> &LayoutEngine {
+        &self.engine
+    }
+
+    pub fn engine_mut(&mut self) -> &mut LayoutEngine {
+        &mut self.engine
+    }
+
+    /// The space shown on the screen at `index`.
+    pub fn space(&self, index: usize) -> SpaceId {
+        self.screens[index].0
+    }
+
+    /// The space commands currently act on.
+    pub fn command_space(&self) -> SpaceId {
+        self.command_space
+    }
+
+    /// Adds `count` new windows for `pid` on the command space.
+    pub fn add_app(&mut self, pid: pid_t, count: usize) -> Vec<WindowId> {
+        self.add_app_on(self.command_space, pid, count)
+    }
+
+    /// Adds `count` new windows for `pid` on `space`, reporting the app's
+    /// full window list there as the reactor does after discovery.
+    pub fn add_app_on(&mut self, space: SpaceId, pid: pid_t, count: usize) -> Vec<WindowId> {
+        let next = self.next_window.entry(pid).or_insert(1);
+        let added: Vec<_> = (0..count)
+            .map(|_| {
+                let wid = WindowId::new(pid, *next);
+                *next += 1;
+                wid
+            })
+            .collect();
+        let windows = self.app_windows.entry((space, pid)).or_default();
+        windows.extend(&added);
+        let windows = windows.iter().map(|&wid| (wid, None, None, None)).collect();
+        let info = AppInfo {
+            bundle_id: Some(format!("com.example.app{pid}")),
+            localized_name: Some(format!("App{pid}")),
+        };
+        let _ = self.engine.handle_event(LayoutEvent::WindowsOnScreenUpdated(
+            space,
+            pid,
+            windows,
+            Some(info),
+        ));
+        self.relayout();
+        added
+    }
+
+    pub fn remove_window(&mut self, wid: WindowId) {
+        for windows in self.app_windows.values_mut() {
+            windows.retain(|&w| w != wid);
+        }
+        let _ = self.engine.handle_event(LayoutEvent::WindowRemoved(wid));
+        self.relayout();
+    }
+
+    /// Reports `wid` as focused and makes its space the command space.
+    pub fn focus(&mut self, wid: WindowId) {
+        let space = self.space_of(wid).expect("focused window is not on any simulated space");
+        self.command_space = space;
+        let _ = self.engine.handle_event(LayoutEvent::WindowFocused(space, wid));
+        self.relayout();
+    }
+
+    /// Runs `cmd` on the command space, routing workspace commands the way
+    /// the reactor does. A window the response asks to focus is reported
+    /// focused, standing in for the app confirming it.
+    pub fn run_command(&mut self, cmd: LayoutCommand) -> EventResponse {
+        let is_workspace_command = matches!(
+            cmd,
+            LayoutCommand::NextWorkspace(_)
+                | LayoutCommand::PrevWorkspace(_)
+                | LayoutCommand::SwitchToWorkspace(_)
+                | LayoutCommand::CreateWorkspace
+                | LayoutCommand::SwitchToLastWorkspace
+                | LayoutCommand::MoveWindowToWorkspace { .. }
+                | LayoutCommand::MergeWorkspaces { .. }
+                | LayoutCommand::ReorderWorkspace { .. }
+        );
+        let response = if is_workspace_command {
+            self.engine.handle_virtual_workspace_command(self.command_space, &cmd)
+        } else {
+            let spaces: Vec<_> = self.screens.iter().map(|&(space, _)| space).collect();
+            let centers = self.screens.iter().map(|&(space, frame)| (space, frame.mid())).collect();
+            self.engine.handle_command(Some(self.command_space), &spaces, &centers, cmd)
+        };
+        if let Some(wid) = response.focus_window
+            && let Some(space) = self.space_of(wid)
+        {
+            self.command_space = space;
+            let _ = self.engine.handle_event(LayoutEvent::WindowFocused(space, wid));
+        }
+        self.relayout();
+        response
+    }
+
+    /// Frames from the latest layout pass, per space. Windows on inactive
+    /// workspaces are included at their hidden positions.
+    pub fn frames(&self) -> &BTreeMap<SpaceId, BTreeMap<WindowId, CGRect>> {
+        &self.frames
+    }
+
+    pub fn frame(&self, wid: WindowId) -> Option<CGRect> {
+        self.frames.values().find_map(|frames| frames.get(&wid).copied())
+    }
+
+    /// Tiled windows visible on the command space, in layout order.
+    pub fn visible_windows(&self) -> Vec<WindowId> {
+        self.engine.visible_windows_in_space(self.command_space)
+    }
+
+    fn space_of(&self, wid: WindowId) -> Option<SpaceId> {
+        self.screens.iter().map(|&(space, _)| space).find(|&space| {
+            self.engine
+                .virtual_workspace_manager()
+                .workspace_for_window(space, wid)
+                .is_some()
+        })
+    }
+
+    /// Runs a layout pass over every screen. Windows keep the size they were
+    /// last given, which is what floating windows are placed from.
+    pub fn relayout(&mut self) {
+        for &(space, screen) in &self.screens {
+            let previous = self.frames.remove(&space).unwrap_or_default();
+            let frames = self.engine.calculate_layout_with_virtual_workspaces(
+                space,
+                screen,
+                &self.gaps,
+                0.0,
+                HorizontalPlacement::Top,
+                VerticalPlacement::Left,
+                |wid| previous.get(&wid).copied(),
+            );
+            self.frames.insert(space, frames.into_iter().collect());
+        }
+    }
+}
+
+/// A `width` x `height` screen at the origin.
+pub fn screen(width: f64, height: f64) -> CGRect {
+    CGRect::new(CGPoint::new(0.0, 0.0), CGSize::new(width, height))
+}
+
+mod tests {
+    use super::*;
+    use crate::layout_engine::Direction;
+
+    #[test]
+    fn windows_tile_across_the_screen() {
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let windows = world.add_app(1, 2);
+
+        assert_eq!(
+            world.frame(windows[0]),
+            Some(CGRect::new(CGPoint::new(0.0, 0.0), CGSize::new(500.0, 800.0)))
+        );
+        assert_eq!(
+            world.frame(windows[1]),
+            Some(CGRect::new(CGPoint::new(500.0, 0.0), CGSize::new(500.0, 800.0)))
+        );
+        assert_eq!(world.frames()[&world.space(0)].len(), 2);
+    }
+
+    #[test]
+    fn commands_follow_focus_between_screens() {
+        let right = CGRect::new(CGPoint::new(1000.0, 0.0), CGSize::new(1000.0, 800.0));
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0), right]);
+        let left_windows = world.add_app(1, 2);
+        let right_windows = world.add_app_on(world.space(1), 2, 2);
+
+        world.focus(right_windows[0]);
+        assert_eq!(world.command_space(), world.space(1));
+        world.run_command(LayoutCommand::MoveNode(Direction::Right));
+
+        assert_eq!(world.frame(right_windows[0]).unwrap().origin.x, 1500.0);
+        assert_eq!(world.frame(right_windows[1]).unwrap().origin.x, 1000.0);
+        assert_eq!(world.frame(left_windows[0]).unwrap().origin.x, 0.0);
+    }
+
+    #[test]
+    fn workspace_switch_restores_focus() {
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let first = world.add_app(1, 2);
+        world.focus(first[1]);
+
+        world.run_command(LayoutCommand::SwitchToWorkspace(1));
+        let second = world.add_app(2, 1);
+        world.focus(second[0]);
+        assert_eq!(world.visible_windows(), second);
+
+        let response = world.run_command(LayoutCommand::SwitchToWorkspace(0));
+        assert_eq!(response.focus_window, Some(first[1]));
+        assert_eq!(world.visible_windows(), first);
+        let hidden = world.frame(second[0]).unwrap();
+        assert!(world.engine().virtual_workspace_manager().is_hidden_position(
+            &screen(1000.0, 800.0),
+            &hidden,
+            Some("com.example.app2"),
+        ));
+    }
+
+    #[test]
+    fn floating_toggle_leaves_the_tiles_to_the_rest() {
+        let mut world = SimulatedWorld::new(vec![screen(1000.0, 800.0)]);
+        let windows = world.add_app(1, 2);
+        world.focus(windows[1]);
+
+        world.run_command(LayoutCommand::ToggleWindowFloating);
+        assert!(world.engine().is_window_floating(windows[1]));
+        assert_eq!(world.visible_windows(), vec![windows[0]]);
+        assert_eq!(world.frame(windows[0]), Some(screen(1000.0, 800.0)));
+        assert_eq!(world.frame(windows[1]).unwrap().size, CGSize::new(500.0, 800.0));
+
+        world.run_command(LayoutCommand::ToggleWindowFloating);
+        assert!(!world.engine().is_window_floating(windows[1]));
+        assert_eq!(world.frame(windows[0]).unwrap().size.width, 500.0);
+    }
+}
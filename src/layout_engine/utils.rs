@@ -24,3 +24,65 @@ pub fn compute_tiling_area(screen: CGRect, gaps: &crate::common::config::GapSett
         .round()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use objc2_core_foundation::{CGPoint, CGSize};
+
+    use super::*;
+    use crate::common::config::{GapSettings, OuterGaps};
+
+    fn screen() -> CGRect {
+        CGRect::new(CGPoint::new(0.0, 0.0), CGSize::new(1920.0, 1080.0))
+    }
+
+    #[test]
+    fn insets_each_edge_independently() {
+        let gaps = GapSettings {
+            outer: OuterGaps {
+                top: 10.0,
+                right: 20.0,
+                bottom: 30.0,
+                left: 40.0,
+            },
+            ..Default::default()
+        };
+        let area = compute_tiling_area(screen(), &gaps);
+        assert_eq!(
+            area,
+            CGRect::new(CGPoint::new(40.0, 10.0), CGSize::new(1860.0, 1040.0))
+        );
+    }
+
+    #[test]
+    fn insets_relative_to_screen_origin() {
+        let gaps = GapSettings {
+            outer: OuterGaps {
+                top: 10.0,
+                right: 20.0,
+                bottom: 30.0,
+                left: 40.0,
+            },
+            ..Default::default()
+        };
+        let screen = CGRect::new(CGPoint::new(1920.0, -200.0), CGSize::new(1280.0, 800.0));
+        let area = compute_tiling_area(screen, &gaps);
+        assert_eq!(
+            area,
+            CGRect::new(CGPoint::new(1960.0, -190.0), CGSize::new(1220.0, 760.0))
+        );
+    }
+
+    #[test]
+    fn uniform_gap_matches_explicit_edges() {
+        let uniform = GapSettings {
+            outer: OuterGaps::uniform(15.0),
+            ..Default::default()
+        };
+        let area = compute_tiling_area(screen(), &uniform);
+        assert_eq!(
+            area,
+            CGRect::new(CGPoint::new(15.0, 15.0), CGSize::new(1890.0, 1050.0))
+        );
+    }
+}
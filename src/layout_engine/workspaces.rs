@@ -1,19 +1,25 @@
 use objc2_core_foundation::CGSize;
 use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
 
 use super::{LayoutId, LayoutSystem};
+use crate::common::persistence::MapEntries;
 use crate::sys::screen::SpaceId;
 
+#[serde_as]
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub(crate) struct WorkspaceLayouts {
+    #[serde_as(as = "MapEntries")]
     map: crate::common::collections::HashMap<
         (SpaceId, crate::model::VirtualWorkspaceId),
         SpaceLayoutInfo,
     >,
 }
 
+#[serde_as]
 #[derive(Serialize, Deserialize, Debug)]
 struct SpaceLayoutInfo {
+    #[serde_as(as = "MapEntries")]
     configurations: crate::common::collections::HashMap<Size, LayoutId>,
     active_size: Size,
     last_saved: Option<LayoutId>,
@@ -41,13 +47,16 @@ impl From<CGSize> for Size {
 }
 
 impl WorkspaceLayouts {
+    /// Makes sure each workspace has an active layout for `size`, returning
+    /// the layouts that had to be created from scratch.
     pub(crate) fn ensure_active_for_space(
         &mut self,
         space: SpaceId,
         size: CGSize,
         workspaces: impl IntoIterator<Item = crate::model::VirtualWorkspaceId>,
         tree: &mut impl LayoutSystem,
-    ) {
+    ) -> Vec<LayoutId> {
+        let mut created = Vec::new();
         let size = Size::from(size);
         for workspace_id in workspaces {
             let workspace_key = (space, workspace_id);
@@ -83,7 +92,9 @@ impl WorkspaceLayouts {
                     } else if let Some(source) = workspace_layout.last_saved {
                         tree.clone_layout(source)
                     } else {
-                        tree.create_layout()
+                        let layout = tree.create_layout();
+                        created.push(layout);
+                        layout
                     })
                 }
                 crate::common::collections::hash_map::Entry::Occupied(entry) => {
@@ -103,6 +114,7 @@ impl WorkspaceLayouts {
                 space
             );
         }
+        created
     }
 
     pub(crate) fn remap_space(&mut self, old_space: SpaceId, new_space: SpaceId) {
@@ -128,6 +140,19 @@ impl WorkspaceLayouts {
         }
     }
 
+    pub(crate) fn remove_workspace(
+        &mut self,
+        space: SpaceId,
+        workspace_id: crate::model::VirtualWorkspaceId,
+        tree: &mut impl LayoutSystem,
+    ) {
+        if let Some(info) = self.map.remove(&(space, workspace_id)) {
+            for layout in info.configurations.into_values() {
+                tree.remove_layout(layout);
+            }
+        }
+    }
+
     pub(crate) fn active(
         &self,
         space: SpaceId,
@@ -171,6 +196,18 @@ impl WorkspaceLayouts {
         }
     }
 
+    /// Whether `space` has been given layouts, by exposure or on demand.
+    pub(crate) fn contains_space(&self, space: SpaceId) -> bool {
+        self.map.keys().any(|(sp, _)| *sp == space)
+    }
+
+    /// The screen size the layouts of `space` are currently sized for.
+    pub(crate) fn active_size(&self, space: SpaceId) -> Option<CGSize> {
+        self.map.iter().find(|((sp, _), _)| *sp == space).map(|(_, info)| {
+            CGSize::new(info.active_size.width as f64, info.active_size.height as f64)
+        })
+    }
+
     pub(crate) fn spaces(&self) -> crate::common::collections::BTreeSet<SpaceId> {
         self.map.keys().map(|(sp, _)| *sp).collect()
     }
@@ -4,5 +4,5 @@ pub mod tree;
 pub mod tx_store;
 pub mod virtual_workspace;
 pub use virtual_workspace::{
-    HideCorner, VirtualWorkspace, VirtualWorkspaceId, VirtualWorkspaceManager,
+    DisplayHints, HideCorner, VirtualWorkspace, VirtualWorkspaceId, VirtualWorkspaceManager,
 };
@@ -68,9 +68,42 @@ impl Selection {
         }
     }
 
+    /// The child selected under `node` and whether the selection stops at
+    /// `node`, for copying the selection by value.
+    pub fn local_selection_info(&self, node: NodeId) -> Option<(NodeId, bool)> {
+        self.nodes.get(node).map(|info| (info.selected_child, info.stop_here))
+    }
+
+    /// Restores what `local_selection_info` returned for `node`.
+    pub fn set_local_selection_info(&mut self, node: NodeId, info: Option<(NodeId, bool)>) {
+        match info {
+            Some((selected_child, stop_here)) => {
+                self.nodes.insert(node, SelectionInfo { selected_child, stop_here });
+            }
+            None => {
+                self.nodes.remove(node);
+            }
+        }
+    }
+
+    /// True if `node` has children but no record of which one is selected.
+    /// This happens when its selected child was removed with no sibling left
+    /// to take over; containers selected on purpose always carry a record.
+    pub fn lost_selected_child(&self, map: &NodeMap, node: NodeId) -> bool {
+        !self.nodes.contains_key(node) && !node.is_empty(map)
+    }
+
     pub fn select(&mut self, map: &NodeMap, selection: NodeId) {
         if let Some(info) = self.nodes.get_mut(selection) {
             info.stop_here = true;
+        } else if let Some(first_child) = selection.first_child(map) {
+            self.nodes.insert(
+                selection,
+                SelectionInfo {
+                    selected_child: first_child,
+                    stop_here: true,
+                },
+            );
         }
         let mut node = selection;
         while let Some(parent) = node.parent(map) {
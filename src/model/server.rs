@@ -26,6 +26,18 @@ pub struct WindowData {
     pub is_focused: bool,
     pub bundle_id: Option<String>,
     pub window_server_id: Option<u32>,
+    #[serde(default)]
+    pub is_minimized: bool,
+    /// The window is out of view because its app is hidden.
+    #[serde(default)]
+    pub is_hidden: bool,
+}
+
+impl WindowData {
+    /// Minimized and hidden windows have no contents to capture.
+    pub fn is_offscreen(&self) -> bool {
+        self.is_minimized || self.is_hidden
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,3 +70,26 @@ pub struct DisplayData {
     /// True if this display corresponds to the context Rift uses when no space_id is provided
     pub is_active_context: bool,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpaceSnapshotData {
+    pub space_id: u64,
+    pub workspaces: Vec<WorkspaceData>,
+}
+
+/// Flags describing what a connected client can rely on from the event stream.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompatibilityData {
+    pub protocol_version: u32,
+    pub features: Vec<String>,
+}
+
+/// Full state sent to a subscriber when it first connects, before any live events.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotData {
+    pub displays: Vec<DisplayData>,
+    pub spaces: Vec<SpaceSnapshotData>,
+    pub focused_window: Option<WindowData>,
+    pub mission_control_active: bool,
+    pub compatibility: CompatibilityData,
+}
@@ -59,6 +59,15 @@ impl NodeMap {
     pub fn contains(&self, id: NodeId) -> bool {
         self.map.contains_key(id)
     }
+
+    /// Number of nodes across every tree in the map.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
 }
 
 impl Index<NodeId> for NodeMap {
@@ -210,6 +219,28 @@ impl NodeId {
         })
     }
 
+    /// The child indices leading from `root` down to this node, or `None`
+    /// if the node is not in `root`'s subtree.
+    #[track_caller]
+    pub fn path_from(self, root: NodeId, map: &NodeMap) -> Option<Vec<usize>> {
+        let mut path = Vec::new();
+        let mut node = self;
+        while node != root {
+            let parent = node.parent(map)?;
+            path.push(parent.children(map).position(|child| child == node)?);
+            node = parent;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// The node reached by following `path` as child indices from this node,
+    /// or `None` if an index is out of range.
+    #[track_caller]
+    pub fn descendant_at(self, path: &[usize], map: &NodeMap) -> Option<NodeId> {
+        path.iter().try_fold(self, |node, &index| node.children(map).nth(index))
+    }
+
     /// Creates a deep copy of the subtree rooted at this node.
     ///
     /// This method does not call observer events on the created nodes.
@@ -920,6 +951,21 @@ mod tests {
         assert_eq!([t.child1], *traverse(t.child1));
     }
 
+    #[test]
+    fn paths() {
+        let t = TestTree::new();
+        let map = &t.tree.map;
+        assert_eq!(Some(vec![1, 0]), t.gc1.path_from(t.root, map));
+        assert_eq!(Some(vec![0]), t.gc1.path_from(t.child2, map));
+        assert_eq!(Some(vec![]), t.root.path_from(t.root, map));
+        assert_eq!(None, t.child1.path_from(t.other_root, map));
+
+        assert_eq!(Some(t.gc1), t.root.descendant_at(&[1, 0], map));
+        assert_eq!(Some(t.root), t.root.descendant_at(&[], map));
+        assert_eq!(None, t.root.descendant_at(&[3], map));
+        assert_eq!(None, t.root.descendant_at(&[0, 0], map));
+    }
+
     #[test]
     fn deep_copy() {
         let mut t = TestTree::new();
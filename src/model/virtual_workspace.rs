@@ -3,14 +3,18 @@
 use objc2_core_foundation::{CGPoint, CGRect, CGSize};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use serde_with::serde_as;
+use serde_with::{Same, serde_as};
 use slotmap::{SlotMap, new_key_type};
 use tracing::{error, warn};
 
 use crate::actor::app::WindowId;
-use crate::common::collections::{HashMap, HashSet};
-use crate::common::config::{AppWorkspaceRule, VirtualWorkspaceSettings, WorkspaceSelector};
+use crate::common::collections::{BTreeSet, HashMap, HashSet};
+use crate::common::config::{
+    AppWorkspaceRule, OnEmptyWorkspace, VirtualWorkspaceSettings, WorkspaceOverride,
+    WorkspaceScope, WorkspaceSelector,
+};
 use crate::common::log::trace_misc;
+use crate::common::persistence::MapEntries;
 use crate::layout_engine::Direction;
 use crate::sys::app::pid_t;
 use crate::sys::geometry::CGRectDef;
@@ -45,8 +49,35 @@ pub enum WorkspaceError {
 #[derive(Debug, Clone, Copy)]
 pub struct AppRuleAssignment {
     pub workspace_id: VirtualWorkspaceId,
+    /// Whether a matching app rule, rather than the default, decided it.
+    pub by_rule: bool,
     pub floating: bool,
     pub prev_rule_decision: bool,
+    pub opacity: Option<f32>,
+    pub always_on_top: bool,
+}
+
+impl AppRuleAssignment {
+    pub fn display_hints(&self) -> DisplayHints {
+        DisplayHints {
+            opacity: self.opacity,
+            always_on_top: self.always_on_top,
+        }
+    }
+}
+
+/// How the app rule matching a window asks for it to be drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct DisplayHints {
+    pub opacity: Option<f32>,
+    pub always_on_top: bool,
+}
+
+impl DisplayHints {
+    /// Whether the window is drawn as it would be without a rule.
+    pub fn is_default(&self) -> bool {
+        *self == DisplayHints::default()
+    }
 }
 
 /// Result of evaluating app rules for a window.
@@ -56,6 +87,15 @@ pub enum AppRuleResult {
     Unmanaged,
 }
 
+/// Where the app rules would put a window now, regardless of where it is.
+#[derive(Debug, Clone, Copy)]
+pub struct RulePlacement {
+    /// The workspace a rule names, or `None` to leave the window where it is.
+    pub workspace: Option<VirtualWorkspaceId>,
+    pub floating: bool,
+    pub hints: DisplayHints,
+}
+
 #[derive(Debug, Clone)]
 struct CachedAppRule {
     rule: AppWorkspaceRule,
@@ -63,6 +103,41 @@ struct CachedAppRule {
     compiled_substring_regex: Option<Regex>,
 }
 
+impl CachedAppRule {
+    fn compile(rule: &AppWorkspaceRule) -> Self {
+        let compiled_title_regex = rule.title_regex.as_ref().and_then(|re| {
+            regex::RegexBuilder::new(re)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| warn!("Invalid title_regex '{}' in app rule: {}", re, e))
+                .ok()
+        });
+
+        let compiled_substring_regex = rule.title_substring.as_ref().and_then(|sub| {
+            let escaped = regex::escape(sub);
+            let pattern = format!("(?i).*{}.*", escaped);
+            regex::RegexBuilder::new(&pattern)
+                .build()
+                .map_err(|e| warn!("Invalid title_substring '{}' in app rule: {}", sub, e))
+                .ok()
+        });
+
+        CachedAppRule {
+            rule: rule.clone(),
+            compiled_title_regex,
+            compiled_substring_regex,
+        }
+    }
+}
+
+/// Workspace count, names and default index resolved for a single display.
+#[derive(Debug, Clone, PartialEq)]
+struct WorkspaceDefinition {
+    count: usize,
+    names: Vec<String>,
+    default_workspace: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VirtualWorkspace {
     pub name: String,
@@ -127,19 +202,35 @@ pub enum HideCorner {
     BottomRight,
 }
 
+#[serde_as]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VirtualWorkspaceManager {
     workspaces: SlotMap<VirtualWorkspaceId, VirtualWorkspace>,
     workspaces_by_space: HashMap<SpaceId, Vec<VirtualWorkspaceId>>,
     pub active_workspace_per_space:
         HashMap<SpaceId, (Option<VirtualWorkspaceId>, VirtualWorkspaceId)>,
+    #[serde_as(as = "MapEntries")]
     pub window_to_workspace: HashMap<(SpaceId, WindowId), VirtualWorkspaceId>,
     #[serde(skip)]
     window_rule_floating: HashMap<(SpaceId, WindowId), bool>,
     #[serde(skip)]
     last_rule_decision: HashMap<(SpaceId, WindowId), bool>,
+    /// Windows moved to another workspace or (un)floated by hand since app
+    /// rules last placed them. Reapplying the rules leaves them alone.
+    #[serde(skip)]
+    placed_by_hand: HashSet<(SpaceId, WindowId)>,
+    #[serde_as(as = "MapEntries")]
     floating_positions: HashMap<(SpaceId, VirtualWorkspaceId), FloatingWindowPositions>,
     workspace_counter: usize,
+    /// Bundle ids whose windows always float, toggled at runtime on top of
+    /// the configured app rules.
+    #[serde(default)]
+    always_floating_apps: BTreeSet<String>,
+    /// Windows adopted into (true) or released from (false) the layout by
+    /// hand, overriding the app rules' `manage` setting.
+    #[serde(default)]
+    #[serde_as(as = "MapEntries")]
+    manage_overrides: HashMap<WindowId, bool>,
     #[serde(skip)]
     app_rules: Vec<AppWorkspaceRule>,
     #[serde(skip)]
@@ -156,6 +247,24 @@ pub struct VirtualWorkspaceManager {
     default_workspace: usize,
     #[serde(skip)]
     workspace_auto_back_and_forth: bool,
+    #[serde(skip)]
+    on_empty: OnEmptyWorkspace,
+    // Restored managers follow moves, as before the setting existed, until
+    // the configured value is applied.
+    #[serde(skip, default = "default_move_follows_window")]
+    move_follows_window: bool,
+    #[serde(skip)]
+    scope: WorkspaceScope,
+    #[serde(skip)]
+    per_display: HashMap<String, WorkspaceOverride>,
+    #[serde(skip)]
+    display_app_rules: HashMap<String, Vec<CachedAppRule>>,
+    #[serde(skip)]
+    space_displays: HashMap<SpaceId, String>,
+}
+
+fn default_move_follows_window() -> bool {
+    true
 }
 
 impl Default for VirtualWorkspaceManager {
@@ -188,7 +297,10 @@ impl VirtualWorkspaceManager {
             active_workspace_per_space: HashMap::default(),
             window_to_workspace: HashMap::default(),
             window_rule_floating: HashMap::default(),
+            always_floating_apps: BTreeSet::new(),
+            manage_overrides: HashMap::default(),
             last_rule_decision: HashMap::default(),
+            placed_by_hand: HashSet::default(),
             floating_positions: HashMap::default(),
             workspace_counter: 1,
             app_rules: config.app_rules.clone(),
@@ -199,36 +311,157 @@ impl VirtualWorkspaceManager {
             default_workspace_names: config.workspace_names.clone(),
             default_workspace,
             workspace_auto_back_and_forth: config.workspace_auto_back_and_forth,
+            on_empty: config.on_empty,
+            move_follows_window: config.move_follows_window,
+            scope: config.scope,
+            per_display: config.per_display.clone(),
+            display_app_rules: HashMap::default(),
+            space_displays: HashMap::default(),
         };
         manager.rebuild_app_rule_cache();
         manager
     }
 
-    pub fn update_settings(&mut self, config: &VirtualWorkspaceSettings) {
+    /// Applies new settings, reconciling the workspaces of every known space
+    /// against its (possibly per-display) definition. Returns the workspaces
+    /// that were dropped because they are no longer configured.
+    pub fn update_settings(
+        &mut self,
+        config: &VirtualWorkspaceSettings,
+    ) -> Vec<(SpaceId, VirtualWorkspaceId)> {
+        let previous: Vec<(SpaceId, WorkspaceDefinition)> = self
+            .workspaces_by_space
+            .keys()
+            .map(|&space| (space, self.definition_for_space(space)))
+            .collect();
+
         self.app_rules = config.app_rules.clone();
         self.default_workspace_count = config.default_workspace_count;
         self.default_workspace_names = config.workspace_names.clone();
         self.workspace_auto_back_and_forth = config.workspace_auto_back_and_forth;
+        self.on_empty = config.on_empty;
+        self.move_follows_window = config.move_follows_window;
+        self.scope = config.scope;
+        self.per_display = config.per_display.clone();
         self.rebuild_app_rule_cache();
 
         let target_count = self.default_workspace_count.max(1).min(self.max_workspaces);
         self.default_workspace = config.default_workspace.min(target_count - 1);
 
-        for (space, ids) in self.workspaces_by_space.iter_mut() {
-            while ids.len() < target_count {
-                let idx = ids.len();
-                let name = if let Some(n) = self.default_workspace_names.get(idx) {
-                    n.clone()
-                } else {
-                    let name = format!("Workspace {}", self.workspace_counter);
-                    self.workspace_counter += 1;
-                    name
-                };
-                let ws = VirtualWorkspace::new(name, *space);
-                let id = self.workspaces.insert(ws);
-                ids.push(id);
+        let mut removed = Vec::new();
+        for (space, old_definition) in previous {
+            for id in self.reconcile_space(space, &old_definition) {
+                removed.push((space, id));
             }
         }
+        removed
+    }
+
+    /// Records which display a space belongs to so that per-display workspace
+    /// overrides apply to it. Returns any workspaces dropped while reconciling
+    /// an already-initialized space with its new definition.
+    pub fn set_space_display(
+        &mut self,
+        space: SpaceId,
+        display_uuid: Option<&str>,
+    ) -> Vec<VirtualWorkspaceId> {
+        if self.space_displays.get(&space).map(String::as_str) == display_uuid {
+            return Vec::new();
+        }
+        let old_definition = self.definition_for_space(space);
+        match display_uuid {
+            Some(uuid) => {
+                self.space_displays.insert(space, uuid.to_string());
+            }
+            None => {
+                self.space_displays.remove(&space);
+            }
+        }
+        if !self.workspaces_by_space.contains_key(&space) {
+            return Vec::new();
+        }
+        self.reconcile_space(space, &old_definition)
+    }
+
+    fn definition_for_space(&self, space: SpaceId) -> WorkspaceDefinition {
+        let overrides = self.space_displays.get(&space).and_then(|uuid| self.per_display.get(uuid));
+        let count = overrides
+            .and_then(|o| o.default_workspace_count)
+            .unwrap_or(self.default_workspace_count)
+            .max(1)
+            .min(self.max_workspaces);
+        let names = overrides
+            .and_then(|o| o.workspace_names.clone())
+            .unwrap_or_else(|| self.default_workspace_names.clone());
+        let default_workspace =
+            overrides.and_then(|o| o.default_workspace).unwrap_or(self.default_workspace);
+        WorkspaceDefinition {
+            count,
+            names,
+            default_workspace: default_workspace.min(count - 1),
+        }
+    }
+
+    /// Brings a space's workspaces in line with its current definition.
+    /// Workspaces that still carry the name the old definition gave them are
+    /// renamed, missing ones are created and surplus ones are dropped from the
+    /// end as long as they are empty and inactive.
+    fn reconcile_space(
+        &mut self,
+        space: SpaceId,
+        old_definition: &WorkspaceDefinition,
+    ) -> Vec<VirtualWorkspaceId> {
+        let definition = self.definition_for_space(space);
+        let Some(mut ids) = self.workspaces_by_space.get(&space).cloned() else {
+            return Vec::new();
+        };
+
+        for (idx, &id) in ids.iter().enumerate().take(definition.count) {
+            if let (Some(old_name), Some(new_name)) =
+                (old_definition.names.get(idx), definition.names.get(idx))
+                && old_name != new_name
+                && let Some(workspace) = self.workspaces.get_mut(id)
+                && workspace.name == *old_name
+            {
+                workspace.name = new_name.clone();
+            }
+        }
+
+        while ids.len() < definition.count {
+            let idx = ids.len();
+            let name = if let Some(n) = definition.names.get(idx) {
+                n.clone()
+            } else {
+                let name = format!("Workspace {}", self.workspace_counter);
+                self.workspace_counter += 1;
+                name
+            };
+            let ws = VirtualWorkspace::new(name, space);
+            let id = self.workspaces.insert(ws);
+            ids.push(id);
+        }
+
+        let active = self.active_workspace(space);
+        let mut removed = Vec::new();
+        while ids.len() > definition.count {
+            let Some(&id) = ids.last() else { break };
+            let in_use = self.workspaces.get(id).is_some_and(|ws| ws.window_count() > 0);
+            if in_use || active == Some(id) {
+                break;
+            }
+            ids.pop();
+            self.workspaces.remove(id);
+            self.floating_positions.remove(&(space, id));
+            removed.push(id);
+        }
+        if let Some((last, _)) = self.active_workspace_per_space.get_mut(&space)
+            && last.is_some_and(|id| removed.contains(&id))
+        {
+            *last = None;
+        }
+
+        self.workspaces_by_space.insert(space, ids);
+        removed
     }
 
     fn rebuild_app_rule_cache(&mut self) {
@@ -236,28 +469,7 @@ impl VirtualWorkspaceManager {
         self.app_rules_by_bundle_id.clear();
 
         for (idx, rule) in self.app_rules.iter().enumerate() {
-            let compiled_title_regex = rule.title_regex.as_ref().and_then(|re| {
-                regex::RegexBuilder::new(re)
-                    .case_insensitive(true)
-                    .build()
-                    .map_err(|e| warn!("Invalid title_regex '{}' in app rule: {}", re, e))
-                    .ok()
-            });
-
-            let compiled_substring_regex = rule.title_substring.as_ref().and_then(|sub| {
-                let escaped = regex::escape(sub);
-                let pattern = format!("(?i).*{}.*", escaped);
-                regex::RegexBuilder::new(&pattern)
-                    .build()
-                    .map_err(|e| warn!("Invalid title_substring '{}' in app rule: {}", sub, e))
-                    .ok()
-            });
-
-            self.cached_app_rules.push(CachedAppRule {
-                rule: rule.clone(),
-                compiled_title_regex,
-                compiled_substring_regex,
-            });
+            self.cached_app_rules.push(CachedAppRule::compile(rule));
 
             if let Some(ref bundle_id) = rule.app_id
                 && !bundle_id.is_empty()
@@ -265,6 +477,22 @@ impl VirtualWorkspaceManager {
                 self.app_rules_by_bundle_id.insert(bundle_id.to_lowercase(), idx);
             }
         }
+
+        self.display_app_rules = self
+            .per_display
+            .iter()
+            .filter_map(|(uuid, overrides)| {
+                let rules = overrides.app_rules.as_ref()?;
+                Some((uuid.clone(), rules.iter().map(CachedAppRule::compile).collect()))
+            })
+            .collect();
+    }
+
+    fn app_rules_for_space(&self, space: SpaceId) -> &[CachedAppRule] {
+        self.space_displays
+            .get(&space)
+            .and_then(|uuid| self.display_app_rules.get(uuid))
+            .unwrap_or(&self.cached_app_rules)
     }
 
     pub(crate) fn ensure_space_initialized(
@@ -276,11 +504,11 @@ impl VirtualWorkspaceManager {
             return;
         }
 
+        let definition = self.definition_for_space(space);
         let mut ids = Vec::new();
-        let count = self.default_workspace_count.max(1).min(self.max_workspaces);
-        for i in 0..count {
-            let name = self
-                .default_workspace_names
+        for i in 0..definition.count {
+            let name = definition
+                .names
                 .get(i)
                 .cloned()
                 .unwrap_or_else(|| format!("Workspace {}", i + 1));
@@ -291,7 +519,7 @@ impl VirtualWorkspaceManager {
         self.workspaces_by_space.insert(space, ids.clone());
 
         let active_id = target_workspace_id.or_else(|| {
-            let default_idx = self.default_workspace.min(ids.len() - 1);
+            let default_idx = definition.default_workspace.min(ids.len() - 1);
             ids.get(default_idx).copied()
         });
 
@@ -332,6 +560,10 @@ impl VirtualWorkspaceManager {
             self.active_workspace_per_space.insert(new_space, (last, active));
         }
 
+        if let Some(uuid) = self.space_displays.remove(&old_space) {
+            self.space_displays.insert(new_space, uuid);
+        }
+
         let mut new_window_to_workspace = HashMap::default();
         for ((space, wid), ws_id) in std::mem::take(&mut self.window_to_workspace) {
             if space == new_space && old_space != new_space {
@@ -364,6 +596,12 @@ impl VirtualWorkspaceManager {
         }
         self.last_rule_decision = new_last_rule_decision;
 
+        self.placed_by_hand = std::mem::take(&mut self.placed_by_hand)
+            .into_iter()
+            .filter(|&(space, _)| !(space == new_space && old_space != new_space))
+            .map(|(space, wid)| (if space == old_space { new_space } else { space }, wid))
+            .collect();
+
         let mut new_positions = HashMap::default();
         for ((space, ws_id), positions) in std::mem::take(&mut self.floating_positions) {
             if space == new_space && old_space != new_space {
@@ -402,6 +640,32 @@ impl VirtualWorkspaceManager {
         Ok(workspace_id)
     }
 
+    /// The workspace at `index` on `space`, creating workspaces up to it when
+    /// the space has fewer. `None` if that would exceed the workspace limit.
+    pub fn workspace_at_index_or_create(
+        &mut self,
+        space: SpaceId,
+        index: usize,
+    ) -> Option<VirtualWorkspaceId> {
+        self.ensure_space_initialized(space, None);
+        loop {
+            if let Some(&id) = self.workspaces_by_space.get(&space)?.get(index) {
+                return Some(id);
+            }
+            self.create_workspace(space, None).ok()?;
+        }
+    }
+
+    /// The workspace at `index` on `space`, without initializing the space.
+    pub fn workspace_at_index(&self, space: SpaceId, index: usize) -> Option<VirtualWorkspaceId> {
+        self.workspaces_by_space.get(&space)?.get(index).copied()
+    }
+
+    /// The position of `workspace` among the workspaces of `space`.
+    pub fn workspace_index(&self, space: SpaceId, workspace: VirtualWorkspaceId) -> Option<usize> {
+        self.workspaces_by_space.get(&space)?.iter().position(|&id| id == workspace)
+    }
+
     pub fn last_workspace(&self, space: SpaceId) -> Option<VirtualWorkspaceId> {
         self.active_workspace_per_space.get(&space)?.0
     }
@@ -424,6 +688,18 @@ impl VirtualWorkspaceManager {
         self.workspace_auto_back_and_forth
     }
 
+    pub fn on_empty(&self) -> OnEmptyWorkspace {
+        self.on_empty
+    }
+
+    pub fn move_follows_window(&self) -> bool {
+        self.move_follows_window
+    }
+
+    pub fn scope(&self) -> WorkspaceScope {
+        self.scope
+    }
+
     pub fn set_active_workspace(
         &mut self,
         space: SpaceId,
@@ -593,6 +869,52 @@ impl VirtualWorkspaceManager {
         self.last_rule_decision.insert((space, window_id), value);
     }
 
+    /// Whether app rules floated `window_id` the last time they were applied.
+    pub fn last_rule_decision(&self, space: SpaceId, window_id: WindowId) -> bool {
+        self.last_rule_decision.get(&(space, window_id)).copied().unwrap_or(false)
+    }
+
+    /// Records that the user moved or (un)floated `window_id` themselves, or
+    /// with `false` that app rules placed it again.
+    pub fn set_placed_by_hand(&mut self, space: SpaceId, window_id: WindowId, value: bool) {
+        if value {
+            self.placed_by_hand.insert((space, window_id));
+        } else {
+            self.placed_by_hand.remove(&(space, window_id));
+        }
+    }
+
+    pub fn is_placed_by_hand(&self, space: SpaceId, window_id: WindowId) -> bool {
+        self.placed_by_hand.contains(&(space, window_id))
+    }
+
+    /// Makes every window of `bundle_id` float (or stops doing so) the next
+    /// time app rules are evaluated for it, regardless of the configured rules.
+    pub fn set_app_always_floating(&mut self, bundle_id: String, floating: bool) {
+        if floating {
+            self.always_floating_apps.insert(bundle_id);
+        } else {
+            self.always_floating_apps.remove(&bundle_id);
+        }
+    }
+
+    pub fn is_app_always_floating(&self, bundle_id: &str) -> bool {
+        self.always_floating_apps.contains(bundle_id)
+    }
+
+    /// Adopts `window_id` into the layout (`Some(true)`) or keeps it out
+    /// (`Some(false)`) whatever the app rules say, or goes back to the rules.
+    pub fn set_manage_override(&mut self, window_id: WindowId, managed: Option<bool>) {
+        match managed {
+            Some(managed) => self.manage_overrides.insert(window_id, managed),
+            None => self.manage_overrides.remove(&window_id),
+        };
+    }
+
+    pub fn manage_override(&self, window_id: WindowId) -> Option<bool> {
+        self.manage_overrides.get(&window_id).copied()
+    }
+
     pub fn remove_window(&mut self, window_id: WindowId) {
         let keys: Vec<(SpaceId, WindowId)> = self
             .window_to_workspace
@@ -607,11 +929,13 @@ impl VirtualWorkspaceManager {
                 }
                 self.window_rule_floating.remove(&(space, wid));
                 self.last_rule_decision.remove(&(space, wid));
+                self.placed_by_hand.remove(&(space, wid));
             }
         }
     }
 
     pub fn remove_windows_for_app(&mut self, pid: pid_t) {
+        self.manage_overrides.retain(|wid, _| wid.pid != pid);
         let windows_to_remove: Vec<_> = self
             .window_to_workspace
             .keys()
@@ -631,6 +955,7 @@ impl VirtualWorkspaceManager {
                 }
                 self.window_rule_floating.remove(&(space, window_id));
                 self.last_rule_decision.remove(&(space, window_id));
+                self.placed_by_hand.remove(&(space, window_id));
             }
         }
     }
@@ -910,6 +1235,48 @@ impl VirtualWorkspaceManager {
         }
     }
 
+    /// Removes `workspace_id` from `space`. Only an empty, inactive workspace
+    /// is removed, and never the last one of a space.
+    pub fn remove_workspace(&mut self, space: SpaceId, workspace_id: VirtualWorkspaceId) -> bool {
+        let Some(ids) = self.workspaces_by_space.get(&space) else {
+            return false;
+        };
+        let Some(pos) = ids.iter().position(|&id| id == workspace_id) else {
+            return false;
+        };
+        let in_use = self.workspaces.get(workspace_id).is_some_and(|ws| ws.window_count() > 0);
+        if in_use || ids.len() == 1 || self.active_workspace(space) == Some(workspace_id) {
+            return false;
+        }
+
+        if let Some(ids) = self.workspaces_by_space.get_mut(&space) {
+            ids.remove(pos);
+        }
+        self.workspaces.remove(workspace_id);
+        self.floating_positions.remove(&(space, workspace_id));
+        if let Some((last, _)) = self.active_workspace_per_space.get_mut(&space)
+            && *last == Some(workspace_id)
+        {
+            *last = None;
+        }
+        true
+    }
+
+    /// Moves the workspace at index `from` of `space` to index `to`, shifting
+    /// the ones in between.
+    pub fn move_workspace(&mut self, space: SpaceId, from: usize, to: usize) -> bool {
+        self.ensure_space_initialized(space, None);
+        let Some(ids) = self.workspaces_by_space.get_mut(&space) else {
+            return false;
+        };
+        if from == to || from >= ids.len() || to >= ids.len() {
+            return false;
+        }
+        let id = ids.remove(from);
+        ids.insert(to, id);
+        true
+    }
+
     pub fn workspace_windows(
         &self,
         space: SpaceId,
@@ -957,11 +1324,27 @@ impl VirtualWorkspaceManager {
             return Err(WorkspaceError::NoWorkspacesAvailable);
         }
 
+        let manage_override = self.manage_override(window_id);
+        if manage_override == Some(false) {
+            self.window_rule_floating.remove(&(space, window_id));
+            return Ok(AppRuleResult::Unmanaged);
+        }
+
         let rule_match = self
-            .find_matching_app_rule(app_bundle_id, app_name, window_title, ax_role, ax_subrole)
+            .find_matching_app_rule(
+                space,
+                app_bundle_id,
+                app_name,
+                window_title,
+                ax_role,
+                ax_subrole,
+            )
+            .filter(|rule| rule.manage || manage_override != Some(true))
             .cloned();
 
         let existing_assignment = self.window_to_workspace.get(&(space, window_id)).copied();
+        let always_floating =
+            app_bundle_id.is_some_and(|bundle_id| self.always_floating_apps.contains(bundle_id));
 
         if let Some(rule) = rule_match {
             if !rule.manage {
@@ -969,101 +1352,77 @@ impl VirtualWorkspaceManager {
                 return Ok(AppRuleResult::Unmanaged);
             }
 
-            let target_workspace_id = if let Some(ref ws_sel) = rule.workspace {
-                let maybe_idx: Option<usize> = match ws_sel {
-                    WorkspaceSelector::Index(i) => Some(*i),
-                    WorkspaceSelector::Name(name) => {
-                        let workspaces = self.list_workspaces(space);
-                        match workspaces.iter().position(|(_, n)| n == name) {
-                            Some(idx) => Some(idx),
-                            None => {
-                                tracing::warn!(
-                                    "App rule references workspace name '{}' which could not be resolved for space {:?}; falling back to default workspace",
-                                    name,
-                                    space
-                                );
-                                None
-                            }
-                        }
-                    }
-                };
-
-                if let Some(workspace_idx) = maybe_idx {
-                    let len = self.workspaces_by_space.get(&space).map(|v| v.len()).unwrap_or(0);
-                    if workspace_idx >= len {
-                        tracing::warn!(
-                            "App rule references non-existent workspace index {}, falling back to active workspace",
-                            workspace_idx
-                        );
-                        self.get_default_workspace(space)?
-                    } else {
-                        let workspaces = self.list_workspaces(space);
-                        if let Some((workspace_id, _)) = workspaces.get(workspace_idx) {
-                            *workspace_id
-                        } else {
-                            tracing::warn!(
-                                "App rule references invalid workspace index {}, falling back to active workspace",
-                                workspace_idx
-                            );
-                            self.get_default_workspace(space)?
-                        }
-                    }
-                } else if let Some(existing_ws) = existing_assignment {
-                    existing_ws
-                } else {
-                    self.get_default_workspace(space)?
-                }
-            } else if let Some(existing_ws) = existing_assignment {
-                existing_ws
-            } else {
-                self.get_default_workspace(space)?
+            let rule_workspace = match &rule.workspace {
+                Some(selector) => self.rule_workspace(space, selector)?,
+                None => None,
+            };
+            let target_workspace_id = match rule_workspace.or(existing_assignment) {
+                Some(workspace_id) => workspace_id,
+                None => self.get_default_workspace(space)?,
             };
 
+            let floating = rule.floating || always_floating;
             if let Some(existing_ws) = existing_assignment {
-                if rule.floating {
+                if floating {
                     self.window_rule_floating.insert((space, window_id), true);
                 } else {
                     self.window_rule_floating.remove(&(space, window_id));
                 }
                 return Ok(AppRuleResult::Managed(AppRuleAssignment {
                     workspace_id: existing_ws,
-                    floating: rule.floating,
+                    by_rule: true,
+                    floating,
                     prev_rule_decision,
+                    opacity: rule.opacity,
+                    always_on_top: rule.always_on_top,
                 }));
             }
 
             if self.assign_window_to_workspace(space, window_id, target_workspace_id) {
-                if rule.floating {
+                if floating {
                     self.window_rule_floating.insert((space, window_id), true);
                 } else {
                     self.window_rule_floating.remove(&(space, window_id));
                 }
                 return Ok(AppRuleResult::Managed(AppRuleAssignment {
                     workspace_id: target_workspace_id,
-                    floating: rule.floating,
+                    by_rule: true,
+                    floating,
                     prev_rule_decision,
+                    opacity: rule.opacity,
+                    always_on_top: rule.always_on_top,
                 }));
             } else {
                 error!("Failed to assign window to workspace from app rule");
             }
         }
 
-        if let Some(existing_ws) = existing_assignment {
+        if always_floating {
+            self.window_rule_floating.insert((space, window_id), true);
+        } else {
             self.window_rule_floating.remove(&(space, window_id));
+        }
+
+        if let Some(existing_ws) = existing_assignment {
             return Ok(AppRuleResult::Managed(AppRuleAssignment {
                 workspace_id: existing_ws,
-                floating: false,
+                by_rule: false,
+                floating: always_floating,
                 prev_rule_decision,
+                opacity: None,
+                always_on_top: false,
             }));
         }
 
         let default_workspace_id = self.get_default_workspace(space)?;
         if self.assign_window_to_workspace(space, window_id, default_workspace_id) {
-            self.window_rule_floating.remove(&(space, window_id));
             Ok(AppRuleResult::Managed(AppRuleAssignment {
                 workspace_id: default_workspace_id,
-                floating: false,
+                by_rule: false,
+                floating: always_floating,
                 prev_rule_decision,
+                opacity: None,
+                always_on_top: false,
             }))
         } else {
             error!("Failed to assign window to default workspace");
@@ -1071,6 +1430,109 @@ impl VirtualWorkspaceManager {
         }
     }
 
+    /// Where the app rules would put `window_id` if it appeared now, or `None`
+    /// if they would leave it unmanaged. Records whether the rules float the
+    /// window, but assigns no workspace.
+    pub fn rule_placement(
+        &mut self,
+        space: SpaceId,
+        window_id: WindowId,
+        app_bundle_id: Option<&str>,
+        app_name: Option<&str>,
+        window_title: Option<&str>,
+        ax_role: Option<&str>,
+        ax_subrole: Option<&str>,
+    ) -> Option<RulePlacement> {
+        let manage_override = self.manage_override(window_id);
+        if manage_override == Some(false) {
+            return None;
+        }
+        let rule = self
+            .find_matching_app_rule(
+                space,
+                app_bundle_id,
+                app_name,
+                window_title,
+                ax_role,
+                ax_subrole,
+            )
+            .filter(|rule| rule.manage || manage_override != Some(true))
+            .cloned();
+        let always_floating =
+            app_bundle_id.is_some_and(|bundle_id| self.always_floating_apps.contains(bundle_id));
+        let placement = match rule {
+            None => RulePlacement {
+                workspace: None,
+                floating: always_floating,
+                hints: DisplayHints::default(),
+            },
+            Some(rule) if !rule.manage => return None,
+            Some(rule) => RulePlacement {
+                workspace: rule
+                    .workspace
+                    .as_ref()
+                    .and_then(|selector| self.rule_workspace(space, selector).ok().flatten()),
+                floating: rule.floating || always_floating,
+                hints: DisplayHints {
+                    opacity: rule.opacity,
+                    always_on_top: rule.always_on_top,
+                },
+            },
+        };
+        if placement.floating {
+            self.window_rule_floating.insert((space, window_id), true);
+        } else {
+            self.window_rule_floating.remove(&(space, window_id));
+        }
+        Some(placement)
+    }
+
+    /// The workspace of `space` an app rule's selector names. An index that
+    /// does not exist falls back to the default workspace; a name that does
+    /// not resolve gives `None`, leaving the window where it is.
+    fn rule_workspace(
+        &mut self,
+        space: SpaceId,
+        selector: &WorkspaceSelector,
+    ) -> Result<Option<VirtualWorkspaceId>, WorkspaceError> {
+        let workspace_idx = match selector {
+            WorkspaceSelector::Index(i) => *i,
+            WorkspaceSelector::Name(name) => {
+                let workspaces = self.list_workspaces(space);
+                match workspaces.iter().position(|(_, n)| n == name) {
+                    Some(idx) => idx,
+                    None => {
+                        tracing::warn!(
+                            "App rule references workspace name '{}' which could not be resolved for space {:?}; falling back to default workspace",
+                            name,
+                            space
+                        );
+                        return Ok(None);
+                    }
+                }
+            }
+        };
+
+        let len = self.workspaces_by_space.get(&space).map(|v| v.len()).unwrap_or(0);
+        if workspace_idx >= len {
+            tracing::warn!(
+                "App rule references non-existent workspace index {}, falling back to active workspace",
+                workspace_idx
+            );
+            return self.get_default_workspace(space).map(Some);
+        }
+        let workspaces = self.list_workspaces(space);
+        if let Some((workspace_id, _)) = workspaces.get(workspace_idx) {
+            Ok(Some(*workspace_id))
+        } else {
+            tracing::warn!(
+                "App rule references invalid workspace index {}, falling back to active workspace",
+                workspace_idx
+            );
+            self.get_default_workspace(space).map(Some)
+        }
+    }
+
     fn get_default_workspace(
         &mut self,
         space: SpaceId,
@@ -1102,8 +1564,36 @@ impl VirtualWorkspaceManager {
         }
     }
 
+    /// The display hints of the rule matching a window, without assigning it
+    /// anywhere.
+    pub fn display_hints_for(
+        &self,
+        space: SpaceId,
+        app_bundle_id: Option<&str>,
+        app_name: Option<&str>,
+        window_title: Option<&str>,
+        ax_role: Option<&str>,
+        ax_subrole: Option<&str>,
+    ) -> DisplayHints {
+        self.find_matching_app_rule(
+            space,
+            app_bundle_id,
+            app_name,
+            window_title,
+            ax_role,
+            ax_subrole,
+        )
+        .filter(|rule| rule.manage)
+        .map(|rule| DisplayHints {
+            opacity: rule.opacity,
+            always_on_top: rule.always_on_top,
+        })
+        .unwrap_or_default()
+    }
+
     fn find_matching_app_rule(
         &self,
+        space: SpaceId,
         app_bundle_id: Option<&str>,
         app_name: Option<&str>,
         window_title: Option<&str>,
@@ -1112,7 +1602,7 @@ impl VirtualWorkspaceManager {
     ) -> Option<&AppWorkspaceRule> {
         let mut matches: Vec<(usize, &AppWorkspaceRule, usize)> = Vec::new();
 
-        for (idx, cached_rule) in self.cached_app_rules.iter().enumerate() {
+        for (idx, cached_rule) in self.app_rules_for_space(space).iter().enumerate() {
             let rule = &cached_rule.rule;
 
             if let Some(ref rule_app_id) = rule.app_id {
@@ -1273,8 +1763,14 @@ impl VirtualWorkspaceManager {
     ) -> Option<VirtualWorkspaceId> {
         self.ensure_space_initialized(space, None);
 
-        let rule =
-            self.find_matching_app_rule(app_bundle_id, app_name, window_title, ax_role, ax_subrole);
+        let rule = self.find_matching_app_rule(
+            space,
+            app_bundle_id,
+            app_name,
+            window_title,
+            ax_role,
+            ax_subrole,
+        );
 
         let ws_sel = rule.and_then(|r| r.workspace.clone());
 
@@ -1328,7 +1824,7 @@ impl VirtualWorkspaceManager {
 #[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct FloatingWindowPositions {
-    #[serde_as(as = "HashMap<_, CGRectDef>")]
+    #[serde_as(as = "MapEntries<Same, CGRectDef>")]
     positions: HashMap<WindowId, CGRect>,
 }
 
@@ -1472,6 +1968,68 @@ mod tests {
         assert_eq!(manager.active_workspace(space), Some(ws2_id));
     }
 
+    #[test]
+    fn remove_workspace_only_drops_empty_inactive_workspaces() {
+        let mut manager = VirtualWorkspaceManager::new();
+        let space = SpaceId::new(1);
+        let ws1_id = manager.create_workspace(space, Some("WS1".to_string())).unwrap();
+        let ws2_id = manager.create_workspace(space, Some("WS2".to_string())).unwrap();
+        assert!(manager.set_active_workspace(space, ws1_id));
+        assert!(manager.set_active_workspace(space, ws2_id));
+        assert!(manager.assign_window_to_workspace(space, WindowId::new(1, 1), ws2_id));
+
+        assert!(!manager.remove_workspace(space, ws2_id));
+        assert!(manager.set_active_workspace(space, ws1_id));
+        assert!(
+            !manager.remove_workspace(space, ws2_id),
+            "workspace still has a window"
+        );
+
+        manager.remove_window(WindowId::new(1, 1));
+        assert!(manager.remove_workspace(space, ws2_id));
+        assert!(manager.workspace_info(space, ws2_id).is_none());
+        assert!(manager.list_workspaces(space).iter().all(|(id, _)| *id != ws2_id));
+        assert_eq!(manager.last_workspace(space), None);
+    }
+
+    #[test]
+    fn move_workspace_shifts_the_ones_in_between() {
+        let mut manager = VirtualWorkspaceManager::new();
+        let space = SpaceId::new(1);
+        let before: Vec<_> = manager.list_workspaces(space).into_iter().map(|(id, _)| id).collect();
+        assert!(before.len() >= 3);
+
+        assert!(manager.move_workspace(space, 0, 2));
+        let after: Vec<_> = manager.list_workspaces(space).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(&after[..3], &[before[1], before[2], before[0]]);
+
+        assert!(manager.move_workspace(space, 2, 0));
+        let restored: Vec<_> =
+            manager.list_workspaces(space).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(restored, before);
+
+        assert!(!manager.move_workspace(space, 1, 1));
+        assert!(!manager.move_workspace(space, 0, before.len()));
+    }
+
+    #[test]
+    fn workspace_at_index_is_created_on_demand() {
+        let mut manager = VirtualWorkspaceManager::new();
+        let space = SpaceId::new(1);
+        let count = manager.list_workspaces(space).len();
+
+        let first = manager.workspace_at_index_or_create(space, 0);
+        assert_eq!(first, manager.list_workspaces(space).first().map(|(id, _)| *id));
+        assert_eq!(manager.list_workspaces(space).len(), count);
+
+        let created = manager.workspace_at_index_or_create(space, count + 1);
+        let workspaces = manager.list_workspaces(space);
+        assert_eq!(workspaces.len(), count + 2);
+        assert_eq!(created, Some(workspaces[count + 1].0));
+
+        assert_eq!(manager.workspace_at_index_or_create(space, 32), None);
+    }
+
     #[test]
     fn test_window_visibility() {
         fn is_window_visible(
@@ -1566,6 +2124,8 @@ mod tests {
                 title_substring: None,
                 ax_role: None,
                 ax_subrole: None,
+                opacity: None,
+                always_on_top: false,
             },
             // Match by app_name -> workspace 1
             AppWorkspaceRule {
@@ -1578,6 +2138,8 @@ mod tests {
                 title_substring: None,
                 ax_role: None,
                 ax_subrole: None,
+                opacity: None,
+                always_on_top: false,
             },
             // Title substring -> workspace 0
             AppWorkspaceRule {
@@ -1590,6 +2152,8 @@ mod tests {
                 title_substring: Some("Preferences".into()),
                 ax_role: None,
                 ax_subrole: None,
+                opacity: None,
+                always_on_top: false,
             },
             // Title regex -> workspace 2
             AppWorkspaceRule {
@@ -1602,6 +2166,8 @@ mod tests {
                 title_substring: None,
                 ax_role: None,
                 ax_subrole: None,
+                opacity: None,
+                always_on_top: false,
             },
             // AX role + subrole floating
             AppWorkspaceRule {
@@ -1614,6 +2180,8 @@ mod tests {
                 title_substring: None,
                 ax_role: Some("AXWindow".into()),
                 ax_subrole: Some("AXDialog".into()),
+                opacity: None,
+                always_on_top: false,
             },
             // Workspace by name
             AppWorkspaceRule {
@@ -1626,6 +2194,8 @@ mod tests {
                 title_substring: None,
                 ax_role: None,
                 ax_subrole: None,
+                opacity: None,
+                always_on_top: false,
             },
             // Specificity tie breaking generic vs substring (generic workspace 0, specific workspace 2)
             AppWorkspaceRule {
@@ -1638,6 +2208,8 @@ mod tests {
                 title_substring: None,
                 ax_role: None,
                 ax_subrole: None,
+                opacity: None,
+                always_on_top: false,
             },
             AppWorkspaceRule {
                 app_id: Some("com.example.tie".into()),
@@ -1649,6 +2221,8 @@ mod tests {
                 title_substring: Some("Editor".into()),
                 ax_role: None,
                 ax_subrole: None,
+                opacity: None,
+                always_on_top: false,
             },
             // Reapplication: Bitwarden title becomes floating
             AppWorkspaceRule {
@@ -1661,6 +2235,8 @@ mod tests {
                 title_substring: Some("Bitwarden".into()),
                 ax_role: None,
                 ax_subrole: None,
+                opacity: None,
+                always_on_top: false,
             },
             AppWorkspaceRule {
                 app_id: Some("app.zen-browser.zen".into()),
@@ -1672,6 +2248,8 @@ mod tests {
                 title_substring: None,
                 ax_role: None,
                 ax_subrole: None,
+                opacity: None,
+                always_on_top: false,
             },
             // Workspace override when specific rule matches different workspace + floating
             AppWorkspaceRule {
@@ -1684,6 +2262,8 @@ mod tests {
                 title_substring: None,
                 ax_role: None,
                 ax_subrole: None,
+                opacity: None,
+                always_on_top: false,
             },
             AppWorkspaceRule {
                 app_id: Some("app.zen-browser.zen".into()),
@@ -1695,6 +2275,8 @@ mod tests {
                 title_substring: Some("bitwarden".into()),
                 ax_role: None,
                 ax_subrole: None,
+                opacity: None,
+                always_on_top: false,
             },
         ];
 
@@ -1923,6 +2505,46 @@ mod tests {
         assert_eq!(manager.workspace_for_window(space, window3), Some(ws_id));
     }
 
+    #[test]
+    fn test_manage_overrides_beat_app_rules() {
+        let mut manager = VirtualWorkspaceManager::new_with_rules(vec![AppWorkspaceRule {
+            app_id: Some("com.example.ignored".into()),
+            workspace: None,
+            floating: false,
+            manage: false,
+            app_name: None,
+            title_regex: None,
+            title_substring: None,
+            ax_role: None,
+            ax_subrole: None,
+            opacity: None,
+            always_on_top: false,
+        }]);
+        let space = SpaceId::new(1);
+        let ignored = WindowId::new(100, 1);
+        let other = WindowId::new(200, 1);
+        let apply = |manager: &mut VirtualWorkspaceManager, wid: WindowId, app_id: &str| {
+            manager.assign_window_with_app_info(wid, space, Some(app_id), None, None, None, None)
+        };
+
+        assert!(matches!(
+            apply(&mut manager, ignored, "com.example.ignored"),
+            Ok(AppRuleResult::Unmanaged)
+        ));
+        manager.set_manage_override(ignored, Some(true));
+        expect_managed(apply(&mut manager, ignored, "com.example.ignored"));
+
+        manager.set_manage_override(other, Some(false));
+        assert!(matches!(
+            apply(&mut manager, other, "com.example.other"),
+            Ok(AppRuleResult::Unmanaged)
+        ));
+
+        manager.remove_windows_for_app(100);
+        assert_eq!(manager.manage_override(ignored), None);
+        assert_eq!(manager.manage_override(other), Some(false));
+    }
+
     #[test]
     fn test_workspace_rename() {
         let mut manager = VirtualWorkspaceManager::new();
@@ -2075,6 +2697,45 @@ mod tests {
         assert_eq!(stats.workspace_window_counts.get(&ws_id), Some(&2));
     }
 
+    #[test]
+    fn always_floating_apps_float_without_rules() {
+        let mut manager = VirtualWorkspaceManager::new();
+        let space = SpaceId::new(1);
+        let window = WindowId::new(1, 1);
+        let bundle = Some("com.example.installer");
+
+        manager.set_app_always_floating("com.example.installer".to_string(), true);
+        assert!(assign(&mut manager, window, space, bundle, None, None, None, None).floating);
+        let other = WindowId::new(2, 1);
+        assert!(
+            !assign(
+                &mut manager,
+                other,
+                space,
+                Some("com.example.app"),
+                None,
+                None,
+                None,
+                None
+            )
+            .floating
+        );
+
+        manager.set_app_always_floating("com.example.installer".to_string(), false);
+        assert!(!assign(&mut manager, window, space, bundle, None, None, None, None).floating);
+    }
+
+    #[test]
+    fn always_floating_apps_survive_serialization() {
+        let mut manager = VirtualWorkspaceManager::new();
+        manager.set_app_always_floating("com.example.picker".to_string(), true);
+
+        let restored: VirtualWorkspaceManager =
+            ron::from_str(&ron::ser::to_string(&manager).unwrap()).unwrap();
+        assert!(restored.is_app_always_floating("com.example.picker"));
+        assert!(!restored.is_app_always_floating("com.example.other"));
+    }
+
     #[test]
     fn test_target_workspace_for_app_info() {
         let settings = VirtualWorkspaceSettings {
@@ -2088,6 +2749,8 @@ mod tests {
                 title_substring: None,
                 ax_role: None,
                 ax_subrole: None,
+                opacity: None,
+                always_on_top: false,
             }],
             ..Default::default()
         };
@@ -2150,4 +2813,122 @@ mod tests {
         assert!(!positions.contains_window(window1));
         assert!(positions.contains_window(window2));
     }
+
+    fn per_display_settings() -> VirtualWorkspaceSettings {
+        let mut settings = VirtualWorkspaceSettings {
+            default_workspace_count: 3,
+            workspace_names: vec!["one".to_string()],
+            ..Default::default()
+        };
+        settings.per_display.insert(
+            "external".to_string(),
+            WorkspaceOverride {
+                default_workspace_count: Some(9),
+                workspace_names: Some(vec!["code".to_string(), "web".to_string()]),
+                default_workspace: Some(1),
+                app_rules: Some(vec![AppWorkspaceRule {
+                    app_id: Some("com.example.browser".into()),
+                    workspace: Some(WorkspaceSelector::Name("web".into())),
+                    floating: false,
+                    manage: true,
+                    app_name: None,
+                    title_regex: None,
+                    title_substring: None,
+                    ax_role: None,
+                    ax_subrole: None,
+                    opacity: None,
+                    always_on_top: false,
+                }]),
+            },
+        );
+        settings
+    }
+
+    #[test]
+    fn per_display_definitions_apply_on_first_initialization() {
+        let mut manager = VirtualWorkspaceManager::new_with_config(&per_display_settings());
+        let laptop = SpaceId::new(1);
+        let external = SpaceId::new(2);
+
+        manager.set_space_display(laptop, Some("builtin"));
+        manager.set_space_display(external, Some("external"));
+
+        let laptop_workspaces = manager.list_workspaces(laptop);
+        assert_eq!(laptop_workspaces.len(), 3);
+        assert_eq!(laptop_workspaces[0].1, "one");
+
+        let external_workspaces = manager.list_workspaces(external);
+        assert_eq!(external_workspaces.len(), 9);
+        assert_eq!(external_workspaces[0].1, "code");
+        assert_eq!(external_workspaces[1].1, "web");
+        assert_eq!(
+            manager.active_workspace(external),
+            Some(external_workspaces[1].0)
+        );
+    }
+
+    #[test]
+    fn per_display_app_rules_target_display_workspaces() {
+        let mut manager = VirtualWorkspaceManager::new_with_config(&per_display_settings());
+        let laptop = SpaceId::new(1);
+        let external = SpaceId::new(2);
+        manager.set_space_display(external, Some("external"));
+
+        let window = WindowId::new(1, 1);
+        let browser = Some("com.example.browser");
+        let decision = assign(&mut manager, window, external, browser, None, None, None, None);
+        let web = manager.list_workspaces(external)[1].0;
+        assert_eq!(decision.workspace_id, web);
+
+        // The laptop falls back to the global rules, which have no browser rule.
+        let window = WindowId::new(1, 2);
+        let decision = assign(&mut manager, window, laptop, browser, None, None, None, None);
+        assert_eq!(Some(decision.workspace_id), manager.active_workspace(laptop));
+    }
+
+    #[test]
+    fn display_known_after_initialization_reconciles_space() {
+        let mut manager = VirtualWorkspaceManager::new_with_config(&per_display_settings());
+        let space = SpaceId::new(1);
+
+        let before = manager.list_workspaces(space);
+        assert_eq!(before.len(), 3);
+
+        assert!(manager.set_space_display(space, Some("external")).is_empty());
+        let after = manager.list_workspaces(space);
+        assert_eq!(after.len(), 9);
+        assert_eq!(after[0], (before[0].0, "code".to_string()));
+        assert_eq!(after[2].0, before[2].0);
+    }
+
+    #[test]
+    fn update_settings_reconciles_without_destroying_used_workspaces() {
+        let mut settings = per_display_settings();
+        let mut manager = VirtualWorkspaceManager::new_with_config(&settings);
+        let space = SpaceId::new(1);
+        manager.set_space_display(space, Some("external"));
+        let before = manager.list_workspaces(space);
+
+        let window = WindowId::new(1, 1);
+        assert!(manager.assign_window_to_workspace(space, window, before[6].0));
+        manager.rename_workspace(space, before[1].0, "custom".to_string());
+
+        let overrides = settings.per_display.get_mut("external").unwrap();
+        overrides.default_workspace_count = Some(4);
+        overrides.workspace_names = Some(vec!["dev".to_string(), "browser".to_string()]);
+        let removed = manager.update_settings(&settings);
+
+        let after = manager.list_workspaces(space);
+        // Workspace 6 holds a window, so it and everything before it survives.
+        assert_eq!(after.len(), 7);
+        assert_eq!(removed, vec![(space, before[8].0), (space, before[7].0)]);
+        assert_eq!(after[0], (before[0].0, "dev".to_string()));
+        assert_eq!(after[1], (before[1].0, "custom".to_string()));
+        assert_eq!(manager.workspace_for_window(space, window), Some(before[6].0));
+
+        assert!(manager.assign_window_to_workspace(space, window, before[0].0));
+        let removed = manager.update_settings(&settings);
+        assert_eq!(removed.len(), 3);
+        assert_eq!(manager.list_workspaces(space).len(), 4);
+    }
 }
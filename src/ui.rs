@@ -2,3 +2,4 @@ pub mod border;
 pub mod menu_bar;
 pub mod mission_control;
 pub mod stack_line;
+pub mod stack_picker;
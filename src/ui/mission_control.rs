@@ -3,6 +3,7 @@ use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::{Sender, unbounded};
 use dispatchr::queue;
@@ -10,24 +11,29 @@ use dispatchr::time::Time;
 use objc2::msg_send;
 use objc2::rc::{Retained, autoreleasepool};
 use objc2::runtime::AnyObject;
-use objc2_app_kit::{NSApplication, NSColor, NSPopUpMenuWindowLevel, NSScreen};
+use objc2_app_kit::{
+    NSApplication, NSColor, NSImage, NSPopUpMenuWindowLevel, NSRunningApplication, NSScreen,
+};
 use objc2_core_foundation::{CFRetained, CFString, CFType, CGPoint, CGRect, CGSize};
 use objc2_core_graphics::{
     CGColor, CGContext, CGDisplayBounds, CGEvent, CGEventField, CGEventFlags, CGEventTapOptions,
     CGEventTapProxy, CGEventType,
 };
 use objc2_foundation::MainThreadMarker;
-use objc2_quartz_core::{CALayer, CATextLayer, CATransaction};
+use objc2_quartz_core::{
+    CALayer, CATextLayer, CATransaction, kCAGravityResize, kCAGravityResizeAspect,
+};
 use once_cell::sync::Lazy;
 use parking_lot::{Mutex, RwLock};
-use tracing::info;
+use serde::Serialize;
+use tracing::{debug, info};
 
-use crate::actor::app::WindowId;
+use crate::actor::app::{WindowId, pid_t};
 use crate::common::collections::{HashMap, HashSet, hash_map};
 use crate::common::config::Config;
 use crate::model::server::{WindowData, WorkspaceData};
 use crate::model::virtual_workspace::VirtualWorkspaceId;
-use crate::sys::cgs_window::CgsWindow;
+use crate::sys::cgs_window::{CgsWindow, CgsWindowError};
 use crate::sys::dispatch::DispatchExt;
 use crate::sys::event::current_cursor_location;
 use crate::sys::geometry::CGRectExt;
@@ -46,6 +52,81 @@ unsafe extern "C" {
     fn CGContextScaleCTM(ctx: *mut CGContext, sx: f64, sy: f64);
 }
 
+/// Presents that failed, and overlay windows rebuilt after a retry failed
+/// too. Reported by `rift query metrics`.
+static RENDER_FAILURES: AtomicU64 = AtomicU64::new(0);
+static OVERLAY_REBUILDS: AtomicU64 = AtomicU64::new(0);
+
+/// How often the overlay failed to render since launch.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RenderHealth {
+    pub render_failures: u64,
+    pub overlay_rebuilds: u64,
+}
+
+pub fn render_health() -> RenderHealth {
+    RenderHealth {
+        render_failures: RENDER_FAILURES.load(Ordering::Relaxed),
+        overlay_rebuilds: OVERLAY_REBUILDS.load(Ordering::Relaxed),
+    }
+}
+
+/// Why a frame of the overlay could not be presented.
+#[derive(Debug)]
+enum PresentError {
+    /// `SLWindowContextCreate` gave no context for the window.
+    NoContext,
+    /// The window server rejected a call on the overlay window, which
+    /// usually means the window is gone (seen after display sleep).
+    Window(CgsWindowError),
+}
+
+fn note_render_failure(err: &PresentError) {
+    let failures = RENDER_FAILURES.fetch_add(1, Ordering::Relaxed) + 1;
+    debug!(?err, failures, "Mission control present failed");
+}
+
+/// Renders `root` into the window server window `window_id` and flushes it.
+fn present_layer(window_id: u32, root: &CALayer, size: CGSize) -> Result<(), PresentError> {
+    unsafe {
+        let ctx: *mut CGContext =
+            SLWindowContextCreate(*G_CONNECTION, window_id, core::ptr::null_mut());
+        if ctx.is_null() {
+            return Err(PresentError::NoContext);
+        }
+        let clear = CGRect::new(CGPoint::new(0.0, 0.0), size);
+        CGContextClearRect(ctx, clear);
+        CGContextSaveGState(ctx);
+        CGContextTranslateCTM(ctx, 0.0, size.height);
+        CGContextScaleCTM(ctx, 1.0, -1.0);
+        root.renderInContext(&*ctx);
+        CGContextRestoreGState(ctx);
+        CGContextFlush(ctx);
+        SLSFlushWindowContentRegion(*G_CONNECTION, window_id, std::ptr::null_mut());
+        CFRelease(ctx as *mut CFType);
+    }
+    Ok(())
+}
+
+fn new_overlay_window(frame: CGRect, scale: f64) -> Result<CgsWindow, CgsWindowError> {
+    let cgs_window = CgsWindow::new(frame)?;
+    let _ = cgs_window.set_resolution(scale);
+    let _ = cgs_window.set_opacity(false);
+    let _ = cgs_window.set_alpha(1.0);
+    let _ = cgs_window.set_level(NSPopUpMenuWindowLevel as i32);
+    let _ = cgs_window.set_blur(30, None);
+    Ok(cgs_window)
+}
+
+fn new_root_layer(size: CGSize, scale: f64) -> Retained<CALayer> {
+    let root_layer = CALayer::layer();
+    root_layer.setGeometryFlipped(true);
+
+    root_layer.setFrame(CGRect::new(CGPoint::new(0.0, 0.0), size));
+    root_layer.setContentsScale(scale);
+    root_layer
+}
+
 #[derive(Debug, Clone)]
 struct CaptureTask {
     window_id: WindowId,
@@ -126,37 +207,34 @@ extern "C" fn refresh_coalesced_cb(ctx: *mut c_void) {
     overlay.refresh_previews();
 }
 
-struct FadeCompletionCtx {
+struct FadeTickCtx {
     overlay_ptr_bits: usize,
     fade_id: u64,
-    final_alpha: f32,
 }
 
-extern "C" fn fade_completion_callback(ctx: *mut c_void) {
+extern "C" fn fade_tick_callback(ctx: *mut c_void) {
     if ctx.is_null() {
         return;
     }
     unsafe {
-        let boxed = Box::from_raw(ctx as *mut FadeCompletionCtx);
+        let boxed = Box::from_raw(ctx as *mut FadeTickCtx);
         if boxed.overlay_ptr_bits == 0 {
             return;
         }
         if let Some(overlay) = (boxed.overlay_ptr_bits as *const MissionControlOverlay).as_ref() {
-            overlay.finish_fade(boxed.fade_id, boxed.final_alpha);
+            overlay.fade_tick(boxed.fade_id);
         }
     }
 }
 
-fn schedule_fade_completion(overlay_ptr_bits: usize, fade_id: u64, final_alpha: f32) {
+fn schedule_fade_tick(overlay_ptr_bits: usize, fade_id: u64) {
     if overlay_ptr_bits == 0 {
         return;
     }
-    let ctx = Box::into_raw(Box::new(FadeCompletionCtx {
-        overlay_ptr_bits,
-        fade_id,
-        final_alpha,
-    })) as *mut c_void;
-    unsafe { queue::main().after_f(Time::NOW, ctx, fade_completion_callback) };
+    let ctx = Box::into_raw(Box::new(FadeTickCtx { overlay_ptr_bits, fade_id })) as *mut c_void;
+    // About one step per frame.
+    let after = Time::new_after(Time::NOW, 16_000_000);
+    unsafe { queue::main().after_f(after, ctx, fade_tick_callback) };
 }
 
 static WORKSPACE_BACKGROUND_COLOR: Lazy<Retained<CGColor>> =
@@ -174,6 +252,9 @@ static WINDOW_BORDER_COLOR: Lazy<Retained<CGColor>> =
 static OVERLAY_BACKGROUND_COLOR: Lazy<Retained<CGColor>> =
     Lazy::new(|| CGColor::new_generic_gray(0.0, 0.25).into());
 
+static BADGE_BACKGROUND_COLOR: Lazy<Retained<CGColor>> =
+    Lazy::new(|| CGColor::new_generic_gray(0.0, 0.55).into());
+
 #[derive(Debug, Clone)]
 pub enum MissionControlMode {
     AllWorkspaces(Vec<WorkspaceData>),
@@ -183,6 +264,11 @@ pub enum MissionControlMode {
 #[derive(Debug, Clone)]
 pub enum MissionControlAction {
     SwitchToWorkspace(usize),
+    /// Move the workspace at index `from` to index `to`.
+    ReorderWorkspace {
+        from: usize,
+        to: usize,
+    },
     FocusWindow {
         window_id: WindowId,
         window_server_id: Option<WindowServerId>,
@@ -239,17 +325,41 @@ impl PreviewLayerStyle {
     }
 }
 
+/// Last selection made in each mission control mode, identified by workspace
+/// id or window id so it can be matched against a freshly queried payload.
+/// Owned by the actor so it outlives the overlay between shows.
+#[derive(Debug, Default)]
+pub struct SelectionMemory {
+    workspace: Option<String>,
+    window: Option<WindowId>,
+}
+
 pub struct MissionControlState {
     mode: Option<MissionControlMode>,
     on_action: Option<Rc<dyn Fn(MissionControlAction)>>,
     selection: Option<Selection>,
+    remember_selection: bool,
+    selection_memory: Rc<RefCell<SelectionMemory>>,
+    /// Workspace to select once the next workspace list arrives, set while a
+    /// reorder is on its way.
+    follow_workspace: Option<String>,
     preview_cache: Arc<RwLock<HashMap<WindowId, CapturedWindowImage>>>,
+    /// Most previews `evict_previews` leaves in `preview_cache`; 0 is no limit.
+    preview_cache_limit: usize,
+    /// The `preview_clock` tick each cached preview was last shown or
+    /// requested at.
+    preview_access: HashMap<WindowId, u64>,
+    preview_clock: u64,
     preview_layers: HashMap<WindowId, Retained<CALayer>>,
     preview_layer_styles: HashMap<WindowId, PreviewLayerStyle>,
     workspace_layers: HashMap<String, Retained<CALayer>>,
     workspace_label_layers: HashMap<String, Retained<CATextLayer>>,
     workspace_label_strings: HashMap<String, WorkspaceLabelText>,
+    workspace_badge_layers: HashMap<String, Retained<CATextLayer>>,
+    workspace_badge_strings: HashMap<String, WorkspaceLabelText>,
     ready_previews: HashSet<WindowId>,
+    /// Stands in for the contents of minimized and hidden windows.
+    app_icons: HashMap<pid_t, Option<Retained<NSImage>>>,
     render_root: Option<Retained<CALayer>>,
     render_window_id: Option<u32>,
     render_size: Option<CGSize>,
@@ -263,13 +373,22 @@ impl Default for MissionControlState {
             mode: None,
             on_action: None,
             selection: None,
+            remember_selection: false,
+            selection_memory: Rc::new(RefCell::new(SelectionMemory::default())),
+            follow_workspace: None,
             preview_cache: Arc::new(RwLock::new(HashMap::default())),
+            preview_cache_limit: 0,
+            preview_access: HashMap::default(),
+            preview_clock: 0,
             preview_layers: HashMap::default(),
             preview_layer_styles: HashMap::default(),
             workspace_layers: HashMap::default(),
             workspace_label_layers: HashMap::default(),
             workspace_label_strings: HashMap::default(),
+            workspace_badge_layers: HashMap::default(),
+            workspace_badge_strings: HashMap::default(),
             ready_previews: HashSet::default(),
+            app_icons: HashMap::default(),
             render_root: None,
             render_window_id: None,
             render_size: None,
@@ -280,11 +399,14 @@ impl Default for MissionControlState {
 
 impl MissionControlState {
     fn set_mode(&mut self, mode: MissionControlMode) {
+        self.remember_current_selection();
         self.mode = Some(mode);
         self.selection = None;
         let _new_gen = CURRENT_GENERATION.fetch_add(1, Ordering::AcqRel) + 1;
         self.ready_previews.clear();
         self.prune_preview_cache();
+        self.restore_remembered_selection();
+        self.select_followed_workspace();
         self.ensure_selection();
     }
 
@@ -293,6 +415,7 @@ impl MissionControlState {
     }
 
     fn purge(&mut self) {
+        self.remember_current_selection();
         self.mode = None;
         self.selection = None;
         self.on_action = None;
@@ -302,12 +425,25 @@ impl MissionControlState {
         let mut cache = self.preview_cache.write();
         cache.clear();
         cache.shrink_to_fit();
+        self.preview_access.clear();
         self.ready_previews.clear();
 
+        self.forget_layers();
+        self.app_icons.clear();
+
+        self.render_root = None;
+        self.render_window_id = None;
+        self.render_size = None;
+    }
+
+    /// Removes every layer drawn so far, so the next draw creates them
+    /// afresh. Cached previews are kept.
+    fn forget_layers(&mut self) {
         for (_id, layer) in self.preview_layers.drain() {
             layer.removeFromSuperlayer();
         }
         self.preview_layer_styles.clear();
+        self.ready_previews.clear();
         for (_id, layer) in self.workspace_layers.drain() {
             layer.removeFromSuperlayer();
         }
@@ -315,10 +451,10 @@ impl MissionControlState {
             layer.removeFromSuperlayer();
         }
         self.workspace_label_strings.clear();
-
-        self.render_root = None;
-        self.render_window_id = None;
-        self.render_size = None;
+        for (_id, layer) in self.workspace_badge_layers.drain() {
+            layer.removeFromSuperlayer();
+        }
+        self.workspace_badge_strings.clear();
     }
 
     fn selection(&self) -> Option<Selection> {
@@ -373,6 +509,73 @@ impl MissionControlState {
         }
     }
 
+    fn remember_current_selection(&self) {
+        if !self.remember_selection {
+            return;
+        }
+        let mut memory = self.selection_memory.borrow_mut();
+        match (self.selection, self.mode.as_ref()) {
+            (
+                Some(Selection::Workspace(idx)),
+                Some(MissionControlMode::AllWorkspaces(workspaces)),
+            ) => {
+                if let Some(ws) =
+                    workspaces.iter().filter(|ws| !ws.windows.is_empty() || ws.is_active).nth(idx)
+                {
+                    memory.workspace = Some(ws.id.clone());
+                }
+            }
+            (Some(Selection::Window(idx)), Some(MissionControlMode::CurrentWorkspace(windows))) => {
+                if let Some(window) = windows.get(idx) {
+                    memory.window = Some(window.id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn restore_remembered_selection(&mut self) {
+        if !self.remember_selection {
+            return;
+        }
+        let memory = self.selection_memory.borrow();
+        self.selection = match self.mode.as_ref() {
+            Some(MissionControlMode::AllWorkspaces(workspaces)) => {
+                memory.workspace.as_deref().and_then(|id| {
+                    workspaces
+                        .iter()
+                        .filter(|ws| !ws.windows.is_empty() || ws.is_active)
+                        .position(|ws| ws.id == id)
+                        .map(Selection::Workspace)
+                })
+            }
+            Some(MissionControlMode::CurrentWorkspace(windows)) => memory
+                .window
+                .and_then(|id| windows.iter().position(|win| win.id == id).map(Selection::Window)),
+            None => None,
+        };
+    }
+
+    fn select_followed_workspace(&mut self) {
+        let Some(MissionControlMode::AllWorkspaces(workspaces)) = self.mode.as_ref() else {
+            return;
+        };
+        // The overlay opens on an empty list before the real one arrives.
+        if workspaces.is_empty() {
+            return;
+        }
+        let Some(id) = self.follow_workspace.take() else {
+            return;
+        };
+        if let Some(idx) = workspaces
+            .iter()
+            .filter(|ws| !ws.windows.is_empty() || ws.is_active)
+            .position(|ws| ws.id == id)
+        {
+            self.selection = Some(Selection::Workspace(idx));
+        }
+    }
+
     fn ensure_selection(&mut self) {
         if self.selection.is_some() {
             return;
@@ -420,6 +623,131 @@ impl MissionControlState {
         }
     }
 
+    fn app_icon(&mut self, pid: pid_t) -> Option<Retained<NSImage>> {
+        self.app_icons
+            .entry(pid)
+            .or_insert_with(|| {
+                NSRunningApplication::runningApplicationWithProcessIdentifier(pid)
+                    .and_then(|app| app.icon())
+            })
+            .clone()
+    }
+
+    /// Captures to start when the overlay opens, most important first.
+    fn prewarm_tasks(&self) -> Vec<(u8, i64, CaptureTask)> {
+        let mut pending = Vec::new();
+        let mut push_window = |window: &WindowData, priority: u8| {
+            if window.is_offscreen() {
+                return;
+            }
+            let Some(wsid) = window.window_server_id else { return };
+
+            let src_w = window.frame.size.width.max(1.0);
+            let src_h = window.frame.size.height.max(1.0);
+
+            let area = (src_w * src_h) as i64;
+            pending.push((
+                priority,
+                area,
+                CaptureTask {
+                    window_id: window.id,
+                    window_server_id: wsid,
+                    target_w: src_w as usize,
+                    target_h: src_h as usize,
+                },
+            ));
+        };
+
+        match self.mode() {
+            Some(MissionControlMode::AllWorkspaces(workspaces)) => {
+                for ws in workspaces {
+                    let workspace_priority = if ws.is_active { 1 } else { 2 };
+                    for window in &ws.windows {
+                        let priority = if window.is_focused {
+                            0
+                        } else {
+                            workspace_priority
+                        };
+                        push_window(window, priority);
+                    }
+                }
+            }
+            Some(MissionControlMode::CurrentWorkspace(wins)) => {
+                for window in wins {
+                    let priority = if window.is_focused { 0 } else { 1 };
+                    push_window(window, priority);
+                }
+            }
+            None => {}
+        }
+
+        pending.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+        pending
+    }
+
+    fn touch_preview(&mut self, window_id: WindowId) {
+        self.preview_clock += 1;
+        self.preview_access.insert(window_id, self.preview_clock);
+    }
+
+    /// Windows whose tiles are on screen: every tile of the current
+    /// workspace, or in the workspace grid those of every workspace drawn.
+    fn on_screen_window_ids(&self) -> HashSet<WindowId> {
+        match self.mode.as_ref() {
+            Some(MissionControlMode::AllWorkspaces(workspaces)) => {
+                MissionControlOverlay::visible_workspaces(workspaces)
+                    .into_iter()
+                    .flat_map(|(_, ws)| ws.windows.iter().map(|w| w.id))
+                    .collect()
+            }
+            Some(MissionControlMode::CurrentWorkspace(windows)) => {
+                windows.iter().map(|w| w.id).collect()
+            }
+            None => HashSet::default(),
+        }
+    }
+
+    /// Which of the `cached` previews to drop, least recently used first, to
+    /// get down to `preview_cache_limit`. Windows whose tiles are on screen
+    /// are never picked, even if that leaves the cache over the limit.
+    fn previews_to_evict(&self, cached: impl IntoIterator<Item = WindowId>) -> Vec<WindowId> {
+        let cached: Vec<WindowId> = cached.into_iter().collect();
+        if self.preview_cache_limit == 0 || cached.len() <= self.preview_cache_limit {
+            return Vec::new();
+        }
+        let excess = cached.len() - self.preview_cache_limit;
+        let on_screen = self.on_screen_window_ids();
+        let mut candidates: Vec<WindowId> =
+            cached.into_iter().filter(|wid| !on_screen.contains(wid)).collect();
+        candidates.sort_by_key(|wid| (self.preview_access.get(wid).copied().unwrap_or(0), *wid));
+        candidates.truncate(excess);
+        candidates
+    }
+
+    /// Drops previews beyond `preview_cache_limit`, clearing them from their
+    /// tiles too so the images are freed. A dropped preview is captured again
+    /// the next time its tile is drawn.
+    fn evict_previews(&mut self) {
+        let evicted = self.previews_to_evict(self.preview_cache.read().keys().copied());
+        if evicted.is_empty() {
+            return;
+        }
+        let mut cache = self.preview_cache.write();
+        for wid in evicted {
+            cache.remove(&wid);
+            self.preview_access.remove(&wid);
+            self.ready_previews.remove(&wid);
+            if let Some(layer) = self.preview_layers.get(&wid) {
+                unsafe {
+                    let _: () = msg_send![
+                        &**layer,
+                        setContents: std::ptr::null_mut::<AnyObject>()
+                    ];
+                }
+            }
+        }
+    }
+
     fn prune_preview_cache(&mut self) {
         let mut cache = self.preview_cache.write();
 
@@ -428,24 +756,28 @@ impl MissionControlState {
         }
 
         let mut valid: HashSet<WindowId> = HashSet::default();
+        let mut capturable: HashSet<WindowId> = HashSet::default();
+        let mut keep = |window: &WindowData| {
+            valid.insert(window.id);
+            if !window.is_offscreen() {
+                capturable.insert(window.id);
+            }
+        };
         if let Some(mode) = self.mode.as_ref() {
             match mode {
                 MissionControlMode::AllWorkspaces(workspaces) => {
                     for ws in workspaces {
-                        for window in &ws.windows {
-                            valid.insert(window.id);
-                        }
+                        ws.windows.iter().for_each(&mut keep);
                     }
                 }
                 MissionControlMode::CurrentWorkspace(windows) => {
-                    for window in windows {
-                        valid.insert(window.id);
-                    }
+                    windows.iter().for_each(&mut keep);
                 }
             }
         }
 
-        cache.retain(|window_id, _| valid.contains(window_id));
+        cache.retain(|window_id, _| capturable.contains(window_id));
+        self.preview_access.retain(|window_id, _| cache.contains_key(window_id));
 
         let mut remove_keys = Vec::new();
         for (&wid, layer) in self.preview_layers.iter() {
@@ -498,6 +830,10 @@ const CURRENT_WS_TILE_SPACING: f64 = 48.0;
 const CURRENT_WS_TILE_PADDING: f64 = 16.0;
 const CURRENT_WS_TILE_SCALE_FACTOR: f64 = 0.9;
 const SYNC_PREWARM_LIMIT: usize = 3;
+const OFFSCREEN_WINDOW_OPACITY: f32 = 0.45;
+/// Scroll events closer together than this in one direction count as one
+/// notch, so a trackpad swipe or accelerated wheel does not skip windows.
+const SCROLL_STEP_INTERVAL: Duration = Duration::from_millis(100);
 
 struct WorkspaceGrid {
     bounds: CGRect,
@@ -580,8 +916,98 @@ impl WindowLayoutMetrics {
     }
 }
 
+/// A window alpha animation in flight.
+#[derive(Debug, Clone, Copy)]
 struct FadeState {
     id: u64,
+    from: f32,
+    to: f32,
+    start: Instant,
+    duration: Duration,
+}
+
+impl FadeState {
+    fn alpha_at(&self, now: Instant) -> f32 {
+        let elapsed = now.saturating_duration_since(self.start).as_secs_f32();
+        let t = (elapsed / self.duration.as_secs_f32()).clamp(0.0, 1.0);
+        self.from + (self.to - self.from) * t
+    }
+}
+
+/// What a fade tick should do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FadeStep {
+    /// The tick belongs to a fade that was cancelled or replaced.
+    Stale,
+    /// Set this alpha and tick again.
+    Running(f32),
+    /// Set this final alpha; the fade is over.
+    Finished(f32),
+}
+
+/// Which fade is running, kept apart from the window so that ticks from
+/// cancelled fades can be told apart from the current one.
+#[derive(Debug, Default)]
+struct Fader {
+    last_id: u64,
+    active: Option<FadeState>,
+}
+
+impl Fader {
+    /// Starts a fade, replacing any running one, and returns its id.
+    fn start(&mut self, from: f32, to: f32, duration: Duration, now: Instant) -> u64 {
+        self.last_id += 1;
+        self.active = Some(FadeState {
+            id: self.last_id,
+            from,
+            to,
+            start: now,
+            duration,
+        });
+        self.last_id
+    }
+
+    /// Cancels the running fade and returns the alpha it had reached.
+    fn cancel(&mut self, now: Instant) -> Option<f32> {
+        self.active.take().map(|fade| fade.alpha_at(now))
+    }
+
+    fn step(&mut self, id: u64, now: Instant) -> FadeStep {
+        let Some(fade) = self.active.filter(|fade| fade.id == id) else {
+            return FadeStep::Stale;
+        };
+        if now.saturating_duration_since(fade.start) >= fade.duration {
+            self.active = None;
+            FadeStep::Finished(fade.to)
+        } else {
+            FadeStep::Running(fade.alpha_at(now))
+        }
+    }
+}
+
+/// Turns scroll wheel deltas into single selection steps.
+#[derive(Debug, Default)]
+struct ScrollStepper {
+    last: Option<(bool, Instant)>,
+}
+
+impl ScrollStepper {
+    /// Whether a scroll of `delta` lines should step the selection, and if
+    /// so, whether forward. Scrolling down steps forward.
+    fn step(&mut self, delta: i64, now: Instant) -> Option<bool> {
+        if delta == 0 {
+            return None;
+        }
+        let forward = delta < 0;
+        if let Some((last_forward, at)) = self.last
+            && last_forward == forward
+            && now.saturating_duration_since(at) < SCROLL_STEP_INTERVAL
+        {
+            return None;
+        }
+        self.last = Some((forward, now));
+        Some(forward)
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -651,23 +1077,44 @@ impl MissionControlOverlay {
         )
     }
 
+    fn distance_to_rect(rect: CGRect, point: CGPoint) -> f64 {
+        let dx = (rect.origin.x - point.x).max(point.x - (rect.origin.x + rect.size.width));
+        let dy = (rect.origin.y - point.y).max(point.y - (rect.origin.y + rect.size.height));
+        dx.max(0.0).hypot(dy.max(0.0))
+    }
+
+    /// The tile under `point`, or else the nearest one at most `slop` away, so
+    /// clicks in the gaps between tiles still land on one. Later tiles are
+    /// drawn on top and win when tiles overlap.
+    fn tile_index_at_point(rects: &[CGRect], point: CGPoint, slop: f64) -> Option<usize> {
+        if let Some(idx) =
+            (0..rects.len()).rev().find(|&idx| Self::rect_contains_point(rects[idx], point))
+        {
+            return Some(idx);
+        }
+        rects
+            .iter()
+            .map(|rect| Self::distance_to_rect(*rect, point))
+            .enumerate()
+            .filter(|&(_, distance)| distance <= slop)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(idx, _)| idx)
+    }
+
     fn workspace_index_at_point(
         workspaces: &[WorkspaceData],
         point: CGPoint,
         bounds: CGRect,
+        slop: f64,
     ) -> Option<(usize, usize)> {
         if !Self::rect_contains_point(bounds, point) {
             return None;
         }
         let visible = Self::visible_workspaces(workspaces);
         let grid = WorkspaceGrid::new(visible.len(), bounds)?;
-        for (order_idx, (original_idx, _)) in visible.iter().enumerate() {
-            let rect = grid.rect_for(order_idx);
-            if Self::rect_contains_point(rect, point) {
-                return Some((order_idx, *original_idx));
-            }
-        }
-        None
+        let rects: Vec<CGRect> = (0..visible.len()).map(|idx| grid.rect_for(idx)).collect();
+        let order_idx = Self::tile_index_at_point(&rects, point, slop)?;
+        Some((order_idx, visible[order_idx].0))
     }
 
     fn window_at_point(
@@ -675,20 +1122,37 @@ impl MissionControlOverlay {
         point: CGPoint,
         bounds: CGRect,
         layout: WindowLayoutKind,
+        slop: f64,
     ) -> Option<(usize, WindowId)> {
         if !Self::rect_contains_point(bounds, point) {
             return None;
         }
         let rects = Self::compute_window_rects(windows, bounds, layout)?;
+        let idx = Self::tile_index_at_point(&rects, point, slop)?;
+        Some((idx, windows[idx].id))
+    }
 
-        for idx in (0..windows.len()).rev() {
-            let window = &windows[idx];
-            let rect = rects[idx];
-            if Self::rect_contains_point(rect, point) {
-                return Some((idx, window.id));
+    /// What a click or hover at `point` (overlay coordinates) selects. Clicks
+    /// and hovers share this so the highlight always shows what a click hits.
+    fn selection_at_point(&self, mode: &MissionControlMode, point: CGPoint) -> Option<Selection> {
+        let content_bounds = Self::content_bounds(CGRect::new(
+            CGPoint::new(0.0, 0.0),
+            CGSize::new(self.frame.size.width, self.frame.size.height),
+        ));
+        match mode {
+            MissionControlMode::AllWorkspaces(workspaces) => {
+                Self::workspace_index_at_point(workspaces, point, content_bounds, self.click_slop)
+                    .map(|(order_idx, _)| Selection::Workspace(order_idx))
             }
+            MissionControlMode::CurrentWorkspace(windows) => Self::window_at_point(
+                windows,
+                point,
+                content_bounds,
+                WindowLayoutKind::Exploded,
+                self.click_slop,
+            )
+            .map(|(order_idx, _)| Selection::Window(order_idx)),
         }
-        None
     }
 
     fn compute_window_layout(
@@ -869,29 +1333,24 @@ impl MissionControlOverlay {
         }
     }
 
+    /// The workspace tile `direction` moves to from `current`. With `wrap`
+    /// off, moving past an edge of the grid stays on `current`.
     fn navigate_workspaces(
         visible: &[(usize, &WorkspaceData)],
         current: usize,
         direction: NavDirection,
+        wrap: bool,
     ) -> Option<usize> {
         if visible.is_empty() {
             return None;
         }
         let len = visible.len();
-        let mut idx = current.min(len.saturating_sub(1));
+        let idx = current.min(len.saturating_sub(1));
         let cols = workspace_column_count(len);
         let rows = if len > cols { 2 } else { 1 };
 
         if rows == 1 {
-            match direction {
-                NavDirection::Left | NavDirection::Up => {
-                    idx = (idx + len - 1) % len;
-                }
-                NavDirection::Right | NavDirection::Down => {
-                    idx = (idx + 1) % len;
-                }
-            }
-            return Some(idx);
+            return Some(Self::step_in_row(len, idx, direction, wrap));
         }
 
         let row = idx % rows;
@@ -907,7 +1366,11 @@ impl MissionControlOverlay {
                 let cols_isize = cols as isize;
                 let mut new_col = col as isize;
                 for _ in 0..cols {
-                    new_col = (new_col + delta + cols_isize) % cols_isize;
+                    new_col += delta;
+                    if !wrap && !(0..cols_isize).contains(&new_col) {
+                        break;
+                    }
+                    new_col = new_col.rem_euclid(cols_isize);
                     let candidate = new_col as usize * rows + row;
                     if candidate < len {
                         return Some(candidate);
@@ -918,6 +1381,8 @@ impl MissionControlOverlay {
             NavDirection::Up => {
                 if row == 1 {
                     Some(col * rows)
+                } else if !wrap {
+                    Some(idx)
                 } else {
                     let candidate = col * rows + 1;
                     if candidate < len {
@@ -935,6 +1400,8 @@ impl MissionControlOverlay {
                     } else {
                         Self::nearest_bottom_index(len, rows, col).or(Some(idx))
                     }
+                } else if !wrap {
+                    Some(idx)
                 } else {
                     Some(col * rows)
                 }
@@ -942,21 +1409,28 @@ impl MissionControlOverlay {
         }
     }
 
-    fn navigate_windows(count: usize, current: usize, direction: NavDirection) -> Option<usize> {
+    fn navigate_windows(
+        count: usize,
+        current: usize,
+        direction: NavDirection,
+        wrap: bool,
+    ) -> Option<usize> {
         if count == 0 {
             return None;
         }
-        let len = count;
-        let mut idx = current.min(len.saturating_sub(1));
+        let idx = current.min(count.saturating_sub(1));
+        Some(Self::step_in_row(count, idx, direction, wrap))
+    }
+
+    /// Steps one tile along a single row of `len` tiles, where up and down
+    /// act as left and right.
+    fn step_in_row(len: usize, idx: usize, direction: NavDirection, wrap: bool) -> usize {
         match direction {
-            NavDirection::Left | NavDirection::Up => {
-                idx = (idx + len - 1) % len;
-            }
-            NavDirection::Right | NavDirection::Down => {
-                idx = (idx + 1) % len;
-            }
+            NavDirection::Left | NavDirection::Up if wrap => (idx + len - 1) % len,
+            NavDirection::Left | NavDirection::Up => idx.saturating_sub(1),
+            NavDirection::Right | NavDirection::Down if wrap => (idx + 1) % len,
+            NavDirection::Right | NavDirection::Down => (idx + 1).min(len - 1),
         }
-        Some(idx)
     }
 
     fn nearest_bottom_index(len: usize, rows: usize, target_col: usize) -> Option<usize> {
@@ -996,7 +1470,8 @@ impl MissionControlOverlay {
                     None
                 } else {
                     let idx = idx.min(visible.len().saturating_sub(1));
-                    Self::navigate_workspaces(&visible, idx, direction).map(Selection::Workspace)
+                    Self::navigate_workspaces(&visible, idx, direction, self.wrap_navigation)
+                        .map(Selection::Workspace)
                 }
             }
             (Some(MissionControlMode::CurrentWorkspace(windows)), Some(Selection::Window(idx))) => {
@@ -1004,7 +1479,8 @@ impl MissionControlOverlay {
                     None
                 } else {
                     let idx = idx.min(windows.len().saturating_sub(1));
-                    Self::navigate_windows(windows.len(), idx, direction).map(Selection::Window)
+                    Self::navigate_windows(windows.len(), idx, direction, self.wrap_navigation)
+                        .map(Selection::Window)
                 }
             }
             (Some(MissionControlMode::AllWorkspaces(workspaces)), None) => {
@@ -1033,6 +1509,45 @@ impl MissionControlOverlay {
         false
     }
 
+    /// Asks for the selected workspace to take the place of its neighbor in
+    /// `direction`. The grid is redrawn once the reordered list comes back
+    /// through [`update`](Self::update), with the moved workspace still
+    /// selected.
+    fn reorder_selected_workspace(&self, direction: NavDirection) {
+        let action = {
+            let mut state = match self.state.try_borrow_mut() {
+                Ok(state) => state,
+                Err(_) => return,
+            };
+            state.ensure_selection();
+            let (
+                Some(MissionControlMode::AllWorkspaces(workspaces)),
+                Some(Selection::Workspace(idx)),
+            ) = (state.mode(), state.selection())
+            else {
+                return;
+            };
+            let visible = Self::visible_workspaces(workspaces);
+            if visible.is_empty() {
+                return;
+            }
+            let idx = idx.min(visible.len() - 1);
+            let Some(target) =
+                Self::navigate_workspaces(&visible, idx, direction, self.wrap_navigation)
+                    .filter(|&target| target != idx)
+            else {
+                return;
+            };
+            // Moving between visible slots lands the workspace on the target
+            // slot whichever way it moves, so visible indices stay in step.
+            let (from, moved) = (visible[idx].0, visible[idx].1.id.clone());
+            let to = visible[target].0;
+            state.follow_workspace = Some(moved);
+            MissionControlAction::ReorderWorkspace { from, to }
+        };
+        self.emit_action(action);
+    }
+
     fn cycle_selection(&self, forward: bool) -> bool {
         let mut state = match self.state.try_borrow_mut() {
             Ok(state) => state,
@@ -1208,7 +1723,8 @@ impl MissionControlOverlay {
                 let ws = &workspaces[*original_idx];
                 let rect = grid.rect_for(order_idx);
                 visible_ids.insert(ws.id.clone());
-                let (ws_layer, label_layer) = {
+                let window_count = ws.windows.len();
+                let (ws_layer, label_layer, badge_layer) = {
                     let mut st = state.borrow_mut();
                     let ws_layer = st
                         .workspace_layers
@@ -1246,7 +1762,34 @@ impl MissionControlOverlay {
                             vac.insert(cache);
                         }
                     }
-                    (ws_layer, label_layer)
+                    let badge_layer = st
+                        .workspace_badge_layers
+                        .entry(ws.id.clone())
+                        .or_insert_with(|| {
+                            let tl = CATextLayer::layer();
+                            parent_layer.addSublayer(&tl);
+                            tl.setContentsScale(self.scale);
+                            tl
+                        })
+                        .clone();
+                    let count_text = window_count.to_string();
+                    match st.workspace_badge_strings.entry(ws.id.clone()) {
+                        hash_map::Entry::Occupied(mut occ) => {
+                            if occ.get_mut().update(&count_text) {
+                                unsafe {
+                                    occ.get().apply_to(&badge_layer);
+                                }
+                            }
+                        }
+                        hash_map::Entry::Vacant(vac) => {
+                            let cache = WorkspaceLabelText::new(&count_text);
+                            unsafe {
+                                cache.apply_to(&badge_layer);
+                            }
+                            vac.insert(cache);
+                        }
+                    }
+                    (ws_layer, label_layer, badge_layer)
                 };
                 ws_layer.setFrame(rect);
                 ws_layer.setCornerRadius(6.0);
@@ -1285,6 +1828,26 @@ impl MissionControlOverlay {
                 label_layer.setForegroundColor(Some(&fg.CGColor()));
 
                 label_layer.setZPosition(2.0);
+
+                badge_layer.setHidden(window_count == 0);
+                if window_count > 0 {
+                    let badge_height = 16.0;
+                    let digits = window_count.to_string().len() as f64;
+                    let badge_width = 10.0 + 7.0 * digits;
+                    let badge_frame = CGRect::new(
+                        CGPoint::new(
+                            rect.origin.x + rect.size.width - badge_width - 6.0,
+                            rect.origin.y + 6.0,
+                        ),
+                        CGSize::new(badge_width, badge_height),
+                    );
+                    badge_layer.setFrame(badge_frame);
+                    badge_layer.setFontSize(11.0);
+                    badge_layer.setCornerRadius(badge_height / 2.0);
+                    badge_layer.setBackgroundColor(Some(&**BADGE_BACKGROUND_COLOR));
+                    badge_layer.setForegroundColor(Some(&NSColor::whiteColor().CGColor()));
+                    badge_layer.setZPosition(3.0);
+                }
             });
         }
         CATransaction::commit();
@@ -1308,6 +1871,15 @@ impl MissionControlOverlay {
                 }
             });
             st.workspace_label_strings.retain(|id, _| visible_ids.contains(id));
+            st.workspace_badge_layers.retain(|id, layer| {
+                if visible_ids.contains(id) {
+                    true
+                } else {
+                    layer.removeFromSuperlayer();
+                    false
+                }
+            });
+            st.workspace_badge_strings.retain(|id, _| visible_ids.contains(id));
         }
     }
 
@@ -1360,10 +1932,20 @@ impl MissionControlOverlay {
                             .map(|img| img.as_ptr() as *mut objc2::runtime::AnyObject)
                     };
                     let mut had_image = false;
-                    if let Some(img_ptr) = maybe_img_ptr {
+                    if window.is_offscreen() {
+                        // Minimized and hidden windows cannot be captured, so
+                        // they show their app's icon instead.
+                        if let Some(icon) = s.app_icon(window.id.pid) {
+                            unsafe {
+                                let _: () = msg_send![&**layer, setContents: &*icon];
+                            }
+                        }
+                        had_image = true;
+                    } else if let Some(img_ptr) = maybe_img_ptr {
                         unsafe {
                             let _: () = msg_send![&**layer, setContents: img_ptr];
                         }
+                        s.touch_preview(window.id);
                         s.ready_previews.insert(window.id);
                         had_image = true;
                     } else if s.ready_previews.contains(&window.id) {
@@ -1376,6 +1958,13 @@ impl MissionControlOverlay {
                 layer.setMasksToBounds(true);
                 layer.setCornerRadius(4.0);
                 layer.setContentsScale(self.scale);
+                if window.is_offscreen() {
+                    layer.setOpacity(OFFSCREEN_WINDOW_OPACITY);
+                    layer.setContentsGravity(unsafe { kCAGravityResizeAspect });
+                } else {
+                    layer.setOpacity(1.0);
+                    layer.setContentsGravity(unsafe { kCAGravityResize });
+                }
                 if style_changed {
                     if is_selected {
                         layer.setBorderColor(Some(&**SELECTED_BORDER_COLOR));
@@ -1418,8 +2007,11 @@ impl MissionControlOverlay {
         target_w: usize,
         target_h: usize,
     ) {
+        if window.is_offscreen() {
+            return;
+        }
         let Some(wsid) = window.window_server_id else { return };
-        let st = state.borrow();
+        let mut st = state.borrow_mut();
         if st.ready_previews.contains(&window.id) {
             return;
         }
@@ -1436,6 +2028,7 @@ impl MissionControlOverlay {
                 return;
             }
         }
+        st.touch_preview(window.id);
         let job = CaptureJob {
             task: CaptureTask {
                 window_id: window.id,
@@ -1453,56 +2046,7 @@ impl MissionControlOverlay {
     fn prewarm_previews(&self) {
         let state_cell = &self.state;
 
-        let mut tasks: Vec<(u8, i64, CaptureTask)> = {
-            let mut pending = Vec::new();
-            {
-                let state_ref = state_cell.borrow();
-                let mut push_window = |window: &WindowData, priority: u8| {
-                    let Some(wsid) = window.window_server_id else { return };
-
-                    let src_w = window.frame.size.width.max(1.0);
-                    let src_h = window.frame.size.height.max(1.0);
-
-                    let area = (src_w * src_h) as i64;
-                    pending.push((
-                        priority,
-                        area,
-                        CaptureTask {
-                            window_id: window.id,
-                            window_server_id: wsid,
-                            target_w: src_w as usize,
-                            target_h: src_h as usize,
-                        },
-                    ));
-                };
-
-                match state_ref.mode() {
-                    Some(MissionControlMode::AllWorkspaces(workspaces)) => {
-                        for ws in workspaces {
-                            let workspace_priority = if ws.is_active { 1 } else { 2 };
-                            for window in &ws.windows {
-                                let priority = if window.is_focused {
-                                    0
-                                } else {
-                                    workspace_priority
-                                };
-                                push_window(window, priority);
-                            }
-                        }
-                    }
-                    Some(MissionControlMode::CurrentWorkspace(wins)) => {
-                        for window in wins {
-                            let priority = if window.is_focused { 0 } else { 1 };
-                            push_window(window, priority);
-                        }
-                    }
-                    None => {}
-                }
-            }
-
-            pending.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
-            pending
-        };
+        let mut tasks = state_cell.borrow().prewarm_tasks();
 
         if tasks.is_empty() {
             return;
@@ -1624,34 +2168,28 @@ impl MissionControlOverlay {
 
         CATransaction::commit();
 
+        let mut present_failed = false;
         if !ready_ids.is_empty()
             && let Ok(mut st) = state_cell.try_borrow_mut()
         {
             for wid in ready_ids.iter().copied() {
                 st.ready_previews.insert(wid);
+                st.touch_preview(wid);
             }
+            st.evict_previews();
             if !st.suppress_live_present
                 && let (Some(root), Some(wid), Some(size)) =
                     (st.render_root.clone(), st.render_window_id, st.render_size)
+                && let Err(err) = present_layer(wid, &root, size)
             {
-                unsafe {
-                    let ctx: *mut CGContext =
-                        SLWindowContextCreate(*G_CONNECTION, wid, core::ptr::null_mut());
-                    if !ctx.is_null() {
-                        let clear = CGRect::new(CGPoint::new(0.0, 0.0), size);
-                        CGContextClearRect(ctx, clear);
-                        CGContextSaveGState(ctx);
-                        CGContextTranslateCTM(ctx, 0.0, size.height);
-                        CGContextScaleCTM(ctx, 1.0, -1.0);
-                        root.renderInContext(&*ctx);
-                        CGContextRestoreGState(ctx);
-                        CGContextFlush(ctx);
-                        SLSFlushWindowContentRegion(*G_CONNECTION, wid, std::ptr::null_mut());
-                        CFRelease(ctx as *mut CFType);
-                    }
-                }
+                note_render_failure(&err);
+                present_failed = true;
             }
         }
+        if present_failed {
+            // Goes through the retry and, failing that, the rebuild.
+            self.draw_and_present();
+        }
     }
 
     fn draw_contents_into_layer(&self, bounds: CGRect, parent_layer: &CALayer) {
@@ -1693,17 +2231,21 @@ impl MissionControlOverlay {
 }
 
 pub struct MissionControlOverlay {
-    cgs_window: CgsWindow,
-    root_layer: Retained<CALayer>,
+    /// Replaced, along with `root_layer`, by `rebuild_window` when the window
+    /// server stops accepting the window.
+    cgs_window: RefCell<CgsWindow>,
+    root_layer: RefCell<Retained<CALayer>>,
     frame: CGRect,
     mtm: MainThreadMarker,
     key_tap: RefCell<Option<crate::sys::event_tap::EventTap>>,
     fade_enabled: bool,
     fade_duration_ms: f64,
+    click_slop: f64,
+    wrap_navigation: bool,
     has_shown: RefCell<bool>,
     state: RefCell<MissionControlState>,
-    fade_state: RefCell<Option<FadeState>>,
-    fade_counter: AtomicU64,
+    fader: RefCell<Fader>,
+    scroll: RefCell<ScrollStepper>,
     pending_hide: RefCell<bool>,
     refresh_pending: AtomicBool,
     scale: f64,
@@ -1727,31 +2269,27 @@ impl MissionControlOverlay {
             }
         }
 
-        let root_layer = CALayer::layer();
-        root_layer.setGeometryFlipped(true);
-
-        root_layer.setFrame(CGRect::new(CGPoint::new(0.0, 0.0), frame.size));
-        root_layer.setContentsScale(scale);
-
-        let cgs_window = CgsWindow::new(frame).expect("failed to create CGS window");
-        let _ = cgs_window.set_resolution(scale);
-        let _ = cgs_window.set_opacity(false);
-        let _ = cgs_window.set_alpha(1.0);
-        let _ = cgs_window.set_level(NSPopUpMenuWindowLevel as i32);
-        let _ = cgs_window.set_blur(30, None);
+        let root_layer = new_root_layer(frame.size, scale);
+        let cgs_window = new_overlay_window(frame, scale).expect("failed to create CGS window");
 
         Self {
-            cgs_window,
-            root_layer,
+            cgs_window: RefCell::new(cgs_window),
+            root_layer: RefCell::new(root_layer),
             frame,
             mtm,
             key_tap: RefCell::new(None),
             fade_enabled: config.settings.ui.mission_control.fade_enabled,
             fade_duration_ms: config.settings.ui.mission_control.fade_duration_ms,
+            click_slop: config.settings.ui.mission_control.click_slop,
+            wrap_navigation: config.settings.ui.mission_control.wrap_navigation,
             has_shown: RefCell::new(false),
-            state: RefCell::new(MissionControlState::default()),
-            fade_state: RefCell::new(None),
-            fade_counter: AtomicU64::new(0),
+            state: RefCell::new(MissionControlState {
+                remember_selection: config.settings.ui.mission_control.remember_selection,
+                preview_cache_limit: config.settings.ui.mission_control.preview_cache_limit,
+                ..MissionControlState::default()
+            }),
+            fader: RefCell::new(Fader::default()),
+            scroll: RefCell::new(ScrollStepper::default()),
             pending_hide: RefCell::new(false),
             refresh_pending: AtomicBool::new(false),
             scale,
@@ -1776,6 +2314,10 @@ impl MissionControlOverlay {
         self.state.borrow_mut().on_action = Some(f);
     }
 
+    pub fn set_selection_memory(&self, memory: Rc<RefCell<SelectionMemory>>) {
+        self.state.borrow_mut().selection_memory = memory;
+    }
+
     pub fn set_fade_enabled(&mut self, enabled: bool) {
         self.fade_enabled = enabled;
     }
@@ -1804,7 +2346,8 @@ impl MissionControlOverlay {
     }
 
     pub fn update(&self, mode: MissionControlMode) {
-        self.stop_active_fade();
+        // Reopening during a fade-out fades back in from where it got to.
+        let interrupted_alpha = self.stop_active_fade();
         *self.pending_hide.borrow_mut() = false;
 
         {
@@ -1819,8 +2362,8 @@ impl MissionControlOverlay {
             let scale_changed = (new_scale - self.scale).abs() > f64::EPSILON;
 
             if frame_changed || scale_changed {
-                let _ = self.cgs_window.set_shape(new_frame);
-                let _ = self.cgs_window.set_resolution(new_scale);
+                let _ = self.cgs_window.borrow().set_shape(new_frame);
+                let _ = self.cgs_window.borrow().set_resolution(new_scale);
 
                 unsafe {
                     let me = self as *const _ as *mut MissionControlOverlay;
@@ -1828,8 +2371,9 @@ impl MissionControlOverlay {
                     (*me).scale = new_scale;
                 }
 
-                self.root_layer.setFrame(CGRect::new(CGPoint::new(0.0, 0.0), self.frame.size));
-                self.root_layer.setContentsScale(self.scale);
+                let root_layer = self.root_layer.borrow();
+                root_layer.setFrame(CGRect::new(CGPoint::new(0.0, 0.0), self.frame.size));
+                root_layer.setContentsScale(self.scale);
             }
             unsafe {
                 let me = self as *const _ as *mut MissionControlOverlay;
@@ -1841,20 +2385,25 @@ impl MissionControlOverlay {
             let mut st = self.state.borrow_mut();
             st.set_mode(mode.clone());
 
-            st.render_root = Some(self.root_layer.clone());
-            st.render_window_id = Some(self.cgs_window.id());
+            st.render_root = Some(self.root_layer.borrow().clone());
+            st.render_window_id = Some(self.cgs_window.borrow().id());
             st.render_size = Some(self.frame.size);
 
             st.suppress_live_present = false;
         }
         self.prewarm_previews();
 
-        if self.fade_enabled && !*self.has_shown.borrow() {
-            let _ = self.cgs_window.set_alpha(0.0);
+        let fading_in = self.fade_enabled && !*self.has_shown.borrow();
+        let start_alpha = if fading_in {
+            interrupted_alpha.unwrap_or(0.0)
         } else {
-            let _ = self.cgs_window.set_alpha(1.0);
+            1.0
+        };
+        if let Err(err) = self.show_window(start_alpha) {
+            note_render_failure(&PresentError::Window(err));
+            self.rebuild_window();
+            let _ = self.show_window(start_alpha);
         }
-        let _ = self.cgs_window.order_above(None);
 
         let app = NSApplication::sharedApplication(self.mtm);
         app.activate();
@@ -1862,8 +2411,8 @@ impl MissionControlOverlay {
 
         self.draw_and_present();
 
-        if self.fade_enabled && !*self.has_shown.borrow() {
-            self.fade_in();
+        if fading_in {
+            self.start_fade(start_alpha, 1.0);
         }
         *self.has_shown.borrow_mut() = true;
     }
@@ -1878,7 +2427,8 @@ impl MissionControlOverlay {
 
         if self.fade_enabled && was_shown {
             *self.pending_hide.borrow_mut() = true;
-            if !self.fade_out() {
+            let from = self.stop_active_fade().unwrap_or(1.0);
+            if !self.start_fade(from, 0.0) {
                 self.finalize_hide();
             }
         } else {
@@ -1896,8 +2446,8 @@ impl MissionControlOverlay {
                 s.purge();
             }
 
-            let _ = self.cgs_window.order_out();
-            let _ = self.cgs_window.set_alpha(1.0);
+            let _ = self.cgs_window.borrow().order_out();
+            let _ = self.cgs_window.borrow().set_alpha(1.0);
             CATransaction::flush();
 
             *self.has_shown.borrow_mut() = false;
@@ -1905,85 +2455,50 @@ impl MissionControlOverlay {
         });
     }
 
-    fn fade_in(&self) {
+    /// Animates the window alpha from `from` to `to`, stepping it from the
+    /// main queue so the blur backdrop fades along with the content. Returns
+    /// false, having jumped straight to `to`, when fades take no time.
+    fn start_fade(&self, from: f32, to: f32) -> bool {
         self.stop_active_fade();
         let duration_ms = self.fade_duration_ms.max(0.0);
         if duration_ms <= 0.0 {
-            let _ = self.cgs_window.set_alpha(1.0);
-            return;
-        }
-
-        let fade_id = self.fade_counter.fetch_add(1, Ordering::AcqRel) + 1;
-        let overlay_ptr_bits = self as *const MissionControlOverlay as usize;
-
-        CATransaction::begin();
-        CATransaction::setAnimationDuration(duration_ms / 1000.0);
-        self.root_layer.setOpacity(0.0);
-        self.root_layer.setOpacity(1.0);
-
-        CATransaction::commit();
-
-        schedule_fade_completion(overlay_ptr_bits, fade_id, 1.0f32);
-
-        self.fade_state.borrow_mut().replace(FadeState { id: fade_id });
-    }
-
-    fn fade_out(&self) -> bool {
-        self.stop_active_fade();
-        let duration_ms = self.fade_duration_ms.max(0.0);
-        if duration_ms <= 0.0 {
-            let _ = self.cgs_window.set_alpha(0.0);
+            let _ = self.cgs_window.borrow().set_alpha(to);
             return false;
         }
-
-        let fade_id = self.fade_counter.fetch_add(1, Ordering::AcqRel) + 1;
-        let overlay_ptr_bits = self as *const MissionControlOverlay as usize;
-
-        CATransaction::begin();
-        CATransaction::setAnimationDuration(duration_ms / 1000.0);
-
-        self.root_layer.setOpacity(1.0);
-        self.root_layer.setOpacity(0.0);
-
-        CATransaction::commit();
-
-        schedule_fade_completion(overlay_ptr_bits, fade_id, 0.0f32);
-
-        self.fade_state.borrow_mut().replace(FadeState { id: fade_id });
+        let _ = self.cgs_window.borrow().set_alpha(from);
+        let duration = Duration::from_secs_f64(duration_ms / 1000.0);
+        let fade_id = self.fader.borrow_mut().start(from, to, duration, Instant::now());
+        schedule_fade_tick(self as *const MissionControlOverlay as usize, fade_id);
         true
     }
 
-    fn stop_active_fade(&self) {
-        self.root_layer.removeAllAnimations();
-        self.fade_state.borrow_mut().take();
+    /// Cancels the running fade, returning the alpha it had reached.
+    fn stop_active_fade(&self) -> Option<f32> {
+        self.fader.borrow_mut().cancel(Instant::now())
     }
 
-    fn finish_fade(&self, fade_id: u64, final_alpha: f32) {
-        match self.fade_state.try_borrow_mut() {
-            Ok(mut slot) => {
-                let matches = slot.as_ref().is_some_and(|state| state.id == fade_id);
-                if !matches {
-                    return;
-                }
-                slot.take();
-                drop(slot);
-            }
+    fn fade_tick(&self, fade_id: u64) {
+        let overlay_ptr_bits = self as *const MissionControlOverlay as usize;
+        let step = match self.fader.try_borrow_mut() {
+            Ok(mut fader) => fader.step(fade_id, Instant::now()),
             Err(_) => {
-                let overlay_ptr_bits = self as *const MissionControlOverlay as usize;
-                schedule_fade_completion(overlay_ptr_bits, fade_id, final_alpha);
+                schedule_fade_tick(overlay_ptr_bits, fade_id);
                 return;
             }
-        }
-
-        let _ = self.cgs_window.set_alpha(final_alpha);
-
-        let should_finalize = if final_alpha <= 0.0 {
-            *self.pending_hide.borrow()
-        } else {
-            false
         };
+        match step {
+            FadeStep::Stale => {}
+            FadeStep::Running(alpha) => {
+                let _ = self.cgs_window.borrow().set_alpha(alpha);
+                schedule_fade_tick(overlay_ptr_bits, fade_id);
+            }
+            FadeStep::Finished(alpha) => self.finish_fade(alpha),
+        }
+    }
 
-        if should_finalize {
+    fn finish_fade(&self, final_alpha: f32) {
+        let _ = self.cgs_window.borrow().set_alpha(final_alpha);
+        if final_alpha <= 0.0 && *self.pending_hide.borrow() {
             self.finalize_hide();
         }
     }
@@ -2001,39 +2516,88 @@ impl MissionControlOverlay {
     }
 
     fn draw_and_present(&self) {
+        self.draw();
+        match self.present() {
+            Ok(()) => return,
+            Err(err) => note_render_failure(&err),
+        }
+        // Retry once if the window server still accepts the window.
+        match self.probe_window() {
+            Ok(()) => match self.present() {
+                Ok(()) => return,
+                Err(err) => note_render_failure(&err),
+            },
+            Err(err) => debug!(?err, "Mission control window was rejected"),
+        }
+        // Presenting failed and the window is likely gone. Start over with
+        // a new one, keeping the state.
+        self.rebuild_window();
+        self.draw();
+        if let Err(err) = self.present() {
+            note_render_failure(&err);
+        }
+    }
+
+    fn draw(&self) {
+        let root_layer = self.root_layer.borrow().clone();
         CATransaction::begin();
         CATransaction::setDisableActions(true);
 
-        self.root_layer.setFrame(CGRect::new(CGPoint::new(0.0, 0.0), self.frame.size));
-        self.root_layer.setGeometryFlipped(true);
+        root_layer.setFrame(CGRect::new(CGPoint::new(0.0, 0.0), self.frame.size));
+        root_layer.setGeometryFlipped(true);
 
         self.draw_contents_into_layer(
             CGRect::new(CGPoint::new(0.0, 0.0), self.frame.size),
-            &self.root_layer,
+            &root_layer,
         );
         CATransaction::commit();
+    }
 
-        let ctx: *mut CGContext = unsafe {
-            SLWindowContextCreate(*G_CONNECTION, self.cgs_window.id(), core::ptr::null_mut())
-        };
-        if !ctx.is_null() {
-            unsafe {
-                let clear = CGRect::new(CGPoint::new(0.0, 0.0), self.frame.size);
-                CGContextClearRect(ctx, clear);
-                CGContextSaveGState(ctx);
-                CGContextTranslateCTM(ctx, 0.0, self.frame.size.height);
-                CGContextScaleCTM(ctx, 1.0, -1.0);
-                self.root_layer.renderInContext(&*ctx);
-                CGContextRestoreGState(ctx);
-                CGContextFlush(ctx);
-                SLSFlushWindowContentRegion(
-                    *G_CONNECTION,
-                    self.cgs_window.id(),
-                    std::ptr::null_mut(),
-                );
-                CFRelease(ctx as *mut CFType);
+    /// Presents the root layer into the overlay window.
+    fn present(&self) -> Result<(), PresentError> {
+        let window_id = self.cgs_window.borrow().id();
+        present_layer(window_id, &self.root_layer.borrow(), self.frame.size)
+    }
+
+    /// Checks the window server still accepts the overlay window by setting
+    /// the resolution it already has. That resizes the backing store, so it
+    /// is only done after a present fails.
+    fn probe_window(&self) -> Result<(), PresentError> {
+        self.cgs_window
+            .borrow()
+            .set_resolution(self.scale)
+            .map_err(PresentError::Window)
+    }
+
+    fn show_window(&self, alpha: f32) -> Result<(), CgsWindowError> {
+        let cgs_window = self.cgs_window.borrow();
+        cgs_window.set_alpha(alpha)?;
+        cgs_window.order_above(None)
+    }
+
+    /// Replaces the window and root layer with new ones and shows the new
+    /// window. The state is kept; only its layers are dropped, to be drawn
+    /// again into the new root.
+    fn rebuild_window(&self) {
+        let cgs_window = match new_overlay_window(self.frame, self.scale) {
+            Ok(cgs_window) => cgs_window,
+            Err(err) => {
+                debug!(?err, "Could not rebuild the mission control window");
+                return;
             }
+        };
+        let rebuilds = OVERLAY_REBUILDS.fetch_add(1, Ordering::Relaxed) + 1;
+        debug!(rebuilds, "Rebuilding the mission control window");
+        let _ = cgs_window.order_above(None);
+        let root_layer = new_root_layer(self.frame.size, self.scale);
+        {
+            let mut st = self.state.borrow_mut();
+            st.forget_layers();
+            st.render_root = Some(root_layer.clone());
+            st.render_window_id = Some(cgs_window.id());
         }
+        *self.root_layer.borrow_mut() = root_layer;
+        let _ = self.cgs_window.replace(cgs_window).order_out();
     }
 
     fn emit_action(&self, action: MissionControlAction) {
@@ -2072,6 +2636,16 @@ impl MissionControlOverlay {
                 self.emit_action(MissionControlAction::Dismiss);
                 true
             }
+            123..=126 if flags.contains(CGEventFlags::MaskCommand) => {
+                let direction = match keycode {
+                    123 => NavDirection::Left,
+                    124 => NavDirection::Right,
+                    125 => NavDirection::Down,
+                    _ => NavDirection::Up,
+                };
+                self.reorder_selected_workspace(direction);
+                true
+            }
             123 => {
                 if self.adjust_selection(NavDirection::Left) {
                     self.draw_and_present();
@@ -2124,21 +2698,7 @@ impl MissionControlOverlay {
             Some(m) => m,
             None => return,
         };
-        let content_bounds = Self::content_bounds(CGRect::new(
-            CGPoint::new(0.0, 0.0),
-            CGSize::new(self.frame.size.width, self.frame.size.height),
-        ));
-
-        let new_sel = match mode {
-            MissionControlMode::AllWorkspaces(workspaces) => {
-                Self::workspace_index_at_point(workspaces, pt, content_bounds)
-                    .map(|(order_idx, _)| Selection::Workspace(order_idx))
-            }
-            MissionControlMode::CurrentWorkspace(windows) => {
-                Self::window_at_point(windows, pt, content_bounds, WindowLayoutKind::Exploded)
-                    .map(|(order_idx, _)| Selection::Window(order_idx))
-            }
-        };
+        let new_sel = self.selection_at_point(mode, pt);
 
         match new_sel {
             Some(sel) => {
@@ -2167,21 +2727,7 @@ impl MissionControlOverlay {
             Some(m) => m,
             None => return,
         };
-        let content_bounds = Self::content_bounds(CGRect::new(
-            CGPoint::new(0.0, 0.0),
-            CGSize::new(self.frame.size.width, self.frame.size.height),
-        ));
-
-        let new_sel = match mode {
-            MissionControlMode::AllWorkspaces(workspaces) => {
-                Self::workspace_index_at_point(workspaces, pt, content_bounds)
-                    .map(|(order_idx, _)| Selection::Workspace(order_idx))
-            }
-            MissionControlMode::CurrentWorkspace(windows) => {
-                Self::window_at_point(windows, pt, content_bounds, WindowLayoutKind::Exploded)
-                    .map(|(order_idx, _)| Selection::Window(order_idx))
-            }
-        };
+        let new_sel = self.selection_at_point(mode, pt);
 
         if let Some(sel) = new_sel
             && state.selection() != Some(sel)
@@ -2192,6 +2738,41 @@ impl MissionControlOverlay {
         }
     }
 
+    /// Steps the window selection by one per wheel notch. Only the current
+    /// workspace view scrolls; the workspace grid ignores the wheel.
+    fn handle_scroll_global(&self, delta: i64) -> bool {
+        let mut state = match self.state.try_borrow_mut() {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+        let Some(MissionControlMode::CurrentWorkspace(windows)) = state.mode() else {
+            return false;
+        };
+        let len = windows.len();
+        if len == 0 {
+            return true;
+        }
+        let Some(forward) = self.scroll.borrow_mut().step(delta, Instant::now()) else {
+            return true;
+        };
+        state.ensure_selection();
+        let idx = match state.selection() {
+            Some(Selection::Window(idx)) => idx.min(len - 1),
+            _ => 0,
+        };
+        let new_idx = if forward {
+            (idx + 1).min(len - 1)
+        } else {
+            idx.saturating_sub(1)
+        };
+        if state.selection() != Some(Selection::Window(new_idx)) {
+            state.set_selection(Selection::Window(new_idx));
+            drop(state);
+            self.draw_and_present();
+        }
+        true
+    }
+
     fn ensure_key_tap(&self) {
         if self.key_tap.borrow().is_some() {
             return;
@@ -2242,6 +2823,15 @@ impl MissionControlOverlay {
                         overlay.handle_move_global(loc);
                         handled = true;
                     }
+                    CGEventType::ScrollWheel => {
+                        let delta = unsafe {
+                            CGEvent::integer_value_field(
+                                Some(event.as_ref()),
+                                CGEventField::ScrollWheelEventDeltaAxis1,
+                            )
+                        };
+                        handled = overlay.handle_scroll_global(delta);
+                    }
                     _ => {}
                 }
             }
@@ -2255,7 +2845,8 @@ impl MissionControlOverlay {
         let mask = (1u64 << CGEventType::KeyDown.0 as u64)
             | (1u64 << CGEventType::LeftMouseDown.0 as u64)
             | (1u64 << CGEventType::LeftMouseUp.0 as u64)
-            | (1u64 << CGEventType::MouseMoved.0 as u64);
+            | (1u64 << CGEventType::MouseMoved.0 as u64)
+            | (1u64 << CGEventType::ScrollWheel.0 as u64);
 
         let overlay_ptr = self as *const _;
 
@@ -2304,3 +2895,376 @@ impl MissionControlOverlay {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(idx: u32, focused: bool) -> WindowData {
+        WindowData {
+            id: WindowId::new(1, idx),
+            title: format!("window {idx}"),
+            frame: CGRect::new(CGPoint::new(0.0, 0.0), CGSize::new(100.0, 100.0)),
+            is_floating: false,
+            is_focused: focused,
+            bundle_id: None,
+            window_server_id: None,
+            is_minimized: false,
+            is_hidden: false,
+        }
+    }
+
+    fn workspace(id: &str, index: usize, active: bool, windows: Vec<WindowData>) -> WorkspaceData {
+        WorkspaceData {
+            id: id.to_string(),
+            index,
+            name: id.to_string(),
+            is_active: active,
+            window_count: windows.len(),
+            windows,
+        }
+    }
+
+    fn all_workspaces() -> MissionControlMode {
+        MissionControlMode::AllWorkspaces(vec![
+            workspace("ws1", 0, true, vec![window(1, true)]),
+            workspace("ws2", 1, false, vec![window(2, false)]),
+            workspace("ws3", 2, false, vec![window(3, false)]),
+        ])
+    }
+
+    fn current_workspace() -> MissionControlMode {
+        MissionControlMode::CurrentWorkspace(vec![
+            window(1, true),
+            window(2, false),
+            window(3, false),
+        ])
+    }
+
+    fn state(remember_selection: bool) -> MissionControlState {
+        MissionControlState {
+            remember_selection,
+            ..MissionControlState::default()
+        }
+    }
+
+    #[test]
+    fn restores_remembered_selection_per_mode() {
+        let mut st = state(true);
+
+        st.set_mode(all_workspaces());
+        st.set_selection(Selection::Workspace(2));
+        st.purge();
+
+        st.set_mode(current_workspace());
+        assert_eq!(st.selected_window(), Some(0));
+        st.set_selection(Selection::Window(1));
+        st.purge();
+
+        st.set_mode(all_workspaces());
+        assert_eq!(st.selected_workspace(), Some(2));
+        st.purge();
+
+        st.set_mode(current_workspace());
+        assert_eq!(st.selected_window(), Some(1));
+    }
+
+    #[test]
+    fn restore_follows_identity_when_indices_shift() {
+        let mut st = state(true);
+
+        st.set_mode(all_workspaces());
+        st.set_selection(Selection::Workspace(2));
+        st.purge();
+
+        // ws2 became empty and is no longer listed, so ws3 is now at index 1.
+        st.set_mode(MissionControlMode::AllWorkspaces(vec![
+            workspace("ws1", 0, true, vec![window(1, true)]),
+            workspace("ws2", 1, false, Vec::new()),
+            workspace("ws3", 2, false, vec![window(3, false)]),
+        ]));
+        assert_eq!(st.selected_workspace(), Some(1));
+    }
+
+    #[test]
+    fn falls_back_when_remembered_selection_is_gone() {
+        let mut st = state(true);
+
+        st.set_mode(current_workspace());
+        st.set_selection(Selection::Window(2));
+        st.purge();
+
+        st.set_mode(MissionControlMode::CurrentWorkspace(vec![
+            window(1, false),
+            window(2, true),
+        ]));
+        assert_eq!(st.selected_window(), Some(1));
+
+        st.set_mode(all_workspaces());
+        st.set_selection(Selection::Workspace(1));
+        st.purge();
+
+        st.set_mode(MissionControlMode::AllWorkspaces(vec![
+            workspace("ws1", 0, false, vec![window(1, false)]),
+            workspace("ws3", 2, true, vec![window(3, true)]),
+        ]));
+        assert_eq!(st.selected_workspace(), Some(1));
+    }
+
+    #[test]
+    fn reordered_workspace_stays_selected() {
+        let mut st = state(false);
+
+        st.set_mode(all_workspaces());
+        st.follow_workspace = Some("ws3".to_string());
+        st.set_mode(MissionControlMode::AllWorkspaces(Vec::new()));
+        st.set_mode(MissionControlMode::AllWorkspaces(vec![
+            workspace("ws1", 0, true, vec![window(1, true)]),
+            workspace("ws3", 1, false, vec![window(3, false)]),
+            workspace("ws2", 2, false, vec![window(2, false)]),
+        ]));
+        assert_eq!(st.selected_workspace(), Some(1));
+        assert_eq!(st.follow_workspace, None);
+    }
+
+    #[test]
+    fn placeholder_mode_does_not_clobber_memory() {
+        let mut st = state(true);
+
+        st.set_mode(current_workspace());
+        st.set_selection(Selection::Window(2));
+        st.purge();
+
+        st.set_mode(MissionControlMode::CurrentWorkspace(Vec::new()));
+        assert_eq!(st.selected_window(), None);
+        st.set_mode(current_workspace());
+        assert_eq!(st.selected_window(), Some(2));
+    }
+
+    #[test]
+    fn evicts_least_recently_used_previews_over_the_limit() {
+        let mut st = MissionControlState {
+            preview_cache_limit: 4,
+            ..MissionControlState::default()
+        };
+        st.set_mode(current_workspace());
+        let cached: Vec<_> = (1..=6).map(|idx| WindowId::new(1, idx)).collect();
+        for idx in [6, 4, 5] {
+            st.touch_preview(WindowId::new(1, idx));
+        }
+
+        assert_eq!(
+            st.previews_to_evict(cached.iter().copied()),
+            vec![WindowId::new(1, 6), WindowId::new(1, 4)]
+        );
+        assert!(st.previews_to_evict(cached.iter().copied().take(4)).is_empty());
+
+        st.preview_cache_limit = 0;
+        assert!(st.previews_to_evict(cached.iter().copied()).is_empty());
+    }
+
+    #[test]
+    fn never_evicts_previews_of_tiles_on_screen() {
+        let mut st = MissionControlState {
+            preview_cache_limit: 1,
+            ..MissionControlState::default()
+        };
+        let cached: Vec<_> = (1..=4).map(|idx| WindowId::new(1, idx)).collect();
+
+        // The workspace grid draws every workspace at once, whichever is selected.
+        st.set_mode(all_workspaces());
+        st.set_selection(Selection::Workspace(1));
+        assert_eq!(
+            st.previews_to_evict(cached.iter().copied()),
+            vec![WindowId::new(1, 4)]
+        );
+
+        st.set_mode(current_workspace());
+        assert_eq!(
+            st.previews_to_evict(cached.iter().copied()),
+            vec![WindowId::new(1, 4)]
+        );
+    }
+
+    #[test]
+    fn selection_is_not_remembered_when_disabled() {
+        let mut st = state(false);
+
+        st.set_mode(all_workspaces());
+        st.set_selection(Selection::Workspace(2));
+        st.purge();
+        st.set_mode(all_workspaces());
+        assert_eq!(st.selected_workspace(), Some(0));
+
+        st.set_mode(current_workspace());
+        st.set_selection(Selection::Window(2));
+        st.purge();
+        st.set_mode(current_workspace());
+        assert_eq!(st.selected_window(), Some(0));
+    }
+
+    #[test]
+    fn memory_is_shared_across_states() {
+        let memory = Rc::new(RefCell::new(SelectionMemory::default()));
+
+        let mut first = state(true);
+        first.selection_memory = memory.clone();
+        first.set_mode(current_workspace());
+        first.set_selection(Selection::Window(1));
+        first.purge();
+        drop(first);
+
+        let mut second = state(true);
+        second.selection_memory = memory;
+        second.set_mode(current_workspace());
+        assert_eq!(second.selected_window(), Some(1));
+    }
+
+    #[test]
+    fn offscreen_windows_are_not_captured() {
+        let mut minimized = window(2, false);
+        minimized.is_minimized = true;
+        let mut hidden = window(3, false);
+        hidden.is_hidden = true;
+        let windows: Vec<WindowData> = [window(1, true), minimized, hidden]
+            .into_iter()
+            .map(|mut w| {
+                w.window_server_id = Some(w.id.idx.get());
+                w
+            })
+            .collect();
+
+        let mut st = state(false);
+        st.set_mode(MissionControlMode::CurrentWorkspace(windows));
+        let captured: Vec<WindowId> =
+            st.prewarm_tasks().into_iter().map(|(_, _, task)| task.window_id).collect();
+        assert_eq!(captured, vec![WindowId::new(1, 1)]);
+    }
+
+    #[test]
+    fn clicks_near_a_tile_snap_to_it() {
+        let rects = [
+            CGRect::new(CGPoint::new(0.0, 0.0), CGSize::new(100.0, 100.0)),
+            CGRect::new(CGPoint::new(140.0, 0.0), CGSize::new(100.0, 100.0)),
+        ];
+        let at = |x, y, slop| {
+            MissionControlOverlay::tile_index_at_point(&rects, CGPoint::new(x, y), slop)
+        };
+
+        assert_eq!(at(50.0, 50.0, 24.0), Some(0));
+        assert_eq!(at(110.0, 50.0, 24.0), Some(0));
+        assert_eq!(at(125.0, 50.0, 24.0), Some(1));
+        assert_eq!(at(120.0, 50.0, 0.0), None);
+        assert_eq!(at(50.0, 130.0, 24.0), None);
+        assert_eq!(at(255.0, 110.0, 24.0), Some(1));
+    }
+
+    #[test]
+    fn fade_out_interrupted_by_reopening() {
+        let duration = Duration::from_millis(200);
+        let t0 = Instant::now();
+        let mut fader = Fader::default();
+
+        let fade_out = fader.start(1.0, 0.0, duration, t0);
+        let FadeStep::Running(alpha) = fader.step(fade_out, t0 + Duration::from_millis(50)) else {
+            panic!("fade-out ended early");
+        };
+        assert!((alpha - 0.75).abs() < 1e-6);
+
+        // `update()` cancels the fade-out and fades in from where it stopped.
+        let reached = fader.cancel(t0 + Duration::from_millis(100)).unwrap();
+        assert!((reached - 0.5).abs() < 1e-6);
+        let fade_in = fader.start(reached, 1.0, duration, t0 + Duration::from_millis(100));
+        assert_ne!(fade_in, fade_out);
+
+        // The cancelled fade's pending tick must not finish (and hide) anything.
+        assert_eq!(
+            fader.step(fade_out, t0 + Duration::from_millis(400)),
+            FadeStep::Stale
+        );
+        assert_eq!(
+            fader.step(fade_in, t0 + Duration::from_millis(400)),
+            FadeStep::Finished(1.0)
+        );
+        assert_eq!(
+            fader.step(fade_in, t0 + Duration::from_millis(416)),
+            FadeStep::Stale
+        );
+        assert_eq!(fader.cancel(t0), None);
+    }
+
+    #[test]
+    fn scroll_steps_once_per_notch() {
+        let mut scroll = ScrollStepper::default();
+        let t0 = Instant::now();
+        let at = |ms| t0 + Duration::from_millis(ms);
+
+        assert_eq!(scroll.step(0, t0), None);
+        assert_eq!(scroll.step(-1, t0), Some(true));
+        assert_eq!(scroll.step(-3, at(20)), None);
+        assert_eq!(scroll.step(-1, at(150)), Some(true));
+        // Reversing steps right away.
+        assert_eq!(scroll.step(2, at(160)), Some(false));
+        assert_eq!(scroll.step(1, at(200)), None);
+    }
+
+    #[test]
+    fn window_navigation_stops_at_the_ends_without_wrap() {
+        let nav = MissionControlOverlay::navigate_windows;
+        assert_eq!(nav(3, 0, NavDirection::Left, true), Some(2));
+        assert_eq!(nav(3, 2, NavDirection::Down, true), Some(0));
+        assert_eq!(nav(3, 0, NavDirection::Up, false), Some(0));
+        assert_eq!(nav(3, 2, NavDirection::Right, false), Some(2));
+        assert_eq!(nav(3, 1, NavDirection::Right, false), Some(2));
+        assert_eq!(nav(0, 0, NavDirection::Right, false), None);
+    }
+
+    fn workspaces(count: usize) -> Vec<WorkspaceData> {
+        (0..count)
+            .map(|i| workspace(&format!("ws{i}"), i, i == 0, vec![window(i as u32, i == 0)]))
+            .collect()
+    }
+
+    #[test]
+    fn single_row_workspace_navigation_stays_put_without_wrap() {
+        let workspaces = workspaces(1);
+        let visible = MissionControlOverlay::visible_workspaces(&workspaces);
+        for wrap in [true, false] {
+            for direction in [NavDirection::Left, NavDirection::Right, NavDirection::Down] {
+                assert_eq!(
+                    MissionControlOverlay::navigate_workspaces(&visible, 0, direction, wrap),
+                    Some(0)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn two_row_workspace_navigation_wraps_only_when_enabled() {
+        // Columns are [0, 1], [2, 3] and [4], top row first.
+        let workspaces = workspaces(5);
+        let visible = MissionControlOverlay::visible_workspaces(&workspaces);
+        let nav = |current, direction, wrap| {
+            MissionControlOverlay::navigate_workspaces(&visible, current, direction, wrap)
+        };
+
+        assert_eq!(nav(0, NavDirection::Left, true), Some(4));
+        assert_eq!(nav(4, NavDirection::Right, true), Some(0));
+        assert_eq!(nav(3, NavDirection::Right, true), Some(1));
+        assert_eq!(nav(0, NavDirection::Up, true), Some(1));
+        assert_eq!(nav(1, NavDirection::Down, true), Some(0));
+
+        assert_eq!(nav(0, NavDirection::Left, false), Some(0));
+        assert_eq!(nav(4, NavDirection::Right, false), Some(4));
+        assert_eq!(nav(3, NavDirection::Right, false), Some(3));
+        assert_eq!(nav(0, NavDirection::Up, false), Some(0));
+        assert_eq!(nav(1, NavDirection::Down, false), Some(1));
+
+        for wrap in [true, false] {
+            assert_eq!(nav(0, NavDirection::Right, wrap), Some(2));
+            assert_eq!(nav(0, NavDirection::Down, wrap), Some(1));
+            assert_eq!(nav(1, NavDirection::Up, wrap), Some(0));
+            assert_eq!(nav(4, NavDirection::Down, wrap), Some(3));
+        }
+    }
+}
@@ -261,6 +261,18 @@ impl GroupIndicatorWindow {
         Self::segment_at_point_static(window_point, group_data, &bounds)
     }
 
+    /// The segment under `point`, given in screen coordinates, or `None`
+    /// if the point is outside the indicator.
+    pub fn segment_at_screen_point(&self, point: CGPoint) -> Option<usize> {
+        let frame = *self.frame.borrow();
+        // Segments are hit tested bottom-up within the window.
+        let local = CGPoint::new(
+            point.x - frame.origin.x,
+            frame.origin.y + frame.size.height - point.y,
+        );
+        self.check_click(local)
+    }
+
     pub fn segment_at_point(&self, point: CGPoint, group_data: &GroupDisplayData) -> Option<usize> {
         let bounds = self.bounds();
         Self::segment_at_point_static(point, group_data, &bounds)
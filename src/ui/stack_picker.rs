@@ -0,0 +1,558 @@
+use core::ffi::c_void;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use dispatchr::queue;
+use dispatchr::time::Time;
+use objc2::rc::Retained;
+use objc2::runtime::AnyObject;
+use objc2_app_kit::{NSColor, NSPopUpMenuWindowLevel};
+use objc2_core_foundation::{CFString, CFType, CGPoint, CGRect, CGSize};
+use objc2_core_graphics::{
+    CGColor, CGContext, CGEvent, CGEventField, CGEventFlags, CGEventTapOptions, CGEventTapProxy,
+    CGEventType,
+};
+use objc2_quartz_core::{CALayer, CATextLayer, CATransaction};
+use once_cell::sync::Lazy;
+use tracing::info;
+
+use crate::sys::cgs_window::{CgsWindow, CgsWindowError};
+use crate::sys::dispatch::DispatchExt;
+use crate::sys::skylight::{
+    CFRelease, G_CONNECTION, SLSFlushWindowContentRegion, SLWindowContextCreate,
+};
+
+unsafe extern "C" {
+    fn CGContextFlush(ctx: *mut CGContext);
+    fn CGContextClearRect(ctx: *mut CGContext, rect: CGRect);
+    fn CGContextSaveGState(ctx: *mut CGContext);
+    fn CGContextRestoreGState(ctx: *mut CGContext);
+    fn CGContextTranslateCTM(ctx: *mut CGContext, tx: f64, ty: f64);
+    fn CGContextScaleCTM(ctx: *mut CGContext, sx: f64, sy: f64);
+    fn CGEventKeyboardGetUnicodeString(
+        event: *mut CGEvent,
+        max_len: usize,
+        actual_len: *mut usize,
+        buf: *mut u16,
+    );
+}
+
+const PICKER_WIDTH: f64 = 360.0;
+const ROW_HEIGHT: f64 = 20.0;
+const PADDING: f64 = 8.0;
+const MAX_VISIBLE_ROWS: usize = 12;
+
+static BACKGROUND_COLOR: Lazy<Retained<CGColor>> =
+    Lazy::new(|| CGColor::new_generic_gray(0.1, 0.92).into());
+
+static HIGHLIGHT_COLOR: Lazy<Retained<CGColor>> =
+    Lazy::new(|| CGColor::new_generic_rgb(0.2, 0.45, 1.0, 0.85).into());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackPickerAction {
+    /// Select the stack member at this index.
+    Select(usize),
+    Dismiss,
+}
+
+/// Returns the indices of the members of `titles` matching `query`, best
+/// match first.
+///
+/// A query made only of digits matches the 1-based member numbers shown in
+/// the picker by prefix, so `1` keeps members 1 and 10-19. Anything else is a
+/// case-insensitive fuzzy match: the query characters must appear in order,
+/// and titles where they sit closer together rank higher.
+pub fn filter_members(titles: &[String], query: &str) -> Vec<usize> {
+    if query.is_empty() {
+        return (0..titles.len()).collect();
+    }
+    if query.chars().all(|c| c.is_ascii_digit()) {
+        return (0..titles.len())
+            .filter(|idx| (idx + 1).to_string().starts_with(query))
+            .collect();
+    }
+    let mut scored: Vec<_> = titles
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, title)| fuzzy_score(title, query).map(|score| (score, idx)))
+        .collect();
+    scored.sort();
+    scored.into_iter().map(|(_, idx)| idx).collect()
+}
+
+/// Width of the tightest window of `title` containing `query` as a
+/// subsequence, then where that window starts. Lower is better.
+fn fuzzy_score(title: &str, query: &str) -> Option<(usize, usize)> {
+    let title: Vec<char> = title.to_lowercase().chars().collect();
+    let query: Vec<char> = query.to_lowercase().chars().collect();
+    let first = *query.first()?;
+    let mut best: Option<(usize, usize)> = None;
+    for start in (0..title.len()).filter(|&i| title[i] == first) {
+        let mut pos = start;
+        let mut matched = true;
+        for &qc in &query[1..] {
+            match title[pos + 1..].iter().position(|&tc| tc == qc) {
+                Some(offset) => pos += offset + 1,
+                None => {
+                    matched = false;
+                    break;
+                }
+            }
+        }
+        if !matched {
+            // Later starts only have less of the title to match against.
+            break;
+        }
+        let candidate = (pos - start + 1, start);
+        if best.is_none_or(|b| candidate < b) {
+            best = Some(candidate);
+        }
+    }
+    best
+}
+
+#[derive(Debug, Default)]
+struct PickerState {
+    titles: Vec<String>,
+    query: String,
+    matches: Vec<usize>,
+    highlighted: usize,
+}
+
+impl PickerState {
+    fn new(titles: Vec<String>) -> Self {
+        let matches = filter_members(&titles, "");
+        Self {
+            titles,
+            query: String::new(),
+            matches,
+            highlighted: 0,
+        }
+    }
+
+    fn push(&mut self, ch: char) {
+        self.query.push(ch);
+        self.refilter();
+    }
+
+    fn pop(&mut self) -> bool {
+        if self.query.pop().is_none() {
+            return false;
+        }
+        self.refilter();
+        true
+    }
+
+    fn refilter(&mut self) {
+        self.matches = filter_members(&self.titles, &self.query);
+        self.highlighted = 0;
+    }
+
+    fn move_highlight(&mut self, forward: bool) -> bool {
+        let len = self.matches.len();
+        if len < 2 {
+            return false;
+        }
+        self.highlighted = if forward {
+            (self.highlighted + 1) % len
+        } else {
+            (self.highlighted + len - 1) % len
+        };
+        true
+    }
+
+    fn selected(&self) -> Option<usize> {
+        self.matches.get(self.highlighted).copied()
+    }
+
+    /// The window of matches to draw, keeping the highlighted row visible.
+    fn visible_range(&self) -> std::ops::Range<usize> {
+        let start = self.highlighted.saturating_sub(MAX_VISIBLE_ROWS - 1);
+        start..self.matches.len().min(start + MAX_VISIBLE_ROWS)
+    }
+}
+
+/// A small overlay anchored to a stack that lists its members for
+/// keyboard selection. Input is read through an event tap while shown.
+pub struct StackPickerOverlay {
+    cgs_window: CgsWindow,
+    root_layer: Retained<CALayer>,
+    frame: CGRect,
+    scale: f64,
+    key_tap: RefCell<Option<crate::sys::event_tap::EventTap>>,
+    state: RefCell<PickerState>,
+    row_layers: RefCell<Vec<Retained<CATextLayer>>>,
+    on_action: Rc<dyn Fn(StackPickerAction)>,
+}
+
+impl StackPickerOverlay {
+    pub fn new(
+        anchor: CGRect,
+        titles: Vec<String>,
+        scale: f64,
+        on_action: Rc<dyn Fn(StackPickerAction)>,
+    ) -> Result<Self, CgsWindowError> {
+        let rows = titles.len().min(MAX_VISIBLE_ROWS) + 1;
+        let frame = CGRect::new(
+            CGPoint::new(anchor.origin.x + PADDING, anchor.origin.y + PADDING),
+            CGSize::new(
+                PICKER_WIDTH.min(anchor.size.width - 2.0 * PADDING).max(160.0),
+                rows as f64 * ROW_HEIGHT + 2.0 * PADDING,
+            ),
+        );
+
+        let root_layer = CALayer::layer();
+        root_layer.setGeometryFlipped(true);
+        root_layer.setFrame(CGRect::new(CGPoint::new(0.0, 0.0), frame.size));
+        root_layer.setContentsScale(scale);
+        root_layer.setCornerRadius(6.0);
+        root_layer.setBackgroundColor(Some(&**BACKGROUND_COLOR));
+
+        let cgs_window = CgsWindow::new(frame)?;
+        let _ = cgs_window.set_resolution(scale);
+        let _ = cgs_window.set_opacity(false);
+        let _ = cgs_window.set_alpha(1.0);
+        let _ = cgs_window.set_level(NSPopUpMenuWindowLevel as i32);
+
+        Ok(Self {
+            cgs_window,
+            root_layer,
+            frame,
+            scale,
+            key_tap: RefCell::new(None),
+            state: RefCell::new(PickerState::new(titles)),
+            row_layers: RefCell::new(Vec::new()),
+            on_action,
+        })
+    }
+
+    /// Shows the picker and starts reading keys. The overlay must not move
+    /// while shown, since the event tap holds a pointer to it.
+    pub fn show(&self) {
+        let _ = self.cgs_window.order_above(None);
+        self.ensure_key_tap();
+        self.draw_and_present();
+    }
+
+    pub fn hide(&self) {
+        self.key_tap.borrow_mut().take();
+        let _ = self.cgs_window.order_out();
+    }
+
+    fn handle_key(&self, keycode: u16, flags: CGEventFlags, text: Option<char>) -> bool {
+        if flags.intersects(CGEventFlags::MaskCommand | CGEventFlags::MaskControl) {
+            return false;
+        }
+        let changed = match keycode {
+            53 => {
+                self.emit_action(StackPickerAction::Dismiss);
+                return true;
+            }
+            36 | 76 => {
+                let action = match self.state.borrow().selected() {
+                    Some(index) => StackPickerAction::Select(index),
+                    None => StackPickerAction::Dismiss,
+                };
+                self.emit_action(action);
+                return true;
+            }
+            51 => self.state.borrow_mut().pop(),
+            125 => self.state.borrow_mut().move_highlight(true),
+            126 => self.state.borrow_mut().move_highlight(false),
+            48 => {
+                let forward = !flags.contains(CGEventFlags::MaskShift);
+                self.state.borrow_mut().move_highlight(forward)
+            }
+            _ => match text.filter(|c| !c.is_control()) {
+                Some(ch) => {
+                    self.state.borrow_mut().push(ch);
+                    true
+                }
+                None => return false,
+            },
+        };
+        if changed {
+            self.draw_and_present();
+        }
+        true
+    }
+
+    fn emit_action(&self, action: StackPickerAction) {
+        // Run the handler on the main queue rather than inside the event tap
+        // callback, since handling it tears down the tap.
+        type Ctx = (Rc<dyn Fn(StackPickerAction)>, StackPickerAction);
+
+        extern "C" fn action_callback(ctx: *mut c_void) {
+            if ctx.is_null() {
+                return;
+            }
+            unsafe {
+                let boxed = Box::from_raw(ctx as *mut Ctx);
+                let (cb, action) = *boxed;
+                cb(action);
+            }
+        }
+
+        let ctx: Box<Ctx> = Box::new((self.on_action.clone(), action));
+        unsafe {
+            queue::main().after_f(Time::NOW, Box::into_raw(ctx) as *mut c_void, action_callback)
+        };
+    }
+
+    fn draw_and_present(&self) {
+        let state = self.state.borrow();
+        let range = state.visible_range();
+        let mut lines = Vec::with_capacity(range.len() + 1);
+        lines.push((format!("> {}", state.query), false));
+        for pos in range {
+            let idx = state.matches[pos];
+            let title = state.titles[idx].as_str();
+            lines.push((format!("{:>2}  {title}", idx + 1), pos == state.highlighted));
+        }
+        drop(state);
+
+        CATransaction::begin();
+        CATransaction::setDisableActions(true);
+        let mut rows = self.row_layers.borrow_mut();
+        while rows.len() < lines.len() {
+            let layer = CATextLayer::layer();
+            layer.setContentsScale(self.scale);
+            layer.setFontSize(12.0);
+            layer.setCornerRadius(4.0);
+            layer.setForegroundColor(Some(&NSColor::whiteColor().CGColor()));
+            self.root_layer.addSublayer(&layer);
+            rows.push(layer);
+        }
+        let row_width = self.frame.size.width - 2.0 * PADDING;
+        for (i, layer) in rows.iter().enumerate() {
+            let Some((text, highlighted)) = lines.get(i) else {
+                layer.setHidden(true);
+                continue;
+            };
+            layer.setHidden(false);
+            layer.setFrame(CGRect::new(
+                CGPoint::new(PADDING, PADDING + i as f64 * ROW_HEIGHT),
+                CGSize::new(row_width, ROW_HEIGHT),
+            ));
+            let background = highlighted.then(|| &**HIGHLIGHT_COLOR);
+            layer.setBackgroundColor(background);
+            let text = CFString::from_str(text);
+            let raw = text.as_ref() as *const AnyObject;
+            unsafe {
+                layer.setString(Some(&*raw));
+            }
+        }
+        drop(rows);
+        CATransaction::commit();
+
+        let ctx: *mut CGContext = unsafe {
+            SLWindowContextCreate(*G_CONNECTION, self.cgs_window.id(), core::ptr::null_mut())
+        };
+        if ctx.is_null() {
+            return;
+        }
+        unsafe {
+            let clear = CGRect::new(CGPoint::new(0.0, 0.0), self.frame.size);
+            CGContextClearRect(ctx, clear);
+            CGContextSaveGState(ctx);
+            CGContextTranslateCTM(ctx, 0.0, self.frame.size.height);
+            CGContextScaleCTM(ctx, 1.0, -1.0);
+            self.root_layer.renderInContext(&*ctx);
+            CGContextRestoreGState(ctx);
+            CGContextFlush(ctx);
+            SLSFlushWindowContentRegion(*G_CONNECTION, self.cgs_window.id(), core::ptr::null_mut());
+            CFRelease(ctx as *mut CFType);
+        }
+    }
+
+    fn ensure_key_tap(&self) {
+        if self.key_tap.borrow().is_some() {
+            return;
+        }
+
+        #[repr(C)]
+        struct KeyCtx {
+            overlay: *const StackPickerOverlay,
+            consumes: bool,
+        }
+
+        unsafe fn drop_ctx(ptr: *mut c_void) {
+            unsafe {
+                drop(Box::from_raw(ptr as *mut KeyCtx));
+            }
+        }
+
+        unsafe extern "C-unwind" fn key_callback(
+            _proxy: CGEventTapProxy,
+            etype: CGEventType,
+            event: core::ptr::NonNull<CGEvent>,
+            user_info: *mut c_void,
+        ) -> *mut CGEvent {
+            let ctx = unsafe { &*(user_info as *const KeyCtx) };
+            let mut handled = false;
+            if let Some(overlay) = unsafe { ctx.overlay.as_ref() }
+                && etype == CGEventType::KeyDown
+            {
+                let keycode = unsafe {
+                    CGEvent::integer_value_field(
+                        Some(event.as_ref()),
+                        CGEventField::KeyboardEventKeycode,
+                    ) as u16
+                };
+                let flags = unsafe { CGEvent::flags(Some(event.as_ref())) };
+                let mut buf = [0u16; 4];
+                let mut len = 0usize;
+                unsafe {
+                    CGEventKeyboardGetUnicodeString(
+                        event.as_ptr(),
+                        buf.len(),
+                        &mut len,
+                        buf.as_mut_ptr(),
+                    );
+                }
+                let text = char::decode_utf16(buf[..len.min(buf.len())].iter().copied())
+                    .next()
+                    .and_then(Result::ok);
+                handled = overlay.handle_key(keycode, flags, text);
+            }
+            if handled && ctx.consumes {
+                core::ptr::null_mut()
+            } else {
+                event.as_ptr()
+            }
+        }
+
+        let mask = 1u64 << CGEventType::KeyDown.0 as u64;
+        let overlay_ptr = self as *const _;
+
+        let tap = unsafe {
+            let ctx_ptr = Box::into_raw(Box::new(KeyCtx {
+                overlay: overlay_ptr,
+                consumes: true,
+            })) as *mut c_void;
+            match crate::sys::event_tap::EventTap::new_with_options(
+                CGEventTapOptions::Default,
+                mask,
+                Some(key_callback),
+                ctx_ptr,
+                Some(drop_ctx),
+            ) {
+                Some(tap) => Some(tap),
+                None => {
+                    drop_ctx(ctx_ptr);
+                    let ctx_ptr = Box::into_raw(Box::new(KeyCtx {
+                        overlay: overlay_ptr,
+                        consumes: false,
+                    })) as *mut c_void;
+                    match crate::sys::event_tap::EventTap::new_listen_only(
+                        mask,
+                        Some(key_callback),
+                        ctx_ptr,
+                        Some(drop_ctx),
+                    ) {
+                        Some(tap) => {
+                            info!(
+                                "Falling back to listen-only event tap; stack picker input will pass through"
+                            );
+                            Some(tap)
+                        }
+                        None => {
+                            drop_ctx(ctx_ptr);
+                            None
+                        }
+                    }
+                }
+            }
+        };
+
+        if let Some(t) = tap {
+            self.key_tap.borrow_mut().replace(t);
+        }
+    }
+}
+
+impl Drop for StackPickerOverlay {
+    fn drop(&mut self) {
+        self.hide();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titles(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn empty_query_keeps_every_member_in_order() {
+        let t = titles(&["a", "b", "c"]);
+        assert_eq!(filter_members(&t, ""), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn digits_match_member_numbers_by_prefix() {
+        let t: Vec<String> = (1..=12).map(|i| format!("Tab {i}")).collect();
+        assert_eq!(filter_members(&t, "1"), vec![0, 9, 10, 11]);
+        assert_eq!(filter_members(&t, "12"), vec![11]);
+        assert_eq!(filter_members(&t, "3"), vec![2]);
+        assert!(filter_members(&t, "13").is_empty());
+    }
+
+    #[test]
+    fn fuzzy_matches_subsequences_case_insensitively() {
+        let t = titles(&[
+            "GitHub - Pull Requests",
+            "Gmail",
+            "Google Docs",
+            "Hacker News",
+        ]);
+        assert_eq!(filter_members(&t, "gh"), vec![0]);
+        assert_eq!(filter_members(&t, "GMAIL"), vec![1]);
+        assert!(filter_members(&t, "xyz").is_empty());
+    }
+
+    #[test]
+    fn fuzzy_ranks_tighter_matches_first() {
+        let t = titles(&["docs on mail", "Gmail", "mail"]);
+        // "mail" is contiguous in all three; the earlier start wins the tie.
+        assert_eq!(filter_members(&t, "mail"), vec![2, 1, 0]);
+        let t = titles(&["d_o_c_s", "docs"]);
+        assert_eq!(filter_members(&t, "docs"), vec![1, 0]);
+    }
+
+    #[test]
+    fn state_narrows_and_widens_with_query() {
+        let mut state = PickerState::new(titles(&["alpha", "beta", "gamma"]));
+        assert_eq!(state.selected(), Some(0));
+        state.push('m');
+        assert_eq!(state.matches, vec![2]);
+        assert_eq!(state.selected(), Some(2));
+        assert!(state.pop());
+        assert_eq!(state.matches, vec![0, 1, 2]);
+        assert!(!state.pop());
+    }
+
+    #[test]
+    fn highlight_wraps_and_resets_on_refilter() {
+        let mut state = PickerState::new(titles(&["one", "two", "three"]));
+        assert!(state.move_highlight(false));
+        assert_eq!(state.selected(), Some(2));
+        assert!(state.move_highlight(true));
+        assert_eq!(state.selected(), Some(0));
+        state.push('t');
+        assert_eq!(state.selected(), Some(1));
+    }
+
+    #[test]
+    fn visible_range_follows_highlight() {
+        let names: Vec<String> = (0..20).map(|i| format!("w{i}")).collect();
+        let mut state = PickerState::new(names);
+        assert_eq!(state.visible_range(), 0..MAX_VISIBLE_ROWS);
+        state.highlighted = 15;
+        let range = state.visible_range();
+        assert!(range.contains(&15));
+        assert_eq!(range.len(), MAX_VISIBLE_ROWS);
+    }
+}